@@ -31,12 +31,11 @@ async fn main() -> Result<()> {
         reader.read_line(&mut line).await?;
         let mut split = line.split_whitespace();
         let container = split.next().ok_or_else(|| anyhow!("missing name"))?;
-        let token: [u8; 40] = split
+        let token: Token = split
             .next()
             .ok_or_else(|| anyhow!("missing token"))
-            .and_then(|t| hex::decode(t).context("malformed token"))
-            .and_then(|t| t.try_into().map_err(|_| anyhow!("invalid token")))?;
-        let token: Token = token.into();
+            .and_then(|t| hex::decode(t).context("malformed token"))?
+            .into();
 
         println!(
             "Verifying user \"{}\" from {} with shared \"{}\"",
@@ -44,7 +43,7 @@ async fn main() -> Result<()> {
         );
 
         match client.verify_token(&token, container, SHARED).await? {
-            VerificationResult::Ok => {
+            VerificationResult::Ok(_) => {
                 println!("Verified! Starting to echo...");
 
                 // Play the echo game
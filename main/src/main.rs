@@ -8,8 +8,12 @@ use clap::Parser;
 use log::{debug, info, warn};
 use nix::mount::MsFlags;
 use northstar::{runtime, runtime::Runtime as Northstar};
-use runtime::config::Config;
+use runtime::{
+    config::{Config, Tracing},
+    Handoff,
+};
 use std::{
+    env,
     fs::{self, read_to_string},
     path::{Path, PathBuf},
     process::exit,
@@ -17,6 +21,7 @@ use std::{
 use tokio::{select, signal::unix::SignalKind};
 
 mod logger;
+mod otlp;
 
 #[derive(Debug, Parser)]
 #[clap(name = "northstar", about = "Northstar")]
@@ -33,6 +38,9 @@ struct Opt {
 }
 
 fn main() -> Result<(), Error> {
+    // Store a core dump and exit if started by the kernel as core dump handler
+    runtime::core_dump::helper();
+
     // Initialize logging
     logger::init();
 
@@ -43,18 +51,31 @@ fn main() -> Result<(), Error> {
         exit(1);
     }));
 
+    // The binary at this path is executed with the same arguments by a handoff. The
+    // path is resolved before the binary is possibly replaced.
+    let exe = env::current_exe().context("failed to get the path of the runtime binary")?;
+    let args = env::args_os().collect::<Vec<_>>();
+
     // Parse command line arguments and prepare the environment
     let config = init()?;
 
+    // The exporter is installed within the tokio runtime
+    let tracing = config.tracing.clone();
+
     // Create the runtime launcher. This must be done *before* spawning the tokio threadpool.
     let northstar = Northstar::new(config)?;
 
-    tokio::runtime::Builder::new_multi_thread()
+    let tokio_runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .thread_name("northstar")
         .build()
-        .context("failed to create runtime")?
-        .block_on(run(northstar))
+        .context("failed to create runtime")?;
+    let handoff = tokio_runtime.block_on(run(northstar, tracing))?;
+
+    // Do not wait for the blocking tasks e.g. the wait for the forker exit. The exec
+    // must be done on this thread because the forkers are terminated once it exits.
+    tokio_runtime.shutdown_background();
+    Err(handoff.exec(&exe, &args)).context("failed to execute the runtime binary")
 }
 
 fn init() -> Result<Config, Error> {
@@ -68,8 +89,13 @@ fn init() -> Result<Config, Error> {
     fs::create_dir_all(&config.data_dir).context("failed to create data_dir")?;
     fs::create_dir_all(&config.log_dir).context("failed to create log dir")?;
 
-    // Skip mount namespace setup in case it's disabled for debugging purposes
-    if !opt.disable_mount_namespace {
+    // Skip mount namespace setup in case it's disabled for debugging purposes. A binary
+    // executed by a handoff stays in the mount namespace of the previous instance.
+    if opt.disable_mount_namespace {
+        debug!("Mount namespace is disabled");
+    } else if Handoff::inherited() {
+        debug!("Keeping the mount namespace of the previous instance");
+    } else {
         // Enter a mount namespace. This needs to be done before spawning the tokio threadpool.
         info!("Entering mount namespace");
         nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNS)?;
@@ -82,14 +108,18 @@ fn init() -> Result<Config, Error> {
         let none = Option::<&str>::None;
         nix::mount::mount(Some(root), root, none, flags, none)
             .map_err(|_| anyhow!("failed to remount root"))?;
-    } else {
-        debug!("Mount namespace is disabled");
     }
 
     Ok(config)
 }
 
-async fn run(northstar: Northstar) -> Result<(), Error> {
+/// Run until the runtime stops or hands over to a new runtime binary on SIGUSR2
+async fn run(northstar: Northstar, tracing: Option<Tracing>) -> Result<Handoff, Error> {
+    if let Some(tracing) = &tracing {
+        info!("Exporting spans to {}", tracing.otlp_endpoint);
+        otlp::init(tracing)?;
+    }
+
     let mut runtime = northstar
         .start()
         .await
@@ -101,23 +131,44 @@ async fn run(northstar: Northstar) -> Result<(), Error> {
         .context("failed to install sigterm handler")?;
     let mut sighup = tokio::signal::unix::signal(SignalKind::hangup())
         .context("failed to install sighup handler")?;
-
-    let status = select! {
-        _ = sigint.recv() => {
-            info!("Received SIGINT. Stopping Northstar runtime");
-            runtime.shutdown().await
+    let mut sigusr2 = tokio::signal::unix::signal(SignalKind::user_defined2())
+        .context("failed to install sigusr2 handler")?;
+
+    let status = loop {
+        select! {
+            _ = sigint.recv() => {
+                info!("Received SIGINT. Stopping Northstar runtime");
+                break runtime.shutdown().await;
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM. Stopping Northstar runtime");
+                break runtime.shutdown().await;
+            }
+            _ = sighup.recv() => {
+                info!("Received SIGHUP. Stopping Northstar runtime");
+                break runtime.shutdown().await;
+            }
+            _ = sigusr2.recv() => {
+                info!("Received SIGUSR2. Handing over to a new Northstar runtime");
+                match runtime.handoff().await {
+                    Ok(handoff) => {
+                        if tracing.is_some() {
+                            otlp::shutdown();
+                        }
+                        return Ok(handoff);
+                    }
+                    Err(e @ runtime::Error::Handoff(_)) => warn!("{}", e),
+                    Err(e) => break Err(e),
+                }
+            }
+            status = runtime.stopped() => break status,
         }
-        _ = sigterm.recv() => {
-            info!("Received SIGTERM. Stopping Northstar runtime");
-            runtime.shutdown().await
-        }
-        _ = sighup.recv() => {
-            info!("Received SIGHUP. Stopping Northstar runtime");
-            runtime.shutdown().await
-        }
-        status = runtime.stopped() => status,
     };
 
+    if tracing.is_some() {
+        otlp::shutdown();
+    }
+
     match status {
         Ok(_) => exit(0),
         Err(e) => {
@@ -0,0 +1,35 @@
+use anyhow::{Context, Error};
+use northstar::runtime::config::Tracing;
+use opentelemetry::{
+    sdk::{trace, Resource},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Install a global tracing subscriber that exports the runtime spans to the OTLP
+/// collector configured in `config`. Events are still forwarded to the logger.
+/// Must be called from within the tokio runtime.
+pub fn init(config: &Tracing) -> Result<(), Error> {
+    let exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(config.otlp_endpoint.as_str());
+    let resource = Resource::new(vec![KeyValue::new(
+        "service.name",
+        config.service_name.clone(),
+    )]);
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .context("failed to install OTLP exporter")?;
+
+    let subscriber = Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    tracing::subscriber::set_global_default(subscriber).context("failed to set tracing subscriber")
+}
+
+/// Export pending spans
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
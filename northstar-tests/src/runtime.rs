@@ -7,15 +7,19 @@ use nanoid::nanoid;
 use northstar::{
     api::{
         client,
-        model::{Container, ExitStatus, Notification},
+        model::{Container, ExitStatus, Notification, SignalCause},
     },
     common::non_nul_string::NonNulString,
     runtime::{
         config::{self, ConsoleConfiguration},
-        Runtime as Northstar,
+        Handle, Runtime as Northstar,
     },
 };
-use std::convert::{TryFrom, TryInto};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    path::{Path, PathBuf},
+};
 use tempfile::TempDir;
 use tokio::{fs::remove_file, net::UnixStream, pin, select, time};
 
@@ -25,6 +29,20 @@ pub fn client() -> &'static mut Client {
     unsafe { CLIENT.as_mut().unwrap() }
 }
 
+/// Handle of the runtime instance
+pub static mut HANDLE: Option<Handle> = None;
+
+pub fn handle() -> &'static Handle {
+    unsafe { HANDLE.as_ref().unwrap() }
+}
+
+/// Directory of the `fs` repository of the runtime instance
+pub static mut TEST_REPOSITORY: Option<PathBuf> = None;
+
+pub fn test_repository() -> &'static Path {
+    unsafe { TEST_REPOSITORY.as_deref().unwrap() }
+}
+
 pub fn console_full() -> url::Url {
     let console = std::env::temp_dir().join(format!("northstar-{}-full", std::process::id()));
     url::Url::parse(&format!("unix://{}", console.display())).unwrap()
@@ -35,6 +53,11 @@ pub fn console_none() -> url::Url {
     url::Url::parse(&format!("unix://{}", console.display())).unwrap()
 }
 
+pub fn console_token() -> url::Url {
+    let console = std::env::temp_dir().join(format!("northstar-{}-token", std::process::id()));
+    url::Url::parse(&format!("unix://{}", console.display())).unwrap()
+}
+
 pub enum Runtime {
     Created(Northstar, TempDir),
     Started(Northstar, TempDir),
@@ -51,17 +74,29 @@ impl Runtime {
         std::fs::create_dir(&log_dir)?;
         let test_repository = tmpdir.path().join("test");
         std::fs::create_dir(&test_repository)?;
+        unsafe {
+            TEST_REPOSITORY = Some(test_repository.clone());
+        }
         let example_key = tmpdir.path().join("key.pub");
         std::fs::write(&example_key, include_bytes!("../../examples/northstar.pub"))?;
         let consoles = [
             (
                 console_full(),
                 ConsoleConfiguration {
+                    name: Some("full".into()),
                     permissions: config::ConsolePermissions::full(),
+                    audit_notifications: Some(true),
                     ..Default::default()
                 },
             ),
             (console_none(), ConsoleConfiguration::default()),
+            (
+                console_token(),
+                ConsoleConfiguration {
+                    token_authentication: Some(true),
+                    ..Default::default()
+                },
+            ),
         ]
         .into();
 
@@ -72,6 +107,11 @@ impl Runtime {
                     mount_on_start: false,
                     r#type: config::RepositoryType::Memory,
                     key: Some(example_key.clone()),
+                    keys: Vec::new(),
+                    watch: false,
+                    install_margin: 0,
+                    readonly: false,
+                    priority: 0,
                 },
             ),
             (
@@ -82,23 +122,61 @@ impl Runtime {
                         dir: test_repository,
                     },
                     key: Some(example_key),
+                    keys: Vec::new(),
+                    watch: true,
+                    install_margin: 0,
+                    readonly: false,
+                    priority: 0,
                 },
             ),
         ]
         .into();
 
+        // Freeze the deferrable containers while the input "test" is 1
+        let policies = HashMap::from([(
+            "test".to_string(),
+            config::Policy {
+                input: "test".to_string(),
+                activate: 1,
+                deactivate: 0,
+                cpu_max: None,
+                freeze: true,
+            },
+        )]);
+
         let config = config::Config {
             run_dir,
             data_dir,
             log_dir,
             event_buffer_size: 128,
+            stats_buffer_size: 64,
+            log_buffer_size: 128,
             notification_buffer_size: 128,
             device_mapper_device_timeout: time::Duration::from_secs(10),
             loop_device_timeout: time::Duration::from_secs(10),
+            mount_timeout: time::Duration::from_secs(30),
+            umount_timeout: time::Duration::from_secs(10),
+            start_timeout: time::Duration::from_secs(10),
+            stop_timeout: time::Duration::from_secs(10),
+            autostart_concurrency: 4,
+            mount_concurrency: 4,
+            idle_umount: None,
+            reconcile_interval: None,
+            mount_backend: config::MountBackend::Kernel,
+            verity_cache: None,
             token_validity: time::Duration::from_secs(60),
+            token_max_validity: None,
             consoles,
             cgroup: NonNulString::try_from(format!("northstar-{}", nanoid!())).unwrap(),
             repositories,
+            restore: config::Restore::None,
+            kernel_messages: false,
+            core_dumps: None,
+            parameters: config::Parameters::default(),
+            policies,
+            rlimits: HashMap::new(),
+            mandatory_lsm: None,
+            tracing: None,
             debug: None,
         };
         let runtime = Northstar::new(config)?;
@@ -110,9 +188,11 @@ impl Runtime {
         if let Runtime::Created(launcher, tmpdir) = self {
             let runtime = launcher.start().await?;
             logger::assume("Runtime up and running", 10u64).await?;
+            logger::assume("Repositories loaded", 10u64).await?;
 
             unsafe {
                 CLIENT = Some(Client::new().await?);
+                HANDLE = Some(runtime.handle());
             }
 
             Ok(Runtime::Started(runtime, tmpdir))
@@ -127,6 +207,7 @@ impl Runtime {
 
         remove_file(console_full().path()).await?;
         remove_file(console_none().path()).await?;
+        remove_file(console_token().path()).await?;
         Ok(())
     }
 }
@@ -178,7 +259,15 @@ impl Client {
         self.client.kill(container, 15).await?;
         let container: Container = container.try_into()?;
         self.assume_notification(
-            |n| n == &Notification::Exit(container.clone(), ExitStatus::Signalled { signal: 15 }),
+            |n| {
+                n == &Notification::Exit(
+                    container.clone(),
+                    ExitStatus::Signalled {
+                        signal: 15,
+                        cause: SignalCause::Runtime,
+                    },
+                )
+            },
             timeout,
         )
         .await?;
@@ -210,10 +299,12 @@ impl Client {
             .context("failed to wait for test container install notification")
     }
 
-    /// Uninstall the test container and wait for the notification
+    /// Umount and uninstall the test container and wait for the notification
     pub async fn uninstall_test_container(&mut self) -> Result<()> {
+        // The test container is mounted if it was started
+        self.client.umount("test-container:0.0.1").await.ok();
         self.client
-            .uninstall("test-container:0.0.1")
+            .uninstall("test-container:0.0.1", false, false)
             .await
             .context("failed to uninstall test container")?;
         self.assume_notification(|n| matches!(n, Notification::Uninstall { .. }), 15)
@@ -231,10 +322,12 @@ impl Client {
             .context("failed to wait for test resource install notification")
     }
 
-    /// Uninstall the test resource and wait for the notification
+    /// Umount and uninstall the test resource and wait for the notification
     pub async fn uninstall_test_resource(&mut self) -> Result<()> {
+        // The test resource is mounted if the test container was started
+        self.client.umount("test-resource:0.0.1").await.ok();
         self.client
-            .uninstall("test-resource:0.0.1")
+            .uninstall("test-resource:0.0.1", false, false)
             .await
             .context("failed to uninstall test resource")?;
         self.assume_notification(|n| matches!(n, Notification::Uninstall { .. }), 15)
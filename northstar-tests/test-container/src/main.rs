@@ -54,6 +54,7 @@ enum Command {
         path: PathBuf,
     },
     Sleep,
+    Stdin,
     Write {
         message: String,
         path: PathBuf,
@@ -74,6 +75,7 @@ fn main() -> Result<()> {
         Command::Inspect => inspect(),
         Command::Print { message, io } => print(&message, &io),
         Command::Sleep => (),
+        Command::Stdin => stdin()?,
         Command::Touch { path } => touch(&path)?,
         Command::Write { message, path } => write(&message, path.as_path())?,
     };
@@ -110,6 +112,15 @@ fn crash() {
     panic!("witness me!");
 }
 
+fn stdin() -> Result<()> {
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .context("failed to read stdin")?;
+    println!("stdin: {}", line.trim_end());
+    Ok(())
+}
+
 fn print(message: &str, io: &Io) {
     match io {
         Io::Stdout => println!("{}", message),
@@ -5,9 +5,9 @@ use api::{client::Error as ClientError, model::Error as ModelError};
 use futures::SinkExt;
 use northstar::api::{
     self,
-    model::{self, ConnectNack, Container},
+    model::{self, ConnectNack, ConnectToken, ConsolePermission, Container, TokenScope},
 };
-use northstar_tests::runtime_test;
+use northstar_tests::{containers::TEST_CONTAINER, runtime::client, runtime_test};
 use tokio::{net::UnixStream, time::Duration};
 
 /// Connect a client to the runtime console without any permission configured.
@@ -32,6 +32,9 @@ async fn api_version() -> Result<()> {
     let connect = api::model::Connect::Connect {
         version,
         subscribe_notifications: false,
+        token: None,
+        encoding: None,
+        compression: None,
     };
     let connect_message = api::model::Message::Connect { connect };
     connection.send(connect_message.clone()).await?;
@@ -51,6 +54,36 @@ async fn api_version() -> Result<()> {
     Ok(())
 }
 
+// Verify that the runtime rejects clients of the previous minor version
+#[runtime_test]
+async fn api_version_minor() -> Result<()> {
+    let mut connection = api::codec::Framed::new(
+        UnixStream::connect(&northstar_tests::runtime::console_none().path()).await?,
+    );
+
+    let mut version = api::VERSION;
+    version.minor -= 1;
+
+    let connect = api::model::Connect::Connect {
+        version,
+        subscribe_notifications: false,
+        token: None,
+        encoding: None,
+        compression: None,
+    };
+    connection
+        .send(api::model::Message::Connect { connect })
+        .await?;
+
+    let connack = connection.next().await.unwrap().unwrap();
+    let error = ConnectNack::InvalidProtocolVersion {
+        version: api::VERSION,
+    };
+    let connect = model::Connect::Nack { error };
+    assert_eq!(connack, model::Message::Connect { connect });
+    Ok(())
+}
+
 /// Expect the connection to be closed if a request with a too long line is sent.
 #[runtime_test]
 async fn too_long_line() -> Result<()> {
@@ -99,6 +132,162 @@ async fn stale_install() -> Result<()> {
     Ok(())
 }
 
+/// Pipelined requests are answered with their request ids
+#[runtime_test]
+async fn pipelining() -> Result<()> {
+    let timeout = Duration::from_secs(10);
+    let io = UnixStream::connect(&northstar_tests::runtime::console_full().path()).await?;
+    let mut client = api::client::Client::new(io, None, timeout).await?;
+
+    client.pipeline(model::Request::Containers, 1).await?;
+    client.pipeline(model::Request::Repositories, 2).await?;
+    // A request without an id is answered in between
+    client.ident().await?;
+
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let (id, response) = client.response().await?;
+        match (id, response) {
+            (1, model::Response::Containers(_)) | (2, model::Response::Repositories(_)) => {
+                ids.push(id)
+            }
+            (id, response) => panic!("unexpected response {:?} to {}", response, id),
+        }
+    }
+    ids.sort_unstable();
+    assert_eq!(ids, [1, 2]);
+
+    Ok(())
+}
+
+/// Requests and responses following the connect handshake are CBOR encoded
+#[runtime_test]
+async fn cbor_encoding() -> Result<()> {
+    let timeout = Duration::from_secs(10);
+    let io = UnixStream::connect(&northstar_tests::runtime::console_full().path()).await?;
+    let encoding = model::Encoding::Cbor;
+    let mut client =
+        api::client::Client::new_with_encoding(io, None, timeout, None, encoding, None).await?;
+    client.containers().await?;
+    client.repositories().await?;
+    Ok(())
+}
+
+/// Messages following the connect handshake are zstd compressed
+#[runtime_test]
+async fn zstd_compression() -> Result<()> {
+    let timeout = Duration::from_secs(10);
+    let io = UnixStream::connect(&northstar_tests::runtime::console_full().path()).await?;
+    let encoding = model::Encoding::Json;
+    let compression = Some(model::Compression::Zstd);
+    let mut client =
+        api::client::Client::new_with_encoding(io, None, timeout, None, encoding, compression)
+            .await?;
+    client.containers().await?;
+    client.repositories().await?;
+    Ok(())
+}
+
+/// Connect a client to the token authenticated console with a token that grants `permissions`
+async fn connect_token(
+    permissions: &[ConsolePermission],
+) -> Result<api::client::Client<UnixStream>> {
+    connect_scoped(permissions, &[]).await
+}
+
+/// Connect a client to the token authenticated console with a token that grants `permissions`
+/// on `containers`
+async fn connect_scoped(
+    permissions: &[ConsolePermission],
+    containers: &[&str],
+) -> Result<api::client::Client<UnixStream>> {
+    let io = UnixStream::connect(&northstar_tests::runtime::console_full().path()).await?;
+    let mut client = api::client::Client::new(io, None, Duration::from_secs(10)).await?;
+    let scope = TokenScope {
+        permissions: permissions.iter().cloned().collect(),
+        containers: containers
+            .iter()
+            .map(|container| Container::try_from(*container))
+            .collect::<Result<_, _>>()?,
+    };
+    let token = client
+        .create_scoped_token(
+            model::CONSOLE_TOKEN_TARGET,
+            model::CONSOLE_TOKEN_SHARED,
+            scope,
+            None,
+        )
+        .await?;
+    let token = ConnectToken {
+        user: "extern".into(),
+        token,
+    };
+
+    let io = UnixStream::connect(&northstar_tests::runtime::console_token().path()).await?;
+    api::client::Client::new_with_token(io, None, Duration::from_secs(10), token)
+        .await
+        .context("failed to connect to the runtime")
+}
+
+/// Connections to a token authenticated console without a token are rejected
+#[runtime_test]
+async fn token_authentication_missing_token() -> Result<()> {
+    let io = UnixStream::connect(&northstar_tests::runtime::console_token().path()).await?;
+    assert!(api::client::Client::new(io, None, Duration::from_secs(10))
+        .await
+        .is_err());
+    Ok(())
+}
+
+/// Connections with a token created for a different user are rejected
+#[runtime_test]
+async fn token_authentication_invalid_token() -> Result<()> {
+    let io = UnixStream::connect(&northstar_tests::runtime::console_full().path()).await?;
+    let mut client = api::client::Client::new(io, None, Duration::from_secs(10)).await?;
+    let token = client
+        .create_token(model::CONSOLE_TOKEN_TARGET, model::CONSOLE_TOKEN_SHARED)
+        .await?;
+    let token = ConnectToken {
+        user: "hello-world".into(),
+        token,
+    };
+    let io = UnixStream::connect(&northstar_tests::runtime::console_token().path()).await?;
+    assert!(
+        api::client::Client::new_with_token(io, None, Duration::from_secs(10), token)
+            .await
+            .is_err()
+    );
+    Ok(())
+}
+
+/// The permissions of a token authenticated connection are the permissions of the token scope
+#[runtime_test]
+async fn token_authentication_permissions() -> Result<()> {
+    let mut client = connect_token(&[ConsolePermission::Containers]).await?;
+    client.containers().await?;
+    assert!(matches!(
+        client.repositories().await,
+        Err(ClientError::Runtime(ModelError::PermissionDenied { .. }))
+    ));
+    Ok(())
+}
+
+/// A token scoped to containers is refused on other containers
+#[runtime_test]
+async fn token_authentication_containers() -> Result<()> {
+    client().install_test_container().await?;
+
+    let mut client = connect_scoped(&[ConsolePermission::Inspect], &[TEST_CONTAINER]).await?;
+    client.inspect(TEST_CONTAINER).await?;
+    assert!(matches!(
+        client.inspect("hello-world:0.0.1").await,
+        Err(ClientError::Runtime(ModelError::OutOfScope {
+            container: Some(_)
+        }))
+    ));
+    Ok(())
+}
+
 /// Check that subscribing to notifications is not permitted on the `console_none` url.
 #[runtime_test]
 async fn notifications() -> Result<()> {
@@ -152,6 +341,22 @@ async fn permissions_start() -> Result<()> {
     Ok(())
 }
 
+/// Overriding arguments or environment variables requires the start_override permission
+#[runtime_test]
+async fn permissions_start_override() -> Result<()> {
+    let mut client = connect_token(&[ConsolePermission::Start]).await?;
+    assert!(matches!(
+        client
+            .start_with_args("hello-world:0.0.1", ["--help"])
+            .await,
+        Err(ClientError::Runtime(ModelError::PermissionDenied {
+            required: ConsolePermission::StartOverride,
+            ..
+        }))
+    ));
+    Ok(())
+}
+
 #[runtime_test]
 async fn permissions_kill() -> Result<()> {
     assert!(matches!(
@@ -176,7 +381,10 @@ async fn permissions_install() -> Result<()> {
 #[runtime_test]
 async fn permissions_uninstall() -> Result<()> {
     assert!(matches!(
-        connect_none().await?.uninstall("hello-world:0.0.1").await,
+        connect_none()
+            .await?
+            .uninstall("hello-world:0.0.1", false, false)
+            .await,
         Err(ClientError::Runtime(ModelError::PermissionDenied { .. }))
     ));
     Result::<()>::Ok(())
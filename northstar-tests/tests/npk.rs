@@ -1,7 +1,13 @@
-use northstar::npk::npk;
+use northstar::{
+    common::container::Container,
+    npk::{
+        index::{self, Index, INDEX_NAME},
+        npk,
+    },
+};
 use std::{
-    fs::{self, File},
-    io::Write,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
 };
 use tempfile::TempDir;
@@ -128,3 +134,63 @@ fn do_not_overwrite_keys() -> Result<(), anyhow::Error> {
     npk::generate_key(TEST_KEY_NAME, dest.path()).expect_err("Cannot overwrite keys");
     Ok(())
 }
+
+#[test]
+fn signed_index() -> Result<(), anyhow::Error> {
+    let dir = tmpdir();
+    let key_dir = tmpdir();
+    let (pub_key, prv_key) = generate_test_key(key_dir.path());
+    let key = ed25519_dalek::PublicKey::from_bytes(&fs::read(&pub_key)?)?;
+
+    // No index present
+    assert!(Index::from_dir(dir.path(), &[key])?.is_none());
+
+    let container = Container::try_from("hello:0.0.2")?;
+    let revoked = Container::try_from("hello:0.0.1")?;
+    let mut index = Index::default();
+    index.npks.insert(container.clone(), "00".repeat(32));
+    index.revoked.insert(revoked.clone());
+    index.write(dir.path(), &prv_key)?;
+
+    let loaded = Index::from_dir(dir.path(), &[key])?.expect("missing index");
+    assert_eq!(loaded, index);
+    assert!(loaded.check(&container, &"00".repeat(32)).is_ok());
+    assert!(loaded.check(&container, &"11".repeat(32)).is_err());
+    assert!(loaded.check(&revoked, &"00".repeat(32)).is_err());
+    assert_eq!(index::hash(&b"northstar"[..])?.len(), 64);
+
+    // Tampering with the index invalidates the signature
+    let content = fs::read_to_string(dir.path().join(INDEX_NAME))?;
+    fs::write(
+        dir.path().join(INDEX_NAME),
+        content.replace("hello:0.0.1", "hello:0.0.0"),
+    )?;
+    assert!(Index::from_dir(dir.path(), &[key]).is_err());
+    Ok(())
+}
+
+#[test]
+fn validate() -> Result<(), anyhow::Error> {
+    let src = tmpdir();
+    let dest = tmpdir();
+    let key_dir = tmpdir();
+    let manifest = create_test_manifest(src.path(), None);
+    let (pub_key, prv_key) = generate_test_key(key_dir.path());
+    let key = ed25519_dalek::PublicKey::from_bytes(&fs::read(&pub_key)?)?;
+    npk::pack(&manifest, src.path(), dest.path(), Some(&prv_key))?;
+    let path = dest.path().join(TEST_CONTAINER_NAME);
+
+    let mut npk = npk::Npk::<BufReader<File>>::from_path(&path, Some(&key))?;
+    npk.validate()?;
+
+    // Corrupt the magic of the squashfs superblock
+    let offset = npk.fsimg_offset();
+    let mut file = OpenOptions::new().write(true).open(&path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(b"xxxx")?;
+    drop(file);
+
+    let mut npk = npk::Npk::<BufReader<File>>::from_path(&path, Some(&key))?;
+    assert!(matches!(npk.validate(), Err(npk::Error::Squashfs(_))));
+    Ok(())
+}
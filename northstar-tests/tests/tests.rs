@@ -1,12 +1,21 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use futures::StreamExt;
 use log::debug;
-use northstar::api::{
-    self,
-    model::{self, ExitStatus, Notification},
+use northstar::{
+    api::{
+        self,
+        model::{self, ExitStatus, Notification},
+    },
+    runtime::{ContainerEvent, Overflow, RuntimeEvent},
+};
+use northstar_tests::{
+    containers::*,
+    logger::assume,
+    runtime::{client, handle, test_repository},
+    runtime_test,
 };
-use northstar_tests::{containers::*, logger::assume, runtime::client, runtime_test};
 
 // Test a good and bad log assumption
 #[runtime_test]
@@ -45,6 +54,21 @@ async fn install_duplicate_other_repository() -> Result<()> {
     Ok(())
 }
 
+// Copy a npk into the watched fs repository
+#[runtime_test]
+async fn install_copy_to_watched_repository() -> Result<()> {
+    let npk = test_repository().join("test-resource.npk");
+    tokio::fs::write(&npk, TEST_RESOURCE_NPK).await?;
+    client()
+        .assume_notification(|n| matches!(n, Notification::Install { .. }), 15)
+        .await?;
+    let container = model::Container::try_from(TEST_RESOURCE)?;
+    let containers = client().containers().await?;
+    assert!(containers.iter().any(|c| c.container == container));
+    client().uninstall_test_resource().await?;
+    Ok(())
+}
+
 // Try to a container into a repository that does not exist
 #[runtime_test]
 async fn install_invalid_repository() -> Result<()> {
@@ -74,7 +98,10 @@ async fn install_uninstall_to_fs_repository() -> Result<()> {
 // Uninstalling an unknown container should fail
 #[runtime_test]
 async fn uninstall_unknown_container() -> Result<()> {
-    assert!(client().uninstall("fckptn:0.0.1").await.is_err());
+    assert!(client()
+        .uninstall("fckptn:0.0.1", false, false)
+        .await
+        .is_err());
     Ok(())
 }
 
@@ -93,6 +120,241 @@ async fn start_stop() -> Result<()> {
     Ok(())
 }
 
+// Pause and resume a started container
+#[runtime_test]
+async fn pause_resume() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    client().pause(TEST_CONTAINER).await?;
+    let n = |n: &Notification| matches!(n, Notification::Paused(_));
+    client().assume_notification(n, 5).await?;
+
+    // Pausing twice is an error
+    assert!(client().pause(TEST_CONTAINER).await.is_err());
+
+    client().resume(TEST_CONTAINER).await?;
+    let n = |n: &Notification| matches!(n, Notification::Resumed(_));
+    client().assume_notification(n, 5).await?;
+
+    // Resuming a running container is an error
+    assert!(client().resume(TEST_CONTAINER).await.is_err());
+
+    client().stop(TEST_CONTAINER, 5).await?;
+    assume("Process test-container:0.0.1 exited", 5).await
+}
+
+// Freeze all started containers on a system suspend and thaw them on resume
+#[runtime_test]
+async fn system_suspend_resume() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    client().system_suspend(Vec::<&str>::new()).await?;
+    let n = |n: &Notification| matches!(n, Notification::Paused(_));
+    client().assume_notification(n, 5).await?;
+
+    // Suspending twice is an error
+    assert!(client().system_suspend(Vec::<&str>::new()).await.is_err());
+
+    client().system_resume().await?;
+    let n = |n: &Notification| matches!(n, Notification::Resumed(_));
+    client().assume_notification(n, 5).await?;
+
+    // Resuming a system that is not suspended is an error
+    assert!(client().system_resume().await.is_err());
+
+    client().stop(TEST_CONTAINER, 5).await?;
+    assume("Process test-container:0.0.1 exited", 5).await
+}
+
+// Freeze deferrable containers while a throttling policy is active
+#[runtime_test]
+async fn policy_freezes_deferrable_containers() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    client().policy_input("test", 1).await?;
+    let n = |n: &Notification| matches!(n, Notification::PolicyActivated(p) if p == "test");
+    client().assume_notification(n, 5).await?;
+    let n = |n: &Notification| matches!(n, Notification::Paused(_));
+    client().assume_notification(n, 5).await?;
+
+    // Unknown inputs are rejected
+    assert!(client().policy_input("foo", 1).await.is_err());
+
+    client().policy_input("test", 0).await?;
+    let n = |n: &Notification| matches!(n, Notification::PolicyDeactivated(p) if p == "test");
+    client().assume_notification(n, 5).await?;
+    let n = |n: &Notification| matches!(n, Notification::Resumed(_));
+    client().assume_notification(n, 5).await?;
+
+    client().stop(TEST_CONTAINER, 5).await?;
+    assume("Process test-container:0.0.1 exited", 5).await
+}
+
+// List and read files of a mounted container
+#[runtime_test]
+async fn fs_list_read() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    // The container is not mounted
+    assert!(client().fs_list(TEST_CONTAINER, "/").await.is_err());
+
+    // Write a file to the persist dir
+    client()
+        .start_with_args(TEST_CONTAINER, ["write", "hello", "/data/hello"])
+        .await?;
+    assume("Process test-container:0.0.1 exited", 5).await?;
+
+    let entries = client().fs_list(TEST_CONTAINER, "/").await?;
+    assert!(entries
+        .iter()
+        .any(|e| e.name == "test-container" && e.kind == model::FsEntryKind::File));
+
+    let mut content = Vec::new();
+    client()
+        .fs_read(TEST_CONTAINER, "/data/hello", &mut content)
+        .await?;
+    assert_eq!(content, b"hello");
+
+    // Paths must not escape the container root
+    let mut content = Vec::new();
+    assert!(client()
+        .fs_read(TEST_CONTAINER, "/../../etc/passwd", &mut content)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+// Export the persist dir of a container and import it again
+#[runtime_test]
+async fn data_export_import() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client()
+        .start_with_args(TEST_CONTAINER, ["write", "hello", "/data/hello"])
+        .await?;
+    assume("Process test-container:0.0.1 exited", 5).await?;
+
+    let mut archive = Vec::new();
+    let size = client().data_export(TEST_CONTAINER, &mut archive).await?;
+    assert_eq!(size, archive.len() as u64);
+
+    client()
+        .start_with_args(TEST_CONTAINER, ["write", "world", "/data/hello"])
+        .await?;
+    assume("Process test-container:0.0.1 exited", 5).await?;
+
+    client()
+        .data_import(TEST_CONTAINER, archive.as_slice(), size)
+        .await?;
+
+    let mut content = Vec::new();
+    client()
+        .fs_read(TEST_CONTAINER, "/data/hello", &mut content)
+        .await?;
+    assert_eq!(content, b"hello");
+
+    Ok(())
+}
+
+// Reset the persist dir of a stopped and a started container
+#[runtime_test]
+async fn data_reset() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client()
+        .start_with_args(TEST_CONTAINER, ["write", "hello", "/data/hello"])
+        .await?;
+    assume("Process test-container:0.0.1 exited", 5).await?;
+
+    client().data_reset(TEST_CONTAINER).await?;
+    let n = |n: &Notification| matches!(n, Notification::DataReset(_));
+    client().assume_notification(n, 5).await?;
+    assert!(client()
+        .fs_read(TEST_CONTAINER, "/data/hello", &mut Vec::new())
+        .await
+        .is_err());
+
+    // A started container is killed before its data is wiped
+    client()
+        .start_with_args(TEST_CONTAINER, ["write", "hello", "/data/hello"])
+        .await?;
+    assume("Process test-container:0.0.1 exited", 5).await?;
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    client().data_reset(TEST_CONTAINER).await?;
+    let n = |n: &Notification| matches!(n, Notification::Exit(_, _));
+    client().assume_notification(n, 5).await?;
+    let n = |n: &Notification| matches!(n, Notification::DataReset(_));
+    client().assume_notification(n, 5).await?;
+    assert!(client()
+        .fs_read(TEST_CONTAINER, "/data/hello", &mut Vec::new())
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+// Inspect an installed container without mounting it
+#[runtime_test]
+async fn inspect() -> Result<()> {
+    client().install_test_container().await?;
+
+    let inspect = client().inspect(TEST_CONTAINER).await?;
+    assert_eq!(inspect.container.to_string(), TEST_CONTAINER);
+    assert_eq!(inspect.repository, "mem");
+    // The test container is packed without a sbom
+    assert!(inspect.sbom.is_none());
+    // The test container was never started
+    assert!(inspect.start_timings.is_none());
+    // The mem repository is verified with the example key
+    let key = include_bytes!("../../examples/northstar.pub")
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    assert_eq!(inspect.key, Some(key));
+
+    Ok(())
+}
+
+// Report the phases of the last start
+#[runtime_test]
+async fn inspect_start_timings() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    let timings = client()
+        .inspect(TEST_CONTAINER)
+        .await?
+        .start_timings
+        .expect("missing start timings");
+    assert!(timings.mount > 0);
+    assert!(timings.clone > 0);
+    assert!(timings.total >= timings.manifest + timings.mount + timings.clone);
+
+    client().stop(TEST_CONTAINER, 5).await?;
+    Ok(())
+}
+
 // Install and uninstall the example npks
 #[runtime_test]
 async fn install_uninstall_examples() -> Result<()> {
@@ -113,22 +375,98 @@ async fn install_uninstall_examples() -> Result<()> {
     client().install(TEST_CONTAINER_NPK, "mem").await?;
     client().install(TEST_RESOURCE_NPK, "mem").await?;
 
-    client().uninstall(EXAMPLE_CPUEATER).await?;
-    client().uninstall(EXAMPLE_CONSOLE).await?;
-    client().uninstall(EXAMPLE_CRASHING).await?;
-    client().uninstall(EXAMPLE_FERRIS).await?;
-    client().uninstall(EXAMPLE_HELLO_FERRIS).await?;
-    client().uninstall(EXAMPLE_HELLO_RESOURCE).await?;
-    client().uninstall(EXAMPLE_INSPECT).await?;
-    client().uninstall(EXAMPLE_MEMEATER).await?;
-    client().uninstall(EXAMPLE_MESSAGE_0_0_1).await?;
-    client().uninstall(EXAMPLE_MESSAGE_0_0_2).await?;
-    client().uninstall(EXAMPLE_PERSISTENCE).await?;
-    client().uninstall(EXAMPLE_SECCOMP).await?;
-    client().uninstall(EXAMPLE_TOKEN_CLIENT).await?;
-    client().uninstall(EXAMPLE_TOKEN_SERVER).await?;
-    client().uninstall(TEST_CONTAINER).await?;
-    client().uninstall(TEST_RESOURCE).await?;
+    client().uninstall(EXAMPLE_CPUEATER, false, false).await?;
+    client().uninstall(EXAMPLE_CONSOLE, false, false).await?;
+    client().uninstall(EXAMPLE_CRASHING, false, false).await?;
+    client()
+        .uninstall(EXAMPLE_HELLO_FERRIS, false, false)
+        .await?;
+    client().uninstall(EXAMPLE_FERRIS, false, false).await?;
+    client()
+        .uninstall(EXAMPLE_HELLO_RESOURCE, false, false)
+        .await?;
+    client().uninstall(EXAMPLE_INSPECT, false, false).await?;
+    client().uninstall(EXAMPLE_MEMEATER, false, false).await?;
+    client()
+        .uninstall(EXAMPLE_MESSAGE_0_0_1, false, false)
+        .await?;
+    client()
+        .uninstall(EXAMPLE_MESSAGE_0_0_2, false, false)
+        .await?;
+    client()
+        .uninstall(EXAMPLE_PERSISTENCE, false, false)
+        .await?;
+    client().uninstall(EXAMPLE_SECCOMP, false, false).await?;
+    client()
+        .uninstall(EXAMPLE_TOKEN_CLIENT, false, false)
+        .await?;
+    client()
+        .uninstall(EXAMPLE_TOKEN_SERVER, false, false)
+        .await?;
+    client().uninstall(TEST_CONTAINER, false, false).await?;
+    client().uninstall(TEST_RESOURCE, false, false).await?;
+    Ok(())
+}
+
+// Permitted requests that change the state are announced with the listener and peer
+#[runtime_test]
+async fn audit_notification() -> Result<()> {
+    client().install_test_container().await?;
+    client().mount(TEST_CONTAINER).await?;
+    let expected = format!("mount {}", TEST_CONTAINER);
+    let n = |n: &Notification| {
+        matches!(n, Notification::Audit { listener: Some(listener), action, .. }
+            if listener == "full" && action == &expected)
+    };
+    client().assume_notification(n, 5).await?;
+    Ok(())
+}
+
+// Install and start the test container through the handle of the runtime
+#[runtime_test]
+async fn handle_install_start() -> Result<()> {
+    let handle = handle();
+    let mut notifications = handle.subscribe();
+
+    let dir = tempfile::tempdir()?;
+    for (name, npk) in [
+        ("test-resource.npk", TEST_RESOURCE_NPK),
+        ("test-container.npk", TEST_CONTAINER_NPK),
+    ] {
+        let path = dir.path().join(name);
+        tokio::fs::write(&path, npk).await?;
+        let response = handle.install(&path, "mem").await?;
+        assert!(matches!(response, model::Response::Install(_)));
+    }
+
+    let container = model::Container::try_from(TEST_CONTAINER)?;
+    assert_eq!(handle.start(container.clone()).await?, model::Response::Ok);
+    let started = async {
+        while notifications.recv().await? != Notification::Started(container.clone()) {}
+        Result::<()>::Ok(())
+    };
+    tokio::time::timeout(tokio::time::Duration::from_secs(5), started).await??;
+    Ok(())
+}
+
+// Receive the typed events of the runtime through the handle
+#[runtime_test]
+async fn handle_events() -> Result<()> {
+    let mut events = handle().events(100, Overflow::Drop);
+    client().install_test_container().await?;
+
+    let container = model::Container::try_from(TEST_CONTAINER)?;
+    let installed = async {
+        while let Some(event) = events.next().await {
+            if let RuntimeEvent::Container(c, ContainerEvent::Installed) = event {
+                if c == container {
+                    return true;
+                }
+            }
+        }
+        false
+    };
+    assert!(tokio::time::timeout(tokio::time::Duration::from_secs(5), installed).await?);
     Ok(())
 }
 
@@ -199,6 +537,49 @@ async fn try_to_mount_known_and_unknown_container() -> Result<()> {
     Ok(())
 }
 
+// Mounts run in the background and do not block requests for other containers
+#[runtime_test]
+async fn kill_while_mounting() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+    let examples = [
+        (EXAMPLE_CPUEATER_NPK, EXAMPLE_CPUEATER),
+        (EXAMPLE_CONSOLE_NPK, EXAMPLE_CONSOLE),
+        (EXAMPLE_CRASHING_NPK, EXAMPLE_CRASHING),
+        (EXAMPLE_INSPECT_NPK, EXAMPLE_INSPECT),
+        (EXAMPLE_MEMEATER_NPK, EXAMPLE_MEMEATER),
+        (EXAMPLE_PERSISTENCE_NPK, EXAMPLE_PERSISTENCE),
+        (EXAMPLE_SECCOMP_NPK, EXAMPLE_SECCOMP),
+        (EXAMPLE_TOKEN_CLIENT_NPK, EXAMPLE_TOKEN_CLIENT),
+        (EXAMPLE_TOKEN_SERVER_NPK, EXAMPLE_TOKEN_SERVER),
+    ];
+    let mut containers = Vec::with_capacity(examples.len());
+    for (npk, container) in examples {
+        client().install(npk, "mem").await?;
+        containers.push(model::Container::try_from(container)?);
+    }
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    // The mount request is sent first. The kill is processed while the examples are mounted.
+    let mut mount = Box::pin(handle().request(model::Request::Mount(containers)));
+    tokio::select! {
+        biased;
+        response = &mut mount => panic!("mount completed before the kill: {:?}", response),
+        result = client().kill(TEST_CONTAINER, 15) => result?,
+    }
+    assume("Process test-container:0.0.1 exited", 5u64).await?;
+
+    match mount.await? {
+        model::Response::Mount(results) => assert!(results
+            .iter()
+            .all(|result| matches!(result, model::MountResult::Ok { .. }))),
+        response => panic!("unexpected response {:?}", response),
+    }
+    Ok(())
+}
+
 // Try to mount a unknown container
 #[runtime_test]
 async fn try_to_umount_used_resource() -> Result<()> {
@@ -290,6 +671,48 @@ async fn try_to_uninstall_a_started_container() -> Result<()> {
     client().stop(TEST_CONTAINER, 5).await
 }
 
+// Force the uninstall of a started container
+#[runtime_test]
+async fn force_uninstall_a_started_container() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping...", 5u64).await?;
+
+    client().uninstall(TEST_CONTAINER, true, false).await?;
+    let container = model::Container::try_from(TEST_CONTAINER)?;
+    let containers = client().containers().await?;
+    assert!(!containers.iter().any(|c| c.container == container));
+
+    client().uninstall_test_resource().await
+}
+
+// Try to uninstall a resource that is required by an installed container
+#[runtime_test]
+async fn try_to_uninstall_a_required_resource() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    let container = model::Container::try_from(TEST_CONTAINER)?;
+    match client().uninstall(TEST_RESOURCE, false, false).await {
+        Err(api::client::Error::Runtime(model::Error::UninstallInUse {
+            mounted,
+            started,
+            dependents,
+            ..
+        })) => {
+            assert!(!mounted);
+            assert!(started.is_empty());
+            assert_eq!(dependents, vec![container]);
+        }
+        e => panic!("Unexpected response: {:?}", e),
+    }
+
+    client().uninstall(TEST_RESOURCE, true, false).await?;
+    client().uninstall_test_container().await
+}
+
 #[runtime_test]
 async fn start_mounted_container_with_not_mounted_resource() -> Result<()> {
     client().install_test_container().await?;
@@ -328,6 +751,120 @@ async fn container_crash_exit() -> Result<()> {
     client().uninstall_test_resource().await
 }
 
+// Keep the last exits of a container in its history
+#[runtime_test]
+async fn container_exit_history() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    for _ in 0..12 {
+        client().start_with_args(TEST_CONTAINER, ["crash"]).await?;
+
+        let n =
+            |n: &Notification| matches!(n, Notification::Exit(_, ExitStatus::Exit { code: 101 }));
+        client().assume_notification(n, 5).await?;
+    }
+
+    let history = client().inspect(TEST_CONTAINER).await?.history;
+    assert_eq!(history.restarts, 0);
+    assert_eq!(history.exits.len(), 10);
+    assert!(history
+        .exits
+        .iter()
+        .all(|exit| exit.status == ExitStatus::Exit { code: 101 } && !exit.oom));
+    assert!(history
+        .exits
+        .windows(2)
+        .all(|exits| exits[0].time <= exits[1].time));
+
+    let containers = client().containers().await?;
+    let data = containers
+        .iter()
+        .find(|c| c.container.to_string() == TEST_CONTAINER)
+        .expect("missing test container");
+    assert_eq!(data.history, history);
+
+    client().uninstall_test_container().await?;
+    client().uninstall_test_resource().await
+}
+
+// Only the variables listed in the manifest can be overridden with a start request
+#[runtime_test]
+async fn start_with_env_overrides() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    let result = client()
+        .start_with_args_env(TEST_CONTAINER, ["sleep"], [("RUST_LOG", "trace")])
+        .await;
+    assert!(matches!(
+        result,
+        Err(api::client::Error::Runtime(
+            model::Error::InvalidArguments { .. }
+        ))
+    ));
+
+    client()
+        .start_with_args_env(TEST_CONTAINER, ["sleep"], [("VERBOSE", "1")])
+        .await?;
+    assume("Sleeping", 5u64).await?;
+    client().stop(TEST_CONTAINER, 5).await
+}
+
+// Start named instances of a container next to the container
+#[runtime_test]
+async fn start_stop_instances() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    let left = format!("{}@left", TEST_CONTAINER);
+    let right = format!("{}@right", TEST_CONTAINER);
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    client().start_with_args(left.as_str(), ["sleep"]).await?;
+    client().start_with_args(right.as_str(), ["sleep"]).await?;
+
+    // The manifest limits the number of instances to two
+    let third = format!("{}@third", TEST_CONTAINER);
+    assert!(client()
+        .start_with_args(third.as_str(), ["sleep"])
+        .await
+        .is_err());
+
+    let started = client()
+        .containers()
+        .await?
+        .into_iter()
+        .filter(|c| c.process.is_some())
+        .map(|c| c.container.to_string())
+        .collect::<Vec<_>>();
+    for container in [TEST_CONTAINER, left.as_str(), right.as_str()] {
+        assert!(started.iter().any(|c| c.as_str() == container));
+    }
+
+    // The container cannot be umounted while an instance is started
+    client().stop(TEST_CONTAINER, 5).await?;
+    assert!(matches!(
+        client().umount(TEST_CONTAINER).await?,
+        model::UmountResult::Error {
+            error: model::Error::UmountBusy { .. },
+            ..
+        }
+    ));
+
+    client().stop(&left, 5).await?;
+    client().stop(&right, 5).await?;
+
+    // Stopped instances are removed
+    assert!(client()
+        .containers()
+        .await?
+        .iter()
+        .all(|c| c.container.instance().is_none()));
+
+    client().uninstall_test_container().await?;
+    client().uninstall_test_resource().await
+}
+
 // Check uid. In the manifest of the test container the uid
 // is set to 1000
 #[runtime_test]
@@ -546,7 +1083,7 @@ async fn seccomp_allowed_syscall_with_prohibited_arg() -> Result<()> {
         matches!(n,
         Notification::Exit (
             _,
-            ExitStatus::Signalled { signal },
+            ExitStatus::Signalled { signal, cause: model::SignalCause::Seccomp { .. } },
         ) if signal == &31)
     };
     client().assume_notification(n, 5).await
@@ -572,6 +1109,18 @@ async fn exit_codes() -> Result<()> {
     Ok(())
 }
 
+// Write to the stdin of a container
+#[runtime_test]
+async fn stdin() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["stdin"]).await?;
+    client().stdin(TEST_CONTAINER, b"hello stdin\n").await?;
+    assume("stdin: hello stdin", 10).await?;
+    client().stop(TEST_CONTAINER, 5).await
+}
+
 // Check printing on stdout and stderr
 #[runtime_test]
 async fn stdout_stderr() -> Result<()> {
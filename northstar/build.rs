@@ -1,8 +1,23 @@
 fn main() {
+    git_commit();
+
     #[cfg(feature = "seccomp")]
     generate_seccomp();
 }
 
+/// Export the commit of the source tree as NORTHSTAR_GIT_COMMIT if built from a git checkout
+fn git_commit() {
+    let commit = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=NORTHSTAR_GIT_COMMIT={}", commit.trim());
+    }
+}
+
 #[cfg(feature = "seccomp")]
 fn generate_seccomp() {
     use std::{env, fs, io::Write, path};
@@ -0,0 +1,32 @@
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use northstar::api::{
+    codec::Codec,
+    model::{Compression, Encoding},
+};
+use tokio_util::codec::Decoder;
+
+/// Maximum line and frame length of the console connections
+const MAX_LENGTH: usize = 1024 * 1024;
+
+// The first byte selects the encoding and compression of the connection. The
+// remaining bytes are decoded until the codec needs more data or fails.
+fuzz_target!(|data: &[u8]| {
+    let (mode, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let mut codec = Codec::new_with_max_length(MAX_LENGTH);
+    if mode & 1 != 0 {
+        codec.set_encoding(Encoding::Cbor);
+    }
+    if mode & 2 != 0 {
+        codec.set_compression(Compression::Zstd);
+    }
+
+    let mut src = BytesMut::from(data);
+    while let Ok(Some(_)) = codec.decode(&mut src) {}
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use ed25519_dalek::{PublicKey, SecretKey};
+use libfuzzer_sys::fuzz_target;
+use northstar::npk::npk::Npk;
+use std::io::Cursor;
+
+// Parse the input without and with a key. The signature of the input does not
+// verify but the hashes are parsed before.
+fuzz_target!(|data: &[u8]| {
+    let _ = Npk::from_reader(Cursor::new(data), None);
+
+    let secret = SecretKey::from_bytes(&[0u8; 32]).expect("invalid key");
+    let key = PublicKey::from(&secret);
+    let _ = Npk::from_reader(Cursor::new(data), Some(&key));
+});
@@ -1,13 +1,18 @@
 use super::{
     codec,
     model::{
-        self, Connect, ConnectNack, Container, ContainerData, ContainerStats, Message, MountResult,
-        Notification, RepositoryId, Request, Response, Token, UmountResult, VerificationResult,
+        self, BatchMode, Compression, Connect, ConnectNack, ConnectToken, Container, ContainerData,
+        ContainerStats, CoreDump, Encoding, FsEntry, FsOp, FsResponse, GroupOp, GroupResult,
+        Inspect, InstallId, InstallStatus, Message, MountResult, Notification, ProcessInfo,
+        RepositoryId, Request, RequestId, Response, Selector, Service, StartCheck, SystemInfo,
+        Token, TokenScope, UmountResult, VerificationResult,
     },
 };
 use crate::common::{
     container,
+    name::{Name, NameError},
     non_nul_string::{InvalidNulChar, NonNulString},
+    selector::SelectorError,
 };
 use futures::{SinkExt, Stream, StreamExt};
 use std::{
@@ -22,13 +27,16 @@ use std::{
 use thiserror::Error;
 use tokio::{
     fs,
-    io::{self, AsyncRead, AsyncWrite, BufWriter},
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter},
     time,
 };
 
 /// Default buffer size for installation transfers
 const BUFFER_SIZE: usize = 1024 * 1024;
 
+/// Chunk size requested when reading files from a container
+const FS_CHUNK_SIZE: u64 = 64 * 1024;
+
 /// API error
 #[allow(missing_docs)]
 #[derive(Error, Debug)]
@@ -47,6 +55,10 @@ pub enum Error {
     Container(container::Error),
     #[error("invalid string {0}")]
     String(InvalidNulChar),
+    #[error("invalid name {0}")]
+    Name(NameError),
+    #[error("invalid selector {0}")]
+    Selector(SelectorError),
     #[error("infalliable")]
     Infalliable,
     #[error("invalid file descriptor from env NORTHSTAR_CONSOLE")]
@@ -59,6 +71,18 @@ impl From<container::Error> for Error {
     }
 }
 
+impl From<NameError> for Error {
+    fn from(e: NameError) -> Self {
+        Error::Name(e)
+    }
+}
+
+impl From<SelectorError> for Error {
+    fn from(e: SelectorError) -> Self {
+        Error::Selector(e)
+    }
+}
+
 impl From<InvalidNulChar> for Error {
     fn from(e: InvalidNulChar) -> Self {
         Error::String(e)
@@ -93,6 +117,9 @@ pub struct Client<T> {
     connection: codec::Framed<T>,
     /// Buffer notifications received during request response communication
     notifications: Option<VecDeque<Notification>>,
+    /// Buffer responses of pipelined requests received during request response
+    /// communication
+    responses: VecDeque<(RequestId, Response)>,
     /// Flag if the client is stopped
     fused: bool,
 }
@@ -105,14 +132,41 @@ pub async fn connect<T: AsyncRead + AsyncWrite + Unpin>(
     io: T,
     notifications: Option<usize>,
     timeout: time::Duration,
+) -> Result<Connection<T>, Error> {
+    connect_with_token(io, notifications, timeout, None).await
+}
+
+/// Connect with an optional authentication token and return a raw stream and sink interface
+pub async fn connect_with_token<T: AsyncRead + AsyncWrite + Unpin>(
+    io: T,
+    notifications: Option<usize>,
+    timeout: time::Duration,
+    token: Option<ConnectToken>,
+) -> Result<Connection<T>, Error> {
+    connect_with_encoding(io, notifications, timeout, token, Encoding::Json, None).await
+}
+
+/// Connect with an optional authentication token and return a raw stream and sink interface
+/// that encodes the messages following the connect handshake with `encoding` and
+/// optionally compresses them with `compression`
+pub async fn connect_with_encoding<T: AsyncRead + AsyncWrite + Unpin>(
+    io: T,
+    notifications: Option<usize>,
+    timeout: time::Duration,
+    token: Option<ConnectToken>,
+    encoding: Encoding,
+    compression: Option<Compression>,
 ) -> Result<Connection<T>, Error> {
     let mut connection = codec::Framed::new(io);
     let subscribe_notifications = notifications.is_some();
 
-    // Send connect message
+    // Send connect message. Json is the default and not requested explicitly.
     let connect = Connect::Connect {
         version: super::VERSION,
         subscribe_notifications,
+        token,
+        encoding: Some(encoding).filter(|encoding| *encoding != Encoding::Json),
+        compression,
     };
     connection
         .send(Message::Connect { connect })
@@ -140,7 +194,13 @@ pub async fn connect<T: AsyncRead + AsyncWrite + Unpin>(
     };
 
     match connect {
-        Connect::Ack { .. } => Ok(connection),
+        Connect::Ack { .. } => {
+            connection.set_encoding(encoding);
+            if let Some(compression) = compression {
+                connection.set_compression(compression);
+            }
+            Ok(connection)
+        }
         Connect::Nack { error } => match dbg!(error) {
             ConnectNack::InvalidProtocolVersion { .. } => Err(Error::Io(io::Error::new(
                 io::ErrorKind::Unsupported,
@@ -180,13 +240,101 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         notifications: Option<usize>,
         timeout: time::Duration,
     ) -> Result<Client<T>, Error> {
-        let connection = time::timeout(timeout, connect(io, notifications, timeout))
-            .await
-            .map_err(|_| Error::Timeout)??;
+        Self::connect(io, notifications, timeout, None, Encoding::Json, None).await
+    }
+
+    /// Create a new northstar client and authenticate with `token`. Listeners that are
+    /// configured with token authentication grant the permissions of the token scope.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::{client::Client, model::{self, ConsolePermission, ConnectToken, TokenScope}};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let scope = TokenScope {
+    ///     permissions: [ConsolePermission::Containers].into_iter().collect(),
+    ///     ..Default::default()
+    /// };
+    /// let token = client
+    ///     .create_scoped_token(model::CONSOLE_TOKEN_TARGET, model::CONSOLE_TOKEN_SHARED, scope, None)
+    ///     .await
+    ///     .unwrap();
+    /// let token = ConnectToken { user: "extern".into(), token };
+    /// let io = tokio::net::TcpStream::connect("localhost:4201").await.unwrap();
+    /// let mut client = Client::new_with_token(io, None, Duration::from_secs(10), token).await.unwrap();
+    /// println!("{:?}", client.containers().await.unwrap());
+    /// # }
+    /// ```
+    pub async fn new_with_token(
+        io: T,
+        notifications: Option<usize>,
+        timeout: time::Duration,
+        token: ConnectToken,
+    ) -> Result<Client<T>, Error> {
+        Self::connect(
+            io,
+            notifications,
+            timeout,
+            Some(token),
+            Encoding::Json,
+            None,
+        )
+        .await
+    }
+
+    /// Create a new northstar client with an optional authentication `token` that
+    /// encodes the messages following the connect handshake with `encoding` and
+    /// optionally compresses them with `compression`. A binary encoding reduces the
+    /// serialization cost and message size e.g. of frequent notifications. Compression
+    /// reduces the size of large responses e.g. container lists.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::{client::Client, model::{Compression, Encoding}};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let io = tokio::net::TcpStream::connect("localhost:4200").await.unwrap();
+    /// let timeout = Duration::from_secs(10);
+    /// let compression = Some(Compression::Zstd);
+    /// let mut client = Client::new_with_encoding(io, Some(10), timeout, None, Encoding::Cbor, compression)
+    ///     .await
+    ///     .unwrap();
+    /// println!("{:?}", client.containers().await.unwrap());
+    /// # }
+    /// ```
+    pub async fn new_with_encoding(
+        io: T,
+        notifications: Option<usize>,
+        timeout: time::Duration,
+        token: Option<ConnectToken>,
+        encoding: Encoding,
+        compression: Option<Compression>,
+    ) -> Result<Client<T>, Error> {
+        Self::connect(io, notifications, timeout, token, encoding, compression).await
+    }
+
+    async fn connect(
+        io: T,
+        notifications: Option<usize>,
+        timeout: time::Duration,
+        token: Option<ConnectToken>,
+        encoding: Encoding,
+        compression: Option<Compression>,
+    ) -> Result<Client<T>, Error> {
+        let connection = time::timeout(
+            timeout,
+            connect_with_encoding(io, notifications, timeout, token, encoding, compression),
+        )
+        .await
+        .map_err(|_| Error::Timeout)??;
 
         Ok(Client {
             connection,
             notifications: notifications.map(VecDeque::with_capacity),
+            responses: VecDeque::new(),
             fused: false,
         })
     }
@@ -212,9 +360,128 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
     /// # }
     /// ```
     pub async fn request(&mut self, request: Request) -> Result<Response, Error> {
+        self.send_request(request, None).await
+    }
+
+    /// Perform a request response sequence with a request `id`. The request can be
+    /// cancelled with this id from another connection of the same peer.
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// # use northstar::api::model::Request::Mount;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let request = Mount(vec!["hello:0.0.1".try_into().unwrap()]);
+    /// let response = client.request_with_id(request, 1).await.expect("failed to mount");
+    /// println!("{:?}", response);
+    /// # }
+    /// ```
+    pub async fn request_with_id(
+        &mut self,
+        request: Request,
+        id: RequestId,
+    ) -> Result<Response, Error> {
+        self.send_request(request, Some(id)).await
+    }
+
+    /// Cancel the request `id` of this peer that is processed on another connection.
+    /// The cancelled request fails with a cancellation error. Requests that transfer
+    /// data close their connection instead.
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.cancel(1).await.expect("failed to cancel request");
+    /// # }
+    /// ```
+    pub async fn cancel(&mut self, id: RequestId) -> Result<(), Error> {
+        match self.request(Request::Cancel(id)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on cancel should be ok or error"),
+        }
+    }
+
+    /// Send a pipelined request with `id` without waiting for the response. The runtime
+    /// processes pipelined requests concurrently and responds in the order of their
+    /// completion. The responses are received with [`Client::response`]. Requests
+    /// that transfer data cannot be pipelined.
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// # use northstar::api::model::Request::{Containers, Repositories};
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.pipeline(Containers, 1).await.expect("failed to send request");
+    /// client.pipeline(Repositories, 2).await.expect("failed to send request");
+    /// for _ in 0..2 {
+    ///     let (id, response) = client.response().await.expect("failed to receive response");
+    ///     println!("{}: {:?}", id, response);
+    /// }
+    /// # }
+    /// ```
+    pub async fn pipeline(&mut self, request: Request, id: RequestId) -> Result<(), Error> {
+        self.fused()?;
+
+        let message = Message::Request {
+            request,
+            id: Some(id),
+        };
+        self.connection.send(message).await.map_err(|e| {
+            self.fuse();
+            Error::Io(e)
+        })
+    }
+
+    /// Receive the next response of a pipelined request together with its request id
+    pub async fn response(&mut self) -> Result<(RequestId, Response), Error> {
+        self.fused()?;
+
+        if let Some(response) = self.responses.pop_front() {
+            return Ok(response);
+        }
+        loop {
+            match self.connection.next().await {
+                Some(Ok(message)) => match message {
+                    Message::Response {
+                        response,
+                        id: Some(id),
+                    } => break Ok((id, response)),
+                    Message::Notification { notification } => {
+                        self.push_notification(notification)?
+                    }
+                    _ => unreachable!("invalid message {:?}", message),
+                },
+                Some(Err(e)) => {
+                    self.fuse();
+                    break Err(Error::Io(e));
+                }
+                None => {
+                    self.fuse();
+                    break Err(Error::Stopped);
+                }
+            }
+        }
+    }
+
+    async fn send_request(
+        &mut self,
+        request: Request,
+        id: Option<RequestId>,
+    ) -> Result<Response, Error> {
         self.fused()?;
 
-        let message = Message::Request { request };
+        let message = Message::Request { request, id };
         self.connection.send(message).await.map_err(|e| {
             self.fuse();
             Error::Io(e)
@@ -222,7 +489,11 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         loop {
             match self.connection.next().await {
                 Some(Ok(message)) => match message {
-                    Message::Response { response } => break Ok(response),
+                    Message::Response {
+                        response,
+                        id: Some(other),
+                    } if id != Some(other) => self.responses.push_back((other, response)),
+                    Message::Response { response, .. } => break Ok(response),
                     Message::Notification { notification } => {
                         self.push_notification(notification)?
                     }
@@ -306,6 +577,27 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
+    /// Request kernel, memory, disk and version information of the host
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let info = client.system_info().await.expect("failed to request system information");
+    /// println!("{} bytes available in the data dir", info.data_dir.available);
+    /// # }
+    /// ```
+    pub async fn system_info(&mut self) -> Result<SystemInfo, Error> {
+        match self.request(Request::SystemInfo).await? {
+            Response::SystemInfo(info) => Ok(info),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on system_info should be system_info or error"),
+        }
+    }
+
     /// Start container with name
     ///
     /// ```no_run
@@ -353,7 +645,9 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
             .await
     }
 
-    /// Start container name and pass args and set additional env variables
+    /// Start container name and pass args and set additional env variables. The
+    /// variables override the manifest values and must be listed in `env_overrides`
+    /// of the manifest. Requires the `start_override` permission.
     ///
     /// ```no_run
     /// # use futures::StreamExt;
@@ -407,148 +701,812 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
-    /// Kill container with name
+    /// Start a template container and pass the values of the template parameters
+    /// declared in the manifest. Parameters without a value use the manifest default.
     ///
     /// ```no_run
     /// # use futures::StreamExt;
-    /// # use tokio::time::Duration;
+    /// # use std::time::Duration;
     /// # use northstar::api::client::Client;
     /// #
     /// # #[tokio::main(flavor = "current_thread")]
     /// # async fn main() {
     /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
-    /// client.kill("hello:0.0.1", 15).await.expect("failed to start \"hello\"");
-    /// // Print stop notification
+    /// client.start_template("browser:0.0.1@left", [("display", ":1")]).await.expect("failed to start \"browser\"");
+    /// // Print start notification
     /// println!("{:#?}", client.next().await);
     /// # }
     /// ```
-    pub async fn kill(
+    pub async fn start_template(
         &mut self,
         container: impl TryInto<Container, Error = impl Into<Error>>,
-        signal: i32,
+        parameters: impl IntoIterator<
+            Item = (
+                impl TryInto<Name, Error = impl Into<Error>>,
+                impl TryInto<NonNulString, Error = impl Into<Error>>,
+            ),
+        >,
     ) -> Result<(), Error> {
         let container = container.try_into().map_err(Into::into)?;
-        match self.request(Request::Kill(container, signal)).await? {
+
+        let mut converted = HashMap::new();
+        for (name, value) in parameters {
+            let name = name.try_into().map_err(Into::into)?;
+            let value = value.try_into().map_err(Into::into)?;
+            converted.insert(name, value);
+        }
+
+        let request = Request::StartTemplate(container, converted);
+        match self.request(request).await? {
             Response::Ok => Ok(()),
             Response::Error(error) => Err(Error::Runtime(error)),
-            _ => unreachable!("response on kill should be ok or error"),
+            _ => unreachable!("response on start should be ok or error"),
         }
     }
 
-    /// Install a npk from path
+    /// Check whether a container can be started without starting it. The checks
+    /// cover the npk, the template parameters with their defaults, the configuration,
+    /// the dependencies, resources and mounts and the seccomp filter.
     ///
     /// ```no_run
-    /// # use northstar::api::client::Client;
     /// # use std::time::Duration;
-    /// # use std::path::Path;
+    /// # use northstar::api::client::Client;
     /// #
     /// # #[tokio::main(flavor = "current_thread")]
     /// # async fn main() {
     /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
-    /// let npk = Path::new("test.npk");
-    /// client.install_file(npk, "default").await.expect("failed to install \"test.npk\" into repository \"default\"");
+    /// for check in client.start_dry_run("hello:0.0.2").await.unwrap() {
+    ///     println!("{:?}: {}", check.kind, check.error.as_deref().unwrap_or("ok"));
+    /// }
     /// # }
     /// ```
-    pub async fn install_file(&mut self, npk: &Path, repository: &str) -> Result<Container, Error> {
-        let file = fs::File::open(npk).await?;
-        let size = file.metadata().await?.len();
-
-        self.install(file, size, repository).await
+    pub async fn start_dry_run(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<Vec<StartCheck>, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::StartDryRun(container)).await? {
+            Response::StartDryRun(checks) => Ok(checks),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on start dry run should be checks"),
+        }
     }
 
-    /// Install a npk
+    /// Apply a list of start, kill, mount and umount requests with one round trip.
+    /// Returns a response for each request. An all or nothing batch stops at the
+    /// first failed request and rolls back the requests applied so far.
     ///
     /// ```no_run
-    /// # use northstar::api::client::Client;
     /// # use std::time::Duration;
-    /// # use std::path::Path;
-    /// # use tokio::fs;
+    /// # use northstar::api::{client::Client, model::{BatchMode, Request}};
     /// #
     /// # #[tokio::main(flavor = "current_thread")]
     /// # async fn main() {
     /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
-    /// let npk = fs::File::open("test.npk").await.expect("failed to open \"test.npk\"");
-    /// let size = npk.metadata().await.unwrap().len();
-    /// client.install(npk, size, "default").await.expect("failed to install \"test.npk\" into repository \"default\"");
+    /// let requests = vec![
+    ///     Request::Mount(vec!["hello-world:0.0.1".try_into().unwrap()]),
+    ///     Request::Kill("cpueater:0.0.1".try_into().unwrap(), 15),
+    /// ];
+    /// let responses = client.batch(requests, BatchMode::AllOrNothing).await.unwrap();
     /// # }
     /// ```
-    pub async fn install(
+    pub async fn batch(
         &mut self,
-        npk: impl AsyncRead + Unpin,
-        size: u64,
-        repository: &str,
-    ) -> Result<Container, Error> {
-        self.fused()?;
-        let request = Request::Install(repository.into(), size);
-        let message = Message::Request { request };
-        self.connection.send(message).await.map_err(|_| {
-            self.fuse();
-            Error::Stopped
-        })?;
-
-        self.connection.flush().await?;
-        debug_assert!(self.connection.write_buffer().is_empty());
-
-        let mut reader = io::BufReader::with_capacity(BUFFER_SIZE, npk);
-        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, self.connection.get_mut());
-        io::copy_buf(&mut reader, &mut writer).await.map_err(|e| {
-            self.fuse();
-            Error::Io(e)
-        })?;
+        requests: Vec<Request>,
+        mode: BatchMode,
+    ) -> Result<Vec<Response>, Error> {
+        match self.request(Request::Batch(requests, mode)).await? {
+            Response::Batch(responses) => Ok(responses),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on batch should be batch"),
+        }
+    }
 
-        loop {
-            match self.connection.next().await {
-                Some(Ok(message)) => match message {
-                    Message::Response { response } => match response {
-                        Response::Install(container) => break Ok(container),
-                        Response::Error(error) => break Err(Error::Runtime(error)),
-                        _ => unreachable!("response on install should be container or error"),
-                    },
-                    Message::Notification { notification } => {
-                        self.push_notification(notification)?
-                    }
-                    _ => unreachable!("invalid response"),
-                },
-                Some(Err(e)) => {
-                    self.fuse();
-                    break Err(Error::Io(e));
-                }
-                None => {
-                    self.fuse();
-                    break Err(Error::Stopped);
-                }
-            }
+    /// Start, kill, mount or umount all containers whose labels match `selector`.
+    /// Returns the result of each matching container.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::{client::Client, model::GroupOp};
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for result in client.group("group=hmi", GroupOp::Start).await.unwrap() {
+    ///     println!("{:?}", result);
+    /// }
+    /// # }
+    /// ```
+    pub async fn group(
+        &mut self,
+        selector: impl TryInto<Selector, Error = impl Into<Error>>,
+        op: GroupOp,
+    ) -> Result<Vec<GroupResult>, Error> {
+        let selector = selector.try_into().map_err(Into::into)?;
+        match self.request(Request::Group(selector, op)).await? {
+            Response::Group(results) => Ok(results),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on group should be group"),
         }
     }
 
-    /// Uninstall a npk
+    /// Kill container with name
     ///
     /// ```no_run
     /// # use futures::StreamExt;
-    /// # use std::time::Duration;
+    /// # use tokio::time::Duration;
     /// # use northstar::api::client::Client;
     /// #
     /// # #[tokio::main(flavor = "current_thread")]
     /// # async fn main() {
     /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
-    /// client.uninstall("hello:0.0.1").await.expect("failed to uninstall \"hello\"");
+    /// client.kill("hello:0.0.1", 15).await.expect("failed to start \"hello\"");
     /// // Print stop notification
     /// println!("{:#?}", client.next().await);
     /// # }
     /// ```
-    pub async fn uninstall(
+    pub async fn kill(
         &mut self,
         container: impl TryInto<Container, Error = impl Into<Error>>,
+        signal: i32,
     ) -> Result<(), Error> {
         let container = container.try_into().map_err(Into::into)?;
-        match self.request(Request::Uninstall(container)).await? {
+        match self.request(Request::Kill(container, signal)).await? {
             Response::Ok => Ok(()),
             Response::Error(error) => Err(Error::Runtime(error)),
-            _ => unreachable!("response on uninstall should be ok or error"),
+            _ => unreachable!("response on kill should be ok or error"),
         }
     }
 
-    /// Stop the runtime
+    /// Send `signal` to the application process of a started container or to all
+    /// of its processes named `process` e.g. to reload the configuration
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.signal("hello:0.0.1", 1, None).await.expect("failed to signal \"hello\"");
+    /// # }
+    /// ```
+    pub async fn signal(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        signal: i32,
+        process: Option<&str>,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let request = Request::Signal(container, signal, process.map(ToString::to_string));
+        match self.request(request).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on signal should be ok or error"),
+        }
+    }
+
+    /// Pause a started container
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.pause("hello:0.0.1").await.expect("failed to pause \"hello\"");
+    /// // Print pause notification
+    /// println!("{:#?}", client.next().await);
+    /// # }
+    /// ```
+    pub async fn pause(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::Pause(container)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on pause should be ok or error"),
+        }
+    }
+
+    /// Write `data` to the stdin of a started container with `io.stdin`
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.stdin("hello:0.0.1", b"status\n").await.expect("failed to write stdin of \"hello\"");
+    /// # }
+    /// ```
+    pub async fn stdin(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self
+            .request(Request::Stdin(container, data.to_vec()))
+            .await?
+        {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on stdin should be ok or error"),
+        }
+    }
+
+    /// Resume a paused container
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.resume("hello:0.0.1").await.expect("failed to resume \"hello\"");
+    /// // Print resume notification
+    /// println!("{:#?}", client.next().await);
+    /// # }
+    /// ```
+    pub async fn resume(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::Resume(container)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on resume should be ok or error"),
+        }
+    }
+
+    /// Prepare a system suspend. The containers are signalled according to their
+    /// manifest and frozen. Persist data is flushed. An empty list of containers
+    /// suspends all started containers.
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.system_suspend(Vec::<&str>::new()).await.expect("failed to suspend");
+    /// // Suspend the host...
+    /// client.system_resume().await.expect("failed to resume");
+    /// # }
+    /// ```
+    pub async fn system_suspend<E, C, I>(&mut self, containers: I) -> Result<(), Error>
+    where
+        E: Into<Error>,
+        C: TryInto<Container, Error = E>,
+        I: 'a + IntoIterator<Item = C>,
+    {
+        self.fused()?;
+        let mut result = vec![];
+        for container in containers.into_iter() {
+            let container = container.try_into().map_err(Into::into)?;
+            result.push(container);
+        }
+
+        match self.request(Request::SystemSuspend(result)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on system suspend should be ok or error"),
+        }
+    }
+
+    /// Thaw the containers frozen by a system suspend
+    pub async fn system_resume(&mut self) -> Result<(), Error> {
+        match self.request(Request::SystemResume).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on system resume should be ok or error"),
+        }
+    }
+
+    /// Update an `input` of the throttling policies e.g. the temperature of a thermal zone
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.policy_input("thermal", 85).await.expect("failed to update input");
+    /// # }
+    /// ```
+    pub async fn policy_input(&mut self, input: &str, value: i64) -> Result<(), Error> {
+        match self
+            .request(Request::PolicyInput(input.to_string(), value))
+            .await?
+        {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on policy input should be ok or error"),
+        }
+    }
+
+    /// Install a npk from path
+    ///
+    /// ```no_run
+    /// # use northstar::api::client::Client;
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let npk = Path::new("test.npk");
+    /// client.install_file(npk, "default").await.expect("failed to install \"test.npk\" into repository \"default\"");
+    /// # }
+    /// ```
+    pub async fn install_file(&mut self, npk: &Path, repository: &str) -> Result<Container, Error> {
+        let file = fs::File::open(npk).await?;
+        let size = file.metadata().await?.len();
+
+        self.install(file, size, repository).await
+    }
+
+    /// Install a npk
+    ///
+    /// ```no_run
+    /// # use northstar::api::client::Client;
+    /// # use std::time::Duration;
+    /// # use std::path::Path;
+    /// # use tokio::fs;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let npk = fs::File::open("test.npk").await.expect("failed to open \"test.npk\"");
+    /// let size = npk.metadata().await.unwrap().len();
+    /// client.install(npk, size, "default").await.expect("failed to install \"test.npk\" into repository \"default\"");
+    /// # }
+    /// ```
+    pub async fn install(
+        &mut self,
+        npk: impl AsyncRead + Unpin,
+        size: u64,
+        repository: &str,
+    ) -> Result<Container, Error> {
+        self.fused()?;
+        let request = Request::Install(repository.into(), size);
+        let message = Message::Request { request, id: None };
+        self.connection.send(message).await.map_err(|_| {
+            self.fuse();
+            Error::Stopped
+        })?;
+
+        self.connection.flush().await?;
+        debug_assert!(self.connection.write_buffer().is_empty());
+
+        let mut reader = io::BufReader::with_capacity(BUFFER_SIZE, npk);
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, self.connection.get_mut());
+        io::copy_buf(&mut reader, &mut writer).await.map_err(|e| {
+            self.fuse();
+            Error::Io(e)
+        })?;
+
+        loop {
+            match self.connection.next().await {
+                Some(Ok(message)) => match message {
+                    Message::Response {
+                        response,
+                        id: Some(id),
+                    } => self.responses.push_back((id, response)),
+                    Message::Response { response, .. } => match response {
+                        Response::Install(container) => break Ok(container),
+                        Response::Error(error) => break Err(Error::Runtime(error)),
+                        _ => unreachable!("response on install should be container or error"),
+                    },
+                    Message::Notification { notification } => {
+                        self.push_notification(notification)?
+                    }
+                    _ => unreachable!("invalid response"),
+                },
+                Some(Err(e)) => {
+                    self.fuse();
+                    break Err(Error::Io(e));
+                }
+                None => {
+                    self.fuse();
+                    break Err(Error::Stopped);
+                }
+            }
+        }
+    }
+
+    /// List the queued and transferring installs of all connections
+    ///
+    /// ```no_run
+    /// # use northstar::api::client::Client;
+    /// # use std::time::Duration;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for install in client.installs().await.unwrap() {
+    ///     println!("{}: {}/{} bytes", install.id, install.bytes, install.total);
+    /// }
+    /// # }
+    /// ```
+    pub async fn installs(&mut self) -> Result<Vec<InstallStatus>, Error> {
+        match self.request(Request::Installs).await? {
+            Response::Installs(installs) => Ok(installs),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on installs should be installs or error"),
+        }
+    }
+
+    /// Cancel a queued or transferring install. The connection of the install is
+    /// closed.
+    ///
+    /// ```no_run
+    /// # use northstar::api::client::Client;
+    /// # use std::time::Duration;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.cancel_install(0).await.expect("failed to cancel install");
+    /// # }
+    /// ```
+    pub async fn cancel_install(&mut self, id: InstallId) -> Result<(), Error> {
+        match self.request(Request::InstallCancel(id)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on cancel_install should be ok or error"),
+        }
+    }
+
+    /// Export the persist directory of a container as tar archive to `writer`.
+    /// Returns the size of the archive.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let mut file = tokio::fs::File::create("hello.tar").await.unwrap();
+    /// client.data_export("hello:0.0.1", &mut file).await.expect("failed to export data");
+    /// # }
+    /// ```
+    pub async fn data_export(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        writer: impl AsyncWrite + Unpin,
+    ) -> Result<u64, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let size = match self.request(Request::DataExport(container)).await? {
+            Response::DataExport(size) => size,
+            Response::Error(error) => return Err(Error::Runtime(error)),
+            _ => unreachable!("response on data export should be data export"),
+        };
+        self.download(size, writer).await
+    }
+
+    /// List the core dumps of a container
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for dump in client.core_dumps("hello:0.0.1").await.expect("failed to list core dumps") {
+    ///     println!("{} ({} bytes)", dump.name, dump.size);
+    /// }
+    /// # }
+    /// ```
+    pub async fn core_dumps(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<Vec<CoreDump>, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::CoreDumps(container)).await? {
+            Response::CoreDumps(dumps) => Ok(dumps),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on core dumps should be core dumps or error"),
+        }
+    }
+
+    /// Export the core dump `name` of a container to `writer`. Returns the size of the dump.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let mut file = tokio::fs::File::create("core").await.unwrap();
+    /// client.core_dump_export("hello:0.0.1", "core.1660000000.1234.11", &mut file).await.expect("failed to export core dump");
+    /// # }
+    /// ```
+    pub async fn core_dump_export(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        name: &str,
+        writer: impl AsyncWrite + Unpin,
+    ) -> Result<u64, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let request = Request::CoreDumpExport(container, name.to_string());
+        let size = match self.request(request).await? {
+            Response::CoreDumpExport(size) => size,
+            Response::Error(error) => return Err(Error::Runtime(error)),
+            _ => unreachable!("response on core dump export should be core dump export"),
+        };
+        self.download(size, writer).await
+    }
+
+    /// Remove the core dump `name` of a container
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.core_dump_remove("hello:0.0.1", "core.1660000000.1234.11").await.expect("failed to remove core dump");
+    /// # }
+    /// ```
+    pub async fn core_dump_remove(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        name: &str,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self
+            .request(Request::CoreDumpRemove(container, name.to_string()))
+            .await?
+        {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on core dump remove should be ok or error"),
+        }
+    }
+
+    /// Copy `size` bytes that follow a response from the connection to `writer`
+    async fn download(
+        &mut self,
+        size: u64,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<u64, Error> {
+        // The codec might have pulled parts of the payload into the read buffer
+        let buffered = (self.connection.read_buffer().len() as u64).min(size);
+        let buffer = self
+            .connection
+            .read_buffer_mut()
+            .split_to(buffered as usize);
+        writer.write_all(&buffer).await?;
+
+        let mut payload = self.connection.get_mut().take(size - buffered);
+        let copied = io::copy(&mut payload, &mut writer).await.map_err(|e| {
+            self.fuse();
+            Error::Io(e)
+        })?;
+        writer.flush().await?;
+
+        if buffered + copied == size {
+            Ok(size)
+        } else {
+            self.fuse();
+            Err(Error::Stopped)
+        }
+    }
+
+    /// Attach to the PTY of a started container with `io.tty`. `input` is forwarded to
+    /// the PTY and the PTY output is written to `output` until the container exits or
+    /// `input` ends. The connection cannot be used for further requests afterwards.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.attach("hello:0.0.1", tokio::io::stdin(), tokio::io::stdout()).await.expect("failed to attach");
+    /// # }
+    /// ```
+    pub async fn attach(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        mut input: impl AsyncRead + Unpin,
+        mut output: impl AsyncWrite + Unpin,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::Attach(container)).await? {
+            Response::Ok => (),
+            Response::Error(error) => return Err(Error::Runtime(error)),
+            _ => unreachable!("response on attach should be ok or error"),
+        }
+
+        // The connection carries the raw PTY data from now on
+        self.fuse();
+
+        // The codec might have pulled output into the read buffer
+        let buffer = self.connection.read_buffer_mut().split();
+        output.write_all(&buffer).await?;
+        output.flush().await?;
+
+        let (mut reader, mut writer) = io::split(self.connection.get_mut());
+        let forward_input = io::copy(&mut input, &mut writer);
+        tokio::pin!(forward_input);
+        let mut buffer = [0u8; 1024];
+        loop {
+            tokio::select! {
+                read = reader.read(&mut buffer) => match read? {
+                    0 => break,
+                    n => {
+                        output.write_all(&buffer[..n]).await?;
+                        output.flush().await?;
+                    }
+                },
+                copied = &mut forward_input => {
+                    copied?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the persist directory of a stopped container with the content of the
+    /// tar archive read from `archive`
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let file = tokio::fs::File::open("hello.tar").await.unwrap();
+    /// let size = file.metadata().await.unwrap().len();
+    /// client.data_import("hello:0.0.1", file, size).await.expect("failed to import data");
+    /// # }
+    /// ```
+    pub async fn data_import(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        archive: impl AsyncRead + Unpin,
+        size: u64,
+    ) -> Result<(), Error> {
+        self.fused()?;
+        let container = container.try_into().map_err(Into::into)?;
+        let request = Request::DataImport(container, size);
+        let message = Message::Request { request, id: None };
+        self.connection.send(message).await.map_err(|_| {
+            self.fuse();
+            Error::Stopped
+        })?;
+
+        self.connection.flush().await?;
+        debug_assert!(self.connection.write_buffer().is_empty());
+
+        let result = {
+            let mut reader = io::BufReader::with_capacity(BUFFER_SIZE, archive.take(size));
+            let mut writer = BufWriter::with_capacity(BUFFER_SIZE, self.connection.get_mut());
+            match io::copy_buf(&mut reader, &mut writer).await {
+                Ok(_) => writer.flush().await,
+                Err(e) => Err(e),
+            }
+        };
+        result.map_err(|e| {
+            self.fuse();
+            Error::Io(e)
+        })?;
+
+        loop {
+            match self.connection.next().await {
+                Some(Ok(message)) => match message {
+                    Message::Response {
+                        response,
+                        id: Some(id),
+                    } => self.responses.push_back((id, response)),
+                    Message::Response { response, .. } => match response {
+                        Response::Ok => break Ok(()),
+                        Response::Error(error) => break Err(Error::Runtime(error)),
+                        _ => unreachable!("response on data import should be ok or error"),
+                    },
+                    Message::Notification { notification } => {
+                        self.push_notification(notification)?
+                    }
+                    _ => unreachable!("invalid response"),
+                },
+                Some(Err(e)) => {
+                    self.fuse();
+                    break Err(Error::Io(e));
+                }
+                None => {
+                    self.fuse();
+                    break Err(Error::Stopped);
+                }
+            }
+        }
+    }
+
+    /// Wipe the persist directory of a container. A started container is killed
+    /// before its data is removed.
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.data_reset("hello:0.0.1").await.expect("failed to reset data of \"hello\"");
+    /// // Print data reset notification
+    /// println!("{:#?}", client.next().await);
+    /// # }
+    /// ```
+    pub async fn data_reset(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::DataReset(container)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on data reset should be ok or error"),
+        }
+    }
+
+    /// Uninstall a npk. Containers that are started or mounted and resources of installed
+    /// containers are refused unless `force` is set. A forced uninstall kills the started
+    /// containers that use the container and returns once it is removed. Set `wipe` to
+    /// remove the persistent data of the container.
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.uninstall("hello:0.0.1", false, false).await.expect("failed to uninstall \"hello\"");
+    /// // Print stop notification
+    /// println!("{:#?}", client.next().await);
+    /// # }
+    /// ```
+    pub async fn uninstall(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        force: bool,
+        wipe: bool,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let request = Request::Uninstall {
+            container,
+            force,
+            wipe,
+        };
+        match self.request(request).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on uninstall should be ok or error"),
+        }
+    }
+
+    /// Stop the runtime
     pub async fn shutdown(&mut self) {
         self.request(Request::Shutdown).await.ok();
     }
@@ -609,6 +1567,30 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
+    /// Mount the containers that are not mounted and whose `name:version` matches
+    /// `pattern`. A `*` matches any sequence of characters.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.mount_matching("hello-*").await.expect("failed to mount");
+    /// # }
+    /// ```
+    pub async fn mount_matching(&mut self, pattern: &str) -> Result<Vec<MountResult>, Error> {
+        match self
+            .request(Request::MountMatching(pattern.to_string()))
+            .await?
+        {
+            Response::Mount(result) => Ok(result),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on mount should be mount"),
+        }
+    }
+
     /// Umount a mounted container
     ///
     /// ```no_run
@@ -665,6 +1647,30 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
+    /// Umount the mounted and not started containers whose `name:version` matches
+    /// `pattern`. A `*` matches any sequence of characters.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.umount_matching("*").await.expect("failed to unmount");
+    /// # }
+    /// ```
+    pub async fn umount_matching(&mut self, pattern: &str) -> Result<Vec<UmountResult>, Error> {
+        match self
+            .request(Request::UmountMatching(pattern.to_string()))
+            .await?
+        {
+            Response::Umount(result) => Ok(result),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on umount should be umount"),
+        }
+    }
+
     /// Gather container statistics
     ///
     /// ```no_run
@@ -689,6 +1695,163 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
+    /// List the processes of a started container
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for process in client.ps("hello:0.0.1").await.unwrap() {
+    ///     println!("{} {} {}", process.pid, process.name, process.rss);
+    /// }
+    /// # }
+    /// ```
+    pub async fn ps(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<Vec<ProcessInfo>, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::Ps(container)).await? {
+            Response::Ps(processes) => Ok(processes),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on ps should be ps"),
+        }
+    }
+
+    /// List the services of started containers. Lists all services without `name`.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for service in client.services(Some("telemetry".try_into().unwrap())).await.unwrap() {
+    ///     println!("{} {} {:?}", service.container, service.port, service.protocol);
+    /// }
+    /// # }
+    /// ```
+    pub async fn services(&mut self, name: Option<Name>) -> Result<Vec<Service>, Error> {
+        match self.request(Request::Services(name)).await? {
+            Response::Services(services) => Ok(services),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on services should be services"),
+        }
+    }
+
+    /// Inspect the manifest and software bill of materials of an installed container
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let inspect = client.inspect("hello:0.0.1").await.unwrap();
+    /// println!("{:?}", inspect.sbom);
+    /// # }
+    /// ```
+    pub async fn inspect(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<Inspect, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::Inspect(container)).await? {
+            Response::Inspect(inspect) => Ok(*inspect),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on inspect should be inspect"),
+        }
+    }
+
+    /// List the entries of directory `path` of a mounted container
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for entry in client.fs_list("hello:0.0.1", "/").await.unwrap() {
+    ///     println!("{}", entry.name);
+    /// }
+    /// # }
+    /// ```
+    pub async fn fs_list(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<FsEntry>, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let path = path.as_ref().to_owned();
+        let op = FsOp::List;
+        match self
+            .request(Request::Fs {
+                container,
+                path,
+                op,
+            })
+            .await?
+        {
+            Response::Fs(FsResponse::List(entries)) => Ok(entries),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on fs list should be a list"),
+        }
+    }
+
+    /// Read the file `path` of a mounted container in chunks and write the content
+    /// to `writer`. Returns the number of bytes read.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.fs_read("hello:0.0.1", "/data/log", &mut tokio::io::stdout()).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn fs_read(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        path: impl AsRef<Path>,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<u64, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let path = path.as_ref();
+        let mut offset = 0;
+        loop {
+            let op = FsOp::Read {
+                offset,
+                length: FS_CHUNK_SIZE,
+            };
+            let request = Request::Fs {
+                container: container.clone(),
+                path: path.to_owned(),
+                op,
+            };
+            match self.request(request).await? {
+                Response::Fs(FsResponse::Read { data, eof }) => {
+                    writer.write_all(&data).await?;
+                    offset += data.len() as u64;
+                    // An empty chunk without eof would loop forever
+                    if eof || data.is_empty() {
+                        writer.flush().await?;
+                        break Ok(offset);
+                    }
+                }
+                Response::Error(error) => break Err(Error::Runtime(error)),
+                _ => unreachable!("response on fs read should be a read"),
+            }
+        }
+    }
+
     /// Create a token
     ///
     /// The `target` parameter must be the container name of the container that
@@ -705,20 +1868,61 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
     /// # }
     /// ```
     pub async fn create_token<R, S>(&mut self, target: R, shared: S) -> Result<Token, Error>
+    where
+        R: AsRef<[u8]>,
+        S: AsRef<[u8]>,
+    {
+        self.create_scoped_token(target, shared, TokenScope::default(), None)
+            .await
+    }
+
+    /// Create a token with scope claims and a validity
+    ///
+    /// The scope is returned to the verifier of the token. A token cannot grant
+    /// permissions that the creator does not have. The validity is capped by the
+    /// runtime configuration. Without a validity the configured default is used.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// # use northstar::api::model::{ConsolePermission, TokenScope};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let scope = TokenScope {
+    ///     permissions: [ConsolePermission::Inspect].into_iter().collect(),
+    ///     ..Default::default()
+    /// };
+    /// let validity = Some(Duration::from_secs(10));
+    /// println!("{:?}", client.create_scoped_token("target", "hello:0.0.1", scope, validity).await.unwrap());
+    /// # }
+    /// ```
+    pub async fn create_scoped_token<R, S>(
+        &mut self,
+        target: R,
+        shared: S,
+        scope: TokenScope,
+        validity: Option<time::Duration>,
+    ) -> Result<Token, Error>
     where
         R: AsRef<[u8]>,
         S: AsRef<[u8]>,
     {
         let target = target.as_ref().to_vec();
         let shared = shared.as_ref().to_vec();
-        match self.request(Request::TokenCreate(target, shared)).await? {
+        let validity = validity.map(|validity| validity.as_secs());
+        match self
+            .request(Request::TokenCreate(target, shared, scope, validity))
+            .await?
+        {
             Response::Token(token) => Ok(token),
             Response::Error(error) => Err(Error::Runtime(error)),
             _ => unreachable!("response on token should be a token reponse created"),
         }
     }
 
-    /// Verify a slice of bytes with a token
+    /// Verify a slice of bytes with a token. On success the scope of the token is returned.
     ///
     /// ```no_run
     /// # use std::time::Duration;
@@ -729,8 +1933,8 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
     /// # async fn main() {
     /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
     /// let token = client.create_token("hello:0.0.1", "target").await.unwrap();
-    /// assert_eq!(client.verify_token(&token, "hello:0.0.1", "target").await.unwrap(), VerificationResult::Ok);
-    /// assert_eq!(client.verify_token(&token, "#noafd", "target").await.unwrap(), VerificationResult::Ok);
+    /// assert!(matches!(client.verify_token(&token, "hello:0.0.1", "target").await.unwrap(), VerificationResult::Ok(_)));
+    /// assert!(matches!(client.verify_token(&token, "#noafd", "target").await.unwrap(), VerificationResult::Ok(_)));
     /// # }
     /// ```
     pub async fn verify_token<R, S>(
@@ -814,20 +2018,27 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Stream for Client<T> {
         }
 
         if let Some(n) = self.notifications.as_mut().and_then(|n| n.pop_front()) {
-            Poll::Ready(Some(Ok(n)))
-        } else {
+            return Poll::Ready(Some(Ok(n)));
+        }
+
+        loop {
             match self.connection.poll_next_unpin(cx) {
                 Poll::Ready(r) => match r {
                     Some(Ok(message)) => match message {
                         Message::Notification { notification } => {
-                            Poll::Ready(Some(Ok(notification)))
+                            break Poll::Ready(Some(Ok(notification)))
                         }
+                        // Responses of pipelined requests are kept for `Client::response`
+                        Message::Response {
+                            response,
+                            id: Some(id),
+                        } => self.responses.push_back((id, response)),
                         _ => unreachable!(),
                     },
-                    Some(Err(e)) => Poll::Ready(Some(Err(e))),
-                    None => Poll::Ready(None),
+                    Some(Err(e)) => break Poll::Ready(Some(Err(e))),
+                    None => break Poll::Ready(None),
                 },
-                Poll::Pending => Poll::Pending,
+                Poll::Pending => break Poll::Pending,
             }
         }
     }
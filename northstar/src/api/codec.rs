@@ -1,13 +1,25 @@
-use super::model;
+use super::model::{self, Compression, Encoding};
 use futures::StreamExt;
-use std::{io::ErrorKind, time::Duration};
+use std::{
+    borrow::Cow,
+    io::{ErrorKind, Read},
+    time::Duration,
+};
 use tokio::{
     io::{self, AsyncRead, AsyncWrite},
     time::Instant,
 };
-use tokio_util::codec::{Decoder, Encoder, LinesCodec};
+use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec, LinesCodec};
+
+/// Messages smaller than this are not compressed
+const COMPRESSION_THRESHOLD: usize = 512;
+/// Flag of a frame with an uncompressed message
+const FRAME_PLAIN: u8 = 0;
+/// Flag of a frame with a zstd compressed message
+const FRAME_ZSTD: u8 = 1;
 
-/// Newline delimited json codec for api::Message that on top implements AsyncRead and Write
+/// Newline delimited json or length delimited CBOR codec for api::Message that on top
+/// implements AsyncRead and Write
 pub struct Framed<T> {
     inner: tokio_util::codec::Framed<T, Codec>,
     rate_limitter: Option<TimeWindowCounter>,
@@ -31,6 +43,16 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Framed<T> {
         }
     }
 
+    /// Encode and decode the following messages with `encoding`
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.inner.codec_mut().set_encoding(encoding);
+    }
+
+    /// Compress the following messages with `compression`
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.inner.codec_mut().set_compression(compression);
+    }
+
     /// Limit the incoming message rate to a maximum inside a time duration
     pub fn throttle_stream(&mut self, rate: usize, duration: Duration) {
         self.rate_limitter = Some(TimeWindowCounter::new(rate, duration));
@@ -63,25 +85,64 @@ impl<T> std::ops::DerefMut for Framed<T> {
     }
 }
 
-/// Newline delimited json
-#[derive(Default)]
+/// Newline delimited json or length delimited CBOR. The codec starts with uncompressed
+/// json. Compressed connections use length delimited frames for both encodings.
 pub struct Codec {
-    inner: LinesCodec,
+    encoding: Encoding,
+    compression: Option<Compression>,
+    /// Maximum length of a line, frame or decompressed message
+    max_length: Option<usize>,
+    lines: LinesCodec,
+    frames: LengthDelimitedCodec,
+}
+
+impl Default for Codec {
+    fn default() -> Codec {
+        Codec {
+            encoding: Encoding::Json,
+            compression: None,
+            max_length: None,
+            lines: LinesCodec::default(),
+            frames: LengthDelimitedCodec::default(),
+        }
+    }
 }
 
 impl Codec {
-    /// Returns a Codec with a maximum line length limit.
+    /// Returns a Codec with a maximum line or frame length limit.
     ///
     /// If this is set, calls to Codec::decode will return a
     /// io::Error when a line exceeds the length limit. Subsequent calls
     /// will discard up to limit bytes from that line until a newline character
     /// is reached, returning None until the line over the limit has been fully
     /// discarded. After that point, calls to decode will function as normal.
+    /// Frames and decompressed messages that exceed the limit are an error.
     pub fn new_with_max_length(max_length: usize) -> Codec {
         Codec {
-            inner: LinesCodec::new_with_max_length(max_length),
+            encoding: Encoding::Json,
+            compression: None,
+            max_length: Some(max_length),
+            lines: LinesCodec::new_with_max_length(max_length),
+            frames: LengthDelimitedCodec::builder()
+                .max_frame_length(max_length)
+                .new_codec(),
         }
     }
+
+    /// Encode and decode the following messages with `encoding`
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Compress the following messages with `compression`
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = Some(compression);
+    }
+
+    /// Returns true if messages are newline delimited
+    fn lines(&self) -> bool {
+        self.encoding == Encoding::Json && self.compression.is_none()
+    }
 }
 
 impl Decoder for Codec {
@@ -89,13 +150,32 @@ impl Decoder for Codec {
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        self.inner
-            .decode(src)
-            .map_err(|e| io::Error::new(ErrorKind::Other, e))? // See LinesCodecError.
-            .as_deref()
-            .map(serde_json::from_str)
-            .transpose()
-            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+        if self.lines() {
+            return self
+                .lines
+                .decode(src)
+                .map_err(|e| io::Error::new(ErrorKind::Other, e))? // See LinesCodecError.
+                .as_deref()
+                .map(serde_json::from_str)
+                .transpose()
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+        }
+
+        let frame = match self.frames.decode(src)? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let message = match self.compression {
+            Some(Compression::Zstd) => decompress(&frame, self.max_length)?,
+            None => Cow::Borrowed(frame.as_ref()),
+        };
+        match self.encoding {
+            Encoding::Json => serde_json::from_slice(&message[..])
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e)),
+            Encoding::Cbor => ciborium::de::from_reader(&message[..])
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string())),
+        }
+        .map(Some)
     }
 }
 
@@ -107,9 +187,65 @@ impl Encoder<model::Message> for Codec {
         item: model::Message,
         dst: &mut bytes::BytesMut,
     ) -> Result<(), Self::Error> {
-        self.inner
-            .encode(serde_json::to_string(&item)?.as_str(), dst)
-            .map_err(|e| io::Error::new(ErrorKind::Other, e))
+        if self.lines() {
+            return self
+                .lines
+                .encode(serde_json::to_string(&item)?.as_str(), dst)
+                .map_err(|e| io::Error::new(ErrorKind::Other, e));
+        }
+
+        let message = match self.encoding {
+            Encoding::Json => serde_json::to_vec(&item)?,
+            Encoding::Cbor => {
+                let mut message = Vec::new();
+                ciborium::ser::into_writer(&item, &mut message)
+                    .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+                message
+            }
+        };
+        let frame = match self.compression {
+            Some(Compression::Zstd) => compress(&message)?,
+            None => message,
+        };
+        self.frames.encode(bytes::Bytes::from(frame), dst)
+    }
+}
+
+/// Compress `message` into a frame that starts with a flag whether the message is
+/// compressed. Small messages are not worth compressing.
+fn compress(message: &[u8]) -> io::Result<Vec<u8>> {
+    let mut frame = Vec::with_capacity(message.len() + 1);
+    if message.len() < COMPRESSION_THRESHOLD {
+        frame.push(FRAME_PLAIN);
+        frame.extend_from_slice(message);
+    } else {
+        frame.push(FRAME_ZSTD);
+        zstd::stream::copy_encode(message, &mut frame, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    }
+    Ok(frame)
+}
+
+/// Decompress the message of `frame`. The decompressed message must not exceed
+/// `max_length`.
+fn decompress(frame: &[u8], max_length: Option<usize>) -> io::Result<Cow<'_, [u8]>> {
+    match frame.split_first() {
+        Some((&FRAME_PLAIN, message)) => Ok(Cow::Borrowed(message)),
+        Some((&FRAME_ZSTD, compressed)) => {
+            let max_length = max_length.map(|l| l as u64).unwrap_or(u64::MAX);
+            let mut message = Vec::new();
+            zstd::stream::read::Decoder::new(compressed)?
+                .take(max_length.saturating_add(1))
+                .read_to_end(&mut message)?;
+            if message.len() as u64 > max_length {
+                Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "decompressed message too long",
+                ))
+            } else {
+                Ok(Cow::Owned(message))
+            }
+        }
+        _ => Err(io::Error::new(ErrorKind::InvalidData, "invalid frame")),
     }
 }
 
@@ -162,12 +298,15 @@ mod tests {
         let mut buffer = encode_messages([
             Message::Request {
                 request: Request::Containers,
+                id: None,
             },
             Message::Request {
                 request: Request::Repositories,
+                id: None,
             },
             Message::Request {
                 request: Request::Shutdown,
+                id: None,
             },
         ])?;
 
@@ -181,7 +320,8 @@ mod tests {
             assert!(matches!(
                 msg,
                 Some(Ok(model::Message::Request {
-                    request: Request::Containers
+                    request: Request::Containers,
+                    id: None
                 }))
             ));
         }
@@ -192,7 +332,8 @@ mod tests {
             assert!(matches!(
                 msg,
                 Some(Ok(model::Message::Request {
-                    request: Request::Repositories
+                    request: Request::Repositories,
+                    id: None
                 }))
             ));
         }
@@ -205,7 +346,8 @@ mod tests {
             assert!(matches!(
                 msg,
                 Some(Ok(model::Message::Request {
-                    request: Request::Shutdown
+                    request: Request::Shutdown,
+                    id: None
                 }))
             ));
         }
@@ -243,21 +385,135 @@ mod tests {
             // Post-condition.
             assert_eq!(message, Some(initial_message));
         }
+
+        #[test]
+        fn encoding_a_message_as_cbor_then_decoding_it_yields_the_same_message(initial_message in mk_message()) {
+            let mut message_as_bytes = BytesMut::default();
+
+            let mut codec = Codec {
+                encoding: Encoding::Cbor,
+                ..Default::default()
+            };
+
+            codec.encode(initial_message.clone(), &mut message_as_bytes)?;
+            let message = codec.decode(&mut message_as_bytes)?;
+
+            assert_eq!(message, Some(initial_message));
+        }
+
+        #[test]
+        fn encoding_a_compressed_message_then_decoding_it_yields_the_same_message(initial_message in mk_message(), cbor: bool) {
+            let mut message_as_bytes = BytesMut::default();
+
+            let mut codec = Codec {
+                encoding: if cbor { Encoding::Cbor } else { Encoding::Json },
+                compression: Some(Compression::Zstd),
+                ..Default::default()
+            };
+
+            codec.encode(initial_message.clone(), &mut message_as_bytes)?;
+            let message = codec.decode(&mut message_as_bytes)?;
+
+            assert_eq!(message, Some(initial_message));
+        }
+    }
+
+    #[test]
+    fn compression() -> std::io::Result<()> {
+        let log = Message::Notification {
+            notification: Notification::Log(
+                "hello:0.0.1".try_into().expect("invalid container"),
+                "hello ".repeat(1000),
+            ),
+        };
+
+        let mut codec = Codec::default();
+        let mut plain = BytesMut::new();
+        codec.encode(log.clone(), &mut plain)?;
+
+        codec.compression = Some(Compression::Zstd);
+        let mut compressed = BytesMut::new();
+        codec.encode(log.clone(), &mut compressed)?;
+        assert_eq!(compressed[4], FRAME_ZSTD);
+        assert!(compressed.len() < plain.len() / 10);
+        assert_eq!(codec.decode(&mut compressed)?, Some(log.clone()));
+
+        // The decompressed message exceeds the maximum length
+        let mut codec = Codec::new_with_max_length(1024);
+        codec.compression = Some(Compression::Zstd);
+        let mut compressed = BytesMut::new();
+        codec.encode(log, &mut compressed)?;
+        assert!(compressed.len() < 1024);
+        assert!(codec.decode(&mut compressed).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn switch_encoding() -> std::io::Result<()> {
+        // A json message followed by a cbor message in the same read
+        let mut codec = Codec::default();
+        let mut buffer = BytesMut::new();
+        codec.encode(
+            Message::Notification {
+                notification: Notification::Shutdown,
+            },
+            &mut buffer,
+        )?;
+        codec.encoding = Encoding::Cbor;
+        codec.encode(
+            Message::Request {
+                request: Request::Containers,
+                id: Some(1),
+            },
+            &mut buffer,
+        )?;
+
+        let mut stream = Framed::new(std::io::Cursor::new(buffer.as_mut()));
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(Message::Notification {
+                notification: Notification::Shutdown
+            }))
+        ));
+        stream.set_encoding(Encoding::Cbor);
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(Message::Request {
+                request: Request::Containers,
+                id: Some(1)
+            }))
+        ));
+        assert!(stream.next().await.is_none());
+
+        Ok(())
     }
 
     fn mk_message() -> impl Strategy<Value = Message> {
         prop_oneof![
             Just(Message::Request {
-                request: Request::Containers
+                request: Request::Containers,
+                id: None
             }),
             Just(Message::Request {
-                request: Request::Shutdown
+                request: Request::Shutdown,
+                id: None
             }),
             Just(Message::Request {
-                request: Request::Mount(vec!())
+                request: Request::Mount(vec!()),
+                id: None
+            }),
+            Just(Message::Request {
+                request: Request::Containers,
+                id: Some(1)
+            }),
+            Just(Message::Response {
+                response: Response::Ok,
+                id: None
             }),
             Just(Message::Response {
-                response: Response::Ok
+                response: Response::Ok,
+                id: Some(1)
             }),
             Just(Message::Notification {
                 notification: Notification::Shutdown
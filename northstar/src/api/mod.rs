@@ -8,4 +8,4 @@ pub mod codec;
 pub mod model;
 
 /// API version
-pub const VERSION: Version = Version::new(0, 3, 0);
+pub const VERSION: Version = Version::new(0, 4, 0);
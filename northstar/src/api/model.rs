@@ -8,6 +8,7 @@ use serde::{de::Visitor, Deserialize, Serialize, Serializer};
 use std::{
     collections::{HashMap, HashSet},
     fmt,
+    path::PathBuf,
 };
 
 /// Console configuration
@@ -18,14 +19,24 @@ pub type ConsolePermission = crate::npk::manifest::console::Permission;
 pub type Container = crate::common::container::Container;
 /// Container exit code
 pub type ExitCode = i32;
+/// Install id
+pub type InstallId = u64;
 /// Manifest
 pub type Manifest = crate::npk::manifest::Manifest;
 /// String that never contains a null byte
 pub type NonNulString = crate::common::non_nul_string::NonNulString;
 /// Process id
 pub type Pid = u32;
+/// Transport protocol
+pub type Protocol = crate::npk::manifest::network::Protocol;
+/// Request id assigned by the client
+pub type RequestId = u64;
 /// Repository id
 pub type RepositoryId = String;
+/// Software bill of materials
+pub type Sbom = crate::npk::npk::Sbom;
+/// Container label selector
+pub type Selector = crate::common::selector::Selector;
 /// Unix signal
 pub type Signal = u32;
 /// Version
@@ -38,10 +49,26 @@ pub type ContainerStats = HashMap<String, serde_json::Value>;
 #[allow(missing_docs)]
 #[serde(untagged)]
 pub enum Message {
-    Connect { connect: Connect },
-    Request { request: Request },
-    Response { response: Response },
-    Notification { notification: Notification },
+    Connect {
+        connect: Connect,
+    },
+    Request {
+        request: Request,
+        /// Optional id that identifies the request e.g. for a cancellation. Requests
+        /// with an id that don't transfer data are pipelined: the runtime processes
+        /// them concurrently and responds in the order of completion.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<RequestId>,
+    },
+    Response {
+        response: Response,
+        /// Id of the request this is the response to
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<RequestId>,
+    },
+    Notification {
+        notification: Notification,
+    },
 }
 
 /// Notification / Event
@@ -49,11 +76,35 @@ pub enum Message {
 #[serde(rename_all = "snake_case")]
 #[allow(missing_docs)]
 pub enum Notification {
+    /// Permitted request of `peer` that changes the state of the runtime
+    Audit {
+        listener: Option<String>,
+        peer: String,
+        action: String,
+    },
     CGroup(Container, CgroupNotification),
+    CoreDump(Container, CoreDump),
+    DataReset(Container),
     Exit(Container, ExitStatus),
     Install(Container),
+    InstallProgress {
+        id: InstallId,
+        bytes: u64,
+        total: u64,
+    },
+    Kernel(Container, KernelNotification),
+    Log(Container, String),
+    Paused(Container),
+    PeerBanned(String, u64),
+    PolicyActivated(String),
+    PolicyDeactivated(String),
+    RepositoryLoaded(RepositoryId),
+    Resumed(Container),
     Shutdown,
+    ShutdownStage(u32, Vec<Container>),
     Started(Container),
+    /// The container could not be created. Contains the reason e.g. a failed mount.
+    StartFailed(Container, String),
     Uninstall(Container),
 }
 
@@ -77,6 +128,62 @@ pub struct MemoryNotification {
     pub oom_kill: Option<u64>,
 }
 
+/// Kernel message about a process of a container
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KernelNotification {
+    /// Type of the message
+    pub kind: KernelNotificationKind,
+    /// Pid of the process in the initial pid namespace
+    pub pid: u32,
+    /// Message text
+    pub message: String,
+}
+
+/// Type of a kernel message
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KernelNotificationKind {
+    /// The process was killed by the oom killer
+    Oom,
+    /// The process violated its seccomp filter
+    Seccomp {
+        /// Syscall number
+        syscall: Option<u32>,
+    },
+    /// The process received a segmentation fault
+    Segfault,
+}
+
+/// Core dump or minidump of a container process
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CoreDump {
+    /// File name used to export and remove the dump
+    pub name: String,
+    /// Format of the dump
+    pub kind: CoreDumpKind,
+    /// Pid of the dumped process in the initial pid namespace. Minidumps carry the
+    /// pid of the container.
+    pub pid: Pid,
+    /// Signal that caused the dump
+    pub signal: Signal,
+    /// Time of the dump in seconds since the epoch
+    pub time: u64,
+    /// Size in bytes. Dumps are truncated to the configured maximum size.
+    pub size: u64,
+}
+
+/// Format of a core dump
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreDumpKind {
+    /// ELF core dump written by the kernel
+    Core,
+    /// Minidump with the threads, stacks and modules of the process
+    Minidump,
+}
+
 /// Connect meta information
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -87,6 +194,15 @@ pub enum Connect {
         version: Version,
         /// Subscribe this connection to notifications
         subscribe_notifications: bool,
+        /// Authentication token. Required by listeners configured with token authentication
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<ConnectToken>,
+        /// Encoding of the messages following the connect handshake. Defaults to json.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encoding: Option<Encoding>,
+        /// Compression of the messages following the connect handshake
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compression: Option<Compression>,
     },
     /// Ack
     Ack { configuration: ConsoleConfiguration },
@@ -97,6 +213,45 @@ pub enum Connect {
     },
 }
 
+/// Wire format of console messages. The connect handshake is always json encoded.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Newline delimited json
+    Json,
+    /// Length delimited CBOR. Each message is prefixed with its length as big endian u32.
+    Cbor,
+}
+
+/// Compression of console messages. Compressed messages of either encoding are length
+/// delimited. Each frame starts with a byte that is 1 if the message is compressed and
+/// 0 if not.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    /// Zstandard
+    Zstd,
+}
+
+/// Target of tokens used to authenticate console connections
+pub const CONSOLE_TOKEN_TARGET: &str = "northstar";
+/// Shared of tokens used to authenticate console connections
+pub const CONSOLE_TOKEN_SHARED: &str = "console";
+
+/// Token authentication of a console connection. The token is created with
+/// `TokenCreate` for the target [`CONSOLE_TOKEN_TARGET`] and the shared
+/// [`CONSOLE_TOKEN_SHARED`]. The connection is granted the permissions of the
+/// token scope.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ConnectToken {
+    /// Name of the container that created the token or `extern` if the token was
+    /// created by an external client
+    pub user: String,
+    /// Token
+    pub token: Token,
+}
+
 /// Connection nack
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -111,28 +266,127 @@ pub enum ConnectNack {
 #[serde(rename_all = "snake_case")]
 #[allow(missing_docs)]
 pub enum Request {
+    Attach(Container),
+    Batch(Vec<Request>, BatchMode),
+    Cancel(RequestId),
     ContainerStats(Container),
     Containers,
+    CoreDumpExport(Container, String),
+    CoreDumpRemove(Container, String),
+    CoreDumps(Container),
+    DataExport(Container),
+    DataReset(Container),
+    DataImport(Container, u64),
+    Fs {
+        container: Container,
+        path: PathBuf,
+        op: FsOp,
+    },
+    Group(Selector, GroupOp),
     Ident,
+    Inspect(Container),
     Install(RepositoryId, u64),
+    InstallCancel(InstallId),
+    Installs,
     Kill(Container, i32),
     Mount(Vec<Container>),
+    MountMatching(String),
+    Pause(Container),
+    PolicyInput(String, i64),
+    Ps(Container),
     Repositories,
+    Resume(Container),
+    Services(Option<Name>),
     Shutdown,
+    Signal(Container, i32, Option<String>),
     Start(
         Container,
         Vec<NonNulString>,
         HashMap<NonNulString, NonNulString>,
     ),
-    TokenCreate(Vec<u8>, Vec<u8>),
+    StartDryRun(Container),
+    StartTemplate(Container, HashMap<Name, NonNulString>),
+    Stdin(Container, Vec<u8>),
+    SystemInfo,
+    SystemResume,
+    SystemSuspend(Vec<Container>),
+    TokenCreate(Vec<u8>, Vec<u8>, TokenScope, Option<u64>),
     TokenVerify(Token, Vec<u8>, Vec<u8>),
     Umount(Vec<Container>),
-    Uninstall(Container),
+    UmountMatching(String),
+    Uninstall {
+        container: Container,
+        /// Uninstall containers that are started or mounted and resources of installed
+        /// containers
+        #[serde(default)]
+        force: bool,
+        /// Remove the persistent data of the container
+        #[serde(default)]
+        wipe: bool,
+    },
+}
+
+/// Filesystem operation on a mounted container. Paths are absolute paths as seen
+/// from within the container. Paths within a `persist` mount refer to the persist
+/// directory of the container. Symlinks are listed but not followed.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FsOp {
+    /// List the entries of a directory
+    List,
+    /// Read `length` bytes of a file starting at `offset`. The runtime limits
+    /// the length of a single read. Read until `eof` to get the full content.
+    Read {
+        /// Offset in bytes
+        offset: u64,
+        /// Maximum number of bytes to read
+        length: u64,
+    },
+}
+
+/// Filesystem operation result
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FsResponse {
+    /// Directory listing
+    List(Vec<FsEntry>),
+    /// Chunk of a file
+    Read {
+        /// File content starting at the requested offset
+        data: Vec<u8>,
+        /// The end of the file is reached
+        eof: bool,
+    },
+}
+
+/// Directory entry
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FsEntry {
+    /// File name
+    pub name: String,
+    /// Entry type
+    pub kind: FsEntryKind,
+    /// Size in bytes
+    pub size: u64,
+    /// Permission bits
+    pub mode: u32,
+}
+
+/// Directory entry type
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum FsEntryKind {
+    File,
+    Directory,
+    Symlink,
+    Other,
 }
 
 /// Token
 #[derive(Clone, Eq, PartialEq, Debug)]
-pub struct Token([u8; 40]);
+pub struct Token(Vec<u8>);
 
 impl AsRef<[u8]> for Token {
     fn as_ref(&self) -> &[u8] {
@@ -140,23 +394,39 @@ impl AsRef<[u8]> for Token {
     }
 }
 
-impl From<Token> for [u8; 40] {
+impl From<Token> for Vec<u8> {
     fn from(value: Token) -> Self {
         value.0
     }
 }
 
-impl From<[u8; 40]> for Token {
-    fn from(value: [u8; 40]) -> Self {
+impl From<Vec<u8>> for Token {
+    fn from(value: Vec<u8>) -> Self {
         Self(value)
     }
 }
 
+/// Scope claims of a token. The runtime does not enforce the scope on the
+/// token holder. The verifying side decides what the holder is allowed to do.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TokenScope {
+    /// Permissions granted to the token holder. The creator of a token cannot
+    /// grant permissions it does not have.
+    #[serde(default)]
+    pub permissions: HashSet<ConsolePermission>,
+    /// Containers the token holder may act on. Any container if empty. Requests on
+    /// other containers and requests that select containers by a pattern or selector
+    /// are refused on connections authenticated with the token.
+    #[serde(default)]
+    pub containers: HashSet<Container>,
+}
+
 /// Token verification result
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum VerificationResult {
-    /// Verification succeeded
-    Ok,
+    /// Verification succeeded. Contains the scope of the token
+    Ok(TokenScope),
     /// Verification failed
     Invalid,
     /// Token is expired
@@ -179,6 +449,75 @@ pub struct ContainerData {
     pub process: Option<Process>,
     /// Mount state
     pub mounted: bool,
+    /// Restarts and last exits of the container
+    pub history: ExitHistory,
+}
+
+/// Container details that are available without mounting the container
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Inspect {
+    /// Container name and version
+    pub container: Container,
+    /// Repository in which the container is installed. The repository with the highest
+    /// priority if the container is present in several repositories.
+    pub repository: RepositoryId,
+    /// Repositories with a lower priority that contain the container as well
+    pub shadowed: Vec<RepositoryId>,
+    /// Container manifest
+    pub manifest: Manifest,
+    /// Software bill of materials if contained in the NPK
+    pub sbom: Option<Sbom>,
+    /// Hex encoded public key that verified the NPK. None for unverified repositories.
+    pub key: Option<String>,
+    /// Phases of the last successful start
+    pub start_timings: Option<StartTimings>,
+    /// Restarts and last exits of the container
+    pub history: ExitHistory,
+}
+
+/// Restarts and last exits of a container since it was installed
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ExitHistory {
+    /// Number of restarts by the runtime
+    pub restarts: u32,
+    /// Last exits ordered from the oldest to the most recent exit
+    pub exits: Vec<ContainerExit>,
+}
+
+/// Exit of a container process
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ContainerExit {
+    /// Time of the exit in seconds since the epoch
+    pub time: u64,
+    /// Process uptime in nanoseconds
+    pub uptime: u64,
+    /// Exit status
+    pub status: ExitStatus,
+    /// The process was killed by the oom killer
+    pub oom: bool,
+}
+
+/// Duration of the phases of a container start in nanoseconds
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct StartTimings {
+    /// Checks of the manifest and resolution of the resources
+    pub manifest: u64,
+    /// Mount of the container and its resources
+    pub mount: u64,
+    /// Environment, console, persist, secret and root overlay setup
+    pub prepare: u64,
+    /// Compilation of the seccomp filter
+    pub seccomp: u64,
+    /// Mount setup and clone of the container init by the forker
+    pub clone: u64,
+    /// Cgroup, io and debug setup and exec of the application
+    pub exec: u64,
+    /// Total duration of the start
+    pub total: u64,
 }
 
 /// Process information
@@ -189,6 +528,85 @@ pub struct Process {
     pub pid: Pid,
     /// Process uptime in nanoseconds
     pub uptime: u64,
+    /// Process state
+    pub state: ProcessState,
+}
+
+/// Process of a running container as listed in `/proc`
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ProcessInfo {
+    /// Process id in the pid namespace of the runtime
+    pub pid: Pid,
+    /// Process id of the parent process
+    pub ppid: Pid,
+    /// Command name truncated to 15 characters
+    pub name: String,
+    /// Resident set size in bytes
+    pub rss: u64,
+}
+
+/// Service of a started container
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Service {
+    /// Container that offers the service
+    pub container: Container,
+    /// Service name
+    pub name: Name,
+    /// Port the service listens on
+    pub port: u16,
+    /// Transport protocol
+    pub protocol: Protocol,
+    /// The container has its own network namespace. The service is not reachable
+    /// on the addresses of the host.
+    pub namespace: bool,
+}
+
+/// Check of a start dry run
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct StartCheck {
+    /// Checked part of the start
+    pub kind: StartCheckKind,
+    /// Reason the start fails. `None` if the check passed.
+    pub error: Option<String>,
+}
+
+/// Part of a start checked by a dry run
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StartCheckKind {
+    /// Signature and hashes of the npk
+    Npk,
+    /// Template parameters and env variables with their defaults
+    Parameters,
+    /// Init of the container. Resource containers cannot be started.
+    Init,
+    /// Supplementary groups and the owner of a shared persist directory
+    Users,
+    /// Security labels and resource limits allowed by the runtime configuration
+    Config,
+    /// Network interfaces and filters
+    Network,
+    /// Installed containers of links, shared memory segments and `after`
+    Dependencies,
+    /// Resource containers of the mounts
+    Resources,
+    /// Bind mount sources, secrets and libraries mounted into the container
+    Mounts,
+    /// Seccomp filter
+    Seccomp,
+}
+
+/// Process state
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessState {
+    /// Process is running
+    Running,
+    /// Process is frozen by the cgroup freezer
+    Paused,
 }
 
 /// Result of a mount operation
@@ -209,6 +627,42 @@ pub enum UmountResult {
     Error { container: Container, error: Error },
 }
 
+/// Handling of failed requests in a batch. Batches contain start, kill, mount and
+/// umount requests.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    /// Check the requests before applying any. Stop at the first failed request and
+    /// roll back the starts, mounts and umounts applied so far. Sent signals are not
+    /// rolled back.
+    AllOrNothing,
+    /// Apply all requests regardless of failures
+    BestEffort,
+}
+
+/// Operation on all containers matching a label selector
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupOp {
+    /// Start the containers that are not started with their default arguments and env
+    Start,
+    /// Send a signal to the started containers
+    Kill(i32),
+    /// Mount the containers
+    Mount,
+    /// Umount the containers
+    Umount,
+}
+
+/// Result of a group operation on a single container
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum GroupResult {
+    Ok { container: Container },
+    Error { container: Container, error: Error },
+}
+
 /// Response
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -216,17 +670,118 @@ pub enum UmountResult {
 pub enum Response {
     Ok,
     Error(Error),
+    Batch(Vec<Response>),
     ContainerStats(Container, ContainerStats),
     Containers(Vec<ContainerData>),
+    CoreDumpExport(u64),
+    CoreDumps(Vec<CoreDump>),
+    DataExport(u64),
+    Fs(FsResponse),
+    Group(Vec<GroupResult>),
     Ident(Container),
+    Inspect(Box<Inspect>),
     Install(Container),
+    Installs(Vec<InstallStatus>),
     Mount(Vec<MountResult>),
+    Ps(Vec<ProcessInfo>),
     Repositories(HashSet<RepositoryId>),
+    Services(Vec<Service>),
+    StartDryRun(Vec<StartCheck>),
+    SystemInfo(SystemInfo),
     Token(Token),
     TokenVerification(VerificationResult),
     Umount(Vec<UmountResult>),
 }
 
+/// Install of a console connection
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct InstallStatus {
+    /// Install id
+    pub id: InstallId,
+    /// Target repository
+    pub repository: RepositoryId,
+    /// Bytes transferred
+    pub bytes: u64,
+    /// Size of the npk
+    pub total: u64,
+    /// Install state
+    pub state: InstallState,
+}
+
+/// Install state
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallState {
+    /// Waiting for the preceding installs
+    Queued,
+    /// Npk is transferred into the repository
+    Transferring,
+}
+
+/// Host and runtime information
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SystemInfo {
+    /// Kernel release
+    pub kernel: String,
+    /// Runtime version
+    pub version: String,
+    /// Commit the runtime is built from if known
+    pub commit: Option<String>,
+    /// Total memory in bytes
+    pub memory_total: u64,
+    /// Free memory in bytes
+    pub memory_free: u64,
+    /// Load average over 1, 5 and 15 minutes in hundredths
+    pub load_average: [u32; 3],
+    /// Usage of the filesystem of the run directory
+    pub run_dir: DiskUsage,
+    /// Usage of the filesystem of the data directory
+    pub data_dir: DiskUsage,
+    /// Usage of the filesystems of the directory based repositories
+    pub repositories: HashMap<RepositoryId, DiskUsage>,
+    /// Queues of the event loop
+    pub event_queues: EventQueues,
+}
+
+/// Queues of the event loop. Console requests and container lifecycle events
+/// are processed before cgroup events and cgroup events before kernel messages.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct EventQueues {
+    /// Console requests, container lifecycle and repository events
+    pub lifecycle: QueueDepth,
+    /// Cgroup events
+    pub stats: QueueDepth,
+    /// Kernel messages
+    pub logs: QueueDepth,
+}
+
+/// Fill level of a queue
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct QueueDepth {
+    /// Maximum number of queued events
+    pub capacity: usize,
+    /// Number of queued events
+    pub depth: usize,
+    /// Maximum number of queued events since the start of the runtime
+    pub max_depth: usize,
+}
+
+/// Filesystem usage of a directory
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DiskUsage {
+    /// Directory
+    pub path: PathBuf,
+    /// Size of the filesystem in bytes
+    pub total: u64,
+    /// Bytes available to unprivileged users
+    pub available: u64,
+}
+
 /// Container exit status
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -240,9 +795,50 @@ pub enum ExitStatus {
     Signalled {
         /// Signal
         signal: Signal,
+        /// Origin of the signal
+        #[serde(default)]
+        cause: SignalCause,
     },
 }
 
+/// Origin of the signal that terminated a container process
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalCause {
+    /// Killed by the oom killer of the memory cgroup
+    Oom,
+    /// Killed by the seccomp filter for a syscall that is not allowed
+    Seccomp {
+        /// Number of the syscall if reported by the kernel
+        syscall: Option<u32>,
+    },
+    /// Signal sent by the runtime e.g. for a kill request or the shutdown
+    Runtime,
+    /// Signal sent by another process or raised by the process itself e.g. on a
+    /// segmentation fault
+    External,
+}
+
+impl Default for SignalCause {
+    fn default() -> SignalCause {
+        SignalCause::External
+    }
+}
+
+/// Container operation that is limited by a timeout
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Mount of the root filesystem
+    Mount,
+    /// Umount of the root filesystem
+    Umount,
+    /// Creation and exec of the container process
+    Start,
+    /// Exit of a killed container and the teardown of its process
+    Stop,
+}
+
 /// API error
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -257,12 +853,20 @@ pub enum Error {
         /// Required permission that was denied
         required: ConsolePermission,
     },
+    /// The container is not in the token scope of the connection. None for requests
+    /// that select containers by a pattern or selector.
+    OutOfScope {
+        container: Option<Container>,
+    },
     DuplicateContainer {
         container: Container,
     },
     InvalidContainer {
         container: Container,
     },
+    ContainerLoading {
+        container: Container,
+    },
     InvalidArguments {
         cause: String,
     },
@@ -290,16 +894,95 @@ pub enum Error {
     StopContainerNotStarted {
         container: Container,
     },
+    PauseContainerPaused {
+        container: Container,
+    },
+    ResumeContainerNotPaused {
+        container: Container,
+    },
+    SystemSuspended,
+    SystemNotSuspended,
+    Fs {
+        container: Container,
+        error: String,
+    },
+    Data {
+        container: Container,
+        error: String,
+    },
+    CoreDump {
+        container: Container,
+        error: String,
+    },
+    Attach {
+        container: Container,
+        error: String,
+    },
+    Stdin {
+        container: Container,
+        error: String,
+    },
     InvalidRepository {
         repository: RepositoryId,
     },
+    RepositoryReadOnly {
+        repository: RepositoryId,
+    },
     InstallDuplicate {
         container: Container,
     },
+    /// The container is used and was not uninstalled
+    UninstallInUse {
+        container: Container,
+        /// The container is mounted
+        mounted: bool,
+        /// Started containers that use the container
+        started: Vec<Container>,
+        /// Installed containers that require the container as resource
+        dependents: Vec<Container>,
+    },
+    InsufficientSpace {
+        needed: u64,
+        available: u64,
+    },
+    Cancelled {
+        id: RequestId,
+    },
+    MissingSecurityLabel {
+        container: Container,
+        module: String,
+    },
+    RLimitExceeded {
+        container: Container,
+        resource: String,
+        max: u64,
+    },
+    /// The container requires another architecture, kernel or runtime version
+    Incompatible {
+        container: Container,
+        /// Requirement e.g. `kernel`
+        requirement: String,
+        /// Required value e.g. `>=5.10`
+        required: String,
+        /// Value of the system
+        found: String,
+    },
+    /// The request of an all or nothing batch was not applied or rolled back
+    BatchAborted {
+        /// Index of the failed request within the batch
+        failed: usize,
+    },
     CriticalContainer {
         container: Container,
         status: ExitStatus,
     },
+    /// An operation on the container did not complete within its configured timeout
+    Timeout {
+        container: Container,
+        operation: Operation,
+        /// Timeout in milliseconds
+        timeout: u64,
+    },
     Unexpected {
         module: String,
         error: String,
@@ -319,27 +1002,20 @@ impl<'de> Deserialize<'de> for Token {
         impl<'de> Visitor<'de> for TokenVisitor {
             type Value = Token;
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("a 40 byte sequence")
+                formatter.write_str("a byte sequence")
             }
 
             fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
-                if v.len() != 40 {
-                    return Err(serde::de::Error::custom("token length is 40 bytes"));
-                }
-                Ok(Token(v.try_into().map_err(|_| {
-                    serde::de::Error::custom("token is not 40 bytes")
-                })?))
+                Ok(Token(v.to_vec()))
             }
 
             fn visit_seq<A: serde::de::SeqAccess<'de>>(
                 self,
                 mut seq: A,
             ) -> Result<Self::Value, A::Error> {
-                let mut v = [0u8; 40];
-                for b in &mut v {
-                    *b = seq
-                        .next_element()?
-                        .ok_or_else(|| serde::de::Error::custom("token is not 40 bytes"))?;
+                let mut v = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+                while let Some(b) = seq.next_element()? {
+                    v.push(b);
                 }
                 Ok(Token(v))
             }
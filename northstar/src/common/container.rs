@@ -12,7 +12,8 @@ use super::{
     version::Version,
 };
 
-/// Container identification
+/// Container identification. Named instances of a container share the npk of the
+/// container and are identified by `name:version@instance`.
 #[derive(Clone, Eq, PartialOrd, Ord, PartialEq, Debug, Hash, JsonSchema)]
 pub struct Container {
     inner: Arc<Inner>,
@@ -22,7 +23,30 @@ impl Container {
     /// Construct a new container
     pub fn new(name: Name, version: Version) -> Container {
         Container {
-            inner: Arc::new(Inner { name, version }),
+            inner: Arc::new(Inner {
+                name,
+                version,
+                instance: None,
+            }),
+        }
+    }
+
+    /// Construct the named `instance` of this container
+    pub fn with_instance(&self, instance: Name) -> Container {
+        Container {
+            inner: Arc::new(Inner {
+                name: self.inner.name.clone(),
+                version: self.inner.version.clone(),
+                instance: Some(instance),
+            }),
+        }
+    }
+
+    /// Construct the container without an instance name
+    pub fn without_instance(&self) -> Container {
+        match self.inner.instance {
+            Some(_) => Container::new(self.inner.name.clone(), self.inner.version.clone()),
+            None => self.clone(),
         }
     }
 
@@ -35,6 +59,11 @@ impl Container {
     pub fn version(&self) -> &Version {
         &self.inner.version
     }
+
+    /// Instance name
+    pub fn instance(&self) -> Option<&Name> {
+        self.inner.instance.as_ref()
+    }
 }
 
 /// Container error
@@ -49,11 +78,17 @@ pub enum Error {
     MissingVersion,
     #[error("invalid container version")]
     InvalidVersion,
+    #[error("invalid container instance")]
+    InvalidInstance(NameError),
 }
 
 impl Display for Container {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}", self.inner.name, self.inner.version,)
+        write!(f, "{}:{}", self.inner.name, self.inner.version)?;
+        if let Some(instance) = &self.inner.instance {
+            write!(f, "@{}", instance)?;
+        }
+        Ok(())
     }
 }
 
@@ -75,8 +110,19 @@ impl TryFrom<&str> for Container {
             .try_into()
             .map_err(Error::InvalidName)?;
         let version = split.next().ok_or(Error::MissingVersion)?;
+        let (version, instance) = match version.split_once('@') {
+            Some((version, instance)) => (version, Some(instance)),
+            None => (version, None),
+        };
         let version = Version::parse(version).map_err(|_| Error::InvalidVersion)?;
-        Ok(Container::new(name, version))
+        let container = Container::new(name, version);
+        match instance {
+            Some(instance) => {
+                let instance = instance.try_into().map_err(Error::InvalidInstance)?;
+                Ok(container.with_instance(instance))
+            }
+            None => Ok(container),
+        }
     }
 }
 
@@ -103,7 +149,7 @@ impl Serialize for Container {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&format!("{}:{}", self.inner.name, self.inner.version))
+        serializer.serialize_str(&self.to_string())
     }
 }
 
@@ -121,6 +167,7 @@ impl<'de> Deserialize<'de> for Container {
 struct Inner {
     name: Name,
     version: Version,
+    instance: Option<Name>,
 }
 
 #[test]
@@ -132,6 +179,24 @@ fn try_from() {
     );
 }
 
+#[test]
+#[allow(clippy::unwrap_used)]
+fn instance() {
+    let container = Container::try_from("test:0.0.1@left").unwrap();
+    assert_eq!(
+        container.instance().map(ToString::to_string).as_deref(),
+        Some("left")
+    );
+    assert_eq!(container.to_string(), "test:0.0.1@left");
+    assert_eq!(
+        container.without_instance(),
+        "test:0.0.1".try_into().unwrap()
+    );
+    assert_ne!(container, container.without_instance());
+    assert!(Container::try_from("test:0.0.1@").is_err());
+    assert!(Container::try_from("test:0.0.1@le%ft").is_err());
+}
+
 #[test]
 fn invalid_name() {
     assert!(Container::try_from("test\0:0.0.1").is_err());
@@ -4,6 +4,9 @@ pub mod container;
 /// Container name
 pub mod name;
 
+/// Container label selector
+pub mod selector;
+
 /// String without null characters
 pub mod non_nul_string;
 
@@ -0,0 +1,223 @@
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, SchemaObject, StringValidation},
+    JsonSchema,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{collections::HashMap, fmt, str::FromStr};
+use thiserror::Error;
+
+/// Maximum length of a label key or value
+pub const MAX_LENGTH: usize = 128;
+
+/// Check whether `key` is a valid label key: ('0'..='9' | 'A'..='Z' | 'a'..='z' | '.' | '_' | '-' | '/')+
+pub fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_LENGTH
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+}
+
+/// Check whether `value` is a valid label value: ('0'..='9' | 'A'..='Z' | 'a'..='z' | '.' | '_' | '-')*
+pub fn is_value(value: &str) -> bool {
+    value.len() <= MAX_LENGTH
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Invalid selector
+#[derive(Error, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SelectorError {
+    /// Selector without requirements
+    #[error("selector cannot be empty")]
+    Empty,
+    /// Invalid label key
+    #[error("invalid label key \"{0}\"")]
+    InvalidKey(String),
+    /// Invalid label value
+    #[error("invalid label value \"{0}\"")]
+    InvalidValue(String),
+}
+
+/// Requirement on the labels of a container
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+enum Requirement {
+    /// The label `key` has the value
+    Equal(String, String),
+    /// The label `key` is missing or has another value
+    NotEqual(String, String),
+    /// The label `key` is set
+    Exists(String),
+    /// The label `key` is missing
+    Missing(String),
+}
+
+/// Label selector. A selector is a comma separated list of requirements that are
+/// all met by the labels of a matching container: `key=value`, `key!=value`,
+/// `key` (the label is set) and `!key` (the label is missing) e.g. `group=hmi,!debug`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Selector(Vec<Requirement>);
+
+impl Selector {
+    /// Parse a selector
+    pub fn parse(selector: &str) -> Result<Selector, SelectorError> {
+        let key = |key: &str| {
+            let key = key.trim();
+            if is_key(key) {
+                Ok(key.to_string())
+            } else {
+                Err(SelectorError::InvalidKey(key.to_string()))
+            }
+        };
+        let value = |value: &str| {
+            let value = value.trim();
+            if is_value(value) {
+                Ok(value.to_string())
+            } else {
+                Err(SelectorError::InvalidValue(value.to_string()))
+            }
+        };
+
+        if selector.trim().is_empty() {
+            return Err(SelectorError::Empty);
+        }
+
+        selector
+            .split(',')
+            .map(|requirement| {
+                if let Some((k, v)) = requirement.split_once("!=") {
+                    Ok(Requirement::NotEqual(key(k)?, value(v)?))
+                } else if let Some((k, v)) = requirement.split_once('=') {
+                    Ok(Requirement::Equal(key(k)?, value(v)?))
+                } else if let Some(k) = requirement.trim().strip_prefix('!') {
+                    Ok(Requirement::Missing(key(k)?))
+                } else {
+                    Ok(Requirement::Exists(key(requirement)?))
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Selector)
+    }
+
+    /// Check whether `labels` meet all requirements of this selector
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.0.iter().all(|requirement| match requirement {
+            Requirement::Equal(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEqual(key, value) => labels.get(key) != Some(value),
+            Requirement::Exists(key) => labels.contains_key(key),
+            Requirement::Missing(key) => !labels.contains_key(key),
+        })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, requirement) in self.0.iter().enumerate() {
+            if n > 0 {
+                write!(f, ",")?;
+            }
+            match requirement {
+                Requirement::Equal(key, value) => write!(f, "{}={}", key, value)?,
+                Requirement::NotEqual(key, value) => write!(f, "{}!={}", key, value)?,
+                Requirement::Exists(key) => write!(f, "{}", key)?,
+                Requirement::Missing(key) => write!(f, "!{}", key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Selector::parse(s)
+    }
+}
+
+impl TryFrom<&str> for Selector {
+    type Error = SelectorError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Selector::parse(value)
+    }
+}
+
+impl Serialize for Selector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Selector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Selector::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for Selector {
+    fn schema_name() -> String {
+        "Selector".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> schemars::schema::Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                min_length: Some(1),
+                max_length: None,
+                pattern: None,
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn parse() {
+    let selector = Selector::parse("group=hmi, tier!=debug,display,!test").unwrap();
+    assert_eq!(selector.to_string(), "group=hmi,tier!=debug,display,!test");
+    assert_eq!(Selector::parse(""), Err(SelectorError::Empty));
+    assert_eq!(
+        Selector::parse("group=hmi,"),
+        Err(SelectorError::InvalidKey("".into()))
+    );
+    assert_eq!(
+        Selector::parse("gr%up=hmi"),
+        Err(SelectorError::InvalidKey("gr%up".into()))
+    );
+    assert_eq!(
+        Selector::parse("group=h/mi"),
+        Err(SelectorError::InvalidValue("h/mi".into()))
+    );
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn matches() {
+    let labels = [("group", "hmi"), ("display", "")]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>();
+    assert!(Selector::parse("group=hmi").unwrap().matches(&labels));
+    assert!(Selector::parse("group=hmi,display")
+        .unwrap()
+        .matches(&labels));
+    assert!(Selector::parse("group!=telemetry,!debug")
+        .unwrap()
+        .matches(&labels));
+    assert!(!Selector::parse("group=telemetry").unwrap().matches(&labels));
+    assert!(!Selector::parse("!display").unwrap().matches(&labels));
+    assert!(!Selector::parse("tier").unwrap().matches(&labels));
+}
@@ -91,6 +91,12 @@ impl VerityHeader {
             let hash_block_size = src.read_u32::<LittleEndian>()?;
             let data_blocks = src.read_u64::<LittleEndian>()?;
             let salt_size = src.read_u16::<LittleEndian>()?;
+            if salt_size as usize > 256 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid salt size {}", salt_size),
+                ));
+            }
             io::copy(&mut src.take(6), &mut io::sink())?; // skip padding
             let mut salt = [0u8; 256];
             src.read_exact(&mut salt)?;
@@ -149,14 +155,74 @@ impl VerityHeader {
 /// <https://gitlab.com/cryptsetup/cryptsetup/-/wikis/DMVerity#hash-tree>
 /// to the given file.
 pub fn append_dm_verity_block(fsimg: &Path, fsimg_size: u64) -> Result<Sha256Digest, Error> {
+    let mut uuid = [0u8; 16];
+    uuid.copy_from_slice(
+        hex::decode(Uuid::new_v4().to_string().replace('-', ""))
+            .map_err(|_e| Error::Uuid)?
+            .as_slice(),
+    );
+    append(fsimg, fsimg_size, &generate_salt(), &uuid)
+}
+
+/// Generate and append a dm-verity superblock and hash tree like `append_dm_verity_block`
+/// but derive the salt and uuid from the content of `fsimg` instead of choosing them
+/// randomly. The result is identical for identical images.
+pub fn append_dm_verity_block_reproducible(
+    fsimg: &Path,
+    fsimg_size: u64,
+) -> Result<Sha256Digest, Error> {
+    let file = std::fs::File::open(&fsimg).map_err(|e| Error::Os {
+        context: format!("Cannot open '{}'", &fsimg.display()),
+        error: e,
+    })?;
+    let mut sha256 = Sha256::new();
+    io::copy(&mut file.take(fsimg_size), &mut sha256).map_err(|e| Error::Os {
+        context: format!("Cannot read '{}'", &fsimg.display()),
+        error: e,
+    })?;
+    let salt: Salt = sha256.finalize().into();
+    let mut uuid = [0u8; 16];
+    uuid.copy_from_slice(&Sha256::digest(salt)[..16]);
+    append(fsimg, fsimg_size, &salt, &uuid)
+}
+
+fn append(
+    fsimg: &Path,
+    fsimg_size: u64,
+    salt: &Salt,
+    uuid: &[u8; 16],
+) -> Result<Sha256Digest, Error> {
+    let file = std::fs::File::open(&fsimg).map_err(|e| Error::Os {
+        context: format!("Cannot open '{}'", &fsimg.display()),
+        error: e,
+    })?;
     let (level_offsets, tree_size) =
         calculate_hash_tree_level_offsets(fsimg_size as usize, BLOCK_SIZE, SHA256_SIZE as usize);
-    let (salt, root_hash, hash_tree) =
-        generate_hash_tree(fsimg, fsimg_size, &level_offsets, tree_size)?;
-    append_superblock_and_hashtree(fsimg, fsimg_size, &salt, &hash_tree)?;
+    let (root_hash, hash_tree) =
+        generate_hash_tree(&file, 0, fsimg_size, &level_offsets, tree_size, salt)?;
+    append_superblock_and_hashtree(fsimg, fsimg_size, salt, uuid, &hash_tree)?;
     Ok(root_hash)
 }
 
+/// Calculate the dm-verity root hash of the `size` bytes at `offset` in `file`. This is
+/// the hash the kernel verifies when the image is mounted via a verity device.
+#[cfg(feature = "runtime")]
+pub fn root_hash(
+    file: &std::fs::File,
+    offset: u64,
+    size: u64,
+    salt: &[u8],
+) -> Result<Sha256Digest, Error> {
+    let salt: &Salt = salt
+        .try_into()
+        .map_err(|_| Error::HashTree(format!("invalid salt size {}", salt.len())))?;
+    let (level_offsets, tree_size) =
+        calculate_hash_tree_level_offsets(size as usize, BLOCK_SIZE, SHA256_SIZE as usize);
+    let (root_hash, _) = generate_hash_tree(file, offset, size, &level_offsets, tree_size, salt)?;
+    Ok(root_hash)
+}
+
+// "1. Choose a random salt (hexadecimal encoding)."
 fn generate_salt() -> Salt {
     let mut salt: Salt = [0u8; SHA256_SIZE];
     OsRng.fill_bytes(&mut salt);
@@ -196,18 +262,16 @@ fn calculate_hash_tree_level_offsets(
 }
 
 fn generate_hash_tree(
-    fsimg: &Path,
+    mut fsimg: &std::fs::File,
+    image_offset: u64,
     image_size: u64,
     level_offsets: &[usize],
     tree_size: usize,
-) -> Result<(Salt, Sha256Digest, Vec<u8>), Error> {
+    salt: &Salt,
+) -> Result<(Sha256Digest, Vec<u8>), Error> {
     // For a description of the overall hash tree generation logic see
     // https://source.android.com/security/verifiedboot/dm-verity#hash-tree
 
-    let mut fsimg = &std::fs::File::open(&fsimg).map_err(|e| Error::Os {
-        context: format!("Cannot open '{}'", &fsimg.display()),
-        error: e,
-    })?;
     let mut hashes: Vec<[u8; SHA256_SIZE]> = vec![];
     let mut level_num = 0;
     let mut level_size = image_size;
@@ -220,9 +284,6 @@ fn generate_hash_tree(
         ));
     }
 
-    // "1. Choose a random salt (hexadecimal encoding)."
-    let salt = generate_salt();
-
     // "To form the hash, the system image is split at layer 0 into 4k blocks, each assigned a SHA256 hash.
     // Layer 1 is formed by joining only those SHA256 hashes into 4k blocks, resulting in a much smaller image.
     // Layer 2 is formed identically, with the SHA256 hashes of Layer 1.
@@ -244,10 +305,12 @@ fn generate_hash_tree(
                 // hash block of original file
                 let offset = level_size - rem_size;
                 let mut data = vec![0_u8; BLOCK_SIZE];
-                fsimg.seek(Start(offset)).map_err(|e| Error::Os {
-                    context: "failed to seek in fs-image".to_string(),
-                    error: e,
-                })?;
+                fsimg
+                    .seek(Start(image_offset + offset))
+                    .map_err(|e| Error::Os {
+                        context: "failed to seek in fs-image".to_string(),
+                        error: e,
+                    })?;
                 fsimg.read_exact(&mut data).map_err(|e| Error::Os {
                     context: "failed to read from fs-image".to_string(),
                     error: e,
@@ -285,13 +348,14 @@ fn generate_hash_tree(
     // "The result of this is a single hash, which is your root hash.
     // This and your salt are used during the construction of your dm-verity mapping table."
     let root_hash = hashes[0];
-    Ok((salt, root_hash, hash_tree))
+    Ok((root_hash, hash_tree))
 }
 
 fn append_superblock_and_hashtree(
     fsimg: &Path,
     fsimg_size: u64,
     salt: &Salt,
+    uuid: &[u8; 16],
     hash_tree: &[u8],
 ) -> Result<(), Error> {
     let mut fsimg = std::fs::OpenOptions::new()
@@ -302,15 +366,9 @@ fn append_superblock_and_hashtree(
             context: format!("Cannot open '{}'", &fsimg.display()),
             error: e,
         })?;
-    let mut uuid = [0u8; 16];
-    uuid.copy_from_slice(
-        hex::decode(Uuid::new_v4().to_string().replace('-', ""))
-            .map_err(|_e| Error::Uuid)?
-            .as_slice(),
-    );
     assert_eq!(fsimg_size % BLOCK_SIZE as u64, 0);
     let data_blocks = fsimg_size / BLOCK_SIZE as u64;
-    let header = VerityHeader::new(&uuid, data_blocks, SHA256_SIZE as u16, salt).to_bytes();
+    let header = VerityHeader::new(uuid, data_blocks, SHA256_SIZE as u16, salt).to_bytes();
     fsimg.write_all(&header).map_err(|e| Error::Os {
         context: "failed to write verity header".to_string(),
         error: e,
@@ -0,0 +1,109 @@
+use super::npk::{read_keypair, Error};
+use crate::common::container::Container;
+use ed25519_dalek::{PublicKey, Signature, Signer};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Index file name
+pub const INDEX_NAME: &str = "index.json";
+/// Index signature file name
+pub const INDEX_SIGNATURE_NAME: &str = "index.json.sig";
+
+/// Signed index of a repository. The index pins the hashes of npks and lists containers
+/// that are revoked and must not be mounted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Index {
+    /// SHA256 of the npk files
+    #[serde(default)]
+    pub npks: BTreeMap<Container, String>,
+    /// Revoked containers
+    #[serde(default)]
+    pub revoked: BTreeSet<Container>,
+}
+
+impl Index {
+    /// Read the index from `dir` and verify the signature with one of `keys`. Return `None`
+    /// if `dir` does not contain an index.
+    pub fn from_dir(dir: &Path, keys: &[PublicKey]) -> Result<Option<Index>, Error> {
+        let index = dir.join(INDEX_NAME);
+        let content = match fs::read(&index) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::io(format!("failed to read {}", index.display()), e)),
+        };
+
+        let signature = dir.join(INDEX_SIGNATURE_NAME);
+        let signature = fs::read_to_string(&signature)
+            .map_err(|e| Error::io(format!("failed to read {}", signature.display()), e))?;
+        let signature = base64::decode(signature.trim()).map_err(|e| {
+            Error::MalformedSignature(format!("failed to decode index signature: {}", e))
+        })?;
+        let signature = Signature::from_bytes(&signature).map_err(|e| {
+            Error::MalformedSignature(format!("failed to parse index signature: {}", e))
+        })?;
+        if !keys
+            .iter()
+            .any(|key| key.verify_strict(&content, &signature).is_ok())
+        {
+            return Err(Error::InvalidSignature(
+                "invalid index signature".to_string(),
+            ));
+        }
+
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|e| Error::Manifest(format!("failed to parse index: {}", e)))
+    }
+
+    /// Write the index and its signature created with the secret `key` to `dir`
+    pub fn write(&self, dir: &Path, key: &Path) -> Result<(), Error> {
+        let keypair = read_keypair(key)?;
+        let content = serde_json::to_vec_pretty(self)
+            .map_err(|e| Error::Manifest(format!("failed to serialize index: {}", e)))?;
+        let signature = base64::encode(keypair.sign(&content).to_bytes());
+
+        let index = dir.join(INDEX_NAME);
+        fs::write(&index, &content)
+            .map_err(|e| Error::io(format!("failed to write {}", index.display()), e))?;
+        let index_signature = dir.join(INDEX_SIGNATURE_NAME);
+        fs::write(&index_signature, signature)
+            .map_err(|e| Error::io(format!("failed to write {}", index_signature.display()), e))
+    }
+
+    /// Check `container` with the npk content `hash` against the index
+    pub fn check(&self, container: &Container, hash: &str) -> Result<(), Error> {
+        if self.revoked.contains(container) {
+            return Err(Error::Revoked(container.to_string()));
+        }
+        match self.npks.get(container) {
+            Some(expected) if expected != hash => Err(Error::InvalidSignature(format!(
+                "invalid hash of {} (expected={} actual={})",
+                container, expected, hash
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hex encoded SHA256 of the content of `reader`
+pub fn hash<R: Read>(mut reader: R) -> Result<String, Error> {
+    let mut sha256 = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = reader
+            .read(&mut buffer)
+            .map_err(|e| Error::io("failed to read npk", e))?;
+        if n == 0 {
+            break;
+        }
+        sha256.update(&buffer[..n]);
+    }
+    Ok(hex::encode(sha256.finalize()))
+}
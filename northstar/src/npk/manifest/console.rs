@@ -15,21 +15,82 @@ use strum_macros::{EnumCount, EnumIter};
 #[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Configuration {
+    /// Name of the listener in audit records and notifications. Ignored for
+    /// container consoles.
+    pub name: Option<String>,
     /// Permissions
     pub permissions: Permissions,
-    /// Limits the number of requests processed per second
+    /// Limits the number of requests processed per second. Listeners apply the
+    /// limit per peer across all connections of the peer
     pub max_requests_per_sec: Option<usize>,
+    /// Maximum number of concurrent connections per peer of a listener
+    pub max_connections_per_peer: Option<usize>,
+    /// Number of consecutive requests exceeding `max_requests_per_sec` after
+    /// which a peer of a listener is banned
+    pub ban_threshold: Option<usize>,
+    /// Ban duration in seconds. Default: 60
+    pub ban_duration: Option<u64>,
     /// Maximum request size in characters
     pub max_request_size: Option<usize>,
     /// Maximum npk size in bytes
     pub max_npk_install_size: Option<u64>,
     /// NPK stream timeout in seconds
     pub npk_stream_timeout: Option<u64>,
+    /// Require connections to authenticate with a token. The permissions of an
+    /// authenticated connection are the permissions of the token scope. Ignored
+    /// for container consoles.
+    pub token_authentication: Option<bool>,
+    /// Permissions granted to processes connecting to a unix listener in addition
+    /// to `permissions`. The processes are identified by their user id and their
+    /// primary and supplementary groups.
+    /// Ignored for tcp listeners, container consoles and token authenticated
+    /// connections.
+    pub peers: Option<Vec<PeerPermissions>>,
+    /// Send an `audit` notification for each permitted request that changes the
+    /// state of the runtime
+    pub audit_notifications: Option<bool>,
+}
+
+/// Permissions of the processes of a user and/or group
+#[skip_serializing_none]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PeerPermissions {
+    /// User id. Matches any user if omitted
+    pub uid: Option<u32>,
+    /// Group id. Matches the primary and the supplementary groups of a process. Matches
+    /// any group if omitted
+    pub gid: Option<u32>,
+    /// Permissions
+    pub permissions: Permissions,
+}
+
+impl PeerPermissions {
+    /// True if the entry applies to a process with `uid`, the primary group `gid` and
+    /// the supplementary `groups`
+    pub fn matches(&self, uid: u32, gid: u32, groups: &[u32]) -> bool {
+        self.uid.map(|u| u == uid).unwrap_or(true)
+            && self
+                .gid
+                .map(|g| g == gid || groups.contains(&g))
+                .unwrap_or(true)
+    }
 }
 
 /// Console features. Matches the api request struct and notifications
 #[derive(
-    Clone, Eq, EnumIter, EnumCount, PartialEq, Debug, Hash, Serialize, Deserialize, JsonSchema,
+    Clone,
+    Eq,
+    EnumIter,
+    EnumCount,
+    PartialEq,
+    PartialOrd,
+    Ord,
+    Debug,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
 )]
 #[serde(rename_all = "snake_case")]
 pub enum Permission {
@@ -41,8 +102,14 @@ pub enum Permission {
     Repositories,
     /// Start a container
     Start,
+    /// Start a container with arguments or environment variables that override the
+    /// manifest
+    StartOverride,
     /// Send a singal to a container
     Kill,
+    /// Send a signal to the application or named processes of a container without
+    /// stopping it
+    Signal,
     /// Install a container
     Install,
     /// Mount a container
@@ -51,14 +118,40 @@ pub enum Permission {
     Umount,
     /// Uninstall a container
     Uninstall,
+    /// Pause and resume a container
+    Pause,
     /// Collect container resource statistics
     ContainerStatistics,
+    /// List and read files of mounted containers
+    Fs,
+    /// Export the persist directory of a container
+    DataExport,
+    /// Import the persist directory of a container
+    DataImport,
+    /// Wipe the persist directory of a container
+    DataReset,
     /// Notifications
     Notifications,
     /// Token creation and verification
     Token,
     /// Identification
     Ident,
+    /// Inspect the manifest and software bill of materials of a container
+    Inspect,
+    /// Suspend and resume the system
+    Suspend,
+    /// Feed the inputs of the throttling policies e.g. a temperature
+    PolicyInput,
+    /// List, export and remove core dumps of a container
+    CoreDumps,
+    /// Attach to the PTY of a container
+    Attach,
+    /// Write to the stdin of containers with `io.stdin`
+    Stdin,
+    /// Query kernel, memory, disk and version information of the host
+    SystemInfo,
+    /// List the services of started containers
+    Services,
 }
 
 #[allow(clippy::unwrap_used)]
@@ -88,6 +181,12 @@ impl Permissions {
     }
 }
 
+impl From<HashSet<Permission>> for Permissions {
+    fn from(permissions: HashSet<Permission>) -> Self {
+        Permissions(permissions)
+    }
+}
+
 impl std::ops::Deref for Permissions {
     type Target = HashSet<Permission>;
 
@@ -155,7 +254,7 @@ impl Serialize for Permissions {
             serializer.serialize_str("full")
         } else {
             let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
-            for e in self.0.iter() {
+            for e in self.0.iter().sorted() {
                 seq.serialize_element(e)?;
             }
             seq.end()
@@ -209,4 +308,27 @@ console:
         assert!(console.permissions.len() == 2);
         Ok(())
     }
+
+    /// Permissions of unix socket peers
+    #[test]
+    fn peers() -> Result<()> {
+        let console = "permissions: [ident]
+peers:
+  - uid: 1000
+    permissions: full
+  - gid: 100
+    permissions: [containers]
+";
+        let console = serde_yaml::from_str::<Configuration>(console)?;
+        let peers = console.peers.unwrap();
+        assert!(peers[0].matches(1000, 0, &[]));
+        assert!(!peers[0].matches(0, 100, &[]));
+        assert!(peers[1].matches(0, 100, &[]));
+        assert!(peers[1].matches(1000, 100, &[]));
+        assert!(!peers[1].matches(1000, 0, &[]));
+        // Supplementary group
+        assert!(peers[1].matches(1000, 0, &[10, 100]));
+        assert!(!peers[1].matches(1000, 0, &[10]));
+        Ok(())
+    }
 }
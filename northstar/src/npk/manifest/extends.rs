@@ -0,0 +1,282 @@
+use super::Error;
+use serde_yaml::{Mapping, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Key of the list of manifests a manifest is based on
+const EXTENDS: &str = "extends";
+/// Key of the mounts. Mount entries are replaced and not merged.
+const MOUNTS: &str = "mounts";
+
+/// Load the manifest at `path` and resolve its `extends` chain into a single document.
+///
+/// `extends` is a path or a list of paths relative to the directory of the manifest
+/// that contains it. The bases are merged in the listed order and the manifest is
+/// merged on top of the result:
+/// * Mappings are merged key by key. The entries of `mounts` are replaced as a whole.
+/// * Sequences and scalars replace the value of the base.
+/// * A `null` value removes the key of the base.
+///
+/// Cycles in the `extends` chain are rejected.
+pub(super) fn resolve(path: &Path) -> Result<Value, Error> {
+    resolve_with(path, &mut Vec::new())
+}
+
+fn resolve_with(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, Error> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| Error::Io(format!("failed to open {}", path.display()), e))?;
+    if stack.contains(&path) {
+        let chain = stack
+            .iter()
+            .chain(std::iter::once(&path))
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        return Err(Error::Invalid(format!("cyclic extends: {}", chain)));
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| Error::Io(format!("failed to read {}", path.display()), e))?;
+    let mut value = serde_yaml::from_str::<Value>(&content)?;
+    let extends = match &mut value {
+        Value::Mapping(mapping) => mapping.remove(&Value::from(EXTENDS)),
+        _ => None,
+    };
+    let bases = match extends {
+        None => Vec::new(),
+        Some(Value::String(base)) => vec![base],
+        Some(Value::Sequence(bases)) => bases
+            .into_iter()
+            .map(|base| match base {
+                Value::String(base) => Ok(base),
+                _ => Err(Error::Invalid(format!(
+                    "invalid {} in {}: expecting a list of paths",
+                    EXTENDS,
+                    path.display()
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(_) => {
+            return Err(Error::Invalid(format!(
+                "invalid {} in {}: expecting a path or a list of paths",
+                EXTENDS,
+                path.display()
+            )))
+        }
+    };
+
+    stack.push(path.clone());
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    let mut result = Value::Mapping(Mapping::new());
+    for base in bases {
+        let base = resolve_with(&dir.join(base), stack)?;
+        result = merge(result, base, false);
+    }
+    stack.pop();
+
+    Ok(merge(result, value, false))
+}
+
+/// Merge `overlay` into `base`. The values of mappings are replaced instead of merged
+/// if `replace` is set.
+fn merge(base: Value, overlay: Value, replace: bool) -> Value {
+    match (base, overlay) {
+        (Value::Mapping(mut base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(&key);
+                    continue;
+                }
+                let value = match base.remove(&key) {
+                    Some(base) if !replace => {
+                        let replace = key.as_str() == Some(MOUNTS);
+                        merge(base, value, replace)
+                    }
+                    _ => value,
+                };
+                base.insert(key, value);
+            }
+            Value::Mapping(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::npk::manifest::{mount::Mount, Manifest};
+    use anyhow::Result;
+    use std::{collections::HashMap, fs, path::Path};
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, name: &str, content: &str) -> Result<()> {
+        fs::write(dir.join(name), content)?;
+        Ok(())
+    }
+
+    #[test]
+    fn extends() -> Result<()> {
+        let dir = TempDir::new()?;
+        write(
+            dir.path(),
+            "base.yaml",
+            "
+uid: 1000
+gid: 1000
+args:
+  - one
+env:
+  BASE: base
+  OVERRIDE: base
+  REMOVE: base
+mounts:
+  /lib:
+    type: bind
+    host: /lib
+  /tmp:
+    type: tmpfs
+    size: 100
+",
+        )?;
+        write(
+            dir.path(),
+            "profile.yaml",
+            "
+extends: base.yaml
+gid: 1001
+",
+        )?;
+        write(
+            dir.path(),
+            "manifest.yaml",
+            "
+extends:
+  - profile.yaml
+name: hello
+version: 0.0.1
+init: /hello
+args:
+  - two
+env:
+  OVERRIDE: manifest
+  REMOVE: ~
+mounts:
+  /tmp:
+    type: tmpfs
+    size: 200
+",
+        )?;
+
+        let manifest = Manifest::from_path(&dir.path().join("manifest.yaml"))?;
+        assert_eq!(manifest.uid, 1000);
+        assert_eq!(manifest.gid, 1001);
+        // Sequences are replaced
+        assert_eq!(manifest.args.len(), 1);
+        assert_eq!(manifest.args[0].to_string(), "two");
+        // Maps are merged
+        let env = manifest
+            .env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(env.len(), 2);
+        assert_eq!(env["BASE"], "base");
+        assert_eq!(env["OVERRIDE"], "manifest");
+        assert_eq!(manifest.mounts.len(), 2);
+        assert!(matches!(
+            manifest.mounts.get(Path::new("/tmp")),
+            Some(Mount::Tmpfs(tmpfs)) if tmpfs.size == 200
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn extends_replaces_mounts() -> Result<()> {
+        let dir = TempDir::new()?;
+        write(
+            dir.path(),
+            "base.yaml",
+            "
+mounts:
+  /data:
+    type: bind
+    host: /data
+    options: rw
+",
+        )?;
+        write(
+            dir.path(),
+            "manifest.yaml",
+            "
+extends: base.yaml
+name: hello
+version: 0.0.1
+init: /hello
+uid: 1000
+gid: 1000
+mounts:
+  /data:
+    type: persist
+",
+        )?;
+        let manifest = Manifest::from_path(&dir.path().join("manifest.yaml"))?;
+        assert!(matches!(
+            manifest.mounts.get(Path::new("/data")),
+            Some(Mount::Persist(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn extends_cycle() -> Result<()> {
+        let dir = TempDir::new()?;
+        write(dir.path(), "a.yaml", "extends: b.yaml")?;
+        write(dir.path(), "b.yaml", "extends: a.yaml")?;
+        write(
+            dir.path(),
+            "manifest.yaml",
+            "
+extends: a.yaml
+name: hello
+version: 0.0.1
+uid: 1000
+gid: 1000
+",
+        )?;
+        assert!(Manifest::from_path(&dir.path().join("manifest.yaml")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn extends_missing_base() -> Result<()> {
+        let dir = TempDir::new()?;
+        write(
+            dir.path(),
+            "manifest.yaml",
+            "
+extends: missing.yaml
+name: hello
+version: 0.0.1
+uid: 1000
+gid: 1000
+",
+        )?;
+        assert!(Manifest::from_path(&dir.path().join("manifest.yaml")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn extends_is_not_part_of_a_manifest() {
+        let manifest = "
+extends: base.yaml
+name: hello
+version: 0.0.1
+uid: 1000
+gid: 1000
+";
+        assert!(manifest.parse::<Manifest>().is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::common::{name::Name, non_nul_string::NonNulString};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Communication with other containers
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Ipc {
+    /// Named pipes shared with other containers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pipes: Vec<Pipe>,
+    /// Directories in which the container binds unix sockets that other containers
+    /// can link
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sockets: Vec<PathBuf>,
+    /// Shared memory segments owned by the container or by other containers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shm: Vec<Shm>,
+    /// Access to the D-Bus through a filtering proxy
+    pub dbus: Option<DBus>,
+}
+
+/// Named pipe created by the runtime. All containers that declare a pipe with the
+/// same name share it. The kernel does not restrict the direction of a named pipe:
+/// `end` declares the intended use.
+/// ```yaml
+/// ipc:
+///   pipes:
+///     - name: sensor
+///       path: /run/sensor
+///       end: write
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Pipe {
+    /// Name of the pipe
+    pub name: Name,
+    /// Path of the pipe within the container
+    pub path: PathBuf,
+    /// End of the pipe used by the container
+    pub end: PipeEnd,
+}
+
+/// Shared memory segment. A segment with a `size` is owned by the container and
+/// is writable. The containers listed in `access` map the segment read only with
+/// an entry that names the owner in `from`.
+/// ```yaml
+/// ipc:
+///   shm:
+///     - name: camera
+///       path: /dev/shm/camera
+///       size: 4M
+///       access:
+///         - hmi
+/// ```
+/// ```yaml
+/// ipc:
+///   shm:
+///     - name: camera
+///       path: /dev/shm/camera
+///       from: camera
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Shm {
+    /// Name of the segment
+    pub name: Name,
+    /// Path of the segment within the container
+    pub path: PathBuf,
+    /// Size of a segment owned by the container in bytes
+    #[serde(
+        default,
+        deserialize_with = "super::mount::deserialize_optional_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub size: Option<u64>,
+    /// Containers that can map a segment owned by the container
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access: Vec<Name>,
+    /// Owner of a segment owned by another container
+    pub from: Option<Name>,
+}
+
+/// Buses proxied into the container. The runtime starts an `xdg-dbus-proxy` for
+/// the container that forwards the messages allowed by the policy of each bus.
+/// The bus sockets are mounted to `/run/dbus` and the bus addresses are passed
+/// in `DBUS_SYSTEM_BUS_ADDRESS` and `DBUS_SESSION_BUS_ADDRESS`.
+/// ```yaml
+/// ipc:
+///   dbus:
+///     system:
+///       talk:
+///         - org.freedesktop.NetworkManager
+///       own:
+///         - com.example.Hmi
+/// ```
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DBus {
+    /// Policy of the system bus
+    pub system: Option<BusPolicy>,
+    /// Policy of the session bus
+    pub session: Option<BusPolicy>,
+}
+
+impl DBus {
+    /// Directory of the bus sockets within the container
+    pub const DIR: &'static str = "/run/dbus";
+}
+
+/// Names and rules the container can use on a bus. See the `xdg-dbus-proxy`
+/// documentation for the format of the names and rules.
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BusPolicy {
+    /// Names the container can own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub own: Vec<NonNulString>,
+    /// Names the container can talk to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub talk: Vec<NonNulString>,
+    /// Names the container can see
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub see: Vec<NonNulString>,
+    /// Method calls the container can make as `NAME=RULE`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub call: Vec<NonNulString>,
+    /// Broadcast signals the container receives as `NAME=RULE`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub broadcast: Vec<NonNulString>,
+}
+
+/// End of a named pipe
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PipeEnd {
+    /// The container reads from the pipe
+    Read,
+    /// The container writes to the pipe
+    Write,
+}
+
+/// Link to a socket directory declared by another container. The directory is
+/// mounted read only to the same path. Only sockets that are declared in the
+/// `ipc` section of the container `from` can be linked.
+/// ```yaml
+/// links:
+///   - from: navigation
+///     socket: /run/navigation
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Link {
+    /// Name of the container that declares the socket
+    pub from: Name,
+    /// Socket directory declared by `from`
+    pub socket: PathBuf,
+}
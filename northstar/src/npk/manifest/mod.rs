@@ -1,6 +1,12 @@
 use crate::{
-    common::{container::Container, name::Name, non_nul_string::NonNulString, version::Version},
-    seccomp::{Seccomp, Selinux, SyscallRule},
+    common::{
+        container::Container,
+        name::Name,
+        non_nul_string::NonNulString,
+        selector,
+        version::{Version, VersionReq},
+    },
+    seccomp::{is_syscall, Seccomp, Selinux, SyscallRule},
 };
 use itertools::Itertools;
 use schemars::JsonSchema;
@@ -10,7 +16,7 @@ use serde_with::{rust::maps_duplicate_key_is_error, skip_serializing_none};
 use std::{
     collections::{HashMap, HashSet},
     io,
-    path::{Component, Component::RootDir, PathBuf},
+    path::{Component, Component::RootDir, Path, PathBuf},
     str::FromStr,
 };
 use thiserror::Error;
@@ -19,8 +25,16 @@ use thiserror::Error;
 pub mod cgroups;
 /// Console configuration
 pub mod console;
+/// Manifest inheritance
+mod extends;
+/// Communication with other containers
+pub mod ipc;
 /// Mount configuration
 pub mod mount;
+/// Network configuration
+pub mod network;
+/// Kernel parameters
+pub mod sysctl;
 
 /// Environment variables used by the runtime and not available to the user.
 const RESERVED_ENV_VARIABLES: &[&str] = &[
@@ -39,6 +53,11 @@ pub struct Manifest {
     pub name: Name,
     /// Container version
     pub version: Version,
+    /// Requirements on the system the container is installed on
+    pub requires: Option<Requires>,
+    /// Free-form labels used to select groups of containers
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
     /// Pass a console fd number in NORTHSTAR_CONSOLE
     pub console: Option<console::Configuration>,
     /// Path to init
@@ -49,6 +68,13 @@ pub struct Manifest {
     /// Environment passed to container
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub env: HashMap<NonNulString, NonNulString>,
+    /// Environment variables that can be overridden with a start request
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
+    pub env_overrides: HashSet<NonNulString>,
     /// UID
     pub uid: u16,
     /// GID
@@ -57,26 +83,92 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     #[serde(deserialize_with = "maps_duplicate_key_is_error::deserialize")]
     pub mounts: HashMap<PathBuf, mount::Mount>,
+    /// Communication with other containers
+    pub ipc: Option<ipc::Ipc>,
+    /// Network configuration
+    pub network: Option<network::Network>,
+    /// Sockets of other containers mounted into this container
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<ipc::Link>,
+    /// Services offered by the container
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<network::Service>,
+    /// Clock offsets
+    pub time: Option<Time>,
+    /// Kernel parameters set in the namespaces of the container
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub sysctl: HashMap<String, NonNulString>,
+    /// Paths hidden from the container e.g. entries of proc. Directories are
+    /// covered with an empty tmpfs, files with /dev/null.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub masked_paths: Vec<PathBuf>,
+    /// Paths remounted read only e.g. entries of proc
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub readonly_paths: Vec<PathBuf>,
     /// Autostart this container upon northstar startup
     pub autostart: Option<Autostart>,
+    /// Autostart this container after the listed containers are started
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
+    pub after: HashSet<Name>,
     /// CGroup configuration
     pub cgroups: Option<cgroups::CGroups>,
+    /// Out of memory configuration
+    pub oom: Option<Oom>,
+    /// Persist directory quota
+    pub persist: Option<Persist>,
+    /// Root filesystem configuration
+    pub root: Option<Root>,
     /// Seccomp configuration
     pub seccomp: Option<Seccomp>,
     /// SELinux configuration
     pub selinux: Option<Selinux>,
+    /// AppArmor profile of the container process
+    pub apparmor_profile: Option<NonNulString>,
     /// Capabilities
-    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
-    pub capabilities: HashSet<Capability>,
+    #[serde(default, skip_serializing_if = "Capabilities::is_empty")]
+    pub capabilities: Capabilities,
+    /// Set `PR_SET_NO_NEW_PRIVS` for the container process. File capabilities and setuid
+    /// bits of executed binaries are ignored if set. Default: true
+    pub no_new_privs: Option<bool>,
     /// String containing group names to give to new container
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub suppl_groups: Vec<NonNulString>,
     /// Resource limits
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub rlimits: HashMap<RLimitResource, RLimitValue>,
+    /// Scheduling policy and priority
+    pub sched: Option<Sched>,
+    /// IO scheduling class and priority
+    pub io_priority: Option<IoPriority>,
     /// IO configuration
     #[serde(default, skip_serializing_if = "is_default")]
     pub io: Io,
+    /// Forwarding of the container output
+    pub log: Option<Log>,
+    /// Containers are stopped in ascending order of their shutdown priority on
+    /// runtime shutdown. Default: 0
+    pub shutdown_priority: Option<u32>,
+    /// Grace period in seconds between SIGTERM and SIGKILL on runtime shutdown.
+    /// Containers without a grace period are killed with SIGKILL.
+    pub shutdown_timeout: Option<u64>,
+    /// System suspend configuration
+    pub suspend: Option<Suspend>,
+    /// Throttle or freeze the container while a throttling policy of the runtime is
+    /// active e.g. on high temperature or low battery
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub deferrable: bool,
+    /// Maximum number of named instances of the container that can be started in
+    /// addition to the container itself
+    pub instances: Option<u32>,
+    /// Template parameters. The values are substituted for `${PARAM:name}` in the
+    /// arguments, the environment and the bind mount sources when the container is
+    /// started.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub parameters: HashMap<Name, Parameter>,
     /// Optional custom data. The runtime doesnt use this.
     pub custom: Option<Value>,
 }
@@ -94,6 +186,17 @@ impl Manifest {
         Ok(manifest)
     }
 
+    /// Read a manifest from `path` and resolve the manifests it `extends`. The bases
+    /// are merged in order with the manifest on top: Mappings are merged key by key
+    /// except the entries of `mounts`, sequences and scalars are replaced and `null`
+    /// removes a key.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let value = extends::resolve(path)?;
+        let manifest: Self = serde_yaml::from_value(value).map_err(Error::SerdeYaml)?;
+        manifest.verify()?;
+        Ok(manifest)
+    }
+
     /// Write the manifest to `writer`
     pub fn to_writer<W: io::Write>(&self, writer: W) -> Result<(), Error> {
         serde_yaml::to_writer(writer, self).map_err(Error::SerdeYaml)
@@ -110,19 +213,101 @@ impl Manifest {
             }
         } else if !self.args.is_empty()
             || !self.env.is_empty()
+            || !self.env_overrides.is_empty()
             || self.autostart.is_some()
+            || !self.after.is_empty()
             || self.cgroups.is_some()
+            || self.oom.is_some()
+            || self.persist.is_some()
+            || self.ipc.is_some()
+            || self.network.is_some()
+            || !self.links.is_empty()
+            || !self.services.is_empty()
+            || self.time.is_some()
+            || !self.sysctl.is_empty()
+            || !self.masked_paths.is_empty()
+            || !self.readonly_paths.is_empty()
+            || self.root.is_some()
             || self.seccomp.is_some()
+            || self.apparmor_profile.is_some()
             || !self.capabilities.is_empty()
+            || self.no_new_privs.is_some()
             || !self.suppl_groups.is_empty()
+            || self.log.is_some()
+            || self.sched.is_some()
+            || self.io_priority.is_some()
+            || self.shutdown_priority.is_some()
+            || self.shutdown_timeout.is_some()
+            || self.suspend.is_some()
+            || self.deferrable
+            || self.instances.is_some()
+            || !self.parameters.is_empty()
         {
             return Err(Error::Invalid(
                 "resource containers must not define any of the following manifest entries:\
-                    args, env, autostart, cgroups, seccomp, capabilities, suppl_groups, io"
+                    args, env, env_overrides, autostart, after, cgroups, oom, persist, ipc, network, links, services, time, sysctl, masked_paths, readonly_paths, root, seccomp, apparmor_profile, capabilities, no_new_privs, suppl_groups, io, log, \
+                    sched, io_priority, shutdown_priority, shutdown_timeout, suspend, deferrable, instances, parameters"
                     .to_string(),
             ));
         }
 
+        if let Some(requires) = &self.requires {
+            if requires.arch.as_ref().map_or(false, |arch| {
+                arch.is_empty() || !arch.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }) {
+                return Err(Error::Invalid("invalid required arch".to_string()));
+            }
+        }
+
+        // Labels
+        for (key, value) in &self.labels {
+            if !selector::is_key(key) {
+                return Err(Error::Invalid(format!("invalid label key {}", key)));
+            }
+            if !selector::is_value(value) {
+                return Err(Error::Invalid(format!(
+                    "invalid value {} of label {}",
+                    value, key
+                )));
+            }
+        }
+
+        if self.instances == Some(0) {
+            return Err(Error::Invalid("invalid instances 0".to_string()));
+        }
+
+        // Parameters without a default need a value with each start
+        for (name, parameter) in &self.parameters {
+            match &parameter.default {
+                Some(default)
+                    if !parameter.values.is_empty() && !parameter.values.contains(default) =>
+                {
+                    return Err(Error::Invalid(format!(
+                        "default of parameter {} is not an allowed value",
+                        name
+                    )));
+                }
+                None if self.autostart.is_some() => {
+                    return Err(Error::Invalid(format!(
+                        "parameter {} of an autostart container needs a default",
+                        name
+                    )));
+                }
+                _ => (),
+            }
+        }
+
+        // Autostart dependencies
+        if !self.after.is_empty() && self.autostart.is_none() {
+            return Err(Error::Invalid("after requires autostart".to_string()));
+        }
+        if self.after.contains(&self.name) {
+            return Err(Error::Invalid(format!(
+                "container {} cannot be started after itself",
+                self.name
+            )));
+        }
+
         // Check for invalid uid or gid of 0
         if self.uid == 0 {
             return Err(Error::Invalid("invalid uid of 0".to_string()));
@@ -131,11 +316,351 @@ impl Manifest {
             return Err(Error::Invalid("invalid gid of 0".to_string()));
         }
 
+        // Check the oom score adjustment range
+        if let Some(score_adj) = self.oom.as_ref().and_then(|oom| oom.score_adj) {
+            if !(-1000..=1000).contains(&score_adj) {
+                return Err(Error::Invalid(format!(
+                    "invalid oom score_adj {}: must be within -1000 and 1000",
+                    score_adj
+                )));
+            }
+        }
+
+        // A persist configuration requires a persist mount
+        if let Some(persist) = &self.persist {
+            if !self
+                .mounts
+                .values()
+                .any(|m| matches!(m, mount::Mount::Persist(_)))
+            {
+                return Err(Error::Invalid(
+                    "persist configuration without persist mount".to_string(),
+                ));
+            }
+            if let Some(size) = persist.size.filter(|size| *size < Persist::MIN_SIZE) {
+                return Err(Error::Invalid(format!(
+                    "invalid persist size {}: must be at least {} bytes",
+                    size,
+                    Persist::MIN_SIZE
+                )));
+            }
+        }
+
+        // Pipes, sockets and links are mounted on their own path
+        let mut paths = HashSet::new();
+        let mut conflicts = |path: &PathBuf, what: String| {
+            if !path.is_absolute() || !paths.insert(path.clone()) {
+                Err(Error::Invalid(format!(
+                    "invalid path {} of {}",
+                    path.display(),
+                    what
+                )))
+            } else if self.mounts.contains_key(path) {
+                Err(Error::Invalid(format!(
+                    "{} conflicts with the mount {}",
+                    what,
+                    path.display()
+                )))
+            } else {
+                Ok(())
+            }
+        };
+        if let Some(ipc) = &self.ipc {
+            let mut names = HashSet::new();
+            for pipe in &ipc.pipes {
+                if !names.insert(&pipe.name) {
+                    return Err(Error::Invalid(format!("duplicate pipe {}", pipe.name)));
+                }
+                conflicts(&pipe.path, format!("pipe {}", pipe.name))?;
+            }
+            for socket in &ipc.sockets {
+                conflicts(socket, "socket".to_string())?;
+            }
+            let mut names = HashSet::new();
+            for shm in &ipc.shm {
+                if !names.insert(&shm.name) {
+                    return Err(Error::Invalid(format!("duplicate shm {}", shm.name)));
+                }
+                match (shm.size, &shm.from) {
+                    (Some(0), _) => {
+                        return Err(Error::Invalid(format!("invalid size 0 of shm {}", shm.name)))
+                    }
+                    (Some(_), None) => (),
+                    (None, Some(from)) if *from != self.name && shm.access.is_empty() => (),
+                    _ => {
+                        return Err(Error::Invalid(format!(
+                            "shm {} must either have a size and optional access or be from another container",
+                            shm.name
+                        )))
+                    }
+                }
+                conflicts(&shm.path, format!("shm {}", shm.name))?;
+            }
+            if let Some(dbus) = &ipc.dbus {
+                if dbus.system.is_none() && dbus.session.is_none() {
+                    return Err(Error::Invalid("dbus without any bus".to_string()));
+                }
+                conflicts(&PathBuf::from(ipc::DBus::DIR), "dbus".to_string())?;
+            }
+        }
+        for link in &self.links {
+            if link.from == self.name {
+                return Err(Error::Invalid(format!(
+                    "invalid link {}: containers cannot link their own sockets",
+                    link.socket.display()
+                )));
+            }
+            conflicts(&link.socket, format!("link from {}", link.from))?;
+        }
+
+        if let Some(time) = &self.time {
+            if time.offset.is_none() && time.realtime.is_none() {
+                return Err(Error::Invalid(
+                    "time without offset or realtime".to_string(),
+                ));
+            }
+            // Offsets with template parameters are checked on start
+            if let Some(offset) = time.offset.as_ref().filter(|o| !o.contains('$')) {
+                if Time::parse_offset(offset).is_none() {
+                    return Err(Error::Invalid(format!("invalid time offset {}", offset)));
+                }
+            }
+            if time.realtime.is_some() {
+                conflicts(&PathBuf::from(Time::FAKETIME), "time".to_string())?;
+            }
+        }
+
+        // Only parameters of the namespaces of the container can be set
+        for key in self.sysctl.keys() {
+            match sysctl::namespace(key) {
+                Some(sysctl::Namespace::Net)
+                    if !self.network.as_ref().map_or(false, |n| n.namespace) =>
+                {
+                    return Err(Error::Invalid(format!(
+                        "sysctl {} requires a network namespace",
+                        key
+                    )))
+                }
+                Some(_) => (),
+                None => return Err(Error::Invalid(format!("sysctl {} is not allowed", key))),
+            }
+        }
+
+        // Masked and read only paths are applied to the mounted root and must not
+        // leave it
+        for (path, what) in self
+            .masked_paths
+            .iter()
+            .map(|p| (p, "masked path"))
+            .chain(self.readonly_paths.iter().map(|p| (p, "read only path")))
+        {
+            if !path.is_absolute()
+                || path.parent().is_none()
+                || path.components().any(|c| c == Component::ParentDir)
+                || NonNulString::try_from(path.display().to_string()).is_err()
+            {
+                return Err(Error::Invalid(format!(
+                    "invalid {} {}",
+                    what,
+                    path.display()
+                )));
+            }
+        }
+
+        if let Some(network) = &self.network {
+            // Interfaces are moved by name into the namespace
+            let mut interfaces = HashSet::new();
+            for interface in &network.interfaces {
+                if interface.is_empty()
+                    || interface.len() > network::Network::INTERFACE_NAME_MAX
+                    || interface.contains('/')
+                    || interface.chars().any(char::is_whitespace)
+                    || !interfaces.insert(interface)
+                {
+                    return Err(Error::Invalid(format!(
+                        "invalid network interface {}",
+                        interface
+                    )));
+                }
+            }
+            if !network.interfaces.is_empty() && !network.namespace {
+                return Err(Error::Invalid(
+                    "network interfaces require a network namespace".to_string(),
+                ));
+            }
+
+            // Ports are matched for tcp and udp only
+            let rules = network.egress.iter().chain(&network.ingress).flatten();
+            for rule in rules {
+                if rule.port == Some(0) {
+                    return Err(Error::Invalid("invalid network port 0".to_string()));
+                }
+            }
+
+            if let Some(bandwidth) = &network.bandwidth {
+                for rate in bandwidth.egress.iter().chain(&bandwidth.ingress) {
+                    if *rate == 0 || *rate > network::Bandwidth::MAX {
+                        return Err(Error::Invalid(format!("invalid bandwidth {}", rate)));
+                    }
+                }
+            }
+        }
+
+        // Services are looked up by name
+        let mut services = HashSet::new();
+        for service in &self.services {
+            if !services.insert(&service.name) {
+                return Err(Error::Invalid(format!(
+                    "duplicate service {}",
+                    service.name
+                )));
+            }
+            if service.port == 0 {
+                return Err(Error::Invalid(format!(
+                    "invalid port 0 of service {}",
+                    service.name
+                )));
+            }
+        }
+
+        // The PTY is the only output of the container
+        if self.io.tty && (self.io.stdout == Output::Pipe || self.io.stderr == Output::Pipe) {
+            return Err(Error::Invalid(
+                "io tty cannot be combined with piped output".to_string(),
+            ));
+        }
+
+        // The input of a PTY is written by the attached client
+        if self.io.tty && self.io.stdin {
+            return Err(Error::Invalid(
+                "io tty cannot be combined with stdin".to_string(),
+            ));
+        }
+
+        // Log forwarding requires at least one piped output and a sane sink
+        if let Some(log) = &self.log {
+            if self.io.stdout == Output::Discard && self.io.stderr == Output::Discard {
+                return Err(Error::Invalid(
+                    "log configuration requires stdout or stderr to be piped".to_string(),
+                ));
+            }
+            if let LogSink::File { path, size, .. } = &log.sink {
+                if !path.is_absolute() {
+                    return Err(Error::Invalid(format!(
+                        "invalid log file {}: must be absolute",
+                        path.display()
+                    )));
+                }
+                if *size == 0 {
+                    return Err(Error::Invalid("invalid log file size 0".to_string()));
+                }
+            }
+            if let Some(rate_limit) = &log.rate_limit {
+                if rate_limit.lines == 0 {
+                    return Err(Error::Invalid(
+                        "invalid log rate limit of 0 lines".to_string(),
+                    ));
+                }
+            }
+        }
+
+        // Capability sets
+        if !self
+            .capabilities
+            .ambient
+            .is_subset(&self.capabilities.permitted)
+        {
+            return Err(Error::Invalid(
+                "ambient capabilities must be permitted".to_string(),
+            ));
+        }
+        if !self
+            .capabilities
+            .permitted
+            .is_subset(&self.capabilities.bounding)
+        {
+            return Err(Error::Invalid(
+                "permitted capabilities must be in the bounding set".to_string(),
+            ));
+        }
+
+        // Installing a seccomp filter without no_new_privs requires CAP_SYS_ADMIN
+        if self.no_new_privs == Some(false)
+            && self.seccomp.is_some()
+            && !self
+                .capabilities
+                .permitted
+                .contains(&Capability::CAP_SYS_ADMIN)
+        {
+            return Err(Error::Invalid(
+                "seccomp without no_new_privs requires CAP_SYS_ADMIN".to_string(),
+            ));
+        }
+
+        if let Some(sched) = &self.sched {
+            sched.verify(&self.capabilities.permitted)?;
+        }
+
+        if let Some(io_priority) = &self.io_priority {
+            match (io_priority.class, io_priority.level) {
+                (IoPriorityClass::Idle, Some(_)) => {
+                    return Err(Error::Invalid(
+                        "io priority class idle has no level".to_string(),
+                    ))
+                }
+                (_, Some(level)) if level > 7 => {
+                    return Err(Error::Invalid(format!(
+                        "invalid io priority level {}: must be within 0 and 7",
+                        level
+                    )))
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(signal) = self.suspend.as_ref().and_then(|s| s.signal.as_ref()) {
+            if nix::sys::signal::Signal::from_str(signal).is_err() {
+                return Err(Error::Invalid(format!("invalid suspend signal {}", signal)));
+            }
+        }
+
+        // The overlay of the root filesystem needs space for the upper and work dir
+        if let Some(root) = &self.root {
+            if root.size == 0 {
+                return Err(Error::Invalid("invalid root overlay size 0".to_string()));
+            }
+        }
+
+        // All persist mounts use the same directory and must agree on the share group
+        let shares = self
+            .mounts
+            .values()
+            .filter_map(|m| match m {
+                mount::Mount::Persist(persist) => Some(&persist.share),
+                _ => None,
+            })
+            .unique()
+            .collect::<Vec<_>>();
+        if shares.len() > 1 {
+            return Err(Error::Invalid(
+                "persist mounts must use the same share group".to_string(),
+            ));
+        }
+        let quota = self
+            .persist
+            .as_ref()
+            .map_or(false, |persist| persist.size.is_some());
+        if matches!(shares.first(), Some(Some(_))) && quota {
+            return Err(Error::Invalid(
+                "persist quota cannot be used with a shared persist mount".to_string(),
+            ));
+        }
+
         // Check for reserved env variable names
         if RESERVED_ENV_VARIABLES.iter().any(|key| {
-            self.env
-                .contains_key(unsafe { &NonNulString::from_str_unchecked(key) })
             // safe - constants
+            let key = unsafe { NonNulString::from_str_unchecked(key) };
+            self.env.contains_key(&key) || self.env_overrides.contains(&key)
         }) {
             return Err(Error::Invalid(
                 "invalid environment: reserved variable name".into(),
@@ -183,9 +708,56 @@ impl Manifest {
                         Ok(())
                     }
                 }
+                mount::Mount::Overlay(m) => {
+                    if m.options.contains(&mount::MountOption::Rec) {
+                        Err(Error::Invalid(
+                            "non bind mounts must not be recursive".to_string(),
+                        ))
+                    } else {
+                        Ok(())
+                    }
+                }
                 _ => Ok(()),
             })?;
 
+        // Overlays are read only and need at least two layers
+        for (target, mount) in &self.mounts {
+            if let mount::Mount::Overlay(overlay) = mount {
+                if overlay.layers.len() < 2 {
+                    return Err(Error::Invalid(format!(
+                        "overlay on {} needs at least two layers",
+                        target.display()
+                    )));
+                }
+                if overlay.options.contains(&mount::MountOption::Rw) {
+                    return Err(Error::Invalid(format!(
+                        "overlay on {} must not be writable",
+                        target.display()
+                    )));
+                }
+            }
+        }
+
+        // Check secret names. Each secret is stored in a file named after the secret.
+        for (target, mount) in &self.mounts {
+            if let mount::Mount::Secret(secret) = mount {
+                if self.init.is_none() {
+                    return Err(Error::Invalid(
+                        "resource containers cannot mount secrets".to_string(),
+                    ));
+                }
+                if let Some(name) = secret.secrets.iter().find(|name| {
+                    name.is_empty() || name.contains('/') || *name == "." || *name == ".."
+                }) {
+                    return Err(Error::Invalid(format!(
+                        "invalid secret name \"{}\" on {}",
+                        name,
+                        target.display()
+                    )));
+                }
+            }
+        }
+
         // Check selinux context
         if let Some(selinux) = &self.selinux {
             // Maximum length since at least Linux v3.7
@@ -208,6 +780,26 @@ impl Manifest {
                         .to_string(),
                 ));
             }
+            if let Some(label) = &selinux.label {
+                if label.is_empty()
+                    || label.len() >= XATTR_SIZE_MAX
+                    || !label.chars().all(|c| {
+                        c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '.' | ',' | '-')
+                    })
+                {
+                    return Err(Error::Invalid(format!("invalid Selinux label {}", label)));
+                }
+            }
+        }
+
+        // Check the apparmor profile name
+        if let Some(profile) = &self.apparmor_profile {
+            if profile.is_empty() || profile.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(Error::Invalid(format!(
+                    "invalid AppArmor profile {}",
+                    profile
+                )));
+            }
         }
 
         // Check seccomp filter
@@ -242,6 +834,20 @@ impl Manifest {
                     }
                 }
             }
+            if let Some(syscall) = seccomp.deny.iter().flatten().find(|s| !is_syscall(s)) {
+                return Err(Error::Invalid(format!(
+                    "Unknown seccomp syscall {} in deny list",
+                    syscall
+                )));
+            }
+            if let (Some(allowlist), Some(denylist)) = (&seccomp.allow, &seccomp.deny) {
+                if let Some(syscall) = denylist.iter().find(|s| allowlist.contains_key(*s)) {
+                    return Err(Error::Invalid(format!(
+                        "Seccomp syscall {} is both allowed and denied",
+                        syscall
+                    )));
+                }
+            }
         }
 
         Ok(())
@@ -258,6 +864,15 @@ impl FromStr for Manifest {
     }
 }
 
+/// Serialize a set in a stable order
+fn serialize_sorted<S, T>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    T: Serialize + Ord,
+{
+    serializer.collect_seq(set.iter().sorted())
+}
+
 impl ToString for Manifest {
     #[allow(clippy::unwrap_used)]
     fn to_string(&self) -> String {
@@ -276,6 +891,8 @@ pub enum Error {
     Invalid(String),
     #[error("failed to parse: {0}")]
     SerdeYaml(#[from] serde_yaml::Error),
+    #[error("{0}: {1}")]
+    Io(String, #[source] io::Error),
 }
 
 /// Autostart options
@@ -290,6 +907,166 @@ pub enum Autostart {
     Critical,
 }
 
+/// Template parameter
+#[skip_serializing_none]
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Parameter {
+    /// Value used if the start request has no value for the parameter
+    pub default: Option<NonNulString>,
+    /// Allowed values. Any value is allowed if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<NonNulString>,
+}
+
+/// Clocks of the container. `CLOCK_MONOTONIC` and `CLOCK_BOOTTIME` are shifted by
+/// `offset` in a time namespace. The kernel does not offset `CLOCK_REALTIME`: It is
+/// faked by preloading libfaketime with `realtime` as `FAKETIME`. Both values are
+/// subject to template parameters.
+/// ```yaml
+/// time:
+///   offset: +30d
+///   realtime: +30d
+/// ```
+#[skip_serializing_none]
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Time {
+    /// Offset with an optional sign and one of the units `s`, `m`, `h` or `d`
+    pub offset: Option<NonNulString>,
+    /// Specification of the realtime clock passed to libfaketime e.g. `-1h` or
+    /// `@2030-01-01 00:00:00`
+    pub realtime: Option<NonNulString>,
+}
+
+impl Time {
+    /// Path libfaketime is mounted to in containers with `realtime`
+    pub const FAKETIME: &'static str = "/run/faketime/libfaketime.so.1";
+
+    /// Parse a time namespace offset into seconds
+    pub fn parse_offset(offset: &str) -> Option<i64> {
+        let (sign, offset) = match offset.strip_prefix('-') {
+            Some(offset) => (-1, offset),
+            None => (1, offset.strip_prefix('+').unwrap_or(offset)),
+        };
+        let split = offset
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(offset.len());
+        let (value, unit) = offset.split_at(split);
+        let factor = match unit {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return None,
+        };
+        value
+            .parse::<i64>()
+            .ok()?
+            .checked_mul(factor)?
+            .checked_mul(sign)
+    }
+}
+
+/// Requirements on the system checked when the container is installed
+///
+/// ```yaml
+/// requires:
+///   arch: aarch64
+///   kernel: ">=5.10"
+///   northstar: ">=0.7"
+/// ```
+#[skip_serializing_none]
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Requires {
+    /// Architecture of the runtime as named by Rust e.g. `aarch64`, `arm` or `x86_64`
+    pub arch: Option<String>,
+    /// Kernel release. Suffixes of the release e.g. `-rt` are ignored.
+    pub kernel: Option<VersionReq>,
+    /// Version of the runtime
+    pub northstar: Option<VersionReq>,
+}
+
+/// Out of memory configuration
+#[skip_serializing_none]
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Oom {
+    /// Value written to `oom_score_adj` of the container init. See proc(5)
+    pub score_adj: Option<i32>,
+    /// Action taken when the memory cgroup reports an oom kill
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub policy: OomPolicy,
+    /// Maximum number of consecutive restarts with the `restart` policy. Default: 3
+    pub max_restarts: Option<u32>,
+}
+
+/// Action taken upon an oom kill
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OomPolicy {
+    /// Kill the container
+    Kill,
+    /// Kill the container and start it again with the same arguments and environment.
+    /// The container is not restarted once `max_restarts` consecutive restarts are
+    /// reached.
+    Restart,
+    /// Send a notification only
+    Notify,
+}
+
+impl Default for OomPolicy {
+    fn default() -> OomPolicy {
+        OomPolicy::Notify
+    }
+}
+
+/// Persist directory configuration
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Persist {
+    /// Maximum size of the persist directory in bytes. The directory is backed
+    /// by a filesystem image of this size.
+    #[serde(
+        default,
+        deserialize_with = "mount::deserialize_optional_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub size: Option<u64>,
+    /// Remove the persist directory when the container is uninstalled
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub remove_on_uninstall: bool,
+    /// Overwrite the files of the persist directory with zeros before they are removed
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub secure_delete: bool,
+}
+
+impl Persist {
+    /// Minimum size of a persist filesystem image
+    pub const MIN_SIZE: u64 = 1024 * 1024;
+}
+
+/// Root filesystem configuration. The root filesystem is read only by default.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Root {
+    /// Writable layer on top of the root filesystem
+    pub overlay: RootOverlay,
+    /// Size of the writable layer in bytes
+    #[serde(deserialize_with = "mount::deserialize_size")]
+    pub size: u64,
+}
+
+/// Type of the writable layer of the root filesystem
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RootOverlay {
+    /// Overlay the root filesystem with a tmpfs. Modifications are lost when the
+    /// container exits.
+    Tmpfs,
+}
+
 /// IO configuration for stdin, stdout, stderr
 #[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -298,6 +1075,14 @@ pub struct Io {
     pub stdout: Output,
     /// stderr configuration
     pub stderr: Output,
+    /// Connect stdin, stdout and stderr to a PTY. Clients can attach to the PTY for
+    /// an interactive session. The output is not forwarded to the logging system.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub tty: bool,
+    /// Connect stdin to a pipe. Clients with the `stdin` permission write to the pipe
+    /// with `stdin` requests. Without stdin is connected to `/dev/null`.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub stdin: bool,
 }
 
 /// Io redirection for stdout/stderr
@@ -317,10 +1102,102 @@ impl Default for Output {
     }
 }
 
-/// Log level
+/// Forwarding of the container output. Lines that are piped according to the `io`
+/// configuration are written to `sink`.
+#[skip_serializing_none]
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Log {
+    /// Destination of the output
+    #[serde(default)]
+    pub sink: LogSink,
+    /// Level of the forwarded lines
+    #[serde(default)]
+    pub level: Level,
+    /// Tag or identifier. Defaults to the container name.
+    pub tag: Option<NonNulString>,
+    /// Limit the number of forwarded lines. Lines exceeding the limit are dropped.
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Log destination
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
-#[serde(rename_all = "lowercase")]
-pub enum Level {
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LogSink {
+    /// Log through the logger of the runtime
+    Runtime,
+    /// Send the lines to the systemd journal
+    Journald,
+    /// Send the lines to an Android logd buffer
+    Logd {
+        /// Logd buffer
+        #[serde(default)]
+        buffer: LogdBuffer,
+    },
+    /// Append the lines to a file on the host and rotate it
+    File {
+        /// Absolute path of the log file
+        path: PathBuf,
+        /// Size in bytes after which the file is rotated
+        #[serde(deserialize_with = "mount::deserialize_size")]
+        size: u64,
+        /// Number of rotated files to keep
+        #[serde(default = "LogSink::default_files")]
+        files: u32,
+    },
+    /// Send the lines as notifications to the console clients
+    Console,
+}
+
+impl LogSink {
+    fn default_files() -> u32 {
+        3
+    }
+}
+
+impl Default for LogSink {
+    fn default() -> LogSink {
+        LogSink::Runtime
+    }
+}
+
+/// Android logd buffer
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogdBuffer {
+    /// Main buffer
+    Main = 0,
+    /// Radio buffer
+    Radio = 1,
+    /// Events buffer
+    Events = 2,
+    /// System buffer
+    System = 3,
+    /// Crash buffer
+    Crash = 4,
+}
+
+impl Default for LogdBuffer {
+    fn default() -> LogdBuffer {
+        LogdBuffer::Main
+    }
+}
+
+/// Token bucket rate limit of forwarded lines
+#[skip_serializing_none]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Sustained number of lines per second
+    pub lines: u32,
+    /// Number of lines that may exceed the sustained rate at once. Defaults to `lines`.
+    pub burst: Option<u32>,
+}
+
+/// Log level
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
     /// The "error" level.
     #[serde(alias = "ERROR")]
     Error = 1,
@@ -346,6 +1223,12 @@ pub enum Level {
     Trace,
 }
 
+impl Default for Level {
+    fn default() -> Level {
+        Level::Debug
+    }
+}
+
 /// Resource limits. See setrlimit(2)
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
@@ -406,8 +1289,279 @@ pub struct RLimitValue {
     pub hard: Option<u64>,
 }
 
+/// Behaviour of a container on a system suspend
+#[skip_serializing_none]
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Suspend {
+    /// Signal sent to the container before it is frozen e.g. `SIGUSR1`
+    pub signal: Option<String>,
+    /// Time in milliseconds the container gets to prepare after the signal
+    pub timeout: Option<u64>,
+    /// Do not freeze the container on a system suspend
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub exempt: bool,
+}
+
+/// Scheduling policy and priority of the container process. The policy is set by the
+/// container init before the application is executed.
+/// ```yaml
+/// sched:
+///   policy: fifo
+///   priority: 50
+/// ```
+#[skip_serializing_none]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Sched {
+    /// Scheduling policy
+    pub policy: SchedPolicy,
+    /// Static priority of `fifo` and `rr` from 1 (lowest) to 99 (highest)
+    pub priority: Option<u32>,
+    /// Nice value of `other` from -20 (highest) to 19 (lowest)
+    pub nice: Option<i32>,
+    /// Runtime of `deadline` in nanoseconds
+    pub runtime: Option<u64>,
+    /// Deadline of `deadline` in nanoseconds
+    pub deadline: Option<u64>,
+    /// Period of `deadline` in nanoseconds. Default: deadline
+    pub period: Option<u64>,
+}
+
+/// Scheduling policy
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedPolicy {
+    /// `SCHED_OTHER`: Default time sharing policy
+    Other,
+    /// `SCHED_FIFO`: Real time first in first out
+    Fifo,
+    /// `SCHED_RR`: Real time round robin
+    Rr,
+    /// `SCHED_DEADLINE`: Earliest deadline first. Requires `CAP_SYS_NICE` because the
+    /// policy is set by the application process itself.
+    Deadline,
+}
+
+impl Sched {
+    fn verify(&self, capabilities: &HashSet<Capability>) -> Result<(), Error> {
+        let realtime = matches!(self.policy, SchedPolicy::Fifo | SchedPolicy::Rr);
+        match self.priority {
+            Some(priority) if realtime && !(1..=99).contains(&priority) => {
+                return Err(Error::Invalid(format!(
+                    "invalid sched priority {}: must be within 1 and 99",
+                    priority
+                )))
+            }
+            Some(_) if !realtime => {
+                return Err(Error::Invalid(
+                    "sched priority requires policy fifo or rr".to_string(),
+                ))
+            }
+            None if realtime => {
+                return Err(Error::Invalid(
+                    "sched policy fifo and rr require a priority".to_string(),
+                ))
+            }
+            _ => (),
+        }
+
+        match self.nice {
+            Some(nice) if !(-20..=19).contains(&nice) => {
+                return Err(Error::Invalid(format!(
+                    "invalid sched nice value {}: must be within -20 and 19",
+                    nice
+                )))
+            }
+            Some(_) if self.policy != SchedPolicy::Other => {
+                return Err(Error::Invalid(
+                    "sched nice value requires policy other".to_string(),
+                ))
+            }
+            _ => (),
+        }
+
+        if self.policy == SchedPolicy::Deadline {
+            let (runtime, deadline) = match (self.runtime, self.deadline) {
+                (Some(runtime), Some(deadline)) => (runtime, deadline),
+                _ => {
+                    return Err(Error::Invalid(
+                        "sched policy deadline requires a runtime and deadline".to_string(),
+                    ))
+                }
+            };
+            let period = self.period.unwrap_or(deadline);
+            if runtime == 0 || runtime > deadline || deadline > period {
+                return Err(Error::Invalid(format!(
+                    "invalid sched deadline parameters: runtime {} <= deadline {} <= period {} required",
+                    runtime, deadline, period
+                )));
+            }
+            if !capabilities.contains(&Capability::CAP_SYS_NICE) {
+                return Err(Error::Invalid(
+                    "sched policy deadline requires CAP_SYS_NICE".to_string(),
+                ));
+            }
+        } else if self.runtime.is_some() || self.deadline.is_some() || self.period.is_some() {
+            return Err(Error::Invalid(
+                "sched runtime, deadline and period require policy deadline".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// IO scheduling class and priority of the container process set with `ioprio_set`
+/// ```yaml
+/// io_priority:
+///   class: best_effort
+///   level: 7
+/// ```
+#[skip_serializing_none]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct IoPriority {
+    /// IO scheduling class
+    pub class: IoPriorityClass,
+    /// Priority within the class from 0 (highest) to 7 (lowest). Default: 4
+    pub level: Option<u8>,
+}
+
+/// IO scheduling class
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IoPriorityClass {
+    /// `IOPRIO_CLASS_RT`: Served first regardless of other IO
+    Realtime,
+    /// `IOPRIO_CLASS_BE`: Default class
+    BestEffort,
+    /// `IOPRIO_CLASS_IDLE`: Served only if no other IO is pending
+    Idle,
+}
+
+/// Capability sets of the container process. A list of capabilities is applied to
+/// all sets. The sets can be configured individually e.g. to keep a capability in the
+/// bounding set that is granted by the file capabilities of an executed binary:
+/// ```yaml
+/// capabilities:
+///   bounding: [CAP_NET_RAW, CAP_NET_BIND_SERVICE]
+///   permitted: [CAP_NET_BIND_SERVICE]
+///   ambient: [CAP_NET_BIND_SERVICE]
+/// ```
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Capabilities {
+    /// Capabilities that can be gained on exec
+    pub bounding: HashSet<Capability>,
+    /// Permitted, effective and inheritable capabilities
+    pub permitted: HashSet<Capability>,
+    /// Capabilities that are preserved across the exec of unprivileged binaries
+    pub ambient: HashSet<Capability>,
+}
+
+impl Capabilities {
+    /// True if all sets are empty
+    pub fn is_empty(&self) -> bool {
+        self.bounding.is_empty() && self.permitted.is_empty() && self.ambient.is_empty()
+    }
+}
+
+impl From<HashSet<Capability>> for Capabilities {
+    fn from(capabilities: HashSet<Capability>) -> Self {
+        Capabilities {
+            bounding: capabilities.clone(),
+            permitted: capabilities.clone(),
+            ambient: capabilities,
+        }
+    }
+}
+
+impl Serialize for Capabilities {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CapabilitiesRepr::from(self.clone()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Capabilities {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        CapabilitiesRepr::deserialize(deserializer).map(CapabilitiesRepr::into_capabilities)
+    }
+}
+
+impl JsonSchema for Capabilities {
+    fn schema_name() -> String {
+        "Capabilities".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        CapabilitiesRepr::json_schema(gen)
+    }
+}
+
+/// Serialized form of `Capabilities`: A list if all sets are equal or the sets
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum CapabilitiesRepr {
+    All(#[serde(serialize_with = "serialize_sorted")] HashSet<Capability>),
+    Sets(CapabilitySets),
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CapabilitySets {
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
+    bounding: HashSet<Capability>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
+    permitted: HashSet<Capability>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
+    ambient: HashSet<Capability>,
+}
+
+impl CapabilitiesRepr {
+    fn into_capabilities(self) -> Capabilities {
+        match self {
+            CapabilitiesRepr::All(capabilities) => capabilities.into(),
+            CapabilitiesRepr::Sets(sets) => Capabilities {
+                bounding: sets.bounding,
+                permitted: sets.permitted,
+                ambient: sets.ambient,
+            },
+        }
+    }
+}
+
+impl From<Capabilities> for CapabilitiesRepr {
+    fn from(capabilities: Capabilities) -> Self {
+        if capabilities.bounding == capabilities.permitted
+            && capabilities.permitted == capabilities.ambient
+        {
+            CapabilitiesRepr::All(capabilities.permitted)
+        } else {
+            CapabilitiesRepr::Sets(CapabilitySets {
+                bounding: capabilities.bounding,
+                permitted: capabilities.permitted,
+                ambient: capabilities.ambient,
+            })
+        }
+    }
+}
+
 /// Linux capability
-#[derive(Clone, Eq, Hash, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(
+    Clone, Eq, Hash, PartialEq, PartialOrd, Ord, Debug, Serialize, Deserialize, JsonSchema,
+)]
 #[allow(non_camel_case_types)]
 pub enum Capability {
     /// `CAP_CHOWN` (from POSIX)
@@ -501,7 +1655,7 @@ fn is_default<T: Default + PartialEq>(t: &T) -> bool {
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
-    use crate::{common::version::VersionReq, npk::manifest::*};
+    use crate::{common::version::VersionReq, npk::manifest::*, seccomp::Profile};
     use anyhow::Result;
     use std::{
         convert::{TryFrom, TryInto},
@@ -590,7 +1744,10 @@ cgroups:
                 options: [mount::MountOption::Rw].iter().cloned().collect(),
             }),
         );
-        mounts.insert(PathBuf::from("/data"), mount::Mount::Persist);
+        mounts.insert(
+            PathBuf::from("/data"),
+            mount::Mount::Persist(mount::Persist::default()),
+        );
         mounts.insert(
             PathBuf::from("/resource"),
             mount::Mount::Resource(mount::Resource {
@@ -620,20 +1777,21 @@ cgroups:
             manifest.seccomp,
             Some(Seccomp {
                 profile: None,
-                allow: Some(syscalls)
+                allow: Some(syscalls),
+                deny: None,
             })
         );
 
         assert_eq!(
             manifest.capabilities,
-            HashSet::from_iter(
+            Capabilities::from(HashSet::from_iter(
                 vec!(
                     Capability::CAP_NET_RAW,
                     Capability::CAP_MKNOD,
                     Capability::CAP_SYS_TIME,
                 )
                 .drain(..)
-            )
+            ))
         );
         assert_eq!(
             manifest.suppl_groups,
@@ -746,6 +1904,35 @@ mounts:
         assert!(Manifest::from_str(manifest).is_err());
     }
 
+    #[test]
+    fn mount_secret() {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /secrets:
+    type: secret
+    secrets:
+      - token
+      - key.pem
+";
+        let manifest = Manifest::from_str(manifest).unwrap();
+        assert_eq!(
+            manifest.mounts.get(&PathBuf::from("/secrets")),
+            Some(&mount::Mount::Secret(mount::Secret {
+                secrets: vec!["token".into(), "key.pem".into()]
+            }))
+        );
+
+        // Secret names must be file names
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /secrets:
+    type: secret
+    secrets:
+      - ../token
+";
+        assert!(Manifest::from_str(manifest).is_err());
+    }
+
     #[test]
     fn dev_minimal() {
         let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\nmounts:\n  /dev:\n    type: dev";
@@ -803,6 +1990,11 @@ mounts:
     type: tmpfs
     size: 42
 autostart: relaxed
+oom:
+  score_adj: 500
+  policy: restart
+persist:
+  size: 1048576
 rlimits:
   nproc:
     soft: 100
@@ -874,6 +2066,1270 @@ env:
         Ok(())
     }
 
+    /// Check the maximum number of instances
+    #[test]
+    fn instances() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+instances: 2";
+        assert_eq!(Manifest::from_str(manifest)?.instances, Some(2));
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+instances: 0";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Resource containers cannot have instances
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+instances: 2";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    /// Check the template parameters
+    #[test]
+    fn parameters() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+args:
+  - --display=${PARAM:display}
+parameters:
+  display:
+    default: \":0\"
+    values: [\":0\", \":1\"]
+  profile: {}";
+        let manifest = Manifest::from_str(manifest)?;
+        let display = &manifest.parameters[&"display".try_into()?];
+        assert_eq!(display.default, Some(":0".try_into()?));
+        assert_eq!(display.values.len(), 2);
+        assert_eq!(
+            manifest.parameters[&"profile".try_into()?],
+            Parameter::default()
+        );
+
+        // The default must be an allowed value
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+parameters:
+  display:
+    default: \":2\"
+    values: [\":0\", \":1\"]";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Autostarted containers cannot have parameters without a default
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+autostart: relaxed
+parameters:
+  display: {}";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Parameter names are limited to the characters valid in container names
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+parameters:
+  dis}play: {}";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Resource containers cannot have parameters
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+parameters:
+  display: {}";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    /// Check the env override list
+    #[test]
+    fn env_overrides() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+env:
+  RUST_LOG: info
+env_overrides:
+  - RUST_LOG
+  - VERBOSE";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.env_overrides.len(), 2);
+        assert!(manifest.env_overrides.contains(&"RUST_LOG".try_into()?));
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+env_overrides:
+  - NORTHSTAR_CONSOLE";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Resource containers cannot have env overrides
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+env_overrides:
+  - RUST_LOG";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    /// Check the oom configuration
+    #[test]
+    fn oom() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+oom:
+  score_adj: -500
+  policy: kill";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.oom,
+            Some(Oom {
+                score_adj: Some(-500),
+                policy: OomPolicy::Kill,
+                max_restarts: None,
+            })
+        );
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+oom:
+  policy: restart
+  max_restarts: 5";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.oom.and_then(|oom| oom.max_restarts), Some(5));
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+oom:
+  score_adj: 10";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.oom.map(|oom| oom.policy), Some(OomPolicy::Notify));
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+oom:
+  score_adj: 1001";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Resource containers cannot have an oom configuration
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+oom:
+  policy: kill";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn persist() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /data:
+    type: persist
+persist:
+  size: 64MB";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.persist,
+            Some(Persist {
+                size: Some(64000000),
+                ..Default::default()
+            })
+        );
+
+        // Remove the persist directory on uninstall
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /data:
+    type: persist
+persist:
+  remove_on_uninstall: true
+  secure_delete: true";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.persist,
+            Some(Persist {
+                size: None,
+                remove_on_uninstall: true,
+                secure_delete: true,
+            })
+        );
+
+        // A persist quota requires a persist mount
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+persist:
+  size: 64MB";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Too small
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /data:
+    type: persist
+persist:
+  size: 100";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn mount_overlay() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /model:
+    type: overlay
+    layers:
+      - name: model-base
+        version: '>=1.0.0'
+        dir: /
+      - name: model-delta
+        version: '>=1.0.0'
+        dir: /patch
+    options: noexec";
+        let manifest = Manifest::from_str(manifest)?;
+        let overlay = match manifest.mounts.get(&PathBuf::from("/model")) {
+            Some(mount::Mount::Overlay(overlay)) => overlay,
+            _ => panic!("missing overlay mount"),
+        };
+        assert_eq!(overlay.layers.len(), 2);
+        assert_eq!(overlay.layers[0].name.to_string(), "model-base");
+        assert_eq!(overlay.layers[1].dir, PathBuf::from("/patch"));
+        assert_eq!(
+            manifest.mounts[&PathBuf::from("/model")].resources().len(),
+            2
+        );
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Single layer
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /model:
+    type: overlay
+    layers:
+      - name: model-base
+        version: '>=1.0.0'
+        dir: /";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Writable overlay
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /model:
+    type: overlay
+    layers:
+      - name: model-base
+        version: '>=1.0.0'
+        dir: /
+      - name: model-delta
+        version: '>=1.0.0'
+        dir: /
+    options: rw";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn root_overlay() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+root:
+  overlay: tmpfs
+  size: 16M";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.root,
+            Some(Root {
+                overlay: RootOverlay::Tmpfs,
+                size: 16000000
+            })
+        );
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Resource containers cannot have a root overlay
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+root:
+  overlay: tmpfs
+  size: 16M";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Invalid size
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+root:
+  overlay: tmpfs
+  size: 0";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Unknown overlay type
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+root:
+  overlay: ext4
+  size: 16M";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn persist_share() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /data:
+    type: persist
+    share: hello-group";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.mounts.get(&PathBuf::from("/data")),
+            Some(&mount::Mount::Persist(mount::Persist {
+                share: Some("hello-group".try_into()?)
+            }))
+        );
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Mixed share groups
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /data:
+    type: persist
+    share: hello-group
+  /other:
+    type: persist";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Quota on a shared persist dir
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /data:
+    type: persist
+    share: hello-group
+persist:
+  size: 64MB";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Invalid group name
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /data:
+    type: persist
+    share: ../hello";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn after() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+autostart: relaxed
+after:
+  - world
+  - foo";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.after.len(), 2);
+        assert!(manifest.after.contains(&Name::try_from("world")?));
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // No autostart
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+after:
+  - world";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Self reference
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+autostart: relaxed
+after:
+  - hello";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn log() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: pipe
+  stderr: pipe
+log:
+  sink:
+    type: file
+    path: /var/log/hello.log
+    size: 1MB
+  level: info
+  rate_limit:
+    lines: 100";
+        let manifest = Manifest::from_str(manifest)?;
+        let log = manifest.log.as_ref().unwrap();
+        assert_eq!(
+            log.sink,
+            LogSink::File {
+                path: PathBuf::from("/var/log/hello.log"),
+                size: 1_000_000,
+                files: 3,
+            }
+        );
+        assert_eq!(log.level, Level::Info);
+        assert_eq!(log.rate_limit.as_ref().unwrap().lines, 100);
+        assert_eq!(log.rate_limit.as_ref().unwrap().burst, None);
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: pipe
+  stderr: discard
+log:
+  sink:
+    type: logd
+    buffer: system
+  tag: hello";
+        let manifest = Manifest::from_str(manifest)?;
+        let log = manifest.log.as_ref().unwrap();
+        assert_eq!(
+            log.sink,
+            LogSink::Logd {
+                buffer: LogdBuffer::System
+            }
+        );
+        assert_eq!(log.level, Level::Debug);
+
+        // Nothing is piped
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+log:
+  sink:
+    type: journald";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Relative log file
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: pipe
+  stderr: discard
+log:
+  sink:
+    type: file
+    path: hello.log
+    size: 1MB";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Zero rate limit
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: pipe
+  stderr: discard
+log:
+  rate_limit:
+    lines: 0";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn tty() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: discard
+  stderr: discard
+  tty: true";
+        let manifest = Manifest::from_str(manifest)?;
+        assert!(manifest.io.tty);
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // The output of a tty is not piped
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: pipe
+  stderr: discard
+  tty: true";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn stdin() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: pipe
+  stderr: pipe
+  stdin: true";
+        let manifest = Manifest::from_str(manifest)?;
+        assert!(manifest.io.stdin);
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // The input of a tty is written by attached clients
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io:
+  stdout: discard
+  stderr: discard
+  tty: true
+  stdin: true";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pipes() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  pipes:
+    - name: sensor
+      path: /run/sensor
+      end: write
+    - name: commands
+      path: /run/commands
+      end: read";
+        let manifest = Manifest::from_str(manifest)?;
+        let pipes = &manifest.ipc.as_ref().expect("missing ipc").pipes;
+        assert_eq!(pipes.len(), 2);
+        assert_eq!(pipes[0].end, ipc::PipeEnd::Write);
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Duplicate pipe
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  pipes:
+    - name: sensor
+      path: /run/a
+      end: write
+    - name: sensor
+      path: /run/b
+      end: read";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Relative path
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  pipes:
+    - name: sensor
+      path: run/sensor
+      end: write";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn shm() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  shm:
+    - name: camera
+      path: /dev/shm/camera
+      size: 4M
+      access:
+        - hmi
+    - name: lidar
+      path: /dev/shm/lidar
+      from: lidar";
+        let manifest = Manifest::from_str(manifest)?;
+        let shm = &manifest.ipc.as_ref().expect("missing ipc").shm;
+        assert_eq!(shm[0].size, Some(4 * 1024 * 1024));
+        assert_eq!(shm[1].from.as_ref().map(|n| n.as_ref()), Some("lidar"));
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Size and owner
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  shm:
+    - name: camera
+      path: /dev/shm/camera
+      size: 4M
+      from: camera";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Access to a segment of another container
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  shm:
+    - name: camera
+      path: /dev/shm/camera
+      from: camera
+      access:
+        - hmi";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Neither size nor owner
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  shm:
+    - name: camera
+      path: /dev/shm/camera";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn dbus() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  dbus:
+    system:
+      talk:
+        - org.freedesktop.NetworkManager
+      call:
+        - org.freedesktop.login1=org.freedesktop.login1.Manager.*@/org/freedesktop/login1
+    session:
+      own:
+        - com.example.Hello";
+        let manifest = Manifest::from_str(manifest)?;
+        let dbus = manifest.ipc.as_ref().and_then(|ipc| ipc.dbus.as_ref());
+        assert!(dbus.and_then(|dbus| dbus.system.as_ref()).is_some());
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // No bus
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  dbus: {}";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Conflict with a mount
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+mounts:
+  /run/dbus:
+    type: tmpfs
+    size: 1M
+ipc:
+  dbus:
+    system: {}";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn network() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  namespace: true
+  interfaces:
+    - can0
+    - eth0.10
+  egress:
+    - address: 10.0.0.0/8
+      port: 443
+      protocol: tcp
+    - address: fd00::/8
+  ingress: []";
+        let manifest = Manifest::from_str(manifest)?;
+        let network = manifest.network.as_ref().expect("missing network");
+        assert_eq!(network.interfaces.len(), 2);
+        assert_eq!(network.egress.as_ref().map(Vec::len), Some(2));
+        assert_eq!(network.ingress.as_ref().map(Vec::len), Some(0));
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Own namespace without interfaces
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  namespace: true";
+        assert!(Manifest::from_str(manifest).is_ok());
+
+        // Interfaces without namespace
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  interfaces:
+    - can0";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Duplicate interface
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  namespace: true
+  interfaces:
+    - can0
+    - can0";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Name too long
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  namespace: true
+  interfaces:
+    - averyveryverylongname";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Bandwidth
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  bandwidth:
+    egress: 1Mbit
+    ingress: 5000000";
+        let manifest = Manifest::from_str(manifest)?;
+        let bandwidth = manifest
+            .network
+            .as_ref()
+            .and_then(|network| network.bandwidth.as_ref())
+            .expect("missing bandwidth");
+        assert_eq!(bandwidth.egress, Some(1_000_000));
+        assert_eq!(bandwidth.ingress, Some(5_000_000));
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Zero bandwidth
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  bandwidth:
+    egress: 0bit";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Invalid prefix
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  egress:
+    - address: 10.0.0.0/40";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn services() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+services:
+  - name: telemetry
+    port: 8080
+    protocol: tcp
+  - name: discovery
+    port: 5353
+    protocol: udp";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.services.len(), 2);
+        assert_eq!(manifest.services[0].port, 8080);
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Duplicate name
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+services:
+  - name: telemetry
+    port: 8080
+    protocol: tcp
+  - name: telemetry
+    port: 8081
+    protocol: udp";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Port 0
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+services:
+  - name: telemetry
+    port: 0
+    protocol: tcp";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Resource container
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+services:
+  - name: telemetry
+    port: 8080
+    protocol: tcp";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn requires() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+requires:
+  arch: aarch64
+  kernel: \">=5.10\"
+  northstar: \">=0.7, <0.9\"";
+        let manifest = Manifest::from_str(manifest)?;
+        let requires = manifest.requires.as_ref().expect("missing requires");
+        assert_eq!(requires.arch.as_deref(), Some("aarch64"));
+        let kernel = requires.kernel.as_ref().expect("missing kernel");
+        assert!(kernel.matches(&Version::new(5, 15, 0)));
+        assert!(!kernel.matches(&Version::new(5, 4, 0)));
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Invalid arch
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+requires:
+  arch: \"\"";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Invalid kernel requirement
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+requires:
+  kernel: five";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn time() -> Result<()> {
+        assert_eq!(Time::parse_offset("+30d"), Some(30 * 24 * 60 * 60));
+        assert_eq!(Time::parse_offset("-2h"), Some(-2 * 60 * 60));
+        assert_eq!(Time::parse_offset("90"), Some(90));
+        assert_eq!(Time::parse_offset("10m"), Some(600));
+        assert_eq!(Time::parse_offset("+"), None);
+        assert_eq!(Time::parse_offset("1y"), None);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+time:
+  offset: -1h
+  realtime: '@2030-01-01 00:00:00'";
+        let manifest = Manifest::from_str(manifest)?;
+        let time = manifest.time.as_ref().expect("missing time");
+        assert_eq!(time.offset.as_ref().map(|o| o.as_str()), Some("-1h"));
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Offset passed as parameter
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+time:
+  offset: ${PARAM:offset}
+parameters:
+  offset:
+    default: 0s";
+        assert!(Manifest::from_str(manifest).is_ok());
+
+        // Invalid offset
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+time:
+  offset: 1 week";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Empty
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+time: {}";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sysctl() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  namespace: true
+sysctl:
+  net.ipv4.ip_unprivileged_port_start: 80
+  net.ipv4.ip_local_port_range: 32768 60999
+  kernel.msgmax: 16384";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.sysctl.len(), 3);
+        assert_eq!(
+            manifest.sysctl["net.ipv4.ip_unprivileged_port_start"].as_str(),
+            "80"
+        );
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Network parameter without network namespace
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+sysctl:
+  net.ipv4.ip_unprivileged_port_start: 80";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Parameter that is not namespaced
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+sysctl:
+  vm.overcommit_memory: 1";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn masked_and_readonly_paths() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+masked_paths:
+  - /proc/kcore
+  - /proc/acpi
+readonly_paths:
+  - /proc/sys";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.masked_paths,
+            vec![PathBuf::from("/proc/kcore"), PathBuf::from("/proc/acpi")]
+        );
+        assert_eq!(manifest.readonly_paths, vec![PathBuf::from("/proc/sys")]);
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Relative
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+masked_paths:
+  - proc/kcore";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Outside of the root
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+readonly_paths:
+  - /proc/../../etc";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Root
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+readonly_paths:
+  - /";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn links() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  sockets:
+    - /run/hello
+links:
+  - from: navigation
+    socket: /run/navigation";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.links.len(), 1);
+        assert_eq!(manifest.links[0].from, Name::try_from("navigation")?);
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Link to a socket of the container itself
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+links:
+  - from: hello
+    socket: /run/hello";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Link and socket on the same path
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+ipc:
+  sockets:
+    - /run/navigation
+links:
+  - from: navigation
+    socket: /run/navigation";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn shutdown() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+shutdown_priority: 10
+shutdown_timeout: 5";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.shutdown_priority, Some(10));
+        assert_eq!(manifest.shutdown_timeout, Some(5));
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Resource containers are not stopped
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+shutdown_priority: 10";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn suspend() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+suspend:
+  signal: SIGUSR1
+  timeout: 200";
+        let manifest = Manifest::from_str(manifest)?;
+        let suspend = manifest.suspend.as_ref().unwrap();
+        assert_eq!(suspend.signal.as_deref(), Some("SIGUSR1"));
+        assert_eq!(suspend.timeout, Some(200));
+        assert!(!suspend.exempt);
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Invalid signal
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+suspend:
+  signal: SIGFOO";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sched() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+sched:
+  policy: fifo
+  priority: 50";
+        let manifest = Manifest::from_str(manifest)?;
+        let sched = manifest.sched.as_ref().unwrap();
+        assert_eq!(sched.policy, SchedPolicy::Fifo);
+        assert_eq!(sched.priority, Some(50));
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+sched:
+  policy: other
+  nice: -5";
+        Manifest::from_str(manifest)?;
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+capabilities: [CAP_SYS_NICE]
+sched:
+  policy: deadline
+  runtime: 1000000
+  deadline: 5000000
+  period: 10000000";
+        Manifest::from_str(manifest)?;
+        Ok(())
+    }
+
+    #[test]
+    fn sched_invalid() {
+        let manifest = |sched: &str| {
+            format!(
+                "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\nsched:\n{}",
+                sched
+            )
+        };
+        // Missing priority
+        assert!(Manifest::from_str(&manifest("  policy: rr")).is_err());
+        // Priority out of range
+        assert!(Manifest::from_str(&manifest("  policy: fifo\n  priority: 100")).is_err());
+        // Priority of a non realtime policy
+        assert!(Manifest::from_str(&manifest("  policy: other\n  priority: 10")).is_err());
+        // Nice out of range
+        assert!(Manifest::from_str(&manifest("  policy: other\n  nice: 20")).is_err());
+        // Nice of a realtime policy
+        assert!(Manifest::from_str(&manifest("  policy: fifo\n  priority: 1\n  nice: 1")).is_err());
+        // Deadline without CAP_SYS_NICE
+        assert!(Manifest::from_str(&manifest(
+            "  policy: deadline\n  runtime: 1000\n  deadline: 2000"
+        ))
+        .is_err());
+        // Runtime exceeds deadline
+        let invalid = "capabilities: [CAP_SYS_NICE]\nsched:\n  policy: deadline\n  runtime: 3000\n  deadline: 2000";
+        assert!(Manifest::from_str(&format!(
+            "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\n{}",
+            invalid
+        ))
+        .is_err());
+        // Deadline parameters of another policy
+        assert!(Manifest::from_str(&manifest("  policy: other\n  runtime: 1000")).is_err());
+    }
+
+    #[test]
+    fn io_priority() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io_priority:
+  class: best_effort
+  level: 7";
+        let manifest = Manifest::from_str(manifest)?;
+        let io_priority = manifest.io_priority.as_ref().unwrap();
+        assert_eq!(io_priority.class, IoPriorityClass::BestEffort);
+        assert_eq!(io_priority.level, Some(7));
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Level out of range
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io_priority:
+  class: realtime
+  level: 8";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Idle has no level
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+io_priority:
+  class: idle
+  level: 0";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn security_labels() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+apparmor_profile: northstar-hello
+selinux:
+  context: unconfined_u:object_r:user_home_t:s0
+  label: system_u:system_r:container_t:s0:c1,c2";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.apparmor_profile.as_ref().map(|p| p.as_str()),
+            Some("northstar-hello")
+        );
+        assert_eq!(
+            manifest
+                .selinux
+                .as_ref()
+                .and_then(|s| s.label.as_ref())
+                .map(|l| l.as_str()),
+            Some("system_u:system_r:container_t:s0:c1,c2")
+        );
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Invalid profile
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+apparmor_profile: \"foo bar\"";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Invalid label
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+selinux:
+  context: unconfined_u:object_r:user_home_t:s0
+  label: \"system_u system_r\"";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn capability_sets() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+capabilities:
+  bounding: [CAP_NET_RAW, CAP_NET_BIND_SERVICE]
+  permitted: [CAP_NET_BIND_SERVICE]
+no_new_privs: false";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.capabilities.bounding,
+            HashSet::from([Capability::CAP_NET_RAW, Capability::CAP_NET_BIND_SERVICE])
+        );
+        assert_eq!(
+            manifest.capabilities.permitted,
+            HashSet::from([Capability::CAP_NET_BIND_SERVICE])
+        );
+        assert!(manifest.capabilities.ambient.is_empty());
+        assert_eq!(manifest.no_new_privs, Some(false));
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // A list is applied to all sets
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+capabilities: [CAP_KILL]";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.capabilities,
+            Capabilities::from(HashSet::from([Capability::CAP_KILL]))
+        );
+        assert!(!manifest.to_string().contains("bounding"));
+
+        // Ambient capabilities must be permitted
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+capabilities:
+  bounding: [CAP_KILL]
+  ambient: [CAP_KILL]";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Permitted capabilities must be in the bounding set
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+capabilities:
+  permitted: [CAP_KILL]";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Seccomp without no_new_privs
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+no_new_privs: false
+seccomp:
+  profile: default";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn seccomp_profiles() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  profile: networking
+  allow:
+    delete_module: any
+  deny:
+    - listen
+    - accept4";
+        let manifest = Manifest::from_str(manifest)?;
+        let seccomp = manifest.seccomp.as_ref().expect("missing seccomp");
+        assert_eq!(seccomp.profile, Some(Profile::Networking));
+        assert_eq!(
+            seccomp.deny,
+            Some(HashSet::from([
+                NonNulString::try_from("listen")?,
+                NonNulString::try_from("accept4")?
+            ]))
+        );
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // A syscall cannot be allowed and denied
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  profile: media
+  allow:
+    delete_module: any
+  deny:
+    - delete_module";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Unknown syscall in the deny list
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  profile: default
+  deny:
+    - listen
+    - no_such_syscall";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Unknown profile
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  profile: everything";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn deferrable() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+deferrable: true";
+        let manifest = Manifest::from_str(manifest)?;
+        assert!(manifest.deferrable);
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Resource containers are not throttled
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+deferrable: true";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn labels() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+labels:
+  group: hmi
+  vendor.example/tier: frontend
+  debug: ''";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.labels.get("group").map(String::as_str),
+            Some("hmi")
+        );
+        assert_eq!(manifest.labels.get("debug").map(String::as_str), Some(""));
+
+        // Roundtrip
+        let roundtrip = Manifest::from_str(&manifest.to_string())?;
+        assert_eq!(manifest, roundtrip);
+
+        // Invalid key
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+labels:
+  'gr=up': hmi";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Invalid value
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+labels:
+  group: h mi";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
     #[test]
     fn schema() {
         schemars::schema_for!(Manifest);
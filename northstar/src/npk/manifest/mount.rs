@@ -23,6 +23,30 @@ pub struct Resource {
     pub options: MountOptions,
 }
 
+/// Layer of an overlay mount
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Layer {
+    /// Name of the resource container
+    pub name: Name,
+    /// Required version of the resource container
+    pub version: VersionReq,
+    /// Directory within the resource container
+    pub dir: PathBuf,
+}
+
+/// Overlay mount configuration
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Overlay {
+    /// Directories of resource containers stacked on top of each other. The first
+    /// layer is the bottom most one. Files of upper layers hide the ones of lower layers.
+    pub layers: Vec<Layer>,
+    /// Mount options
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub options: MountOptions,
+}
+
 /// Bind mount configuration
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -38,10 +62,29 @@ pub struct Bind {
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Tmpfs {
     /// Size in bytes
-    #[serde(deserialize_with = "deserialize_tmpfs_size")]
+    #[serde(deserialize_with = "deserialize_size")]
     pub size: u64,
 }
 
+/// Persist mount configuration
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Persist {
+    /// Share the persist directory with all containers that use the same share group.
+    /// All containers of a share group must run with the same uid and gid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share: Option<Name>,
+}
+
+/// Secret mount configuration
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Secret {
+    /// Names of the secrets requested from the runtime. Each secret is placed in a
+    /// file with the name of the secret.
+    pub secrets: Vec<String>,
+}
+
 /// Mounts
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
@@ -52,20 +95,41 @@ pub enum Mount {
     /// Use a minimal dev tree
     #[serde(rename = "dev")]
     Dev,
-    /// Mount a rw host directory dedicated to this container rw
+    /// Mount a read only overlay of directories from resources
+    #[serde(rename = "overlay")]
+    Overlay(Overlay),
+    /// Mount a rw host directory dedicated to this container or its share group
     #[serde(rename = "persist")]
-    Persist,
+    Persist(Persist),
     /// Mount proc
     #[serde(rename = "proc")]
     Proc,
     /// Mount a directory from a resource
     #[serde(rename = "resource")]
     Resource(Resource),
+    /// Mount a read only tmpfs with secrets provided by the runtime
+    #[serde(rename = "secret")]
+    Secret(Secret),
     /// Mount a tmpfs with size
     #[serde(rename = "tmpfs")]
     Tmpfs(Tmpfs),
 }
 
+impl Mount {
+    /// Resource containers required by this mount
+    pub fn resources(&self) -> Vec<(&Name, &VersionReq)> {
+        match self {
+            Mount::Resource(resource) => vec![(&resource.name, &resource.version)],
+            Mount::Overlay(overlay) => overlay
+                .layers
+                .iter()
+                .map(|layer| (&layer.name, &layer.version))
+                .collect(),
+            _ => Vec::with_capacity(0),
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 #[allow(missing_docs)]
 /// Mount option
@@ -136,7 +200,7 @@ impl Serialize for MountOptions {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.0.iter().map(ToString::to_string).join(","))
+        serializer.serialize_str(&self.0.iter().map(ToString::to_string).sorted().join(","))
     }
 }
 
@@ -180,7 +244,15 @@ impl<'de> Deserialize<'de> for MountOptions {
     }
 }
 
-fn deserialize_tmpfs_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+pub(super) fn deserialize_optional_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    deserialize_size(deserializer).map(Some)
+}
+
+pub(super) fn deserialize_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u64, D::Error> {
     struct SizeVisitor;
 
     impl<'de> Visitor<'de> for SizeVisitor {
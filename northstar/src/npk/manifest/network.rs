@@ -0,0 +1,273 @@
+use crate::common::{name::Name, non_nul_string::NonNulString};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, SchemaObject},
+    JsonSchema,
+};
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// Network configuration. Containers use the network namespace of the runtime
+/// unless `namespace` is set. The listed host interfaces are moved into the
+/// namespace and moved back when the container exits.
+/// ```yaml
+/// network:
+///   namespace: true
+///   interfaces:
+///     - can0
+/// ```
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Network {
+    /// Create a network namespace with a loopback interface for the container
+    #[serde(default, skip_serializing_if = "super::is_default")]
+    pub namespace: bool,
+    /// Host interfaces moved into the network namespace of the container
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interfaces: Vec<NonNulString>,
+    /// Allow list of outgoing packets. All outgoing IP packets are allowed if unset.
+    pub egress: Option<Vec<Rule>>,
+    /// Allow list of incoming packets. All incoming IP packets are allowed if unset.
+    pub ingress: Option<Vec<Rule>>,
+    /// Bandwidth limits of the IP traffic
+    pub bandwidth: Option<Bandwidth>,
+}
+
+impl Network {
+    /// Maximum length of an interface name
+    pub const INTERFACE_NAME_MAX: usize = 15;
+}
+
+/// Bandwidth limits in bits per second. Packets that exceed the rate are dropped.
+/// ```yaml
+/// network:
+///   bandwidth:
+///     egress: 1Mbit
+///     ingress: 5Mbit
+/// ```
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Bandwidth {
+    /// Rate of outgoing packets
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_rate",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub egress: Option<u64>,
+    /// Rate of incoming packets
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_rate",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ingress: Option<u64>,
+}
+
+impl Bandwidth {
+    /// Maximum rate in bits per second
+    pub const MAX: u64 = 100_000_000_000;
+}
+
+/// Filter rule. A packet matches a rule if it matches all fields of the rule. The
+/// address is the remote address: the destination of outgoing and the source of
+/// incoming packets. The port is the destination port of the packet.
+/// ```yaml
+/// network:
+///   egress:
+///     - address: 10.0.0.0/8
+///       port: 443
+///       protocol: tcp
+///     - address: 127.0.0.1
+/// ```
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Address or network of the remote end
+    pub address: Option<Cidr>,
+    /// Destination port
+    pub port: Option<u16>,
+    /// Transport protocol
+    pub protocol: Option<Protocol>,
+}
+
+/// Service offered by a container. The services of started containers are listed
+/// by the runtime so that other containers and tools can discover them.
+/// ```yaml
+/// services:
+///   - name: telemetry
+///     port: 8080
+///     protocol: tcp
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Service {
+    /// Service name
+    pub name: Name,
+    /// Port the service listens on
+    pub port: u16,
+    /// Transport protocol
+    pub protocol: Protocol,
+}
+
+/// Transport protocol
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    /// TCP
+    Tcp,
+    /// UDP
+    Udp,
+}
+
+/// IP address with an optional prefix length e.g. `192.168.0.0/16` or `fd00::1`
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Cidr {
+    /// Address
+    pub address: IpAddr,
+    /// Number of leading bits of the address that are matched
+    pub prefix: u8,
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = if self.address.is_ipv4() { 32 } else { 128 };
+        if self.prefix == max {
+            write!(f, "{}", self.address)
+        } else {
+            write!(f, "{}/{}", self.address, self.prefix)
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let address = address
+            .parse::<IpAddr>()
+            .map_err(|e| format!("invalid address {}: {}", s, e))?;
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("invalid prefix length of {}", s))?,
+            None => max,
+        };
+        Ok(Cidr { address, prefix })
+    }
+}
+
+impl Serialize for Cidr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct CidrVisitor;
+
+        impl<'de> Visitor<'de> for CidrVisitor {
+            type Value = Cidr;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("IP address with an optional prefix length e.g. 10.0.0.0/8")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(CidrVisitor)
+    }
+}
+
+impl JsonSchema for Cidr {
+    fn schema_name() -> String {
+        "Cidr".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> schemars::schema::Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Parse a rate in bits per second from a number or a string with a unit e.g. `5Mbit`
+fn deserialize_optional_rate<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    struct RateVisitor;
+
+    impl<'de> Visitor<'de> for RateVisitor {
+        type Value = u64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a number of bits per second or a rate string (e.g. 5Mbit)")
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<u64, E> {
+            Ok(v)
+        }
+
+        fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<u64, E> {
+            parse_rate(v).ok_or_else(|| E::custom(format!("invalid rate {}", v)))
+        }
+    }
+
+    deserializer.deserialize_any(RateVisitor).map(Some)
+}
+
+/// Parse a rate with one of the units `bit`, `kbit`, `mbit` or `gbit`
+fn parse_rate(s: &str) -> Option<u64> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let factor = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "bit" => 1,
+        "kbit" => 1_000,
+        "mbit" => 1_000_000,
+        "gbit" => 1_000_000_000,
+        _ => return None,
+    };
+    value.parse::<u64>().ok()?.checked_mul(factor)
+}
+
+#[test]
+fn rate() {
+    assert_eq!(parse_rate("1Mbit"), Some(1_000_000));
+    assert_eq!(parse_rate("5 mbit"), Some(5_000_000));
+    assert_eq!(parse_rate("100kbit"), Some(100_000));
+    assert_eq!(parse_rate("2Gbit"), Some(2_000_000_000));
+    assert_eq!(parse_rate("64000"), Some(64_000));
+    assert_eq!(parse_rate("1MB"), None);
+    assert_eq!(parse_rate("Mbit"), None);
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn cidr() {
+    let cidr = "10.0.0.0/8".parse::<Cidr>().unwrap();
+    assert_eq!(cidr.prefix, 8);
+    assert_eq!(cidr.to_string(), "10.0.0.0/8");
+    assert_eq!("fd00::1".parse::<Cidr>().unwrap().prefix, 128);
+    assert_eq!("fd00::1".parse::<Cidr>().unwrap().to_string(), "fd00::1");
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("10.0.0/8".parse::<Cidr>().is_err());
+}
@@ -0,0 +1,67 @@
+/// Namespace of a kernel parameter
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Namespace {
+    /// IPC namespace
+    Ipc,
+    /// Network namespace
+    Net,
+}
+
+/// Parameters of the IPC namespace
+const IPC: &[&str] = &[
+    "kernel.msgmax",
+    "kernel.msgmnb",
+    "kernel.msgmni",
+    "kernel.sem",
+    "kernel.shm_rmid_forced",
+    "kernel.shmall",
+    "kernel.shmmax",
+    "kernel.shmmni",
+];
+
+/// Prefix of the POSIX message queue parameters of the IPC namespace
+const MQUEUE: &str = "fs.mqueue.";
+
+/// Parameters of the network namespace that do not affect other namespaces
+const NET: &[&str] = &[
+    "net.ipv4.ip_local_port_range",
+    "net.ipv4.ip_local_reserved_ports",
+    "net.ipv4.ip_unprivileged_port_start",
+    "net.ipv4.ping_group_range",
+    "net.ipv4.tcp_fin_timeout",
+    "net.ipv4.tcp_keepalive_intvl",
+    "net.ipv4.tcp_keepalive_probes",
+    "net.ipv4.tcp_keepalive_time",
+    "net.ipv4.tcp_syncookies",
+    "net.core.somaxconn",
+];
+
+/// Namespace of the kernel parameter `key` or `None` if the parameter cannot be set
+/// by containers
+pub fn namespace(key: &str) -> Option<Namespace> {
+    if IPC.contains(&key) || key.strip_prefix(MQUEUE).map_or(false, is_name) {
+        Some(Namespace::Ipc)
+    } else if NET.contains(&key) {
+        Some(Namespace::Net)
+    } else {
+        None
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+}
+
+#[test]
+fn namespaces() {
+    assert_eq!(namespace("kernel.msgmax"), Some(Namespace::Ipc));
+    assert_eq!(namespace("fs.mqueue.msg_max"), Some(Namespace::Ipc));
+    assert_eq!(namespace("fs.mqueue."), None);
+    assert_eq!(namespace("fs.mqueue.../x"), None);
+    assert_eq!(
+        namespace("net.ipv4.ip_unprivileged_port_start"),
+        Some(Namespace::Net)
+    );
+    assert_eq!(namespace("kernel.hostname"), None);
+    assert_eq!(namespace("vm.overcommit_memory"), None);
+}
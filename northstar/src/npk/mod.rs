@@ -3,6 +3,9 @@ use crate::common::version::Version;
 /// dm-verity for integrity checking of block devices
 pub(crate) mod dm_verity;
 
+/// Signed repository index
+pub mod index;
+
 /// Container manifest
 pub mod manifest;
 
@@ -1,16 +1,22 @@
 use crate::{
     common::version::Version,
     npk::{
-        dm_verity::{append_dm_verity_block, Error as VerityError, VerityHeader, BLOCK_SIZE},
+        dm_verity::{
+            append_dm_verity_block, append_dm_verity_block_reproducible, Error as VerityError,
+            VerityHeader, BLOCK_SIZE,
+        },
         manifest::{
+            ipc::DBus,
             mount::{Bind, Mount, MountOption},
-            Manifest,
+            Manifest, Time,
         },
     },
 };
+use byteorder::{ByteOrder, LittleEndian};
 use ed25519_dalek::{Keypair, PublicKey, SecretKey, SignatureError, Signer, SECRET_KEY_LENGTH};
 use itertools::Itertools;
 use rand_core::{OsRng, RngCore};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{
@@ -39,13 +45,34 @@ pub const FS_IMG_NAME: &str = "fs.img";
 pub const MANIFEST_NAME: &str = "manifest.yaml";
 /// Signature file name
 pub const SIGNATURE_NAME: &str = "signature.yaml";
+/// Software bill of materials file name
+pub const SBOM_NAME: &str = "sbom.json";
 /// NPK extension
 pub const NPK_EXT: &str = "npk";
 
+/// Maximum size of the manifest
+pub const MANIFEST_SIZE_MAX: u64 = 256 * 1024;
+/// Maximum size of the signature file
+pub const SIGNATURE_SIZE_MAX: u64 = 16 * 1024;
+/// Maximum size of the software bill of materials
+pub const SBOM_SIZE_MAX: u64 = 16 * 1024 * 1024;
+/// Maximum number of entries of a NPK archive
+pub const ARCHIVE_ENTRIES_MAX: usize = 8;
+
 /// Minimum mksquashfs major version supported
 const MKSQUASHFS_MAJOR_VERSION_MIN: u64 = 4;
 /// Minimum mksquashfs minor version supported
 const MKSQUASHFS_MINOR_VERSION_MIN: u64 = 1;
+/// Minimum mksquashfs version that supports fixed timestamps for reproducible images
+const MKSQUASHFS_REPRODUCIBLE_VERSION_MIN: (u64, u64) = (4, 4);
+/// Minimum squashfs block size
+const SQUASHFS_BLOCK_SIZE_MIN: u32 = 4096;
+/// Maximum squashfs block size
+const SQUASHFS_BLOCK_SIZE_MAX: u32 = 1024 * 1024;
+/// Squashfs superblock magic ("hsqs")
+const SQUASHFS_MAGIC: &[u8; 4] = b"hsqs";
+/// Size of the squashfs superblock
+const SQUASHFS_SUPERBLOCK_SIZE: usize = 96;
 
 type Zip<R> = ZipArchive<R>;
 
@@ -91,10 +118,20 @@ pub enum Error {
     InvalidCompressionAlgorithm,
     #[error("version mismatch {0} vs {1}")]
     Version(Version, Version),
+    #[error("not reproducible: {0}")]
+    NotReproducible(String),
+    #[error("sbom error: {0}")]
+    Sbom(String),
+    #[error("revoked: {0}")]
+    Revoked(String),
+    #[error("limit exceeded: {context} (limit {limit})")]
+    Limit { context: String, limit: u64 },
+    #[error("invalid fs image: {0}")]
+    FsImage(String),
 }
 
 impl Error {
-    fn io<T: ToString>(context: T, error: io::Error) -> Error {
+    pub(crate) fn io<T: ToString>(context: T, error: io::Error) -> Error {
         Error::Io {
             context: context.to_string(),
             error,
@@ -118,6 +155,8 @@ pub struct Hashes {
     pub fs_verity_hash: String,
     /// Offset of the verity block within the fs image
     pub fs_verity_offset: u64,
+    /// Hash of the sbom.json if present
+    pub sbom_hash: Option<String>,
 }
 
 impl FromStr for Hashes {
@@ -143,6 +182,8 @@ impl FromStr for Hashes {
             manifest: ManifestHash,
             #[serde(rename = "fs.img")]
             fs: FsHash,
+            #[serde(rename = "sbom.json", default)]
+            sbom: Option<ManifestHash>,
         }
 
         let hashes = serde_yaml::from_str::<SerdeHashes>(s)
@@ -152,6 +193,49 @@ impl FromStr for Hashes {
             manifest_hash: hashes.manifest.hash,
             fs_verity_hash: hashes.fs.verity_hash,
             fs_verity_offset: hashes.fs.verity_offset,
+            sbom_hash: hashes.sbom.map(|sbom| sbom.hash),
+        })
+    }
+}
+
+/// Software bill of materials format
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SbomFormat {
+    /// SPDX JSON
+    Spdx,
+    /// CycloneDX JSON
+    CycloneDx,
+}
+
+/// Software bill of materials of the content of an NPK
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Sbom {
+    /// Format of `content`
+    pub format: SbomFormat,
+    /// SBOM document
+    pub content: String,
+}
+
+impl FromStr for Sbom {
+    type Err = Error;
+
+    /// Parse a SPDX or CycloneDX JSON document
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let document = serde_json::from_str::<serde_json::Value>(s)
+            .map_err(|e| Error::Sbom(format!("failed to parse sbom: {}", e)))?;
+        let format = if document.get("spdxVersion").is_some() {
+            SbomFormat::Spdx
+        } else if document.get("bomFormat").and_then(|f| f.as_str()) == Some("CycloneDX") {
+            SbomFormat::CycloneDx
+        } else {
+            return Err(Error::Sbom(
+                "unknown format. Expecting SPDX or CycloneDX JSON".into(),
+            ));
+        };
+        Ok(Sbom {
+            format,
+            content: s.to_string(),
         })
     }
 }
@@ -166,46 +250,66 @@ pub struct Npk<R> {
     fs_img_size: u64,
     verity_header: Option<VerityHeader>,
     hashes: Option<Hashes>,
+    sbom: Option<Sbom>,
+    key: Option<PublicKey>,
 }
 
 impl<R: Read + Seek> Npk<R> {
     /// Read a npk from `reader`
     pub fn from_reader(reader: R, key: Option<&PublicKey>) -> Result<Self, Error> {
+        Self::from_reader_with_keys(reader, key.map(std::slice::from_ref))
+    }
+
+    /// Read a npk from `reader` and verify the signature with one of `keys`
+    pub fn from_reader_with_keys(reader: R, keys: Option<&[PublicKey]>) -> Result<Self, Error> {
         let mut zip = Zip::new(reader).map_err(|error| Error::Zip {
             context: "failed to open NPK".to_string(),
             error,
         })?;
+        if zip.len() > ARCHIVE_ENTRIES_MAX {
+            return Err(Error::Limit {
+                context: format!("archive has {} entries", zip.len()),
+                limit: ARCHIVE_ENTRIES_MAX as u64,
+            });
+        }
 
         let meta = meta(&zip)?;
         if meta.version != VERSION {
             return Err(Error::Version(meta.version, VERSION));
         }
 
-        // Read hashes from the npk if a key is passed
-        let hashes = if let Some(key) = key {
-            let hashes = hashes(&mut zip, key)?;
-            Some(hashes)
+        // Read hashes from the npk if keys are passed
+        let (hashes, key) = if let Some(keys) = keys {
+            let (hashes, key) = hashes(&mut zip, keys)?;
+            (Some(hashes), Some(key))
         } else {
-            None
+            (None, None)
         };
 
         let manifest = manifest(&mut zip, hashes.as_ref())?;
+        let sbom = sbom(&mut zip, hashes.as_ref())?;
         let (fs_img_offset, fs_img_size) = {
             let fs_img = &zip.by_name(FS_IMG_NAME).map_err(|e| Error::Zip {
                 context: format!("failed to locate {} in ZIP file", &FS_IMG_NAME),
                 error: e,
             })?;
+            // The image is mounted from within the archive
+            if fs_img.compression() != zip::CompressionMethod::Stored {
+                return Err(Error::FsImage(format!("{} is compressed", FS_IMG_NAME)));
+            }
             (fs_img.data_start(), fs_img.size())
         };
 
         let mut file = zip.into_inner();
         let verity_header = match &hashes {
             Some(hs) => {
-                file.seek(SeekFrom::Start(fs_img_offset + hs.fs_verity_offset))
-                    .map_err(|e| Error::Io {
-                        context: format!("{} too small to extract verity header", &FS_IMG_NAME),
-                        error: e,
-                    })?;
+                let offset = fs_img_offset
+                    .checked_add(hs.fs_verity_offset)
+                    .ok_or_else(|| Error::MalformedHashes("invalid verity offset".into()))?;
+                file.seek(SeekFrom::Start(offset)).map_err(|e| Error::Io {
+                    context: format!("{} too small to extract verity header", &FS_IMG_NAME),
+                    error: e,
+                })?;
                 Some(VerityHeader::from_bytes(&mut file).map_err(Error::Verity)?)
             }
             None => None,
@@ -219,6 +323,8 @@ impl<R: Read + Seek> Npk<R> {
             fs_img_size,
             verity_header,
             hashes,
+            sbom,
+            key,
         })
     }
 
@@ -236,6 +342,20 @@ impl<R: Read + Seek> Npk<R> {
             .and_then(|r| Npk::from_reader(r, key))
     }
 
+    /// Load manifest from `npk` and verify the signature with one of `keys`
+    pub fn from_path_with_keys(
+        npk: &Path,
+        keys: Option<&[PublicKey]>,
+    ) -> Result<Npk<BufReader<fs::File>>, Error> {
+        fs::File::open(npk)
+            .map_err(|error| Error::Io {
+                context: format!("Open file {}", npk.display()),
+                error,
+            })
+            .map(BufReader::new)
+            .and_then(|r| Npk::from_reader_with_keys(r, keys))
+    }
+
     /// Meta information
     pub fn meta(&self) -> &Meta {
         &self.meta
@@ -270,6 +390,63 @@ impl<R: Read + Seek> Npk<R> {
     pub fn verity_header(&self) -> Option<&VerityHeader> {
         self.verity_header.as_ref()
     }
+
+    /// Software bill of materials
+    pub fn sbom(&self) -> Option<&Sbom> {
+        self.sbom.as_ref()
+    }
+
+    /// Key that verified the signature
+    pub fn key(&self) -> Option<&PublicKey> {
+        self.key.as_ref()
+    }
+
+    /// Check the layout of the NPK before it is installed: The fs image is within the
+    /// archive, its squashfs superblock is sane and the verity header matches the
+    /// signed hashes. The content of the fs image is not verified.
+    pub fn validate(&mut self) -> Result<(), Error> {
+        let len = self
+            .file
+            .seek(SeekFrom::End(0))
+            .map_err(|e| Error::io("failed to seek NPK", e))?;
+        match self.fs_img_offset.checked_add(self.fs_img_size) {
+            Some(end) if end <= len => (),
+            _ => {
+                return Err(Error::FsImage(format!(
+                    "{} exceeds the archive",
+                    FS_IMG_NAME
+                )))
+            }
+        }
+
+        // The squashfs image ends before the verity header
+        let squashfs_size = match &self.hashes {
+            Some(hashes) => {
+                match hashes.fs_verity_offset.checked_add(BLOCK_SIZE as u64) {
+                    Some(end) if end <= self.fs_img_size => (),
+                    _ => {
+                        return Err(Error::FsImage(format!(
+                            "verity offset {} exceeds {}",
+                            hashes.fs_verity_offset, FS_IMG_NAME
+                        )))
+                    }
+                }
+                hashes.fs_verity_offset
+            }
+            None => self.fs_img_size,
+        };
+
+        self.file
+            .seek(SeekFrom::Start(self.fs_img_offset))
+            .map_err(|e| Error::io("failed to seek squashfs superblock", e))?;
+        check_superblock(&mut self.file, squashfs_size)?;
+
+        if let (Some(hashes), Some(header)) = (&self.hashes, &self.verity_header) {
+            check_verity_header(header, hashes)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl AsRawFd for Npk<BufReader<fs::File>> {
@@ -282,9 +459,12 @@ fn meta<R: Read + Seek>(zip: &Zip<R>) -> Result<Meta, Error> {
     serde_yaml::from_slice(zip.comment()).map_err(|e| Error::MalformedComment(e.to_string()))
 }
 
-fn hashes<R: Read + Seek>(zip: &mut Zip<R>, key: &PublicKey) -> Result<Hashes, Error> {
+fn hashes<R: Read + Seek>(
+    zip: &mut Zip<R>,
+    keys: &[PublicKey],
+) -> Result<(Hashes, PublicKey), Error> {
     // Read the signature file from the zip
-    let signature_content = read_to_string(zip, SIGNATURE_NAME)?;
+    let signature_content = read_to_string(zip, SIGNATURE_NAME, SIGNATURE_SIZE_MAX)?;
 
     // Split the two yaml components
     let mut documents = signature_content.split("---");
@@ -298,14 +478,22 @@ fn hashes<R: Read + Seek>(zip: &mut Zip<R>, key: &PublicKey) -> Result<Hashes, E
         .ok_or_else(|| Error::InvalidSignature("malformed signatures file".to_string()))?;
     let signature = decode_signature(signature)?;
 
-    key.verify_strict(hashes_str.as_bytes(), &signature)
-        .map_err(|e| Error::InvalidSignature(format!("invalid signature: {}", e)))?;
-
-    Ok(hashes)
+    // The first key that verifies the signature is used
+    let mut error = None;
+    for key in keys {
+        match key.verify_strict(hashes_str.as_bytes(), &signature) {
+            Ok(()) => return Ok((hashes, *key)),
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(Error::InvalidSignature(match error {
+        Some(e) => format!("invalid signature: {}", e),
+        None => "no key to verify the signature".to_string(),
+    }))
 }
 
 fn manifest<R: Read + Seek>(zip: &mut Zip<R>, hashes: Option<&Hashes>) -> Result<Manifest, Error> {
-    let content = read_to_string(zip, MANIFEST_NAME)?;
+    let content = read_to_string(zip, MANIFEST_NAME, MANIFEST_SIZE_MAX)?;
     if let Some(Hashes { manifest_hash, .. }) = &hashes {
         let expected_hash = hex::decode(manifest_hash)
             .map_err(|e| Error::Manifest(format!("failed to parse manifest hash {}", e)))?;
@@ -322,19 +510,128 @@ fn manifest<R: Read + Seek>(zip: &mut Zip<R>, hashes: Option<&Hashes>) -> Result
         .map_err(|e| Error::Manifest(format!("failed to parse manifest: {}", e)))
 }
 
-fn read_to_string<R: Read + Seek>(zip: &mut Zip<R>, name: &str) -> Result<String, Error> {
-    let mut file = zip.by_name(name).map_err(|error| Error::Zip {
+fn sbom<R: Read + Seek>(zip: &mut Zip<R>, hashes: Option<&Hashes>) -> Result<Option<Sbom>, Error> {
+    let expected_hash = hashes.and_then(|hashes| hashes.sbom_hash.as_ref());
+    if !zip.file_names().any(|name| name == SBOM_NAME) {
+        return match expected_hash {
+            Some(_) => Err(Error::Sbom("signed sbom is missing".into())),
+            None => Ok(None),
+        };
+    }
+
+    let content = read_to_string(zip, SBOM_NAME, SBOM_SIZE_MAX)?;
+    if let Some(expected_hash) = expected_hash {
+        let actual_hash = hex::encode(Sha256::digest(content.as_bytes()));
+        if expected_hash != &actual_hash {
+            return Err(Error::Sbom(format!(
+                "invalid sbom hash (expected={} actual={})",
+                expected_hash, actual_hash
+            )));
+        }
+    }
+    Sbom::from_str(&content).map(Some)
+}
+
+/// Read the entry `name` that must not exceed `limit` bytes. The size declared in the
+/// archive is not trusted: Compressed entries can expand beyond it.
+fn read_to_string<R: Read + Seek>(
+    zip: &mut Zip<R>,
+    name: &str,
+    limit: u64,
+) -> Result<String, Error> {
+    let file = zip.by_name(name).map_err(|error| Error::Zip {
         context: format!("failed to locate {} in ZIP file", name),
         error,
     })?;
-    let mut content = String::with_capacity(file.size() as usize);
-    file.read_to_string(&mut content).map_err(|e| Error::Io {
-        context: format!("failed to read from {}", name),
-        error: e,
-    })?;
+    let exceeded = || Error::Limit {
+        context: format!("size of {}", name),
+        limit,
+    };
+    if file.size() > limit {
+        return Err(exceeded());
+    }
+    let mut content = String::new();
+    file.take(limit + 1)
+        .read_to_string(&mut content)
+        .map_err(|e| Error::Io {
+            context: format!("failed to read from {}", name),
+            error: e,
+        })?;
+    if content.len() as u64 > limit {
+        return Err(exceeded());
+    }
     Ok(content)
 }
 
+/// Sanity check of the squashfs superblock at the current position of `reader`. The
+/// image must not use more than `size` bytes.
+fn check_superblock<R: Read>(reader: &mut R, size: u64) -> Result<(), Error> {
+    let mut superblock = [0u8; SQUASHFS_SUPERBLOCK_SIZE];
+    reader
+        .read_exact(&mut superblock)
+        .map_err(|e| Error::io("failed to read squashfs superblock", e))?;
+
+    if &superblock[0..4] != SQUASHFS_MAGIC {
+        return Err(Error::Squashfs("invalid superblock magic".into()));
+    }
+    let major = LittleEndian::read_u16(&superblock[28..30]);
+    let minor = LittleEndian::read_u16(&superblock[30..32]);
+    if (major, minor) != (4, 0) {
+        return Err(Error::Squashfs(format!(
+            "unsupported version {}.{}",
+            major, minor
+        )));
+    }
+    let block_size = LittleEndian::read_u32(&superblock[12..16]);
+    let block_log = LittleEndian::read_u16(&superblock[22..24]);
+    if !(SQUASHFS_BLOCK_SIZE_MIN..=SQUASHFS_BLOCK_SIZE_MAX).contains(&block_size)
+        || 1u32.checked_shl(block_log.into()) != Some(block_size)
+    {
+        return Err(Error::Squashfs(format!(
+            "invalid block size {} (log {})",
+            block_size, block_log
+        )));
+    }
+    let compression = LittleEndian::read_u16(&superblock[20..22]);
+    if CompressionAlgorithm::from_squashfs_id(compression).is_none() {
+        return Err(Error::Squashfs(format!(
+            "unknown compression {}",
+            compression
+        )));
+    }
+    let bytes_used = LittleEndian::read_u64(&superblock[40..48]);
+    if bytes_used > size {
+        return Err(Error::Squashfs(format!(
+            "image size {} exceeds {} bytes",
+            bytes_used, size
+        )));
+    }
+    Ok(())
+}
+
+/// Check that `header` describes a sha256 verity tree of the image in `hashes`
+fn check_verity_header(header: &VerityHeader, hashes: &Hashes) -> Result<(), Error> {
+    if !header.header.starts_with(VerityHeader::HEADER) {
+        return Err(Error::Verity(VerityError::InvalidHeader));
+    }
+    if header.version != VerityHeader::VERITY_VERSION {
+        return Err(Error::Verity(VerityError::UnsupportedVersion(
+            header.version,
+        )));
+    }
+    if !header.algorithm.starts_with(VerityHeader::ALGORITHM) {
+        return Err(Error::Verity(VerityError::UnsupportedAlgorithm()));
+    }
+    let block_size = BLOCK_SIZE as u32;
+    if header.data_block_size != block_size
+        || header.hash_block_size != block_size
+        || header.data_blocks.checked_mul(BLOCK_SIZE as u64) != Some(hashes.fs_verity_offset)
+    {
+        return Err(Error::Verity(VerityError::InvalidHeader));
+    }
+    Ok(())
+}
+
 fn decode_signature(s: &str) -> Result<ed25519_dalek::Signature, Error> {
     #[allow(unused)]
     #[derive(Debug, Deserialize)]
@@ -360,6 +657,8 @@ struct Builder {
     manifest: Manifest,
     key: Option<PathBuf>,
     squashfs_options: SquashfsOptions,
+    reproducible: bool,
+    sbom: Option<PathBuf>,
 }
 
 impl Builder {
@@ -369,6 +668,8 @@ impl Builder {
             manifest,
             key: None,
             squashfs_options: SquashfsOptions::default(),
+            reproducible: false,
+            sbom: None,
         }
     }
 
@@ -382,6 +683,16 @@ impl Builder {
         self
     }
 
+    fn reproducible(mut self, reproducible: bool) -> Builder {
+        self.reproducible = reproducible;
+        self
+    }
+
+    fn sbom(mut self, sbom: &Path) -> Builder {
+        self.sbom = Some(sbom.to_path_buf());
+        self
+    }
+
     fn build<W: Write + Seek>(&self, writer: W) -> Result<(), Error> {
         // Create squashfs image
         let tmp = tempfile::TempDir::new().map_err(|e| Error::Io {
@@ -389,33 +700,81 @@ impl Builder {
             error: e,
         })?;
         let fsimg = tmp.path().join(FS_IMG_NAME);
-        create_squashfs_img(&self.manifest, &self.root, &fsimg, &self.squashfs_options)?;
+        create_squashfs_img(
+            &self.manifest,
+            &self.root,
+            &fsimg,
+            &self.squashfs_options,
+            self.reproducible,
+        )?;
+
+        // The manifest is serialized once. The signature covers exactly the written bytes.
+        let manifest = if self.reproducible {
+            canonical_manifest(&self.manifest)?
+        } else {
+            self.manifest.to_string()
+        };
+
+        // The sbom is added as is after checking the format
+        let sbom = match &self.sbom {
+            Some(sbom) => {
+                let content = fs::read_to_string(sbom)
+                    .map_err(|e| Error::io(format!("failed to read '{}'", sbom.display()), e))?;
+                Some(Sbom::from_str(&content)?.content)
+            }
+            None => None,
+        };
+        let sbom = sbom.as_deref();
 
         // Sign and write NPK
         if let Some(key) = &self.key {
-            let signature = signature(key, &fsimg, &self.manifest)?;
-            write_npk(writer, &self.manifest, &fsimg, Some(&signature))
+            let signature = signature(key, &fsimg, &manifest, sbom, self.reproducible)?;
+            write_npk(
+                writer,
+                &manifest,
+                &fsimg,
+                sbom,
+                Some(&signature),
+                self.reproducible,
+            )
         } else {
-            write_npk(writer, &self.manifest, &fsimg, None)
+            write_npk(writer, &manifest, &fsimg, sbom, None, self.reproducible)
         }
     }
 }
 
 /// Squashfs compression algorithm
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[allow(missing_docs)]
 pub enum CompressionAlgorithm {
     Gzip,
+    Lz4,
     Lzma,
     Lzo,
     Xz,
     Zstd,
 }
 
+impl CompressionAlgorithm {
+    /// Compression algorithm for the compressor id stored in a squashfs superblock
+    pub fn from_squashfs_id(id: u16) -> Option<CompressionAlgorithm> {
+        match id {
+            1 => Some(CompressionAlgorithm::Gzip),
+            2 => Some(CompressionAlgorithm::Lzma),
+            3 => Some(CompressionAlgorithm::Lzo),
+            4 => Some(CompressionAlgorithm::Xz),
+            5 => Some(CompressionAlgorithm::Lz4),
+            6 => Some(CompressionAlgorithm::Zstd),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for CompressionAlgorithm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CompressionAlgorithm::Gzip => write!(f, "gzip"),
+            CompressionAlgorithm::Lz4 => write!(f, "lz4"),
             CompressionAlgorithm::Lzma => write!(f, "lzma"),
             CompressionAlgorithm::Lzo => write!(f, "lzo"),
             CompressionAlgorithm::Xz => write!(f, "xz"),
@@ -430,6 +789,7 @@ impl FromStr for CompressionAlgorithm {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "gzip" => Ok(CompressionAlgorithm::Gzip),
+            "lz4" => Ok(CompressionAlgorithm::Lz4),
             "lzma" => Ok(CompressionAlgorithm::Lzma),
             "lzo" => Ok(CompressionAlgorithm::Lzo),
             "xz" => Ok(CompressionAlgorithm::Xz),
@@ -480,7 +840,7 @@ impl Default for SquashfsOptions {
 /// --out target/northstar/repository \
 /// --key examples/keys/northstar.key \
 pub fn pack(manifest: &Path, root: &Path, out: &Path, key: Option<&Path>) -> Result<(), Error> {
-    pack_with(manifest, root, out, key, SquashfsOptions::default())
+    pack_with(manifest, root, out, key, SquashfsOptions::default(), None)
 }
 
 /// Create an NPK with special `squashfs` options
@@ -493,6 +853,7 @@ pub fn pack(manifest: &Path, root: &Path, out: &Path, key: Option<&Path>) -> Res
 /// * `out` - Target directory or filename of the packed NPK
 /// * `key` - Path to the key used to sign the package
 /// * `squashfs_opts` - Options for `mksquashfs`
+/// * `sbom` - Optional SPDX or CycloneDX JSON software bill of materials
 ///
 /// # Example
 ///
@@ -511,6 +872,114 @@ pub fn pack_with(
     out: &Path,
     key: Option<&Path>,
     squashfs_opts: SquashfsOptions,
+    sbom: Option<&Path>,
+) -> Result<(), Error> {
+    pack_npk(manifest, root, out, key, squashfs_opts, sbom, false)
+}
+
+/// Create an NPK that is byte for byte identical for identical inputs. Timestamps are
+/// fixed, entries are sorted, the squashfs options are stable and uid and gid are
+/// normalized to the values of the manifest. The dm-verity salt is derived from the
+/// filesystem image. Requires mksquashfs 4.4 or newer.
+///
+/// # Arguments
+/// * `manifest` - Path to the container's manifest file
+/// * `root` - Path to the container's root directory
+/// * `out` - Target directory or filename of the packed NPK
+/// * `key` - Path to the key used to sign the package
+/// * `squashfs_opts` - Options for `mksquashfs`
+/// * `sbom` - Optional SPDX or CycloneDX JSON software bill of materials
+///
+/// # Example
+///
+/// sextant pack \
+/// --manifest examples/hello/manifest.yaml \
+/// --root examples/hello/root \
+/// --out target/northstar/repository \
+/// --key examples/keys/northstar.key \
+/// --reproducible \
+pub fn pack_reproducible(
+    manifest: &Path,
+    root: &Path,
+    out: &Path,
+    key: Option<&Path>,
+    squashfs_opts: SquashfsOptions,
+    sbom: Option<&Path>,
+) -> Result<(), Error> {
+    pack_npk(manifest, root, out, key, squashfs_opts, sbom, true)
+}
+
+/// Pack `manifest` and `root` with `pack_reproducible` into a temporary file and compare
+/// the result with `npk` byte for byte. Returns `Error::NotReproducible` with the first
+/// differing entry if the NPKs are not identical.
+///
+/// # Arguments
+/// * `npk` - Path to the NPK to verify
+/// * `manifest` - Path to the container's manifest file
+/// * `root` - Path to the container's root directory
+/// * `key` - Path to the key used to sign the package
+/// * `squashfs_opts` - Options for `mksquashfs`
+/// * `sbom` - Optional SPDX or CycloneDX JSON software bill of materials
+pub fn verify_reproducible(
+    npk: &Path,
+    manifest: &Path,
+    root: &Path,
+    key: Option<&Path>,
+    squashfs_opts: SquashfsOptions,
+    sbom: Option<&Path>,
+) -> Result<(), Error> {
+    let tmp = tempfile::TempDir::new()
+        .map_err(|e| Error::io("failed to create temporary directory", e))?;
+    let repacked = tmp.path().join("repacked.npk");
+    pack_reproducible(manifest, root, &repacked, key, squashfs_opts, sbom)?;
+
+    let expected = fs::read(&repacked).map_err(|e| Error::io("failed to read repacked NPK", e))?;
+    let actual =
+        fs::read(&npk).map_err(|e| Error::io(format!("failed to read '{}'", npk.display()), e))?;
+    if expected == actual {
+        return Ok(());
+    }
+
+    // Find the first differing entry for a useful error message
+    let mut expected = ZipArchive::new(io::Cursor::new(expected)).map_err(|error| Error::Zip {
+        context: "failed to parse repacked NPK".to_string(),
+        error,
+    })?;
+    let mut actual = ZipArchive::new(io::Cursor::new(actual)).map_err(|error| Error::Zip {
+        context: format!("failed to parse ZIP format: '{}'", npk.display()),
+        error,
+    })?;
+    let names = expected.file_names().map(ToString::to_string).collect_vec();
+    if names != actual.file_names().map(ToString::to_string).collect_vec() {
+        return Err(Error::NotReproducible("entries differ".to_string()));
+    }
+    for name in names {
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        || -> Result<(), ZipError> {
+            expected.by_name(&name)?.read_to_end(&mut a)?;
+            actual.by_name(&name)?.read_to_end(&mut b)?;
+            Ok(())
+        }()
+        .map_err(|error| Error::Zip {
+            context: format!("failed to read {}", name),
+            error,
+        })?;
+        if a != b {
+            return Err(Error::NotReproducible(format!("{} differs", name)));
+        }
+    }
+    Err(Error::NotReproducible("archive layout differs".to_string()))
+}
+
+fn pack_npk(
+    manifest: &Path,
+    root: &Path,
+    out: &Path,
+    key: Option<&Path>,
+    squashfs_opts: SquashfsOptions,
+    sbom: Option<&Path>,
+    reproducible: bool,
 ) -> Result<(), Error> {
     let manifest = read_manifest(manifest)?;
     let name = manifest.name.clone();
@@ -519,7 +988,12 @@ pub fn pack_with(
     if let Some(key) = key {
         builder = builder.key(key);
     }
-    builder = builder.squashfs_opts(squashfs_opts);
+    if let Some(sbom) = sbom {
+        builder = builder.sbom(sbom);
+    }
+    builder = builder
+        .squashfs_opts(squashfs_opts)
+        .reproducible(reproducible);
 
     let mut dest = out.to_path_buf();
     // Append filename from manifest if only a directory path was given
@@ -594,13 +1068,11 @@ pub fn generate_key(name: &str, out: &Path) -> Result<(), Error> {
 }
 
 fn read_manifest(path: &Path) -> Result<Manifest, Error> {
-    let file = fs::File::open(&path)
-        .map_err(|e| Error::io(format!("failed to open '{}'", &path.display()), e))?;
-    Manifest::from_reader(&file)
+    Manifest::from_path(path)
         .map_err(|e| Error::Manifest(format!("failed to parse '{}': {}", &path.display(), e)))
 }
 
-fn read_keypair(key_file: &Path) -> Result<Keypair, Error> {
+pub(crate) fn read_keypair(key_file: &Path) -> Result<Keypair, Error> {
     let mut secret_key_bytes = [0u8; SECRET_KEY_LENGTH];
     fs::File::open(&key_file)
         .map_err(|e| Error::io(format!("failed to open '{}'", &key_file.display()), e))?
@@ -630,8 +1102,13 @@ fn secret_key(mut bytes: [u8; SECRET_KEY_LENGTH]) -> Result<SecretKey, Error> {
 }
 
 /// Generate the signatures yaml file
-fn hashes_yaml(manifest_hash: &[u8], verity_hash: &[u8], verity_offset: u64) -> String {
-    format!(
+fn hashes_yaml(
+    manifest_hash: &[u8],
+    verity_hash: &[u8],
+    verity_offset: u64,
+    sbom_hash: Option<&[u8]>,
+) -> String {
+    let mut yaml = format!(
         "{}:\n  hash: {:02x?}\n\
          {}:\n  verity-hash: {:02x?}\n  verity-offset: {}\n",
         &MANIFEST_NAME,
@@ -639,16 +1116,31 @@ fn hashes_yaml(manifest_hash: &[u8], verity_hash: &[u8], verity_offset: u64) ->
         &FS_IMG_NAME,
         verity_hash.iter().format(""),
         verity_offset
-    )
+    );
+    if let Some(sbom_hash) = sbom_hash {
+        yaml.push_str(&format!(
+            "{}:\n  hash: {:02x?}\n",
+            &SBOM_NAME,
+            sbom_hash.iter().format("")
+        ));
+    }
+    yaml
 }
 
 /// Try to construct the signature yaml file
-fn signature(key: &Path, fsimg: &Path, manifest: &Manifest) -> Result<String, Error> {
+fn signature(
+    key: &Path,
+    fsimg: &Path,
+    manifest: &str,
+    sbom: Option<&str>,
+    reproducible: bool,
+) -> Result<String, Error> {
     let manifest_hash = {
         let mut sha256 = Sha256::new();
-        sha2::digest::Update::update(&mut sha256, manifest.to_string().as_bytes());
+        sha2::digest::Update::update(&mut sha256, manifest.as_bytes());
         sha256.finalize()
     };
+    let sbom_hash = sbom.map(|sbom| Sha256::digest(sbom.as_bytes()));
 
     // The size of the fs image is the offset of the verity block. The verity block
     // is appended to the fs.img
@@ -659,10 +1151,20 @@ fn signature(key: &Path, fsimg: &Path, manifest: &Manifest) -> Result<String, Er
         })?
         .len();
     // Calculate verity root hash
-    let fsimg_hash: &[u8] = &append_dm_verity_block(fsimg, fsimg_size).map_err(Error::Verity)?;
+    let fsimg_hash: &[u8] = &if reproducible {
+        append_dm_verity_block_reproducible(fsimg, fsimg_size)
+    } else {
+        append_dm_verity_block(fsimg, fsimg_size)
+    }
+    .map_err(Error::Verity)?;
 
     // Format the signatures.yaml
-    let hashes_yaml = hashes_yaml(&manifest_hash, fsimg_hash, fsimg_size);
+    let hashes_yaml = hashes_yaml(
+        &manifest_hash,
+        fsimg_hash,
+        fsimg_size,
+        sbom_hash.as_ref().map(|h| h.as_slice()),
+    );
 
     let key_pair = read_keypair(key)?;
     let signature = key_pair.sign(hashes_yaml.as_bytes());
@@ -672,6 +1174,30 @@ fn signature(key: &Path, fsimg: &Path, manifest: &Manifest) -> Result<String, Er
     Ok(signature_yaml)
 }
 
+/// Serialize `manifest` with sorted map keys. The manifest contains hash maps that
+/// are serialized in random order.
+fn canonical_manifest(manifest: &Manifest) -> Result<String, Error> {
+    fn sort(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.into_iter()
+                    .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                    .map(|(k, v)| (k, sort(v)))
+                    .collect(),
+            ),
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(sort).collect())
+            }
+            value => value,
+        }
+    }
+
+    let value = serde_json::to_value(manifest)
+        .map_err(|e| Error::Manifest(format!("failed to serialize manifest: {}", e)))?;
+    serde_yaml::to_string(&sort(value))
+        .map_err(|e| Error::Manifest(format!("failed to serialize manifest: {}", e)))
+}
+
 /// Returns a temporary file with all the pseudo file definitions
 fn pseudo_files(manifest: &Manifest) -> Result<NamedTempFile, Error> {
     let uid = manifest.uid;
@@ -704,9 +1230,11 @@ fn pseudo_files(manifest: &Manifest) -> Result<NamedTempFile, Error> {
                     };
                     pseudo_directory(target, mode)
                 }
-                Mount::Persist => pseudo_directory(target, 755),
+                Mount::Persist(_) => pseudo_directory(target, 755),
                 Mount::Proc => pseudo_directory(target, 444),
                 Mount::Resource { .. } => pseudo_directory(target, 555),
+                Mount::Overlay { .. } => pseudo_directory(target, 555),
+                Mount::Secret { .. } => pseudo_directory(target, 555),
                 Mount::Tmpfs { .. } => pseudo_directory(target, 755),
                 Mount::Dev => {
                     // Create a minimal set of chardevs:
@@ -756,6 +1284,82 @@ fn pseudo_files(manifest: &Manifest) -> Result<NamedTempFile, Error> {
                 }
             }
         })
+        // Empty files as mountpoints of the pipes
+        .chain(
+            manifest
+                .ipc
+                .iter()
+                .flat_map(|ipc| &ipc.pipes)
+                .flat_map(|pipe| {
+                    let mut pseudos = pipe
+                        .path
+                        .parent()
+                        .map(|parent| pseudo_directory(parent, 555))
+                        .unwrap_or_default();
+                    pseudos.push(format!(
+                        "{} f 444 {} {} true",
+                        pipe.path.display(),
+                        uid,
+                        gid
+                    ));
+                    pseudos
+                }),
+        )
+        // Empty files as mountpoints of the shared memory segments
+        .chain(
+            manifest
+                .ipc
+                .iter()
+                .flat_map(|ipc| &ipc.shm)
+                .flat_map(|shm| {
+                    let mut pseudos = shm
+                        .path
+                        .parent()
+                        .map(|parent| pseudo_directory(parent, 555))
+                        .unwrap_or_default();
+                    pseudos.push(format!("{} f 444 {} {} true", shm.path.display(), uid, gid));
+                    pseudos
+                }),
+        )
+        // Directories as mountpoints of sockets and links
+        .chain(
+            manifest
+                .ipc
+                .iter()
+                .flat_map(|ipc| &ipc.sockets)
+                .flat_map(|socket| pseudo_directory(socket, 755)),
+        )
+        .chain(
+            manifest
+                .ipc
+                .iter()
+                .filter(|ipc| ipc.dbus.is_some())
+                .flat_map(|_| pseudo_directory(Path::new(DBus::DIR), 555)),
+        )
+        .chain(
+            manifest
+                .links
+                .iter()
+                .flat_map(|link| pseudo_directory(&link.socket, 555)),
+        )
+        // Empty file as mountpoint of libfaketime
+        .chain(
+            manifest
+                .time
+                .iter()
+                .filter(|time| time.realtime.is_some())
+                .flat_map(|_| {
+                    let path = Path::new(Time::FAKETIME);
+                    let mut pseudos = path
+                        .parent()
+                        .map(|parent| pseudo_directory(parent, 555))
+                        .unwrap_or_default();
+                    pseudos.push(format!("{} f 444 {} {} true", path.display(), uid, gid));
+                    pseudos
+                }),
+        )
+        .sorted()
+        .dedup()
         .collect::<Vec<String>>();
 
     let mut pseudo_file_entries = NamedTempFile::new()
@@ -774,6 +1378,7 @@ fn create_squashfs_img(
     root: &Path,
     image: &Path,
     squashfs_opts: &SquashfsOptions,
+    reproducible: bool,
 ) -> Result<(), Error> {
     let pseudo_files = pseudo_files(manifest)?;
     let mksquashfs = &squashfs_opts.mksquashfs;
@@ -830,6 +1435,13 @@ fn create_squashfs_img(
             major, minor, MKSQUASHFS_MAJOR_VERSION_MIN, MKSQUASHFS_MINOR_VERSION_MIN
         )));
     }
+    let (major_reproducible, minor_reproducible) = MKSQUASHFS_REPRODUCIBLE_VERSION_MIN;
+    if reproducible && actual < Version::new(major_reproducible, minor_reproducible, 0) {
+        return Err(Error::Squashfs(format!(
+            "Detected mksquashfs version {}.{} is too old for reproducible images. The required minimum version is {}.{}",
+            major, minor, major_reproducible, minor_reproducible
+        )));
+    }
 
     // Run mksquashfs to create image
     let mut cmd = Command::new(mksquashfs);
@@ -846,8 +1458,24 @@ fn create_squashfs_img(
         .arg("-pf")
         .arg(pseudo_files.path());
     if let Some(block_size) = squashfs_opts.block_size {
+        if !block_size.is_power_of_two()
+            || !(SQUASHFS_BLOCK_SIZE_MIN..=SQUASHFS_BLOCK_SIZE_MAX).contains(&block_size)
+        {
+            return Err(Error::Squashfs(format!(
+                "Invalid block size {}. The block size must be a power of two between {} and {}",
+                block_size, SQUASHFS_BLOCK_SIZE_MIN, SQUASHFS_BLOCK_SIZE_MAX
+            )));
+        }
         cmd.arg("-b").arg(format!("{}", block_size));
     }
+    if reproducible {
+        // Fix all timestamps and omit the extended attributes of the build host
+        cmd.arg("-mkfs-time")
+            .arg("0")
+            .arg("-all-time")
+            .arg("0")
+            .arg("-no-xattrs");
+    }
     cmd.output().map_err(|e| {
         Error::Squashfs(format!(
             "failed to execute '{}': {}",
@@ -893,16 +1521,21 @@ fn unpack_squashfs(image: &Path, out: &Path, unsquashfs: &Path) -> Result<(), Er
 
 fn write_npk<W: Write + Seek>(
     npk: W,
-    manifest: &Manifest,
+    manifest: &str,
     fsimg: &Path,
+    sbom: Option<&str>,
     signature: Option<&str>,
+    reproducible: bool,
 ) -> Result<(), Error> {
     let mut fsimg = fs::File::open(&fsimg)
         .map_err(|e| Error::io(format!("failed to open '{}'", &fsimg.display()), e))?;
-    let options =
+    let mut options =
         zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
-    let manifest_string = serde_yaml::to_string(&manifest)
-        .map_err(|e| Error::Manifest(format!("failed to serialize manifest: {}", e)))?;
+    if reproducible {
+        options = options
+            .last_modified_time(zip::DateTime::default())
+            .unix_permissions(0o644);
+    }
 
     let mut zip = zip::ZipWriter::new(npk);
     zip.set_comment(
@@ -926,11 +1559,21 @@ fn write_npk<W: Write + Seek>(
             context: "failed to write manifest to NPK".to_string(),
             error: e,
         })?;
-    zip.write_all(manifest_string.as_bytes())
+    zip.write_all(manifest.as_bytes()).map_err(|e| Error::Io {
+        context: "failed to convert manifest to NPK".to_string(),
+        error: e,
+    })?;
+
+    if let Some(sbom) = sbom {
+        || -> Result<(), io::Error> {
+            zip.start_file(SBOM_NAME, options)?;
+            zip.write_all(sbom.as_bytes())
+        }()
         .map_err(|e| Error::Io {
-            context: "failed to convert manifest to NPK".to_string(),
+            context: "failed to write sbom to NPK".to_string(),
             error: e,
         })?;
+    }
 
     // We need to ensure that the fs.img start at an offset of 4096 so we add empty (zeros) ZIP
     // 'extra data' to inflate the header of the ZIP file.
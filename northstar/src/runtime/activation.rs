@@ -0,0 +1,126 @@
+use super::ipc::{owned_fd::OwnedFd, RawFdExt};
+use nix::{
+    fcntl,
+    sys::socket::{self, sockopt, AddressFamily, SockaddrLike, SockaddrStorage},
+    unistd,
+};
+use std::{
+    env, io,
+    net::TcpListener,
+    os::unix::{
+        net::UnixListener,
+        prelude::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    },
+};
+
+/// First file descriptor passed by the service manager
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Listening socket passed by the service manager
+pub(super) enum Socket {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Set the close-on-exec flag of the passed sockets. The sockets must not leak into
+/// the containers.
+pub(super) fn set_cloexec() -> io::Result<()> {
+    listen_fds()
+        .into_iter()
+        .try_for_each(|(_, fd)| fd.set_cloexec(true))
+}
+
+/// Duplicate the passed listening socket `name`. The passed socket itself stays open
+/// in the service manager and the runtime process and can be reused.
+pub(super) fn listener(name: &str) -> io::Result<Socket> {
+    let fd = listen_fds()
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, fd)| fd)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no socket named \"{}\" passed", name),
+            )
+        })?;
+    let fd = fcntl::fcntl(fd, fcntl::FcntlArg::F_DUPFD_CLOEXEC(LISTEN_FDS_START))
+        .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    socket(fd, name)
+}
+
+/// Convert the listening socket `fd` named `name` into a tcp or unix listener
+pub(super) fn socket(fd: OwnedFd, name: &str) -> io::Result<Socket> {
+    let listening = socket::getsockopt(fd.as_raw_fd(), sockopt::AcceptConn)
+        .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+    if !listening {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("socket \"{}\" is not listening", name),
+        ));
+    }
+
+    let address = socket::getsockname::<SockaddrStorage>(fd.as_raw_fd())
+        .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+    let socket = match address.family() {
+        Some(AddressFamily::Inet) | Some(AddressFamily::Inet6) => {
+            Socket::Tcp(unsafe { TcpListener::from_raw_fd(fd.into_raw_fd()) })
+        }
+        Some(AddressFamily::Unix) => {
+            Socket::Unix(unsafe { UnixListener::from_raw_fd(fd.into_raw_fd()) })
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("socket \"{}\" is not a tcp or unix socket", name),
+            ))
+        }
+    };
+    Ok(socket)
+}
+
+/// Sockets passed by the service manager e.g. with systemd socket activation. The
+/// sockets are named with `FileDescriptorName=` of the socket unit which defaults
+/// to the name of the unit.
+fn listen_fds() -> Vec<(String, RawFd)> {
+    let pid = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<i32>().ok());
+    if pid != Some(unistd::getpid().as_raw()) {
+        return Vec::new();
+    }
+
+    let count = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+    parse(count, &names)
+}
+
+/// Number `count` passed sockets and name them after the colon separated `names`
+fn parse(count: RawFd, names: &str) -> Vec<(String, RawFd)> {
+    let mut names = names.split(':');
+    (0..count)
+        .map(|n| {
+            let name = names
+                .next()
+                .filter(|name| !name.is_empty())
+                .unwrap_or("unknown");
+            (name.to_string(), LISTEN_FDS_START + n)
+        })
+        .collect()
+}
+
+#[test]
+fn parse_listen_fds() {
+    assert_eq!(
+        parse(2, "console:debug"),
+        vec![("console".to_string(), 3), ("debug".to_string(), 4)]
+    );
+    assert_eq!(
+        parse(2, "console"),
+        vec![("console".to_string(), 3), ("unknown".to_string(), 4)]
+    );
+    assert!(parse(0, "console").is_empty());
+}
@@ -0,0 +1,750 @@
+use super::error::Error;
+use crate::{
+    common::container::Container,
+    npk::manifest::{
+        network::{Protocol, Rule},
+        Manifest,
+    },
+};
+use nix::{errno::Errno, libc};
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::IpAddr,
+    os::unix::prelude::{AsRawFd, FromRawFd, RawFd},
+    path::Path,
+};
+use tracing::debug;
+
+/// `bpf` command to create a map
+const BPF_MAP_CREATE: libc::c_long = 0;
+/// `bpf` command to load a program
+const BPF_PROG_LOAD: libc::c_long = 5;
+/// `bpf` command to attach a program
+const BPF_PROG_ATTACH: libc::c_long = 8;
+/// Map type of the token buckets
+const BPF_MAP_TYPE_ARRAY: u32 = 2;
+/// Program type of cgroup socket buffer programs
+const BPF_PROG_TYPE_CGROUP_SKB: u32 = 8;
+/// Attach type of programs that filter incoming packets
+const BPF_CGROUP_INET_INGRESS: u32 = 0;
+/// Attach type of programs that filter outgoing packets
+const BPF_CGROUP_INET_EGRESS: u32 = 1;
+/// Keep programs attached to the cgroup or its parents by others
+const BPF_F_ALLOW_MULTI: u32 = 2;
+/// Size of the buffer for the verifier log of programs that fail to load
+const LOG_SIZE: usize = 64 * 1024;
+
+/// Helper functions
+const BPF_FUNC_MAP_LOOKUP_ELEM: i32 = 1;
+const BPF_FUNC_KTIME_GET_NS: i32 = 5;
+
+/// Minimum size of a token bucket in bytes. Segmentation offloading passes packets
+/// of up to 64K to the programs.
+const BUCKET_MIN: u64 = 64 * 1024;
+/// Duration of traffic at the limited rate that fits into a token bucket
+const BUCKET_NANOS: u64 = 100_000_000;
+
+/// Offsets of the `__sk_buff` fields used by the programs
+const SKB_LEN: i16 = 0;
+const SKB_PROTOCOL: i16 = 16;
+const SKB_DATA: i16 = 76;
+const SKB_DATA_END: i16 = 80;
+
+/// Direction of the packets filtered by a program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Ingress,
+    Egress,
+}
+
+/// Packet filters and bandwidth limits of a container. The filters are loaded before
+/// the container is created and attached to its cgroup. The programs are detached
+/// when the cgroup is removed.
+#[derive(Debug)]
+pub(super) struct Filters {
+    programs: Vec<(Direction, fs::File)>,
+}
+
+impl Filters {
+    /// Load the filters and bandwidth limits of the `network` section of `manifest`
+    pub(super) fn load(
+        container: &Container,
+        manifest: &Manifest,
+    ) -> Result<Option<Filters>, Error> {
+        let network = match &manifest.network {
+            Some(network)
+                if network.egress.is_some()
+                    || network.ingress.is_some()
+                    || network.bandwidth.is_some() =>
+            {
+                network
+            }
+            _ => return Ok(None),
+        };
+        if !cgroups_rs::hierarchies::is_cgroup2_unified_mode() {
+            return Err(Error::StartContainerFailed(
+                container.clone(),
+                "network filters require cgroups v2".into(),
+            ));
+        }
+        let failed = |e: String| {
+            Error::StartContainerFailed(
+                container.clone(),
+                format!("failed to load network filter: {}", e),
+            )
+        };
+
+        let bandwidth = network.bandwidth.clone().unwrap_or_default();
+        let mut programs = Vec::with_capacity(2);
+        for (direction, rules, rate) in [
+            (Direction::Ingress, &network.ingress, bandwidth.ingress),
+            (Direction::Egress, &network.egress, bandwidth.egress),
+        ] {
+            if rules.is_none() && rate.is_none() {
+                continue;
+            }
+            debug!(
+                "Loading {:?} filter of {} with {} rules and rate {:?}",
+                direction,
+                container,
+                rules.as_ref().map_or(0, Vec::len),
+                rate
+            );
+            // The map is referenced by the program and released with it
+            let bucket = rate
+                .map(|_| bucket().map_err(|e| failed(e.to_string())))
+                .transpose()?;
+            let limit = bucket.as_ref().zip(rate).map(|(map, rate)| Limit {
+                map: map.as_raw_fd(),
+                rate,
+            });
+            let program = program(direction, rules.as_deref(), limit);
+            let fd = load(&program, direction).map_err(failed)?;
+            programs.push((direction, fd));
+        }
+        Ok(Some(Filters { programs }))
+    }
+
+    /// Attach the filters to the cgroup v2 directory `cgroup`
+    pub(super) fn attach(self, cgroup: &Path) -> io::Result<()> {
+        let cgroup = fs::File::open(cgroup)?;
+        for (direction, program) in &self.programs {
+            let attach_type = match direction {
+                Direction::Ingress => BPF_CGROUP_INET_INGRESS,
+                Direction::Egress => BPF_CGROUP_INET_EGRESS,
+            };
+            let attr = ProgAttach {
+                target_fd: cgroup.as_raw_fd() as u32,
+                attach_bpf_fd: program.as_raw_fd() as u32,
+                attach_type,
+                attach_flags: BPF_F_ALLOW_MULTI,
+            };
+            bpf(BPF_PROG_ATTACH, &attr).map_err(io::Error::from)?;
+        }
+        Ok(())
+    }
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ProgLoad {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+    prog_ifindex: u32,
+    expected_attach_type: u32,
+}
+
+#[repr(C)]
+struct MapCreate {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+}
+
+#[repr(C)]
+struct ProgAttach {
+    target_fd: u32,
+    attach_bpf_fd: u32,
+    attach_type: u32,
+    attach_flags: u32,
+}
+
+/// Invoke the `bpf` system call
+fn bpf<T>(cmd: libc::c_long, attr: &T) -> Result<RawFd, Errno> {
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *const T,
+            std::mem::size_of::<T>(),
+        )
+    };
+    Errno::result(result).map(|fd| fd as RawFd)
+}
+
+/// Create a map with a single token bucket. A bucket is the time of the last
+/// refill in nanoseconds and the number of available bytes.
+fn bucket() -> Result<fs::File, Errno> {
+    let attr = MapCreate {
+        map_type: BPF_MAP_TYPE_ARRAY,
+        key_size: 4,
+        value_size: 16,
+        max_entries: 1,
+        map_flags: 0,
+    };
+    bpf(BPF_MAP_CREATE, &attr).map(|fd| unsafe { fs::File::from_raw_fd(fd) })
+}
+
+/// Load `program`. Returns the verifier log on failure.
+fn load(program: &[Insn], direction: Direction) -> Result<fs::File, String> {
+    let insns = program.iter().flat_map(Insn::encode).collect::<Vec<u8>>();
+    let license = b"Apache-2.0\0";
+    let mut attr = ProgLoad {
+        prog_type: BPF_PROG_TYPE_CGROUP_SKB,
+        insn_cnt: program.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: license.as_ptr() as u64,
+        expected_attach_type: match direction {
+            Direction::Ingress => BPF_CGROUP_INET_INGRESS,
+            Direction::Egress => BPF_CGROUP_INET_EGRESS,
+        },
+        ..Default::default()
+    };
+    attr.prog_name[..9].copy_from_slice(b"northstar");
+
+    if let Ok(fd) = bpf(BPF_PROG_LOAD, &attr) {
+        return Ok(unsafe { fs::File::from_raw_fd(fd) });
+    }
+
+    // Load again with the verifier log enabled
+    let mut log = vec![0u8; LOG_SIZE];
+    attr.log_level = 1;
+    attr.log_size = LOG_SIZE as u32;
+    attr.log_buf = log.as_mut_ptr() as u64;
+    match bpf(BPF_PROG_LOAD, &attr) {
+        Ok(fd) => Ok(unsafe { fs::File::from_raw_fd(fd) }),
+        Err(e) => {
+            let len = log.iter().position(|b| *b == 0).unwrap_or(log.len());
+            Err(format!(
+                "{}: {}",
+                e,
+                String::from_utf8_lossy(&log[..len]).trim()
+            ))
+        }
+    }
+}
+
+/// Registers
+const R0: u8 = 0;
+const R1: u8 = 1;
+const R2: u8 = 2;
+const R3: u8 = 3;
+const R4: u8 = 4;
+const R5: u8 = 5;
+const R6: u8 = 6;
+const R7: u8 = 7;
+const R8: u8 = 8;
+const R9: u8 = 9;
+const R10: u8 = 10;
+
+/// Jump targets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Label {
+    Allow,
+    Drop,
+    Pass,
+    Exceeded,
+    Refill,
+    Bucket,
+    Ipv4,
+    Ipv6,
+    Ports,
+    Rules,
+    Next(usize),
+}
+
+/// Instruction with an optional unresolved jump target
+#[derive(Clone, Copy, Debug)]
+struct Insn {
+    code: u8,
+    dst: u8,
+    src: u8,
+    off: i16,
+    imm: i32,
+    target: Option<Label>,
+}
+
+impl Insn {
+    fn encode(&self) -> [u8; 8] {
+        let mut insn = [0u8; 8];
+        insn[0] = self.code;
+        insn[1] = self.dst | self.src << 4;
+        insn[2..4].copy_from_slice(&self.off.to_le_bytes());
+        insn[4..8].copy_from_slice(&self.imm.to_le_bytes());
+        insn
+    }
+}
+
+/// Minimal assembler for the filter programs
+#[derive(Default)]
+struct Assembler {
+    insns: Vec<Insn>,
+    labels: HashMap<Label, usize>,
+}
+
+impl Assembler {
+    fn insn(&mut self, code: u8, dst: u8, src: u8, off: i16, imm: i32) {
+        self.insns.push(Insn {
+            code,
+            dst,
+            src,
+            off,
+            imm,
+            target: None,
+        });
+    }
+
+    fn jump(&mut self, code: u8, dst: u8, src: u8, imm: i32, target: Label) {
+        self.insns.push(Insn {
+            code,
+            dst,
+            src,
+            off: 0,
+            imm,
+            target: Some(target),
+        });
+    }
+
+    fn label(&mut self, label: Label) {
+        self.labels.insert(label, self.insns.len());
+    }
+
+    /// dst = src
+    fn mov(&mut self, dst: u8, src: u8) {
+        self.insn(0xbf, dst, src, 0, 0);
+    }
+
+    /// dst = imm
+    fn mov_imm(&mut self, dst: u8, imm: i32) {
+        self.insn(0xb7, dst, 0, 0, imm);
+    }
+
+    /// dst = imm as 64 bit value. The instruction takes two slots.
+    fn ld_imm64(&mut self, dst: u8, imm: u64) {
+        self.insn(0x18, dst, 0, 0, imm as u32 as i32);
+        self.insn(0, 0, 0, 0, (imm >> 32) as u32 as i32);
+    }
+
+    /// dst = map referred to by the file descriptor `fd`
+    fn ld_map_fd(&mut self, dst: u8, fd: RawFd) {
+        self.insn(0x18, dst, 1, 0, fd);
+        self.insn(0, 0, 0, 0, 0);
+    }
+
+    /// dst = imm as 32 bit value without sign extension
+    fn mov32_imm(&mut self, dst: u8, imm: u32) {
+        self.insn(0xb4, dst, 0, 0, imm as i32);
+    }
+
+    /// dst += imm
+    fn add_imm(&mut self, dst: u8, imm: i32) {
+        self.insn(0x07, dst, 0, 0, imm);
+    }
+
+    /// dst += src
+    fn add(&mut self, dst: u8, src: u8) {
+        self.insn(0x0f, dst, src, 0, 0);
+    }
+
+    /// dst -= src
+    fn sub(&mut self, dst: u8, src: u8) {
+        self.insn(0x1f, dst, src, 0, 0);
+    }
+
+    /// dst *= src
+    fn mul(&mut self, dst: u8, src: u8) {
+        self.insn(0x2f, dst, src, 0, 0);
+    }
+
+    /// dst /= imm
+    fn div_imm(&mut self, dst: u8, imm: i32) {
+        self.insn(0x37, dst, 0, 0, imm);
+    }
+
+    /// dst &= imm
+    fn and_imm(&mut self, dst: u8, imm: i32) {
+        self.insn(0x57, dst, 0, 0, imm);
+    }
+
+    /// dst &= imm on the lower 32 bits
+    fn and32_imm(&mut self, dst: u8, imm: u32) {
+        self.insn(0x54, dst, 0, 0, imm as i32);
+    }
+
+    /// dst <<= imm
+    fn lsh_imm(&mut self, dst: u8, imm: i32) {
+        self.insn(0x67, dst, 0, 0, imm);
+    }
+
+    /// dst = *(u8 *)(src + off)
+    fn load_u8(&mut self, dst: u8, src: u8, off: i16) {
+        self.insn(0x71, dst, src, off, 0);
+    }
+
+    /// dst = *(u16 *)(src + off)
+    fn load_u16(&mut self, dst: u8, src: u8, off: i16) {
+        self.insn(0x69, dst, src, off, 0);
+    }
+
+    /// dst = *(u32 *)(src + off)
+    fn load_u32(&mut self, dst: u8, src: u8, off: i16) {
+        self.insn(0x61, dst, src, off, 0);
+    }
+
+    /// dst = *(u64 *)(src + off)
+    fn load_u64(&mut self, dst: u8, src: u8, off: i16) {
+        self.insn(0x79, dst, src, off, 0);
+    }
+
+    /// *(u32 *)(dst + off) = imm
+    fn store_imm_u32(&mut self, dst: u8, off: i16, imm: i32) {
+        self.insn(0x62, dst, 0, off, imm);
+    }
+
+    /// *(u64 *)(dst + off) = src
+    fn store_u64(&mut self, dst: u8, off: i16, src: u8) {
+        self.insn(0x7b, dst, src, off, 0);
+    }
+
+    /// Call the helper function `function`
+    fn call(&mut self, function: i32) {
+        self.insn(0x85, 0, 0, 0, function);
+    }
+
+    /// goto target
+    fn ja(&mut self, target: Label) {
+        self.jump(0x05, 0, 0, 0, target);
+    }
+
+    /// if dst == imm goto target
+    fn jeq_imm(&mut self, dst: u8, imm: i32, target: Label) {
+        self.jump(0x15, dst, 0, imm, target);
+    }
+
+    /// if dst != imm goto target
+    fn jne_imm(&mut self, dst: u8, imm: i32, target: Label) {
+        self.jump(0x55, dst, 0, imm, target);
+    }
+
+    /// if dst != src goto target
+    fn jne(&mut self, dst: u8, src: u8, target: Label) {
+        self.jump(0x5d, dst, src, 0, target);
+    }
+
+    /// if dst > src goto target
+    fn jgt(&mut self, dst: u8, src: u8, target: Label) {
+        self.jump(0x2d, dst, src, 0, target);
+    }
+
+    /// if dst <= src goto target
+    fn jle(&mut self, dst: u8, src: u8, target: Label) {
+        self.jump(0xbd, dst, src, 0, target);
+    }
+
+    /// if dst < src goto target
+    fn jlt(&mut self, dst: u8, src: u8, target: Label) {
+        self.jump(0xad, dst, src, 0, target);
+    }
+
+    /// return imm
+    fn exit(&mut self, imm: i32) {
+        self.mov_imm(R0, imm);
+        self.insn(0x95, 0, 0, 0, 0);
+    }
+
+    /// Resolve the jump targets
+    fn finish(mut self) -> Vec<Insn> {
+        for (pc, insn) in self.insns.iter_mut().enumerate() {
+            if let Some(target) = insn.target.take() {
+                let target = self.labels[&target];
+                insn.off = (target as isize - pc as isize - 1) as i16;
+            }
+        }
+        self.insns
+    }
+}
+
+/// Token bucket of a program
+#[derive(Clone, Copy, Debug)]
+struct Limit {
+    /// Map with the bucket
+    map: RawFd,
+    /// Rate in bits per second
+    rate: u64,
+}
+
+/// Generate a cgroup skb program that allows IP packets that match one of `rules`
+/// and that do not exceed the rate of `limit`. Packets of other protocols are
+/// allowed. All IP packets match if there are no `rules`.
+fn program(direction: Direction, rules: Option<&[Rule]>, limit: Option<Limit>) -> Vec<Insn> {
+    let mut asm = Assembler::default();
+
+    asm.mov(R6, R1);
+    match rules {
+        Some(rules) => filter(&mut asm, direction, rules),
+        None => asm.ja(Label::Allow),
+    }
+
+    asm.label(Label::Drop);
+    asm.exit(0);
+    asm.label(Label::Allow);
+    if let Some(limit) = limit {
+        police(&mut asm, direction, limit);
+    }
+    asm.exit(1);
+
+    asm.finish()
+}
+
+/// Jump to `Label::Allow` if the packet matches one of `rules` and to `Label::Drop`
+/// otherwise.
+///
+/// Registers: r2 packet start, r3 packet end, r7 transport protocol, r9 destination
+/// port in network byte order or 0 if the packet is not a tcp or udp packet.
+fn filter(asm: &mut Assembler, direction: Direction, rules: &[Rule]) {
+    // Offsets of the remote address within the IPv4 and IPv6 headers
+    let (ipv4_address, ipv6_address) = match direction {
+        Direction::Ingress => (12, 8),
+        Direction::Egress => (16, 24),
+    };
+
+    asm.load_u32(R7, R6, SKB_PROTOCOL);
+    asm.load_u32(R2, R6, SKB_DATA);
+    asm.load_u32(R3, R6, SKB_DATA_END);
+    asm.jeq_imm(R7, (libc::ETH_P_IP as u16).to_be() as i32, Label::Ipv4);
+    asm.jeq_imm(R7, (libc::ETH_P_IPV6 as u16).to_be() as i32, Label::Ipv6);
+    asm.ja(Label::Allow);
+
+    // IPv4: the header length is variable
+    asm.label(Label::Ipv4);
+    asm.mov(R4, R2);
+    asm.add_imm(R4, 20);
+    asm.jgt(R4, R3, Label::Drop);
+    asm.load_u8(R7, R2, 9);
+    asm.load_u8(R8, R2, 0);
+    asm.and_imm(R8, 0x0f);
+    asm.lsh_imm(R8, 2);
+    asm.mov_imm(R9, 0);
+    asm.jeq_imm(R7, libc::IPPROTO_TCP, Label::Ports);
+    asm.jne_imm(R7, libc::IPPROTO_UDP, Label::Rules);
+    asm.label(Label::Ports);
+    asm.mov(R5, R2);
+    asm.add(R5, R8);
+    asm.mov(R4, R5);
+    asm.add_imm(R4, 4);
+    asm.jgt(R4, R3, Label::Rules);
+    asm.load_u16(R9, R5, 2);
+    asm.label(Label::Rules);
+    let mut next = 0;
+    for rule in rules {
+        match rule.address.map(|cidr| (cidr.address, cidr.prefix)) {
+            Some((IpAddr::V4(address), prefix)) => {
+                rule_prologue(asm, rule, next);
+                let mask = if prefix == 0 {
+                    0
+                } else {
+                    u32::MAX << (32 - prefix)
+                };
+                compare(
+                    asm,
+                    ipv4_address,
+                    &address.octets(),
+                    &mask.to_be_bytes(),
+                    next,
+                );
+            }
+            Some((IpAddr::V6(_), _)) => continue,
+            None => rule_prologue(asm, rule, next),
+        }
+        asm.ja(Label::Allow);
+        asm.label(Label::Next(next));
+        next += 1;
+    }
+    asm.ja(Label::Drop);
+
+    // IPv6: extension headers are not parsed
+    asm.label(Label::Ipv6);
+    asm.mov(R4, R2);
+    asm.add_imm(R4, 40);
+    asm.jgt(R4, R3, Label::Drop);
+    asm.load_u8(R7, R2, 6);
+    asm.mov_imm(R9, 0);
+    asm.mov(R4, R2);
+    asm.add_imm(R4, 44);
+    asm.jgt(R4, R3, Label::Next(next));
+    asm.jeq_imm(R7, libc::IPPROTO_TCP, Label::Next(next + 1));
+    asm.jne_imm(R7, libc::IPPROTO_UDP, Label::Next(next));
+    asm.label(Label::Next(next + 1));
+    asm.load_u16(R9, R2, 42);
+    asm.label(Label::Next(next));
+    next += 2;
+    for rule in rules {
+        match rule.address.map(|cidr| (cidr.address, cidr.prefix)) {
+            Some((IpAddr::V6(address), prefix)) => {
+                rule_prologue(asm, rule, next);
+                let mask = if prefix == 0 {
+                    0
+                } else {
+                    u128::MAX << (128 - prefix)
+                };
+                compare(
+                    asm,
+                    ipv6_address,
+                    &address.octets(),
+                    &mask.to_be_bytes(),
+                    next,
+                );
+            }
+            Some((IpAddr::V4(_), _)) => continue,
+            None => rule_prologue(asm, rule, next),
+        }
+        asm.ja(Label::Allow);
+        asm.label(Label::Next(next));
+        next += 1;
+    }
+    asm.ja(Label::Drop);
+}
+
+/// Take the length of the packet from the token bucket of `limit` and drop the
+/// packet if the bucket holds less. Outgoing packets are dropped with a congestion
+/// notification that makes TCP reduce its window. The bucket is not locked and
+/// concurrent packets on other cpus can exceed the rate slightly.
+///
+/// Registers: r8 bucket, r9 packet length, r0 elapsed time and refill, r2 tokens
+fn police(asm: &mut Assembler, direction: Direction, limit: Limit) {
+    let bytes = (limit.rate / 8).max(1);
+    let size = (bytes * BUCKET_NANOS / 1_000_000_000).max(BUCKET_MIN);
+    // Time to fill an empty bucket
+    let fill = (size * 1_000_000_000 + bytes - 1) / bytes;
+    let exceeded = match direction {
+        Direction::Ingress => 0,
+        Direction::Egress => 2,
+    };
+
+    asm.load_u32(R9, R6, SKB_LEN);
+    asm.store_imm_u32(R10, -4, 0);
+    asm.ld_map_fd(R1, limit.map);
+    asm.mov(R2, R10);
+    asm.add_imm(R2, -4);
+    asm.call(BPF_FUNC_MAP_LOOKUP_ELEM);
+    asm.jeq_imm(R0, 0, Label::Pass);
+    asm.mov(R8, R0);
+    asm.call(BPF_FUNC_KTIME_GET_NS);
+    asm.load_u64(R1, R8, 0);
+    asm.load_u64(R2, R8, 8);
+    asm.store_u64(R8, 0, R0);
+    asm.sub(R0, R1);
+    asm.ld_imm64(R1, fill);
+    asm.jle(R0, R1, Label::Refill);
+    asm.mov(R0, R1);
+    asm.label(Label::Refill);
+    asm.ld_imm64(R1, bytes);
+    asm.mul(R0, R1);
+    asm.div_imm(R0, 1_000_000_000);
+    asm.add(R2, R0);
+    asm.ld_imm64(R1, size);
+    asm.jle(R2, R1, Label::Bucket);
+    asm.mov(R2, R1);
+    asm.label(Label::Bucket);
+    asm.jlt(R2, R9, Label::Exceeded);
+    asm.sub(R2, R9);
+    asm.store_u64(R8, 8, R2);
+    asm.ja(Label::Pass);
+    asm.label(Label::Exceeded);
+    asm.store_u64(R8, 8, R2);
+    asm.exit(exceeded);
+    asm.label(Label::Pass);
+}
+
+/// Jump to the next rule if the protocol or port of `rule` does not match
+fn rule_prologue(asm: &mut Assembler, rule: &Rule, next: usize) {
+    if let Some(protocol) = rule.protocol {
+        let protocol = match protocol {
+            Protocol::Tcp => libc::IPPROTO_TCP,
+            Protocol::Udp => libc::IPPROTO_UDP,
+        };
+        asm.jne_imm(R7, protocol, Label::Next(next));
+    }
+    if let Some(port) = rule.port {
+        asm.jne_imm(R9, port.to_be() as i32, Label::Next(next));
+    }
+}
+
+/// Jump to the next rule if the address at `offset` in the packet masked with
+/// `mask` differs from `address`
+fn compare(asm: &mut Assembler, offset: i16, address: &[u8], mask: &[u8], next: usize) {
+    for (i, (address, mask)) in address.chunks(4).zip(mask.chunks(4)).enumerate() {
+        let mask = u32::from_ne_bytes([mask[0], mask[1], mask[2], mask[3]]);
+        if mask == 0 {
+            continue;
+        }
+        let address = u32::from_ne_bytes([address[0], address[1], address[2], address[3]]);
+        asm.load_u32(R8, R2, offset + 4 * i as i16);
+        asm.and32_imm(R8, mask);
+        asm.mov32_imm(R1, address & mask);
+        asm.jne(R8, R1, Label::Next(next));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jumps() {
+        let rules = vec![
+            Rule {
+                address: "10.0.0.0/8".parse().ok(),
+                port: Some(443),
+                protocol: Some(Protocol::Tcp),
+            },
+            Rule {
+                address: "fd00::/8".parse().ok(),
+                ..Default::default()
+            },
+            Rule::default(),
+        ];
+        let limit = Limit {
+            map: 0,
+            rate: 1_000_000,
+        };
+        for direction in [Direction::Ingress, Direction::Egress] {
+            for (rules, limit) in [
+                (Some(rules.as_slice()), None),
+                (Some(rules.as_slice()), Some(limit)),
+                (None, Some(limit)),
+            ] {
+                let program = program(direction, rules, limit);
+                // Programs end with an exit and jump forward only
+                assert_eq!(program.last().map(|i| i.code), Some(0x95));
+                for (pc, insn) in program.iter().enumerate() {
+                    if matches!(insn.code, 0x05 | 0x15 | 0x55 | 0x5d | 0x2d | 0xbd | 0xad) {
+                        assert!(insn.off >= 0);
+                        assert!(pc + 1 + (insn.off as usize) < program.len());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use super::{
+    error::{Context, Error},
+    Container,
+};
+use crate::api::model::{FsEntry, FsEntryKind, FsResponse};
+use nix::{
+    dir::Dir,
+    fcntl::{self, AtFlags, OFlag},
+    libc,
+    sys::stat::{self, FileStat, Mode, SFlag},
+};
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd},
+    path::{Component, Path},
+};
+use tokio::task;
+
+/// Maximum number of bytes returned by a single read
+const MAX_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// List the entries of the directory `path` within `base`
+pub(super) async fn list(
+    container: &Container,
+    base: &Path,
+    path: &Path,
+) -> Result<FsResponse, Error> {
+    let (base, path) = (base.to_owned(), path.to_owned());
+    task::spawn_blocking(move || list_dir(&base, &path).map_err(|e| (path, e)))
+        .await
+        .context("failed to join list task")?
+        .map(FsResponse::List)
+        .map_err(|(path, e)| error(container, &path, e))
+}
+
+/// Read up to `length` bytes at `offset` from the file `path` within `base`
+pub(super) async fn read(
+    container: &Container,
+    base: &Path,
+    path: &Path,
+    offset: u64,
+    length: u64,
+) -> Result<FsResponse, Error> {
+    let (base, path) = (base.to_owned(), path.to_owned());
+    task::spawn_blocking(move || read_file(&base, &path, offset, length).map_err(|e| (path, e)))
+        .await
+        .context("failed to join read task")?
+        .map(|(data, eof)| FsResponse::Read { data, eof })
+        .map_err(|(path, e)| error(container, &path, e))
+}
+
+fn list_dir(base: &Path, path: &Path) -> io::Result<Vec<FsEntry>> {
+    let dir = open(
+        base,
+        path,
+        SFlag::S_IFDIR,
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY,
+    )?;
+    let mut dir = Dir::from_fd(dir.into_raw_fd())?;
+    let fd = dir.as_raw_fd();
+
+    let mut entries = Vec::new();
+    for entry in dir.iter() {
+        let entry = entry?;
+        let name = entry.file_name().to_bytes();
+        if name == b"." || name == b".." {
+            continue;
+        }
+        // Do not follow symlinks that might point outside of base
+        let file_stat = stat::fstatat(fd, entry.file_name(), AtFlags::AT_SYMLINK_NOFOLLOW)?;
+        let kind = match file_type(&file_stat) {
+            SFlag::S_IFREG => FsEntryKind::File,
+            SFlag::S_IFDIR => FsEntryKind::Directory,
+            SFlag::S_IFLNK => FsEntryKind::Symlink,
+            _ => FsEntryKind::Other,
+        };
+        entries.push(FsEntry {
+            name: String::from_utf8_lossy(name).to_string(),
+            kind,
+            size: file_stat.st_size as u64,
+            mode: file_stat.st_mode & 0o7777,
+        });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(entries)
+}
+
+fn read_file(base: &Path, path: &Path, offset: u64, length: u64) -> io::Result<(Vec<u8>, bool)> {
+    let mut file = open(base, path, SFlag::S_IFREG, OFlag::O_RDONLY)?;
+    let size = file.metadata()?.len();
+
+    let length = length.min(MAX_CHUNK_SIZE).min(size.saturating_sub(offset));
+    let mut data = vec![0u8; length as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut data)?;
+    let eof = offset + length >= size;
+
+    Ok((data, eof))
+}
+
+/// Open `path` within `base` if it is of the type `kind`. The components of `path` are
+/// opened one by one relative to their parent without following symlinks: A symlink
+/// placed in a writable directory e.g. the persist directory cannot redirect the open
+/// to a location outside of `base`, even if it is replaced concurrently. The type is
+/// checked before the open in order to not block on fifos or open devices and again on
+/// the opened file.
+fn open(base: &Path, path: &Path, kind: SFlag, flags: OFlag) -> io::Result<File> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid path");
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name),
+            Component::RootDir | Component::CurDir => (),
+            Component::ParentDir | Component::Prefix(_) => return Err(invalid()),
+        }
+    }
+    let name = components.pop().unwrap_or_else(|| OsStr::new("."));
+
+    let directory = OFlag::O_PATH | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC;
+    let mut dir = open_at(None, base.as_os_str(), directory)?;
+    for component in components {
+        dir = open_at(Some(&dir), component, directory | OFlag::O_NOFOLLOW)?;
+    }
+
+    let file_stat = stat::fstatat(dir.as_raw_fd(), name, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    check_type(&file_stat, kind)?;
+
+    let flags = flags | OFlag::O_NOFOLLOW | OFlag::O_NONBLOCK | OFlag::O_CLOEXEC;
+    let file = open_at(Some(&dir), name, flags)?;
+    check_type(&stat::fstat(file.as_raw_fd())?, kind)?;
+    Ok(file)
+}
+
+/// Open `path` relative to `dir` or the current directory
+fn open_at(dir: Option<&File>, path: &OsStr, flags: OFlag) -> io::Result<File> {
+    let dir = dir.map_or(libc::AT_FDCWD, AsRawFd::as_raw_fd);
+    let fd = fcntl::openat(dir, path, flags, Mode::empty())?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn check_type(stat: &FileStat, kind: SFlag) -> io::Result<()> {
+    if file_type(stat) == kind {
+        Ok(())
+    } else if kind == SFlag::S_IFDIR {
+        Err(io::Error::new(io::ErrorKind::Other, "not a directory"))
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, "not a regular file"))
+    }
+}
+
+fn file_type(stat: &FileStat) -> SFlag {
+    SFlag::from_bits_truncate(stat.st_mode & SFlag::S_IFMT.bits())
+}
+
+fn error(container: &Container, path: &Path, error: io::Error) -> Error {
+    Error::Fs(container.clone(), format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use nix::unistd::mkfifo;
+    use std::{fs, os::unix::fs::symlink};
+
+    #[test]
+    fn symlink_escape() {
+        let outside = tempfile::TempDir::new().unwrap();
+        fs::write(outside.path().join("secret"), "secret").unwrap();
+
+        let base = tempfile::TempDir::new().unwrap();
+        fs::create_dir(base.path().join("dir")).unwrap();
+        fs::write(base.path().join("dir/file"), "hello").unwrap();
+        symlink(outside.path(), base.path().join("escape")).unwrap();
+        symlink(
+            outside.path().join("secret"),
+            base.path().join("dir/secret"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_file(base.path(), Path::new("/dir/file"), 0, 5).unwrap(),
+            (b"hello".to_vec(), true)
+        );
+
+        // Symlinks in the middle and at the end of the path are not followed
+        assert!(read_file(base.path(), Path::new("/escape/secret"), 0, 6).is_err());
+        assert!(read_file(base.path(), Path::new("/dir/secret"), 0, 6).is_err());
+        assert!(list_dir(base.path(), Path::new("/escape")).is_err());
+
+        // Symlinks are listed but not followed
+        let entries = list_dir(base.path(), Path::new("/")).unwrap();
+        let escape = entries.iter().find(|e| e.name == "escape").unwrap();
+        assert_eq!(escape.kind, FsEntryKind::Symlink);
+
+        // Parent dir components are rejected
+        assert!(read_file(base.path(), Path::new("/dir/../dir/file"), 0, 5).is_err());
+    }
+
+    #[test]
+    fn file_types() {
+        let base = tempfile::TempDir::new().unwrap();
+        fs::create_dir(base.path().join("dir")).unwrap();
+        fs::write(base.path().join("file"), "hello").unwrap();
+        mkfifo(&base.path().join("fifo"), Mode::S_IRWXU).unwrap();
+
+        // A fifo is neither read nor listed and does not block
+        assert!(read_file(base.path(), Path::new("/fifo"), 0, 5).is_err());
+        assert!(list_dir(base.path(), Path::new("/fifo")).is_err());
+
+        assert!(read_file(base.path(), Path::new("/dir"), 0, 5).is_err());
+        assert!(list_dir(base.path(), Path::new("/file")).is_err());
+        assert_eq!(list_dir(base.path(), Path::new("/")).unwrap().len(), 3);
+    }
+}
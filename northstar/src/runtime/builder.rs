@@ -0,0 +1,88 @@
+use super::{
+    activation,
+    config::Config,
+    error::{Context, Error},
+    fork, handoff, parameters,
+    start_policy::{PassThrough, StartPolicy},
+    Runtime,
+};
+use std::sync::Arc;
+
+/// Runtime builder for embedding the runtime into another process
+///
+/// ```no_run
+/// # use northstar::runtime::{config::Config, Error, Runtime};
+/// # use std::path::Path;
+/// # async fn embed(config: Config) -> Result<(), Error> {
+/// // Build the runtime *before* the tokio threadpool is spawned
+/// let runtime = Runtime::builder(config).console(false).build()?;
+///
+/// let runtime = runtime.start().await?;
+/// let handle = runtime.handle();
+/// handle.install(Path::new("hello-world.npk"), "default").await?;
+/// handle.start("hello-world:0.0.1".try_into().expect("invalid container")).await?;
+///
+/// runtime.shutdown().await
+/// # }
+/// ```
+pub struct Builder {
+    config: Config,
+    provider: Option<Arc<dyn parameters::Provider>>,
+    start_policy: Arc<dyn StartPolicy>,
+    console: bool,
+}
+
+impl Builder {
+    pub(super) fn new(config: Config) -> Builder {
+        Builder {
+            config,
+            provider: None,
+            start_policy: Arc::new(PassThrough),
+            console: true,
+        }
+    }
+
+    /// Resolve container environment parameters with `provider` instead of the
+    /// `parameters` section of the configuration
+    pub fn provider(mut self, provider: Arc<dyn parameters::Provider>) -> Builder {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Decide about container starts with `start_policy`. Default: start every
+    /// container in the default order
+    pub fn start_policy(mut self, start_policy: Arc<dyn StartPolicy>) -> Builder {
+        self.start_policy = start_policy;
+        self
+    }
+
+    /// Serve the consoles of the configuration. Embedders that use the runtime
+    /// through its `Handle` only can disable the consoles. Default: true
+    pub fn console(mut self, console: bool) -> Builder {
+        self.console = console;
+        self
+    }
+
+    /// Create the runtime. This forks the process that spawns the containers and
+    /// must be done before any threads are spawned e.g. by a tokio runtime.
+    pub fn build(self) -> Result<Runtime, Error> {
+        let provider = self
+            .provider
+            .unwrap_or_else(|| Arc::new(parameters::ConfigProvider::new(&self.config.parameters)));
+
+        // Sockets passed with socket activation must not be inherited by the forker
+        activation::set_cloexec().context("failed to set close-on-exec on passed sockets")?;
+        // The state of a previous instance if this binary is executed by a handoff
+        let handoff = handoff::take()?;
+        let (forker_pid, forker_channels) = fork::start()?;
+        Ok(Runtime::Created {
+            config: self.config,
+            provider,
+            start_policy: self.start_policy,
+            console: self.console,
+            forker_pid,
+            forker_channels,
+            handoff,
+        })
+    }
+}
@@ -7,13 +7,18 @@ use crate::{
     runtime::{CGroupEvent, ContainerEvent, Event, MemoryEvent},
 };
 use cgroups_rs::{
-    memory::MemController, BlkIoDeviceResource, BlkIoDeviceThrottleResource, BlkIoResources,
-    Controller, CpuResources, Hierarchy, MemoryResources,
+    cpu::CpuController, freezer::FreezerController, memory::MemController, BlkIoDeviceResource,
+    BlkIoDeviceThrottleResource, BlkIoResources, Controller, CpuResources, Hierarchy,
+    MemoryResources,
 };
 use futures::stream::StreamExt;
 use inotify::{Inotify, WatchMask};
-use log::{debug, info, warn};
-use std::{collections::HashMap, fmt::Debug, os::unix::io::AsRawFd, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 use tokio::{
     fs,
@@ -25,6 +30,7 @@ use tokio::{
 };
 use tokio_eventfd::EventFd;
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -34,6 +40,9 @@ pub enum Error {
     CGroups(String),
 }
 
+/// Period in microseconds of the cpu bandwidth limits set by the throttling policies
+const CPU_PERIOD: u64 = 100_000;
+
 /// Default runtime hierarchy that yields only implemented and supported controllers
 /// instead of the default list.
 fn hierarchy() -> Box<dyn Hierarchy> {
@@ -61,6 +70,57 @@ pub async fn shutdown(dir: &Path) -> Result<(), Error> {
         .map_err(|e| Error::CGroups(e.to_string()))
 }
 
+/// Kill the tasks and remove the cgroups below `top_level_dir` of containers whose name
+/// is not in `started`. Cgroups with tasks are removed by a later call once the killed
+/// tasks exited. Returns the names of the leftover cgroups.
+pub async fn reap(top_level_dir: &str, started: &HashSet<String>) -> Vec<String> {
+    let top_level = cgroups_rs::Cgroup::new(hierarchy(), top_level_dir);
+    let dir = match top_level.controller_of::<MemController>() {
+        Some(controller) => controller.path().to_owned(),
+        None => return Vec::with_capacity(0),
+    };
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("failed to read {}: {}", dir.display(), e);
+            return Vec::with_capacity(0);
+        }
+    };
+
+    let mut leftovers = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_dir() || started.contains(&name) {
+            continue;
+        }
+
+        let cgroup = cgroups_rs::Cgroup::new(hierarchy(), Path::new(top_level_dir).join(&name));
+        let tasks = cgroup.tasks();
+        if tasks.is_empty() {
+            warn!("Removing leftover cgroup {}", name);
+            if let Err(e) = cgroup.delete() {
+                warn!("failed to remove cgroup {}: {}", name, e);
+            }
+        } else {
+            warn!(
+                "Killing {} leftover task(s) in cgroup {}",
+                tasks.len(),
+                name
+            );
+            // Frozen tasks do not handle the SIGKILL before they are thawed
+            if let Some(freezer) = cgroup.controller_of::<FreezerController>() {
+                freezer.thaw().ok();
+            }
+            for task in tasks {
+                let pid = nix::unistd::Pid::from_raw(task.pid as i32);
+                nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL).ok();
+            }
+        }
+        leftovers.push(name);
+    }
+    leftovers
+}
+
 /// Implement a custom type for Hierarchy that filters subsystems
 #[derive(Debug)]
 struct RuntimeHierarchy {
@@ -89,7 +149,7 @@ impl Hierarchy for RuntimeHierarchy {
                 cgroups_rs::Subsystem::CpuAcct(_) => false,
                 cgroups_rs::Subsystem::Cpu(_) => true,
                 cgroups_rs::Subsystem::Devices(_) => false,
-                cgroups_rs::Subsystem::Freezer(_) => false,
+                cgroups_rs::Subsystem::Freezer(_) => true,
                 cgroups_rs::Subsystem::NetCls(_) => false,
                 cgroups_rs::Subsystem::BlkIo(_) => true,
                 cgroups_rs::Subsystem::PerfEvent(_) => false,
@@ -119,6 +179,8 @@ pub struct CGroups {
     container: Container,
     cgroup: cgroups_rs::Cgroup,
     memory_monitor: MemoryMonitor,
+    /// Cpu bandwidth quota and period configured in the manifest
+    cpu_bandwidth: (Option<i64>, Option<u64>),
 }
 
 impl CGroups {
@@ -154,6 +216,42 @@ impl CGroups {
             .add_task(cgroups_rs::CgroupPid::from(pid as u64))
             .expect("failed to assign pid");
 
+        Ok(CGroups::monitor(container, cgroup, config, tx).await)
+    }
+
+    /// Take over the cgroup of `container` that was created by a previous runtime
+    /// instance. The resources are applied already.
+    pub(super) async fn adopt(
+        top_level_dir: &str,
+        tx: EventTx,
+        container: &Container,
+        config: &manifest::cgroups::CGroups,
+    ) -> Result<CGroups, Error> {
+        debug!("Adopting cgroups of {}", container);
+        let name: &str = container.name().as_ref();
+        let cgroup: cgroups_rs::Cgroup =
+            cgroups_rs::Cgroup::load(hierarchy(), Path::new(top_level_dir).join(name));
+
+        let exists = cgroup
+            .controller_of::<MemController>()
+            .map_or(false, |controller| controller.path().exists());
+        if !exists {
+            return Err(Error::CGroups(format!(
+                "cgroup of {} is missing",
+                container
+            )));
+        }
+
+        Ok(CGroups::monitor(container, cgroup, config, tx).await)
+    }
+
+    /// Start the memory monitor of `cgroup`
+    async fn monitor(
+        container: &Container,
+        cgroup: cgroups_rs::Cgroup,
+        config: &manifest::cgroups::CGroups,
+        tx: EventTx,
+    ) -> CGroups {
         let memory_controller = cgroup
             .controller_of::<MemController>()
             .expect("failed to get memory controller");
@@ -164,11 +262,18 @@ impl CGroups {
             MemoryMonitor::new_v1(container.clone(), memory_path, tx).await
         };
 
-        Ok(CGroups {
+        let cpu_bandwidth = config
+            .cpu
+            .as_ref()
+            .map(|cpu| (cpu.quota, cpu.period))
+            .unwrap_or_default();
+
+        CGroups {
             container: container.clone(),
             cgroup,
             memory_monitor,
-        })
+            cpu_bandwidth,
+        }
     }
 
     pub async fn destroy(self) {
@@ -180,18 +285,136 @@ impl CGroups {
         self.cgroup.delete().expect("failed to remove cgroups");
     }
 
-    /// Gather statistics from controllers
+    /// Stop the memory monitor and keep the cgroup for the next runtime instance
+    pub(super) async fn handoff(self) {
+        debug!("Stopping oom monitor of {}", self.container);
+        self.memory_monitor.stop().await;
+    }
+
+    /// Directory of the cgroup in the unified hierarchy or `None` on cgroups v1
+    pub(super) fn unified_path(&self) -> Option<PathBuf> {
+        if !self.cgroup.v2() {
+            return None;
+        }
+        self.cgroup
+            .controller_of::<MemController>()
+            .map(|controller| controller.path().to_owned())
+    }
+
+    /// Freeze all tasks of the cgroup
+    pub(super) fn freeze(&self) -> Result<(), Error> {
+        debug!("Freezing {}", self.container);
+        self.freezer()?
+            .freeze()
+            .map_err(|e| Error::CGroups(e.to_string()))
+    }
+
+    /// Thaw all tasks of the cgroup
+    pub(super) fn thaw(&self) -> Result<(), Error> {
+        debug!("Thawing {}", self.container);
+        self.freezer()?
+            .thaw()
+            .map_err(|e| Error::CGroups(e.to_string()))
+    }
+
+    /// Limit the cpu bandwidth to `percent` of a cpu or restore the bandwidth
+    /// configured in the manifest if `percent` is `None`
+    pub(super) fn cpu_max(&self, percent: Option<u32>) -> Result<(), Error> {
+        let (quota, period) = match percent {
+            Some(percent) => ((CPU_PERIOD * percent as u64 / 100) as i64, Some(CPU_PERIOD)),
+            None => (self.cpu_bandwidth.0.unwrap_or(-1), self.cpu_bandwidth.1),
+        };
+        debug!("Setting cpu quota of {} to {}", self.container, quota);
+
+        let cpu = self
+            .cgroup
+            .controller_of::<CpuController>()
+            .ok_or_else(|| Error::CGroups("cpu controller is not available".into()))?;
+        if let Some(period) = period {
+            cpu.set_cfs_period(period)
+                .map_err(|e| Error::CGroups(e.to_string()))?;
+        }
+        cpu.set_cfs_quota(quota)
+            .map_err(|e| Error::CGroups(e.to_string()))
+    }
+
+    fn freezer(&self) -> Result<&FreezerController, Error> {
+        self.cgroup
+            .controller_of::<FreezerController>()
+            .ok_or_else(|| Error::CGroups("freezer controller is not available".into()))
+    }
+
+    /// Pids of the tasks in the cgroup
+    pub(super) fn tasks(&self) -> Vec<Pid> {
+        self.cgroup
+            .tasks()
+            .into_iter()
+            .map(|task| task.pid as Pid)
+            .collect()
+    }
+
+    /// Number of processes of the cgroup killed by the oom killer if reported by the
+    /// kernel
+    pub(super) fn oom_kills(&self) -> Option<u64> {
+        let memory = self.cgroup.controller_of::<MemController>()?;
+        let file = if self.cgroup.v2() {
+            "memory.events"
+        } else {
+            "memory.oom_control"
+        };
+        read_stat(memory.path(), file, parse_flat_keyed)?
+            .get("oom_kill")
+            .copied()
+    }
+
+    /// Gather statistics from controllers. The keyed stat files of the controllers
+    /// are exposed completely and summarized with the rss, page cache and throttled
+    /// time of the cgroup.
     pub(super) fn stats(&self) -> ContainerStats {
+        let v2 = self.cgroup.v2();
         let mut stats = HashMap::new();
+        let mut summary = HashMap::new();
         for c in self.cgroup.subsystems() {
             match c {
                 cgroups_rs::Subsystem::BlkIo(c) => {
                     stats.insert("blkio".into(), to_value(c.blkio()).unwrap_or_default());
+                    // The v1 blkio files are covered by the controller stats
+                    if v2 {
+                        if let Some(io) = read_stat(c.path(), "io.stat", parse_nested_keyed) {
+                            stats.insert("io.stat".into(), to_value(io).unwrap_or_default());
+                        }
+                    }
                 }
                 cgroups_rs::Subsystem::Cpu(c) => {
                     stats.insert("cpu".into(), to_value(c.cpu()).unwrap_or_default());
+                    if let Some(cpu) = read_stat(c.path(), "cpu.stat", parse_flat_keyed) {
+                        let throttled = if v2 {
+                            cpu.get("throttled_usec").copied()
+                        } else {
+                            cpu.get("throttled_time").map(|ns| ns / 1000)
+                        };
+                        if let Some(throttled) = throttled {
+                            summary.insert("throttled_usec", throttled);
+                        }
+                        stats.insert("cpu.stat".into(), to_value(cpu).unwrap_or_default());
+                    }
                 }
                 cgroups_rs::Subsystem::Mem(c) => {
+                    if let Some(memory) = read_stat(c.path(), "memory.stat", parse_flat_keyed) {
+                        let (rss, cache) = if v2 {
+                            ("anon", "file")
+                        } else {
+                            ("total_rss", "total_cache")
+                        };
+                        if let Some(rss) = memory.get(rss) {
+                            summary.insert("rss", *rss);
+                        }
+                        if let Some(cache) = memory.get(cache) {
+                            summary.insert("cache", *cache);
+                        }
+                        stats.insert("memory.stat".into(), to_value(memory).unwrap_or_default());
+                    }
+
                     let mut memory = HashMap::new();
                     memory.insert(
                         "memory".to_string(),
@@ -210,11 +433,49 @@ impl CGroups {
                 _ => (),
             }
         }
+        stats.insert("summary".into(), to_value(summary).unwrap_or_default());
 
         stats
     }
 }
 
+/// Read and parse the stat file `name` of a controller at `path`
+fn read_stat<T>(path: &Path, name: &str, parse: fn(&str) -> T) -> Option<T> {
+    match std::fs::read_to_string(path.join(name)) {
+        Ok(content) => Some(parse(&content)),
+        Err(e) => {
+            debug!("Failed to read {}: {}", path.join(name).display(), e);
+            None
+        }
+    }
+}
+
+/// Parse a flat keyed file like `memory.stat` or `cpu.stat` with one `key value`
+/// pair per line
+fn parse_flat_keyed(content: &str) -> HashMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter_map(|(key, value)| Some((key.to_string(), value.trim().parse().ok()?)))
+        .collect()
+}
+
+/// Parse a nested keyed file like `io.stat` with lines of `device key=value...`
+fn parse_nested_keyed(content: &str) -> HashMap<String, HashMap<String, u64>> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?.to_string();
+            let values = fields
+                .filter_map(|field| field.split_once('='))
+                .filter_map(|(key, value)| Some((key.to_string(), value.parse().ok()?)))
+                .collect();
+            Some((device, values))
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 struct MemoryMonitor {
     token: CancellationToken,
@@ -445,3 +706,19 @@ impl From<manifest::cgroups::BlkIoDeviceThrottleResource> for BlkIoDeviceThrottl
         }
     }
 }
+
+#[test]
+fn parse_stat() {
+    let memory = parse_flat_keyed("anon 4096\nfile 8192\nsock 0\n");
+    assert_eq!(memory.len(), 3);
+    assert_eq!(memory.get("anon"), Some(&4096));
+    assert_eq!(memory.get("file"), Some(&8192));
+
+    let io = parse_nested_keyed(
+        "254:0 rbytes=1024 wbytes=2048 rios=1 wios=2 dbytes=0 dios=0\n8:0 rbytes=4096\n",
+    );
+    assert_eq!(io.len(), 2);
+    assert_eq!(io["254:0"].get("wbytes"), Some(&2048));
+    assert_eq!(io["254:0"].len(), 6);
+    assert_eq!(io["8:0"].get("rbytes"), Some(&4096));
+}
@@ -0,0 +1,43 @@
+use super::error::Error;
+use crate::{
+    common::container::Container,
+    npk::manifest::{Manifest, Time},
+};
+use nix::time::{clock_gettime, ClockId};
+
+/// Offset of the time namespace of `manifest` in seconds. The clocks of the
+/// namespace must not be negative.
+pub(crate) fn offset(container: &Container, manifest: &Manifest) -> Result<Option<i64>, Error> {
+    let offset = match manifest.time.as_ref().and_then(|time| time.offset.as_ref()) {
+        Some(offset) => offset,
+        None => return Ok(None),
+    };
+    let failed = |reason: String| Error::StartContainerFailed(container.clone(), reason);
+
+    let seconds = Time::parse_offset(offset)
+        .ok_or_else(|| failed(format!("invalid time offset {}", offset)))?;
+    // The monotonic clock is behind the boot time clock
+    let monotonic = clock_gettime(ClockId::CLOCK_MONOTONIC)
+        .map_err(|e| failed(format!("failed to read the monotonic clock: {}", e)))?;
+    if monotonic.tv_sec() + seconds < 0 {
+        return Err(failed(format!(
+            "time offset {} exceeds the monotonic clock",
+            offset
+        )));
+    }
+    Ok(Some(seconds))
+}
+
+/// Variables that preload libfaketime into the application
+pub(super) fn env(manifest: &Manifest) -> Vec<String> {
+    let realtime = match manifest.time.as_ref().and_then(|t| t.realtime.as_ref()) {
+        Some(realtime) => realtime,
+        None => return Vec::new(),
+    };
+    // The monotonic clocks are shifted by the time namespace
+    vec![
+        format!("LD_PRELOAD={}", Time::FAKETIME),
+        format!("FAKETIME={}", realtime),
+        "DONT_FAKE_MONOTONIC=1".to_string(),
+    ]
+}
@@ -1,9 +1,17 @@
-use super::{Error, RepositoryId};
-use crate::common::non_nul_string::NonNulString;
+use super::{token, Error, RepositoryId};
+use crate::{
+    common::non_nul_string::NonNulString,
+    npk::manifest::{Manifest, RLimitResource, RLimitValue},
+};
 use nix::{sys::stat, unistd};
-use serde::{de::Error as SerdeError, Deserialize, Deserializer};
+use serde::{
+    de::{Error as SerdeError, IntoDeserializer},
+    Deserialize, Deserializer,
+};
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
     os::unix::prelude::{MetadataExt, PermissionsExt},
     path::{Path, PathBuf},
     time,
@@ -28,9 +36,15 @@ pub struct Config {
     pub log_dir: PathBuf,
     /// Top level cgroup name
     pub cgroup: NonNulString,
-    /// Event loop buffer size
+    /// Event loop buffer size of console requests and container lifecycle events
     #[serde(default = "default_event_buffer_size")]
     pub event_buffer_size: usize,
+    /// Event loop buffer size of cgroup events
+    #[serde(default = "default_stats_buffer_size")]
+    pub stats_buffer_size: usize,
+    /// Event loop buffer size of kernel messages
+    #[serde(default = "default_log_buffer_size")]
+    pub log_buffer_size: usize,
     /// Notification buffer size
     #[serde(default = "default_notification_buffer_size")]
     pub notification_buffer_size: usize,
@@ -40,19 +54,242 @@ pub struct Config {
     /// Loop device timeout
     #[serde(with = "humantime_serde", default = "default_loop_device_timeout")]
     pub loop_device_timeout: time::Duration,
+    /// Timeout of a container mount. A mount that completes after the timeout is
+    /// umounted again.
+    #[serde(with = "humantime_serde", default = "default_mount_timeout")]
+    pub mount_timeout: time::Duration,
+    /// Timeout of a container umount. The mount is detached lazily after the timeout.
+    #[serde(with = "humantime_serde", default = "default_umount_timeout")]
+    pub umount_timeout: time::Duration,
+    /// Timeout of the creation and the exec of a container process. The process is
+    /// killed if the forker replies after the timeout.
+    #[serde(with = "humantime_serde", default = "default_start_timeout")]
+    pub start_timeout: time::Duration,
+    /// Timeout for a container that is killed for an uninstall or a data reset to
+    /// exit and for the teardown of an exited container
+    #[serde(with = "humantime_serde", default = "default_stop_timeout")]
+    pub stop_timeout: time::Duration,
+    /// Maximum number of containers that are mounted concurrently during autostart
+    #[serde(default = "default_autostart_concurrency")]
+    pub autostart_concurrency: usize,
+    /// Maximum number of concurrent mounts of a mount request or a start with
+    /// resources
+    #[serde(default = "default_mount_concurrency")]
+    pub mount_concurrency: usize,
+    /// Umount containers that were mounted for a start once they and the containers
+    /// using them as resource are not started for this duration. Containers mounted
+    /// with a mount request stay mounted.
+    #[serde(with = "humantime_serde", default)]
+    pub idle_umount: Option<time::Duration>,
+    /// Audit the cgroups, mounts and loop devices for leftovers of exited containers
+    /// in this interval and clean them up
+    #[serde(with = "humantime_serde", default)]
+    pub reconcile_interval: Option<time::Duration>,
+    /// Backend used to mount containers
+    #[serde(default)]
+    pub mount_backend: MountBackend,
+    /// File that keeps the root hashes verified by the fuse and simulation mount
+    /// backend across runtime restarts. Images of unmodified npks are not verified
+    /// again.
+    pub verity_cache: Option<PathBuf>,
     /// Token validity
     #[serde(with = "humantime_serde", default = "default_token_validity")]
     pub token_validity: time::Duration,
+    /// Maximum validity that can be requested on token creation. Defaults to `token_validity`
+    #[serde(with = "humantime_serde", default)]
+    pub token_max_validity: Option<time::Duration>,
     /// Console configuration
     #[serde(deserialize_with = "console")]
     pub consoles: HashMap<Url, ConsoleConfiguration>,
     /// Repositories
     #[serde(default)]
     pub repositories: HashMap<RepositoryId, Repository>,
+    /// Restore containers after an unclean exit of the previous runtime instance
+    #[serde(default)]
+    pub restore: Restore,
+    /// Forward kernel messages about container processes e.g. oom kills and seccomp
+    /// violations as notifications. Requires access to `/dev/kmsg`.
+    #[serde(default)]
+    pub kernel_messages: bool,
+    /// Capture core dumps of container processes
+    pub core_dumps: Option<CoreDumps>,
+    /// Parameters substituted into container environments
+    #[serde(default)]
+    pub parameters: Parameters,
+    /// Throttling policies applied to deferrable containers
+    #[serde(default)]
+    pub policies: HashMap<String, Policy>,
+    /// Default resource limits of containers. Limits in the manifest override the defaults.
+    #[serde(default, deserialize_with = "rlimit_table")]
+    pub rlimits: HashMap<RLimitResource, RLimit>,
+    /// Linux security module that is mandatory on this system. Containers without a
+    /// label for this module are rejected on install and start.
+    pub mandatory_lsm: Option<Lsm>,
+    /// Tracing options
+    pub tracing: Option<Tracing>,
+    /// Host network interfaces that containers can move into their network namespace
+    #[serde(default)]
+    pub network_interfaces: HashSet<String>,
+    /// Path of libfaketime that is preloaded into containers with `time.realtime`
+    pub faketime: Option<PathBuf>,
+    /// D-Bus proxy for containers with a `dbus` section
+    pub dbus: Option<DBus>,
     /// Debugging options
     pub debug: Option<Debug>,
 }
 
+/// Restore policy applied on startup if the previous runtime instance exited without
+/// a clean shutdown. Container processes do not outlive the runtime and are started
+/// again if requested.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Restore {
+    /// Discard the state of the previous instance
+    None,
+    /// Mount the containers that were mounted
+    Mount,
+    /// Mount the containers that were mounted and start the containers that were started
+    Start,
+}
+
+impl Default for Restore {
+    fn default() -> Restore {
+        Restore::None
+    }
+}
+
+/// Mechanism used to mount the filesystem images and root overlays of containers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MountBackend {
+    /// Loop devices, dm-verity and the kernel squashfs and overlayfs drivers
+    Kernel,
+    /// `squashfuse` and `fuse-overlayfs`. Does not need `CAP_SYS_ADMIN` for
+    /// mounting images e.g. when the runtime runs within another container.
+    Fuse,
+    /// Extract the images into plain directories with `unsquashfs` and start the
+    /// containers without pid namespace and seccomp filters. Containers are not
+    /// isolated from each other. For application development on Linux hosts only.
+    #[cfg(feature = "simulation")]
+    Simulation,
+}
+
+impl Default for MountBackend {
+    fn default() -> MountBackend {
+        MountBackend::Kernel
+    }
+}
+
+/// Default and maximum of a container resource limit
+/// ```toml
+/// [rlimits.nofile]
+/// soft = 1024
+/// hard = 4096
+/// max = 8192
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RLimit {
+    /// Default soft limit. Default: max
+    pub soft: Option<u64>,
+    /// Default hard limit. Default: max
+    pub hard: Option<u64>,
+    /// Maximum soft and hard limit of a container. Default: unlimited
+    pub max: Option<u64>,
+}
+
+/// Core dump capture. The runtime registers itself as the kernel core dump handler
+/// and stores the dumps of the container processes per container in `dir`.
+/// ```toml
+/// [core_dumps]
+/// dir = "/data/northstar/core"
+/// max_size = 104857600
+/// max_count = 4
+/// minidump = true
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoreDumps {
+    /// Absolute path of the directory the dumps are stored in
+    pub dir: PathBuf,
+    /// Maximum size of a dump in bytes. Larger dumps are truncated. Default: 100 MB
+    #[serde(default = "default_core_dump_max_size")]
+    pub max_size: u64,
+    /// Maximum number of dumps kept per container. The oldest dump is removed first. Default: 4
+    #[serde(default = "default_core_dump_max_count")]
+    pub max_count: usize,
+    /// Capture full core dumps with the kernel core dump handler. Default: true
+    #[serde(default = "default_true")]
+    pub core: bool,
+    /// Write a minidump if the application process of a container crashes. The
+    /// application is traced by the init process of the container. Default: false
+    #[serde(default)]
+    pub minidump: bool,
+}
+
+/// Linux security module
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lsm {
+    /// SELinux. Requires a `selinux.label` in the manifest
+    Selinux,
+    /// AppArmor. Requires an `apparmor_profile` in the manifest
+    Apparmor,
+}
+
+impl fmt::Display for Lsm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lsm::Selinux => write!(f, "SELinux"),
+            Lsm::Apparmor => write!(f, "AppArmor"),
+        }
+    }
+}
+
+/// Parameters that are substituted into the environment of containers. See
+/// [`crate::runtime::parameters`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Parameters {
+    /// Device id substituted for `${DEVICE_ID}`
+    pub device_id: Option<String>,
+    /// File the device id is read from if `device_id` is not set e.g `/etc/machine-id`
+    pub device_id_file: Option<PathBuf>,
+    /// Values substituted for `${CONFIG:key}`
+    #[serde(default)]
+    pub config: HashMap<String, String>,
+    /// Files with the content of the secrets available to `secret` mounts
+    #[serde(default)]
+    pub secrets: HashMap<String, PathBuf>,
+}
+
+/// Throttling policy. The policy is activated and deactivated by the value of an
+/// external input that is fed with `policy_input` requests. While active the cpu
+/// bandwidth of the deferrable containers is limited or the containers are frozen.
+/// ```toml
+/// [policies.hot]
+/// input = "thermal"
+/// activate = 80
+/// deactivate = 70
+/// cpu_max = 50
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Name of the input e.g. `thermal` or `battery`
+    pub input: String,
+    /// Input value that activates the policy
+    pub activate: i64,
+    /// Input value that deactivates the policy. The policy activates on a rising input
+    /// if `deactivate` is less than `activate` and on a falling input otherwise.
+    pub deactivate: i64,
+    /// Limit the cpu bandwidth of the deferrable containers to this percentage of a cpu
+    pub cpu_max: Option<u32>,
+    /// Freeze the deferrable containers
+    #[serde(default)]
+    pub freeze: bool,
+}
+
 /// Repository type
 #[derive(Clone, Debug, Deserialize)]
 pub enum RepositoryType {
@@ -76,8 +313,86 @@ pub struct Repository {
     pub mount_on_start: bool,
     /// Optional key for this repository
     pub key: Option<PathBuf>,
+    /// Additional keys with optional validity windows e.g. to rotate signing keys
+    #[serde(default)]
+    pub keys: Vec<RepositoryKey>,
     /// Repository type: fs or mem
     pub r#type: RepositoryType,
+    /// Watch the directory of a fs repository and add npks that are copied into it. Default: false
+    #[serde(default)]
+    pub watch: bool,
+    /// Space in bytes that must remain available in the directory of a fs repository
+    /// in addition to the size of an npk that is installed. Default: 0
+    #[serde(default)]
+    pub install_margin: u64,
+    /// Reject installs and uninstalls and never write to the directory of a fs repository
+    /// e.g. a factory flashed partition mounted read only. Default: false
+    #[serde(default)]
+    pub readonly: bool,
+    /// Containers that are present in several repositories are taken from the repository
+    /// with the highest priority. Default: 0
+    #[serde(default)]
+    pub priority: u32,
+}
+
+impl Repository {
+    /// All keys of this repository. The repository is verified if there's at least one key.
+    pub fn keys(&self) -> Vec<RepositoryKey> {
+        self.key
+            .iter()
+            .map(|path| RepositoryKey {
+                path: path.clone(),
+                not_before: None,
+                not_after: None,
+            })
+            .chain(self.keys.iter().cloned())
+            .collect()
+    }
+}
+
+/// Repository key
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepositoryKey {
+    /// Path to the public key
+    pub path: PathBuf,
+    /// Start of the validity e.g. `2022-06-01T00:00:00Z`
+    #[serde(default, with = "humantime_serde")]
+    pub not_before: Option<time::SystemTime>,
+    /// End of the validity
+    #[serde(default, with = "humantime_serde")]
+    pub not_after: Option<time::SystemTime>,
+}
+
+/// Export of the runtime spans e.g. request handling, mounts, forks and installs
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tracing {
+    /// OpenTelemetry collector endpoint that receives the spans via OTLP/gRPC
+    /// e.g. `http://localhost:4317`
+    pub otlp_endpoint: Url,
+    /// Service name reported to the collector
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+/// Buses proxied into containers with `xdg-dbus-proxy`
+/// ```toml
+/// [dbus]
+/// system = "unix:path=/run/dbus/system_bus_socket"
+/// session = "unix:path=/run/user/1000/bus"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DBus {
+    /// Path of the proxy binary. Default: `xdg-dbus-proxy`
+    #[serde(default = "default_dbus_proxy")]
+    pub proxy: PathBuf,
+    /// Address of the system bus. Default: `unix:path=/run/dbus/system_bus_socket`
+    #[serde(default = "default_dbus_system")]
+    pub system: String,
+    /// Address of the session bus. Containers cannot use the session bus if unset.
+    pub session: Option<String>,
 }
 
 /// Container debug settings
@@ -88,6 +403,9 @@ pub struct Debug {
     pub strace: Option<debug::Strace>,
     /// perf options
     pub perf: Option<debug::Perf>,
+    /// Allow clients to attach to the PTY of containers with `io.tty`
+    #[serde(default)]
+    pub attach: bool,
 }
 
 /// Container debug facilities
@@ -172,8 +490,193 @@ impl Config {
             )));
         }
 
+        if self.autostart_concurrency == 0 {
+            return Err(Error::Configuration(
+                "autostart_concurrency must be at least 1".to_string(),
+            ));
+        }
+
+        if self.mount_concurrency == 0 {
+            return Err(Error::Configuration(
+                "mount_concurrency must be at least 1".to_string(),
+            ));
+        }
+
+        for (id, repository) in &self.repositories {
+            for key in &repository.keys {
+                if let (Some(not_before), Some(not_after)) = (key.not_before, key.not_after) {
+                    if not_before >= not_after {
+                        return Err(Error::Configuration(format!(
+                            "key {} of repository {} has an empty validity window",
+                            key.path.display(),
+                            id
+                        )));
+                    }
+                }
+            }
+            if repository.watch && !matches!(repository.r#type, RepositoryType::Fs { .. }) {
+                return Err(Error::Configuration(format!(
+                    "repository {} cannot be watched: not a fs repository",
+                    id
+                )));
+            }
+            if repository.readonly && !matches!(repository.r#type, RepositoryType::Fs { .. }) {
+                return Err(Error::Configuration(format!(
+                    "repository {} cannot be read only: not a fs repository",
+                    id
+                )));
+            }
+        }
+
+        for (name, policy) in &self.policies {
+            if policy.activate == policy.deactivate {
+                return Err(Error::Configuration(format!(
+                    "policy {} has no hysteresis: activate equals deactivate",
+                    name
+                )));
+            }
+            if policy.cpu_max == Some(0) {
+                return Err(Error::Configuration(format!(
+                    "policy {} has a cpu_max of 0",
+                    name
+                )));
+            }
+        }
+
+        if let Some(core_dumps) = &self.core_dumps {
+            // The dump handler is started by the kernel with / as working directory
+            if !core_dumps.dir.is_absolute() {
+                return Err(Error::Configuration(format!(
+                    "core dump dir {} is not absolute",
+                    core_dumps.dir.display()
+                )));
+            }
+            if core_dumps.max_count == 0 {
+                return Err(Error::Configuration(
+                    "core dump max_count must be at least 1".to_string(),
+                ));
+            }
+        }
+
+        for (resource, limit) in &self.rlimits {
+            let soft = limit.soft.or(limit.max).unwrap_or(rlimit::INFINITY);
+            let hard = limit.hard.or(limit.max).unwrap_or(rlimit::INFINITY);
+            if soft > hard || hard > limit.max.unwrap_or(rlimit::INFINITY) {
+                return Err(Error::Configuration(format!(
+                    "invalid rlimit {:?}: soft <= hard <= max required",
+                    resource
+                )));
+            }
+        }
+
+        if let Some(max) = self.token_max_validity {
+            if max < self.token_validity {
+                return Err(Error::Configuration(format!(
+                    "token_max_validity {:?} is less than token_validity {:?}",
+                    max, self.token_validity
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that `manifest` contains a label of the mandatory security module.
+    /// Resource containers do not run processes and are not checked.
+    pub(crate) fn check_security_label(&self, manifest: &Manifest) -> Result<(), Error> {
+        let lsm = match self.mandatory_lsm {
+            Some(lsm) if manifest.init.is_some() => lsm,
+            _ => return Ok(()),
+        };
+        let labelled = match lsm {
+            Lsm::Selinux => manifest
+                .selinux
+                .as_ref()
+                .map(|selinux| selinux.label.is_some())
+                .unwrap_or(false),
+            Lsm::Apparmor => manifest.apparmor_profile.is_some(),
+        };
+        if labelled {
+            Ok(())
+        } else {
+            Err(Error::MissingSecurityLabel(
+                manifest.container(),
+                lsm.to_string(),
+            ))
+        }
+    }
+
+    /// Check that the network interfaces of `manifest` are allowed
+    pub(crate) fn check_network_interfaces(&self, manifest: &Manifest) -> Result<(), Error> {
+        let interfaces = manifest.network.iter().flat_map(|n| &n.interfaces);
+        for interface in interfaces {
+            if !self.network_interfaces.contains(interface.as_str()) {
+                return Err(Error::StartContainerFailed(
+                    manifest.container(),
+                    format!("network interface {} is not allowed", interface),
+                ));
+            }
+        }
         Ok(())
     }
+
+    /// Resource limits of the container of `manifest`: The limits of the manifest merged
+    /// with the configured defaults. Fails if a limit exceeds the configured maximum.
+    pub(crate) fn rlimits(
+        &self,
+        manifest: &Manifest,
+    ) -> Result<HashMap<RLimitResource, RLimitValue>, Error> {
+        let mut rlimits = manifest.rlimits.clone();
+        for (resource, limit) in &self.rlimits {
+            let value = rlimits
+                .entry(resource.clone())
+                .or_insert_with(|| RLimitValue {
+                    soft: limit.soft.or(limit.max),
+                    hard: limit.hard.or(limit.max),
+                });
+            if let Some(max) = limit.max {
+                let soft = value.soft.unwrap_or(rlimit::INFINITY);
+                let hard = value.hard.unwrap_or(rlimit::INFINITY);
+                if soft > max || hard > max {
+                    return Err(Error::RLimitExceeded(
+                        manifest.container(),
+                        format!("{:?}", resource).to_lowercase(),
+                        max,
+                    ));
+                }
+            }
+        }
+        Ok(rlimits)
+    }
+
+    /// True if minidumps of crashing container processes are written
+    pub(crate) fn minidumps(&self) -> bool {
+        self.core_dumps
+            .as_ref()
+            .map(|core_dumps| core_dumps.minidump)
+            .unwrap_or(false)
+    }
+
+    /// Order of the repositories `a` and `b` for a container that is present in both:
+    /// Repositories with a higher priority come first. Repositories with the same
+    /// priority are ordered by their id.
+    pub(crate) fn repository_order(&self, a: &str, b: &str) -> Ordering {
+        let priority = |id: &str| {
+            self.repositories
+                .get(id)
+                .map(|repository| repository.priority)
+                .unwrap_or_default()
+        };
+        priority(b).cmp(&priority(a)).then_with(|| a.cmp(b))
+    }
+
+    /// Validity of console tokens
+    pub(crate) fn token_validity(&self) -> token::Validity {
+        token::Validity {
+            default: self.token_validity,
+            max: self.token_max_validity.unwrap_or(self.token_validity),
+        }
+    }
 }
 
 /// Return true if path is read and writeable
@@ -197,7 +700,8 @@ async fn is_rw(path: &Path) -> bool {
     }
 }
 
-/// Validate the console configuration that the url schemes are all "tcp" or "unix"
+/// Validate the console configuration that the url schemes are all "tcp", "unix",
+/// "unix+abstract" or "systemd"
 fn console<'de, D>(deserializer: D) -> Result<HashMap<Url, ConsoleConfiguration>, D::Error>
 where
     D: Deserializer<'de>,
@@ -205,14 +709,30 @@ where
     let consoles = HashMap::<Url, _>::deserialize(deserializer)?;
     if consoles
         .keys()
-        .any(|url| url.scheme() != "tcp" && url.scheme() != "unix")
+        .any(|url| !matches!(url.scheme(), "tcp" | "unix" | "unix+abstract" | "systemd"))
     {
-        Err(D::Error::custom("console must be tcp or unix"))
+        Err(D::Error::custom(
+            "console must be tcp, unix, unix+abstract or systemd",
+        ))
     } else {
         Ok(consoles)
     }
 }
 
+/// Deserialize the rlimit table. Toml table keys are plain strings that are converted
+/// into the resource names of the manifest.
+fn rlimit_table<'de, D>(deserializer: D) -> Result<HashMap<RLimitResource, RLimit>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::<String, RLimit>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(resource, limit)| {
+            RLimitResource::deserialize(resource.into_deserializer()).map(|r| (r, limit))
+        })
+        .collect()
+}
+
 const fn default_device_mapper_timeout() -> time::Duration {
     time::Duration::from_secs(10)
 }
@@ -221,10 +741,34 @@ const fn default_loop_device_timeout() -> time::Duration {
     time::Duration::from_secs(10)
 }
 
+const fn default_mount_timeout() -> time::Duration {
+    time::Duration::from_secs(30)
+}
+
+const fn default_umount_timeout() -> time::Duration {
+    time::Duration::from_secs(10)
+}
+
+const fn default_start_timeout() -> time::Duration {
+    time::Duration::from_secs(10)
+}
+
+const fn default_stop_timeout() -> time::Duration {
+    time::Duration::from_secs(10)
+}
+
 const fn default_event_buffer_size() -> usize {
     256
 }
 
+const fn default_stats_buffer_size() -> usize {
+    64
+}
+
+const fn default_log_buffer_size() -> usize {
+    256
+}
+
 const fn default_notification_buffer_size() -> usize {
     128
 }
@@ -233,6 +777,38 @@ const fn default_token_validity() -> time::Duration {
     time::Duration::from_secs(60)
 }
 
+const fn default_autostart_concurrency() -> usize {
+    4
+}
+
+const fn default_mount_concurrency() -> usize {
+    4
+}
+
+const fn default_core_dump_max_size() -> u64 {
+    100 * 1_000_000
+}
+
+const fn default_core_dump_max_count() -> usize {
+    4
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+fn default_service_name() -> String {
+    "northstar".into()
+}
+
+fn default_dbus_proxy() -> PathBuf {
+    "xdg-dbus-proxy".into()
+}
+
+fn default_dbus_system() -> String {
+    "unix:path=/run/dbus/system_bus_socket".into()
+}
+
 #[test]
 #[allow(clippy::unwrap_used)]
 fn console_url() {
@@ -246,6 +822,12 @@ cgroup = "northstar"
 permissions = "full"
 
 [consoles."unix://tmp/foo"]
+permissions = []
+
+[consoles."unix+abstract://northstar"]
+permissions = []
+
+[consoles."systemd://northstar.socket"]
 permissions = []"#;
 
     toml::from_str::<Config>(config).unwrap();
@@ -262,3 +844,91 @@ permissions = []"#;
 
     assert!(toml::from_str::<Config>(config).is_err());
 }
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn rlimits() {
+    use std::str::FromStr;
+
+    let config = r#"
+run_dir = "target/northstar/run"
+data_dir = "target/northstar/data"
+log_dir = "target/northstar/logs"
+cgroup = "northstar"
+
+[consoles."tcp://localhost:4200"]
+permissions = "full"
+
+[rlimits.nofile]
+soft = 1024
+hard = 4096
+max = 8192
+
+[rlimits.core]
+soft = 0
+hard = 0"#;
+    let config = toml::from_str::<Config>(config).unwrap();
+
+    // Defaults
+    let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001";
+    let rlimits = config
+        .rlimits(&Manifest::from_str(manifest).unwrap())
+        .unwrap();
+    assert_eq!(rlimits[&RLimitResource::NOFILE].soft, Some(1024));
+    assert_eq!(rlimits[&RLimitResource::NOFILE].hard, Some(4096));
+    assert_eq!(rlimits[&RLimitResource::CORE].hard, Some(0));
+
+    // Overrides
+    let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+rlimits:
+  nofile:
+    soft: 2048
+    hard: 8192";
+    let rlimits = config
+        .rlimits(&Manifest::from_str(manifest).unwrap())
+        .unwrap();
+    assert_eq!(rlimits[&RLimitResource::NOFILE].soft, Some(2048));
+    assert_eq!(rlimits[&RLimitResource::NOFILE].hard, Some(8192));
+
+    // Above the maximum
+    let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+rlimits:
+  nofile:
+    soft: 2048";
+    assert!(config
+        .rlimits(&Manifest::from_str(manifest).unwrap())
+        .is_err());
+}
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn repository_order() {
+    let config = r#"
+run_dir = "target/northstar/run"
+data_dir = "target/northstar/data"
+log_dir = "target/northstar/logs"
+cgroup = "northstar"
+
+[consoles."tcp://localhost:4200"]
+permissions = "full"
+
+[repositories.factory]
+readonly = true
+type = { fs = { dir = "/apps" }}
+
+[repositories.user]
+priority = 10
+type = { fs = { dir = "/data/apps" }}
+
+[repositories.backup]
+type = { fs = { dir = "/backup/apps" }}"#;
+    let config = toml::from_str::<Config>(config).unwrap();
+
+    assert_eq!(config.repository_order("user", "factory"), Ordering::Less);
+    assert_eq!(
+        config.repository_order("factory", "user"),
+        Ordering::Greater
+    );
+    assert_eq!(config.repository_order("backup", "factory"), Ordering::Less);
+    assert_eq!(config.repository_order("user", "user"), Ordering::Equal);
+}
@@ -1,8 +1,20 @@
-use super::{ContainerEvent, Event, NotificationTx, RepositoryId};
+use super::{
+    activation,
+    installs::Installs,
+    kmsg::KernelMessageKind,
+    pending::{Pending, PendingRequest},
+    ContainerEvent, Event, NotificationTx, RepositoryId,
+};
 use crate::{
     api::{self, codec::Framed, VERSION as API_VERSION},
     common::container::Container,
-    runtime::{token::Token, EventTx, ExitStatus},
+    runtime::{
+        ipc::owned_fd::OwnedFd,
+        peers::{Admission, Peers, Verdict},
+        process,
+        token::{self, Token},
+        EventTx, ExitStatus, Pid,
+    },
 };
 use api::model;
 use async_stream::stream;
@@ -13,40 +25,53 @@ use futures::{
     stream::{self, FuturesUnordered},
     Future, StreamExt, TryFutureExt,
 };
-use log::{debug, error, info, trace, warn};
+use nix::{
+    fcntl,
+    sys::socket::{self, AddressFamily, SockFlag, SockType, UnixAddr},
+};
 use std::{
+    collections::HashSet,
     fmt,
+    os::unix::prelude::{AsRawFd, FromRawFd},
     path::{Path, PathBuf},
+    sync::Arc,
     unreachable,
 };
 use thiserror::Error;
 use tokio::{
     fs,
-    io::{self, AsyncRead, AsyncReadExt, AsyncWrite},
-    net::{TcpListener, UnixListener},
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, UnixListener, UnixStream},
     pin, select,
     sync::{broadcast, mpsc, oneshot},
     task, time,
 };
 use tokio_util::{either::Either, io::ReaderStream, sync::CancellationToken};
+use tracing::{debug, info, trace, warn};
 use url::Url;
 
 pub use crate::npk::manifest::console::{Configuration, Permission, Permissions};
 
 /// Default maximum requests per second
-const DEFAULT_REQUESTS_PER_SECOND: usize = 1024;
+pub(super) const DEFAULT_REQUESTS_PER_SECOND: usize = 1024;
 /// Default maximum length per request
 const DEFAULT_MAX_REQUEST_SIZE: usize = 1024 * 1024;
 /// Default maximum NPK size
 const DEFAULT_MAX_INSTALL_STREAM_SIZE: u64 = 256 * 1_000_000;
 /// Default timeout between two npks stream chunks
 const DEFAULT_NPK_STREAM_TIMEOUT: u64 = 5;
+/// Maximum number of pipelined requests per connection that are processed concurrently
+const MAX_PIPELINED_REQUESTS: usize = 32;
 
 // Request from the main loop to the console
 #[derive(Debug)]
 pub(crate) enum Request {
     Request(model::Request),
-    Install(RepositoryId, mpsc::Receiver<Bytes>),
+    Install(RepositoryId, u64, mpsc::Receiver<Bytes>),
+    DataExport(Container, mpsc::Sender<Bytes>),
+    CoreDumpExport(Container, String, mpsc::Sender<Bytes>),
+    DataImport(Container, mpsc::Receiver<Bytes>),
+    Attach(Container, mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>),
 }
 
 /// A console is responsible for monitoring and serving incoming client connections
@@ -57,11 +82,18 @@ pub(crate) struct Console {
     event_tx: EventTx,
     /// Broadcast channel passed to connections to forward notifications
     notification_tx: NotificationTx,
+    /// Installs of all connections
+    installs: Installs,
+    /// Cancellable requests of all connections
+    pending: Pending,
     /// Shutdown the console by canceling this token
     stop: CancellationToken,
     /// Listener tasks. Currently there's just one task but when the console
     /// is exposed to containers via unix sockets this list will grow
     tasks: Vec<task::JoinHandle<()>>,
+    /// Duplicates of the listening sockets that are handed over to the next runtime
+    /// instance
+    listeners: Vec<(Url, OwnedFd)>,
 }
 
 #[derive(Error, Debug)]
@@ -72,16 +104,28 @@ pub enum Error {
     Io(String, #[source] io::Error),
     #[error("shutting down")]
     Shutdown,
+    #[error("detached")]
+    Detached,
+    #[error("cancelled")]
+    Cancelled,
 }
 
 impl Console {
     /// Construct a new console instance
-    pub(super) fn new(event_tx: EventTx, notification_tx: NotificationTx) -> Console {
+    pub(super) fn new(
+        event_tx: EventTx,
+        notification_tx: NotificationTx,
+        installs: Installs,
+        pending: Pending,
+    ) -> Console {
         Self {
             event_tx,
             notification_tx,
+            installs,
+            pending,
             stop: CancellationToken::new(),
             tasks: Vec::new(),
+            listeners: Vec::new(),
         }
     }
 
@@ -91,27 +135,63 @@ impl Console {
         &mut self,
         url: &Url,
         configuration: &Configuration,
-        token_validity: time::Duration,
+        token_validity: token::Validity,
+    ) -> Result<(), Error> {
+        debug!(
+            "Starting console on {} with permissions \"{:?}\"",
+            url, configuration
+        );
+        let listener = Listener::new(url)
+            .await
+            .map_err(|e| Error::Io("failed start console listener".into(), e))?;
+        self.serve(url, listener, configuration, token_validity)
+    }
+
+    /// Serve the listening socket `fd` of `url` that is handed over by a previous
+    /// runtime instance
+    pub(super) fn resume(
+        &mut self,
+        url: &Url,
+        configuration: &Configuration,
+        token_validity: token::Validity,
+        fd: OwnedFd,
     ) -> Result<(), Error> {
+        debug!("Resuming console on {}", url);
+        let listener = activation::socket(fd, url.as_str())
+            .and_then(Listener::from_socket)
+            .map_err(|e| Error::Io("failed to resume console listener".into(), e))?;
+        self.serve(url, listener, configuration, token_validity)
+    }
+
+    /// Spawn a task that accepts connections on `listener`
+    fn serve(
+        &mut self,
+        url: &Url,
+        listener: Listener,
+        configuration: &Configuration,
+        token_validity: token::Validity,
+    ) -> Result<(), Error> {
+        let fd = listener
+            .try_clone_fd()
+            .map_err(|e| Error::Io("failed to duplicate console listener".into(), e))?;
+        self.listeners.push((url.clone(), fd));
+
         let event_tx = self.event_tx.clone();
         let notification_tx = self.notification_tx.clone();
+        let installs = self.installs.clone();
+        let pending = self.pending.clone();
         let configuration = configuration.clone();
         // Stop token for self *and* the connections
         let stop = self.stop.clone();
 
-        debug!(
-            "Starting console on {} with permissions \"{:?}\"",
-            url, configuration
-        );
-        let task = match Listener::new(url)
-            .await
-            .map_err(|e| Error::Io("failed start console listener".into(), e))?
-        {
+        let task = match listener {
             Listener::Tcp(listener) => task::spawn(async move {
                 serve(
                     || listener.accept(),
                     event_tx,
                     notification_tx,
+                    installs,
+                    pending,
                     stop,
                     configuration,
                     token_validity,
@@ -120,9 +200,11 @@ impl Console {
             }),
             Listener::Unix(listener) => task::spawn(async move {
                 serve(
-                    || listener.accept(),
+                    || accept_unix(&listener),
                     event_tx,
                     notification_tx,
+                    installs,
+                    pending,
                     stop,
                     configuration,
                     token_validity,
@@ -143,26 +225,86 @@ impl Console {
         Ok(())
     }
 
+    /// Stop the listeners and wait for their shutdown. Returns the listening sockets
+    /// that are served by the next runtime instance.
+    pub(super) async fn handoff(self) -> Result<Vec<(Url, OwnedFd)>, Error> {
+        self.stop.cancel();
+        join_all(self.tasks).await;
+        Ok(self.listeners)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub(super) async fn connection<T: AsyncRead + AsyncWrite + Unpin>(
         stream: T,
         peer: Peer,
         stop: CancellationToken,
         container: Option<Container>,
-        configuration: Configuration,
-        token_validity: time::Duration,
+        mut configuration: Configuration,
+        token_validity: token::Validity,
         event_tx: EventTx,
-        mut notification_rx: broadcast::Receiver<(Container, ContainerEvent)>,
+        notification_tx: NotificationTx,
+        installs: Installs,
+        pending: Pending,
+        peers: Option<Arc<Peers>>,
         timeout: Option<time::Duration>,
     ) -> Result<(), Error> {
-        let permissions = &configuration.permissions;
+        let mut notification_rx = notification_tx.subscribe();
+
+        // Register the connection with the per peer limits of the listener
+        let peer_id = peer.id();
+        let _connection = match peers.as_ref().map(|peers| peers.connect(&peer_id)) {
+            Some(Admission::Accepted(connection)) => Some(connection),
+            Some(Admission::Banned(remaining)) => {
+                warn!(
+                    "{}: Peer is banned for {}s. Disconnecting...",
+                    peer,
+                    remaining.as_secs()
+                );
+                return Ok(());
+            }
+            Some(Admission::TooManyConnections(max)) => {
+                warn!(
+                    "{}: Peer exceeds the maximum of {} connections. Disconnecting...",
+                    peer, max
+                );
+                return Ok(());
+            }
+            None => None,
+        };
+
+        let token_authentication =
+            container.is_none() && configuration.token_authentication.unwrap_or(false);
+        // Name of the listener in audit records. Container consoles are not named.
+        let listener = configuration.name.clone().filter(|_| container.is_none());
+
+        // Grant the permissions configured for the user and group of a local process
+        if let (Peer::Local(_, credentials), false) = (&peer, token_authentication) {
+            let granted = configuration
+                .peers
+                .iter()
+                .flatten()
+                .filter(|peer| peer.matches(credentials.uid, credentials.gid, &credentials.groups))
+                .flat_map(|peer| peer.permissions.iter().cloned());
+            let permissions = configuration
+                .permissions
+                .iter()
+                .cloned()
+                .chain(granted)
+                .collect::<HashSet<_>>();
+            configuration.permissions = permissions.into();
+        }
         if let Some(container) = &container {
             debug!(
                 "Container {} connected with permissions {}",
-                container, permissions
+                container, configuration.permissions
             );
+        } else if token_authentication {
+            debug!("Client {} connected with token authentication", peer);
         } else {
-            debug!("Client {} connected with permissions {}", peer, permissions);
+            debug!(
+                "Client {} connected with permissions {}",
+                peer, configuration.permissions
+            );
         }
 
         // Get a framed stream and sink interface.
@@ -171,26 +313,37 @@ impl Console {
             .unwrap_or(DEFAULT_MAX_REQUEST_SIZE);
         let mut network_stream = api::codec::Framed::new_with_max_length(stream, max_request_size);
 
-        // Get a framed stream and sink interface.
-        let max_requests_per_sec = configuration
-            .max_requests_per_sec
-            .unwrap_or(DEFAULT_REQUESTS_PER_SECOND);
-        network_stream.throttle_stream(max_requests_per_sec, time::Duration::from_secs(1));
+        // Throttle the connection if there are no per peer limits
+        if peers.is_none() {
+            let max_requests_per_sec = configuration
+                .max_requests_per_sec
+                .unwrap_or(DEFAULT_REQUESTS_PER_SECOND);
+            network_stream.throttle_stream(max_requests_per_sec, time::Duration::from_secs(1));
+        }
 
         // Wait for a connect message within timeout
         let connect = network_stream.next();
         // TODO: This can for sure be done nicer
         let timeout = timeout.unwrap_or_else(|| time::Duration::from_secs(u64::MAX));
         let connect = time::timeout(timeout, connect);
-        let (protocol_version, notifications) = match connect.await {
+        let (protocol_version, notifications, token, encoding, compression) = match connect.await {
             Ok(Some(Ok(m))) => match m {
                 model::Message::Connect {
                     connect:
                         model::Connect::Connect {
                             version,
                             subscribe_notifications,
+                            token,
+                            encoding,
+                            compression,
                         },
-                } => (version, subscribe_notifications),
+                } => (
+                    version,
+                    subscribe_notifications,
+                    token,
+                    encoding,
+                    compression,
+                ),
                 _ => {
                     warn!("{}: Received {:?} instead of Connect", peer, m);
                     return Ok(());
@@ -226,8 +379,49 @@ impl Console {
             return Ok(());
         }
 
+        // Containers of the token scope. Requests on other containers are refused.
+        let mut scope_containers = None;
+
+        // Derive the permissions from the token scope if the listener requires authentication
+        if token_authentication {
+            let scope = match token.as_ref().map(authenticate) {
+                Some(Ok(scope)) => Some(scope),
+                Some(Err(result)) => {
+                    warn!("{}: Token verification failed: {:?}", peer, result);
+                    None
+                }
+                None => {
+                    warn!("{}: Missing authentication token", peer);
+                    None
+                }
+            };
+
+            if let Some(scope) = scope {
+                debug!(
+                    "{}: Authenticated with permissions {:?}",
+                    peer, scope.permissions
+                );
+                configuration.permissions = scope.permissions.into();
+                if !scope.containers.is_empty() {
+                    debug!(
+                        "{}: Authenticated for containers {:?}",
+                        peer, scope.containers
+                    );
+                    scope_containers = Some(scope.containers);
+                }
+            } else {
+                // Send a ConnectNack and return -> closes the connection
+                let error = model::ConnectNack::PermissionDenied;
+                let connect = model::Connect::Nack { error };
+                let message = model::Message::Connect { connect };
+                network_stream.send(message).await.ok();
+                return Ok(());
+            }
+        }
+
         // Check notification permission if the client want's to subscribe to
         // notifications
+        let permissions = &configuration.permissions;
         if notifications && !permissions.contains(&Permission::Notifications) {
             warn!(
                 "{}: Requested notifications without notification permission. Disconnecting...",
@@ -251,6 +445,16 @@ impl Console {
             return Ok(());
         }
 
+        // Switch to the requested encoding and compression after the handshake
+        if let Some(encoding) = encoding {
+            debug!("{}: Using {:?} encoding", peer, encoding);
+            network_stream.set_encoding(encoding);
+        }
+        if let Some(compression) = compression {
+            debug!("{}: Using {:?} compression", peer, compression);
+            network_stream.set_compression(compression);
+        }
+
         // Notification input: If the client subscribe create a stream from the broadcast
         // receiver and otherwise drop it
         let notifications = if notifications {
@@ -263,6 +467,9 @@ impl Console {
         };
         pin!(notifications);
 
+        // Pipelined requests in progress
+        let mut pipelined = FuturesUnordered::new();
+
         loop {
             select! {
                 _ = stop.cancelled() => {
@@ -273,7 +480,7 @@ impl Console {
                     // Process notifications received via the notification
                     // broadcast channel
                     let notification = match notification {
-                        Some(Ok((container, event))) => (container, event).into(),
+                        Some(Ok(notification)) => notification,
                         Some(Err(broadcast::error::RecvError::Closed)) => break,
                         Some(Err(broadcast::error::RecvError::Lagged(_))) => {
                             warn!("Client connection lagged notifications. Closing");
@@ -290,12 +497,70 @@ impl Console {
                         break;
                     }
                 }
-                item = network_stream.next() => {
+                Some(result) = pipelined.next() => {
+                    let response = match result {
+                        Ok(response) => response,
+                        Err(e) => {
+                            warn!("Failed to process request: {}", e);
+                            break;
+                        }
+                    };
+                    trace!("{}: <-- {:?}", peer, response);
+
+                    if let Err(e) = network_stream.send(response).await {
+                        warn!("{}: Connection error: {}", peer, e);
+                        break;
+                    }
+                }
+                item = network_stream.next(), if pipelined.len() < MAX_PIPELINED_REQUESTS => {
                     match item {
-                        Some(Ok(model::Message::Request { request })) => {
+                        Some(Ok(model::Message::Request { request, id })) => {
+                            if let Some(peers) = &peers {
+                                match peers.request(&peer_id) {
+                                    Verdict::Accept => (),
+                                    Verdict::Delay(delay) => time::sleep(delay).await,
+                                    Verdict::Banned => {
+                                        warn!("{}: Peer is banned. Disconnecting...", peer);
+                                        break;
+                                    }
+                                }
+                            }
+
                             trace!("{}: --> {:?}", peer, request);
-                            let response = match process_request(&peer, &mut network_stream, &stop, &configuration, &event_tx, token_validity, request).await {
-                                Ok(response) => response,
+
+                            if let Some(response) = scope_containers.as_ref().and_then(|containers| out_of_scope(containers, &request)) {
+                                warn!("{}: Request out of the token scope: {:?}", peer, request);
+                                let response = model::Message::Response { response, id };
+                                if let Err(e) = network_stream.send(response).await {
+                                    warn!("{}: Connection error: {}", peer, e);
+                                    break;
+                                }
+                                continue;
+                            }
+
+                            audit(listener.as_deref(), &peer, &configuration, &notification_tx, &request);
+
+                            // Requests with an id that don't transfer data are processed
+                            // concurrently and answered in the order of their completion
+                            if id.is_some() && !is_transfer(&request) {
+                                pipelined.push(respond(&peer, &stop, &configuration, &event_tx, &installs, &pending, token_validity, id, request));
+                                continue;
+                            }
+
+                            let response = match process_request(&peer, &mut network_stream, &stop, &configuration, &event_tx, &installs, &pending, token_validity, id, request).await {
+                                Ok(Some(response)) => response,
+                                // The response is already sent
+                                Ok(None) => continue,
+                                // The connection carried an attach session and cannot be reused
+                                Err(Error::Detached) => {
+                                    debug!("{}: Detached", peer);
+                                    break;
+                                }
+                                // The connection carried the data of a cancelled transfer
+                                Err(Error::Cancelled) => {
+                                    info!("{}: Transfer cancelled", peer);
+                                    break;
+                                }
                                 Err(e) => {
                                     warn!("Failed to process request: {}", e);
                                     break;
@@ -328,139 +593,746 @@ impl Console {
     }
 }
 
-/// Process a request
-///
-/// # Errors
-///
-/// If the streamed NPK is not valid and parseable a `Error::Npk(..)` is returned.
-/// If the event loop is closed due to shutdown, this function will return `Error::EventLoopClosed`.
-///
+/// Record a permitted request of `peer` that changes the state of the runtime. Audit
+/// records are logged with the target `audit` and sent as notification if enabled in
+/// the configuration of the listener.
+fn audit(
+    listener: Option<&str>,
+    peer: &Peer,
+    configuration: &Configuration,
+    notification_tx: &NotificationTx,
+    request: &model::Request,
+) {
+    let action = match action(request) {
+        Some(action) if permission_denied(configuration, request).is_none() => action,
+        _ => return,
+    };
+
+    match listener {
+        Some(listener) => info!(target: "audit", "{} on {}: {}", peer, listener, action),
+        None => info!(target: "audit", "{}: {}", peer, action),
+    }
+
+    if configuration.audit_notifications.unwrap_or(false) && notification_tx.receiver_count() > 0 {
+        let notification = model::Notification::Audit {
+            listener: listener.map(ToString::to_string),
+            peer: peer.to_string(),
+            action,
+        };
+        notification_tx.send(notification).ok();
+    }
+}
+
+/// Description of `request` for audit records. Returns `None` for requests that don't
+/// change the state of the runtime.
+fn action(request: &model::Request) -> Option<String> {
+    let list = |containers: &[Container]| {
+        containers
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let action = match request {
+        model::Request::Attach(container) => format!("attach {}", container),
+        model::Request::Batch(requests, mode) => format!(
+            "batch ({:?}): {}",
+            mode,
+            requests
+                .iter()
+                .filter_map(action)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        model::Request::Stdin(container, data) => {
+            format!("write {} bytes to stdin of {}", data.len(), container)
+        }
+        model::Request::CoreDumpRemove(container, name) => {
+            format!("remove core dump {} of {}", name, container)
+        }
+        model::Request::DataImport(container, size) => {
+            format!("import {} bytes of data of {}", size, container)
+        }
+        model::Request::DataReset(container) => format!("reset data of {}", container),
+        model::Request::Install(repository, size) => {
+            format!("install {} bytes into {}", size, repository)
+        }
+        model::Request::Group(selector, op) => match op {
+            model::GroupOp::Start => format!("start containers matching {}", selector),
+            model::GroupOp::Kill(signal) => {
+                format!(
+                    "kill containers matching {} with signal {}",
+                    selector, signal
+                )
+            }
+            model::GroupOp::Mount => format!("mount containers matching {}", selector),
+            model::GroupOp::Umount => format!("umount containers matching {}", selector),
+        },
+        model::Request::InstallCancel(id) => format!("cancel install {}", id),
+        model::Request::Kill(container, signal) => {
+            format!("kill {} with signal {}", container, signal)
+        }
+        model::Request::Mount(containers) => format!("mount {}", list(containers)),
+        model::Request::MountMatching(pattern) => format!("mount containers matching {}", pattern),
+        model::Request::Pause(container) => format!("pause {}", container),
+        model::Request::PolicyInput(input, value) => {
+            format!("set policy input {} to {}", input, value)
+        }
+        model::Request::Resume(container) => format!("resume {}", container),
+        model::Request::Shutdown => "shutdown".to_string(),
+        model::Request::Signal(container, signal, _) => {
+            format!("send signal {} to {}", signal, container)
+        }
+        model::Request::Start(container, ..) | model::Request::StartTemplate(container, _) => {
+            format!("start {}", container)
+        }
+        model::Request::SystemResume => "resume the system".to_string(),
+        model::Request::SystemSuspend(_) => "suspend the system".to_string(),
+        model::Request::TokenCreate(..) => "create a token".to_string(),
+        model::Request::Umount(containers) => format!("umount {}", list(containers)),
+        model::Request::UmountMatching(pattern) => {
+            format!("umount containers matching {}", pattern)
+        }
+        model::Request::Uninstall {
+            container,
+            force,
+            wipe,
+        } => format!("uninstall {} (force: {}, wipe: {})", container, force, wipe),
+        model::Request::Cancel(_)
+        | model::Request::ContainerStats(_)
+        | model::Request::Containers
+        | model::Request::CoreDumpExport(..)
+        | model::Request::CoreDumps(_)
+        | model::Request::DataExport(_)
+        | model::Request::Fs { .. }
+        | model::Request::Ident
+        | model::Request::Inspect(_)
+        | model::Request::Installs
+        | model::Request::Ps(_)
+        | model::Request::Repositories
+        | model::Request::Services(_)
+        | model::Request::StartDryRun(_)
+        | model::Request::SystemInfo
+        | model::Request::TokenVerify(..) => return None,
+    };
+    Some(action)
+}
+
+/// Returns true if `request` can be part of a batch
+fn is_batchable(request: &model::Request) -> bool {
+    matches!(
+        request,
+        model::Request::Kill(..)
+            | model::Request::Mount(..)
+            | model::Request::Start(..)
+            | model::Request::StartTemplate(..)
+            | model::Request::Umount(..)
+    )
+}
+
+/// Returns true if `request` transfers data on the connection after its message
+fn is_transfer(request: &model::Request) -> bool {
+    matches!(
+        request,
+        model::Request::Attach(..)
+            | model::Request::CoreDumpExport(..)
+            | model::Request::DataExport(..)
+            | model::Request::DataImport(..)
+            | model::Request::Install(..)
+    )
+}
+
+/// Process a request that is not pipelined. Requests with an id can be cancelled by
+/// the peer until the response is sent. Returns `None` if the response is already sent.
+#[allow(clippy::too_many_arguments)]
 async fn process_request<S>(
     peer: &Peer,
     stream: &mut Framed<S>,
     stop: &CancellationToken,
     configuration: &Configuration,
     event_loop: &EventTx,
-    token_validity: time::Duration,
+    installs: &Installs,
+    pending: &Pending,
+    token_validity: token::Validity,
+    id: Option<model::RequestId>,
     request: model::Request,
-) -> Result<model::Message, Error>
+) -> Result<Option<model::Message>, Error>
 where
-    S: AsyncRead + Unpin,
+    S: AsyncRead + AsyncWrite + Unpin,
 {
+    if !is_transfer(&request) {
+        return respond(
+            peer,
+            stop,
+            configuration,
+            event_loop,
+            installs,
+            pending,
+            token_validity,
+            id,
+            request,
+        )
+        .await
+        .map(Some);
+    }
+
+    if let Some(response) = permission_denied(configuration, &request) {
+        return Ok(Some(model::Message::Response { response, id }));
+    }
+
+    let pending_request = match register(peer, pending, id) {
+        Ok(pending_request) => pending_request,
+        Err(response) => return Ok(Some(model::Message::Response { response, id })),
+    };
+
+    let transfer = transfer(
+        peer,
+        stream,
+        stop,
+        configuration,
+        event_loop,
+        installs,
+        id,
+        request,
+    );
+    match pending_request {
+        // A cancelled transfer leaves unread or incomplete data on the connection
+        Some(pending_request) => {
+            let cancelled = pending_request.token();
+            select! {
+                result = transfer => result,
+                _ = cancelled.cancelled() => {
+                    info!("{}: Request {} cancelled", peer, pending_request.id());
+                    Err(Error::Cancelled)
+                }
+            }
+        }
+        None => transfer.await,
+    }
+}
+
+/// Process a request that doesn't transfer data on the connection. Requests with an
+/// id can be cancelled by the peer until the response is sent.
+#[allow(clippy::too_many_arguments)]
+async fn respond(
+    peer: &Peer,
+    stop: &CancellationToken,
+    configuration: &Configuration,
+    event_loop: &EventTx,
+    installs: &Installs,
+    pending: &Pending,
+    token_validity: token::Validity,
+    id: Option<model::RequestId>,
+    request: model::Request,
+) -> Result<model::Message, Error> {
+    // Cancelling a request of the same peer requires no permission
+    if let model::Request::Cancel(target) = request {
+        let response = if pending.cancel(&peer.id(), target) {
+            info!("{}: Cancelling request {}", peer, target);
+            model::Response::Ok
+        } else {
+            model::Response::Error(model::Error::InvalidArguments {
+                cause: format!("unknown request {}", target),
+            })
+        };
+        return Ok(model::Message::Response { response, id });
+    }
+
+    if let model::Request::Batch(requests, _) = &request {
+        if !requests.iter().all(is_batchable) {
+            let response = model::Response::Error(model::Error::InvalidArguments {
+                cause: "batches can only contain start, kill, mount and umount requests"
+                    .to_string(),
+            });
+            return Ok(model::Message::Response { response, id });
+        }
+    }
+
+    if let Some(response) = permission_denied(configuration, &request) {
+        return Ok(model::Message::Response { response, id });
+    }
+
+    let pending_request = match register(peer, pending, id) {
+        Ok(pending_request) => pending_request,
+        Err(response) => return Ok(model::Message::Response { response, id }),
+    };
+
+    let processing = process(
+        peer,
+        stop,
+        configuration,
+        event_loop,
+        installs,
+        token_validity,
+        request,
+    );
+    let response = match pending_request {
+        // Cancellation drops the reply receiver of the runtime. The runtime skips the
+        // request if it's not yet processed.
+        Some(pending_request) => {
+            let cancelled = pending_request.token();
+            select! {
+                response = processing => response?,
+                _ = cancelled.cancelled() => {
+                    let id = pending_request.id();
+                    info!("{}: Request {} cancelled", peer, id);
+                    model::Response::Error(model::Error::Cancelled { id })
+                }
+            }
+        }
+        None => processing.await?,
+    };
+    Ok(model::Message::Response { response, id })
+}
+
+/// Register request `id` of `peer` for cancellation. Returns an error response if the
+/// peer has a request with this id in progress.
+fn register(
+    peer: &Peer,
+    pending: &Pending,
+    id: Option<model::RequestId>,
+) -> Result<Option<PendingRequest>, model::Response> {
+    match id {
+        Some(id) => match pending.register(&peer.id(), id) {
+            Some(pending_request) => Ok(Some(pending_request)),
+            None => Err(model::Response::Error(model::Error::InvalidArguments {
+                cause: format!("request {} is in progress", id),
+            })),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Check the permission of `request`. Returns an error response if the permission is
+/// not granted.
+fn permission_denied(
+    configuration: &Configuration,
+    request: &model::Request,
+) -> Option<model::Response> {
     let permissions = &configuration.permissions;
-    let required_permission = match &request {
+    let required_permission = match request {
+        model::Request::Attach { .. } => Permission::Attach,
+        // Requests that cannot be batched are rejected before the permission check
+        model::Request::Batch(requests, _) => {
+            return requests
+                .iter()
+                .filter(|request| is_batchable(request))
+                .find_map(|request| permission_denied(configuration, request))
+        }
+        model::Request::Stdin(..) => Permission::Stdin,
+        model::Request::Cancel(..) => unreachable!(), // handled in respond
         model::Request::ContainerStats { .. } => Permission::ContainerStatistics,
         model::Request::Containers => Permission::Containers,
+        model::Request::CoreDumpExport { .. } => Permission::CoreDumps,
+        model::Request::CoreDumpRemove { .. } => Permission::CoreDumps,
+        model::Request::CoreDumps { .. } => Permission::CoreDumps,
+        model::Request::DataExport { .. } => Permission::DataExport,
+        model::Request::DataImport { .. } => Permission::DataImport,
+        model::Request::DataReset { .. } => Permission::DataReset,
+        model::Request::Fs { .. } => Permission::Fs,
+        model::Request::Group(_, model::GroupOp::Start) => Permission::Start,
+        model::Request::Group(_, model::GroupOp::Kill(_)) => Permission::Kill,
+        model::Request::Group(_, model::GroupOp::Mount) => Permission::Mount,
+        model::Request::Group(_, model::GroupOp::Umount) => Permission::Umount,
         model::Request::Ident { .. } => Permission::Ident,
+        model::Request::Inspect { .. } => Permission::Inspect,
         model::Request::Install { .. } => Permission::Install,
+        model::Request::InstallCancel { .. } => Permission::Install,
+        model::Request::Installs => Permission::Install,
         model::Request::Kill { .. } => Permission::Kill,
         model::Request::Mount { .. } => Permission::Mount,
+        model::Request::MountMatching { .. } => Permission::Mount,
+        model::Request::Pause { .. } => Permission::Pause,
+        model::Request::PolicyInput { .. } => Permission::PolicyInput,
+        model::Request::Ps { .. } => Permission::ContainerStatistics,
         model::Request::Repositories => Permission::Repositories,
+        model::Request::Resume { .. } => Permission::Pause,
+        model::Request::Services { .. } => Permission::Services,
         model::Request::Shutdown => Permission::Shutdown,
+        model::Request::Signal { .. } => Permission::Signal,
+        model::Request::Start(_, args, env) if !args.is_empty() || !env.is_empty() => {
+            Permission::StartOverride
+        }
         model::Request::Start { .. } => Permission::Start,
+        model::Request::StartDryRun { .. } => Permission::Start,
+        model::Request::StartTemplate { .. } => Permission::Start,
+        model::Request::SystemInfo => Permission::SystemInfo,
+        model::Request::SystemResume => Permission::Suspend,
+        model::Request::SystemSuspend { .. } => Permission::Suspend,
         model::Request::TokenCreate { .. } => Permission::Token,
         model::Request::TokenVerify { .. } => Permission::Token,
         model::Request::Umount { .. } => Permission::Umount,
+        model::Request::UmountMatching { .. } => Permission::Umount,
+        // Removing the persistent data requires the permissions to uninstall and reset data
+        model::Request::Uninstall { wipe: true, .. }
+            if !permissions.contains(&Permission::DataReset) =>
+        {
+            Permission::DataReset
+        }
         model::Request::Uninstall { .. } => Permission::Uninstall,
     };
 
-    if !permissions.contains(&required_permission) {
-        return Ok(model::Message::Response {
-            response: model::Response::Error(model::Error::PermissionDenied {
-                permissions: permissions.iter().cloned().collect(),
-                required: required_permission,
-            }),
-        });
+    if permissions.contains(&required_permission) {
+        None
+    } else {
+        Some(model::Response::Error(model::Error::PermissionDenied {
+            permissions: permissions.iter().cloned().collect(),
+            required: required_permission,
+        }))
     }
+}
+
+/// Check that `request` only acts on `containers` of the token scope of the connection.
+/// Requests that select containers by a pattern or selector cannot be checked and are
+/// refused. Tokens created on the connection must be limited to the scope as well.
+fn out_of_scope(
+    containers: &HashSet<Container>,
+    request: &model::Request,
+) -> Option<model::Response> {
+    let refuse = |container: Option<&Container>| {
+        Some(model::Response::Error(model::Error::OutOfScope {
+            container: container.cloned(),
+        }))
+    };
+    let check = |requested: &[Container]| {
+        requested
+            .iter()
+            .find(|container| !containers.contains(container))
+            .and_then(|container| refuse(Some(container)))
+    };
+
+    match request {
+        model::Request::Attach(container)
+        | model::Request::ContainerStats(container)
+        | model::Request::CoreDumpExport(container, _)
+        | model::Request::CoreDumpRemove(container, _)
+        | model::Request::CoreDumps(container)
+        | model::Request::DataExport(container)
+        | model::Request::DataImport(container, _)
+        | model::Request::DataReset(container)
+        | model::Request::Fs { container, .. }
+        | model::Request::Inspect(container)
+        | model::Request::Kill(container, _)
+        | model::Request::Pause(container)
+        | model::Request::Ps(container)
+        | model::Request::Resume(container)
+        | model::Request::Signal(container, ..)
+        | model::Request::Start(container, ..)
+        | model::Request::StartDryRun(container)
+        | model::Request::StartTemplate(container, _)
+        | model::Request::Stdin(container, _)
+        | model::Request::Uninstall { container, .. } => check(std::slice::from_ref(container)),
+        model::Request::Mount(requested)
+        | model::Request::Umount(requested)
+        | model::Request::SystemSuspend(requested) => check(requested),
+        model::Request::Batch(requests, _) => requests
+            .iter()
+            .find_map(|request| out_of_scope(containers, request)),
+        model::Request::Group(..)
+        | model::Request::MountMatching(_)
+        | model::Request::UmountMatching(_) => refuse(None),
+        model::Request::TokenCreate(_, _, scope, _) if scope.containers.is_empty() => refuse(None),
+        model::Request::TokenCreate(_, _, scope, _) => scope
+            .containers
+            .iter()
+            .find(|container| !containers.contains(container))
+            .and_then(|container| refuse(Some(container))),
+        model::Request::Cancel(_)
+        | model::Request::Containers
+        | model::Request::Ident
+        | model::Request::Install(..)
+        | model::Request::InstallCancel(_)
+        | model::Request::Installs
+        | model::Request::PolicyInput(..)
+        | model::Request::Repositories
+        | model::Request::Services(_)
+        | model::Request::Shutdown
+        | model::Request::SystemInfo
+        | model::Request::SystemResume
+        | model::Request::TokenVerify(..) => None,
+    }
+}
+
+/// Process a request that transfers data on the connection. Returns `None` if the
+/// response is already sent.
+///
+/// # Errors
+///
+/// If the streamed NPK is not valid and parseable a `Error::Npk(..)` is returned.
+/// If the event loop is closed due to shutdown, this function will return `Error::EventLoopClosed`.
+///
+#[allow(clippy::too_many_arguments)]
+async fn transfer<S>(
+    peer: &Peer,
+    stream: &mut Framed<S>,
+    stop: &CancellationToken,
+    configuration: &Configuration,
+    event_loop: &EventTx,
+    installs: &Installs,
+    id: Option<model::RequestId>,
+    request: model::Request,
+) -> Result<Option<model::Message>, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Queue entry and turn of an install. Both are kept until the runtime replies.
+    let mut _install = None;
 
     let (reply_tx, reply_rx) = oneshot::channel();
     match request {
-        model::Request::Ident => {
-            let ident = match peer {
-                #[allow(clippy::unwrap_used)]
-                Peer::Extern(_) => Container::try_from("remote:0.0.0").unwrap(),
-                Peer::Container(container) => container.clone(),
-            };
-            let response = api::model::Response::Ident(ident);
-            reply_tx.send(response).ok();
-        }
-        model::Request::Install(repository, mut size) => {
+        model::Request::Install(repository, size) => {
             debug!(
                 "{}: Received installation request with size {}",
                 peer,
                 bytesize::ByteSize::b(size)
             );
 
-            // Check the installation request size
-            let max_install_stream_size = configuration
-                .max_npk_install_size
-                .unwrap_or(DEFAULT_MAX_INSTALL_STREAM_SIZE);
-            if size > max_install_stream_size {
-                return Err(Error::Io(
-                    "npk size too large".into(),
-                    io::Error::new(io::ErrorKind::InvalidData, "npk size too large"),
-                ));
-            }
+            check_upload_size(configuration, size)?;
 
             info!("{}: Using repository \"{}\"", peer, repository);
 
+            // Wait for the preceding installs
+            let mut install = installs.enqueue(repository.clone(), size);
+            let cancel = install.cancel_token();
+            debug!("{}: Queued install {}", peer, install.id());
+            let turn = select! {
+                turn = install.turn() => turn,
+                _ = cancel.cancelled() => return Err(Error::Cancelled),
+                _ = stop.cancelled() => return Err(Error::Shutdown),
+            };
+
             // Send a Receiver<Bytes> to the runtime and forward n bytes to this channel
             let (tx, rx) = mpsc::channel(10);
-            let request = Request::Install(repository, rx);
+            let request = Request::Install(repository, size, rx);
             trace!("    {:?} -> event loop", request);
             let event = Event::Console(request, reply_tx);
             event_loop.send(event).map_err(|_| Error::Shutdown).await?;
 
-            // The codec might have pulled bytes in the the read buffer of the connection.
-            if !stream.read_buffer().is_empty() {
-                let read_buffer = stream.read_buffer_mut().split();
+            // A cancelled install leaves unread bytes on the connection which is closed
+            let upload = forward_upload(stream, configuration, size, tx, |bytes| {
+                install.progress(bytes)
+            });
+            select! {
+                result = upload => result?,
+                _ = cancel.cancelled() => return Err(Error::Cancelled),
+            }
+            _install = Some((install, turn));
+        }
+        model::Request::DataImport(container, size) => {
+            debug!(
+                "{}: Received data import request for {} with size {}",
+                peer,
+                container,
+                bytesize::ByteSize::b(size)
+            );
+
+            check_upload_size(configuration, size)?;
+
+            let (tx, rx) = mpsc::channel(10);
+            let request = Request::DataImport(container, rx);
+            trace!("    {:?} -> event loop", request);
+            let event = Event::Console(request, reply_tx);
+            event_loop.send(event).map_err(|_| Error::Shutdown).await?;
+
+            forward_upload(stream, configuration, size, tx, |_| ()).await?;
+        }
+        model::Request::DataExport(_) | model::Request::CoreDumpExport(_, _) => {
+            let (tx, mut rx) = mpsc::channel(10);
+            let (request, context) = match request {
+                model::Request::DataExport(container) => {
+                    debug!("{}: Received data export request for {}", peer, container);
+                    (Request::DataExport(container, tx), "data export")
+                }
+                model::Request::CoreDumpExport(container, name) => {
+                    debug!(
+                        "{}: Received core dump export request for {} of {}",
+                        peer, name, container
+                    );
+                    (
+                        Request::CoreDumpExport(container, name, tx),
+                        "core dump export",
+                    )
+                }
+                _ => unreachable!(),
+            };
+            trace!("    {:?} -> event loop", request);
+            let event = Event::Console(request, reply_tx);
+            event_loop.send(event).map_err(|_| Error::Shutdown).await?;
+
+            let response = reply(stop, reply_rx).await?;
 
-                // TODO: handle this case. The connected entity pushed the install file
-                // and a subsequent request. If the codec pulls in the *full* install blob
-                // and some bytes from the following command the logic is screwed up.
-                assert!(read_buffer.len() as u64 <= size);
+            // Send the response followed by `size` bytes of the archive or dump
+            let size = match response {
+                model::Response::DataExport(size) | model::Response::CoreDumpExport(size) => size,
+                response => return Ok(Some(model::Message::Response { response, id })),
+            };
+            let message = model::Message::Response { response, id };
+            stream
+                .send(message)
+                .await
+                .map_err(|e| Error::Io(context.into(), e))?;
 
-                size -= read_buffer.len() as u64;
-                tx.send(read_buffer.freeze()).await.ok();
+            let mut written = 0;
+            while let Some(buf) = rx.recv().await {
+                stream
+                    .get_mut()
+                    .write_all(&buf)
+                    .await
+                    .map_err(|e| Error::Io(context.into(), e))?;
+                written += buf.len() as u64;
             }
+            stream
+                .get_mut()
+                .flush()
+                .await
+                .map_err(|e| Error::Io(context.into(), e))?;
 
-            // If the connections breaks: just break. If the receiver is dropped: just break.
-            let mut take = ReaderStream::with_capacity(stream.get_mut().take(size), 1024 * 1024);
-            let timeout = time::Duration::from_secs(
-                configuration
-                    .npk_stream_timeout
-                    .unwrap_or(DEFAULT_NPK_STREAM_TIMEOUT),
-            );
-            while let Some(buf) = time::timeout(timeout, take.next()).await.map_err(|_| {
-                Error::Io(
-                    "npk stream timeout".into(),
-                    io::Error::new(io::ErrorKind::TimedOut, "timeout"),
-                )
-            })? {
-                let buf = buf.map_err(|e| Error::Io("npk stream".into(), e))?;
-                // Ignore any sending error because the stream needs to be drained for `size` bytes.
-                tx.send(buf).await.ok();
+            // The client expects exactly `size` bytes and cannot resync
+            if written != size {
+                return Err(Error::Io(
+                    context.into(),
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "incomplete transfer"),
+                ));
             }
+            return Ok(None);
         }
-        model::Request::TokenCreate(target, shared) => {
+        model::Request::Attach(container) => {
+            debug!("{}: Received attach request for {}", peer, container);
+
+            let (output_tx, mut output_rx) = mpsc::channel(10);
+            let (input_tx, input_rx) = mpsc::channel(10);
+            let request = Request::Attach(container, output_tx, input_rx);
+            trace!("    {:?} -> event loop", request);
+            let event = Event::Console(request, reply_tx);
+            event_loop.send(event).map_err(|_| Error::Shutdown).await?;
+
+            let response = reply(stop, reply_rx).await?;
+
+            // Send the response followed by the raw PTY data in both directions
+            if response != model::Response::Ok {
+                return Ok(Some(model::Message::Response { response, id }));
+            }
+            let message = model::Message::Response { response, id };
+            stream
+                .send(message)
+                .await
+                .map_err(|e| Error::Io("attach".into(), e))?;
+
+            // The codec might have pulled input into the read buffer of the connection
+            if !stream.read_buffer().is_empty() {
+                let read_buffer = stream.read_buffer_mut().split();
+                input_tx.send(read_buffer.freeze()).await.ok();
+            }
+
+            // Bridge until the container exits or the client disconnects
+            let (reader, mut writer) = io::split(stream.get_mut());
+            let mut input = ReaderStream::new(reader);
+            loop {
+                select! {
+                    output = output_rx.recv() => match output {
+                        Some(output) => {
+                            writer
+                                .write_all(&output)
+                                .await
+                                .map_err(|e| Error::Io("attach".into(), e))?;
+                            writer
+                                .flush()
+                                .await
+                                .map_err(|e| Error::Io("attach".into(), e))?;
+                        }
+                        None => break,
+                    },
+                    buf = input.next() => match buf {
+                        Some(Ok(buf)) => {
+                            if input_tx.send(buf).await.is_err() {
+                                break;
+                            }
+                        }
+                        _ => break,
+                    },
+                    _ = stop.cancelled() => break,
+                }
+            }
+            return Err(Error::Detached);
+        }
+        _ => unreachable!("not a transfer"),
+    }
+
+    reply(stop, reply_rx)
+        .await
+        .map(|response| Some(model::Message::Response { response, id }))
+}
+
+/// Process a request that doesn't transfer data on the connection
+async fn process(
+    peer: &Peer,
+    stop: &CancellationToken,
+    configuration: &Configuration,
+    event_loop: &EventTx,
+    installs: &Installs,
+    token_validity: token::Validity,
+    request: model::Request,
+) -> Result<model::Response, Error> {
+    let permissions = &configuration.permissions;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    match request {
+        model::Request::Ident => {
+            let ident = match peer {
+                #[allow(clippy::unwrap_used)]
+                Peer::Extern(_) | Peer::Local(..) => Container::try_from("remote:0.0.0").unwrap(),
+                Peer::Container(container) => container.clone(),
+            };
+            let response = api::model::Response::Ident(ident);
+            reply_tx.send(response).ok();
+        }
+        model::Request::Installs => {
+            let response = model::Response::Installs(installs.list());
+            reply_tx.send(response).ok();
+        }
+        model::Request::InstallCancel(id) => {
+            let response = if installs.cancel(id) {
+                info!("{}: Cancelling install {}", peer, id);
+                model::Response::Ok
+            } else {
+                model::Response::Error(model::Error::InvalidArguments {
+                    cause: format!("unknown install {}", id),
+                })
+            };
+            reply_tx.send(response).ok();
+        }
+        model::Request::TokenCreate(target, shared, scope, validity) => {
+            // A token cannot grant more permissions than its creator has
+            if let Some(permission) = scope
+                .permissions
+                .iter()
+                .find(|permission| !permissions.contains(permission))
+            {
+                return Ok(model::Response::Error(model::Error::PermissionDenied {
+                    permissions: permissions.iter().cloned().collect(),
+                    required: permission.clone(),
+                }));
+            }
+
             let user = match peer {
-                Peer::Extern(_) => "extern",
+                Peer::Extern(_) | Peer::Local(..) => "extern",
                 Peer::Container(container) => container.name().as_ref(),
             };
+            let validity = token_validity.get(validity.map(time::Duration::from_secs));
             info!(
-                "Creating token for user \"{}\" and target \"{}\" with shared \"{}\"",
+                "Creating token for user \"{}\" and target \"{}\" with shared \"{}\" valid for {:?}",
                 hex::encode(&user),
                 hex::encode(&target),
-                hex::encode(&shared)
+                hex::encode(&shared),
+                validity
             );
-            let token: [u8; 40] = Token::new(token_validity, user, target, shared).into();
+            let token: Vec<u8> = Token::new(validity, user, target, shared, &scope).into();
             let token = api::model::Token::from(token);
             let response = api::model::Response::Token(token);
             reply_tx.send(response).ok();
         }
         model::Request::TokenVerify(token, user, shared) => {
             let target = match peer {
-                Peer::Extern(_) => "extern",
+                Peer::Extern(_) | Peer::Local(..) => "extern",
                 Peer::Container(container) => container.name().as_ref(),
             };
             info!(
@@ -469,9 +1341,10 @@ where
                 hex::encode(&target),
                 hex::encode(&shared)
             );
-            let token: [u8; 40] = token.into();
-            let token = Token::from((token_validity, token));
-            let result = token.verify(user, target, &shared).into();
+            let result = match Token::try_from(token.as_ref()) {
+                Ok(token) => token.verify(user, target, &shared).into(),
+                Err(_) => api::model::VerificationResult::Invalid,
+            };
             let response = api::model::Response::TokenVerification(result);
             reply_tx.send(response).ok();
         }
@@ -483,15 +1356,108 @@ where
         }
     }
 
-    (select! {
-        reply = reply_rx => reply.map_err(|_| Error::Shutdown),
-        _ = stop.cancelled() => Err(Error::Shutdown), // There can be a shutdown while we're waiting for an reply
-    })
-    .map(|response| {
-        trace!("    {:?} <- event loop", response);
-        response
-    })
-    .map(|response| model::Message::Response { response })
+    reply(stop, reply_rx).await
+}
+
+/// Wait for the reply of the runtime to a request
+async fn reply(
+    stop: &CancellationToken,
+    reply_rx: oneshot::Receiver<model::Response>,
+) -> Result<model::Response, Error> {
+    let response = select! {
+        reply = reply_rx => reply.map_err(|_| Error::Shutdown)?,
+        _ = stop.cancelled() => return Err(Error::Shutdown), // There can be a shutdown while we're waiting for an reply
+    };
+    trace!("    {:?} <- event loop", response);
+    Ok(response)
+}
+
+/// Verify the token of a connect message and return the scope of a valid token
+fn authenticate(
+    token: &model::ConnectToken,
+) -> Result<model::TokenScope, model::VerificationResult> {
+    let result = match Token::try_from(token.token.as_ref()) {
+        Ok(t) => t
+            .verify(
+                &token.user,
+                model::CONSOLE_TOKEN_TARGET,
+                model::CONSOLE_TOKEN_SHARED,
+            )
+            .into(),
+        Err(_) => model::VerificationResult::Invalid,
+    };
+    match result {
+        model::VerificationResult::Ok(scope) => Ok(scope),
+        result => Err(result),
+    }
+}
+
+/// Check the size of an upload against the configured maximum
+fn check_upload_size(configuration: &Configuration, size: u64) -> Result<(), Error> {
+    let max_install_stream_size = configuration
+        .max_npk_install_size
+        .unwrap_or(DEFAULT_MAX_INSTALL_STREAM_SIZE);
+    if size > max_install_stream_size {
+        Err(Error::Io(
+            "npk size too large".into(),
+            io::Error::new(io::ErrorKind::InvalidData, "npk size too large"),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Forward `size` bytes from the connection to `tx`. This is used for uploads that
+/// follow the request on the connection e.g. npks. `progress` is called with the
+/// number of bytes forwarded so far.
+async fn forward_upload<S, F>(
+    stream: &mut Framed<S>,
+    configuration: &Configuration,
+    mut size: u64,
+    tx: mpsc::Sender<Bytes>,
+    mut progress: F,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnMut(u64),
+{
+    let mut forwarded = 0;
+
+    // The codec might have pulled bytes in the the read buffer of the connection.
+    if !stream.read_buffer().is_empty() {
+        let read_buffer = stream.read_buffer_mut().split();
+
+        // TODO: handle this case. The connected entity pushed the install file
+        // and a subsequent request. If the codec pulls in the *full* install blob
+        // and some bytes from the following command the logic is screwed up.
+        assert!(read_buffer.len() as u64 <= size);
+
+        size -= read_buffer.len() as u64;
+        forwarded += read_buffer.len() as u64;
+        tx.send(read_buffer.freeze()).await.ok();
+        progress(forwarded);
+    }
+
+    // If the connections breaks: just break. If the receiver is dropped: just break.
+    let mut take = ReaderStream::with_capacity(stream.get_mut().take(size), 1024 * 1024);
+    let timeout = time::Duration::from_secs(
+        configuration
+            .npk_stream_timeout
+            .unwrap_or(DEFAULT_NPK_STREAM_TIMEOUT),
+    );
+    while let Some(buf) = time::timeout(timeout, take.next()).await.map_err(|_| {
+        Error::Io(
+            "npk stream timeout".into(),
+            io::Error::new(io::ErrorKind::TimedOut, "timeout"),
+        )
+    })? {
+        let buf = buf.map_err(|e| Error::Io("npk stream".into(), e))?;
+        forwarded += buf.len() as u64;
+        // Ignore any sending error because the stream needs to be drained for `size` bytes.
+        tx.send(buf).await.ok();
+        progress(forwarded);
+    }
+    Ok(())
 }
 
 /// Types of listeners for console connections
@@ -529,10 +1495,68 @@ impl Listener {
                 debug!("Started console on {}", path.display());
                 Listener::Unix(listener)
             }
+            "unix+abstract" => {
+                let name = format!("{}{}", url.host_str().unwrap_or_default(), url.path());
+                let listener = bind_abstract(&name)?;
+
+                debug!("Started console on abstract socket {}", name);
+                Listener::Unix(listener)
+            }
+            "systemd" => {
+                let name = url.host_str().unwrap_or_default();
+                let listener = Listener::from_socket(activation::listener(name)?)?;
+
+                debug!("Started console on passed socket {}", name);
+                listener
+            }
             _ => unreachable!(),
         };
         Ok(listener)
     }
+
+    fn from_socket(socket: activation::Socket) -> io::Result<Listener> {
+        match socket {
+            activation::Socket::Tcp(listener) => {
+                listener.set_nonblocking(true)?;
+                Ok(Listener::Tcp(TcpListener::from_std(listener)?))
+            }
+            activation::Socket::Unix(listener) => {
+                listener.set_nonblocking(true)?;
+                Ok(Listener::Unix(UnixListener::from_std(listener)?))
+            }
+        }
+    }
+
+    /// Duplicate the listening socket
+    fn try_clone_fd(&self) -> io::Result<OwnedFd> {
+        let fd = match self {
+            Listener::Tcp(listener) => listener.as_raw_fd(),
+            Listener::Unix(listener) => listener.as_raw_fd(),
+        };
+        fcntl::fcntl(fd, fcntl::FcntlArg::F_DUPFD_CLOEXEC(0))
+            .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+            .map_err(|e| io::Error::from_raw_os_error(e as i32))
+    }
+}
+
+/// Bind a listener to the abstract unix socket `name`. Abstract sockets have no
+/// file system entry.
+fn bind_abstract(name: &str) -> io::Result<UnixListener> {
+    let errno = |e: nix::Error| io::Error::from_raw_os_error(e as i32);
+    let fd = socket::socket(
+        AddressFamily::Unix,
+        SockType::Stream,
+        SockFlag::SOCK_CLOEXEC | SockFlag::SOCK_NONBLOCK,
+        None,
+    )
+    .map_err(errno)?;
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+
+    let address = UnixAddr::new_abstract(name.as_bytes()).map_err(errno)?;
+    socket::bind(fd, &address).map_err(errno)?;
+    socket::listen(fd, 128).map_err(errno)?;
+
+    UnixListener::from_std(listener)
 }
 
 /// Function to handle connections
@@ -545,19 +1569,23 @@ impl Listener {
 /// these tasks terminate, they are removed from the connections container. Once a stop is issued,
 /// the termination of the remaining connections will be awaited.
 ///
+#[allow(clippy::too_many_arguments)]
 async fn serve<AcceptFun, AcceptFuture, Stream, Addr>(
     accept: AcceptFun,
     event_tx: EventTx,
-    notification_tx: broadcast::Sender<(Container, ContainerEvent)>,
+    notification_tx: NotificationTx,
+    installs: Installs,
+    pending: Pending,
     stop: CancellationToken,
     configuration: Configuration,
-    token_validity: time::Duration,
+    token_validity: token::Validity,
 ) where
     AcceptFun: Fn() -> AcceptFuture,
     AcceptFuture: Future<Output = Result<(Stream, Addr), io::Error>>,
     Stream: AsyncWrite + AsyncRead + Unpin + Send + 'static,
     Addr: Into<Peer>,
 {
+    let peers = Arc::new(Peers::new(&configuration, notification_tx.clone()));
     let mut connections = FuturesUnordered::new();
     loop {
         select! {
@@ -576,7 +1604,10 @@ async fn serve<AcceptFun, AcceptFuture, Stream, Addr>(
                             configuration.clone(),
                             token_validity,
                             event_tx.clone(),
-                            notification_tx.subscribe(),
+                            notification_tx.clone(),
+                            installs.clone(),
+                            pending.clone(),
+                            Some(peers.clone()),
                             Some(time::Duration::from_secs(10)),
                         )));
                     }
@@ -598,11 +1629,62 @@ async fn serve<AcceptFun, AcceptFuture, Stream, Addr>(
     debug!("Closed listener");
 }
 
+/// Accept a connection on a unix listener and identify the peer by the credentials
+/// of the connecting process
+async fn accept_unix(listener: &UnixListener) -> Result<(UnixStream, Peer), io::Error> {
+    let (stream, address) = listener.accept().await?;
+    let path = address
+        .as_pathname()
+        .unwrap_or_else(|| Path::new("unnamed"))
+        .display();
+    let url = Url::parse(&format!("unix://{}", path)).expect("invalid url");
+    let peer = match stream.peer_cred() {
+        Ok(credentials) => {
+            let pid = credentials.pid();
+            let uid = credentials.uid();
+            let gid = credentials.gid();
+            // SO_PEERCRED only contains the primary group
+            let groups = pid
+                .and_then(|pid| process::groups(pid as Pid, uid, gid))
+                .unwrap_or_else(|| {
+                    debug!("Failed to read the supplementary groups of {}", url);
+                    Vec::new()
+                });
+            Peer::Local(
+                url,
+                Credentials {
+                    pid,
+                    uid,
+                    gid,
+                    groups,
+                },
+            )
+        }
+        Err(e) => {
+            warn!("Failed to get the credentials of {}: {}", url, e);
+            Peer::Extern(url)
+        }
+    };
+    Ok((stream, peer))
+}
+
 pub enum Peer {
     Extern(Url),
+    /// Process connected to a unix listener
+    Local(Url, Credentials),
     Container(Container),
 }
 
+/// Credentials of a process connected to a unix listener (SO_PEERCRED)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    pub pid: Option<i32>,
+    pub uid: u32,
+    pub gid: u32,
+    /// Supplementary groups from `/proc/<pid>/status`. Empty if not available.
+    pub groups: Vec<u32>,
+}
+
 impl From<std::net::SocketAddr> for Peer {
     fn from(socket: std::net::SocketAddr) -> Self {
         Url::parse(&format!("tcp://{}:{}", socket.ip(), socket.port()))
@@ -611,15 +1693,18 @@ impl From<std::net::SocketAddr> for Peer {
     }
 }
 
-impl From<tokio::net::unix::SocketAddr> for Peer {
-    fn from(socket: tokio::net::unix::SocketAddr) -> Self {
-        let path = socket
-            .as_pathname()
-            .unwrap_or_else(|| Path::new("unnamed"))
-            .display();
-        Url::parse(&format!("unix://{}", path))
-            .map(Peer::Extern)
-            .expect("invalid url")
+impl Peer {
+    /// Identity of the peer across connections. Remote tcp peers are identified by
+    /// their ip address and local processes by their user id.
+    fn id(&self) -> String {
+        match self {
+            Peer::Extern(url) if url.scheme() == "tcp" => {
+                format!("tcp://{}", url.host_str().unwrap_or_default())
+            }
+            Peer::Extern(url) => url.to_string(),
+            Peer::Local(url, credentials) => format!("{}?uid={}", url, credentials.uid),
+            Peer::Container(container) => container.to_string(),
+        }
     }
 }
 
@@ -627,6 +1712,17 @@ impl fmt::Display for Peer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Peer::Extern(url) => write!(f, "Remote({})", url),
+            Peer::Local(url, credentials) => {
+                write!(
+                    f,
+                    "Local({}, uid {}, gid {}",
+                    url, credentials.uid, credentials.gid
+                )?;
+                if let Some(pid) = credentials.pid {
+                    write!(f, ", pid {}", pid)?;
+                }
+                write!(f, ")")
+            }
             Peer::Container(container) => write!(f, "Container({})", container),
         }
     }
@@ -636,8 +1732,9 @@ impl From<ExitStatus> for model::ExitStatus {
     fn from(e: ExitStatus) -> Self {
         match e {
             ExitStatus::Exit(code) => api::model::ExitStatus::Exit { code },
-            ExitStatus::Signalled(signal) => api::model::ExitStatus::Signalled {
+            ExitStatus::Signalled(signal, cause) => api::model::ExitStatus::Signalled {
                 signal: signal as u32,
+                cause,
             },
         }
     }
@@ -648,11 +1745,36 @@ impl From<(Container, ContainerEvent)> for model::Notification {
         let container = p.0.clone();
         match p.1 {
             ContainerEvent::Started => api::model::Notification::Started(container),
+            ContainerEvent::StartFailed(reason) => {
+                api::model::Notification::StartFailed(container, reason)
+            }
             ContainerEvent::Exit(status) => {
                 api::model::Notification::Exit(container, status.into())
             }
             ContainerEvent::Installed => api::model::Notification::Install(container),
             ContainerEvent::Uninstalled => api::model::Notification::Uninstall(container),
+            ContainerEvent::Paused => api::model::Notification::Paused(container),
+            ContainerEvent::Resumed => api::model::Notification::Resumed(container),
+            ContainerEvent::DataReset => api::model::Notification::DataReset(container),
+            ContainerEvent::Log(line) => api::model::Notification::Log(container, line),
+            ContainerEvent::CoreDump(dump) => api::model::Notification::CoreDump(container, dump),
+            ContainerEvent::Kernel(message) => {
+                let kind = match message.kind {
+                    KernelMessageKind::Oom => api::model::KernelNotificationKind::Oom,
+                    KernelMessageKind::Seccomp { syscall } => {
+                        api::model::KernelNotificationKind::Seccomp { syscall }
+                    }
+                    KernelMessageKind::Segfault => api::model::KernelNotificationKind::Segfault,
+                };
+                api::model::Notification::Kernel(
+                    container,
+                    api::model::KernelNotification {
+                        kind,
+                        pid: message.pid,
+                        message: message.message,
+                    },
+                )
+            }
             ContainerEvent::CGroup(event) => match event {
                 super::CGroupEvent::Memory(memory) => api::model::Notification::CGroup(
                     container,
@@ -0,0 +1,422 @@
+use super::{
+    config::CoreDumps,
+    error::{Context, Error},
+};
+use crate::{
+    api::model::{self, CoreDumpKind},
+    common::container::Container,
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    ffi::OsStr,
+    fs as stdfs,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs, sync::mpsc, task};
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
+
+/// First argument of the runtime binary when started by the kernel as core dump handler
+const HELPER_ARG: &str = "--northstar-core-dump";
+/// Kernel core pattern
+const CORE_PATTERN: &str = "/proc/sys/kernel/core_pattern";
+/// Number of concurrent dumps the kernel waits for the handler to finish
+const CORE_PIPE_LIMIT: &str = "/proc/sys/kernel/core_pipe_limit";
+/// Maximum length of the kernel core pattern
+const CORE_PATTERN_MAX: usize = 127;
+/// Prefix of core dump file names
+const CORE_PREFIX: &str = "core";
+/// Prefix of minidump file names
+const MINIDUMP_PREFIX: &str = "minidump";
+
+/// Handle a core dump if the current process is started by the kernel as core dump
+/// handler. The dump is read from stdin and the process exits. Returns otherwise.
+///
+/// Call this first in the main function of the binary that starts the runtime if
+/// core dumps are configured.
+pub fn helper() {
+    let mut args = env::args_os().skip(1);
+    if args.next().as_deref() != Some(OsStr::new(HELPER_ARG)) {
+        return;
+    }
+    let args = args
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    let code = match receive(&args, io::stdin().lock()) {
+        Ok(_) => 0,
+        Err(_) => 1,
+    };
+    process::exit(code);
+}
+
+/// Kernel core dump settings replaced by the runtime
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct CorePattern {
+    pattern: String,
+    pipe_limit: String,
+}
+
+impl CorePattern {
+    /// Register the runtime binary as the core dump handler of the kernel. Returns
+    /// the previous settings that are restored on shutdown.
+    pub(super) async fn install(
+        config: &CoreDumps,
+        cgroup: &str,
+    ) -> Result<Option<CorePattern>, Error> {
+        // The kernel passes the pid in the initial pid namespace, the signal and the time
+        let pattern = format!(
+            "|/proc/{}/exe {} {} {} {} {} %P %s %t",
+            process::id(),
+            HELPER_ARG,
+            config.dir.display(),
+            config.max_size,
+            config.max_count,
+            cgroup
+        );
+        if pattern.len() > CORE_PATTERN_MAX {
+            warn!(
+                "Core dumps are disabled: core pattern {} exceeds {} bytes",
+                pattern, CORE_PATTERN_MAX
+            );
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&config.dir)
+            .await
+            .context(format!("failed to create {}", config.dir.display()))?;
+
+        let previous = CorePattern {
+            pattern: read(CORE_PATTERN).await?,
+            pipe_limit: read(CORE_PIPE_LIMIT).await?,
+        };
+
+        info!("Installing core dump handler");
+        // The pid of a dumping process is valid until the handler exits if the limit is non zero
+        if previous.pipe_limit == "0" {
+            write(CORE_PIPE_LIMIT, "16").await?;
+        }
+        write(CORE_PATTERN, &pattern).await?;
+
+        Ok(Some(previous))
+    }
+
+    /// Restore the settings replaced on install
+    pub(super) async fn restore(self) -> Result<(), Error> {
+        info!("Restoring core pattern {}", self.pattern);
+        write(CORE_PATTERN, &self.pattern).await?;
+        write(CORE_PIPE_LIMIT, &self.pipe_limit).await
+    }
+}
+
+/// Create the unnamed file a minidump of a container is written to
+pub(super) fn minidump_file(config: &CoreDumps) -> Result<stdfs::File, Error> {
+    stdfs::create_dir_all(&config.dir)
+        .context(format!("failed to create {}", config.dir.display()))?;
+    tempfile::tempfile_in(&config.dir).context("failed to create minidump file")
+}
+
+/// Store the minidump written to `file` by the init process of `container` if
+/// there's any. `pid` is the pid of the container.
+pub(super) async fn store_minidump(
+    config: &CoreDumps,
+    container: &Container,
+    mut file: stdfs::File,
+    pid: u32,
+    signal: u32,
+) -> Result<Option<String>, Error> {
+    let size = file
+        .metadata()
+        .context("failed to stat minidump file")?
+        .len();
+    if size == 0 {
+        return Ok(None);
+    } else if size > config.max_size {
+        warn!(
+            "Discarding minidump of {} with {} bytes: exceeds the maximum size",
+            container, size
+        );
+        return Ok(None);
+    }
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let name = file_name(CoreDumpKind::Minidump, time, pid, signal);
+    let dir = dir(config, container);
+    let max_count = config.max_count;
+    let dump = name.clone();
+    task::spawn_blocking(move || {
+        file.rewind()?;
+        store(&dir, &dump, size, max_count, file)
+    })
+    .await
+    .context("failed to join minidump task")?
+    .map_err(|e| Error::CoreDump(container.clone(), e.to_string()))?;
+    Ok(Some(name))
+}
+
+/// Core dumps of `container` ordered by time
+pub(super) async fn list(
+    config: &CoreDumps,
+    container: &Container,
+) -> Result<Vec<model::CoreDump>, Error> {
+    let dir = dir(config, container);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    task::spawn_blocking(move || list_dir(&dir))
+        .await
+        .context("failed to join list task")?
+        .map_err(|e| Error::CoreDump(container.clone(), e.to_string()))
+}
+
+/// Stream the core dump `name` of `container` to `tx`. Returns the size of the dump.
+pub(super) async fn export(
+    config: &CoreDumps,
+    container: &Container,
+    name: &str,
+    tx: mpsc::Sender<Bytes>,
+) -> Result<u64, Error> {
+    let path = path(config, container, name)?;
+    let error = |e: io::Error| Error::CoreDump(container.clone(), e.to_string());
+    let file = fs::File::open(&path).await.map_err(error)?;
+    let size = file.metadata().await.map_err(error)?.len();
+
+    info!("Exporting core dump {} of {}", name, container);
+    let mut stream = ReaderStream::new(file);
+    task::spawn(async move {
+        while let Some(Ok(buf)) = stream.next().await {
+            if tx.send(buf).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(size)
+}
+
+/// Remove the core dump `name` of `container`
+pub(super) async fn remove(
+    config: &CoreDumps,
+    container: &Container,
+    name: &str,
+) -> Result<(), Error> {
+    let path = path(config, container, name)?;
+    info!("Removing core dump {} of {}", name, container);
+    fs::remove_file(&path)
+        .await
+        .map_err(|e| Error::CoreDump(container.clone(), e.to_string()))
+}
+
+/// Directory with the dumps of `container`
+fn dir(config: &CoreDumps, container: &Container) -> PathBuf {
+    config.dir.join(container.name().to_string())
+}
+
+/// Path of the dump `name`. Fails if `name` is not the name of a dump.
+fn path(config: &CoreDumps, container: &Container, name: &str) -> Result<PathBuf, Error> {
+    if parse(name).is_none() {
+        return Err(Error::CoreDump(
+            container.clone(),
+            format!("invalid core dump {}", name),
+        ));
+    }
+    Ok(dir(config, container).join(name))
+}
+
+/// Dump file names are "core.<time>.<pid>.<signal>" and "minidump.<time>.<pid>.<signal>"
+fn file_name(kind: CoreDumpKind, time: u64, pid: u32, signal: u32) -> String {
+    let prefix = match kind {
+        CoreDumpKind::Core => CORE_PREFIX,
+        CoreDumpKind::Minidump => MINIDUMP_PREFIX,
+    };
+    format!("{}.{}.{}.{}", prefix, time, pid, signal)
+}
+
+/// Parse a dump file name into kind, time, pid and signal
+fn parse(name: &str) -> Option<(CoreDumpKind, u64, u32, u32)> {
+    let mut parts = name.split('.');
+    let kind = match parts.next()? {
+        CORE_PREFIX => CoreDumpKind::Core,
+        MINIDUMP_PREFIX => CoreDumpKind::Minidump,
+        _ => return None,
+    };
+    let time = parts.next()?.parse().ok()?;
+    let pid = parts.next()?.parse().ok()?;
+    let signal = parts.next()?.parse().ok()?;
+    parts.next().is_none().then(|| (kind, time, pid, signal))
+}
+
+fn list_dir(dir: &Path) -> io::Result<Vec<model::CoreDump>> {
+    let mut dumps = Vec::new();
+    for entry in stdfs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some((kind, time, pid, signal)) = parse(&name) {
+            dumps.push(model::CoreDump {
+                name,
+                kind,
+                pid,
+                signal,
+                time,
+                size: entry.metadata()?.len(),
+            });
+        }
+    }
+    dumps.sort_by_key(|dump| dump.time);
+    Ok(dumps)
+}
+
+/// Name of the container of a process from the content of /proc/<pid>/cgroup. Container
+/// cgroups are "<cgroup>/<name>".
+fn container_name(cgroups: &str, top_level: &str) -> Option<String> {
+    // Lines of /proc/<pid>/cgroup are "hierarchy-ID:controller-list:cgroup-path"
+    cgroups
+        .lines()
+        .filter_map(|l| l.splitn(3, ':').nth(2))
+        .find_map(|cgroup| {
+            let cgroup = Path::new(cgroup.trim_start_matches('/'));
+            if cgroup.parent()? == Path::new(top_level) {
+                cgroup.file_name()?.to_str().map(ToString::to_string)
+            } else {
+                None
+            }
+        })
+}
+
+/// Store the dump read from `core`. The arguments are the ones of the core pattern.
+/// This runs in the handler process started by the kernel.
+fn receive(args: &[String], core: impl Read) -> io::Result<()> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidInput, what.to_string());
+    let (dir, max_size, max_count, cgroup, pid, signal, time) = match args {
+        [dir, max_size, max_count, cgroup, pid, signal, time] => (
+            Path::new(dir),
+            max_size.parse::<u64>().map_err(|_| invalid("max size"))?,
+            max_count
+                .parse::<usize>()
+                .map_err(|_| invalid("max count"))?,
+            cgroup,
+            pid.parse::<u32>().map_err(|_| invalid("pid"))?,
+            signal.parse::<u32>().map_err(|_| invalid("signal"))?,
+            time.parse::<u64>().map_err(|_| invalid("time"))?,
+        ),
+        _ => return Err(invalid("arguments")),
+    };
+
+    // Discard dumps of processes that are not started by the runtime
+    let cgroups = stdfs::read_to_string(format!("/proc/{}/cgroup", pid))?;
+    let name = container_name(&cgroups, cgroup).ok_or_else(|| invalid("cgroup"))?;
+
+    let dir = dir.join(name);
+    let name = file_name(CoreDumpKind::Core, time, pid, signal);
+    store(&dir, &name, max_size, max_count, core)
+}
+
+/// Write at most `max_size` bytes of `core` to `dir/name` and keep the newest
+/// `max_count` dumps of the same kind in `dir`
+fn store(
+    dir: &Path,
+    name: &str,
+    max_size: u64,
+    max_count: usize,
+    core: impl Read,
+) -> io::Result<()> {
+    stdfs::create_dir_all(dir)?;
+    let mut file = stdfs::File::create(dir.join(name))?;
+    io::copy(&mut core.take(max_size), &mut file)?;
+    file.sync_all()?;
+
+    let kind = parse(name).map(|(kind, ..)| kind);
+    let dumps = list_dir(dir)?
+        .into_iter()
+        .filter(|dump| Some(dump.kind) == kind)
+        .collect::<Vec<_>>();
+    for dump in dumps.iter().take(dumps.len().saturating_sub(max_count)) {
+        stdfs::remove_file(dir.join(&dump.name))?;
+    }
+    Ok(())
+}
+
+async fn read(path: &str) -> Result<String, Error> {
+    fs::read_to_string(path)
+        .await
+        .map(|value| value.trim_end().to_string())
+        .context(format!("failed to read {}", path))
+}
+
+async fn write(path: &str, value: &str) -> Result<(), Error> {
+    fs::write(path, value)
+        .await
+        .context(format!("failed to write {}", path))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        let name = file_name(CoreDumpKind::Core, 1660000000, 1234, 11);
+        assert_eq!(name, "core.1660000000.1234.11");
+        assert_eq!(
+            parse(&name),
+            Some((CoreDumpKind::Core, 1660000000, 1234, 11))
+        );
+        let name = file_name(CoreDumpKind::Minidump, 1660000000, 1234, 11);
+        assert_eq!(name, "minidump.1660000000.1234.11");
+        assert_eq!(
+            parse(&name),
+            Some((CoreDumpKind::Minidump, 1660000000, 1234, 11))
+        );
+        assert_eq!(parse("core.1660000000.1234"), None);
+        assert_eq!(parse("core.1660000000.1234.11.1"), None);
+        assert_eq!(parse("dump.1660000000.1234.11"), None);
+        assert_eq!(parse("../core.1660000000.1234.11"), None);
+    }
+
+    #[test]
+    fn cgroup_container_name() {
+        let cgroups = "0::/northstar/hello-world\n";
+        assert_eq!(
+            container_name(cgroups, "northstar"),
+            Some("hello-world".to_string())
+        );
+        let cgroups = "4:memory:/northstar/hello-world\n1:name=systemd:/user.slice\n";
+        assert_eq!(
+            container_name(cgroups, "northstar"),
+            Some("hello-world".to_string())
+        );
+        assert_eq!(container_name("0::/user.slice/foo\n", "northstar"), None);
+        assert_eq!(container_name("0::/northstar\n", "northstar"), None);
+    }
+
+    #[test]
+    fn store_truncates_and_rotates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let minidump = file_name(CoreDumpKind::Minidump, 0, 1, 6);
+        store(dir.path(), &minidump, 4, 2, &b"minidump"[..]).unwrap();
+        for time in 1..=3 {
+            let name = file_name(CoreDumpKind::Core, time, 1234, 11);
+            store(dir.path(), &name, 4, 2, &b"core dump"[..]).unwrap();
+        }
+        let dumps = list_dir(dir.path()).unwrap();
+        assert_eq!(dumps.len(), 3);
+        assert_eq!(dumps[0].name, minidump);
+        assert_eq!(dumps[1].time, 2);
+        assert_eq!(dumps[2].time, 3);
+        assert!(dumps.iter().all(|dump| dump.size == 4));
+    }
+
+    #[test]
+    fn receive_invalid_arguments() {
+        assert!(receive(&["/tmp".to_string()], &b"core dump"[..]).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use super::error::{Context, Error};
+use bytes::Bytes;
+use futures::StreamExt;
+use nix::unistd;
+use std::{
+    fs, io,
+    io::{Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+use tokio::{io::AsyncWriteExt, sync::mpsc, task};
+use tokio_util::io::ReaderStream;
+use tracing::debug;
+
+/// Create a tar archive of `dir` in a temporary file in `run_dir`. The
+/// archive is streamed to `tx`. Returns the size of the archive.
+pub(super) async fn export(
+    run_dir: &Path,
+    dir: &Path,
+    tx: mpsc::Sender<Bytes>,
+) -> Result<u64, Error> {
+    let run_dir = run_dir.to_owned();
+    let dir = dir.to_owned();
+    let (file, size) = task::spawn_blocking(move || archive(&run_dir, &dir))
+        .await
+        .context("failed to join export task")??;
+
+    // Stream the archive. The temporary file is unnamed and vanishes once the stream is dropped.
+    let mut stream = ReaderStream::new(tokio::fs::File::from_std(file));
+    task::spawn(async move {
+        while let Some(Ok(buf)) = stream.next().await {
+            if tx.send(buf).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(size)
+}
+
+/// Replace the content of `dir` with the tar archive received from `rx`. All
+/// entries are owned by `uid` and `gid`.
+pub(super) async fn import(
+    run_dir: &Path,
+    dir: &Path,
+    uid: u16,
+    gid: u16,
+    rx: &mut mpsc::Receiver<Bytes>,
+) -> Result<(), Error> {
+    let file = tempfile::tempfile_in(run_dir).context("failed to create temporary file")?;
+    let mut file = tokio::fs::File::from_std(file);
+    while let Some(buf) = rx.recv().await {
+        file.write_all(&buf)
+            .await
+            .context("failed to write archive")?;
+    }
+    let file = file.into_std().await;
+
+    let dir = dir.to_owned();
+    task::spawn_blocking(move || unpack(file, &dir, uid, gid))
+        .await
+        .context("failed to join import task")?
+}
+
+fn archive(run_dir: &Path, dir: &Path) -> Result<(fs::File, u64), Error> {
+    debug!("Creating archive of {}", dir.display());
+    let file = tempfile::tempfile_in(run_dir).context("failed to create temporary file")?;
+    let mut builder = tar::Builder::new(file);
+    builder.follow_symlinks(false);
+    if dir.exists() {
+        builder
+            .append_dir_all(".", dir)
+            .context(format!("failed to archive {}", dir.display()))?;
+    }
+    let mut file = builder
+        .into_inner()
+        .context(format!("failed to archive {}", dir.display()))?;
+    let size = file
+        .seek(SeekFrom::End(0))
+        .context("failed to seek archive")?;
+    file.seek(SeekFrom::Start(0))
+        .context("failed to seek archive")?;
+    Ok((file, size))
+}
+
+fn unpack(mut file: fs::File, dir: &Path, uid: u16, gid: u16) -> Result<(), Error> {
+    file.seek(SeekFrom::Start(0))
+        .context("failed to seek archive")?;
+
+    // Remove the current content. The dir itself might be a mount point.
+    if dir.exists() {
+        for entry in fs::read_dir(dir).context(format!("failed to read {}", dir.display()))? {
+            let path = entry
+                .context(format!("failed to read {}", dir.display()))?
+                .path();
+            remove(&path).context(format!("failed to remove {}", path.display()))?;
+        }
+    } else {
+        fs::create_dir_all(dir).context(format!("failed to create {}", dir.display()))?;
+    }
+
+    debug!("Unpacking archive to {}", dir.display());
+    let mut archive = tar::Archive::new(file);
+    archive.set_preserve_permissions(true);
+    archive.set_overwrite(true);
+    archive
+        .unpack(dir)
+        .context(format!("failed to unpack archive to {}", dir.display()))?;
+
+    chown(dir, uid, gid)
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Recursively chown `dir` without following symlinks
+fn chown(dir: &Path, uid: u16, gid: u16) -> Result<(), Error> {
+    let uid = unistd::Uid::from_raw(uid.into());
+    let gid = unistd::Gid::from_raw(gid.into());
+    let mut dirs: Vec<PathBuf> = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        unistd::fchownat(
+            None,
+            &dir,
+            Some(uid),
+            Some(gid),
+            unistd::FchownatFlags::NoFollowSymlink,
+        )
+        .context(format!("failed to chown {}", dir.display()))?;
+        for entry in fs::read_dir(&dir).context(format!("failed to read {}", dir.display()))? {
+            let path = entry
+                .context(format!("failed to read {}", dir.display()))?
+                .path();
+            if fs::symlink_metadata(&path)
+                .context(format!("failed to stat {}", path.display()))?
+                .is_dir()
+            {
+                dirs.push(path);
+            } else {
+                unistd::fchownat(
+                    None,
+                    &path,
+                    Some(uid),
+                    Some(gid),
+                    unistd::FchownatFlags::NoFollowSymlink,
+                )
+                .context(format!("failed to chown {}", path.display()))?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use super::{
+    config::Config,
+    error::{Context, Error},
+};
+use crate::{
+    common::container::Container,
+    npk::manifest::{
+        ipc::{BusPolicy, DBus},
+        Manifest,
+    },
+};
+use nix::sys::stat;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use tokio::{
+    fs,
+    process::{Child, Command},
+    time,
+};
+use tracing::debug;
+
+/// Directory within the run dir that holds the bus sockets of each container
+const DBUS_DIR: &str = "dbus";
+
+/// Socket name of the proxied system bus
+const SYSTEM_BUS_SOCKET: &str = "system_bus_socket";
+
+/// Socket name of the proxied session bus
+const SESSION_BUS_SOCKET: &str = "session_bus_socket";
+
+/// Maximum duration until the proxy created its sockets
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Directory on the host that is mounted to `/run/dbus` in the container
+pub(crate) fn dir(run_dir: &Path, container: &Container) -> PathBuf {
+    run_dir.join(DBUS_DIR).join(container.to_string())
+}
+
+/// Bus address variables passed to the application
+pub(super) fn env(manifest: &Manifest) -> Vec<String> {
+    let dbus = match manifest.ipc.as_ref().and_then(|ipc| ipc.dbus.as_ref()) {
+        Some(dbus) => dbus,
+        None => return Vec::new(),
+    };
+    let dir = Path::new(DBus::DIR);
+    let mut env = Vec::with_capacity(2);
+    if dbus.system.is_some() {
+        env.push(format!(
+            "DBUS_SYSTEM_BUS_ADDRESS=unix:path={}",
+            dir.join(SYSTEM_BUS_SOCKET).display()
+        ));
+    }
+    if dbus.session.is_some() {
+        env.push(format!(
+            "DBUS_SESSION_BUS_ADDRESS=unix:path={}",
+            dir.join(SESSION_BUS_SOCKET).display()
+        ));
+    }
+    env
+}
+
+/// Filtering D-Bus proxy of a container
+#[derive(Debug)]
+pub(crate) struct Proxy {
+    child: Child,
+    dir: PathBuf,
+}
+
+impl Proxy {
+    /// Start an `xdg-dbus-proxy` for `container` if the manifest has a `dbus` section
+    /// and wait until the bus sockets are created
+    pub(super) async fn new(
+        config: &Config,
+        container: &Container,
+        manifest: &Manifest,
+    ) -> Result<Option<Proxy>, Error> {
+        let dbus = match manifest.ipc.as_ref().and_then(|ipc| ipc.dbus.as_ref()) {
+            Some(dbus) => dbus,
+            None => return Ok(None),
+        };
+        let failed = |reason: &str| Error::StartContainerFailed(container.clone(), reason.into());
+        let proxy = config
+            .dbus
+            .as_ref()
+            .ok_or_else(|| failed("dbus is not configured"))?;
+
+        let dir = dir(&config.run_dir, container);
+        // Remove left overs of a previous instance
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .await
+                .context(format!("failed to remove {}", dir.display()))?;
+        }
+        fs::create_dir_all(&dir)
+            .await
+            .context(format!("failed to create {}", dir.display()))?;
+
+        let mut cmd = Command::new(&proxy.proxy);
+        let mut sockets = Vec::with_capacity(2);
+        if let Some(policy) = &dbus.system {
+            let socket = dir.join(SYSTEM_BUS_SOCKET);
+            args(&mut cmd, &proxy.system, &socket, policy);
+            sockets.push(socket);
+        }
+        if let Some(policy) = &dbus.session {
+            let address = proxy
+                .session
+                .as_ref()
+                .ok_or_else(|| failed("dbus session bus is not configured"))?;
+            let socket = dir.join(SESSION_BUS_SOCKET);
+            args(&mut cmd, address, &socket, policy);
+            sockets.push(socket);
+        }
+
+        debug!("Starting dbus proxy of {}: {:?}", container, cmd);
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context(format!("failed to spawn {}", proxy.proxy.display()))?;
+
+        // The proxy creates the sockets once it is connected to the buses
+        let ready = async {
+            while !sockets.iter().all(|socket| socket.exists()) {
+                if let Ok(Some(status)) = child.try_wait() {
+                    return Err(failed(&format!("dbus proxy exited with {}", status)));
+                }
+                time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(())
+        };
+        time::timeout(STARTUP_TIMEOUT, ready)
+            .await
+            .map_err(|_| failed("timeout waiting for the dbus proxy"))??;
+
+        // The application does not run as root
+        for socket in &sockets {
+            stat::fchmodat(
+                None,
+                socket,
+                stat::Mode::from_bits_truncate(0o666),
+                stat::FchmodatFlags::FollowSymlink,
+            )
+            .context(format!("failed to chmod {}", socket.display()))?;
+        }
+
+        Ok(Some(Proxy { child, dir }))
+    }
+
+    /// Stop the proxy and remove its sockets
+    pub(super) async fn destroy(mut self) -> Result<(), Error> {
+        self.child.kill().await.ok();
+        self.child
+            .wait()
+            .await
+            .context("failed to join dbus proxy")?;
+        fs::remove_dir_all(&self.dir)
+            .await
+            .context(format!("failed to remove {}", self.dir.display()))
+    }
+}
+
+/// Append the proxy arguments for the bus at `address` with `policy`
+fn args(cmd: &mut Command, address: &str, socket: &Path, policy: &BusPolicy) {
+    cmd.arg(address).arg(socket).arg("--filter");
+    for (option, values) in [
+        ("own", &policy.own),
+        ("talk", &policy.talk),
+        ("see", &policy.see),
+        ("call", &policy.call),
+        ("broadcast", &policy.broadcast),
+    ] {
+        for value in values {
+            cmd.arg(format!("--{}={}", option, value));
+        }
+    }
+}
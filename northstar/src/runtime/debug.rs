@@ -5,7 +5,6 @@ use super::{
 };
 use crate::{npk::manifest::Manifest, runtime::error::Context};
 use futures::future::OptionFuture;
-use log::{debug, error, info};
 use std::{path::Path, process::Stdio};
 use tokio::{
     fs,
@@ -15,6 +14,7 @@ use tokio::{
     task::{self, JoinHandle},
 };
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
 
 #[derive(Debug)]
 pub struct Strace {
@@ -24,13 +24,18 @@ pub struct Strace {
 }
 
 /// Debugging facilities attached to a started container
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub(crate) struct Debug {
     strace: Option<Strace>,
     perf: Option<Perf>,
 }
 
 impl Debug {
+    /// True if a debug facility is attached
+    pub(crate) fn is_attached(&self) -> bool {
+        self.strace.is_some() || self.perf.is_some()
+    }
+
     /// Start configured debug facilities and attach to `pid`
     pub(crate) async fn new(
         config: &Config,
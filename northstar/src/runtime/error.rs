@@ -1,3 +1,4 @@
+use itertools::Itertools;
 use thiserror::Error;
 
 use crate::{
@@ -7,12 +8,16 @@ use crate::{
     runtime::{Container, ExitStatus, RepositoryId},
 };
 
+/// Runtime error
+#[allow(missing_docs)]
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("invalid configuration: {0}")]
     Configuration(String),
     #[error("invalid container {0}")]
     InvalidContainer(Container),
+    #[error("container {0} is unknown while repositories are loading")]
+    ContainerLoading(Container),
     #[error("invalid arguments {0}")]
     InvalidArguments(String),
     #[error("container {0} cannot be mounted: busy")]
@@ -29,16 +34,58 @@ pub enum Error {
     StartContainerMissingResource(Container, Name, String),
     #[error("container {0} failed to start: {1}")]
     StartContainerFailed(Container, String),
+    #[error("container {0} start is deferred for {1:?}")]
+    StartDeferred(Container, std::time::Duration),
+    #[error("container {0} start waits for the mount of {1:?}")]
+    StartMount(Container, Vec<Container>),
     #[error("container {0} failed to stop")]
     StopContainerNotStarted(Container),
     #[error("container {0} failed is stop: not started")]
     ContainerNotStarted(Container),
+    #[error("container {0} failed to pause: already paused")]
+    PauseContainerPaused(Container),
+    #[error("container {0} failed to resume: not paused")]
+    ResumeContainerNotPaused(Container),
+    #[error("system is suspended")]
+    SystemSuspended,
+    #[error("system is not suspended")]
+    SystemNotSuspended,
+    #[error("container {0} filesystem access failed: {1}")]
+    Fs(Container, String),
+    #[error("container {0} data access failed: {1}")]
+    Data(Container, String),
+    #[error("container {0} core dump access failed: {1}")]
+    CoreDump(Container, String),
+    #[error("container {0} attach failed: {1}")]
+    Attach(Container, String),
+    #[error("container {0} stdin failed: {1}")]
+    Stdin(Container, String),
     #[error("invalid repository {0}")]
     InvalidRepository(RepositoryId),
+    #[error("repository {0} is read only")]
+    RepositoryReadOnly(RepositoryId),
     #[error("failed to install {0}: already installed")]
     InstallDuplicate(Container),
+    #[error("failed to uninstall {0}: in use (mounted: {1}, started: {2:?}, dependents: {3:?})")]
+    UninstallInUse(Container, bool, Vec<Container>, Vec<Container>),
+    #[error("insufficient space: {0} bytes needed, {1} bytes available")]
+    InsufficientSpace(u64, u64),
+    #[error("container {0} has no {1} label")]
+    MissingSecurityLabel(Container, String),
+    #[error("container {0} exceeds the maximum {2} of rlimit {1}")]
+    RLimitExceeded(Container, String, u64),
+    #[error("container {0} requires {1} {2} but the system has {3}")]
+    Incompatible(Container, String, String, String),
+    #[error("not applied: request {0} of the batch failed")]
+    BatchAborted(usize),
     #[error("critical container failure")]
     CriticalContainer(Container, ExitStatus),
+    #[error("{1:?} of container {0} timed out after {2:?}")]
+    Timeout(Container, api::model::Operation, std::time::Duration),
+    #[error("runtime is not running")]
+    NotRunning,
+    #[error("handoff refused: {0}")]
+    Handoff(String),
 
     #[error("npk {0:?}: {1:?}")]
     Npk(String, npk::npk::Error),
@@ -77,6 +124,7 @@ impl From<Error> for api::model::Error {
                 api::model::Error::DuplicateContainer { container }
             }
             Error::InvalidContainer(container) => api::model::Error::InvalidContainer { container },
+            Error::ContainerLoading(container) => api::model::Error::ContainerLoading { container },
             Error::InvalidArguments(cause) => api::model::Error::InvalidArguments { cause },
             Error::MountBusy(container) => api::model::Error::MountBusy { container },
             Error::UmountBusy(container) => api::model::Error::UmountBusy { container },
@@ -96,20 +144,80 @@ impl From<Error> for api::model::Error {
             Error::StartContainerFailed(container, error) => {
                 api::model::Error::StartContainerFailed { container, error }
             }
+            Error::StartDeferred(container, delay) => api::model::Error::StartContainerFailed {
+                container,
+                error: format!("deferred for {:?}", delay),
+            },
+            Error::StartMount(container, mounts) => api::model::Error::StartContainerFailed {
+                container,
+                error: format!("waiting for the mount of {}", mounts.iter().join(", ")),
+            },
             Error::StopContainerNotStarted(container) => {
                 api::model::Error::StopContainerNotStarted { container }
             }
             Error::ContainerNotStarted(container) => {
                 api::model::Error::StopContainerNotStarted { container }
             }
+            Error::PauseContainerPaused(container) => {
+                api::model::Error::PauseContainerPaused { container }
+            }
+            Error::ResumeContainerNotPaused(container) => {
+                api::model::Error::ResumeContainerNotPaused { container }
+            }
+            Error::SystemSuspended => api::model::Error::SystemSuspended,
+            Error::SystemNotSuspended => api::model::Error::SystemNotSuspended,
+            Error::Fs(container, error) => api::model::Error::Fs { container, error },
+            Error::Data(container, error) => api::model::Error::Data { container, error },
+            Error::CoreDump(container, error) => api::model::Error::CoreDump { container, error },
+            Error::Attach(container, error) => api::model::Error::Attach { container, error },
+            Error::Stdin(container, error) => api::model::Error::Stdin { container, error },
             Error::InvalidRepository(repository) => {
                 api::model::Error::InvalidRepository { repository }
             }
+            Error::RepositoryReadOnly(repository) => {
+                api::model::Error::RepositoryReadOnly { repository }
+            }
             Error::InstallDuplicate(container) => api::model::Error::InstallDuplicate { container },
+            Error::UninstallInUse(container, mounted, started, dependents) => {
+                api::model::Error::UninstallInUse {
+                    container,
+                    mounted,
+                    started,
+                    dependents,
+                }
+            }
+            Error::InsufficientSpace(needed, available) => {
+                api::model::Error::InsufficientSpace { needed, available }
+            }
+            Error::MissingSecurityLabel(container, module) => {
+                api::model::Error::MissingSecurityLabel { container, module }
+            }
+            Error::RLimitExceeded(container, resource, max) => api::model::Error::RLimitExceeded {
+                container,
+                resource,
+                max,
+            },
+            Error::Incompatible(container, requirement, required, found) => {
+                api::model::Error::Incompatible {
+                    container,
+                    requirement,
+                    required,
+                    found,
+                }
+            }
+            Error::BatchAborted(failed) => api::model::Error::BatchAborted { failed },
             Error::CriticalContainer(container, status) => api::model::Error::CriticalContainer {
                 container,
                 status: status.into(),
             },
+            Error::NotRunning => api::model::Error::Unexpected {
+                module: "Runtime".into(),
+                error: "not running".into(),
+            },
+            Error::Handoff(error) => api::model::Error::Unexpected {
+                module: "Handoff".into(),
+                error,
+            },
             Error::Npk(cause, error) => api::model::Error::Unexpected {
                 module: "Npk".into(),
                 error: format!("{}: {}", cause, error),
@@ -122,6 +230,11 @@ impl From<Error> for api::model::Error {
                 module: "CGroups".into(),
                 error: error.to_string(),
             },
+            Error::Timeout(container, operation, timeout) => api::model::Error::Timeout {
+                container,
+                operation,
+                timeout: timeout.as_millis() as u64,
+            },
             Error::Mount(error) => api::model::Error::Unexpected {
                 module: "Mount".into(),
                 error: error.to_string(),
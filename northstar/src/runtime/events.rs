@@ -0,0 +1,109 @@
+use super::{ContainerEvent, RepositoryId};
+use crate::api::model::Container;
+use futures::Stream;
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// Event of the runtime delivered to in-process subscribers
+#[derive(Clone, Debug)]
+pub enum RuntimeEvent {
+    /// Event of a container. Output lines of containers are forwarded to console
+    /// notifications only.
+    Container(Container, ContainerEvent),
+    /// Repository finished loading
+    RepositoryLoaded(RepositoryId),
+    /// The runtime shuts down
+    Shutdown,
+}
+
+/// Handling of events for a subscriber with a full buffer. The runtime never waits
+/// for a subscriber.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop the events until the subscriber catches up
+    Drop,
+    /// End the subscription. A subscriber that relies on seeing every event can
+    /// resubscribe and resynchronize with a `containers` request.
+    Disconnect,
+}
+
+/// Stream of the events of the runtime. The stream ends when the runtime stops or
+/// the subscription is disconnected due to an overflow.
+#[derive(Debug)]
+pub struct Subscription {
+    rx: mpsc::Receiver<RuntimeEvent>,
+}
+
+impl Stream for Subscription {
+    type Item = RuntimeEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Channel of a subscriber and its overflow behaviour
+type Subscriber = (mpsc::Sender<RuntimeEvent>, Overflow);
+
+/// In-process subscribers
+#[derive(Clone, Debug, Default)]
+pub(super) struct Subscribers {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl Subscribers {
+    /// Add a subscriber that buffers up to `capacity` events
+    pub(super) fn subscribe(&self, capacity: usize, overflow: Overflow) -> Subscription {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let mut subscribers = self.subscribers.lock().expect("poisoned lock");
+        subscribers.push((tx, overflow));
+        Subscription { rx }
+    }
+
+    /// Send `event` to all subscribers
+    pub(super) fn publish(&self, event: RuntimeEvent) {
+        let mut subscribers = self.subscribers.lock().expect("poisoned lock");
+        subscribers.retain(|(tx, overflow)| match tx.try_send(event.clone()) {
+            Ok(_) => true,
+            Err(TrySendError::Full(_)) => *overflow == Overflow::Drop,
+            Err(TrySendError::Closed(_)) => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn overflow() {
+        let subscribers = Subscribers::default();
+        let mut dropping = subscribers.subscribe(1, Overflow::Drop);
+        let mut disconnect = subscribers.subscribe(1, Overflow::Disconnect);
+
+        subscribers.publish(RuntimeEvent::RepositoryLoaded("default".into()));
+        subscribers.publish(RuntimeEvent::Shutdown);
+
+        assert!(matches!(
+            dropping.next().await,
+            Some(RuntimeEvent::RepositoryLoaded(_))
+        ));
+        assert!(matches!(
+            disconnect.next().await,
+            Some(RuntimeEvent::RepositoryLoaded(_))
+        ));
+        assert!(disconnect.next().await.is_none());
+
+        // The dropping subscriber receives the events after catching up
+        subscribers.publish(RuntimeEvent::Shutdown);
+        assert!(matches!(
+            dropping.next().await,
+            Some(RuntimeEvent::Shutdown)
+        ));
+    }
+}
@@ -20,6 +20,7 @@ use futures::{
 use itertools::Itertools;
 use nix::{
     errno::Errno,
+    libc,
     sys::{signal::Signal, wait::waitpid},
     unistd,
 };
@@ -35,6 +36,9 @@ use tokio::{net::UnixStream, select};
 
 type Inits = HashMap<Container, InitProcess>;
 
+/// Flag of `unshare` that creates a time namespace for the children of the caller
+const CLONE_NEWTIME: libc::c_int = 0x80;
+
 /// Handle the communication between the forker and the init process.
 struct InitProcess {
     pid: Pid,
@@ -58,14 +62,19 @@ pub async fn run(stream: StdUnixStream, notifications: StdUnixStream) -> ! {
         select! {
             request = recv(&mut stream) => {
                 match request {
-                    Some(Message::CreateRequest { init, console }) => {
+                    Some(Message::CreateRequest { init, console, minidump }) => {
                         debug!("Creating init process for {}", init.container);
                         let container = init.container.clone();
-                        let (pid, init) = create(init, console).await;
-                        if inits.insert(container.clone(), init).is_some() {
-                            panic!("duplicate init request for {}", container);
-                        }
-                        stream.send(Message::CreateResult { init: pid }).await.expect("failed to send response");
+                        let response = match create(init, console, minidump).await {
+                            Ok((pid, init)) => {
+                                if inits.insert(container.clone(), init).is_some() {
+                                    panic!("duplicate init request for {}", container);
+                                }
+                                Message::CreateResult { init: pid }
+                            }
+                            Err(error) => Message::Failure(error),
+                        };
+                        stream.send(response).await.expect("failed to send response");
                     }
                     Some(Message::ExecRequest { container, path, args, env, io }) => {
                         let io = io.expect("exec request without io");
@@ -95,8 +104,13 @@ pub async fn run(stream: StdUnixStream, notifications: StdUnixStream) -> ! {
     }
 }
 
-/// Create a new init process ("container")
-async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
+/// Create a new init process ("container") and wait until it is set up. Returns
+/// the reason if the init process failed to set up the container.
+async fn create(
+    init: Init,
+    console: Option<OwnedFd>,
+    minidump: Option<OwnedFd>,
+) -> Result<(Pid, InitProcess), String> {
     let container = init.container.clone();
     debug!("Creating container {}", container);
     let mut stream = socket_pair().expect("failed to create socket pair");
@@ -106,9 +120,30 @@ async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
         util::set_parent_death_signal(Signal::SIGKILL);
 
         // Create pid namespace
-        debug!("Creating pid namespace");
-        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWPID)
-            .expect("failed to create pid namespace");
+        if init.pid_namespace {
+            debug!("Creating pid namespace");
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWPID)
+                .expect("failed to create pid namespace");
+        }
+
+        // Create network namespace. The init process is created in the namespace
+        // before its pid is reported to the runtime that moves the interfaces.
+        if init.net_namespace {
+            debug!("Creating network namespace");
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET)
+                .expect("failed to create network namespace");
+        }
+
+        // Create time namespace. The offsets apply to the children of the trampoline
+        // and cannot be changed once init is forked into the namespace.
+        if let Some(offset) = init.time_offset {
+            debug!("Creating time namespace with offset {}s", offset);
+            Errno::result(unsafe { libc::unshare(CLONE_NEWTIME) })
+                .expect("failed to create time namespace");
+            let offsets = format!("monotonic {} 0\nboottime {} 0\n", offset, offset);
+            std::fs::write("/proc/self/timens_offsets", offsets)
+                .expect("failed to set time namespace offsets");
+        }
 
         // Work around the borrow checker and fork
         let stream = stream.second().into_raw_fd();
@@ -119,7 +154,7 @@ async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
             let stream = unsafe { StdUnixStream::from_raw_fd(stream) };
             // Dive into init and never return
             let stream = IpcMessage::from(stream);
-            init.run(stream, console);
+            init.run(stream, console, minidump);
         })
         .expect("failed to fork init");
 
@@ -154,9 +189,25 @@ async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
         Err(e) => panic!("failed to wait for the trampoline process: {}", e),
     }
 
+    // Wait for init to set up the container
+    let failure = match stream.recv().await {
+        Ok(Some(init::Message::Ready)) => None,
+        Ok(Some(init::Message::MountFailed(error))) => Some(error.to_string()),
+        Ok(Some(_)) => panic!("Unexpected init message"),
+        Ok(None) | Err(_) => Some("init exited during the container setup".to_string()),
+    };
+    if let Some(failure) = failure {
+        debug!("Reaping init process of {} ({})", container, pid);
+        match waitpid(unistd::Pid::from_raw(pid as i32), None) {
+            Ok(_) | Err(Errno::ECHILD) => (),
+            Err(e) => panic!("failed to reap init process: {}", e),
+        }
+        return Err(failure);
+    }
+
     debug!("Created container {} with pid {}", container, pid);
 
-    (pid, InitProcess { pid, stream })
+    Ok((pid, InitProcess { pid, stream }))
 }
 
 /// Send a exec request to a container
@@ -229,7 +280,7 @@ async fn recv(stream: &mut AsyncMessage<UnixStream>) -> Option<Message> {
         }
     };
     match request {
-        Some(Message::CreateRequest { init, console: _ }) => {
+        Some(Message::CreateRequest { init, .. }) => {
             let console = if init.console {
                 debug!("Console is enabled. Waiting for console stream");
                 let console = stream
@@ -241,7 +292,22 @@ async fn recv(stream: &mut AsyncMessage<UnixStream>) -> Option<Message> {
             } else {
                 None
             };
-            Some(Message::CreateRequest { init, console })
+            let minidump = if init.minidump {
+                debug!("Minidumps are enabled. Waiting for minidump file");
+                let minidump = stream
+                    .recv_fds::<RawFd, 1>()
+                    .await
+                    .expect("failed to receive minidump fd");
+                let minidump = unsafe { OwnedFd::from_raw_fd(minidump[0]) };
+                Some(minidump)
+            } else {
+                None
+            };
+            Some(Message::CreateRequest {
+                init,
+                console,
+                minidump,
+            })
         }
         Some(Message::ExecRequest {
             container,
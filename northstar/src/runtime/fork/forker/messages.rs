@@ -7,12 +7,15 @@ use serde::{Deserialize, Serialize};
 
 /// Request from the runtime to the forker
 #[non_exhaustive]
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
     CreateRequest {
         init: Init,
         #[serde(skip)]
         console: Option<OwnedFd>,
+        #[serde(skip)]
+        minidump: Option<OwnedFd>,
     },
     CreateResult {
         init: Pid,
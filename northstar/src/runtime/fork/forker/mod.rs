@@ -4,6 +4,7 @@ use super::{
     util::{self},
 };
 use crate::{
+    api::model::Operation,
     common::{container::Container, non_nul_string::NonNulString},
     debug,
     npk::manifest::Manifest,
@@ -14,11 +15,19 @@ use crate::{
         ipc::{owned_fd::OwnedFd, socket_pair, AsyncMessage},
     },
 };
-use futures::FutureExt;
+use bytes::BytesMut;
+use futures::{future::select_all, FutureExt};
 pub use messages::{Message, Notification};
-use nix::sys::signal::{signal, SigHandler, Signal};
-use std::os::unix::net::UnixStream as StdUnixStream;
-use tokio::{net::UnixStream, runtime};
+use nix::{
+    sys::{
+        signal::{self, signal, SigHandler, Signal},
+        wait::waitpid,
+    },
+    unistd,
+};
+use std::{collections::VecDeque, os::unix::net::UnixStream as StdUnixStream, time::Duration};
+use tokio::{net::UnixStream, runtime, task, time};
+use tracing::{instrument, warn};
 
 mod r#impl;
 mod messages;
@@ -70,48 +79,91 @@ pub fn start() -> Result<(Pid, ForkerChannels), Error> {
     Ok((pid, forker))
 }
 
+/// Kill the forker process `pid` and wait for its termination. Used for the forkers
+/// of a previous runtime instance that are not needed anymore.
+pub async fn reap(pid: Pid) {
+    debug!("Killing forker {}", pid);
+    let pid = unistd::Pid::from_raw(pid as i32);
+    signal::kill(pid, Signal::SIGKILL).ok();
+    task::spawn_blocking(move || loop {
+        if waitpid(Some(pid), None) != Err(nix::Error::EINTR) {
+            break;
+        }
+    })
+    .await
+    .expect("failed to join reap task");
+}
+
 /// Handle to the forker process
 #[derive(Debug)]
 pub struct Forker {
+    /// Pid of the forker process
+    pid: Pid,
     /// Framed stream/sink for sending messages to the forker process
     stream: AsyncMessage<UnixStream>,
+    /// Timeout of a request
+    timeout: Duration,
+    /// Requests that timed out and whose replies are not received yet. The container
+    /// is set for create requests.
+    stale: VecDeque<Option<Container>>,
+    /// Containers whose init is killed after a timeout. The exit of init is not an
+    /// exit of a started container.
+    killed: Vec<Container>,
 }
 
 impl Forker {
-    /// Create a new forker handle
-    pub fn new(stream: StdUnixStream) -> Self {
+    /// Create a new forker handle. Requests that are not answered within `timeout`
+    /// fail with a timeout error.
+    pub fn new(pid: Pid, stream: StdUnixStream, timeout: Duration) -> Self {
         let stream = stream.try_into().expect("failed to create AsyncMessage");
-        Self { stream }
+        Self {
+            pid,
+            stream,
+            timeout,
+            stale: VecDeque::new(),
+            killed: Vec::new(),
+        }
     }
 
-    /// Send a request to the forker process to create a new container
+    /// Send a request to the forker process to create a new container. Returns the pid
+    /// of init and the duration of the seccomp filter compilation.
+    #[instrument(name = "fork", skip_all, fields(container = %container))]
     pub async fn create<'a, I: Iterator<Item = &'a Container> + Clone>(
         &mut self,
         config: &Config,
+        container: &Container,
         manifest: &Manifest,
         console: Option<OwnedFd>,
+        minidump: Option<OwnedFd>,
         containers: I,
-    ) -> Result<Pid, Error> {
+    ) -> Result<(Pid, Duration), Error> {
         debug_assert_eq!(manifest.console.is_some(), console.is_some());
+        debug_assert_eq!(config.minidumps(), minidump.is_some());
 
-        let init = init::build(config, manifest, containers).await?;
+        let (init, seccomp) = init::build(config, container, manifest, containers).await?;
         let console = console.map(Into::into);
-        let message = Message::CreateRequest { init, console };
+        let message = Message::CreateRequest {
+            init,
+            console,
+            minidump,
+        };
 
         match self
-            .request_response(message)
+            .request_response(message, Some(container))
             .await
             .expect("failed to send request")
         {
-            Message::CreateResult { init } => Ok(init),
-            Message::Failure(error) => {
-                Err(Error::StartContainerFailed(manifest.container(), error))
+            Some(Message::CreateResult { init }) => Ok((init, seccomp)),
+            Some(Message::Failure(error)) => {
+                Err(Error::StartContainerFailed(container.clone(), error))
             }
-            _ => panic!("Unexpected forker response"),
+            Some(_) => panic!("Unexpected forker response"),
+            None => Err(self.timeout_error(container)),
         }
     }
 
     /// Start container process in a previously created container
+    #[instrument(skip_all, fields(container = %container))]
     pub async fn exec(
         &mut self,
         container: Container,
@@ -121,26 +173,104 @@ impl Forker {
         io: [OwnedFd; 3],
     ) -> Result<(), Error> {
         let message = Message::ExecRequest {
-            container,
+            container: container.clone(),
             path,
             args,
             env,
             io: Some(io),
         };
-        self.request_response(message).await.map(drop)
+        match self.request_response(message, None).await? {
+            Some(_) => Ok(()),
+            None => Err(self.timeout_error(&container)),
+        }
     }
 
-    /// Send a request to the forker process
-    async fn request_response(&mut self, request: Message) -> Result<Message, Error> {
-        let mut request = request;
+    /// Kill the init process `pid` of `container` after a timeout. The exit of init is
+    /// ignored.
+    pub fn kill(&mut self, container: &Container, pid: Pid) {
+        warn!("Killing init {} of {} after a timeout", pid, container);
+        let pid = nix::unistd::Pid::from_raw(pid as i32);
+        if nix::sys::signal::kill(pid, Signal::SIGKILL).is_ok() {
+            self.killed.push(container.clone());
+        }
+    }
+
+    /// True once if the exit of `container` is the exit of an init that is killed
+    /// after a timeout
+    pub fn is_killed(&mut self, container: &Container) -> bool {
+        match self.killed.iter().position(|c| c == container) {
+            Some(index) => {
+                self.killed.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pid of the forker process
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// True if replies to requests that timed out or exits of killed inits are
+    /// outstanding
+    pub fn is_busy(&self) -> bool {
+        !self.stale.is_empty() || !self.killed.is_empty()
+    }
+
+    /// Return the request stream of the forker process for the next runtime instance
+    pub fn handoff(self) -> Result<(Pid, StdUnixStream), Error> {
+        let (stream, buffer) = self
+            .stream
+            .into_parts()
+            .context("failed to convert forker stream")?;
+        if !buffer.is_empty() {
+            return Err(Error::Handoff("unexpected forker reply".into()));
+        }
+        Ok((self.pid, stream))
+    }
+
+    fn timeout_error(&self, container: &Container) -> Error {
+        Error::Timeout(container.clone(), Operation::Start, self.timeout)
+    }
+
+    /// Send a request to the forker process. Returns `None` if the reply is not
+    /// received within the timeout. Replies to requests that timed out before are
+    /// skipped and the init processes they report are killed.
+    async fn request_response(
+        &mut self,
+        request: Message,
+        container: Option<&Container>,
+    ) -> Result<Option<Message>, Error> {
+        let deadline = time::Instant::now() + self.timeout;
 
-        // Remove fds from message
-        let fds = match &mut request {
-            Message::CreateRequest { init: _, console } => {
-                console.take().map(|console| Vec::from([console]))
+        while let Some(stale) = self.stale.front().cloned() {
+            let reply = match time::timeout_at(deadline, self.recv()).await {
+                Ok(reply) => reply?,
+                Err(_) => return Ok(None),
+            };
+            self.stale.pop_front();
+            if let (Some(container), Message::CreateResult { init }) = (stale, reply) {
+                self.kill(&container, init);
             }
-            Message::ExecRequest { io, .. } => io.take().map(Vec::from),
-            _ => None,
+        }
+
+        let mut request = request;
+
+        // Remove fds from message. Each group of fds is sent separately.
+        let fds: Vec<Vec<OwnedFd>> = match &mut request {
+            Message::CreateRequest {
+                init: _,
+                console,
+                minidump,
+            } => console
+                .take()
+                .into_iter()
+                .chain(minidump.take())
+                .map(|fd| Vec::from([fd]))
+                .collect(),
+            Message::ExecRequest { io, .. } => io.take().map(Vec::from).into_iter().collect(),
+            _ => Vec::new(),
         };
 
         // Send it
@@ -150,7 +280,7 @@ impl Forker {
             .context("failed to send request")?;
 
         // Send fds if any
-        if let Some(fds) = fds {
+        for fds in fds {
             self.stream
                 .send_fds(&fds)
                 .await
@@ -159,13 +289,89 @@ impl Forker {
         }
 
         // Receive reply
-        let reply = self
-            .stream
+        match time::timeout_at(deadline, self.recv()).await {
+            Ok(reply) => reply.map(Some),
+            Err(_) => {
+                self.stale.push_back(container.cloned());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Receive a message from the forker process. Cancel safe.
+    async fn recv(&mut self) -> Result<Message, Error> {
+        self.stream
             .recv()
             .map(|s| s.map(|s| s.expect("invalid message")))
             .await
-            .context("failed to receive response from forker")?;
+            .context("failed to receive response from forker")
+    }
+}
+
+/// Exit notifications of the forker process and of the forkers of a previous runtime
+/// instance
+#[derive(Debug)]
+pub struct Notifications {
+    forkers: Vec<(Pid, AsyncMessage<UnixStream>)>,
+}
+
+impl Notifications {
+    /// Receive the notifications of the forker process `pid` from `stream`
+    pub fn new(pid: Pid, stream: StdUnixStream) -> Self {
+        let stream = stream
+            .try_into()
+            .expect("failed to convert exit notification handle");
+        Self {
+            forkers: vec![(pid, stream)],
+        }
+    }
+
+    /// Receive the notifications of the forker `pid` of a previous runtime instance.
+    /// `buffer` holds a partially received notification.
+    pub fn adopt(&mut self, pid: Pid, stream: StdUnixStream, buffer: &[u8]) -> Result<(), Error> {
+        let stream = AsyncMessage::from_parts(stream, buffer)
+            .context("failed to convert exit notification handle")?;
+        self.forkers.push((pid, stream));
+        Ok(())
+    }
+
+    /// Receive the next notification of any forker. Forkers that closed their stream
+    /// are removed. Cancel safe.
+    pub async fn recv(&mut self) -> Notification {
+        loop {
+            if self.forkers.is_empty() {
+                futures::future::pending::<()>().await;
+            }
+
+            let (result, index, _) = select_all(
+                self.forkers
+                    .iter_mut()
+                    .map(|(_, stream)| stream.recv().boxed()),
+            )
+            .await;
+            match result {
+                Ok(Some(notification)) => break notification,
+                Ok(None) => warn!("Forker {} closed its notifications", self.forkers[index].0),
+                Err(e) => warn!(
+                    "Failed to receive notification of forker {}: {}",
+                    self.forkers[index].0, e
+                ),
+            }
+            self.forkers.remove(index);
+        }
+    }
 
-        Ok(reply)
+    /// Return the notification streams and the partially received notifications for
+    /// the next runtime instance
+    pub fn handoff(self) -> Result<Vec<(Pid, StdUnixStream, BytesMut)>, Error> {
+        self.forkers
+            .into_iter()
+            .map(|(pid, stream)| {
+                stream
+                    .into_parts()
+                    .map(|(stream, buffer)| (pid, stream, buffer))
+                    .context("failed to convert exit notification handle")
+            })
+            .collect()
     }
 }
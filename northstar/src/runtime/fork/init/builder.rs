@@ -1,19 +1,29 @@
 use super::{Init, Mount};
 use crate::{
     common::container::Container,
-    npk::manifest::{mount, Manifest},
+    npk::manifest::{
+        ipc::DBus,
+        mount,
+        sysctl::{self, Namespace},
+        Manifest, Time,
+    },
     runtime::{
-        config::Config,
+        clock,
+        config::{Config, MountBackend},
+        dbus,
         error::{Context, Error},
+        overlay, persist, pipe, secret, shm, socket,
         state::State,
     },
     seccomp,
 };
+use itertools::Itertools;
 use nix::{mount::MsFlags, unistd};
 use std::{
     ffi::{c_void, CString},
     path::{Path, PathBuf},
     ptr::null,
+    time::{Duration, Instant},
 };
 use tokio::fs;
 
@@ -21,35 +31,86 @@ trait PathExt {
     fn join_strip<T: AsRef<Path>>(&self, w: T) -> PathBuf;
 }
 
+/// Build the init configuration of `container` with `manifest`. Named instances use
+/// the root filesystem of the container. Returns the duration of the seccomp filter
+/// compilation alongside.
 pub async fn build<'a, I: Iterator<Item = &'a Container> + Clone>(
     config: &Config,
+    container: &Container,
     manifest: &Manifest,
     containers: I,
-) -> Result<Init, Error> {
-    let container = manifest.container();
-    let root = config.run_dir.join(container.to_string());
+) -> Result<(Init, Duration), Error> {
+    let root = config
+        .run_dir
+        .join(container.without_instance().to_string());
 
     let capabilities = manifest.capabilities.clone();
+    let no_new_privs = manifest.no_new_privs.unwrap_or(true);
     let console = manifest.console.is_some();
+    let minidump = config.minidumps();
     let gid = manifest.gid;
     let groups = groups(manifest);
-    let mounts = prepare_mounts(config, &root, manifest, containers).await?;
-    let rlimits = manifest.rlimits.clone();
-    let seccomp = seccomp_filter(manifest);
+    let mounts = prepare_mounts(config, &root, container, manifest, containers).await?;
+    let rlimits = config.rlimits(manifest)?;
+    let sched = manifest.sched.clone();
+    let io_priority = manifest.io_priority.clone();
+    let selinux_label = manifest.selinux.as_ref().and_then(|s| s.label.clone());
+    let apparmor_profile = manifest.apparmor_profile.clone();
+    let seccomp_start = Instant::now();
+    let seccomp = seccomp_filter(config, container, manifest)?;
+    let seccomp_duration = seccomp_start.elapsed();
     let uid = manifest.uid;
+    let pid_namespace = !simulation(config);
+    let net_namespace = manifest.network.as_ref().map_or(false, |n| n.namespace);
+    let time_offset = clock::offset(container, manifest)?;
+    let ipc_namespace = manifest
+        .sysctl
+        .keys()
+        .any(|key| sysctl::namespace(key) == Some(Namespace::Ipc));
+    let sysctls = manifest
+        .sysctl
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .collect();
+    let masked_paths = manifest
+        .masked_paths
+        .iter()
+        .map(|path| root.join_strip(path))
+        .collect();
+    let readonly_paths = manifest
+        .readonly_paths
+        .iter()
+        .map(|path| root.join_strip(path))
+        .collect();
 
-    Ok(Init {
-        container,
+    let init = Init {
+        container: container.clone(),
         root,
         uid,
         gid,
         mounts,
         groups,
         capabilities,
+        no_new_privs,
         rlimits,
+        sched,
+        io_priority,
+        selinux_label,
+        apparmor_profile,
         seccomp,
         console,
-    })
+        minidump,
+        pid_namespace,
+        net_namespace,
+        time_offset,
+        ipc_namespace,
+        sysctls,
+        masked_paths,
+        readonly_paths,
+    };
+
+    Ok((init, seccomp_duration))
 }
 
 /// Generate a list of supplementary gids if the groups info can be retrieved. This
@@ -72,15 +133,39 @@ fn groups(manifest: &Manifest) -> Vec<u32> {
     result
 }
 
-/// Generate seccomp filter applied in init
-fn seccomp_filter(manifest: &Manifest) -> Option<seccomp::AllowList> {
-    manifest.seccomp.as_ref().map(|seccomp| {
-        seccomp::seccomp_filter(
-            seccomp.profile.as_ref(),
-            seccomp.allow.as_ref(),
-            &manifest.capabilities,
-        )
-    })
+/// True if the containers are started without pid namespace and seccomp filters
+#[cfg(feature = "simulation")]
+fn simulation(config: &Config) -> bool {
+    config.mount_backend == MountBackend::Simulation
+}
+
+#[cfg(not(feature = "simulation"))]
+fn simulation(_: &Config) -> bool {
+    false
+}
+
+/// Generate seccomp filter applied in init. The simulation backend skips the filter.
+fn seccomp_filter(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+) -> Result<Option<seccomp::AllowList>, Error> {
+    if simulation(config) {
+        return Ok(None);
+    }
+    manifest
+        .seccomp
+        .as_ref()
+        .map(|seccomp| {
+            seccomp::seccomp_filter(
+                seccomp.profile.as_ref(),
+                seccomp.allow.as_ref(),
+                seccomp.deny.as_ref(),
+                &manifest.capabilities.permitted,
+            )
+            .map_err(|e| Error::StartContainerFailed(container.clone(), e.to_string()))
+        })
+        .transpose()
 }
 
 /// Iterate the mounts of a container and assemble a list of `mount` calls to be
@@ -89,26 +174,43 @@ fn seccomp_filter(manifest: &Manifest) -> Option<seccomp::AllowList> {
 async fn prepare_mounts<'a, I: Iterator<Item = &'a Container> + Clone>(
     config: &Config,
     root: &Path,
+    container: &Container,
     manifest: &Manifest,
     containers: I,
 ) -> Result<Vec<Mount>, Error> {
     let mut mounts = vec![];
     let manifest_mounts = &manifest.mounts;
 
+    // The overlay must be mounted before anything is mounted into the root
+    if manifest.root.is_some() && !simulation(config) {
+        mounts.push(root_overlay(config, root, container));
+    }
+
     for (target, mount) in manifest_mounts {
         match mount {
             mount::Mount::Bind(mount::Bind { host, options }) => {
                 mounts.extend(bind(root, target, host, options));
             }
-            mount::Mount::Persist => {
-                // Note that the version is intentionally not part of the path. This allows
-                // upgrades with persistent data migration
-                let source = config.data_dir.join(manifest.name.to_string());
+            mount::Mount::Overlay(mount::Overlay { layers, options }) => {
+                let layers = layers
+                    .iter()
+                    .map(|layer| {
+                        let dependency =
+                            State::match_container(&layer.name, &layer.version, containers.clone())
+                                .expect("failed to locate required resource container"); // Already checked in State::start()
+                        (dependency, layer.dir.as_path())
+                    })
+                    .collect::<Vec<_>>();
+                mounts.push(resource_overlay(
+                    root, target, config, container, &layers, options,
+                )?);
+            }
+            mount::Mount::Persist(_) => {
+                let source = persist::dir(config, container, manifest);
                 mounts.push(persist(root, &source, target, manifest.uid, manifest.gid).await?);
             }
             mount::Mount::Proc => mounts.push(proc(root, target)),
             mount::Mount::Resource(requirement) => {
-                let container = Container::new(manifest.name.clone(), manifest.version.clone());
                 let dependency = State::match_container(
                     &requirement.name,
                     &requirement.version,
@@ -119,7 +221,7 @@ async fn prepare_mounts<'a, I: Iterator<Item = &'a Container> + Clone>(
                     root,
                     target,
                     config,
-                    &container,
+                    container,
                     dependency,
                     &requirement.dir,
                     &requirement.options,
@@ -127,14 +229,98 @@ async fn prepare_mounts<'a, I: Iterator<Item = &'a Container> + Clone>(
                 mounts.push(mount);
                 mounts.push(remount_ro);
             }
+            mount::Mount::Secret(_) => {
+                let source = secret::dir(&config.run_dir, container, target);
+                let (mount, remount_ro) = secrets(root, &source, target);
+                mounts.push(mount);
+                mounts.push(remount_ro);
+            }
             mount::Mount::Tmpfs(mount::Tmpfs { size }) => mounts.push(tmpfs(root, target, *size)),
             mount::Mount::Dev => {}
         }
     }
 
+    if let Some(ipc) = &manifest.ipc {
+        for p in &ipc.pipes {
+            let source = pipe::path(&config.run_dir, &p.name);
+            mounts.push(pipe(root, &source, &p.path));
+        }
+    }
+
+    // Sockets of the container are writable, linked sockets are read only
+    if let Some(ipc) = &manifest.ipc {
+        for s in &ipc.sockets {
+            let source = socket::dir(&config.run_dir, &manifest.name, s);
+            mounts.extend(shared(root, &source, s, true));
+        }
+    }
+    for link in &manifest.links {
+        let source = socket::dir(&config.run_dir, &link.from, &link.socket);
+        mounts.extend(shared(root, &source, &link.socket, false));
+    }
+
+    // Owned shared memory segments are writable, others are read only
+    for s in manifest.ipc.iter().flat_map(|ipc| &ipc.shm) {
+        let owner = s.from.as_ref().unwrap_or(&manifest.name);
+        let source = shm::path(&config.run_dir, owner, &s.name);
+        mounts.extend(shared(root, &source, &s.path, s.from.is_none()));
+    }
+
+    // Bus sockets of the D-Bus proxy
+    if manifest.ipc.iter().any(|ipc| ipc.dbus.is_some()) {
+        let source = dbus::dir(&config.run_dir, container);
+        mounts.extend(shared(root, &source, Path::new(DBus::DIR), false));
+    }
+
+    // Library that fakes the realtime clock
+    if manifest.time.iter().any(|time| time.realtime.is_some()) {
+        let source = config.faketime.as_ref().ok_or_else(|| {
+            Error::StartContainerFailed(container.clone(), "faketime is not configured".into())
+        })?;
+        mounts.extend(shared(root, source, Path::new(Time::FAKETIME), false));
+    }
+
     Ok(mounts)
 }
 
+fn root_overlay(config: &Config, root: &Path, container: &Container) -> Mount {
+    // The FUSE overlay is mounted by the runtime
+    if config.mount_backend == MountBackend::Fuse {
+        let merged = overlay::merged(&config.run_dir, container);
+        log::debug!(
+            "Adding bind mount of FUSE overlay {} on {}",
+            merged.display(),
+            root.display()
+        );
+        let flags = MsFlags::MS_BIND | MsFlags::MS_REC;
+        return Mount::new(Some(merged), root.to_owned(), None, flags, None);
+    }
+
+    let upper = overlay::upper(&config.run_dir, container);
+    let work = overlay::work(&config.run_dir, container);
+    log::debug!(
+        "Adding overlay on {} with upper dir {}",
+        root.display(),
+        upper.display()
+    );
+    // The overlay is mounted on top of its lower dir
+    let data = format!(
+        "lowerdir={},upperdir={},workdir={}",
+        root.display(),
+        upper.display(),
+        work.display()
+    );
+    let source = PathBuf::from("overlay");
+    let flags = MsFlags::MS_NODEV | MsFlags::MS_NOSUID;
+    Mount::new(
+        Some(source),
+        root.to_owned(),
+        Some("overlay"),
+        flags,
+        Some(data),
+    )
+}
+
 fn proc(root: &Path, target: &Path) -> Mount {
     log::debug!(
         "Adding proc on {} with options ro, nosuid, noexec and nodev",
@@ -290,6 +476,119 @@ fn resource(
     Ok((mount, remount_ro))
 }
 
+fn resource_overlay(
+    root: &Path,
+    target: &Path,
+    config: &Config,
+    container: &Container,
+    layers: &[(&Container, &Path)],
+    options: &mount::MountOptions,
+) -> Result<Mount, Error> {
+    let mut lower = Vec::with_capacity(layers.len());
+    for (dependency, dir) in layers {
+        let resource_root =
+            config
+                .run_dir
+                .join(format!("{}:{}", dependency.name(), dependency.version()));
+        let src = dir
+            .strip_prefix("/")
+            .map(|d| resource_root.join(d))
+            .unwrap_or(resource_root);
+        if !src.exists() {
+            return Err(Error::StartContainerMissingResource(
+                container.clone(),
+                dependency.name().clone(),
+                dependency.version().to_string(),
+            ));
+        }
+        lower.push(src);
+    }
+
+    // Overlayfs expects the top most layer first
+    let lowerdir = lower.iter().rev().map(|l| l.display()).join(":");
+
+    log::debug!(
+        "Mounting overlay of {} on {} with {}",
+        lowerdir,
+        target.display(),
+        options
+    );
+
+    let target = root.join_strip(target);
+    let mut flags = options_to_flags(options);
+    flags |= MsFlags::MS_RDONLY;
+    let data = format!("lowerdir={}", lowerdir);
+    Ok(Mount::new(
+        Some(PathBuf::from("overlay")),
+        target,
+        Some("overlay"),
+        flags,
+        Some(data),
+    ))
+}
+
+fn secrets(root: &Path, source: &Path, target: &Path) -> (Mount, Mount) {
+    log::debug!(
+        "Mounting secrets {} on {} with options ro, nodev, nosuid and noexec",
+        source.display(),
+        target.display()
+    );
+    let target = root.join_strip(target);
+    let mut flags = MsFlags::MS_BIND
+        | MsFlags::MS_RDONLY
+        | MsFlags::MS_NODEV
+        | MsFlags::MS_NOSUID
+        | MsFlags::MS_NOEXEC;
+    let mount = Mount::new(Some(source.to_owned()), target.clone(), None, flags, None);
+
+    // Remount ro
+    flags.set(MsFlags::MS_REMOUNT, true);
+    let remount_ro = Mount::new(Some(source.to_owned()), target, None, flags, None);
+    (mount, remount_ro)
+}
+
+fn pipe(root: &Path, source: &Path, target: &Path) -> Mount {
+    log::debug!(
+        "Mounting pipe {} on {} with options nodev, nosuid and noexec",
+        source.display(),
+        target.display()
+    );
+    let target = root.join_strip(target);
+    let flags = MsFlags::MS_BIND | MsFlags::MS_NODEV | MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC;
+    Mount::new(Some(source.to_owned()), target, None, flags, None)
+}
+
+fn shared(root: &Path, source: &Path, target: &Path, rw: bool) -> Vec<Mount> {
+    log::debug!(
+        "Mounting {} on {} with options {}nodev, nosuid and noexec",
+        source.display(),
+        target.display(),
+        if rw { "" } else { "ro, " }
+    );
+    let target = root.join_strip(target);
+    let mut flags = MsFlags::MS_BIND | MsFlags::MS_NODEV | MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC;
+    let mut mounts = vec![Mount::new(
+        Some(source.to_owned()),
+        target.clone(),
+        None,
+        flags,
+        None,
+    )];
+    if !rw {
+        // Remount ro
+        flags.set(MsFlags::MS_RDONLY, true);
+        flags.set(MsFlags::MS_REMOUNT, true);
+        mounts.push(Mount::new(
+            Some(source.to_owned()),
+            target,
+            None,
+            flags,
+            None,
+        ));
+    }
+    mounts
+}
+
 fn tmpfs(root: &Path, target: &Path, size: u64) -> Mount {
     log::debug!(
         "Mounting tmpfs with size {} on {}",
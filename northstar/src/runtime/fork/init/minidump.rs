@@ -0,0 +1,181 @@
+use super::wait;
+use crate::{
+    debug, info,
+    runtime::{ExitStatus, Pid},
+    warn,
+};
+use minidump_writer::minidump_writer::MinidumpWriter;
+use nix::{
+    libc,
+    sys::{
+        ptrace,
+        signal::{self, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd,
+};
+use std::{collections::HashSet, fs, io::Seek};
+
+/// Signals that terminate a process with a core dump if not handled by the process
+const CRASH_SIGNALS: &[Signal] = &[
+    Signal::SIGABRT,
+    Signal::SIGBUS,
+    Signal::SIGFPE,
+    Signal::SIGILL,
+    Signal::SIGSEGV,
+    Signal::SIGSYS,
+    Signal::SIGTRAP,
+];
+
+/// Stop signals that put a process into group-stop
+const STOP_SIGNALS: &[Signal] = &[
+    Signal::SIGSTOP,
+    Signal::SIGTSTP,
+    Signal::SIGTTIN,
+    Signal::SIGTTOU,
+];
+
+/// Trace the threads of the application process `pid`. Fails if the process is
+/// traced already e.g. by a debug strace instance.
+pub(super) fn seize(pid: Pid) -> nix::Result<()> {
+    let options = ptrace::Options::PTRACE_O_TRACECLONE
+        | ptrace::Options::PTRACE_O_TRACEEXEC
+        | ptrace::Options::PTRACE_O_EXITKILL;
+    ptrace::seize(unistd::Pid::from_raw(pid as i32), options)
+}
+
+/// Wait for the traced application process `pid` to exit. If a thread of the process
+/// receives a crash signal that is not handled by the process a minidump is written
+/// to `file` before the signal is delivered.
+pub(super) fn wait_traced(pid: Pid, mut file: fs::File) -> ExitStatus {
+    let process = unistd::Pid::from_raw(pid as i32);
+    let mut threads = HashSet::from([process]);
+
+    loop {
+        match waitpid(None, Some(WaitPidFlag::__WALL)) {
+            // New thread. The thread starts traced and reports an event stop.
+            Ok(WaitStatus::PtraceEvent(tid, _, libc::PTRACE_EVENT_CLONE)) => {
+                if let Ok(thread) = ptrace::getevent(tid) {
+                    threads.insert(unistd::Pid::from_raw(thread as i32));
+                }
+                ptrace::cont(tid, None).ok();
+            }
+            // Group-stop or the initial stop of a new thread
+            Ok(WaitStatus::PtraceEvent(tid, _, libc::PTRACE_EVENT_STOP)) => {
+                let group_stop = ptrace::getsiginfo(tid)
+                    .ok()
+                    .and_then(|info| Signal::try_from(info.si_signo).ok())
+                    .map(|signal| STOP_SIGNALS.contains(&signal))
+                    .unwrap_or(false);
+                if group_stop {
+                    // Keep the thread stopped until SIGCONT
+                    unsafe { libc::ptrace(libc::PTRACE_LISTEN, tid.as_raw(), 0, 0) };
+                } else {
+                    ptrace::cont(tid, None).ok();
+                }
+            }
+            Ok(WaitStatus::PtraceEvent(tid, _, _)) => {
+                ptrace::cont(tid, None).ok();
+            }
+            // Signal delivery
+            Ok(WaitStatus::Stopped(tid, signal)) => {
+                if CRASH_SIGNALS.contains(&signal) && !is_handled(process, signal) {
+                    info!("Application process {} crashed with {}", pid, signal);
+                    detach(process, tid, signal, &threads);
+                    write(process, tid, &mut file);
+
+                    // Deliver the crash signal and let the process terminate
+                    unsafe {
+                        libc::syscall(
+                            libc::SYS_tgkill,
+                            process.as_raw(),
+                            tid.as_raw(),
+                            signal as i32,
+                        )
+                    };
+                    signal::kill(process, Signal::SIGCONT).ok();
+                    return wait(pid);
+                }
+                ptrace::cont(tid, signal).ok();
+            }
+            Ok(WaitStatus::Exited(tid, status)) if tid == process => {
+                break ExitStatus::Exit(status);
+            }
+            Ok(WaitStatus::Signaled(tid, signal, _)) if tid == process => {
+                break signal.into();
+            }
+            // Exit of a thread or of an orphan adopted by init
+            Ok(WaitStatus::Exited(tid, _)) | Ok(WaitStatus::Signaled(tid, _, _)) => {
+                threads.remove(&tid);
+            }
+            Ok(_) => (),
+            Err(nix::Error::EINTR) => (),
+            e => panic!("failed to waitpid on {}: {:?}", pid, e),
+        }
+    }
+}
+
+/// True if the process installed a handler for `signal`
+fn is_handled(process: unistd::Pid, signal: Signal) -> bool {
+    // The mask of the caught signals is the "SigCgt" line of the status
+    fs::read_to_string(format!("/proc/{}/status", process))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("SigCgt:"))
+                .and_then(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+        })
+        .map(|mask| mask & (1 << (signal as u64 - 1)) != 0)
+        .unwrap_or(false)
+}
+
+/// Stop the process and detach from all threads. The crash signal of the thread
+/// `crashed` is suppressed.
+fn detach(
+    process: unistd::Pid,
+    crashed: unistd::Pid,
+    signal: Signal,
+    threads: &HashSet<unistd::Pid>,
+) {
+    debug!("Stopping process {} after {}", process, signal);
+    signal::kill(process, Signal::SIGSTOP).ok();
+    ptrace::detach(crashed, None).ok();
+
+    for thread in threads.iter().filter(|thread| **thread != crashed) {
+        loop {
+            match waitpid(*thread, Some(WaitPidFlag::__WALL)) {
+                // Pass pending signals of other threads on
+                Ok(WaitStatus::Stopped(_, signal)) => {
+                    ptrace::detach(*thread, signal).ok();
+                    break;
+                }
+                Ok(WaitStatus::PtraceEvent(..)) => {
+                    ptrace::detach(*thread, None).ok();
+                    break;
+                }
+                Err(nix::Error::EINTR) => continue,
+                _ => break,
+            }
+        }
+    }
+}
+
+/// Write a minidump of the stopped `process` with `crashed` as the crashing thread
+fn write(process: unistd::Pid, crashed: unistd::Pid, file: &mut fs::File) {
+    debug!("Writing minidump of {}", process);
+    let result = file.rewind().map_err(|e| e.to_string()).and_then(|_| {
+        MinidumpWriter::new(process.as_raw(), crashed.as_raw())
+            .dump(file)
+            .map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(_) => {
+            info!("Wrote minidump of {}", process);
+        }
+        Err(e) => {
+            warn!("failed to write minidump of {}: {}", process, e);
+            file.set_len(0).ok();
+        }
+    }
+}
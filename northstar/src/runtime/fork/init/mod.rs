@@ -1,13 +1,17 @@
 use crate::{
     common::{container::Container, non_nul_string::NonNulString},
     debug, info,
-    npk::manifest::{Capability, RLimitResource, RLimitValue},
+    npk::manifest::{
+        Capabilities, Capability, IoPriority, IoPriorityClass, RLimitResource, RLimitValue, Sched,
+        SchedPolicy,
+    },
     runtime::{
         fork::util::{self, fork, set_child_subreaper, set_log_target, set_process_name},
         ipc::{owned_fd::OwnedFd, Message as IpcMessage},
         ExitStatus, Pid,
     },
     seccomp::AllowList,
+    warn,
 };
 pub use builder::build;
 use itertools::Itertools;
@@ -28,19 +32,25 @@ use std::{
     collections::{HashMap, HashSet},
     env,
     ffi::CString,
+    fmt, fs,
     os::unix::{
         net::UnixStream,
-        prelude::{AsRawFd, RawFd},
+        prelude::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
     },
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::exit,
 };
 
 mod builder;
+mod minidump;
 
 // Message from the forker to init and response
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
+    /// The init process set up the container and waits for the exec request
+    Ready,
+    /// A mount of the init process failed. The init process exits afterwards.
+    MountFailed(MountError),
     /// The init process forked a new child with `pid`
     Forked { pid: Pid },
     /// A child of init exited with `exit_status`
@@ -61,14 +71,32 @@ pub struct Init {
     pub gid: u16,
     pub mounts: Vec<Mount>,
     pub groups: Vec<u32>,
-    pub capabilities: HashSet<Capability>,
+    pub capabilities: Capabilities,
+    pub no_new_privs: bool,
     pub rlimits: HashMap<RLimitResource, RLimitValue>,
+    pub sched: Option<Sched>,
+    pub io_priority: Option<IoPriority>,
+    pub selinux_label: Option<NonNulString>,
+    pub apparmor_profile: Option<NonNulString>,
     pub seccomp: Option<AllowList>,
     pub console: bool,
+    pub minidump: bool,
+    pub pid_namespace: bool,
+    pub net_namespace: bool,
+    pub time_offset: Option<i64>,
+    pub ipc_namespace: bool,
+    pub sysctls: Vec<(String, NonNulString)>,
+    pub masked_paths: Vec<PathBuf>,
+    pub readonly_paths: Vec<PathBuf>,
 }
 
 impl Init {
-    pub fn run(self, mut stream: IpcMessage<UnixStream>, console: Option<OwnedFd>) -> ! {
+    pub fn run(
+        self,
+        mut stream: IpcMessage<UnixStream>,
+        console: Option<OwnedFd>,
+        minidump: Option<OwnedFd>,
+    ) -> ! {
         set_log_target(format!("northstar::init::{}", self.container));
 
         // Become a subreaper
@@ -86,8 +114,32 @@ impl Init {
         debug!("Entering mount namespace");
         unshare(nix::sched::CloneFlags::CLONE_NEWNS).expect("failed to unshare NEWNS");
 
-        // Perform all mounts passed in mounts
-        self.mount();
+        // Enter IPC namespace
+        if self.ipc_namespace {
+            debug!("Entering IPC namespace");
+            unshare(nix::sched::CloneFlags::CLONE_NEWIPC).expect("failed to unshare NEWIPC");
+        }
+
+        // Perform all mounts passed in mounts and hide and protect paths below the
+        // mounts e.g. entries of proc. A failed mount is reported to the runtime.
+        let mounted = self
+            .mount()
+            .and_then(|_| self.mount_readonly_paths())
+            .and_then(|_| self.mask_paths());
+        if let Err(e) = mounted {
+            warn!("{}", e);
+            stream
+                .send(Message::MountFailed(e))
+                .expect("failed to send mount failure");
+            exit(1);
+        }
+
+        // Security labels. Needs the proc filesystem of the host and happens before the chroot.
+        self.set_security_labels();
+
+        // Kernel parameters of the namespaces. Needs the proc filesystem of the host and
+        // happens before the chroot.
+        self.set_sysctls();
 
         // Set the chroot to the containers root mount point
         debug!("Chrooting to {}", self.root.display());
@@ -106,12 +158,25 @@ impl Init {
         // Apply resource limits
         self.set_rlimits();
 
+        // Scheduling policy and priority. Needs to happen before the capabilities are dropped.
+        self.set_sched();
+
+        // IO priority
+        self.set_io_priority();
+
         // No new privileges
-        Self::set_no_new_privs(true);
+        if self.no_new_privs {
+            Self::set_no_new_privs(true);
+        }
 
         // Capabilities
         self.drop_privileges();
 
+        // The container is set up and waits for the exec request
+        stream
+            .send(Message::Ready)
+            .expect("failed to send ready message");
+
         loop {
             match stream.recv() {
                 Ok(Some(Message::Exec {
@@ -139,6 +204,11 @@ impl Init {
                     let stdout = io[1];
                     let stderr = io[2];
 
+                    // The application waits until it is traced for minidumps
+                    let trace = minidump
+                        .as_ref()
+                        .map(|_| unistd::pipe().expect("failed to create pipe"));
+
                     // Start new process inside the container
                     let pid = fork(|| {
                         set_log_target(format!("northstar::{}", self.container));
@@ -152,6 +222,22 @@ impl Init {
                         unistd::close(stdout).expect("failed to close stdout after dup2");
                         unistd::close(stderr).expect("failed to close stderr after dup2");
 
+                        // A SCHED_DEADLINE task cannot fork. Set the policy of the application
+                        // process itself.
+                        if let Some(sched) = self
+                            .sched
+                            .as_ref()
+                            .filter(|sched| sched.policy == SchedPolicy::Deadline)
+                        {
+                            Self::set_sched_deadline(sched);
+                        }
+
+                        if let Some((read, write)) = trace {
+                            unistd::close(write).expect("failed to close pipe");
+                            unistd::read(read, &mut [0u8]).expect("failed to read pipe");
+                            unistd::close(read).expect("failed to close pipe");
+                        }
+
                         // Set seccomp filter
                         if let Some(ref filter) = self.seccomp {
                             filter.apply().expect("failed to apply seccomp filter.");
@@ -176,49 +262,42 @@ impl Init {
                     unistd::close(stdout).expect("failed to close stdout");
                     unistd::close(stderr).expect("failed to close stderr");
 
+                    // Trace the application and release it
+                    let minidump = match (minidump, trace) {
+                        (Some(file), Some((read, write))) => {
+                            let traced = match minidump::seize(pid) {
+                                Ok(_) => Some(unsafe { fs::File::from_raw_fd(file.into_raw_fd()) }),
+                                Err(e) => {
+                                    warn!("failed to trace {}. Minidumps are disabled: {}", pid, e);
+                                    None
+                                }
+                            };
+                            unistd::close(read).expect("failed to close pipe");
+                            unistd::write(write, &[0u8]).expect("failed to write pipe");
+                            unistd::close(write).expect("failed to close pipe");
+                            traced
+                        }
+                        _ => None,
+                    };
+
                     let message = Message::Forked { pid };
                     stream.send(&message).expect("failed to send fork result");
 
                     // Wait for the child to exit
-                    loop {
-                        debug!("Waiting for child process {} to exit", pid);
-                        match waitpid(Some(unistd::Pid::from_raw(pid as i32)), None) {
-                            Ok(WaitStatus::Exited(_pid, status)) => {
-                                debug!("Child process {} exited with status code {}", pid, status);
-                                let exit_status = ExitStatus::Exit(status);
-                                stream
-                                    .send(Message::Exit { pid, exit_status })
-                                    .expect("Channel error");
-
-                                assert_eq!(
-                                    waitpid(Some(unistd::Pid::from_raw(pid as i32)), None),
-                                    Err(nix::Error::ECHILD)
-                                );
-
-                                exit(0);
-                            }
-                            Ok(WaitStatus::Signaled(_pid, status, _)) => {
-                                debug!("Child process {} exited with signal {}", pid, status);
-                                let exit_status = ExitStatus::Signalled(status as u8);
-                                stream
-                                    .send(Message::Exit { pid, exit_status })
-                                    .expect("Channel error");
-
-                                assert_eq!(
-                                    waitpid(Some(unistd::Pid::from_raw(pid as i32)), None),
-                                    Err(nix::Error::ECHILD)
-                                );
-
-                                exit(0);
-                            }
-                            Ok(WaitStatus::Continued(_)) | Ok(WaitStatus::Stopped(_, _)) => {
-                                log::error!("Child process continued or stopped");
-                                continue;
-                            }
-                            Err(nix::Error::EINTR) => continue,
-                            e => panic!("failed to waitpid on {}: {:?}", pid, e),
-                        }
-                    }
+                    let exit_status = match minidump {
+                        Some(file) => minidump::wait_traced(pid, file),
+                        None => wait(pid),
+                    };
+                    stream
+                        .send(Message::Exit { pid, exit_status })
+                        .expect("Channel error");
+
+                    assert_eq!(
+                        waitpid(Some(unistd::Pid::from_raw(pid as i32)), None),
+                        Err(nix::Error::ECHILD)
+                    );
+
+                    exit(0);
                 }
                 Ok(None) => {
                     info!("Channel closed. Exiting...");
@@ -296,28 +375,158 @@ impl Init {
         }
     }
 
+    /// Set the scheduling policy, priority and nice value of init. The settings are
+    /// inherited by the application process.
+    fn set_sched(&self) {
+        let sched = match &self.sched {
+            Some(sched) => sched,
+            None => return,
+        };
+        let policy = match sched.policy {
+            SchedPolicy::Other => libc::SCHED_OTHER,
+            SchedPolicy::Fifo => libc::SCHED_FIFO,
+            SchedPolicy::Rr => libc::SCHED_RR,
+            SchedPolicy::Deadline => return,
+        };
+
+        debug!("Setting scheduling policy {:?}", sched.policy);
+        let param = libc::sched_param {
+            sched_priority: sched.priority.unwrap_or(0) as libc::c_int,
+        };
+        let result = unsafe { libc::sched_setscheduler(0, policy, &param) };
+        Errno::result(result)
+            .map(drop)
+            .expect("failed to set scheduling policy");
+
+        if let Some(nice) = sched.nice {
+            debug!("Setting nice value {}", nice);
+            let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+            Errno::result(result)
+                .map(drop)
+                .expect("failed to set nice value");
+        }
+    }
+
+    /// Set the SELinux label and AppArmor profile that are applied on the exec of the
+    /// application. The attributes are inherited by the forked application process.
+    fn set_security_labels(&self) {
+        if let Some(label) = &self.selinux_label {
+            debug!("Setting SELinux exec label {}", label);
+            fs::write("/proc/self/attr/exec", label.as_str())
+                .expect("failed to set SELinux exec label");
+        }
+
+        if let Some(profile) = &self.apparmor_profile {
+            debug!("Setting AppArmor exec profile {}", profile);
+            // Kernels with stackable LSMs provide an AppArmor specific interface
+            let path = Path::new("/proc/self/attr/apparmor/exec");
+            let path = if path.exists() {
+                path
+            } else {
+                Path::new("/proc/self/attr/exec")
+            };
+            fs::write(path, format!("exec {}", profile))
+                .expect("failed to set AppArmor exec profile");
+        }
+    }
+
+    /// Set the kernel parameters. The parameters of the IPC and network namespaces
+    /// are resolved with the namespaces of the writing process.
+    fn set_sysctls(&self) {
+        for (key, value) in &self.sysctls {
+            debug!("Setting sysctl {} to {}", key, value);
+            let path = Path::new("/proc/sys").join(key.replace('.', "/"));
+            fs::write(&path, value.as_str())
+                .unwrap_or_else(|e| panic!("failed to set sysctl {}: {}", key, e));
+        }
+    }
+
+    /// Set the io scheduling class and priority of init. The settings are inherited by
+    /// the application process.
+    fn set_io_priority(&self) {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+        let io_priority = match &self.io_priority {
+            Some(io_priority) => io_priority,
+            None => return,
+        };
+        let class = match io_priority.class {
+            IoPriorityClass::Realtime => 1,
+            IoPriorityClass::BestEffort => 2,
+            IoPriorityClass::Idle => 3,
+        };
+        let level = io_priority.level.unwrap_or(4) as libc::c_int;
+        let ioprio = class << IOPRIO_CLASS_SHIFT | level;
+
+        debug!("Setting io priority {:?}", io_priority);
+        let result = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+        Errno::result(result)
+            .map(drop)
+            .expect("failed to set io priority");
+    }
+
+    /// Set the SCHED_DEADLINE policy of the calling process. There's no libc wrapper
+    /// for sched_setattr.
+    fn set_sched_deadline(sched: &Sched) {
+        const SCHED_DEADLINE: u32 = 6;
+
+        #[repr(C)]
+        struct SchedAttr {
+            size: u32,
+            sched_policy: u32,
+            sched_flags: u64,
+            sched_nice: i32,
+            sched_priority: u32,
+            sched_runtime: u64,
+            sched_deadline: u64,
+            sched_period: u64,
+        }
+
+        let deadline = sched.deadline.unwrap_or_default();
+        let attr = SchedAttr {
+            size: std::mem::size_of::<SchedAttr>() as u32,
+            sched_policy: SCHED_DEADLINE,
+            sched_flags: 0,
+            sched_nice: 0,
+            sched_priority: 0,
+            sched_runtime: sched.runtime.unwrap_or_default(),
+            sched_deadline: deadline,
+            sched_period: sched.period.unwrap_or(deadline),
+        };
+        let result =
+            unsafe { libc::syscall(libc::SYS_sched_setattr, 0, &attr as *const SchedAttr, 0) };
+        Errno::result(result)
+            .map(drop)
+            .expect("failed to set scheduling policy deadline");
+    }
+
     /// Drop capabilities
     fn drop_privileges(&self) {
         debug!("Dropping priviledges");
         let mut bounded =
             caps::read(None, caps::CapSet::Bounding).expect("failed to read bounding caps");
-        // Convert the set from the manifest to a set of caps::Capability
-        let set = self
-            .capabilities
-            .iter()
-            .cloned()
-            .map(Into::into)
-            .collect::<HashSet<caps::Capability>>();
-        bounded.retain(|c| !set.contains(c));
+        // Convert the sets from the manifest to sets of caps::Capability
+        let convert = |set: &HashSet<Capability>| {
+            set.iter()
+                .cloned()
+                .map(Into::into)
+                .collect::<HashSet<caps::Capability>>()
+        };
+        let bounding = convert(&self.capabilities.bounding);
+        let permitted = convert(&self.capabilities.permitted);
+        let ambient = convert(&self.capabilities.ambient);
+        bounded.retain(|c| !bounding.contains(c));
 
         for cap in &bounded {
             // caps::set cannot be called for bounded
             caps::drop(None, caps::CapSet::Bounding, *cap).expect("failed to drop bounding cap");
         }
-        caps::set(None, caps::CapSet::Effective, &set).expect("failed to set effective caps");
-        caps::set(None, caps::CapSet::Permitted, &set).expect("failed to set permitted caps");
-        caps::set(None, caps::CapSet::Inheritable, &set).expect("failed to set inheritable caps");
-        caps::set(None, caps::CapSet::Ambient, &set).expect("failed to set ambient caps");
+        caps::set(None, caps::CapSet::Effective, &permitted).expect("failed to set effective caps");
+        caps::set(None, caps::CapSet::Permitted, &permitted).expect("failed to set permitted caps");
+        caps::set(None, caps::CapSet::Inheritable, &permitted)
+            .expect("failed to set inheritable caps");
+        caps::set(None, caps::CapSet::Ambient, &ambient).expect("failed to set ambient caps");
     }
 
     // Reset effective caps to the most possible set
@@ -327,9 +536,62 @@ impl Init {
     }
 
     /// Execute list of mount calls
-    fn mount(&self) {
-        for mount in &self.mounts {
-            mount.mount();
+    fn mount(&self) -> Result<(), MountError> {
+        self.mounts.iter().try_for_each(Mount::mount)
+    }
+
+    /// Bind mount the read only paths onto themselves and remount them read only.
+    /// Paths that do not exist in the root are skipped.
+    fn mount_readonly_paths(&self) -> Result<(), MountError> {
+        for path in &self.readonly_paths {
+            if !Self::mountable(path) {
+                continue;
+            }
+            debug!("Remounting {} read only", path.display());
+            let flags = MsFlags::MS_BIND | MsFlags::MS_REC;
+            Mount::new(Some(path.clone()), path.clone(), None, flags, None).mount()?;
+            let flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY;
+            Mount::new(None, path.clone(), None, flags, None).mount()?;
+        }
+        Ok(())
+    }
+
+    /// Cover directories with an empty read only tmpfs and files with /dev/null.
+    /// Paths that do not exist in the root are skipped.
+    fn mask_paths(&self) -> Result<(), MountError> {
+        for path in &self.masked_paths {
+            if !Self::mountable(path) {
+                continue;
+            }
+            debug!("Masking {}", path.display());
+            let mount = if path.is_dir() {
+                let flags = MsFlags::MS_RDONLY
+                    | MsFlags::MS_NOSUID
+                    | MsFlags::MS_NODEV
+                    | MsFlags::MS_NOEXEC;
+                let data = Some("mode=0555".to_string());
+                Mount::new(None, path.clone(), Some("tmpfs"), flags, data)
+            } else {
+                let source = Some(PathBuf::from("/dev/null"));
+                Mount::new(source, path.clone(), None, MsFlags::MS_BIND, None)
+            };
+            mount.mount()?;
+        }
+        Ok(())
+    }
+
+    /// Symlinks are not followed because they could point out of the root
+    fn mountable(path: &Path) -> bool {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                warn!("Skipping symlink {}", path.display());
+                false
+            }
+            Ok(_) => true,
+            Err(_) => {
+                debug!("Skipping nonexistent {}", path.display());
+                false
+            }
         }
     }
 
@@ -347,6 +609,29 @@ impl Init {
     }
 }
 
+/// Wait for the child process `pid` to exit
+fn wait(pid: Pid) -> ExitStatus {
+    loop {
+        debug!("Waiting for child process {} to exit", pid);
+        match waitpid(Some(unistd::Pid::from_raw(pid as i32)), None) {
+            Ok(WaitStatus::Exited(_pid, status)) => {
+                debug!("Child process {} exited with status code {}", pid, status);
+                break ExitStatus::Exit(status);
+            }
+            Ok(WaitStatus::Signaled(_pid, status, _)) => {
+                debug!("Child process {} exited with signal {}", pid, status);
+                break status.into();
+            }
+            Ok(WaitStatus::Continued(_)) | Ok(WaitStatus::Stopped(_, _)) => {
+                log::error!("Child process continued or stopped");
+                continue;
+            }
+            Err(nix::Error::EINTR) => continue,
+            e => panic!("failed to waitpid on {}: {:?}", pid, e),
+        }
+    }
+}
+
 impl From<Capability> for caps::Capability {
     fn from(cap: Capability) -> Self {
         match cap {
@@ -403,7 +688,6 @@ pub struct Mount {
     pub fstype: Option<String>,
     pub flags: u64,
     pub data: Option<String>,
-    pub error_msg: String,
 }
 
 impl Mount {
@@ -414,34 +698,59 @@ impl Mount {
         flags: MsFlags,
         data: Option<String>,
     ) -> Mount {
-        let error_msg = format!(
-            "failed to mount '{}' of type '{}' on '{}' with flags '{:?}' and data '{}'",
-            source.clone().unwrap_or_default().display(),
-            fstype.unwrap_or_default(),
-            target.display(),
-            flags,
-            data.clone().unwrap_or_default()
-        );
         Mount {
             source,
             target,
             fstype: fstype.map(|s| s.to_string()),
             flags: flags.bits(),
             data,
-            error_msg,
         }
     }
 
     /// Execute this mount call
-    pub(super) fn mount(&self) {
+    pub(super) fn mount(&self) -> Result<(), MountError> {
+        // Safe because flags is private and only set in Mount::new via MsFlags::bits
+        let flags = unsafe { MsFlags::from_bits_unchecked(self.flags) };
         nix::mount::mount(
             self.source.as_ref(),
             &self.target,
             self.fstype.as_deref(),
-            // Safe because flags is private and only set in Mount::new via MsFlags::bits
-            unsafe { MsFlags::from_bits_unchecked(self.flags) },
+            flags,
             self.data.as_deref(),
         )
-        .expect(&self.error_msg);
+        .map_err(|errno| MountError {
+            source: self.source.clone(),
+            target: self.target.clone(),
+            fstype: self.fstype.clone(),
+            flags: format!("{:?}", flags),
+            data: self.data.clone(),
+            errno: errno as i32,
+        })
+    }
+}
+
+/// Failed mount call of init reported to the runtime
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MountError {
+    pub source: Option<PathBuf>,
+    pub target: PathBuf,
+    pub fstype: Option<String>,
+    pub flags: String,
+    pub data: Option<String>,
+    pub errno: i32,
+}
+
+impl fmt::Display for MountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to mount '{}' of type '{}' on '{}' with flags '{}' and data '{}': {}",
+            self.source.clone().unwrap_or_default().display(),
+            self.fstype.as_deref().unwrap_or_default(),
+            self.target.display(),
+            self.flags,
+            self.data.as_deref().unwrap_or_default(),
+            Errno::from_i32(self.errno)
+        )
     }
 }
@@ -2,4 +2,4 @@ mod forker;
 mod init;
 mod util;
 
-pub use forker::{start, Forker, ForkerChannels, Notification};
+pub use forker::{reap, start, Forker, ForkerChannels, Notification, Notifications};
@@ -0,0 +1,63 @@
+use super::mount::Error;
+use std::{path::Path, process::Stdio};
+use tokio::process::Command;
+use tracing::debug;
+
+/// Squashfs FUSE driver
+const SQUASHFUSE: &str = "squashfuse";
+/// Overlayfs FUSE driver
+const FUSE_OVERLAYFS: &str = "fuse-overlayfs";
+/// Umount helper for FUSE mounts
+const FUSERMOUNT: &str = "fusermount";
+
+/// Mount the squashfs image located at `offset` in `image` on `target`
+pub(super) async fn squashfs(image: &Path, offset: u64, target: &Path) -> Result<(), Error> {
+    // The container processes run with different uids than the runtime
+    let options = format!("allow_other,offset={}", offset);
+    let mut cmd = Command::new(SQUASHFUSE);
+    cmd.arg("-o").arg(options).arg(image).arg(target);
+    run(cmd).await
+}
+
+/// Mount an overlay of `lower` with the writable `upper` and `work` dirs on `target`
+pub(super) async fn overlay(
+    lower: &Path,
+    upper: &Path,
+    work: &Path,
+    target: &Path,
+) -> Result<(), Error> {
+    let options = format!(
+        "lowerdir={},upperdir={},workdir={},allow_other",
+        lower.display(),
+        upper.display(),
+        work.display()
+    );
+    let mut cmd = Command::new(FUSE_OVERLAYFS);
+    cmd.arg("-o").arg(options).arg(target);
+    run(cmd).await
+}
+
+/// Umount the FUSE filesystem on `target`. This terminates the driver process.
+pub(super) async fn umount(target: &Path) -> Result<(), Error> {
+    let mut cmd = Command::new(FUSERMOUNT);
+    cmd.arg("-u").arg(target);
+    run(cmd).await
+}
+
+async fn run(mut cmd: Command) -> Result<(), Error> {
+    debug!("Running {:?}", cmd);
+    // The FUSE drivers daemonize once the filesystem is mounted. Do not attach any pipe
+    // that is inherited by the daemon.
+    let status = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .map_err(|e| Error::Io(format!("failed to run {:?}", cmd), e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::Fuse(format!("{:?} failed: {}", cmd, status)))
+    }
+}
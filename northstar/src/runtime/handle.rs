@@ -0,0 +1,122 @@
+use super::{
+    console,
+    error::{Context, Error},
+    events::{Overflow, Subscribers, Subscription},
+    installs::Installs,
+    Event, EventTx, NotificationTx,
+};
+use crate::api::model::{self, Container};
+use futures::StreamExt;
+use std::{collections::HashMap, path::Path};
+use tokio::{
+    fs,
+    sync::{broadcast, mpsc, oneshot},
+};
+use tokio_util::io::ReaderStream;
+
+/// Handle to a started runtime. The handle is cheap to clone and processes requests
+/// like a console connection with full permissions.
+#[derive(Clone, Debug)]
+pub struct Handle {
+    pub(super) event_tx: EventTx,
+    pub(super) notification_tx: NotificationTx,
+    pub(super) installs: Installs,
+    pub(super) subscribers: Subscribers,
+}
+
+impl Handle {
+    /// Subscribe to the notifications of the runtime. A receiver that lags more than
+    /// `notification_buffer_size` notifications behind loses notifications.
+    pub fn subscribe(&self) -> broadcast::Receiver<model::Notification> {
+        self.notification_tx.subscribe()
+    }
+
+    /// Subscribe to the typed events of the runtime. The subscription buffers up to
+    /// `capacity` events. `overflow` decides what happens if the buffer is full.
+    pub fn events(&self, capacity: usize, overflow: Overflow) -> Subscription {
+        self.subscribers.subscribe(capacity, overflow)
+    }
+
+    /// Process `request`. Requests that transfer data and requests that refer to a
+    /// console connection are rejected with `Error::InvalidArguments`. Use `install`
+    /// to install a npk.
+    pub async fn request(&self, request: model::Request) -> Result<model::Response, Error> {
+        if matches!(
+            request,
+            model::Request::Attach(..)
+                | model::Request::Cancel(..)
+                | model::Request::CoreDumpExport(..)
+                | model::Request::DataExport(..)
+                | model::Request::DataImport(..)
+                | model::Request::Ident
+                | model::Request::Install(..)
+                | model::Request::InstallCancel(..)
+                | model::Request::Installs
+                | model::Request::TokenCreate(..)
+                | model::Request::TokenVerify(..)
+        ) {
+            return Err(Error::InvalidArguments(format!(
+                "unsupported request {:?}",
+                request
+            )));
+        }
+
+        self.send(console::Request::Request(request)).await
+    }
+
+    /// Install the npk at `npk` into `repository`. The install is queued with the
+    /// installs of the console connections.
+    pub async fn install(&self, npk: &Path, repository: &str) -> Result<model::Response, Error> {
+        let file = fs::File::open(npk)
+            .await
+            .context(format!("failed to open {}", npk.display()))?;
+        let size = file
+            .metadata()
+            .await
+            .context(format!("failed to read metadata of {}", npk.display()))?
+            .len();
+
+        // Wait for the preceding installs
+        let mut install = self.installs.enqueue(repository.to_string(), size);
+        let _turn = install.turn().await;
+
+        let (tx, rx) = mpsc::channel(10);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let request = console::Request::Install(repository.to_string(), size, rx);
+        self.event_tx
+            .send(Event::Console(request, reply_tx))
+            .await
+            .map_err(|_| Error::NotRunning)?;
+
+        let mut bytes = 0;
+        let mut chunks = ReaderStream::new(file);
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.context(format!("failed to read {}", npk.display()))?;
+            bytes += chunk.len() as u64;
+            // The runtime drops the receiver if the install fails
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+            install.progress(bytes);
+        }
+        drop(tx);
+
+        reply_rx.await.map_err(|_| Error::NotRunning)
+    }
+
+    /// Start `container` with the arguments and environment of its manifest
+    pub async fn start(&self, container: Container) -> Result<model::Response, Error> {
+        self.request(model::Request::Start(container, Vec::new(), HashMap::new()))
+            .await
+    }
+
+    /// Send `request` to the main loop and wait for the response
+    async fn send(&self, request: console::Request) -> Result<model::Response, Error> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.event_tx
+            .send(Event::Console(request, reply_tx))
+            .await
+            .map_err(|_| Error::NotRunning)?;
+        reply_rx.await.map_err(|_| Error::NotRunning)
+    }
+}
@@ -0,0 +1,277 @@
+use super::{
+    core_dump::CorePattern,
+    error::{Context, Error},
+    ipc::{owned_fd::OwnedFd, RawFdExt},
+    Container, Pid, RepositoryId,
+};
+use crate::{
+    api::model,
+    common::{name::Name, non_nul_string::NonNulString},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    env,
+    ffi::{CString, OsStr, OsString},
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        prelude::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    },
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{debug, info};
+use url::Url;
+
+/// Environment variable with the memfd that holds the state of the previous instance
+const ENV_HANDOFF: &str = "NORTHSTAR_HANDOFF";
+/// Variables of the socket activation. The passed sockets are closed on the exec.
+const ENV_LISTEN: [&str; 3] = ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"];
+
+/// Supervision of the started containers handed over from a stopped runtime to a new
+/// runtime binary. The containers keep running.
+#[derive(Debug)]
+pub struct Handoff {
+    pub(super) state: State,
+    /// Fds referenced by `state` that are kept open across the exec
+    fds: Vec<OwnedFd>,
+}
+
+/// State of a runtime instance that is passed to the next instance
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct State {
+    /// Forker processes of the started containers
+    pub forkers: Vec<Forker>,
+    /// Listening sockets of the consoles
+    pub consoles: Vec<(Url, RawFd)>,
+    /// Core dump settings replaced by the first instance
+    pub core_pattern: Option<CorePattern>,
+    /// Mounted and started containers and containers with a history
+    pub containers: Vec<Entry>,
+    /// Containers with the autostart flag that are handled and whether they started
+    pub autostarted: Vec<(Container, bool)>,
+}
+
+/// Forker process with its request and notification streams
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Forker {
+    pub pid: Pid,
+    pub stream: RawFd,
+    pub notifications: RawFd,
+    /// Partially received notification
+    pub buffer: Vec<u8>,
+}
+
+/// State of a container
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Entry {
+    pub container: Container,
+    pub repository: RepositoryId,
+    pub root: Option<PathBuf>,
+    pub mounted_for_start: bool,
+    pub start_timings: Option<model::StartTimings>,
+    pub history: model::ExitHistory,
+    pub oom_restarts: u32,
+    pub process: Option<Process>,
+}
+
+/// Started container process
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Process {
+    pub pid: Pid,
+    /// Pid of the forker that created the container
+    pub forker: Pid,
+    pub uptime: Duration,
+    pub paused: bool,
+    pub oom_kills: u64,
+    pub oom: bool,
+    /// Signals sent by the runtime
+    pub signals: Vec<i32>,
+    pub syscall: Option<u32>,
+    pub args: Vec<NonNulString>,
+    pub env: HashMap<NonNulString, NonNulString>,
+    pub parameters: HashMap<Name, NonNulString>,
+    pub resources: Vec<Container>,
+    /// Piped output with an incomplete line
+    pub output: Option<(RawFd, Vec<u8>)>,
+    pub minidump: Option<RawFd>,
+    /// Network namespace with the moved interfaces
+    pub network: Option<(RawFd, Vec<NonNulString>)>,
+}
+
+impl State {
+    /// Fds referenced by the state
+    fn fds(&self) -> Vec<RawFd> {
+        let forkers = self
+            .forkers
+            .iter()
+            .flat_map(|forker| [forker.stream, forker.notifications]);
+        let consoles = self.consoles.iter().map(|(_, fd)| *fd);
+        let processes = self
+            .containers
+            .iter()
+            .filter_map(|entry| entry.process.as_ref())
+            .flat_map(|process| {
+                let output = process.output.as_ref().map(|(fd, _)| *fd);
+                let network = process.network.as_ref().map(|(fd, _)| *fd);
+                output.into_iter().chain(process.minidump).chain(network)
+            });
+        forkers.chain(consoles).chain(processes).collect()
+    }
+}
+
+impl Handoff {
+    pub(super) fn new() -> Handoff {
+        Handoff {
+            state: State::default(),
+            fds: Vec::new(),
+        }
+    }
+
+    /// Keep `fd` open across the exec. Returns the number of the fd in the new binary.
+    pub(super) fn pass<T: IntoRawFd>(&mut self, fd: T) -> RawFd {
+        let fd = unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) };
+        let raw = fd.as_raw_fd();
+        self.fds.push(fd);
+        raw
+    }
+
+    /// True if this process is executed with the state of a previous instance
+    pub fn inherited() -> bool {
+        env::var_os(ENV_HANDOFF).is_some()
+    }
+
+    /// Replace the process image with `program` started with `args` and pass the
+    /// state. The new binary continues the supervision of the started containers.
+    /// Must be called from the thread that built the runtime after the async runtime
+    /// is shut down: The forker processes are terminated once this thread exits.
+    /// Returns only on error.
+    pub fn exec(self, program: &Path, args: &[OsString]) -> Error {
+        match self.try_exec(program, args) {
+            Ok(never) => match never {},
+            Err(e) => e,
+        }
+    }
+
+    fn try_exec(self, program: &Path, args: &[OsString]) -> Result<Infallible, Error> {
+        let state = serde_json::to_vec(&self.state).context("failed to serialize state")?;
+        let mut file = memfd::MemfdOptions::default()
+            .close_on_exec(false)
+            .create("northstar-handoff")
+            .context("failed to create memfd")?
+            .into_file();
+        file.write_all(&state).context("failed to write state")?;
+
+        for fd in &self.fds {
+            fd.set_cloexec(false).context("failed to clear cloexec")?;
+        }
+
+        let cstring = |s: &OsStr| CString::new(s.as_bytes()).context("invalid string");
+        let path = cstring(program.as_os_str())?;
+        let args = args
+            .iter()
+            .map(|arg| cstring(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut vars = Vec::new();
+        for (key, value) in env::vars_os() {
+            if key == ENV_HANDOFF || ENV_LISTEN.iter().any(|listen| key == *listen) {
+                continue;
+            }
+            let mut var = key;
+            var.push("=");
+            var.push(value);
+            vars.push(cstring(&var)?);
+        }
+        let var = format!("{}={}", ENV_HANDOFF, file.as_raw_fd());
+        vars.push(cstring(OsStr::new(&var))?);
+
+        info!("Handing over to {}", program.display());
+        nix::unistd::execve(&path, &args, &vars).context("failed to exec")
+    }
+}
+
+/// Take the state of the previous instance if this process is executed with it. The
+/// passed fds are closed on a later exec.
+pub(super) fn take() -> Result<Option<Handoff>, Error> {
+    let fd = match env::var(ENV_HANDOFF) {
+        Ok(fd) => fd,
+        Err(_) => return Ok(None),
+    };
+    env::remove_var(ENV_HANDOFF);
+
+    debug!("Reading the state of the previous instance");
+    let fd = fd.parse::<RawFd>().context("invalid handoff fd")?;
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    let mut state = Vec::new();
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_end(&mut state))
+        .context("failed to read state")?;
+    let state: State = serde_json::from_slice(&state).context("invalid state")?;
+
+    for fd in state.fds() {
+        fd.set_cloexec(true).context("failed to set cloexec")?;
+    }
+
+    Ok(Some(Handoff {
+        state,
+        fds: Vec::new(),
+    }))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state() {
+        let container = Container::try_from("hello:0.0.1").unwrap();
+        let state = State {
+            forkers: vec![Forker {
+                pid: 10,
+                stream: 3,
+                notifications: 4,
+                buffer: vec![0, 0],
+            }],
+            consoles: vec![("tcp://localhost:4200".parse().unwrap(), 5)],
+            core_pattern: None,
+            containers: vec![Entry {
+                container: container.clone(),
+                repository: "default".into(),
+                root: Some("/run/northstar/hello:0.0.1".into()),
+                mounted_for_start: true,
+                start_timings: None,
+                history: model::ExitHistory::default(),
+                oom_restarts: 1,
+                process: Some(Process {
+                    pid: 11,
+                    forker: 10,
+                    uptime: Duration::from_secs(1),
+                    paused: false,
+                    oom_kills: 0,
+                    oom: false,
+                    signals: vec![15],
+                    syscall: None,
+                    args: Vec::new(),
+                    env: HashMap::new(),
+                    parameters: HashMap::new(),
+                    resources: Vec::new(),
+                    output: Some((6, b"incomplete".to_vec())),
+                    minidump: None,
+                    network: Some((7, Vec::new())),
+                }),
+            }],
+            autostarted: vec![(container.clone(), true)],
+        };
+
+        let state: State = serde_json::from_slice(&serde_json::to_vec(&state).unwrap()).unwrap();
+        assert_eq!(state.fds(), vec![3, 4, 5, 6, 7]);
+        assert_eq!(state.containers[0].container, container);
+        let process = state.containers[0].process.as_ref().unwrap();
+        assert_eq!(process.output.as_ref().unwrap().1, b"incomplete");
+        assert_eq!(state.autostarted, vec![(container, true)]);
+    }
+}
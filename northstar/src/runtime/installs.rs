@@ -0,0 +1,255 @@
+use super::{NotificationTx, RepositoryId};
+use crate::api::model::{self, InstallId, InstallState};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    sync::OwnedMutexGuard,
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Minimum interval between two progress notifications of an install
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Installs of all console connections. Npks are transferred into the runtime one
+/// after the other in the order of their requests.
+#[derive(Clone, Debug)]
+pub(crate) struct Installs {
+    /// Progress notifications
+    notification_tx: NotificationTx,
+    /// Turn of the transferring install. The lock is fair and granted in the order
+    /// of the requests.
+    turn: Arc<tokio::sync::Mutex<()>>,
+    /// Queued and transferring installs
+    queue: Arc<Mutex<Queue>>,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    next_id: InstallId,
+    installs: BTreeMap<InstallId, Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    repository: RepositoryId,
+    bytes: u64,
+    total: u64,
+    state: InstallState,
+    cancel: CancellationToken,
+}
+
+impl Installs {
+    pub(crate) fn new(notification_tx: NotificationTx) -> Installs {
+        Installs {
+            notification_tx,
+            turn: Arc::default(),
+            queue: Arc::default(),
+        }
+    }
+
+    /// Add an install of `total` bytes into `repository` to the queue
+    pub(crate) fn enqueue(&self, repository: RepositoryId, total: u64) -> Install {
+        let mut queue = self.queue.lock().expect("poisoned lock");
+        let id = queue.next_id;
+        queue.next_id += 1;
+
+        let cancel = CancellationToken::new();
+        let entry = Entry {
+            repository,
+            bytes: 0,
+            total,
+            state: InstallState::Queued,
+            cancel: cancel.clone(),
+        };
+        queue.installs.insert(id, entry);
+
+        Install {
+            id,
+            installs: self.clone(),
+            cancel,
+            notified: None,
+        }
+    }
+
+    /// Queued and transferring installs in the order of their requests
+    pub(crate) fn list(&self) -> Vec<model::InstallStatus> {
+        let queue = self.queue.lock().expect("poisoned lock");
+        queue
+            .installs
+            .iter()
+            .map(|(id, entry)| model::InstallStatus {
+                id: *id,
+                repository: entry.repository.clone(),
+                bytes: entry.bytes,
+                total: entry.total,
+                state: entry.state.clone(),
+            })
+            .collect()
+    }
+
+    /// Cancel the install `id`. Returns false if there's no such install.
+    pub(crate) fn cancel(&self, id: InstallId) -> bool {
+        let queue = self.queue.lock().expect("poisoned lock");
+        match queue.installs.get(&id) {
+            Some(entry) => {
+                entry.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Install in the queue. The install is removed from the queue when dropped.
+pub(crate) struct Install {
+    id: InstallId,
+    installs: Installs,
+    cancel: CancellationToken,
+    /// Time of the last progress notification
+    notified: Option<Instant>,
+}
+
+impl Install {
+    pub(crate) fn id(&self) -> InstallId {
+        self.id
+    }
+
+    /// Token that is cancelled if the install is cancelled
+    pub(crate) fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Wait until the preceding installs are done. The install transfers as long as
+    /// the returned guard is held.
+    pub(crate) async fn turn(&self) -> OwnedMutexGuard<()> {
+        let turn = self.installs.turn.clone().lock_owned().await;
+        let mut queue = self.installs.queue.lock().expect("poisoned lock");
+        if let Some(entry) = queue.installs.get_mut(&self.id) {
+            entry.state = InstallState::Transferring;
+        }
+        turn
+    }
+
+    /// Record the transfer of `bytes` in total. Progress is notified at most every
+    /// `PROGRESS_INTERVAL` and on completion.
+    pub(crate) fn progress(&mut self, bytes: u64) {
+        let total = {
+            let mut queue = self.installs.queue.lock().expect("poisoned lock");
+            match queue.installs.get_mut(&self.id) {
+                Some(entry) => {
+                    entry.bytes = bytes;
+                    entry.total
+                }
+                None => return,
+            }
+        };
+
+        let now = Instant::now();
+        let due = self
+            .notified
+            .map(|notified| now.saturating_duration_since(notified) >= PROGRESS_INTERVAL)
+            .unwrap_or(true);
+        if (due || bytes == total) && self.installs.notification_tx.receiver_count() > 0 {
+            let notification = model::Notification::InstallProgress {
+                id: self.id,
+                bytes,
+                total,
+            };
+            self.installs.notification_tx.send(notification).ok();
+            self.notified = Some(now);
+        }
+    }
+}
+
+impl Drop for Install {
+    fn drop(&mut self) {
+        let mut queue = self.installs.queue.lock().expect("poisoned lock");
+        queue.installs.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+    use tokio::sync::broadcast;
+
+    fn installs() -> (Installs, broadcast::Receiver<model::Notification>) {
+        let (tx, rx) = broadcast::channel(10);
+        (Installs::new(tx), rx)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queue() {
+        let (installs, _rx) = installs();
+
+        let first = installs.enqueue("default".into(), 100);
+        let second = installs.enqueue("default".into(), 200);
+        assert_ne!(first.id(), second.id());
+
+        let turn = first.turn().await;
+        let list = installs.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].state, InstallState::Transferring);
+        assert_eq!(list[1].state, InstallState::Queued);
+
+        // The second install transfers once the first is done
+        let (tx, mut transferring) = tokio::sync::oneshot::channel();
+        let waiting = tokio::spawn(async move {
+            let _turn = second.turn().await;
+            tx.send(()).unwrap();
+        });
+        tokio::task::yield_now().await;
+        assert!(transferring.try_recv().is_err());
+        drop(turn);
+        drop(first);
+        waiting.await.unwrap();
+        assert!(transferring.try_recv().is_ok());
+        assert!(installs.list().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel() {
+        let (installs, _rx) = installs();
+
+        let install = installs.enqueue("default".into(), 100);
+        let token = install.cancel_token();
+        assert!(!installs.cancel(install.id() + 1));
+        assert!(installs.cancel(install.id()));
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn progress() {
+        let (installs, mut rx) = installs();
+
+        let mut install = installs.enqueue("default".into(), 100);
+        let id = install.id();
+        install.progress(10);
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            model::Notification::InstallProgress {
+                id,
+                bytes: 10,
+                total: 100
+            }
+        );
+
+        // Throttled within the interval but the completion is always notified
+        install.progress(20);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(installs.list()[0].bytes, 20);
+        install.progress(100);
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            model::Notification::InstallProgress {
+                id,
+                bytes: 100,
+                total: 100
+            }
+        );
+    }
+}
@@ -1,49 +1,135 @@
 use std::{
-    os::unix::prelude::{AsRawFd, FromRawFd, IntoRawFd},
+    os::unix::{
+        net::UnixStream as StdUnixStream,
+        prelude::{AsRawFd, FromRawFd, IntoRawFd},
+    },
     path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
     common::container::Container,
-    npk::manifest::{self, Output},
+    npk::manifest::{Level, Log, LogSink, LogdBuffer, Manifest, Output, RateLimit},
 };
-use log::debug;
+use bytes::Bytes;
+use futures::future;
 use nix::{
     fcntl::OFlag,
     pty,
     sys::{stat::Mode, termios::SetArg},
 };
 use tokio::{
-    io::{self, AsyncBufReadExt, AsyncRead},
+    fs,
+    io::{self, AsyncReadExt, AsyncWriteExt},
+    net::{UnixDatagram, UnixStream},
+    select,
+    sync::mpsc,
     task::{self, JoinHandle},
 };
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
 
-use super::ipc::owned_fd::{OwnedFd, OwnedFdRw};
+use super::{
+    ipc::owned_fd::{OwnedFd, OwnedFdRw},
+    ContainerEvent, NotificationTx,
+};
+
+/// Socket of the systemd journal native protocol
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+/// Socket of the Android log daemon
+const LOGD_SOCKET: &str = "/dev/socket/logdw";
+/// Size of the chunks read from a PTY
+const PTY_BUFFER_SIZE: usize = 4096;
+/// Size of the chunks read from the output of a container
+const OUTPUT_BUFFER_SIZE: usize = 4096;
+/// Number of stdin writes buffered for a container
+const STDIN_BUFFER: usize = 16;
+
+/// Output of the PTY and input from the client that is attached to a container
+pub type Attachment = (mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>);
 
 pub struct ContainerIo {
     pub io: [OwnedFd; 3],
     /// A handle to the io forwarding task if stdout or stderr is set to `Output::Pipe`
-    pub log_task: Option<JoinHandle<io::Result<()>>>,
+    /// or to the PTY task if `tty` is set
+    pub log_task: Option<JoinHandle<io::Result<Option<PipedOutput>>>>,
+    /// Stop the io forwarding task and return the piped output for a handoff
+    pub handoff: CancellationToken,
+    /// Attachments to the PTY if `tty` is set
+    pub pty: Option<mpsc::Sender<Attachment>>,
+    /// Input written to stdin if `stdin` is set
+    pub stdin: Option<mpsc::Sender<Bytes>>,
+}
+
+/// Piped output of a container with the bytes of an incomplete line read from it
+#[derive(Debug)]
+pub struct PipedOutput {
+    pub stream: StdUnixStream,
+    pub buffer: Vec<u8>,
 }
 
 /// Create a new pty handle if configured in the manifest or open /dev/null instead.
-pub async fn open(container: &Container, io: &manifest::Io) -> io::Result<ContainerIo> {
+/// Piped output is forwarded according to the log configuration of `manifest`.
+pub async fn open(
+    container: &Container,
+    manifest: &Manifest,
+    notification_tx: NotificationTx,
+) -> io::Result<ContainerIo> {
+    let io = &manifest.io;
+
+    // Connect stdin, stdout and stderr to the sub of a new PTY
+    if io.tty {
+        debug!("Spawning PTY task for {}", container);
+        let (main, sub) = openpty();
+        let sub = openrw(sub)?;
+        let (tx, rx) = mpsc::channel(1);
+        let pty = pty(container.clone(), OwnedFdRw::new(main)?, rx);
+        let pty_task = task::spawn(async move { pty.await.map(|_| None) });
+        return Ok(ContainerIo {
+            io: [sub.clone()?, sub.clone()?, sub],
+            log_task: Some(pty_task),
+            handoff: CancellationToken::new(),
+            pty: Some(tx),
+            stdin: None,
+        });
+    }
+
     // Open dev null - needed in any case for stdin
     let dev_null = openrw("/dev/null")?;
 
+    let (stdin, stdin_tx) = if io.stdin {
+        debug!("Spawning stdin task for {}", container);
+        let (read, write) = pipe()?;
+        let (tx, rx) = mpsc::channel(STDIN_BUFFER);
+        task::spawn(stdin_writer(container.clone(), OwnedFdRw::new(write)?, rx));
+        (read, Some(tx))
+    } else {
+        (dev_null.clone()?, None)
+    };
+
     // Don't start the output task if it is configured to be discarded
     if io.stdout == Output::Discard && io.stderr == Output::Discard {
         return Ok(ContainerIo {
-            io: [dev_null.clone()?, dev_null.clone()?, dev_null],
+            io: [stdin, dev_null.clone()?, dev_null],
             log_task: None,
+            handoff: CancellationToken::new(),
+            pty: None,
+            stdin: stdin_tx,
         });
     }
 
     debug!("Spawning output logging task for {}", container);
-    let (write, read) = output_device(OutputDevice::Socket)?;
-
-    let log_target = format!("northstar::{}", container);
-    let log_task = task::spawn(log_lines(log_target, read));
+    let (write, read) = output_socket()?;
+    let handoff = CancellationToken::new();
+    let log_task = spawn_output(
+        container,
+        manifest,
+        notification_tx,
+        read,
+        Vec::new(),
+        handoff.clone(),
+    )
+    .await?;
 
     let (stdout, stderr) = match (&io.stdout, &io.stderr) {
         (Output::Discard, Output::Pipe) => (dev_null.clone()?, write),
@@ -52,48 +138,89 @@ pub async fn open(container: &Container, io: &manifest::Io) -> io::Result<Contai
         _ => unreachable!(),
     };
 
-    let io = [dev_null, stdout, stderr];
+    let io = [stdin, stdout, stderr];
 
     Ok(ContainerIo {
         io,
         log_task: Some(log_task),
+        handoff,
+        pty: None,
+        stdin: stdin_tx,
     })
 }
 
-/// Type of output device
-enum OutputDevice {
-    Socket,
-    #[allow(dead_code)]
-    Pty,
-}
-
-/// Open a device used to collect the container output and forward it to Northstar's log
-fn output_device(
-    dev: OutputDevice,
-) -> io::Result<(OwnedFd, Box<dyn AsyncRead + Unpin + Send + Sync + 'static>)> {
-    match dev {
-        OutputDevice::Socket => {
-            let (msock, csock) = std::os::unix::net::UnixStream::pair()?;
-            let msock = {
-                msock.set_nonblocking(true)?;
-                tokio::net::UnixStream::from_std(msock)?
-            };
-            Ok((csock.into(), Box::new(msock)))
-        }
-        OutputDevice::Pty => {
-            let (main, sec_path) = openpty();
-            Ok((openrw(sec_path)?, Box::new(OwnedFdRw::new(main)?)))
-        }
-    }
+/// Forward the piped output of a container that is handed over by a previous runtime
+/// instance according to the log configuration of `manifest`
+pub async fn resume(
+    container: &Container,
+    manifest: &Manifest,
+    notification_tx: NotificationTx,
+    output: PipedOutput,
+) -> io::Result<(
+    JoinHandle<io::Result<Option<PipedOutput>>>,
+    CancellationToken,
+)> {
+    let PipedOutput { stream, buffer } = output;
+    stream.set_nonblocking(true)?;
+    let stream = UnixStream::from_std(stream)?;
+    let handoff = CancellationToken::new();
+    let log_task = spawn_output(
+        container,
+        manifest,
+        notification_tx,
+        stream,
+        buffer,
+        handoff.clone(),
+    )
+    .await?;
+    Ok((log_task, handoff))
+}
+
+/// Spawn the output task that forwards the output read from `output` according to the
+/// log configuration of `manifest`
+async fn spawn_output(
+    container: &Container,
+    manifest: &Manifest,
+    notification_tx: NotificationTx,
+    output: UnixStream,
+    buffer: Vec<u8>,
+    handoff: CancellationToken,
+) -> io::Result<JoinHandle<io::Result<Option<PipedOutput>>>> {
+    let log = manifest.log.clone().unwrap_or_default();
+    let sink = Sink::new(container, &log, notification_tx).await?;
+    Ok(task::spawn(log_lines(
+        container.clone(),
+        sink,
+        log.rate_limit.map(Limiter::new),
+        output,
+        buffer,
+        handoff,
+    )))
 }
 
-/// Open a path for reading and writing.
+/// Open a socket pair used to collect the container output. Returns the end passed to
+/// the container and the end read by the runtime.
+fn output_socket() -> io::Result<(OwnedFd, UnixStream)> {
+    let (msock, csock) = StdUnixStream::pair()?;
+    msock.set_nonblocking(true)?;
+    Ok((csock.into(), UnixStream::from_std(msock)?))
+}
+
+/// Open a path for reading and writing. A PTY sub never becomes the controlling
+/// terminal of the runtime.
 fn openrw<T: AsRef<Path>>(f: T) -> io::Result<OwnedFd> {
-    nix::fcntl::open(f.as_ref(), OFlag::O_RDWR, Mode::empty())
+    nix::fcntl::open(f.as_ref(), OFlag::O_RDWR | OFlag::O_NOCTTY, Mode::empty())
         .map_err(|err| io::Error::from_raw_os_error(err as i32))
         .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
 }
 
+/// Create a pipe and return the read and write end
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    nix::unistd::pipe2(OFlag::O_CLOEXEC)
+        .map_err(|err| io::Error::from_raw_os_error(err as i32))
+        .map(|(read, write)| unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) })
+}
+
 /// Create a new pty and return the main fd along with the sub name.
 fn openpty() -> (OwnedFd, PathBuf) {
     let main = pty::posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY | OFlag::O_NONBLOCK)
@@ -122,12 +249,518 @@ fn openpty() -> (OwnedFd, PathBuf) {
     (main, sub)
 }
 
-/// Pipe task: Read pty until stop is cancelled. Write linewist to `log`.
-async fn log_lines<R: AsyncRead + Unpin>(target: String, output: R) -> io::Result<()> {
-    let mut lines = io::BufReader::new(output).lines();
-    while let Ok(Some(line)) = lines.next_line().await {
-        log::debug!(target: &target, "{}", line);
+/// PTY task: Forward the output of the PTY to the attached client and the input of
+/// the client to the PTY. The output is discarded while no client is attached. A
+/// new attachment replaces the current one. The task ends once the container exited.
+async fn pty(
+    container: Container,
+    main: OwnedFdRw,
+    mut attachments: mpsc::Receiver<Attachment>,
+) -> io::Result<()> {
+    let (mut reader, mut writer) = io::split(main);
+    let mut attached: Option<Attachment> = None;
+    let mut buffer = vec![0u8; PTY_BUFFER_SIZE];
+
+    loop {
+        let input = async {
+            match attached.as_mut() {
+                Some((_, input)) => input.recv().await,
+                None => future::pending().await,
+            }
+        };
+
+        select! {
+            attachment = attachments.recv() => match attachment {
+                Some(attachment) => {
+                    debug!("Attaching to the PTY of {}", container);
+                    attached = Some(attachment);
+                }
+                None => break,
+            },
+            // Reading the main fails with EIO once all subs are closed
+            read = reader.read(&mut buffer) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if let Some((output, _)) = attached.as_ref() {
+                        if output.send(Bytes::copy_from_slice(&buffer[..n])).await.is_err() {
+                            debug!("Detached from the PTY of {}", container);
+                            attached = None;
+                        }
+                    }
+                }
+            },
+            input = input => match input {
+                Some(input) => writer.write_all(&input).await?,
+                None => {
+                    debug!("Detached from the PTY of {}", container);
+                    attached = None;
+                }
+            },
+        }
     }
 
     Ok(())
 }
+
+/// Stdin task: Write the input of clients to the stdin pipe of the container. The
+/// task ends once the container exited or closed its stdin.
+async fn stdin_writer(container: Container, mut pipe: OwnedFdRw, mut input: mpsc::Receiver<Bytes>) {
+    while let Some(input) = input.recv().await {
+        if let Err(e) = pipe.write_all(&input).await {
+            debug!("Closing stdin of {}: {}", container, e);
+            break;
+        }
+    }
+}
+
+/// Output task: Read the output until the container closed it and write it linewise
+/// to `sink`. `buffer` holds an incomplete line read before. Once `handoff` is
+/// cancelled the task stops and returns the output with the incomplete line.
+async fn log_lines(
+    container: Container,
+    mut sink: Sink,
+    mut limiter: Option<Limiter>,
+    mut output: UnixStream,
+    mut buffer: Vec<u8>,
+    handoff: CancellationToken,
+) -> io::Result<Option<PipedOutput>> {
+    loop {
+        while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+            let line = buffer.drain(..=end).collect::<Vec<_>>();
+            log_line(&container, &mut sink, &mut limiter, &line[..end]).await?;
+        }
+
+        buffer.reserve(OUTPUT_BUFFER_SIZE);
+        select! {
+            _ = handoff.cancelled() => {
+                let stream = output.into_std()?;
+                return Ok(Some(PipedOutput { stream, buffer }));
+            }
+            read = output.read_buf(&mut buffer) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            },
+        }
+    }
+
+    // The last line is not terminated
+    if !buffer.is_empty() {
+        log_line(&container, &mut sink, &mut limiter, &buffer).await?;
+    }
+
+    Ok(None)
+}
+
+/// Write `line` without a carriage return to `sink` unless it exceeds the rate limit
+async fn log_line(
+    container: &Container,
+    sink: &mut Sink,
+    limiter: &mut Option<Limiter>,
+    line: &[u8],
+) -> io::Result<()> {
+    if let Some(limiter) = limiter.as_mut() {
+        match limiter.acquire() {
+            Acquire::Pass => (),
+            Acquire::PassAfterDrop(dropped) => {
+                warn!(
+                    "Dropped {} lines of {} due to rate limit",
+                    dropped, container
+                )
+            }
+            Acquire::Drop => return Ok(()),
+        }
+    }
+
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let line = String::from_utf8_lossy(line);
+    if let Err(e) = sink.write(&line).await {
+        // Do not lose the output if the sink is gone. Fall back to the runtime logger.
+        warn!(
+            "Failed to forward output of {}: {}. Using runtime logger",
+            container, e
+        );
+        *sink = Sink::runtime(container, Level::default());
+        sink.write(&line).await?;
+    }
+
+    Ok(())
+}
+
+/// Destination of the container output
+enum Sink {
+    /// Log crate with a per container target
+    Runtime { target: String, level: log::Level },
+    /// Systemd journal native protocol
+    Journald {
+        socket: UnixDatagram,
+        identifier: String,
+        priority: u8,
+    },
+    /// Android logd
+    Logd {
+        socket: UnixDatagram,
+        buffer: LogdBuffer,
+        tag: String,
+        priority: u8,
+    },
+    /// Rotated file on the host
+    File(RotatingFile),
+    /// Console notifications
+    Console {
+        container: Container,
+        notification_tx: NotificationTx,
+    },
+}
+
+impl Sink {
+    async fn new(
+        container: &Container,
+        log: &Log,
+        notification_tx: NotificationTx,
+    ) -> io::Result<Sink> {
+        let tag = log
+            .tag
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| container.name().to_string());
+        let sink = match &log.sink {
+            LogSink::Runtime => Sink::runtime(container, log.level),
+            LogSink::Journald => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(JOURNALD_SOCKET)?;
+                Sink::Journald {
+                    socket,
+                    identifier: tag,
+                    priority: syslog_priority(log.level),
+                }
+            }
+            LogSink::Logd { buffer } => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(LOGD_SOCKET)?;
+                Sink::Logd {
+                    socket,
+                    buffer: *buffer,
+                    tag,
+                    priority: logd_priority(log.level),
+                }
+            }
+            LogSink::File { path, size, files } => {
+                Sink::File(RotatingFile::open(path, *size, *files).await?)
+            }
+            LogSink::Console => Sink::Console {
+                container: container.clone(),
+                notification_tx,
+            },
+        };
+        Ok(sink)
+    }
+
+    fn runtime(container: &Container, level: Level) -> Sink {
+        let level = match level {
+            Level::Error => log::Level::Error,
+            Level::Warn => log::Level::Warn,
+            Level::Info => log::Level::Info,
+            Level::Debug => log::Level::Debug,
+            Level::Trace => log::Level::Trace,
+        };
+        Sink::Runtime {
+            target: format!("northstar::{}", container),
+            level,
+        }
+    }
+
+    async fn write(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Sink::Runtime { target, level } => {
+                log::log!(target: target, *level, "{}", line);
+            }
+            Sink::Journald {
+                socket,
+                identifier,
+                priority,
+            } => {
+                // Lines never contain a newline which allows the simple form of the protocol
+                let message = format!(
+                    "PRIORITY={}\nSYSLOG_IDENTIFIER={}\nMESSAGE={}\n",
+                    priority, identifier, line
+                );
+                socket.send(message.as_bytes()).await?;
+            }
+            Sink::Logd {
+                socket,
+                buffer,
+                tag,
+                priority,
+            } => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                let tid = nix::unistd::gettid().as_raw() as u16;
+                let mut message = Vec::with_capacity(12 + tag.len() + line.len());
+                message.push(*buffer as u8);
+                message.extend(tid.to_le_bytes());
+                message.extend((now.as_secs() as u32).to_le_bytes());
+                message.extend(now.subsec_nanos().to_le_bytes());
+                message.push(*priority);
+                message.extend(tag.as_bytes());
+                message.push(0);
+                message.extend(line.as_bytes());
+                message.push(0);
+                socket.send(&message).await?;
+            }
+            Sink::File(file) => file.write(line).await?,
+            Sink::Console {
+                container,
+                notification_tx,
+            } => {
+                // Do not fill the notification channel if there's nobody subscribed
+                if notification_tx.receiver_count() > 0 {
+                    let event = ContainerEvent::Log(line.to_string());
+                    notification_tx.send((container.clone(), event).into()).ok();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Map `level` to a syslog priority
+fn syslog_priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Map `level` to an Android log priority
+fn logd_priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 6,
+        Level::Warn => 5,
+        Level::Info => 4,
+        Level::Debug => 3,
+        Level::Trace => 2,
+    }
+}
+
+/// Log file that is rotated once it exceeds `size` bytes. The rotated files are
+/// suffixed with `.1` (newest) up to `.<files>` (oldest).
+struct RotatingFile {
+    path: PathBuf,
+    size: u64,
+    files: u32,
+    file: fs::File,
+    written: u64,
+}
+
+impl RotatingFile {
+    async fn open(path: &Path, size: u64, files: u32) -> io::Result<RotatingFile> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let written = file.metadata().await?.len();
+        Ok(RotatingFile {
+            path: path.to_owned(),
+            size,
+            files,
+            file,
+            written,
+        })
+    }
+
+    async fn write(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.written > 0 && self.written + len > self.size {
+            self.rotate().await?;
+        }
+        self.file.write_all(line.as_bytes()).await?;
+        self.file.write_all(b"\n").await?;
+        self.written += len;
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush().await?;
+        if self.files == 0 {
+            self.file.set_len(0).await?;
+        } else {
+            for n in (1..self.files).rev() {
+                match fs::rename(self.rotated(n), self.rotated(n + 1)).await {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+            fs::rename(&self.path, self.rotated(1)).await?;
+            self.file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, n: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+}
+
+/// Result of a rate limiter acquisition
+enum Acquire {
+    /// Forward the line
+    Pass,
+    /// Forward the line. The number of lines dropped before is attached.
+    PassAfterDrop(u64),
+    /// Drop the line
+    Drop,
+}
+
+/// Token bucket limiting the number of forwarded lines per second
+struct Limiter {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+    dropped: u64,
+}
+
+impl Limiter {
+    fn new(rate_limit: RateLimit) -> Limiter {
+        let rate = rate_limit.lines as f64;
+        let burst = rate_limit.burst.unwrap_or(rate_limit.lines).max(1) as f64;
+        Limiter {
+            rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+            dropped: 0,
+        }
+    }
+
+    fn acquire(&mut self) -> Acquire {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+
+        if self.tokens < 1.0 {
+            self.dropped += 1;
+            Acquire::Drop
+        } else {
+            self.tokens -= 1.0;
+            match std::mem::take(&mut self.dropped) {
+                0 => Acquire::Pass,
+                dropped => Acquire::PassAfterDrop(dropped),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{log_lines, Acquire, Limiter, RotatingFile, Sink};
+    use crate::{api::model::Notification, common::container::Container, npk::manifest::RateLimit};
+    use std::{io::Write, os::unix::net::UnixStream as StdUnixStream};
+    use tokio::{fs, io::AsyncWriteExt, net::UnixStream, sync::broadcast, task};
+    use tokio_util::sync::CancellationToken;
+
+    #[test]
+    fn limiter() {
+        let mut limiter = Limiter::new(RateLimit {
+            lines: 1,
+            burst: Some(2),
+        });
+        assert!(matches!(limiter.acquire(), Acquire::Pass));
+        assert!(matches!(limiter.acquire(), Acquire::Pass));
+        assert!(matches!(limiter.acquire(), Acquire::Drop));
+        assert!(matches!(limiter.acquire(), Acquire::Drop));
+
+        // Refill
+        limiter.tokens = 1.0;
+        assert!(matches!(limiter.acquire(), Acquire::PassAfterDrop(2)));
+    }
+
+    #[tokio::test]
+    async fn rotate() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("log");
+        let mut file = RotatingFile::open(&path, 10, 2).await?;
+        for line in ["one", "two", "three", "four", "five"] {
+            file.write(line).await?;
+        }
+        file.file.flush().await?;
+
+        assert_eq!(fs::read_to_string(&path).await?, "four\nfive\n");
+        assert_eq!(fs::read_to_string(file.rotated(1)).await?, "three\n");
+        assert_eq!(fs::read_to_string(file.rotated(2)).await?, "one\ntwo\n");
+        assert!(!file.rotated(3).exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn output_handoff() -> anyhow::Result<()> {
+        async fn line(notifications: &mut broadcast::Receiver<Notification>) -> String {
+            match notifications.recv().await {
+                Ok(Notification::Log(_, line)) => line,
+                notification => panic!("unexpected notification {:?}", notification),
+            }
+        }
+
+        let container = Container::try_from("test:0.0.1")?;
+        let (notification_tx, mut notifications) = broadcast::channel(8);
+        let sink = || Sink::Console {
+            container: container.clone(),
+            notification_tx: notification_tx.clone(),
+        };
+
+        let (mut writer, reader) = StdUnixStream::pair()?;
+        reader.set_nonblocking(true)?;
+        let reader = UnixStream::from_std(reader)?;
+        let handoff = CancellationToken::new();
+        let output = log_lines(
+            container.clone(),
+            sink(),
+            None,
+            reader,
+            Vec::new(),
+            handoff.clone(),
+        );
+        let output = task::spawn(output);
+
+        // The incomplete line is returned with the output
+        writer.write_all(b"one\r\ntw")?;
+        assert_eq!(line(&mut notifications).await, "one");
+        handoff.cancel();
+        let output = output.await??.expect("missing output");
+        assert_eq!(output.buffer, b"tw");
+
+        output.stream.set_nonblocking(true)?;
+        let reader = UnixStream::from_std(output.stream)?;
+        let output = log_lines(
+            container.clone(),
+            sink(),
+            None,
+            reader,
+            output.buffer,
+            CancellationToken::new(),
+        );
+        let output = task::spawn(output);
+
+        writer.write_all(b"o\nthree")?;
+        drop(writer);
+        assert_eq!(line(&mut notifications).await, "two");
+        assert_eq!(line(&mut notifications).await, "three");
+        assert!(output.await??.is_none());
+        Ok(())
+    }
+}
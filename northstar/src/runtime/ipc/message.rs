@@ -180,6 +180,31 @@ impl<T: AsyncRead + AsyncWrite + Unpin> AsyncMessage<T> {
 }
 
 impl AsyncMessage<tokio::net::UnixStream> {
+    /// Create a message stream from `inner` and the bytes of a partially received
+    /// message that were read from `inner` before
+    pub fn from_parts(
+        inner: std::os::unix::net::UnixStream,
+        read_buffer: &[u8],
+    ) -> io::Result<Self> {
+        let mut message = AsyncMessage::try_from(inner)?;
+        message.read_buffer.extend_from_slice(read_buffer);
+        Ok(message)
+    }
+
+    /// Split into the underlying stream and the bytes of a partially received
+    /// message. Fails if a message is partially sent.
+    pub fn into_parts(self) -> io::Result<(std::os::unix::net::UnixStream, BytesMut)> {
+        if !self.write_buffer.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "message is partially sent",
+            ));
+        }
+        let inner = self.inner.into_std()?;
+        inner.set_nonblocking(false)?;
+        Ok((inner, self.read_buffer))
+    }
+
     /// Send a file descriptor via the stream. Ensure that fd is open until this fn returns.
     pub async fn send_fds<T: AsRawFd>(&self, fds: &[T]) -> io::Result<()> {
         assert!(self.write_buffer.is_empty());
@@ -375,6 +400,33 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn parts() {
+        let (mut writer, reader) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut reader = AsyncMessage::try_from(reader).unwrap();
+
+        let payload = OPTIONS.serialize(&"hello".to_string()).unwrap();
+        let mut message = (payload.len() as u32).to_be_bytes().to_vec();
+        message.extend(payload);
+        let (first, second) = message.split_at(6);
+
+        // A receive cancelled within a message keeps the received bytes
+        writer.write_all(first).unwrap();
+        let recv = reader.recv::<String>();
+        let timeout = tokio::time::Duration::from_millis(10);
+        assert!(tokio::time::timeout(timeout, recv).await.is_err());
+
+        let (inner, read_buffer) = reader.into_parts().unwrap();
+        assert_eq!(&read_buffer[..], first);
+
+        let mut reader = AsyncMessage::from_parts(inner, &read_buffer).unwrap();
+        writer.write_all(second).unwrap();
+        assert_eq!(
+            reader.recv::<String>().await.unwrap(),
+            Some("hello".to_string())
+        );
+    }
+
     #[test]
     fn send_recv_fd_blocking() {
         let mut files = open_test_files();
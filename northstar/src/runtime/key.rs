@@ -1,10 +1,27 @@
+use super::config::RepositoryKey;
 use ed25519_dalek::SignatureError;
-use std::path::Path;
+use std::{path::Path, time::SystemTime};
 use thiserror::Error;
 use tokio::{fs, io};
 
 pub type PublicKey = ed25519_dalek::PublicKey;
 
+/// Key with a validity window
+#[derive(Clone, Debug)]
+pub(super) struct Key {
+    pub key: PublicKey,
+    pub not_before: Option<SystemTime>,
+    pub not_after: Option<SystemTime>,
+}
+
+impl Key {
+    /// True if the key is valid at `time`
+    pub fn is_valid(&self, time: SystemTime) -> bool {
+        self.not_before.map(|t| time >= t).unwrap_or(true)
+            && self.not_after.map(|t| time < t).unwrap_or(true)
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("invalid key file: {0}")]
@@ -22,3 +39,57 @@ pub(super) async fn load(path: &Path) -> Result<PublicKey, Error> {
 
     PublicKey::from_bytes(&key_bytes).map_err(Error::Signature)
 }
+
+/// Load the repository `keys`. Return `None` if no key is configured.
+pub(super) async fn load_all(keys: &[RepositoryKey]) -> Result<Option<Vec<Key>>, Error> {
+    if keys.is_empty() {
+        return Ok(None);
+    }
+    let mut result = Vec::with_capacity(keys.len());
+    for key in keys {
+        result.push(Key {
+            key: load(&key.path).await?,
+            not_before: key.not_before,
+            not_after: key.not_after,
+        });
+    }
+    Ok(Some(result))
+}
+
+/// Keys that are valid now. Return `None` for an unverified repository.
+pub(super) fn valid(keys: Option<&[Key]>) -> Option<Vec<PublicKey>> {
+    let now = SystemTime::now();
+    keys.map(|keys| {
+        keys.iter()
+            .filter(|key| key.is_valid(now))
+            .map(|key| key.key)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, PublicKey};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn validity() {
+        let now = SystemTime::now();
+        let key = Key {
+            key: PublicKey::default(),
+            not_before: Some(now),
+            not_after: Some(now + Duration::from_secs(10)),
+        };
+        assert!(!key.is_valid(now - Duration::from_secs(1)));
+        assert!(key.is_valid(now));
+        assert!(key.is_valid(now + Duration::from_secs(9)));
+        assert!(!key.is_valid(now + Duration::from_secs(10)));
+
+        let key = Key {
+            key: PublicKey::default(),
+            not_before: None,
+            not_after: None,
+        };
+        assert!(key.is_valid(now));
+    }
+}
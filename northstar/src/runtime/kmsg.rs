@@ -0,0 +1,222 @@
+use super::{
+    ipc::owned_fd::{OwnedFd, OwnedFdRw},
+    Event, EventTx, Pid,
+};
+use nix::{
+    fcntl::{self, OFlag},
+    sys::stat::Mode,
+    unistd::{self, Whence},
+};
+use std::os::unix::prelude::FromRawFd;
+use tokio::{io::AsyncReadExt, select, task};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// Kernel log device
+const KMSG: &str = "/dev/kmsg";
+/// Maximum size of a kmsg record
+const RECORD_SIZE: usize = 8192;
+
+/// Kernel message that relates to a process
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KernelMessage {
+    /// Type of the message
+    pub kind: KernelMessageKind,
+    /// Pid in the initial pid namespace of the affected process
+    pub pid: Pid,
+    /// Cgroup of the affected process if reported by the kernel
+    pub cgroup: Option<String>,
+    /// Message text
+    pub message: String,
+}
+
+/// Kernel messages that are forwarded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KernelMessageKind {
+    /// The process was killed by the oom killer
+    Oom,
+    /// Seccomp audit of a syscall that is not allowed
+    Seccomp {
+        /// Number of the syscall if reported by the kernel
+        syscall: Option<u32>,
+    },
+    /// The process received a segmentation fault
+    Segfault,
+}
+
+/// Read the kernel log starting at the current end and send messages that relate to
+/// processes to the main loop until `stop` is cancelled.
+pub(super) fn spawn(tx: EventTx, stop: CancellationToken) -> std::io::Result<task::JoinHandle<()>> {
+    let fd = fcntl::open(
+        KMSG,
+        OFlag::O_RDONLY | OFlag::O_NONBLOCK | OFlag::O_CLOEXEC,
+        Mode::empty(),
+    )?;
+    // Skip the messages that are already in the buffer
+    unistd::lseek(fd, 0, Whence::SeekEnd)?;
+    let mut kmsg = OwnedFdRw::new(unsafe { OwnedFd::from_raw_fd(fd) })?;
+
+    Ok(task::spawn(async move {
+        debug!("Reading kernel messages from {}", KMSG);
+        let mut parser = Parser::default();
+        let mut buffer = vec![0u8; RECORD_SIZE];
+        loop {
+            // Every read returns a single record
+            let n = select! {
+                _ = stop.cancelled() => break,
+                _ = tx.closed() => break,
+                n = kmsg.read(&mut buffer) => n,
+            };
+            let record = match n {
+                Ok(0) => break,
+                Ok(n) => String::from_utf8_lossy(&buffer[..n]),
+                // Records were overwritten before they were read
+                Err(e) if e.raw_os_error() == Some(nix::libc::EPIPE) => continue,
+                Err(e) => {
+                    warn!("Failed to read {}: {}", KMSG, e);
+                    break;
+                }
+            };
+            if let Some(message) = parser.parse(&record) {
+                if tx.send(Event::Kernel(message)).await.is_err() {
+                    break;
+                }
+            }
+        }
+        debug!("Stopped reading kernel messages");
+    }))
+}
+
+/// Stateful parser of kmsg records. The oom killer reports a kill twice on recent
+/// kernels: `oom-kill:` with the cgroup of the task followed by `Killed process`.
+#[derive(Default)]
+struct Parser {
+    /// Pid of the last `oom-kill:` record
+    oom_kill: Option<Pid>,
+}
+
+impl Parser {
+    fn parse(&mut self, record: &str) -> Option<KernelMessage> {
+        // Record format: "<prio>,<seq>,<timestamp>,<flags>[,...];<message>\n"
+        // followed by optional continuation lines that start with a space
+        let (_, message) = record.split_once(';')?;
+        let message = message.lines().next()?.trim_end();
+
+        if let Some(oom) = message.strip_prefix("oom-kill:") {
+            let pid = field(oom, "pid=")?.parse().ok()?;
+            self.oom_kill = Some(pid);
+            return Some(KernelMessage {
+                kind: KernelMessageKind::Oom,
+                pid,
+                cgroup: field(oom, "task_memcg=").map(ToString::to_string),
+                message: message.to_string(),
+            });
+        }
+
+        if let Some((_, killed)) = message.split_once("Killed process ") {
+            let pid = killed.split_whitespace().next()?.parse().ok()?;
+            // Already reported by the preceding oom-kill record
+            if self.oom_kill.take() == Some(pid) {
+                return None;
+            }
+            return Some(KernelMessage {
+                kind: KernelMessageKind::Oom,
+                pid,
+                cgroup: None,
+                message: message.to_string(),
+            });
+        }
+
+        // Seccomp audit records have type 1326 (AUDIT_SECCOMP)
+        if message.contains("type=1326") {
+            let pid = field(message, " pid=")?.parse().ok()?;
+            let syscall = field(message, " syscall=").and_then(|s| s.parse().ok());
+            return Some(KernelMessage {
+                kind: KernelMessageKind::Seccomp { syscall },
+                pid,
+                cgroup: None,
+                message: message.to_string(),
+            });
+        }
+
+        // "<comm>[<pid>]: segfault at ..."
+        if let Some((comm, _)) = message.split_once("]: segfault at ") {
+            let (_, pid) = comm.rsplit_once('[')?;
+            return Some(KernelMessage {
+                kind: KernelMessageKind::Segfault,
+                pid: pid.parse().ok()?,
+                cgroup: None,
+                message: message.to_string(),
+            });
+        }
+
+        None
+    }
+}
+
+/// Value of `key` in a comma or whitespace separated list of key value pairs
+fn field<'a>(s: &'a str, key: &str) -> Option<&'a str> {
+    let start = s.find(key)? + key.len();
+    s[start..]
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::{KernelMessageKind, Parser};
+
+    #[test]
+    fn oom() {
+        let mut parser = Parser::default();
+        let message = parser
+            .parse("3,1234,5678,-;oom-kill:constraint=CONSTRAINT_MEMCG,nodemask=(null),cpuset=/,mems_allowed=0,oom_memcg=/northstar/hello,task_memcg=/northstar/hello,task=hello,pid=4242,uid=1000\n")
+            .unwrap();
+        assert_eq!(message.kind, KernelMessageKind::Oom);
+        assert_eq!(message.pid, 4242);
+        assert_eq!(message.cgroup.as_deref(), Some("/northstar/hello"));
+
+        // The same kill is not reported twice
+        assert!(parser
+            .parse("3,1235,5679,-;Memory cgroup out of memory: Killed process 4242 (hello) total-vm:1000kB\n")
+            .is_none());
+
+        // Older kernels only report the kill
+        let message = parser
+            .parse("3,1236,5680,-;Memory cgroup out of memory: Killed process 4343 (hello) total-vm:1000kB\n")
+            .unwrap();
+        assert_eq!(message.kind, KernelMessageKind::Oom);
+        assert_eq!(message.pid, 4343);
+        assert_eq!(message.cgroup, None);
+    }
+
+    #[test]
+    fn seccomp() {
+        let message = Parser::default()
+            .parse("5,99,100,-;audit: type=1326 audit(1650000000.123:42): auid=4294967295 uid=1000 gid=1000 ses=4294967295 pid=4242 comm=\"hello\" exe=\"/hello\" sig=31 arch=c000003e syscall=39 compat=0 ip=0x7f code=0x0\n")
+            .unwrap();
+        assert_eq!(
+            message.kind,
+            KernelMessageKind::Seccomp { syscall: Some(39) }
+        );
+        assert_eq!(message.pid, 4242);
+    }
+
+    #[test]
+    fn segfault() {
+        let message = Parser::default()
+            .parse("6,100,200,-;hello[4242]: segfault at 0 ip 000055 sp 00007ff error 6 in hello[55+1000]\n SUBSYSTEM=foo\n")
+            .unwrap();
+        assert_eq!(message.kind, KernelMessageKind::Segfault);
+        assert_eq!(message.pid, 4242);
+    }
+
+    #[test]
+    fn unrelated() {
+        assert!(Parser::default()
+            .parse("6,100,200,-;usb 1-1: new high-speed USB device number 2\n")
+            .is_none());
+        assert!(Parser::default().parse("garbage").is_none());
+    }
+}
@@ -1,13 +1,16 @@
-use crate::{api, api::model::Container, runtime::ipc::AsyncMessage};
+use crate::{
+    api,
+    api::model::{Container, SignalCause},
+    runtime::ipc::owned_fd::OwnedFd,
+};
 use async_stream::stream;
 use config::Config;
-use error::Error;
+use error::Context;
 use fmt::Debug;
 use futures::{
     future::{ready, Either},
     FutureExt, StreamExt,
 };
-use log::{debug, info};
 use nix::{
     libc::{EXIT_FAILURE, EXIT_SUCCESS},
     sys::{
@@ -20,40 +23,86 @@ use nix::{
 use serde::{Deserialize, Serialize};
 use state::State;
 use std::{
+    collections::HashSet,
     convert::TryFrom,
     fmt::{self},
     future::Future,
+    os::unix::{net::UnixStream as StdUnixStream, prelude::FromRawFd},
     path::Path,
+    sync::Arc,
 };
-use sync::mpsc;
 use tokio::{
     pin, select,
     sync::{self, broadcast, oneshot},
     task::{self, JoinHandle},
 };
 use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, info, warn};
 
 use self::fork::ForkerChannels;
 
+mod activation;
+mod bpf;
+mod browse;
+mod builder;
 mod cgroups;
+mod clock;
 mod console;
+/// Core dump capture
+pub mod core_dump;
+mod data;
+pub(crate) mod dbus;
 mod debug;
 mod error;
+mod events;
 mod fork;
+mod fuse;
+mod handle;
+mod handoff;
+mod installs;
 mod io;
 mod ipc;
 mod key;
+mod kmsg;
 mod mount;
+mod network;
+mod overlay;
+mod peers;
+mod pending;
+mod persist;
+pub(crate) mod pipe;
+mod policy;
+mod process;
+mod queue;
 mod repository;
+pub(crate) mod secret;
+pub(crate) mod shm;
+#[cfg(feature = "simulation")]
+mod simulation;
+mod snapshot;
+pub(crate) mod socket;
 mod state;
 mod stats;
+mod system;
 mod token;
 
 /// Runtime configuration
 pub mod config;
+/// Container environment parameters
+pub mod parameters;
+/// Start policies of embedders
+pub mod start_policy;
+
+pub use builder::Builder;
+pub use error::Error;
+pub use events::{Overflow, RuntimeEvent, Subscription};
+pub use handle::Handle;
+pub use handoff::Handoff;
+pub use kmsg::{KernelMessage, KernelMessageKind};
+
+use queue::{EventRx, EventTx};
 
-type EventTx = mpsc::Sender<Event>;
-type NotificationTx = broadcast::Sender<(Container, ContainerEvent)>;
+type NotificationTx = broadcast::Sender<api::model::Notification>;
 type RepositoryId = String;
 type ExitCode = i32;
 type Pid = u32;
@@ -75,46 +124,81 @@ enum Event {
     Shutdown,
     /// Container event
     Container(Container, ContainerEvent),
+    /// Kernel message related to a process
+    Kernel(kmsg::KernelMessage),
+    /// Repository finished loading
+    Repository(RepositoryId, Result<state::Repository, Error>),
+    /// Files in a watched repository changed
+    RepositoryChanged(RepositoryId),
+    /// Container start deferred by the start policy
+    Start(state::DeferredStart),
+    /// Containers to umount if they are still idle
+    UmountIdle(Vec<Container>),
+    /// Background mounts completed
+    Mounted(state::Mounts),
+    /// Stop timeout of a killed container process expired
+    StopTimeout(Container, Pid),
+    /// Audit for leftovers of exited containers
+    Reconcile,
+    /// Stop and hand over the started containers if possible
+    Handoff(oneshot::Sender<Result<(), Error>>),
 }
 
+/// Container event
 #[derive(Clone, Debug)]
-enum ContainerEvent {
+pub enum ContainerEvent {
     /// Container has been started
     Started,
+    /// The container could not be created e.g. because a mount of init failed
+    StartFailed(String),
     /// Container exited with status
     Exit(ExitStatus),
     /// Container is installed
     Installed,
     /// Container is uninstalled
     Uninstalled,
+    /// Container is paused
+    Paused,
+    /// Container is resumed
+    Resumed,
+    /// Persist directory of the container is wiped
+    DataReset,
     /// CGroup event
     CGroup(CGroupEvent),
+    /// Output line of the container forwarded to the console
+    Log(String),
+    /// Kernel message related to a process of the container
+    Kernel(kmsg::KernelMessage),
+    /// Core dump of a process of the container
+    CoreDump(api::model::CoreDump),
 }
 
 /// Events generated by cgroup controllers
 #[derive(Clone, Debug)]
-enum CGroupEvent {
+pub enum CGroupEvent {
+    /// Memory controller event
     Memory(MemoryEvent),
 }
 
+/// Memory controller event counters
 #[derive(Clone, Default, Debug)]
-struct MemoryEvent {
+pub struct MemoryEvent {
     /// The number of times the cgroup is reclaimed due to
     /// high memory pressure even though its usage is under
     /// the low boundary.  This usually indicates that the low
     /// boundary is over-committed.
-    low: Option<u64>,
+    pub low: Option<u64>,
     /// The number of times processes of the cgroup are
     /// throttled and routed to perform direct memory reclaim
     /// because the high memory boundary was exceeded.  For a
     /// cgroup whose memory usage is capped by the high limit
     /// rather than global memory pressure, this event's
     /// occurrences are expected.
-    high: Option<u64>,
+    pub high: Option<u64>,
     /// The number of times the cgroup's memory usage was
     /// about to go over the max boundary.  If direct reclaim
     /// fails to bring it down, the cgroup goes to OOM state.
-    max: Option<u64>,
+    pub max: Option<u64>,
     /// The number of time the cgroup's memory usage was
     /// reached the limit and allocation was about to fail.
     /// Depending on context result could be invocation of OOM
@@ -123,10 +207,10 @@ struct MemoryEvent {
     /// userspace as -ENOMEM or silently ignored in cases like
     /// disk readahead. For now OOM in memory cgroup kills
     /// tasks if shortage has happened inside page fault.
-    oom: Option<u64>,
+    pub oom: Option<u64>,
     /// The number of processes belonging to this cgroup
     /// killed by any kind of OOM killer.
-    oom_kill: Option<u64>,
+    pub oom_kill: Option<u64>,
 }
 
 /// Container exit status
@@ -134,13 +218,14 @@ struct MemoryEvent {
 pub enum ExitStatus {
     /// Process exited with exit code
     Exit(ExitCode),
-    /// Process was terminated by a signal
-    Signalled(u8),
+    /// Process was terminated by a signal. The forker reports every signal as
+    /// external. The runtime determines the cause on exit.
+    Signalled(u8, SignalCause),
 }
 
 impl From<Signal> for ExitStatus {
     fn from(signal: Signal) -> Self {
-        ExitStatus::Signalled(signal as u8)
+        ExitStatus::Signalled(signal as u8, SignalCause::External)
     }
 }
 
@@ -166,10 +251,21 @@ impl fmt::Display for ExitStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ExitStatus::Exit(code) => write!(f, "Exit({})", code),
-            ExitStatus::Signalled(signal) => match sys::signal::Signal::try_from(*signal as i32) {
-                Ok(signal) => write!(f, "Signalled({})", signal),
-                Err(_) => write!(f, "Signalled({})", signal),
-            },
+            ExitStatus::Signalled(signal, cause) => {
+                match sys::signal::Signal::try_from(*signal as i32) {
+                    Ok(signal) => write!(f, "Signalled({}", signal)?,
+                    Err(_) => write!(f, "Signalled({}", signal)?,
+                }
+                match cause {
+                    SignalCause::Oom => write!(f, ", oom)"),
+                    SignalCause::Seccomp {
+                        syscall: Some(syscall),
+                    } => write!(f, ", seccomp syscall {})", syscall),
+                    SignalCause::Seccomp { syscall: None } => write!(f, ", seccomp)"),
+                    SignalCause::Runtime => write!(f, ", runtime)"),
+                    SignalCause::External => write!(f, ")"),
+                }
+            }
         }
     }
 }
@@ -181,62 +277,133 @@ pub enum Runtime {
     Created {
         /// Runtime configuration
         config: Config,
+        /// Parameter provider
+        provider: Arc<dyn parameters::Provider>,
+        /// Start policy
+        start_policy: Arc<dyn start_policy::StartPolicy>,
+        /// Serve the configured consoles
+        console: bool,
         /// Forker pid
         forker_pid: Pid,
         /// Forker channles
         forker_channels: ForkerChannels,
+        /// State handed over by a previous instance
+        handoff: Option<Handoff>,
     },
     /// The runtime is started.
     Running {
         /// Drop guard to stop the runtime
         guard: DropGuard,
         /// Runtime task
-        task: JoinHandle<Result<(), Error>>,
+        task: JoinHandle<Result<Option<Handoff>, Error>>,
+        /// Handle for requests and notifications
+        handle: Handle,
     },
 }
 
 impl Runtime {
+    /// Create a builder for a runtime with configuration `config`
+    pub fn builder(config: Config) -> Builder {
+        Builder::new(config)
+    }
+
     /// Create new runtime instance
     pub fn new(config: Config) -> Result<Runtime, Error> {
-        let (forker_pid, forker_channels) = fork::start()?;
-        Ok(Runtime::Created {
-            config,
-            forker_pid,
-            forker_channels,
-        })
+        Runtime::builder(config).build()
+    }
+
+    /// Create new runtime instance that resolves container environment parameters
+    /// with `provider` instead of the `parameters` section of the configuration
+    pub fn new_with_provider(
+        config: Config,
+        provider: Arc<dyn parameters::Provider>,
+    ) -> Result<Runtime, Error> {
+        Runtime::builder(config).provider(provider).build()
     }
 
     /// Start runtime with configuration `config`
     pub async fn start(self) -> Result<Runtime, Error> {
-        let (config, forker_pid, forker_channels) = if let Runtime::Created {
-            config,
-            forker_pid,
-            forker_channels,
-        } = self
-        {
-            (config, forker_pid, forker_channels)
-        } else {
-            panic!("Runtime::start called on a running runtime");
-        };
+        let (config, provider, start_policy, console, forker_pid, forker_channels, handoff) =
+            if let Runtime::Created {
+                config,
+                provider,
+                start_policy,
+                console,
+                forker_pid,
+                forker_channels,
+                handoff,
+            } = self
+            {
+                (
+                    config,
+                    provider,
+                    start_policy,
+                    console,
+                    forker_pid,
+                    forker_channels,
+                    handoff,
+                )
+            } else {
+                panic!("Runtime::start called on a running runtime");
+            };
 
         config.check().await?;
 
         let token = CancellationToken::new();
         let guard = token.clone().drop_guard();
 
+        let (event_tx, event_rx) = queue::channel(
+            config.event_buffer_size,
+            config.stats_buffer_size,
+            config.log_buffer_size,
+        );
+        let (notification_tx, _) = sync::broadcast::channel(config.notification_buffer_size);
+        let installs = installs::Installs::new(notification_tx.clone());
+        let handle = Handle {
+            event_tx,
+            notification_tx,
+            installs,
+            subscribers: events::Subscribers::default(),
+        };
+
         // Start a task that drives the main loop and wait for shutdown results
-        let task = task::spawn(run(config, token, forker_pid, forker_channels));
+        let task = task::spawn(run(
+            config,
+            provider,
+            start_policy,
+            console,
+            token,
+            forker_pid,
+            forker_channels,
+            handoff,
+            handle.clone(),
+            event_rx,
+        ));
+
+        Ok(Runtime::Running {
+            guard,
+            task,
+            handle,
+        })
+    }
 
-        Ok(Runtime::Running { guard, task })
+    /// Handle for requests and notifications of the started runtime
+    pub fn handle(&self) -> Handle {
+        match self {
+            Runtime::Running { handle, .. } => handle.clone(),
+            Runtime::Created { .. } => {
+                panic!("Runtime::handle called on a runtime that is not started")
+            }
+        }
     }
 
     /// Stop the runtime and wait for the termination
     pub fn shutdown(self) -> impl Future<Output = Result<(), Error>> {
-        if let Runtime::Running { guard, task } = self {
+        if let Runtime::Running { guard, task, .. } = self {
             drop(guard);
             Either::Left({
                 task.then(|n| match n {
-                    Ok(n) => ready(n),
+                    Ok(n) => ready(n.map(drop)),
                     Err(_) => ready(Ok(())),
                 })
             })
@@ -249,21 +416,55 @@ impl Runtime {
     pub async fn stopped(&mut self) -> Result<(), Error> {
         match self {
             Runtime::Running { ref mut task, .. } => match task.await {
-                Ok(r) => r,
+                Ok(r) => r.map(drop),
                 Err(_) => Ok(()),
             },
             Runtime::Created { .. } => panic!("Stopped called on a stopped runtime"),
         }
     }
+
+    /// Stop the runtime without stopping the started containers and return their
+    /// supervision for a new runtime binary that is started with `Handoff::exec`. The
+    /// handoff is refused with `Error::Handoff` while operations are in progress or if
+    /// a started container uses a facility that cannot be handed over. The runtime
+    /// keeps running in this case. Any other error means that the runtime stopped.
+    pub async fn handoff(&mut self) -> Result<Handoff, Error> {
+        match self {
+            Runtime::Running { task, handle, .. } => {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                handle
+                    .event_tx
+                    .send(Event::Handoff(reply_tx))
+                    .await
+                    .map_err(|_| Error::NotRunning)?;
+                reply_rx.await.map_err(|_| Error::NotRunning)??;
+                match task.await {
+                    Ok(Ok(Some(handoff))) => Ok(handoff),
+                    Ok(Err(e)) => Err(e),
+                    Ok(Ok(None)) | Err(_) => Err(Error::NotRunning),
+                }
+            }
+            Runtime::Created { .. } => {
+                panic!("Runtime::handoff called on a runtime that is not started")
+            }
+        }
+    }
 }
 
 /// Main loop
+#[allow(clippy::too_many_arguments)]
 async fn run(
     config: Config,
+    provider: Arc<dyn parameters::Provider>,
+    start_policy: Arc<dyn start_policy::StartPolicy>,
+    console: bool,
     token: CancellationToken,
     forker_pid: Pid,
     forker_channels: ForkerChannels,
-) -> Result<(), Error> {
+    handoff: Option<Handoff>,
+    handle: Handle,
+    mut event_rx: EventRx,
+) -> Result<Option<Handoff>, Error> {
     // Setup root cgroup(s)
     let cgroup = Path::new(config.cgroup.as_str()).to_owned();
     cgroups::init(&cgroup).await?;
@@ -277,7 +478,7 @@ async fn run(
                     break ExitStatus::Exit(status);
                 }
                 Ok(WaitStatus::Signaled(_pid, status, _)) => {
-                    break ExitStatus::Signalled(status as u8);
+                    break status.into();
                 }
                 Ok(WaitStatus::Continued(_)) | Ok(WaitStatus::Stopped(_, _)) => (),
                 Err(nix::Error::EINTR) => (),
@@ -287,94 +488,267 @@ async fn run(
     });
 
     // Northstar runs in a event loop
-    let (event_tx, mut event_rx) = mpsc::channel::<Event>(config.event_buffer_size);
-    let (notification_tx, _) = sync::broadcast::channel(config.notification_buffer_size);
+    let Handle {
+        event_tx,
+        notification_tx,
+        installs,
+        subscribers,
+    } = handle;
+    let pending = pending::Pending::default();
+
+    let ForkerChannels {
+        stream,
+        notifications,
+    } = forker_channels;
+    let forker = fork::Forker::new(forker_pid, stream, config.start_timeout);
+    let mut notifications = fork::Notifications::new(forker_pid, notifications);
+
+    // Take over the forkers, console listeners and core dump settings of the previous
+    // instance. The forkers are kept until their containers exited.
+    let mut inherited = handoff.map(|handoff| handoff.state);
+    let mut previous = Vec::new();
+    let mut listeners = Vec::new();
+    let mut inherited_core_pattern = None;
+    if let Some(inherited) = inherited.as_mut() {
+        info!("Taking over from the previous runtime instance");
+        for forker in inherited.forkers.drain(..) {
+            let stream = unsafe { StdUnixStream::from_raw_fd(forker.stream) };
+            let stream_notifications = unsafe { StdUnixStream::from_raw_fd(forker.notifications) };
+            notifications.adopt(forker.pid, stream_notifications, &forker.buffer)?;
+            previous.push((forker.pid, stream));
+        }
+        for (url, fd) in inherited.consoles.drain(..) {
+            listeners.push((url, unsafe { OwnedFd::from_raw_fd(fd) }));
+        }
+        inherited_core_pattern = inherited.core_pattern.take();
+    }
 
     // Initialize the console if configured
-    let console = if !config.consoles.is_empty() {
-        let mut console = console::Console::new(event_tx.clone(), notification_tx.clone());
+    let mut console = if console && !config.consoles.is_empty() {
+        let mut console = console::Console::new(
+            event_tx.clone(),
+            notification_tx.clone(),
+            installs.clone(),
+            pending.clone(),
+        );
         for (url, configuration) in config.consoles.iter() {
-            console
-                .listen(url, configuration, config.token_validity)
-                .await
-                .map_err(Error::Console)?;
+            // Serve the listener of the previous instance in order to not miss connections
+            match listeners.iter().position(|(listener, _)| listener == url) {
+                Some(index) => {
+                    let (_, fd) = listeners.swap_remove(index);
+                    console.resume(url, configuration, config.token_validity(), fd)
+                }
+                None => {
+                    console
+                        .listen(url, configuration, config.token_validity())
+                        .await
+                }
+            }
+            .map_err(Error::Console)?;
         }
         Some(console)
     } else {
         None
     };
+    drop(listeners);
 
-    // Convert stream and stream_fd into Tokio UnixStream
-    let (forker, mut exit_notifications) = {
-        let ForkerChannels {
-            stream,
-            notifications,
-        } = forker_channels;
-
-        let forker = fork::Forker::new(stream);
-        let exit_notifications: AsyncMessage<_> = notifications
-            .try_into()
-            .expect("failed to convert exit notification handle");
-        (forker, exit_notifications)
+    // Forward kernel messages of container processes if configured
+    let kmsg = if config.kernel_messages {
+        let task =
+            kmsg::spawn(event_tx.clone(), token.clone()).context("failed to open kernel log")?;
+        Some(task)
+    } else {
+        None
     };
 
-    // Merge the exit notification from the forker process with other events into the main loop channel
-    let event_rx = stream! {
-        loop {
-            select! {
-                Some(event) = event_rx.recv() => yield event,
-                Ok(Some(fork::Notification::Exit { container, exit_status })) = exit_notifications.recv() => {
-                    let event = ContainerEvent::Exit(exit_status);
-                    yield Event::Container(container, event);
-                }
-                else => unimplemented!(),
-            }
+    // Register the core dump handler if configured. The settings replaced by the first
+    // instance are kept for the restore.
+    let core_pattern = match config.core_dumps.as_ref() {
+        Some(core_dumps) if core_dumps.core => {
+            core_dump::CorePattern::install(core_dumps, config.cgroup.as_str()).await?
         }
+        _ => None,
+    };
+    let core_pattern = match (inherited_core_pattern, core_pattern) {
+        (Some(inherited), Some(_)) => Some(inherited),
+        (Some(inherited), None) => {
+            inherited.restore().await?;
+            None
+        }
+        (None, core_pattern) => core_pattern,
     };
-    pin!(event_rx);
 
-    let mut state = State::new(config, event_tx.clone(), notification_tx, forker).await?;
+    let mut state = State::new(
+        config,
+        provider,
+        start_policy,
+        event_tx.clone(),
+        notification_tx,
+        installs,
+        pending,
+        subscribers,
+        forker,
+        inherited,
+    )
+    .await?;
 
     info!("Runtime up and running");
 
-    // Enter main loop
-    loop {
-        tokio::select! {
-            // External shutdown event via the token
-            _ = token.cancelled() => event_tx.send(Event::Shutdown).await.expect("failed to send shutdown event"),
-            // Process events
-            event = event_rx.next() => {
-                if let Err(e) = match event.expect("internal error") {
-                    // Process console events enqueued by console::Console
-                    Event::Console(request, response) => state.on_request(request, response).await,
-                    // The runtime os commanded to shut down and exit.
-                    Event::Shutdown => {
-                        debug!("Shutting down Northstar runtime");
-                        if let Some(console) = console {
-                            debug!("Shutting down console");
-                            console.shutdown().await.map_err(Error::Console)?;
+    let handoff = {
+        // Merge the exit notification from the forker processes with other events into the main loop channel
+        let notifications = &mut notifications;
+        let event_rx = stream! {
+            loop {
+                select! {
+                    Some(event) = event_rx.recv() => yield event,
+                    fork::Notification::Exit { container, exit_status } = notifications.recv() => {
+                        let event = ContainerEvent::Exit(exit_status);
+                        yield Event::Container(container, event);
+                    }
+                }
+            }
+        };
+        pin!(event_rx);
+
+        // Enter main loop
+        loop {
+            tokio::select! {
+                // External shutdown event via the token
+                _ = token.cancelled() => event_tx.send(Event::Shutdown).await.expect("failed to send shutdown event"),
+                // Process events
+                event = event_rx.next() => {
+                    if let Err(e) = match event.expect("internal error") {
+                        // Process console events enqueued by console::Console
+                        Event::Console(request, response) => state.on_request(request, response).await,
+                        // The runtime os commanded to shut down and exit.
+                        Event::Shutdown => {
+                            debug!("Shutting down Northstar runtime");
+                            // Keep the console up while the containers are stopped in order
+                            // to report the shutdown progress to clients
+                            let result = state.shutdown(event_rx).await;
+                            if let Some(console) = console.take() {
+                                debug!("Shutting down console");
+                                console.shutdown().await.map_err(Error::Console)?;
+                            }
+                            break result.map(|_| None);
                         }
-                        break state.shutdown(event_rx).await;
+                        // Hand over the started containers to a new runtime binary
+                        Event::Handoff(reply) => match state.check_handoff() {
+                            Ok(()) => {
+                                reply.send(Ok(())).ok();
+                                break Ok(Some(state));
+                            }
+                            Err(e) => {
+                                warn!("Refusing handoff: {}", e);
+                                reply.send(Err(e)).ok();
+                                Ok(())
+                            }
+                        },
+                        // Container event
+                        Event::Container(container, event) => state.on_event(&container, &event, false).await,
+                        // Kernel message
+                        Event::Kernel(message) => state.on_kernel_message(message).await,
+                        // Repository loaded in the background
+                        Event::Repository(id, repository) => state.on_repository(id, repository).await,
+                        // Npks copied into a watched repository
+                        Event::RepositoryChanged(id) => state.on_repository_changed(&id).await,
+                        // Deferred container start
+                        Event::Start(start) => state.on_start(start).await,
+                        // Idle containers mounted for a start
+                        Event::UmountIdle(containers) => state.on_umount_idle(&containers).await,
+                        // Containers mounted in the background
+                        Event::Mounted(mounts) => state.on_mounted(mounts).await,
+                        // Killed container that might not have exited
+                        Event::StopTimeout(container, pid) => state.on_stop_timeout(&container, pid).await,
+                        // Periodic cleanup of leftovers
+                        Event::Reconcile => state.on_reconcile().await,
+                    } {
+                        break Err(e);
                     }
-                    // Container event
-                    Event::Container(container, event) => state.on_event(&container, &event, false).await,
-                } {
-                    break Err(e);
                 }
+                exit_status = &mut join_forker => panic!("Forker exited with {:?}", exit_status),
+            }
+        }?
+    };
+
+    // Stop reading kernel messages
+    if let Some(kmsg) = kmsg {
+        kmsg.abort();
+    }
+
+    if let Some(state) = handoff {
+        debug!("Handing over the started containers");
+        let mut handoff = Handoff::new();
+        if let Some(console) = console {
+            for (url, fd) in console.handoff().await.map_err(Error::Console)? {
+                let fd = handoff.pass(fd);
+                handoff.state.consoles.push((url, fd));
+            }
+        }
+
+        let forker = state.handoff(&mut handoff).await?;
+        previous.push(forker.handoff()?);
+
+        // Pass the forkers of the started containers and terminate the others
+        let used = handoff
+            .state
+            .containers
+            .iter()
+            .filter_map(|entry| entry.process.as_ref())
+            .map(|process| process.forker)
+            .collect::<HashSet<_>>();
+        let mut notifications = notifications.handoff()?;
+        let mut forker_passed = false;
+        for (pid, stream) in previous {
+            let index = notifications.iter().position(|(p, _, _)| *p == pid);
+            match index.filter(|_| used.contains(&pid)) {
+                Some(index) => {
+                    let (_, stream_notifications, buffer) = notifications.swap_remove(index);
+                    let forker = handoff::Forker {
+                        pid,
+                        stream: handoff.pass(stream),
+                        notifications: handoff.pass(stream_notifications),
+                        buffer: buffer.to_vec(),
+                    };
+                    handoff.state.forkers.push(forker);
+                    forker_passed |= pid == forker_pid;
+                }
+                // The forker exits once its stream is closed
+                None if pid == forker_pid => drop(stream),
+                None => fork::reap(pid).await,
             }
-            exit_status = &mut join_forker => panic!("Forker exited with {:?}", exit_status),
         }
-    }?;
+        if !forker_passed {
+            debug!("Joining forker with pid {}", forker_pid);
+            join_forker.await.expect("failed to join forker");
+        }
+
+        // The core dump settings are restored by the last instance
+        handoff.state.core_pattern = core_pattern;
+
+        debug!("Handoff complete");
+        return Ok(Some(handoff));
+    }
 
     // Terminate forker process
     debug!("Joining forker with pid {}", forker_pid);
     // signal::kill(forker_pid, Some(SIGTERM)).ok();
     join_forker.await.expect("failed to join forker");
 
+    // Terminate the forkers of the previous instances
+    for (pid, _) in previous {
+        fork::reap(pid).await;
+    }
+
+    // Restore the previous core dump handler
+    if let Some(core_pattern) = core_pattern {
+        core_pattern.restore().await?;
+    }
+
     // Shutdown cgroups
     cgroups::shutdown(&cgroup).await?;
 
     debug!("Shutdown complete");
 
-    Ok(())
+    Ok(None)
 }
@@ -1,28 +1,51 @@
-use super::{key::PublicKey, repository::Npk};
+#[cfg(feature = "simulation")]
+use super::simulation;
+use super::{config::MountBackend, fuse, key::PublicKey, repository::Npk};
 use crate::{
     common::version::Version,
-    npk::{dm_verity::VerityHeader, npk::Hashes},
+    npk::{
+        dm_verity::{self, VerityHeader},
+        npk::{CompressionAlgorithm, Hashes},
+    },
 };
 use devicemapper::{DevId, DmError, DmName, DmOptions};
-use futures::{Future, FutureExt};
+use futures::{
+    future::{ready, Either},
+    Future, FutureExt,
+};
 use humantime::format_duration;
-use log::{debug, warn};
+use lazy_static::lazy_static;
 use loopdev::LoopControl;
+use nix::errno::Errno;
+use serde::{Deserialize, Serialize};
 use std::{
-    io,
-    os::unix::{io::AsRawFd, prelude::RawFd},
+    collections::{HashMap, HashSet},
+    fs, io,
+    os::unix::{fs::MetadataExt, io::AsRawFd, prelude::RawFd},
     path::{Path, PathBuf},
     str::Utf8Error,
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 use thiserror::Error;
 use tokio::{task, time};
+use tracing::{debug, info, warn};
 
 use crate::seccomp::Selinux;
 pub use nix::mount::MsFlags as MountFlags;
 
 const FS_TYPE: &str = "squashfs";
 
+/// Squashfs superblock magic ("hsqs")
+const SQUASHFS_MAGIC: &[u8; 4] = b"hsqs";
+/// Offset of the compressor id in the squashfs superblock
+const SQUASHFS_COMPRESSION_OFFSET: usize = 20;
+
+lazy_static! {
+    /// Squashfs decompressors of the running kernel. `None` if the kernel
+    /// configuration is not available.
+    static ref KERNEL_COMPRESSION: Option<Vec<CompressionAlgorithm>> = probe_kernel_compression();
+}
+
 #[cfg(not(target_os = "android"))]
 const DEVICE_MAPPER_DEV: &str = "/dev/dm-";
 #[cfg(target_os = "android")]
@@ -44,6 +67,15 @@ pub enum Error {
     Utf8Conversion(Utf8Error),
     #[error("Timeout error {0}")]
     Timeout(String),
+    #[error("Squashfs compression {0} is not supported by the kernel")]
+    UnsupportedCompression(CompressionAlgorithm),
+    #[error("FUSE error: {0}")]
+    Fuse(String),
+    #[error("Verity error: {0}")]
+    Verity(String),
+    #[cfg(feature = "simulation")]
+    #[error("Simulation error: {0}")]
+    Simulation(String),
 }
 
 pub(super) struct MountControl {
@@ -51,10 +83,23 @@ pub(super) struct MountControl {
     dm_timeout: time::Duration,
     /// Timeout for lo device setup
     lo_timeout: time::Duration,
-    /// Device mapper handle
-    dm: Arc<devicemapper::DM>,
-    /// Loop device control
-    lc: Arc<loopdev::LoopControl>,
+    /// Mount backend
+    backend: Backend,
+    /// Root hashes verified by the FUSE and simulation backend
+    verified: Option<Arc<VerifiedCache>>,
+}
+
+enum Backend {
+    Kernel {
+        /// Device mapper handle
+        dm: Arc<devicemapper::DM>,
+        /// Loop device control
+        lc: Arc<loopdev::LoopControl>,
+    },
+    Fuse,
+    /// Images extracted into directories on a Linux development host
+    #[cfg(feature = "simulation")]
+    Simulation,
 }
 
 impl std::fmt::Debug for MountControl {
@@ -67,20 +112,46 @@ impl MountControl {
     pub(super) async fn new(
         dm_timeout: time::Duration,
         lo_timeout: time::Duration,
+        backend: MountBackend,
+        verity_cache: Option<&Path>,
     ) -> Result<MountControl, Error> {
-        debug!("Opening loop control");
-        let lc = LoopControl::open()?;
-        debug!("Opening device mapper control");
-        let dm = devicemapper::DM::new()?;
+        let backend = match backend {
+            MountBackend::Kernel => {
+                debug!("Opening loop control");
+                let lc = LoopControl::open()?;
+                debug!("Opening device mapper control");
+                let dm = devicemapper::DM::new()?;
+
+                let dm_version = dm.version().map(Version::from)?;
+                debug!("Device mapper version is {}", dm_version);
+
+                Backend::Kernel {
+                    lc: Arc::new(lc),
+                    dm: Arc::new(dm),
+                }
+            }
+            MountBackend::Fuse => {
+                debug!("Using FUSE mount backend");
+                Backend::Fuse
+            }
+            #[cfg(feature = "simulation")]
+            MountBackend::Simulation => {
+                warn!("Using simulation mount backend. Containers are not isolated");
+                Backend::Simulation
+            }
+        };
 
-        let dm_version = dm.version().map(Version::from)?;
-        debug!("Device mapper version is {}", dm_version);
+        // The kernel backend verifies the images on access with dm-verity
+        let verified = match (&backend, verity_cache) {
+            (Backend::Kernel { .. }, _) | (_, None) => None,
+            (_, Some(path)) => Some(Arc::new(VerifiedCache::load(path).await)),
+        };
 
         Ok(MountControl {
             dm_timeout,
             lo_timeout,
-            lc: Arc::new(lc),
-            dm: Arc::new(dm),
+            backend,
+            verified,
         })
     }
 
@@ -91,8 +162,18 @@ impl MountControl {
         target: &Path,
         key: Option<&PublicKey>,
     ) -> impl Future<Output = Result<(), Error>> {
-        let dm = self.dm.clone();
-        let lc = self.lc.clone();
+        let (dm, lc) = match &self.backend {
+            Backend::Kernel { dm, lc } => (dm.clone(), lc.clone()),
+            Backend::Fuse => {
+                let verified = self.verified.clone();
+                return Either::Right(mount_fuse(npk, target, key, verified).boxed());
+            }
+            #[cfg(feature = "simulation")]
+            Backend::Simulation => {
+                let verified = self.verified.clone();
+                return Either::Right(mount_simulation(npk, target, key, verified).boxed());
+            }
+        };
         let key = key.cloned();
         let target = target.to_owned();
         let fd = npk.as_raw_fd();
@@ -106,54 +187,74 @@ impl MountControl {
         let dm_timeout = self.dm_timeout;
         let lo_timeout = self.lo_timeout;
 
-        task::spawn_blocking(move || {
-            let start = time::Instant::now();
-
-            debug!("Mounting {}:{}", name, version);
-            let device = mount(
-                dm,
-                lc,
-                fd,
-                fsimg_offset,
-                fsimg_size,
-                &version,
-                verity_header,
-                selinux,
-                hashes,
-                &target,
-                key.is_some(),
-                dm_timeout,
-                lo_timeout,
-            )?;
+        Either::Left(
+            task::spawn_blocking(move || {
+                let start = time::Instant::now();
+
+                debug!("Mounting {}:{}", name, version);
+                let device = mount(
+                    dm,
+                    lc,
+                    fd,
+                    fsimg_offset,
+                    fsimg_size,
+                    &version,
+                    verity_header,
+                    selinux,
+                    hashes,
+                    &target,
+                    key.is_some(),
+                    dm_timeout,
+                    lo_timeout,
+                )?;
 
-            let duration = start.elapsed();
-            debug!(
-                "Finished mount of {}:{} in {}",
-                name,
-                version,
-                format_duration(duration)
-            );
+                let duration = start.elapsed();
+                debug!(
+                    "Finished mount of {}:{} in {}",
+                    name,
+                    version,
+                    format_duration(duration)
+                );
 
-            Ok(device)
-        })
-        .map(|r| match r {
-            Ok(r) => r,
-            Err(e) => panic!("task error: {}", e),
-        })
+                Ok(device)
+            })
+            .map(|r| match r {
+                Ok(r) => r,
+                Err(e) => panic!("task error: {}", e),
+            }),
+        )
     }
 
     /// Umount target
-    pub(super) fn umount(target: &Path) -> impl Future<Output = Result<(), Error>> {
+    pub(super) fn umount(&self, target: &Path) -> impl Future<Output = Result<(), Error>> {
         let target = target.to_owned();
+        let fuse = matches!(self.backend, Backend::Fuse);
+        #[cfg(feature = "simulation")]
+        let simulation = matches!(self.backend, Backend::Simulation);
 
-        task::spawn_blocking(move || {
+        async move {
             let start = time::Instant::now();
 
+            // Extracted images are plain directories
+            #[cfg(feature = "simulation")]
+            if simulation {
+                debug!("Removing extracted image {}", target.display());
+                return simulation::remove(&target).await;
+            }
+
             debug!("Unmounting {}", target.display());
-            nix::mount::umount(&target)?;
+            if fuse {
+                fuse::umount(&target).await?;
+            } else {
+                let target = target.clone();
+                task::spawn_blocking(move || nix::mount::umount(&target))
+                    .await
+                    .expect("task error")?;
+            }
 
             debug!("Removing mountpoint {}", target.display());
-            std::fs::remove_dir(&target)
+            tokio::fs::remove_dir(&target)
+                .await
                 .map_err(|e| Error::Io(format!("failed to remove {}", target.display()), e))?;
 
             let duration = start.elapsed();
@@ -164,11 +265,293 @@ impl MountControl {
             );
 
             Ok(())
-        })
-        .map(|r| match r {
-            Ok(r) => r,
-            Err(e) => panic!("Task error: {}", e),
-        })
+        }
+    }
+
+    /// Lazily detach target and remove the mount point. The file system is unmounted
+    /// by the kernel once it is not busy anymore.
+    pub(super) fn detach(&self, target: &Path) -> impl Future<Output = Result<(), Error>> {
+        let target = target.to_owned();
+
+        async move {
+            debug!("Detaching {}", target.display());
+            let path = target.clone();
+            task::spawn_blocking(move || {
+                nix::mount::umount2(&path, nix::mount::MntFlags::MNT_DETACH)
+            })
+            .await
+            .expect("task error")?;
+
+            debug!("Removing mountpoint {}", target.display());
+            tokio::fs::remove_dir(&target)
+                .await
+                .map_err(|e| Error::Io(format!("failed to remove {}", target.display()), e))
+        }
+    }
+
+    /// Verity devices and loop devices of npks that are neither mounted nor used by
+    /// another device. Verity devices are identified by their device mapper name e.g.
+    /// "northstar-xyz" and loop devices by their block device name e.g. "loop4".
+    pub(super) fn unused_devices(&self) -> impl Future<Output = Result<Vec<String>, Error>> {
+        match &self.backend {
+            Backend::Kernel { .. } => {
+                Either::Left(task::spawn_blocking(unused_devices).map(|r| r.expect("task error")))
+            }
+            _ => Either::Right(ready(Ok(Vec::new()))),
+        }
+    }
+
+    /// Remove the verity devices and detach the loop devices in `devices` e.g. after a
+    /// crash between the device setup and the mount. Returns the released devices.
+    pub(super) fn release_devices(
+        &self,
+        devices: Vec<String>,
+    ) -> impl Future<Output = Vec<String>> {
+        let dm = match &self.backend {
+            Backend::Kernel { dm, .. } => dm.clone(),
+            _ => return Either::Right(ready(Vec::new())),
+        };
+
+        Either::Left(
+            task::spawn_blocking(move || release_devices(&dm, devices))
+                .map(|r| r.expect("task error")),
+        )
+    }
+}
+
+/// Mount `npk` on `target` with squashfuse. There is no verity device in userspace:
+/// The root hash of the image is checked against the signed hashes before the image
+/// is mounted.
+fn mount_fuse(
+    npk: &Npk,
+    target: &Path,
+    key: Option<&PublicKey>,
+    verified: Option<Arc<VerifiedCache>>,
+) -> impl Future<Output = Result<(), Error>> {
+    let verify = key.is_some();
+    let target = target.to_owned();
+    // The npk is opened by the runtime only. Let squashfuse open it via procfs.
+    let image = PathBuf::from(format!(
+        "/proc/{}/fd/{}",
+        std::process::id(),
+        npk.as_raw_fd()
+    ));
+    let fsimg_offset = npk.fsimg_offset();
+    let name = npk.manifest().name.clone();
+    let version = npk.manifest().version.clone();
+    let verity_header = npk.verity_header().cloned();
+    let hashes = npk.hashes().cloned();
+
+    async move {
+        let start = time::Instant::now();
+        debug!("Mounting {}:{} with squashfuse", name, version);
+
+        if verify {
+            let image = image.clone();
+            task::spawn_blocking(move || {
+                verify_root_hash(
+                    &image,
+                    fsimg_offset,
+                    verity_header,
+                    hashes,
+                    verified.as_deref(),
+                )
+            })
+            .await
+            .expect("task error")?;
+        }
+
+        if !target.exists() {
+            debug!("Creating mount point {}", target.display());
+            tokio::fs::create_dir_all(&target).await.map_err(|e| {
+                Error::Io(
+                    format!("failed to create directory {}", target.display()),
+                    e,
+                )
+            })?;
+        }
+
+        fuse::squashfs(&image, fsimg_offset, &target).await?;
+
+        debug!(
+            "Finished mount of {}:{} in {}",
+            name,
+            version,
+            format_duration(start.elapsed())
+        );
+        Ok(())
+    }
+}
+
+/// Extract `npk` into `target`. Like with the FUSE backend the root hash of the image
+/// is checked against the signed hashes before the image is extracted.
+#[cfg(feature = "simulation")]
+fn mount_simulation(
+    npk: &Npk,
+    target: &Path,
+    key: Option<&PublicKey>,
+    verified: Option<Arc<VerifiedCache>>,
+) -> impl Future<Output = Result<(), Error>> {
+    let verify = key.is_some();
+    let target = target.to_owned();
+    let image = PathBuf::from(format!(
+        "/proc/{}/fd/{}",
+        std::process::id(),
+        npk.as_raw_fd()
+    ));
+    let fsimg_offset = npk.fsimg_offset();
+    let name = npk.manifest().name.clone();
+    let version = npk.manifest().version.clone();
+    let verity_header = npk.verity_header().cloned();
+    let hashes = npk.hashes().cloned();
+
+    async move {
+        let start = time::Instant::now();
+        debug!("Extracting {}:{}", name, version);
+
+        if verify {
+            let image = image.clone();
+            task::spawn_blocking(move || {
+                verify_root_hash(
+                    &image,
+                    fsimg_offset,
+                    verity_header,
+                    hashes,
+                    verified.as_deref(),
+                )
+            })
+            .await
+            .expect("task error")?;
+        }
+
+        simulation::extract(&image, fsimg_offset, &target).await?;
+
+        debug!(
+            "Finished extraction of {}:{} in {}",
+            name,
+            version,
+            format_duration(start.elapsed())
+        );
+        Ok(())
+    }
+}
+
+/// Calculate the verity root hash of the fs image and compare it with the signed value.
+/// Images found in `verified` are not read again.
+fn verify_root_hash(
+    image: &Path,
+    fsimg_offset: u64,
+    verity_header: Option<VerityHeader>,
+    hashes: Option<Hashes>,
+    verified: Option<&VerifiedCache>,
+) -> Result<(), Error> {
+    let (header, hashes) = match (verity_header, hashes) {
+        (Some(header), Some(hashes)) => (header, hashes),
+        _ => return Err(Error::Npk("Missing verity information in NPK")),
+    };
+    let file = std::fs::File::open(image)
+        .map_err(|e| Error::Io(format!("failed to open {}", image.display()), e))?;
+
+    let entry = match verified {
+        Some(verified) => {
+            let metadata = file
+                .metadata()
+                .map_err(|e| Error::Io(format!("failed to stat {}", image.display()), e))?;
+            let entry = Verified::new(&metadata, fsimg_offset);
+            if verified.contains(&hashes.fs_verity_hash, &entry) {
+                debug!("Root hash {} is verified", hashes.fs_verity_hash);
+                return Ok(());
+            }
+            Some(entry)
+        }
+        None => None,
+    };
+
+    let salt = &header.salt[..(header.salt_size as usize)];
+    let root_hash = dm_verity::root_hash(&file, fsimg_offset, hashes.fs_verity_offset, salt)
+        .map_err(|e| Error::Verity(e.to_string()))?;
+    if hex::encode(root_hash) != hashes.fs_verity_hash {
+        return Err(Error::Verity("root hash mismatch".into()));
+    }
+
+    if let (Some(verified), Some(entry)) = (verified, entry) {
+        verified.insert(hashes.fs_verity_hash, entry);
+    }
+    Ok(())
+}
+
+/// Root hashes of fs images that are verified in userspace. The cache is stored in a
+/// file and survives runtime restarts. Entries are keyed by the signed root hash and
+/// are valid as long as the npk file is not modified.
+#[derive(Debug)]
+struct VerifiedCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, Verified>>,
+}
+
+/// File attributes of a verified npk used to detect modifications
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Verified {
+    device: u64,
+    inode: u64,
+    size: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+    fsimg_offset: u64,
+}
+
+impl Verified {
+    fn new(metadata: &fs::Metadata, fsimg_offset: u64) -> Verified {
+        Verified {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.size(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            fsimg_offset,
+        }
+    }
+}
+
+impl VerifiedCache {
+    /// Load the cache from `path`. An invalid cache is discarded.
+    async fn load(path: &Path) -> VerifiedCache {
+        let entries = match tokio::fs::read(path).await {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                warn!("Discarding invalid verity cache {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        VerifiedCache {
+            path: path.to_owned(),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn contains(&self, root_hash: &str, entry: &Verified) -> bool {
+        let entries = self.entries.lock().expect("failed to lock verity cache");
+        entries.get(root_hash) == Some(entry)
+    }
+
+    /// Add a verified image and write the cache. Entries of a replaced file are removed.
+    fn insert(&self, root_hash: String, entry: Verified) {
+        let mut entries = self.entries.lock().expect("failed to lock verity cache");
+        entries.retain(|_, e| e.device != entry.device || e.inode != entry.inode);
+        entries.insert(root_hash, entry);
+
+        let tmp = self.path.with_extension("tmp");
+        let result = serde_json::to_vec(&*entries)
+            .map_err(io::Error::from)
+            .and_then(|content| fs::write(&tmp, content))
+            .and_then(|_| fs::rename(&tmp, &self.path));
+        if let Err(e) = result {
+            warn!(
+                "failed to write verity cache {}: {}",
+                self.path.display(),
+                e
+            );
+        }
     }
 }
 
@@ -193,6 +576,15 @@ fn mount(
     // loop until successful or timeout.
     let start = time::Instant::now();
 
+    // Check that the kernel is able to decompress the image before setting up any device
+    let compression = compression(fd, fsimg_offset)?;
+    debug!("Squashfs compression is {}", compression);
+    if let Some(supported) = KERNEL_COMPRESSION.as_ref() {
+        if !supported.contains(&compression) {
+            return Err(Error::UnsupportedCompression(compression));
+        }
+    }
+
     if !target.exists() {
         debug!("Creating mount point {}", target.display());
         std::fs::create_dir_all(&target).map_err(|e| {
@@ -298,7 +690,17 @@ fn mount(
         None
     };
     let data = data.as_deref();
-    let mount_result = nix::mount::mount(source, target, fstype, flags, data).map_err(Error::Os);
+    let mount_result =
+        nix::mount::mount(source, target, fstype, flags, data).map_err(|e| match e {
+            // Without a kernel configuration the decompressor support is unknown. The kernel
+            // rejects images with an unknown compressor with EINVAL.
+            Errno::EINVAL
+                if KERNEL_COMPRESSION.is_none() && compression != CompressionAlgorithm::Gzip =>
+            {
+                Error::UnsupportedCompression(compression)
+            }
+            e => Error::Os(e),
+        });
 
     if let Err(ref e) = mount_result {
         warn!("failed to mount: {}", e);
@@ -317,6 +719,72 @@ fn mount(
     Ok(())
 }
 
+/// Read the compression algorithm from the superblock of the squashfs image
+/// at `offset` in `fd`.
+fn compression(fd: RawFd, offset: u64) -> Result<CompressionAlgorithm, Error> {
+    let mut superblock = [0u8; SQUASHFS_COMPRESSION_OFFSET + 2];
+    let len = nix::sys::uio::pread(fd, &mut superblock, offset as i64)?;
+    if len != superblock.len() || &superblock[0..4] != SQUASHFS_MAGIC {
+        return Err(Error::Npk("Invalid squashfs superblock"));
+    }
+    let id = u16::from_le_bytes([
+        superblock[SQUASHFS_COMPRESSION_OFFSET],
+        superblock[SQUASHFS_COMPRESSION_OFFSET + 1],
+    ]);
+    CompressionAlgorithm::from_squashfs_id(id).ok_or(Error::Npk("Unknown squashfs compression"))
+}
+
+/// Determine the squashfs decompressors of the running kernel from its configuration. Returns
+/// `None` if no uncompressed kernel configuration is found.
+fn probe_kernel_compression() -> Option<Vec<CompressionAlgorithm>> {
+    let release = fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+    let candidates = [
+        PathBuf::from("/proc/config"),
+        PathBuf::from(format!("/boot/config-{}", release.trim())),
+    ];
+    let config = candidates
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok());
+    let config = match config {
+        Some(config) => config,
+        None => {
+            info!("Kernel configuration is not available. Skipping squashfs compression probing");
+            return None;
+        }
+    };
+
+    let enabled = |option: &str| {
+        config.lines().any(|line| {
+            line.strip_prefix(option)
+                .map(|value| value == "=y" || value == "=m")
+                .unwrap_or(false)
+        })
+    };
+
+    // Lzma is not supported by mainline kernels
+    let supported = [
+        ("CONFIG_SQUASHFS_ZLIB", CompressionAlgorithm::Gzip),
+        ("CONFIG_SQUASHFS_LZ4", CompressionAlgorithm::Lz4),
+        ("CONFIG_SQUASHFS_LZO", CompressionAlgorithm::Lzo),
+        ("CONFIG_SQUASHFS_XZ", CompressionAlgorithm::Xz),
+        ("CONFIG_SQUASHFS_ZSTD", CompressionAlgorithm::Zstd),
+    ]
+    .into_iter()
+    .filter(|(option, _)| enabled(option))
+    .map(|(_, algorithm)| algorithm)
+    .collect::<Vec<_>>();
+
+    info!(
+        "Kernel supports squashfs compression {}",
+        supported
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Some(supported)
+}
+
 fn dmsetup(
     dm: Arc<devicemapper::DM>,
     dev: &str,
@@ -403,3 +871,140 @@ fn dmsetup(
 
     Ok(device)
 }
+
+/// Unused verity devices created by northstar and unused loop devices backed by npks
+fn unused_devices() -> Result<Vec<String>, Error> {
+    let mounted = mountinfo()?
+        .into_iter()
+        .map(|mount| mount.device)
+        .collect::<HashSet<_>>();
+    let read = |path: &Path| fs::read_to_string(path).map(|s| s.trim().to_owned());
+
+    let mut unused = Vec::new();
+    let devices =
+        fs::read_dir("/sys/block").map_err(|e| Error::Io("failed to read /sys/block".into(), e))?;
+    for entry in devices.flatten() {
+        let sys = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        let is_unused = match (read(&sys.join("dev")), fs::read_dir(sys.join("holders"))) {
+            (Ok(device), Ok(mut holders)) => !mounted.contains(&device) && holders.next().is_none(),
+            _ => false,
+        };
+        if !is_unused {
+            continue;
+        }
+
+        if name.starts_with("dm-") {
+            match read(&sys.join("dm").join("name")) {
+                Ok(dm_name) if dm_name.starts_with("northstar-") => unused.push(dm_name),
+                _ => (),
+            }
+        } else if name.starts_with("loop") {
+            // Only attached loop devices have a backing file
+            let backing_file = match read(&sys.join("loop").join("backing_file")) {
+                Ok(backing_file) => backing_file,
+                Err(_) => continue,
+            };
+            let backing_file = Path::new(backing_file.trim_end_matches(" (deleted)"));
+            if backing_file
+                .extension()
+                .map_or(false, |extension| extension == "npk")
+            {
+                unused.push(name);
+            }
+        }
+    }
+
+    Ok(unused)
+}
+
+/// Remove the verity devices and detach the loop devices in `devices`
+fn release_devices(dm: &devicemapper::DM, devices: Vec<String>) -> Vec<String> {
+    let mut released = Vec::new();
+    for device in devices {
+        if device.starts_with("northstar-") {
+            warn!("Removing stale verity device {}", device);
+            let result = DmName::new(&device)
+                .and_then(|name| dm.device_remove(&DevId::Name(name), DmOptions::default()));
+            match result {
+                Ok(_) => released.push(device),
+                Err(e) => warn!("failed to remove {}: {}", device, e),
+            }
+        } else {
+            let path = Path::new("/dev").join(&device);
+            warn!("Detaching stale loop device {}", path.display());
+            match loopdev::LoopDevice::open(&path).and_then(|device| device.detach()) {
+                Ok(_) => released.push(device),
+                Err(e) => warn!("failed to detach {}: {}", path.display(), e),
+            }
+        }
+    }
+    released
+}
+
+/// Mount points whose parent is `dir`
+pub(super) fn mount_points(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    Ok(mountinfo()?
+        .into_iter()
+        .map(|mount| mount.mount_point)
+        .filter(|mount_point| mount_point.parent() == Some(dir))
+        .collect())
+}
+
+/// Entry of /proc/self/mountinfo
+#[derive(Debug, PartialEq, Eq)]
+struct MountInfo {
+    /// Major and minor number of the device e.g. "7:0"
+    device: String,
+    mount_point: PathBuf,
+}
+
+/// Read the mounts of the mount namespace of the runtime
+fn mountinfo() -> Result<Vec<MountInfo>, Error> {
+    fs::read_to_string("/proc/self/mountinfo")
+        .map(|content| parse_mountinfo(&content))
+        .map_err(|e| Error::Io("failed to read /proc/self/mountinfo".into(), e))
+}
+
+/// Parse the content of a mountinfo file. Lines are
+/// "id parent major:minor root mount-point options ..."
+fn parse_mountinfo(content: &str) -> Vec<MountInfo> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ').skip(2);
+            let device = fields.next()?.to_owned();
+            let mount_point = unescape(fields.nth(1)?).into();
+            Some(MountInfo {
+                device,
+                mount_point,
+            })
+        })
+        .collect()
+}
+
+/// Replace the octal escapes of spaces, tabs, newlines and backslashes in mountinfo
+fn unescape(field: &str) -> String {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\012", "\n")
+        .replace("\\134", "\\")
+}
+
+#[test]
+fn parse_mount_info() {
+    let mounts = parse_mountinfo(
+        "22 1 0:21 / /proc rw,nosuid - proc proc rw\n\
+         98 29 253:3 / /run/northstar/hello-world:0.0.1 ro,nosuid - squashfs /dev/dm-3 ro\n\
+         99 29 7:4 / /run/northstar/a\\040b:0.0.1 ro - squashfs /dev/loop4 ro\n",
+    );
+    assert_eq!(mounts.len(), 3);
+    assert_eq!(mounts[1].device, "253:3");
+    assert_eq!(
+        mounts[1].mount_point,
+        Path::new("/run/northstar/hello-world:0.0.1")
+    );
+    assert_eq!(mounts[2].mount_point, Path::new("/run/northstar/a b:0.0.1"));
+}
@@ -0,0 +1,212 @@
+use super::{
+    error::{Context, Error},
+    Pid,
+};
+use crate::{
+    common::{container::Container, non_nul_string::NonNulString},
+    npk::manifest::Manifest,
+};
+use nix::{
+    errno::Errno,
+    libc,
+    net::if_::if_nametoindex,
+    sched::{self, CloneFlags},
+    sys::socket::{self, AddressFamily, SockFlag, SockProtocol, SockType},
+    unistd,
+};
+use std::{
+    fs,
+    os::unix::prelude::{AsRawFd, RawFd},
+    thread,
+};
+use tracing::{debug, warn};
+
+/// Network namespace of a container with the interfaces moved into it
+#[derive(Debug)]
+pub(crate) struct Network {
+    container: Container,
+    /// Network namespace of the container. The namespace is kept alive until the
+    /// interfaces are moved back.
+    namespace: fs::File,
+    /// Network namespace of the runtime
+    host: fs::File,
+    interfaces: Vec<NonNulString>,
+}
+
+impl Network {
+    /// Move the interfaces of `manifest` into the network namespace of `pid` and
+    /// bring them and the loopback interface up
+    pub(super) fn new(
+        container: &Container,
+        manifest: &Manifest,
+        pid: Pid,
+    ) -> Result<Option<Network>, Error> {
+        let network = match &manifest.network {
+            Some(network) if network.namespace => network,
+            _ => return Ok(None),
+        };
+
+        let path = format!("/proc/{}/ns/net", pid);
+        let namespace = fs::File::open(&path).context(format!("failed to open {}", path))?;
+        let host =
+            fs::File::open("/proc/self/ns/net").context("failed to open network namespace")?;
+        let mut result = Network {
+            container: container.clone(),
+            namespace,
+            host,
+            interfaces: Vec::with_capacity(network.interfaces.len()),
+        };
+
+        for interface in &network.interfaces {
+            debug!(
+                "Moving {} into the network namespace of {}",
+                interface, container
+            );
+            let moved = if_nametoindex(interface.as_str())
+                .and_then(|index| move_link(index, result.namespace.as_raw_fd()));
+            if let Err(e) = moved {
+                result.destroy();
+                return Err(Error::StartContainerFailed(
+                    container.clone(),
+                    format!("failed to move network interface {}: {}", interface, e),
+                ));
+            }
+            result.interfaces.push(interface.clone());
+        }
+
+        let mut interfaces = vec!["lo".to_string()];
+        interfaces.extend(result.interfaces.iter().map(ToString::to_string));
+        let up = in_namespace(result.namespace.as_raw_fd(), move || {
+            interfaces
+                .iter()
+                .try_for_each(|interface| if_nametoindex(interface.as_str()).and_then(set_up))
+        });
+        if let Err(e) = up {
+            result.destroy();
+            return Err(Error::StartContainerFailed(
+                container.clone(),
+                format!("failed to set network interfaces up: {}", e),
+            ));
+        }
+
+        Ok(Some(result))
+    }
+
+    /// Take over the network namespace of `container` with the moved `interfaces`
+    /// from a previous runtime instance
+    pub(super) fn adopt(
+        container: &Container,
+        namespace: fs::File,
+        interfaces: Vec<NonNulString>,
+    ) -> Result<Network, Error> {
+        let host =
+            fs::File::open("/proc/self/ns/net").context("failed to open network namespace")?;
+        Ok(Network {
+            container: container.clone(),
+            namespace,
+            host,
+            interfaces,
+        })
+    }
+
+    /// Namespace and moved interfaces handed over to the next runtime instance
+    pub(super) fn handoff(self) -> (fs::File, Vec<NonNulString>) {
+        (self.namespace, self.interfaces)
+    }
+
+    /// Move the interfaces back to the network namespace of the runtime
+    pub(super) fn destroy(self) {
+        let container = self.container;
+        let host = self.host.as_raw_fd();
+        for interface in self.interfaces {
+            debug!("Moving {} back from {}", interface, container);
+            let name = interface.to_string();
+            let moved = in_namespace(self.namespace.as_raw_fd(), move || {
+                if_nametoindex(name.as_str()).and_then(|index| move_link(index, host))
+            });
+            if let Err(e) = moved {
+                warn!(
+                    "failed to move network interface {} back from {}: {}",
+                    interface, container, e
+                );
+            }
+        }
+    }
+}
+
+/// Run `f` on a thread in the network namespace `namespace`
+fn in_namespace<F>(namespace: RawFd, f: F) -> Result<(), Errno>
+where
+    F: FnOnce() -> Result<(), Errno> + Send + 'static,
+{
+    // The namespace of the thread cannot be restored reliably. Use a new thread
+    // that exits afterwards.
+    thread::spawn(move || {
+        sched::setns(namespace, CloneFlags::CLONE_NEWNET)?;
+        f()
+    })
+    .join()
+    .expect("network namespace thread panicked")
+}
+
+/// Move the interface `index` into the network namespace `namespace`
+fn move_link(index: u32, namespace: RawFd) -> Result<(), Errno> {
+    let attribute = (libc::IFLA_NET_NS_FD, namespace as u32);
+    new_link(index, 0, Some(attribute))
+}
+
+/// Set the interface `index` up
+fn set_up(index: u32) -> Result<(), Errno> {
+    new_link(index, libc::IFF_UP as u32, None)
+}
+
+/// Send a `RTM_NEWLINK` request for the interface `index` that changes `flags` and
+/// sets the u32 `attribute` and wait for the acknowledgement
+fn new_link(index: u32, flags: u32, attribute: Option<(u16, u32)>) -> Result<(), Errno> {
+    const NLMSG_HDR_LEN: usize = 16;
+    const IFINFOMSG_LEN: usize = 16;
+    const RTATTR_U32_LEN: usize = 8;
+
+    let len = NLMSG_HDR_LEN + IFINFOMSG_LEN + attribute.map_or(0, |_| RTATTR_U32_LEN);
+    let mut message = Vec::with_capacity(len);
+    // nlmsghdr
+    message.extend_from_slice(&(len as u32).to_ne_bytes());
+    message.extend_from_slice(&libc::RTM_NEWLINK.to_ne_bytes());
+    message.extend_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16).to_ne_bytes());
+    message.extend_from_slice(&1u32.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    // ifinfomsg
+    message.push(libc::AF_UNSPEC as u8);
+    message.push(0);
+    message.extend_from_slice(&0u16.to_ne_bytes());
+    message.extend_from_slice(&(index as i32).to_ne_bytes());
+    message.extend_from_slice(&flags.to_ne_bytes());
+    message.extend_from_slice(&flags.to_ne_bytes());
+    // rtattr
+    if let Some((kind, value)) = attribute {
+        message.extend_from_slice(&(RTATTR_U32_LEN as u16).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    let fd = socket::socket(
+        AddressFamily::Netlink,
+        SockType::Raw,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkRoute,
+    )?;
+    let mut ack = [0u8; 1024];
+    let result = unistd::write(fd, &message).and_then(|_| unistd::read(fd, &mut ack));
+    unistd::close(fd).ok();
+    let len = result?;
+
+    // The acknowledgement is a nlmsgerr with the error code after the header
+    if len < NLMSG_HDR_LEN + 4 || u16::from_ne_bytes([ack[4], ack[5]]) != libc::NLMSG_ERROR as u16 {
+        return Err(Errno::EPROTO);
+    }
+    let error = i32::from_ne_bytes([ack[16], ack[17], ack[18], ack[19]]);
+    match error {
+        0 => Ok(()),
+        error => Err(Errno::from_i32(-error)),
+    }
+}
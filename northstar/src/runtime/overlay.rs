@@ -0,0 +1,144 @@
+use super::{
+    config::{Config, MountBackend},
+    error::{Context, Error},
+    fuse,
+};
+use crate::{
+    common::container::Container,
+    npk::manifest::{Manifest, RootOverlay},
+};
+use nix::{
+    mount::{self, MntFlags, MsFlags},
+    unistd,
+};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{debug, warn};
+
+/// Directory within the run dir that holds the root overlay of each container
+const OVERLAY_DIR: &str = "overlay";
+
+/// Upper dir of the overlay of `container`
+pub(crate) fn upper(run_dir: &Path, container: &Container) -> PathBuf {
+    dir(run_dir, container).join("upper")
+}
+
+/// Work dir of the overlay of `container`
+pub(crate) fn work(run_dir: &Path, container: &Container) -> PathBuf {
+    dir(run_dir, container).join("work")
+}
+
+/// Mount point of the FUSE overlay of `container`
+pub(crate) fn merged(run_dir: &Path, container: &Container) -> PathBuf {
+    dir(run_dir, container).join("merged")
+}
+
+/// Mount the writable layer of the root filesystem of `container` if configured. The
+/// upper dir is owned by the uid and gid of the container.
+pub(super) async fn create(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+) -> Result<(), Error> {
+    let root = match manifest.root.as_ref() {
+        Some(root) => root,
+        None => return Ok(()),
+    };
+
+    // The extracted root of the simulation backend is writable
+    #[cfg(feature = "simulation")]
+    if config.mount_backend == MountBackend::Simulation {
+        warn!(
+            "Root overlay of {} is not supported by the simulation backend. Writes go to the extracted image",
+            container
+        );
+        return Ok(());
+    }
+
+    // Remove left overs of a previous instance
+    remove(config, container).await?;
+
+    let dir = dir(&config.run_dir, container);
+    fs::create_dir_all(&dir)
+        .await
+        .context(format!("failed to create {}", dir.display()))?;
+
+    match root.overlay {
+        RootOverlay::Tmpfs if config.mount_backend == MountBackend::Fuse => {
+            warn!(
+                "Cannot mount tmpfs for the root overlay of {} with the FUSE backend. The size is not limited",
+                container
+            );
+        }
+        RootOverlay::Tmpfs => {
+            debug!(
+                "Mounting root overlay tmpfs of {} with size {} on {}",
+                container,
+                root.size,
+                dir.display()
+            );
+            let flags = MsFlags::MS_NODEV | MsFlags::MS_NOSUID;
+            let data = format!("size={},mode=0700", root.size);
+            mount::mount(
+                Some("tmpfs"),
+                &dir,
+                Some("tmpfs"),
+                flags,
+                Some(data.as_str()),
+            )
+            .context(format!("failed to mount tmpfs on {}", dir.display()))?;
+        }
+    }
+
+    let upper = upper(&config.run_dir, container);
+    let work = work(&config.run_dir, container);
+    for dir in [&upper, &work] {
+        fs::create_dir(dir)
+            .await
+            .context(format!("failed to create {}", dir.display()))?;
+    }
+
+    let uid = unistd::Uid::from_raw(manifest.uid.into());
+    let gid = unistd::Gid::from_raw(manifest.gid.into());
+    unistd::chown(&upper, Some(uid), Some(gid))
+        .context(format!("failed to chown {}", upper.display()))?;
+
+    // Without CAP_SYS_ADMIN the overlay cannot be mounted by init. Mount it here with
+    // fuse-overlayfs. Init bind mounts it onto the root.
+    if config.mount_backend == MountBackend::Fuse {
+        let root = config
+            .run_dir
+            .join(container.without_instance().to_string());
+        let merged = merged(&config.run_dir, container);
+        fs::create_dir(&merged)
+            .await
+            .context(format!("failed to create {}", merged.display()))?;
+        fuse::overlay(&root, &upper, &work, &merged)
+            .await
+            .map_err(Error::Mount)?;
+    }
+
+    Ok(())
+}
+
+/// Umount and remove the root overlay of `container` if present
+pub(super) async fn remove(config: &Config, container: &Container) -> Result<(), Error> {
+    let dir = dir(&config.run_dir, container);
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    debug!("Removing root overlay of {}", container);
+    if config.mount_backend == MountBackend::Fuse {
+        fuse::umount(&merged(&config.run_dir, container)).await.ok();
+    }
+    // The tmpfs is not mounted if a previous instance failed in between
+    mount::umount2(&dir, MntFlags::MNT_DETACH).ok();
+    fs::remove_dir_all(&dir)
+        .await
+        .context(format!("failed to remove {}", dir.display()))
+}
+
+fn dir(run_dir: &Path, container: &Container) -> PathBuf {
+    run_dir.join(OVERLAY_DIR).join(container.to_string())
+}
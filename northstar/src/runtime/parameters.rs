@@ -0,0 +1,350 @@
+use super::config;
+use crate::{
+    common::{name::Name, non_nul_string::NonNulString},
+    npk::manifest::{mount::Mount, Manifest},
+};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use tracing::warn;
+
+/// Template variable for the device id
+const DEVICE_ID: &str = "DEVICE_ID";
+/// Template variable prefix for configuration values
+const CONFIG: &str = "CONFIG:";
+/// Template variable for the instance name
+const INSTANCE: &str = "INSTANCE";
+/// Template variable prefix for template parameters
+const PARAM: &str = "PARAM:";
+
+/// Template resolution error
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Unterminated variable
+    #[error("unterminated variable in \"{0}\"")]
+    Unterminated(String),
+    /// Unknown variable
+    #[error("unknown variable \"{0}\"")]
+    Unknown(String),
+    /// The provider has no value for the variable
+    #[error("missing value for \"{0}\"")]
+    Missing(String),
+    /// The parameter is not declared in the manifest
+    #[error("undeclared parameter \"{0}\"")]
+    Undeclared(String),
+    /// The value is not in the allowed values of the parameter
+    #[error("invalid value \"{1}\" for parameter \"{0}\"")]
+    Invalid(String, String),
+    /// The substituted value contains a nul byte
+    #[error("nul byte in \"{0}\"")]
+    Nul(String),
+}
+
+/// Template arguments of a container start
+#[derive(Debug, Default)]
+pub(super) struct Arguments {
+    /// Instance name substituted for `${INSTANCE}`
+    instance: Option<String>,
+    /// Parameter values substituted for `${PARAM:name}`
+    values: HashMap<String, String>,
+    /// The manifest declares parameters. `$$` is an escaped `$` in templates only.
+    template: bool,
+}
+
+impl Arguments {
+    /// Check `values` against the parameters declared in `manifest` and complete them
+    /// with the defaults
+    pub(super) fn new(
+        manifest: &Manifest,
+        instance: Option<&Name>,
+        values: &HashMap<Name, NonNulString>,
+    ) -> Result<Arguments, Error> {
+        if let Some(name) = values
+            .keys()
+            .find(|n| !manifest.parameters.contains_key(*n))
+        {
+            return Err(Error::Undeclared(name.to_string()));
+        }
+
+        let mut result = HashMap::with_capacity(manifest.parameters.len());
+        for (name, parameter) in &manifest.parameters {
+            let value = values
+                .get(name)
+                .or(parameter.default.as_ref())
+                .ok_or_else(|| Error::Missing(format!("{}{}", PARAM, name)))?;
+            if !parameter.values.is_empty() && !parameter.values.contains(value) {
+                return Err(Error::Invalid(name.to_string(), value.to_string()));
+            }
+            result.insert(name.to_string(), value.to_string());
+        }
+
+        Ok(Arguments {
+            instance: instance.map(ToString::to_string),
+            values: result,
+            template: !manifest.parameters.is_empty(),
+        })
+    }
+}
+
+/// Source of device specific values that are substituted into the environment
+/// of containers when they are started. Implement this trait to feed values
+/// from a platform specific store into the runtime.
+pub trait Provider: Debug + Send + Sync {
+    /// Device identification substituted for `${DEVICE_ID}`
+    fn device_id(&self) -> Option<String>;
+
+    /// Configuration value substituted for `${CONFIG:key}`
+    fn config(&self, key: &str) -> Option<String>;
+
+    /// Content of the secret `name` for containers with a `secret` mount
+    fn secret(&self, name: &str) -> Option<Vec<u8>>;
+}
+
+/// Provider backed by the `parameters` section of the runtime configuration
+#[derive(Debug, Default)]
+pub struct ConfigProvider {
+    device_id: Option<String>,
+    config: HashMap<String, String>,
+    secrets: HashMap<String, PathBuf>,
+}
+
+impl ConfigProvider {
+    /// Create a new provider from the runtime configuration
+    pub fn new(parameters: &config::Parameters) -> ConfigProvider {
+        let device_id = parameters.device_id.clone().or_else(|| {
+            parameters.device_id_file.as_ref().and_then(|file| {
+                std::fs::read_to_string(file)
+                    .map(|id| id.trim().to_string())
+                    .map_err(|e| warn!("failed to read {}: {}", file.display(), e))
+                    .ok()
+            })
+        });
+
+        ConfigProvider {
+            device_id,
+            config: parameters.config.clone(),
+            secrets: parameters.secrets.clone(),
+        }
+    }
+}
+
+impl Provider for ConfigProvider {
+    fn device_id(&self) -> Option<String> {
+        self.device_id.clone()
+    }
+
+    fn config(&self, key: &str) -> Option<String> {
+        self.config.get(key).cloned()
+    }
+
+    fn secret(&self, name: &str) -> Option<Vec<u8>> {
+        self.secrets.get(name).and_then(|file| read(file))
+    }
+}
+
+/// Read a secret from `file`. Secrets are read on each container start and not cached.
+fn read(file: &Path) -> Option<Vec<u8>> {
+    std::fs::read(file)
+        .map_err(|e| warn!("failed to read {}: {}", file.display(), e))
+        .ok()
+}
+
+/// Substitute the variables in `template` with values from `provider` and the
+/// template `arguments` of the start. Variables are written as `${DEVICE_ID}`,
+/// `${CONFIG:key}`, `${INSTANCE}` or `${PARAM:name}`. In templates a literal `$` is written
+/// as `$$`. Values of containers without parameters keep `$$` unchanged.
+pub(super) fn resolve(
+    template: &str,
+    provider: &dyn Provider,
+    arguments: &Arguments,
+) -> Result<String, Error> {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(n) = rest.find('$') {
+        result.push_str(&rest[..n]);
+        rest = &rest[n..];
+
+        if let Some(remaining) = rest.strip_prefix("$$").filter(|_| arguments.template) {
+            result.push('$');
+            rest = remaining;
+        } else if let Some(remaining) = rest.strip_prefix("${") {
+            let end = remaining
+                .find('}')
+                .ok_or_else(|| Error::Unterminated(template.to_string()))?;
+            let variable = &remaining[..end];
+            let value = if variable == DEVICE_ID {
+                provider.device_id()
+            } else if let Some(key) = variable.strip_prefix(CONFIG) {
+                provider.config(key)
+            } else if variable == INSTANCE {
+                arguments.instance.clone()
+            } else if let Some(name) = variable.strip_prefix(PARAM) {
+                arguments.values.get(name).cloned()
+            } else {
+                return Err(Error::Unknown(variable.to_string()));
+            };
+            result.push_str(&value.ok_or_else(|| Error::Missing(variable.to_string()))?);
+            rest = &remaining[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Substitute the variables in the arguments, bind mount sources and clock
+/// offsets of the template `manifest`. Manifests without parameters are not modified.
+pub(super) fn apply(
+    manifest: &mut Manifest,
+    provider: &dyn Provider,
+    arguments: &Arguments,
+) -> Result<(), Error> {
+    if manifest.parameters.is_empty() {
+        return Ok(());
+    }
+
+    let substitute = |template: &str| {
+        resolve(template, provider, arguments).and_then(|value| {
+            NonNulString::try_from(value).map_err(|_| Error::Nul(template.to_string()))
+        })
+    };
+
+    for arg in manifest.args.iter_mut() {
+        *arg = substitute(arg.as_str())?;
+    }
+    for mount in manifest.mounts.values_mut() {
+        if let Mount::Bind(bind) = mount {
+            bind.host = PathBuf::from(substitute(&bind.host.display().to_string())?.as_str());
+        }
+    }
+    if let Some(time) = manifest.time.as_mut() {
+        for value in time.offset.iter_mut().chain(time.realtime.iter_mut()) {
+            *value = substitute(value.as_str())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve_default(template: &str, provider: &dyn Provider) -> Result<String, Error> {
+        resolve(template, provider, &Arguments::default())
+    }
+
+    fn provider() -> ConfigProvider {
+        ConfigProvider {
+            device_id: Some("abc123".into()),
+            config: [("backend".to_string(), "https://example.com".to_string())].into(),
+            secrets: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(resolve_default("hello", &provider()), Ok("hello".into()));
+        assert_eq!(resolve_default("", &provider()), Ok("".into()));
+        assert_eq!(resolve_default("a$b", &provider()), Ok("a$b".into()));
+        // `$$` is not an escape outside of templates
+        assert_eq!(resolve_default("$$", &provider()), Ok("$$".into()));
+        assert_eq!(resolve_default("a$$b", &provider()), Ok("a$$b".into()));
+        assert_eq!(
+            resolve_default("$$${DEVICE_ID}", &provider()),
+            Ok("$$abc123".into())
+        );
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            resolve_default("${DEVICE_ID}", &provider()),
+            Ok("abc123".into())
+        );
+        assert_eq!(
+            resolve_default("${CONFIG:backend}/${DEVICE_ID}", &provider()),
+            Ok("https://example.com/abc123".into())
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            resolve_default("${DEVICE_ID", &provider()),
+            Err(Error::Unterminated(_))
+        ));
+        assert!(matches!(
+            resolve_default("${FOO}", &provider()),
+            Err(Error::Unknown(_))
+        ));
+        assert!(matches!(
+            resolve_default("${CONFIG:foo}", &provider()),
+            Err(Error::Missing(_))
+        ));
+        assert!(matches!(
+            resolve_default("${DEVICE_ID}", &ConfigProvider::default()),
+            Err(Error::Missing(_))
+        ));
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn template() {
+        use std::str::FromStr;
+
+        let manifest = Manifest::from_str(
+            "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+args:
+  - --display=${PARAM:display}
+  - --profile=${INSTANCE}
+parameters:
+  display:
+    default: \":0\"
+    values: [\":0\", \":1\"]",
+        )
+        .unwrap();
+        let instance = Name::try_from("left").unwrap();
+        let display = Name::try_from("display").unwrap();
+
+        // The default is used without a value
+        let arguments = Arguments::new(&manifest, Some(&instance), &HashMap::new()).unwrap();
+        let mut template = manifest.clone();
+        apply(&mut template, &provider(), &arguments).unwrap();
+        assert_eq!(template.args[0].as_str(), "--display=:0");
+        assert_eq!(template.args[1].as_str(), "--profile=left");
+
+        let values = HashMap::from([(display.clone(), ":1".try_into().unwrap())]);
+        let arguments = Arguments::new(&manifest, None, &values).unwrap();
+        assert_eq!(
+            resolve("${PARAM:display}", &provider(), &arguments),
+            Ok(":1".into())
+        );
+        assert!(matches!(
+            resolve("${INSTANCE}", &provider(), &arguments),
+            Err(Error::Missing(_))
+        ));
+        assert_eq!(resolve("$$", &provider(), &arguments), Ok("$".into()));
+        assert_eq!(
+            resolve("$${PARAM:display}", &provider(), &arguments),
+            Ok("${PARAM:display}".into())
+        );
+
+        let values = HashMap::from([(display, ":2".try_into().unwrap())]);
+        assert!(matches!(
+            Arguments::new(&manifest, None, &values),
+            Err(Error::Invalid(_, _))
+        ));
+        let values = HashMap::from([(Name::try_from("foo").unwrap(), "".try_into().unwrap())]);
+        assert!(matches!(
+            Arguments::new(&manifest, None, &values),
+            Err(Error::Undeclared(_))
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+use super::{
+    console::{Configuration, DEFAULT_REQUESTS_PER_SECOND},
+    NotificationTx,
+};
+use crate::api::model;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+
+/// Default ban duration in seconds
+const DEFAULT_BAN_DURATION: u64 = 60;
+/// Request rate window
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Request rates, connection counts and bans of the peers of a console listener.
+/// Peers are tracked across connections. A peer cannot reset its limits by
+/// reconnecting.
+pub(crate) struct Peers {
+    /// Maximum number of requests per second and peer
+    max_requests_per_sec: usize,
+    /// Maximum number of concurrent connections per peer
+    max_connections: Option<usize>,
+    /// Number of consecutive throttled requests that ban a peer
+    ban_threshold: Option<usize>,
+    /// Duration of a ban
+    ban_duration: Duration,
+    /// Notifications about bans
+    notification_tx: NotificationTx,
+    /// Peer states by peer identity
+    peers: Mutex<HashMap<String, PeerState>>,
+}
+
+#[derive(Default)]
+struct PeerState {
+    /// Number of open connections
+    connections: usize,
+    /// Time of the requests within the rate window
+    requests: VecDeque<Instant>,
+    /// Consecutive throttled requests
+    violations: usize,
+    /// End of the current ban
+    banned_until: Option<Instant>,
+}
+
+impl PeerState {
+    fn is_idle(&self, now: Instant) -> bool {
+        self.connections == 0
+            && self.banned_until.map(|until| until <= now).unwrap_or(true)
+            && self
+                .requests
+                .back()
+                .map(|t| now.saturating_duration_since(*t) >= WINDOW)
+                .unwrap_or(true)
+    }
+}
+
+/// Result of a connection attempt
+#[derive(Debug)]
+pub(crate) enum Admission {
+    /// The connection is accepted. The guard must be kept for the lifetime of the connection
+    Accepted(Connection),
+    /// The peer is banned
+    Banned(Duration),
+    /// The peer reached the maximum number of connections
+    TooManyConnections(usize),
+}
+
+/// Result of a request
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    /// Process the request
+    Accept,
+    /// Process the request after the delay
+    Delay(Duration),
+    /// The peer is banned. Close the connection
+    Banned,
+}
+
+/// Open connection of a peer. Releases the connection slot on drop.
+#[derive(Debug)]
+pub(crate) struct Connection {
+    peers: Arc<Peers>,
+    peer: String,
+}
+
+impl Peers {
+    pub fn new(configuration: &Configuration, notification_tx: NotificationTx) -> Peers {
+        Peers {
+            max_requests_per_sec: configuration
+                .max_requests_per_sec
+                .unwrap_or(DEFAULT_REQUESTS_PER_SECOND),
+            max_connections: configuration.max_connections_per_peer,
+            ban_threshold: configuration.ban_threshold,
+            ban_duration: Duration::from_secs(
+                configuration.ban_duration.unwrap_or(DEFAULT_BAN_DURATION),
+            ),
+            notification_tx,
+            peers: Mutex::default(),
+        }
+    }
+
+    /// Register a new connection of `peer`
+    pub fn connect(self: &Arc<Self>, peer: &str) -> Admission {
+        let now = Instant::now();
+        let mut peers = self.peers.lock().expect("poisoned lock");
+        peers.retain(|_, state| !state.is_idle(now));
+        let state = peers.entry(peer.to_string()).or_default();
+
+        if let Some(until) = state.banned_until.filter(|until| *until > now) {
+            return Admission::Banned(until - now);
+        }
+
+        if let Some(max) = self.max_connections {
+            if state.connections >= max {
+                return Admission::TooManyConnections(max);
+            }
+        }
+
+        state.connections += 1;
+        Admission::Accepted(Connection {
+            peers: self.clone(),
+            peer: peer.to_string(),
+        })
+    }
+
+    /// Account a request of `peer`
+    pub fn request(&self, peer: &str) -> Verdict {
+        let now = Instant::now();
+        let mut peers = self.peers.lock().expect("poisoned lock");
+        let state = peers.entry(peer.to_string()).or_default();
+
+        if state.banned_until.map(|until| until > now).unwrap_or(false) {
+            return Verdict::Banned;
+        }
+
+        while let Some(t) = state.requests.front() {
+            if now.saturating_duration_since(*t) >= WINDOW {
+                state.requests.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        // The request that once out of the window allows a new request
+        let pivot = state
+            .requests
+            .iter()
+            .rev()
+            .nth(self.max_requests_per_sec.saturating_sub(1))
+            .cloned();
+
+        match pivot {
+            None => {
+                state.violations = 0;
+                state.requests.push_back(now);
+                Verdict::Accept
+            }
+            Some(pivot) => {
+                state.violations += 1;
+                if let Some(threshold) = self.ban_threshold {
+                    if state.violations >= threshold {
+                        state.violations = 0;
+                        state.requests.clear();
+                        state.banned_until = Some(now + self.ban_duration);
+                        drop(peers);
+                        self.ban(peer);
+                        return Verdict::Banned;
+                    }
+                }
+                let delay = pivot + WINDOW - now;
+                state.requests.push_back(now + delay);
+                Verdict::Delay(delay)
+            }
+        }
+    }
+
+    fn ban(&self, peer: &str) {
+        warn!(
+            "Banning peer {} for {}s after exceeding the request rate limit",
+            peer,
+            self.ban_duration.as_secs()
+        );
+        if self.notification_tx.receiver_count() > 0 {
+            let notification =
+                model::Notification::PeerBanned(peer.to_string(), self.ban_duration.as_secs());
+            self.notification_tx.send(notification).ok();
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let mut peers = self.peers.peers.lock().expect("poisoned lock");
+        if let Some(state) = peers.get_mut(&self.peer) {
+            state.connections = state.connections.saturating_sub(1);
+        }
+    }
+}
+
+impl std::fmt::Debug for Peers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Peers")
+            .field("max_requests_per_sec", &self.max_requests_per_sec)
+            .field("max_connections", &self.max_connections)
+            .field("ban_threshold", &self.ban_threshold)
+            .field("ban_duration", &self.ban_duration)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+    use tokio::sync::broadcast;
+
+    const PEER: &str = "tcp://127.0.0.1";
+
+    fn peers(
+        configuration: Configuration,
+    ) -> (Arc<Peers>, broadcast::Receiver<model::Notification>) {
+        let (tx, rx) = broadcast::channel(10);
+        (Arc::new(Peers::new(&configuration, tx)), rx)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connection_limit() {
+        let (peers, _rx) = peers(Configuration {
+            max_connections_per_peer: Some(2),
+            ..Default::default()
+        });
+
+        let first = peers.connect(PEER);
+        assert!(matches!(first, Admission::Accepted(_)));
+        let second = peers.connect(PEER);
+        assert!(matches!(second, Admission::Accepted(_)));
+        assert!(matches!(
+            peers.connect(PEER),
+            Admission::TooManyConnections(2)
+        ));
+        // Other peers are not affected
+        assert!(matches!(
+            peers.connect("tcp://127.0.0.2"),
+            Admission::Accepted(_)
+        ));
+
+        drop(first);
+        assert!(matches!(peers.connect(PEER), Admission::Accepted(_)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_is_shared_across_connections() {
+        let (peers, _rx) = peers(Configuration {
+            max_requests_per_sec: Some(2),
+            ..Default::default()
+        });
+
+        let _first = peers.connect(PEER);
+        assert_eq!(peers.request(PEER), Verdict::Accept);
+        assert_eq!(peers.request(PEER), Verdict::Accept);
+
+        // Reconnecting does not reset the rate
+        let _second = peers.connect(PEER);
+        assert!(matches!(peers.request(PEER), Verdict::Delay(_)));
+
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(peers.request(PEER), Verdict::Accept);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ban() {
+        let (peers, mut rx) = peers(Configuration {
+            max_requests_per_sec: Some(1),
+            ban_threshold: Some(2),
+            ban_duration: Some(10),
+            ..Default::default()
+        });
+
+        let connection = peers.connect(PEER);
+        assert_eq!(peers.request(PEER), Verdict::Accept);
+        assert!(matches!(peers.request(PEER), Verdict::Delay(_)));
+        assert_eq!(peers.request(PEER), Verdict::Banned);
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            model::Notification::PeerBanned(PEER.to_string(), 10)
+        );
+
+        // New connections are refused while the peer is banned
+        drop(connection);
+        assert!(matches!(peers.connect(PEER), Admission::Banned(_)));
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(matches!(peers.connect(PEER), Admission::Accepted(_)));
+        assert_eq!(peers.request(PEER), Verdict::Accept);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::api::model::RequestId;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Requests in progress that were sent with an id. A request is identified by its
+/// peer and the id assigned by the peer and can be cancelled from any connection
+/// of the peer.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Pending {
+    requests: Arc<Mutex<HashMap<(String, RequestId), CancellationToken>>>,
+}
+
+impl Pending {
+    /// Register request `id` of `peer`. Returns `None` if the peer has a request
+    /// with this id in progress.
+    pub(crate) fn register(&self, peer: &str, id: RequestId) -> Option<PendingRequest> {
+        let mut requests = self.requests.lock().expect("poisoned lock");
+        let key = (peer.to_string(), id);
+        if requests.contains_key(&key) {
+            return None;
+        }
+
+        let token = CancellationToken::new();
+        requests.insert(key.clone(), token.clone());
+        Some(PendingRequest {
+            key,
+            pending: self.clone(),
+            token,
+        })
+    }
+
+    /// Cancel request `id` of `peer`. Returns false if there's no such request.
+    pub(crate) fn cancel(&self, peer: &str, id: RequestId) -> bool {
+        let requests = self.requests.lock().expect("poisoned lock");
+        match requests.get(&(peer.to_string(), id)) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Request in progress. The request is unregistered when dropped.
+pub(crate) struct PendingRequest {
+    key: (String, RequestId),
+    pending: Pending,
+    token: CancellationToken,
+}
+
+impl PendingRequest {
+    /// Id assigned by the peer
+    pub(crate) fn id(&self) -> RequestId {
+        self.key.1
+    }
+
+    /// Token that is cancelled if the request is cancelled
+    pub(crate) fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        let mut requests = self.pending.requests.lock().expect("poisoned lock");
+        requests.remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PEER: &str = "tcp://127.0.0.1";
+
+    #[test]
+    fn cancel() {
+        let pending = Pending::default();
+
+        let request = pending.register(PEER, 1).expect("failed to register");
+        assert!(pending.register(PEER, 1).is_none());
+        let other = pending
+            .register("tcp://127.0.0.2", 1)
+            .expect("failed to register");
+
+        assert!(!pending.cancel(PEER, 2));
+        assert!(pending.cancel(PEER, 1));
+        assert!(request.token().is_cancelled());
+        assert!(!other.token().is_cancelled());
+
+        drop(request);
+        assert!(!pending.cancel(PEER, 1));
+        assert!(pending.register(PEER, 1).is_some());
+    }
+}
@@ -0,0 +1,397 @@
+use super::{
+    config::Config,
+    error::{Context, Error},
+    stats::{to_value, Value},
+};
+use crate::{
+    common::{container::Container, name::Name},
+    npk::manifest::{mount::Mount, Manifest},
+};
+use loopdev::LoopControl;
+use nix::{
+    mount::{self, MntFlags, MsFlags},
+    sys::statvfs,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tokio::task;
+use tracing::{debug, info, warn};
+
+/// Tool used to format persist images
+const MKFS: &str = "mkfs.ext4";
+
+/// Filesystem type of persist images
+const FS_TYPE: &str = "ext4";
+
+/// Persist directory of `container`. The version is intentionally not part of the
+/// path. This allows upgrades with persistent data migration. Containers of a share
+/// group use a common directory named after the group.
+pub(crate) fn dir(config: &Config, container: &Container, manifest: &Manifest) -> PathBuf {
+    match share(manifest) {
+        Some(group) => config.data_dir.join(format!("{}:share", group)),
+        None => config.data_dir.join(owner(container)),
+    }
+}
+
+/// Name of the persist directory of `container`. Named instances have a directory
+/// of their own. An at sign is not valid in container names and avoids clashes.
+fn owner(container: &Container) -> String {
+    match container.instance() {
+        Some(instance) => format!("{}@{}", container.name(), instance),
+        None => container.name().to_string(),
+    }
+}
+
+/// Share group of the persist mounts of `manifest`. The manifest verification
+/// ensures that all persist mounts use the same group.
+pub(super) fn share(manifest: &Manifest) -> Option<&Name> {
+    manifest.mounts.values().find_map(|m| match m {
+        Mount::Persist(persist) => persist.share.as_ref(),
+        _ => None,
+    })
+}
+
+/// Filesystem image backing the persist directory if a quota is configured. A
+/// colon is not valid in container names and avoids clashes with persist dirs.
+fn image(config: &Config, container: &Container) -> PathBuf {
+    config
+        .data_dir
+        .join(format!("{}:persist.img", owner(container)))
+}
+
+/// Mount the filesystem image that enforces the persist quota of `manifest` on the
+/// persist dir. The image is created and formatted on first use.
+pub(super) async fn mount(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+) -> Result<(), Error> {
+    let size = match manifest.persist.as_ref().and_then(|persist| persist.size) {
+        Some(size) if has_persist_mount(manifest) => size,
+        _ => return Ok(()),
+    };
+    let dir = dir(config, container, manifest);
+    let image = image(config, container);
+    let uid = manifest.uid;
+    let gid = manifest.gid;
+
+    task::spawn_blocking(move || mount_image(&image, &dir, size, uid, gid))
+        .await
+        .context("failed to join persist task")?
+}
+
+/// Umount the persist image of `manifest` if mounted. The loop device is released
+/// automatically.
+pub(super) async fn umount(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+) -> Result<(), Error> {
+    let dir = dir(config, container, manifest);
+    let quota = manifest
+        .persist
+        .as_ref()
+        .map_or(false, |persist| persist.size.is_some());
+    if quota && is_mountpoint(&dir)? {
+        debug!("Umounting persist image from {}", dir.display());
+        mount::umount2(&dir, MntFlags::MNT_DETACH)
+            .context(format!("failed to umount {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Persist usage of `manifest` in bytes and the configured quota
+// The statvfs fields are 32 bit wide on some targets
+#[allow(clippy::useless_conversion)]
+pub(super) async fn usage(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+) -> Option<Value> {
+    if !has_persist_mount(manifest) {
+        return None;
+    }
+
+    let dir = dir(config, container, manifest);
+    let size = manifest.persist.as_ref().and_then(|persist| persist.size);
+    let used = task::spawn_blocking(move || {
+        if is_mountpoint(&dir).unwrap_or(false) {
+            statvfs::statvfs(&dir)
+                .map(|s| u64::from(s.blocks() - s.blocks_free()) * u64::from(s.fragment_size()))
+                .map_err(io::Error::from)
+        } else {
+            du(&dir)
+        }
+    })
+    .await
+    .ok()?
+    .map_err(|e| warn!("failed to calculate persist usage: {}", e))
+    .ok()?;
+
+    let mut usage = HashMap::new();
+    usage.insert("used", Some(used));
+    usage.insert("size", size);
+    to_value(usage).ok()
+}
+
+/// Remove all persistent data of `manifest`. The persist directory is moved
+/// aside before removal in order to never leave partially removed data. The
+/// persist image of containers with a quota is removed and recreated on the next
+/// start. Files are overwritten with zeros before removal if `secure_delete` is set.
+/// The container must not be started.
+pub(super) async fn reset(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+) -> Result<(), Error> {
+    umount(config, container, manifest).await?;
+
+    let secure_delete = manifest
+        .persist
+        .as_ref()
+        .map_or(false, |persist| persist.secure_delete);
+
+    let image = image(config, container);
+    if image.exists() {
+        if secure_delete {
+            overwrite(&image).await?;
+        }
+        info!("Removing persist image {}", image.display());
+        tokio::fs::remove_file(&image)
+            .await
+            .context(format!("failed to remove {}", image.display()))?;
+    }
+
+    let dir = dir(config, container, manifest);
+    if dir.exists() {
+        let mut trash = dir.clone().into_os_string();
+        trash.push(":reset");
+        let trash = PathBuf::from(trash);
+        if trash.exists() {
+            tokio::fs::remove_dir_all(&trash)
+                .await
+                .context(format!("failed to remove {}", trash.display()))?;
+        }
+        info!("Removing {}", dir.display());
+        tokio::fs::rename(&dir, &trash)
+            .await
+            .context(format!("failed to move {}", dir.display()))?;
+        if secure_delete {
+            overwrite(&trash).await?;
+        }
+        tokio::fs::remove_dir_all(&trash)
+            .await
+            .context(format!("failed to remove {}", trash.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Remove the persistent data of `container` and of its named instances
+pub(super) async fn remove(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+) -> Result<(), Error> {
+    reset(config, container, manifest).await?;
+
+    // Named instances of a share group use the directory of the group
+    if share(manifest).is_some() {
+        return Ok(());
+    }
+
+    // A colon is not valid in names and marks persist images and removed directories
+    let prefix = format!("{}@", container.name());
+    let mut instances = Vec::new();
+    let mut entries = tokio::fs::read_dir(&config.data_dir)
+        .await
+        .context(format!("failed to read {}", config.data_dir.display()))?;
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .context(format!("failed to read {}", config.data_dir.display()))?
+    {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(instance) = file_name
+            .strip_prefix(&prefix)
+            .filter(|instance| !instance.contains(':'))
+            .and_then(|instance| Name::try_from(instance).ok())
+        {
+            instances.push(container.with_instance(instance));
+        }
+    }
+
+    for instance in instances {
+        reset(config, &instance, manifest).await?;
+    }
+
+    Ok(())
+}
+
+fn mount_image(image: &Path, dir: &Path, size: u64, uid: u16, gid: u16) -> Result<(), Error> {
+    if !dir.exists() {
+        debug!("Creating {}", dir.display());
+        fs::create_dir_all(dir).context(format!("failed to create {}", dir.display()))?;
+    }
+
+    // A previous instance of the container or runtime might have left the image mounted
+    if is_mountpoint(dir)? {
+        debug!("Persist image {} is already mounted", image.display());
+        return Ok(());
+    }
+
+    if !image.exists() {
+        info!(
+            "Creating persist image {} with size {}",
+            image.display(),
+            size
+        );
+        let file =
+            fs::File::create(image).context(format!("failed to create {}", image.display()))?;
+        file.set_len(size)
+            .context(format!("failed to resize {}", image.display()))?;
+        let status = Command::new(MKFS)
+            .arg("-q")
+            .arg("-F")
+            .arg("-E")
+            .arg(format!("root_owner={}:{}", uid, gid))
+            .arg(image)
+            .status()
+            .context(format!("failed to run {}", MKFS))?;
+        if !status.success() {
+            fs::remove_file(image).ok();
+            return Err(Error::Unexpected(
+                format!("failed to format {}", image.display()),
+                Box::new(io::Error::new(io::ErrorKind::Other, status.to_string())),
+            ));
+        }
+    } else {
+        let len = fs::metadata(image)
+            .context(format!("failed to stat {}", image.display()))?
+            .len();
+        if len != size {
+            warn!(
+                "Persist image {} has size {} instead of the configured {}",
+                image.display(),
+                len,
+                size
+            );
+        }
+    }
+
+    if fs::read_dir(dir)
+        .map(|mut d| d.next().is_some())
+        .unwrap_or(false)
+    {
+        warn!(
+            "Mounting persist image over non empty directory {}",
+            dir.display()
+        );
+    }
+
+    let lc = LoopControl::open().context("failed to open loop control")?;
+    let loop_device = lc.next_free().context("failed to acquire loop device")?;
+    loop_device
+        .with()
+        .autoclear(true)
+        .attach(image)
+        .context(format!("failed to attach {}", image.display()))?;
+    let device = loop_device
+        .path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "failed to get loop device path"))
+        .context("failed to get loop device path")?;
+
+    debug!(
+        "Mounting {} with {} on {}",
+        image.display(),
+        device.display(),
+        dir.display()
+    );
+    let flags = MsFlags::MS_NODEV | MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC;
+    let result = mount::mount(Some(&device), dir, Some(FS_TYPE), flags, None::<&str>)
+        .context(format!("failed to mount {}", image.display()));
+    if result.is_err() {
+        loop_device.detach().ok();
+    }
+    result
+}
+
+/// True if `manifest` has a persist mount
+pub(super) fn has_persist_mount(manifest: &Manifest) -> bool {
+    manifest
+        .mounts
+        .values()
+        .any(|m| matches!(m, Mount::Persist(_)))
+}
+
+/// Check if `dir` is a mount point by comparing the device with the parent's device
+fn is_mountpoint(dir: &Path) -> Result<bool, Error> {
+    let parent = match dir.parent() {
+        Some(parent) if dir.exists() => parent,
+        _ => return Ok(false),
+    };
+    let dev = fs::metadata(dir)
+        .context(format!("failed to stat {}", dir.display()))?
+        .dev();
+    let parent_dev = fs::metadata(parent)
+        .context(format!("failed to stat {}", parent.display()))?
+        .dev();
+    Ok(dev != parent_dev)
+}
+
+/// Overwrite the regular files in `path` with zeros
+async fn overwrite(path: &Path) -> Result<(), Error> {
+    debug!("Overwriting {}", path.display());
+    let target = path.to_owned();
+    task::spawn_blocking(move || zero(&target))
+        .await
+        .context("failed to join persist task")?
+        .context(format!("failed to overwrite {}", path.display()))
+}
+
+/// Overwrite the content of the regular files in `path` with zeros and sync them to
+/// the disk. Symlinks are not followed.
+fn zero(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            zero(&entry?.path())?;
+        }
+    } else if metadata.is_file() {
+        let mut file = fs::OpenOptions::new().write(true).open(path)?;
+        let zeros = [0u8; 64 * 1024];
+        let mut remaining = metadata.len();
+        while remaining > 0 {
+            let len = remaining.min(zeros.len() as u64) as usize;
+            file.write_all(&zeros[..len])?;
+            remaining -= len as u64;
+        }
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Sum up the size of all files in `dir`
+fn du(dir: &Path) -> io::Result<u64> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.is_dir() {
+            size += du(&path)?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
@@ -0,0 +1,62 @@
+use super::{
+    config::Config,
+    error::{Context, Error},
+};
+use crate::{common::name::Name, npk::manifest::Manifest};
+use nix::{sys::stat, unistd};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::debug;
+
+/// Directory within the run dir that holds the named pipes shared between containers
+const PIPES_DIR: &str = "pipes";
+
+/// Named pipe on the host that is bind mounted into the containers that declare `name`
+pub(crate) fn path(run_dir: &Path, name: &Name) -> PathBuf {
+    run_dir.join(PIPES_DIR).join(name.to_string())
+}
+
+/// Create the named pipes of `manifest` that do not exist yet. Pipes are shared by
+/// all containers that declare the same name and outlive the containers.
+pub(super) async fn create(config: &Config, manifest: &Manifest) -> Result<(), Error> {
+    let pipes = match &manifest.ipc {
+        Some(ipc) if !ipc.pipes.is_empty() => &ipc.pipes,
+        _ => return Ok(()),
+    };
+
+    let dir = config.run_dir.join(PIPES_DIR);
+    fs::create_dir_all(&dir)
+        .await
+        .context(format!("failed to create {}", dir.display()))?;
+
+    for pipe in pipes {
+        let path = path(&config.run_dir, &pipe.name);
+        if path.exists() {
+            continue;
+        }
+        debug!("Creating pipe {} at {}", pipe.name, path.display());
+        // The mode is masked by the umask of the runtime
+        unistd::mkfifo(&path, stat::Mode::from_bits_truncate(0o666))
+            .context(format!("failed to create pipe {}", path.display()))?;
+        stat::fchmodat(
+            None,
+            &path,
+            stat::Mode::from_bits_truncate(0o666),
+            stat::FchmodatFlags::FollowSymlink,
+        )
+        .context(format!("failed to chmod {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Remove all named pipes
+pub(super) async fn remove(config: &Config) -> Result<(), Error> {
+    let dir = config.run_dir.join(PIPES_DIR);
+    if !dir.exists() {
+        return Ok(());
+    }
+    debug!("Removing pipes in {}", dir.display());
+    fs::remove_dir_all(&dir)
+        .await
+        .context(format!("failed to remove {}", dir.display()))
+}
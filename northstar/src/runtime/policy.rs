@@ -0,0 +1,174 @@
+use super::config;
+use std::collections::HashMap;
+
+/// State of the throttling policies. Policies are activated and deactivated by the
+/// values of external inputs e.g. a thermal zone or the battery level.
+#[derive(Debug)]
+pub(super) struct Policies {
+    policies: HashMap<String, (config::Policy, bool)>,
+}
+
+/// Change of the activation of a policy
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Transition {
+    Activated(String),
+    Deactivated(String),
+}
+
+impl Policies {
+    pub fn new(policies: &HashMap<String, config::Policy>) -> Policies {
+        Policies {
+            policies: policies
+                .iter()
+                .map(|(name, policy)| (name.clone(), (policy.clone(), false)))
+                .collect(),
+        }
+    }
+
+    /// Update the value of `input` and return the resulting transitions. A policy
+    /// that activates on a rising input (`activate` > `deactivate`) is active from
+    /// `activate` until the input falls to `deactivate` and vice versa.
+    pub fn input(&mut self, input: &str, value: i64) -> Vec<Transition> {
+        let mut transitions = Vec::new();
+        for (name, (policy, active)) in self
+            .policies
+            .iter_mut()
+            .filter(|(_, (policy, _))| policy.input == input)
+        {
+            let rising = policy.activate > policy.deactivate;
+            if !*active
+                && ((rising && value >= policy.activate) || (!rising && value <= policy.activate))
+            {
+                *active = true;
+                transitions.push(Transition::Activated(name.clone()));
+            } else if *active
+                && ((rising && value <= policy.deactivate)
+                    || (!rising && value >= policy.deactivate))
+            {
+                *active = false;
+                transitions.push(Transition::Deactivated(name.clone()));
+            }
+        }
+        transitions
+    }
+
+    /// True if at least one policy reads `input`
+    pub fn is_input(&self, input: &str) -> bool {
+        self.policies
+            .values()
+            .any(|(policy, _)| policy.input == input)
+    }
+
+    /// True if at least one policy is active
+    pub fn is_active(&self) -> bool {
+        self.active().next().is_some()
+    }
+
+    /// Lowest cpu bandwidth limit in percent of a cpu of the active policies
+    pub fn cpu_max(&self) -> Option<u32> {
+        self.active().filter_map(|policy| policy.cpu_max).min()
+    }
+
+    /// True if an active policy freezes the deferrable containers
+    pub fn freeze(&self) -> bool {
+        self.active().any(|policy| policy.freeze)
+    }
+
+    fn active(&self) -> impl Iterator<Item = &config::Policy> {
+        self.policies
+            .values()
+            .filter(|(_, active)| *active)
+            .map(|(policy, _)| policy)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+
+    fn policies() -> Policies {
+        let mut policies = HashMap::new();
+        policies.insert(
+            "hot".to_string(),
+            config::Policy {
+                input: "thermal".to_string(),
+                activate: 80,
+                deactivate: 70,
+                cpu_max: Some(50),
+                freeze: false,
+            },
+        );
+        policies.insert(
+            "critical".to_string(),
+            config::Policy {
+                input: "thermal".to_string(),
+                activate: 95,
+                deactivate: 85,
+                cpu_max: Some(10),
+                freeze: true,
+            },
+        );
+        policies.insert(
+            "battery".to_string(),
+            config::Policy {
+                input: "battery".to_string(),
+                activate: 15,
+                deactivate: 20,
+                cpu_max: None,
+                freeze: true,
+            },
+        );
+        Policies::new(&policies)
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut policies = policies();
+        assert!(policies.input("thermal", 79).is_empty());
+        assert_eq!(
+            policies.input("thermal", 80),
+            vec![Transition::Activated("hot".into())]
+        );
+        assert_eq!(policies.cpu_max(), Some(50));
+        assert!(!policies.freeze());
+
+        // Within the hysteresis
+        assert!(policies.input("thermal", 75).is_empty());
+        assert!(policies.input("thermal", 81).is_empty());
+        assert_eq!(policies.cpu_max(), Some(50));
+
+        assert_eq!(
+            policies.input("thermal", 70),
+            vec![Transition::Deactivated("hot".into())]
+        );
+        assert_eq!(policies.cpu_max(), None);
+    }
+
+    #[test]
+    fn falling_input() {
+        let mut policies = policies();
+        assert!(policies.input("battery", 16).is_empty());
+        assert_eq!(
+            policies.input("battery", 15),
+            vec![Transition::Activated("battery".into())]
+        );
+        assert!(policies.freeze());
+        assert!(policies.input("battery", 19).is_empty());
+        assert_eq!(
+            policies.input("battery", 20),
+            vec![Transition::Deactivated("battery".into())]
+        );
+        assert!(!policies.freeze());
+    }
+
+    #[test]
+    fn lowest_limit_wins() {
+        let mut policies = policies();
+        assert_eq!(policies.input("thermal", 100).len(), 2);
+        assert_eq!(policies.cpu_max(), Some(10));
+        assert!(policies.freeze());
+        assert!(policies.is_input("battery"));
+        assert!(!policies.is_input("foo"));
+    }
+}
@@ -0,0 +1,143 @@
+use super::Pid;
+use std::fs;
+
+/// Process of a container
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Process {
+    /// Pid in the pid namespace of the runtime
+    pub pid: Pid,
+    /// Pid of the parent process
+    pub ppid: Pid,
+    /// Command name truncated to 15 characters
+    pub name: String,
+    /// Resident set size in bytes
+    pub rss: u64,
+}
+
+/// Read the processes of `tasks` from `/proc`. Threads and tasks that exited in the
+/// meantime are skipped.
+pub(super) fn list<I: IntoIterator<Item = Pid>>(tasks: I) -> Vec<Process> {
+    tasks
+        .into_iter()
+        .filter_map(|pid| {
+            fs::read_to_string(format!("/proc/{}/status", pid))
+                .ok()
+                .and_then(|status| parse(&status))
+                .filter(|process| process.pid == pid)
+        })
+        .collect()
+}
+
+/// Parse the content of `/proc/<pid>/status`. The pid of a thread is the pid of its
+/// process.
+fn parse(status: &str) -> Option<Process> {
+    let mut name = None;
+    let mut pid = None;
+    let mut ppid = None;
+    let mut rss = 0;
+    for line in status.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key, value.trim()),
+            None => continue,
+        };
+        match key {
+            "Name" => name = Some(value.to_string()),
+            "Tgid" => pid = value.parse().ok(),
+            "PPid" => ppid = value.parse().ok(),
+            // Kernel threads and zombies have no resident set
+            "VmRSS" => {
+                rss = value
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .map(|kb| kb * 1024)
+                    .ok()?
+            }
+            _ => (),
+        }
+    }
+
+    Some(Process {
+        pid: pid?,
+        ppid: ppid?,
+        name: name?,
+        rss,
+    })
+}
+
+/// Supplementary groups of the process `pid` if its effective user and group id are
+/// `uid` and `gid`. The check guards against a pid that is reused after the process
+/// exited.
+pub(super) fn groups(pid: Pid, uid: u32, gid: u32) -> Option<Vec<u32>> {
+    fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| parse_groups(&status, uid, gid))
+}
+
+/// Parse the supplementary groups from the content of `/proc/<pid>/status`
+fn parse_groups(status: &str, uid: u32, gid: u32) -> Option<Vec<u32>> {
+    // Real, effective, saved set and filesystem id
+    let effective = |value: &str| value.split_whitespace().nth(1)?.parse::<u32>().ok();
+    let mut ids = (None, None);
+    let mut groups = None;
+    for line in status.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key, value.trim()),
+            None => continue,
+        };
+        match key {
+            "Uid" => ids.0 = effective(value),
+            "Gid" => ids.1 = effective(value),
+            "Groups" => {
+                groups = value
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<Vec<u32>, _>>()
+                    .ok()
+            }
+            _ => (),
+        }
+    }
+
+    if ids == (Some(uid), Some(gid)) {
+        groups
+    } else {
+        None
+    }
+}
+
+#[test]
+fn parse_status() {
+    let status = "Name:\thello\nUmask:\t0022\nState:\tS (sleeping)\nTgid:\t4242\nNgid:\t0\n\
+        Pid:\t4243\nPPid:\t4200\nVmRSS:\t    1024 kB\nThreads:\t2\n";
+    assert_eq!(
+        parse(status),
+        Some(Process {
+            pid: 4242,
+            ppid: 4200,
+            name: "hello".into(),
+            rss: 1024 * 1024,
+        })
+    );
+
+    // Zombie
+    let status = "Name:\thello\nState:\tZ (zombie)\nTgid:\t4242\nPid:\t4242\nPPid:\t4200\n";
+    assert_eq!(parse(status).map(|process| process.rss), Some(0));
+
+    assert_eq!(parse("Name:\thello\n"), None);
+}
+
+#[test]
+fn parse_groups_status() {
+    let status = "Name:\thello\nUid:\t1000\t1000\t1000\t1000\nGid:\t100\t100\t100\t100\n\
+        FDSize:\t64\nGroups:\t4 24 27 \nNStgid:\t4242\n";
+    assert_eq!(parse_groups(status, 1000, 100), Some(vec![4, 24, 27]));
+
+    // Other process with the same pid
+    assert_eq!(parse_groups(status, 0, 100), None);
+    assert_eq!(parse_groups(status, 1000, 0), None);
+
+    // No supplementary groups
+    let status = "Uid:\t1000\t1000\t1000\t1000\nGid:\t100\t100\t100\t100\nGroups:\t\n";
+    assert_eq!(parse_groups(status, 1000, 100), Some(vec![]));
+}
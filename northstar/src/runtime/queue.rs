@@ -0,0 +1,199 @@
+use super::{ContainerEvent, Event};
+use crate::api::model;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::{
+    select,
+    sync::mpsc::{
+        self,
+        error::{SendError, TrySendError},
+    },
+};
+
+/// Create the queues of the main loop with the given capacities
+pub(super) fn channel(lifecycle: usize, stats: usize, logs: usize) -> (EventTx, EventRx) {
+    let (lifecycle_tx, lifecycle_rx) = mpsc::channel(lifecycle.max(1));
+    let (stats_tx, stats_rx) = mpsc::channel(stats.max(1));
+    let (logs_tx, logs_rx) = mpsc::channel(logs.max(1));
+    let tx = EventTx {
+        lifecycle: Queue::new(lifecycle_tx, lifecycle.max(1)),
+        stats: Queue::new(stats_tx, stats.max(1)),
+        logs: Queue::new(logs_tx, logs.max(1)),
+    };
+    let rx = EventRx {
+        lifecycle: lifecycle_rx,
+        stats: stats_rx,
+        logs: logs_rx,
+    };
+    (tx, rx)
+}
+
+/// Sender of main loop events. Events are queued by their class into separate
+/// bounded queues: A flood of memory events or kernel messages fills its own
+/// queue and never delays console requests or container lifecycle events.
+#[derive(Clone, Debug)]
+pub(super) struct EventTx {
+    /// Console requests, container lifecycle and repository events
+    lifecycle: Queue,
+    /// Cgroup events
+    stats: Queue,
+    /// Kernel messages
+    logs: Queue,
+}
+
+impl EventTx {
+    /// Send `event` and wait for a free slot in the queue of its class
+    pub(super) async fn send(&self, event: Event) -> Result<(), SendError<Event>> {
+        let queue = self.queue(&event);
+        queue.tx.send(event).await?;
+        queue.record();
+        Ok(())
+    }
+
+    /// Send `event` if the queue of its class is not full
+    pub(super) fn try_send(&self, event: Event) -> Result<(), TrySendError<Event>> {
+        let queue = self.queue(&event);
+        queue.tx.try_send(event)?;
+        queue.record();
+        Ok(())
+    }
+
+    /// Wait until the main loop stopped receiving events
+    pub(super) async fn closed(&self) {
+        self.lifecycle.tx.closed().await
+    }
+
+    /// True if the main loop stopped receiving events
+    pub(super) fn is_closed(&self) -> bool {
+        self.lifecycle.tx.is_closed()
+    }
+
+    /// Current and maximum depths of the queues
+    pub(super) fn depths(&self) -> model::EventQueues {
+        model::EventQueues {
+            lifecycle: self.lifecycle.depth(),
+            stats: self.stats.depth(),
+            logs: self.logs.depth(),
+        }
+    }
+
+    fn queue(&self, event: &Event) -> &Queue {
+        match event {
+            Event::Container(_, ContainerEvent::CGroup(_)) => &self.stats,
+            Event::Container(_, ContainerEvent::Log(_) | ContainerEvent::Kernel(_))
+            | Event::Kernel(_) => &self.logs,
+            _ => &self.lifecycle,
+        }
+    }
+}
+
+/// Receiver of main loop events
+#[derive(Debug)]
+pub(super) struct EventRx {
+    lifecycle: mpsc::Receiver<Event>,
+    stats: mpsc::Receiver<Event>,
+    logs: mpsc::Receiver<Event>,
+}
+
+impl EventRx {
+    /// Receive the next event. Lifecycle events are received before cgroup events
+    /// and cgroup events before kernel messages. Cancel safe.
+    pub(super) async fn recv(&mut self) -> Option<Event> {
+        select! {
+            biased;
+            Some(event) = self.lifecycle.recv() => Some(event),
+            Some(event) = self.stats.recv() => Some(event),
+            Some(event) = self.logs.recv() => Some(event),
+            else => None,
+        }
+    }
+}
+
+/// Sending side of a queue with the maximum depth seen
+#[derive(Clone, Debug)]
+struct Queue {
+    tx: mpsc::Sender<Event>,
+    capacity: usize,
+    max_depth: Arc<AtomicUsize>,
+}
+
+impl Queue {
+    fn new(tx: mpsc::Sender<Event>, capacity: usize) -> Queue {
+        Queue {
+            tx,
+            capacity,
+            max_depth: Arc::default(),
+        }
+    }
+
+    /// Update the maximum depth after an event is queued
+    fn record(&self) {
+        let depth = self.capacity - self.tx.capacity();
+        self.max_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    fn depth(&self) -> model::QueueDepth {
+        model::QueueDepth {
+            capacity: self.capacity,
+            depth: self.capacity - self.tx.capacity(),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+    use crate::runtime::{CGroupEvent, MemoryEvent};
+
+    #[tokio::test]
+    async fn priority() {
+        let (tx, mut rx) = channel(4, 4, 4);
+        let container = model::Container::try_from("hello:0.0.1").unwrap();
+        let memory = ContainerEvent::CGroup(CGroupEvent::Memory(MemoryEvent::default()));
+
+        tx.send(Event::Container(container.clone(), memory.clone()))
+            .await
+            .unwrap();
+        tx.send(Event::Container(container.clone(), memory))
+            .await
+            .unwrap();
+        tx.send(Event::Shutdown).await.unwrap();
+
+        let depths = tx.depths();
+        assert_eq!(depths.lifecycle.depth, 1);
+        assert_eq!(depths.stats.depth, 2);
+        assert_eq!(depths.stats.max_depth, 2);
+        assert_eq!(depths.logs.depth, 0);
+
+        // The shutdown overtakes the queued cgroup events
+        assert!(matches!(rx.recv().await, Some(Event::Shutdown)));
+        assert!(matches!(
+            rx.recv().await,
+            Some(Event::Container(_, ContainerEvent::CGroup(_)))
+        ));
+
+        let depths = tx.depths();
+        assert_eq!(depths.stats.depth, 1);
+        assert_eq!(depths.stats.max_depth, 2);
+    }
+
+    #[tokio::test]
+    async fn full() {
+        let (tx, _rx) = channel(1, 1, 1);
+        let container = model::Container::try_from("hello:0.0.1").unwrap();
+        let memory = ContainerEvent::CGroup(CGroupEvent::Memory(MemoryEvent::default()));
+
+        tx.try_send(Event::Container(container.clone(), memory.clone()))
+            .unwrap();
+        assert!(matches!(
+            tx.try_send(Event::Container(container, memory)),
+            Err(TrySendError::Full(_))
+        ));
+        // A full stats queue does not block lifecycle events
+        tx.try_send(Event::Shutdown).unwrap();
+    }
+}
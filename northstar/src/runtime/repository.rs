@@ -1,32 +1,50 @@
 use super::{
+    config::RepositoryKey,
     error::{Context, Error},
-    key::{self, PublicKey},
-    Container,
+    key::{self, Key, PublicKey},
+    Container, Event, EventTx, RepositoryId,
+};
+use crate::{
+    npk::{
+        self,
+        index::{self, Index as SignedIndex},
+        manifest::Manifest,
+        npk::Npk as NpkNpk,
+    },
+    runtime::ipc::RawFdExt,
 };
-use crate::{npk::npk::Npk as NpkNpk, runtime::ipc::RawFdExt};
 use bytes::Bytes;
-use futures::{future::try_join_all, FutureExt};
-use log::{debug, info, warn};
+use futures::{future::try_join_all, FutureExt, StreamExt};
+use inotify::{Inotify, WatchMask};
 use mpsc::Receiver;
 use nanoid::nanoid;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt,
+    fs::Metadata,
     future::ready,
     io::{BufReader, SeekFrom},
-    os::unix::prelude::{AsRawFd, FromRawFd, IntoRawFd},
+    os::unix::prelude::{AsRawFd, FromRawFd, IntoRawFd, MetadataExt},
     path::{Path, PathBuf},
 };
 use tokio::{
     fs::{self},
     io::{AsyncSeekExt, AsyncWriteExt},
+    select,
     sync::mpsc,
     task,
     time::Instant,
 };
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
 pub(super) type Npk = NpkNpk<BufReader<std::fs::File>>;
 
+/// Name of the manifest index of directory repositories
+const INDEX: &str = ".index.json";
+
 #[async_trait::async_trait]
 pub(super) trait Repository: fmt::Debug {
     /// Stream an npk from `rx` into the repository and load it
@@ -35,85 +53,320 @@ pub(super) trait Repository: fmt::Debug {
     /// Add container from repository if present
     async fn remove(&mut self, container: &Container) -> Result<(), Error>;
 
-    /// Return npk matching container if present
-    fn get(&self, container: &Container) -> Option<&Npk>;
+    /// Return npk matching container if present. The npk is opened on first access.
+    fn get(&self, container: &Container) -> Option<Result<&Npk, Error>>;
 
-    /// Key of this repository
-    fn key(&self) -> Option<&PublicKey>;
+    /// Return the manifest of container if present
+    fn manifest(&self, container: &Container) -> Option<&Manifest>;
 
     /// All containers in this repository
-    fn containers(&self) -> Vec<&Npk>;
+    fn containers(&self) -> Vec<Container>;
+
+    /// Add npks that were added to the repository without `insert`. Return the added containers.
+    async fn refresh(&mut self) -> Result<Vec<Container>, Error> {
+        Ok(Vec::new())
+    }
 }
 
-/// Repository backed by a directory
+/// Repository backed by a directory. The manifests of the npks are cached in an index
+/// file within the directory. Npks that are unchanged since they were indexed are
+/// opened and verified on first access only.
 #[derive(Debug)]
 pub(super) struct DirRepository {
     dir: PathBuf,
-    key: Option<PublicKey>,
-    containers: HashMap<Container, (PathBuf, Npk)>,
+    keys: Option<Vec<Key>>,
+    containers: HashMap<Container, DirEntry>,
+    /// Files with a container that is already present
+    duplicates: HashSet<PathBuf>,
+    /// Signed index with npk hashes and revoked containers
+    signed_index: Option<SignedIndex>,
+    /// The index is never written to a read only repository
+    readonly: bool,
+}
+
+/// Npk in a directory repository
+#[derive(Debug)]
+struct DirEntry {
+    path: PathBuf,
+    index: IndexEntry,
+    npk: OnceCell<Npk>,
+    /// Hash of the npk checked against the signed index
+    hash: OnceCell<String>,
+}
+
+/// Cached information of a npk. The entry is valid as long as the file is not modified.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    stat: Stat,
+    manifest: Manifest,
+}
+
+/// File attributes used to detect modifications
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Stat {
+    size: u64,
+    inode: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+}
+
+impl From<&Metadata> for Stat {
+    fn from(metadata: &Metadata) -> Stat {
+        Stat {
+            size: metadata.size(),
+            inode: metadata.ino(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+        }
+    }
 }
 
 impl DirRepository {
-    pub async fn new(dir: &Path, key: Option<&Path>) -> Result<DirRepository, Error> {
+    pub async fn new(
+        dir: &Path,
+        keys: &[RepositoryKey],
+        readonly: bool,
+    ) -> Result<DirRepository, Error> {
         let mut containers = HashMap::new();
 
-        // Load key
-        let key = if let Some(key) = key {
-            info!(
-                "Loading repository {} with key {}",
+        // Load keys
+        let keys = key::load_all(keys).await.map_err(Error::Key)?;
+        match &keys {
+            Some(keys) => info!(
+                "Loading repository {} with {} key(s)",
                 dir.display(),
-                dir.display()
-            );
-            Some(key::load(key).await.map_err(Error::Key)?)
-        } else {
-            info!("Loading repository {} (unverified)", dir.display());
-            None
-        };
+                keys.len()
+            ),
+            None => info!("Loading repository {} (unverified)", dir.display()),
+        }
+        let valid_keys = key::valid(keys.as_deref());
+
+        let signed_index = load_signed_index(dir, valid_keys.as_deref())?;
+
+        let mut index = load_index(dir).await;
 
         let mut readir = fs::read_dir(&dir).await.context("Repository read dir")?;
 
         let start = Instant::now();
         let mut tasks = Vec::new();
+        let mut indexed = 0;
         while let Ok(Some(entry)) = readir.next_entry().await {
             let file = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if is_ignored(&file_name) {
+                continue;
+            }
+
+            let stat = entry
+                .metadata()
+                .await
+                .map(|metadata| Stat::from(&metadata))
+                .context("failed to stat npk")?;
+
+            // Take the manifest from the index if the file is unchanged
+            match index.remove(&file_name) {
+                Some(cached) if cached.stat == stat => {
+                    debug!("Indexed {}", file.display());
+                    let entry = DirEntry {
+                        path: file,
+                        index: cached,
+                        npk: OnceCell::new(),
+                        hash: OnceCell::new(),
+                    };
+                    containers.insert(entry.index.manifest.container(), entry);
+                    indexed += 1;
+                    continue;
+                }
+                _ => (),
+            }
+
+            let valid_keys = valid_keys.clone();
             let load_task = task::spawn_blocking(move || {
                 debug!(
                     "Loading {}{}",
                     file.display(),
-                    if key.is_some() { " [verified]" } else { "" }
+                    if valid_keys.is_some() {
+                        " [verified]"
+                    } else {
+                        ""
+                    }
                 );
                 let reader = std::fs::File::open(&file).context("failed to open npk")?;
                 let reader = std::io::BufReader::new(reader);
-                let npk = NpkNpk::from_reader(reader, key.as_ref())
+                let npk = NpkNpk::from_reader_with_keys(reader, valid_keys.as_deref())
                     .map_err(|e| Error::Npk(file.display().to_string(), e))?;
-                let name = npk.manifest().name.clone();
-                let version = npk.manifest().version.clone();
-                let container = Container::new(name, version);
-                Result::<_, Error>::Ok((container, (file, npk)))
+                let entry = DirEntry {
+                    path: file,
+                    index: IndexEntry {
+                        stat,
+                        manifest: npk.manifest().clone(),
+                    },
+                    npk: OnceCell::with_value(npk),
+                    hash: OnceCell::new(),
+                };
+                Result::<_, Error>::Ok(entry)
             })
             .then(|r| ready(r.expect("Task error")));
 
             tasks.push(load_task);
         }
 
-        for result in try_join_all(tasks).await? {
-            let (container, (file, npk)) = result;
-            containers.insert(container, (file, npk));
+        // Rewrite the index if npks were loaded or removed
+        let update_index = !tasks.is_empty() || !index.is_empty();
+
+        for entry in try_join_all(tasks).await? {
+            containers.insert(entry.index.manifest.container(), entry);
         }
 
         let duration = start.elapsed();
         info!(
-            "Loaded {} containers from {} in {:.03}s",
+            "Loaded {} containers ({} indexed) from {} in {:.03}s",
             containers.len(),
+            indexed,
             dir.display(),
             duration.as_secs_f32(),
         );
 
-        Ok(DirRepository {
+        let repository = DirRepository {
             dir: dir.to_owned(),
-            key,
+            keys,
             containers,
-        })
+            duplicates: HashSet::new(),
+            signed_index,
+            readonly,
+        };
+
+        if update_index {
+            repository.write_index().await;
+        }
+
+        Ok(repository)
+    }
+
+    /// Write the index of all npks. Failing to write the index is not fatal: The npks
+    /// are loaded on the next start. The index of a read only repository is not written.
+    async fn write_index(&self) {
+        if self.readonly {
+            return;
+        }
+        let index = self
+            .containers
+            .values()
+            .filter_map(|entry| Some((entry.path.file_name()?.to_str()?, &entry.index)))
+            .collect::<HashMap<_, _>>();
+        let path = self.dir.join(INDEX);
+        let tmp = self.dir.join(format!("{}.tmp", INDEX));
+        let content = match serde_json::to_vec(&index) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Failed to serialize index {}: {}", path.display(), e);
+                return;
+            }
+        };
+        // Write to a temporary file and rename to replace the index atomically
+        if let Err(e) = fs::write(&tmp, content).await {
+            warn!("Failed to write index {}: {}", tmp.display(), e);
+        } else if let Err(e) = fs::rename(&tmp, &path).await {
+            warn!("Failed to write index {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Watch `dir` for files that are written or moved into it and send a `RepositoryChanged`
+/// event to the main loop until `stop` is cancelled.
+pub(super) fn watch(
+    id: RepositoryId,
+    dir: &Path,
+    tx: EventTx,
+    stop: CancellationToken,
+) -> std::io::Result<()> {
+    let mut inotify = Inotify::init()?;
+    inotify.add_watch(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+    let dir = dir.to_owned();
+
+    task::spawn(async move {
+        debug!("Watching repository {} in {}", id, dir.display());
+        let mut buffer = [0; 1024];
+        let mut stream = match inotify.event_stream(&mut buffer) {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to watch {}: {}", dir.display(), e);
+                return;
+            }
+        };
+
+        loop {
+            select! {
+                _ = stop.cancelled() => break,
+                _ = tx.closed() => break,
+                event = stream.next() => match event {
+                    Some(Ok(event)) => {
+                        let name = event.name.as_ref().map(|name| name.to_string_lossy());
+                        // Skip hidden files e.g. the index or temporary files of copy tools
+                        if name.map(|name| name.starts_with('.')).unwrap_or(true) {
+                            continue;
+                        }
+                        if tx.send(Event::RepositoryChanged(id.clone())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Err(e)) => {
+                        warn!("Failed to watch {}: {}", dir.display(), e);
+                        break;
+                    }
+                    None => break,
+                }
+            }
+        }
+        debug!("Stopped watching repository {}", id);
+    });
+
+    Ok(())
+}
+
+/// True for files in a repository directory that are not npks: The signed index and hidden
+/// files e.g. the manifest cache or temporary files of copy tools
+fn is_ignored(file_name: &str) -> bool {
+    file_name.starts_with('.')
+        || file_name == index::INDEX_NAME
+        || file_name == index::INDEX_SIGNATURE_NAME
+}
+
+/// Read the signed index of the repository in `dir`. The index is only used in verified
+/// repositories.
+fn load_signed_index(dir: &Path, keys: Option<&[PublicKey]>) -> Result<Option<SignedIndex>, Error> {
+    let path = dir.join(index::INDEX_NAME);
+    match keys {
+        Some(keys) => {
+            SignedIndex::from_dir(dir, keys).map_err(|e| Error::Npk(path.display().to_string(), e))
+        }
+        None => {
+            if path.exists() {
+                warn!("Ignoring index {} of unverified repository", path.display());
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Hash of the file backing `npk`
+fn hash_npk(npk: &Npk) -> Result<String, npk::npk::Error> {
+    // Read the file that is opened and not the one that is currently at the path
+    let path = format!("/proc/self/fd/{}", npk.as_raw_fd());
+    let file = std::fs::File::open(&path)
+        .map_err(|e| npk::npk::Error::io(format!("failed to open {}", path), e))?;
+    index::hash(BufReader::new(file))
+}
+
+/// Read the index of the repository in `dir`. An invalid index is discarded.
+async fn load_index(dir: &Path) -> HashMap<String, IndexEntry> {
+    let path = dir.join(INDEX);
+    match fs::read(&path).await {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            warn!("Discarding invalid index {}: {}", path.display(), e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
     }
 }
 
@@ -131,7 +384,9 @@ impl<'a> Repository for DirRepository {
         drop(file);
 
         debug!("Loading temporary npk {}", dest.display());
-        let npk = match Npk::from_path(dest.as_path(), self.key.as_ref())
+        let keys = key::valid(self.keys.as_deref());
+        let npk = match Npk::from_path_with_keys(dest.as_path(), keys.as_deref())
+            .and_then(|mut npk| npk.validate().map(|_| npk))
             .map_err(|e| Error::Npk(dest.display().to_string(), e))
         {
             Ok(n) => Ok(n),
@@ -145,6 +400,22 @@ impl<'a> Repository for DirRepository {
         let container = npk.manifest().container();
         info!("Loaded {} from {}", container, dest.display());
 
+        // Reject revoked containers and npks that do not match the signed index
+        let hash = match &self.signed_index {
+            Some(signed_index) => match hash_npk(&npk)
+                .and_then(|hash| signed_index.check(&container, &hash).map(|_| hash))
+            {
+                Ok(hash) => OnceCell::with_value(hash),
+                Err(e) => {
+                    fs::remove_file(&dest)
+                        .await
+                        .context("Remove file from repository")?;
+                    return Err(Error::Npk(dest.display().to_string(), e));
+                }
+            },
+            None => OnceCell::new(),
+        };
+
         // Check of the container is present
         if self.containers.contains_key(&container) {
             warn!("Container {} is already present in repository", container);
@@ -161,56 +432,179 @@ impl<'a> Repository for DirRepository {
             fs::rename(&old, &new)
                 .await
                 .context("Rename file in repository")?;
-            self.containers.insert(container.clone(), (new, npk));
+            let stat = fs::metadata(&new)
+                .await
+                .map(|metadata| Stat::from(&metadata))
+                .context("failed to stat npk")?;
+            let entry = DirEntry {
+                path: new,
+                index: IndexEntry {
+                    stat,
+                    manifest: npk.manifest().clone(),
+                },
+                npk: OnceCell::with_value(npk),
+                hash,
+            };
+            self.containers.insert(container.clone(), entry);
+            self.write_index().await;
             Ok(container)
         }
     }
 
     async fn remove(&mut self, container: &Container) -> Result<(), Error> {
-        let (path, npk) = self
+        let entry = self
             .containers
             .remove(container)
             .expect("Container not found");
-        debug!("Removing {}", path.display());
-        drop(npk);
-        fs::remove_file(path)
+        debug!("Removing {}", entry.path.display());
+        fs::remove_file(&entry.path)
             .await
-            .context("failed to remove npk")
-            .map(drop)
+            .context("failed to remove npk")?;
+        self.write_index().await;
+        Ok(())
+    }
+
+    fn get(&self, container: &Container) -> Option<Result<&Npk, Error>> {
+        let entry = self.containers.get(container)?;
+        let npk = entry.npk.get_or_try_init(|| {
+            debug!("Opening {}", entry.path.display());
+            let keys = key::valid(self.keys.as_deref());
+            let npk = Npk::from_path_with_keys(&entry.path, keys.as_deref())
+                .map_err(|e| Error::Npk(entry.path.display().to_string(), e))?;
+            // The index is not signed. Ensure that the manifest used so far is the one of the npk.
+            if npk.manifest() != &entry.index.manifest {
+                let error = npk::npk::Error::Manifest(
+                    "manifest does not match the repository index".to_string(),
+                );
+                return Err(Error::Npk(entry.path.display().to_string(), error));
+            }
+            Ok(npk)
+        });
+        let npk = match npk {
+            Ok(npk) => npk,
+            Err(e) => return Some(Err(e)),
+        };
+
+        // Check for revocation and the npk hash
+        if let Some(signed_index) = &self.signed_index {
+            let result = entry
+                .hash
+                .get_or_try_init(|| hash_npk(npk))
+                .and_then(|hash| signed_index.check(container, hash));
+            if let Err(e) = result {
+                return Some(Err(Error::Npk(entry.path.display().to_string(), e)));
+            }
+        }
+
+        Some(Ok(npk))
     }
 
-    fn get(&self, container: &Container) -> Option<&Npk> {
-        self.containers.get(container).map(|(_, npk)| npk)
+    fn manifest(&self, container: &Container) -> Option<&Manifest> {
+        self.containers
+            .get(container)
+            .map(|entry| &entry.index.manifest)
     }
 
-    fn key(&self) -> Option<&PublicKey> {
-        self.key.as_ref()
+    fn containers(&self) -> Vec<Container> {
+        self.containers.keys().cloned().collect()
     }
 
-    fn containers(&self) -> Vec<&Npk> {
-        self.containers.values().map(|(_, npk)| npk).collect()
+    async fn refresh(&mut self) -> Result<Vec<Container>, Error> {
+        // Pick up an updated signed index e.g. with new revocations
+        let keys = key::valid(self.keys.as_deref());
+        match load_signed_index(&self.dir, keys.as_deref()) {
+            Ok(signed_index) => self.signed_index = signed_index,
+            Err(e) => warn!("Keeping the current index of {}: {}", self.dir.display(), e),
+        }
+
+        let known = self
+            .containers
+            .values()
+            .map(|entry| entry.path.clone())
+            .collect::<HashSet<_>>();
+
+        let mut readir = fs::read_dir(&self.dir)
+            .await
+            .context("Repository read dir")?;
+
+        let mut added = Vec::new();
+        while let Ok(Some(entry)) = readir.next_entry().await {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if is_ignored(&file_name) || known.contains(&path) || self.duplicates.contains(&path) {
+                continue;
+            }
+
+            let stat = match entry.metadata().await {
+                Ok(metadata) => Stat::from(&metadata),
+                Err(_) => continue,
+            };
+
+            let keys = keys.clone();
+            let file = path.clone();
+            let npk =
+                task::spawn_blocking(move || Npk::from_path_with_keys(&file, keys.as_deref()))
+                    .await
+                    .expect("Task error");
+            // The file might still be written. It's retried once it's modified again.
+            let npk = match npk {
+                Ok(npk) => npk,
+                Err(e) => {
+                    warn!("Failed to load {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let container = npk.manifest().container();
+            if self.containers.contains_key(&container) {
+                warn!(
+                    "Skipping {} with duplicate container {}",
+                    path.display(),
+                    container
+                );
+                self.duplicates.insert(path);
+                continue;
+            }
+
+            info!("Discovered {} in {}", container, path.display());
+            let entry = DirEntry {
+                path,
+                index: IndexEntry {
+                    stat,
+                    manifest: npk.manifest().clone(),
+                },
+                npk: OnceCell::with_value(npk),
+                hash: OnceCell::new(),
+            };
+            self.containers.insert(container.clone(), entry);
+            added.push(container);
+        }
+
+        if !added.is_empty() {
+            self.write_index().await;
+        }
+
+        Ok(added)
     }
 }
 
 /// In memory repository
 #[derive(Debug)]
 pub(super) struct MemRepository {
-    key: Option<PublicKey>,
+    keys: Option<Vec<Key>>,
     containers: HashMap<Container, Npk>,
 }
 
 impl MemRepository {
-    pub async fn new(key: Option<&Path>) -> Result<MemRepository, Error> {
-        let key = if let Some(key) = key {
-            info!("Loading memory repository with key {}", key.display());
-            Some(key::load(key).await.map_err(Error::Key)?)
-        } else {
-            info!("Loading repository (unverified)");
-            None
-        };
+    pub async fn new(keys: &[RepositoryKey]) -> Result<MemRepository, Error> {
+        let keys = key::load_all(keys).await.map_err(Error::Key)?;
+        match &keys {
+            Some(keys) => info!("Loading memory repository with {} key(s)", keys.len()),
+            None => info!("Loading repository (unverified)"),
+        }
 
         Ok(MemRepository {
-            key,
+            keys,
             containers: HashMap::new(),
         })
     }
@@ -253,7 +647,9 @@ impl<'a> Repository for MemRepository {
 
         // Load npk
         debug!("Loading memfd as npk");
-        let npk = NpkNpk::from_reader(file, self.key.as_ref())
+        let keys = key::valid(self.keys.as_deref());
+        let npk = NpkNpk::from_reader_with_keys(file, keys.as_deref())
+            .and_then(|mut npk| npk.validate().map(|_| npk))
             .map_err(|e| Error::Npk("memory".into(), e))?;
         let container = npk.manifest().container();
         info!("Loaded {} from memfd", container);
@@ -276,15 +672,15 @@ impl<'a> Repository for MemRepository {
         Ok(())
     }
 
-    fn get(&self, container: &Container) -> Option<&Npk> {
-        self.containers.get(container)
+    fn get(&self, container: &Container) -> Option<Result<&Npk, Error>> {
+        self.containers.get(container).map(Ok)
     }
 
-    fn containers(&self) -> Vec<&Npk> {
-        self.containers.values().collect()
+    fn manifest(&self, container: &Container) -> Option<&Manifest> {
+        self.containers.get(container).map(|npk| npk.manifest())
     }
 
-    fn key(&self) -> Option<&PublicKey> {
-        self.key.as_ref()
+    fn containers(&self) -> Vec<Container> {
+        self.containers.keys().cloned().collect()
     }
 }
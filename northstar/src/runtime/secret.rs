@@ -0,0 +1,154 @@
+use super::{
+    config::Config,
+    error::{Context, Error},
+    parameters::Provider,
+};
+use crate::{
+    common::container::Container,
+    npk::manifest::{mount::Mount, Manifest},
+};
+use nix::{
+    mount::{self, MntFlags, MsFlags},
+    unistd,
+};
+use std::path::{Path, PathBuf};
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::debug;
+
+/// Directory within the run dir that holds the secret tmpfs of each container
+const SECRETS_DIR: &str = "secrets";
+
+/// Page size used to calculate the size of the tmpfs
+const PAGE_SIZE: usize = 4096;
+
+/// Directory on the host that is bind mounted to `target` in the container
+pub(crate) fn dir(run_dir: &Path, container: &Container, target: &Path) -> PathBuf {
+    // Escape the target to get a unique directory name
+    let name = target
+        .display()
+        .to_string()
+        .trim_start_matches('/')
+        .replace('%', "%25")
+        .replace('/', "%2F");
+    root(run_dir, container).join(name)
+}
+
+/// Mount a tmpfs for `manifest` and write the secrets of each secret mount with
+/// mode 0400 and the uid and gid of the container. The tmpfs is remounted read only.
+pub(super) async fn create(
+    config: &Config,
+    container: &Container,
+    manifest: &Manifest,
+    provider: &dyn Provider,
+) -> Result<(), Error> {
+    let mounts = manifest
+        .mounts
+        .iter()
+        .filter_map(|(target, mount)| match mount {
+            Mount::Secret(secret) => Some((target, secret)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if mounts.is_empty() {
+        return Ok(());
+    }
+
+    // Remove left overs of a previous instance
+    remove(config, container).await?;
+
+    // Fetch the secrets before anything is mounted
+    let mut secrets = Vec::new();
+    for (target, secret) in mounts {
+        for name in &secret.secrets {
+            let content = provider.secret(name).ok_or_else(|| {
+                Error::StartContainerFailed(container.clone(), format!("missing secret {}", name))
+            })?;
+            secrets.push((target, name, content));
+        }
+    }
+
+    let root = root(&config.run_dir, container);
+    let size = secrets
+        .iter()
+        .map(|(_, _, content)| (content.len() / PAGE_SIZE + 1) * PAGE_SIZE)
+        .sum::<usize>()
+        + PAGE_SIZE;
+    let uid = unistd::Uid::from_raw(manifest.uid.into());
+    let gid = unistd::Gid::from_raw(manifest.gid.into());
+
+    debug!(
+        "Mounting secrets tmpfs of {} on {}",
+        container,
+        root.display()
+    );
+    fs::create_dir_all(&root)
+        .await
+        .context(format!("failed to create {}", root.display()))?;
+    let flags = MsFlags::MS_NODEV | MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC;
+    let data = format!("size={},mode=0500,uid={},gid={}", size, uid, gid);
+    mount::mount(
+        Some("tmpfs"),
+        &root,
+        Some("tmpfs"),
+        flags,
+        Some(data.as_str()),
+    )
+    .context(format!("failed to mount tmpfs on {}", root.display()))?;
+
+    for (target, name, content) in secrets {
+        let dir = dir(&config.run_dir, container, target);
+        if !dir.exists() {
+            fs::DirBuilder::new()
+                .mode(0o500)
+                .create(&dir)
+                .await
+                .context(format!("failed to create {}", dir.display()))?;
+            unistd::chown(&dir, Some(uid), Some(gid))
+                .context(format!("failed to chown {}", dir.display()))?;
+        }
+
+        let file = dir.join(name);
+        debug!("Writing secret {} of {}", name, container);
+        let mut f = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o400)
+            .open(&file)
+            .await
+            .context(format!("failed to create {}", file.display()))?;
+        f.write_all(&content)
+            .await
+            .context(format!("failed to write {}", file.display()))?;
+        unistd::chown(&file, Some(uid), Some(gid))
+            .context(format!("failed to chown {}", file.display()))?;
+    }
+
+    mount::mount(
+        None::<&str>,
+        &root,
+        None::<&str>,
+        flags | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+        None::<&str>,
+    )
+    .context(format!("failed to remount {} read only", root.display()))
+}
+
+/// Umount and remove the secrets tmpfs of `container` if present
+pub(super) async fn remove(config: &Config, container: &Container) -> Result<(), Error> {
+    let root = root(&config.run_dir, container);
+    if !root.exists() {
+        return Ok(());
+    }
+
+    debug!("Removing secrets of {}", container);
+    // The tmpfs is not mounted if a previous instance failed in between
+    mount::umount2(&root, MntFlags::MNT_DETACH).ok();
+    fs::remove_dir_all(&root)
+        .await
+        .context(format!("failed to remove {}", root.display()))
+}
+
+fn root(run_dir: &Path, container: &Container) -> PathBuf {
+    run_dir.join(SECRETS_DIR).join(container.to_string())
+}
@@ -0,0 +1,101 @@
+use super::{
+    config::Config,
+    error::{Context, Error},
+};
+use crate::{common::name::Name, npk::manifest::Manifest};
+use nix::{
+    mount::{self, MntFlags, MsFlags},
+    unistd,
+};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::debug;
+
+/// Directory within the run dir that holds the tmpfs with the shared memory segments
+const SHM_DIR: &str = "shm";
+
+/// Segment `name` of the container `owner` on the host
+pub(crate) fn path(run_dir: &Path, owner: &Name, name: &Name) -> PathBuf {
+    run_dir
+        .join(SHM_DIR)
+        .join(owner.to_string())
+        .join(name.to_string())
+}
+
+/// Create the shared memory segments owned by `manifest` that do not exist yet.
+/// The segments are files with mode 0644 and the uid and gid of the owner on a
+/// tmpfs that is mounted on first use. The segments outlive the containers.
+pub(super) async fn create(config: &Config, manifest: &Manifest) -> Result<(), Error> {
+    let segments = manifest
+        .ipc
+        .iter()
+        .flat_map(|ipc| &ipc.shm)
+        .filter_map(|shm| shm.size.map(|size| (&shm.name, size)))
+        .collect::<Vec<_>>();
+    if segments.is_empty() {
+        return Ok(());
+    }
+
+    let root = config.run_dir.join(SHM_DIR);
+    if !root.exists() {
+        debug!("Mounting shm tmpfs on {}", root.display());
+        fs::create_dir_all(&root)
+            .await
+            .context(format!("failed to create {}", root.display()))?;
+        let flags = MsFlags::MS_NODEV | MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC;
+        mount::mount(
+            Some("tmpfs"),
+            &root,
+            Some("tmpfs"),
+            flags,
+            Some("mode=0755"),
+        )
+        .context(format!("failed to mount tmpfs on {}", root.display()))?;
+    }
+
+    let uid = unistd::Uid::from_raw(manifest.uid.into());
+    let gid = unistd::Gid::from_raw(manifest.gid.into());
+    let dir = root.join(manifest.name.to_string());
+    fs::create_dir_all(&dir)
+        .await
+        .context(format!("failed to create {}", dir.display()))?;
+
+    for (name, size) in segments {
+        let path = path(&config.run_dir, &manifest.name, name);
+        if path.exists() {
+            continue;
+        }
+        debug!(
+            "Creating shm {} of {} with size {}",
+            name,
+            manifest.name,
+            bytesize::ByteSize::b(size)
+        );
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o644)
+            .open(&path)
+            .await
+            .context(format!("failed to create {}", path.display()))?;
+        file.set_len(size)
+            .await
+            .context(format!("failed to resize {}", path.display()))?;
+        unistd::chown(&path, Some(uid), Some(gid))
+            .context(format!("failed to chown {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Umount and remove the shm tmpfs
+pub(super) async fn remove(config: &Config) -> Result<(), Error> {
+    let root = config.run_dir.join(SHM_DIR);
+    if !root.exists() {
+        return Ok(());
+    }
+    debug!("Removing shm in {}", root.display());
+    mount::umount2(&root, MntFlags::MNT_DETACH).ok();
+    fs::remove_dir_all(&root)
+        .await
+        .context(format!("failed to remove {}", root.display()))
+}
@@ -0,0 +1,46 @@
+use super::mount::Error;
+use std::{path::Path, process::Stdio};
+use tokio::{fs, process::Command};
+use tracing::debug;
+
+/// Squashfs extraction tool
+const UNSQUASHFS: &str = "unsquashfs";
+
+/// Extract the squashfs image located at `offset` in `image` into `target`. This
+/// replaces the loop and verity devices of a mount on a Linux development host.
+pub(super) async fn extract(image: &Path, offset: u64, target: &Path) -> Result<(), Error> {
+    let mut cmd = Command::new(UNSQUASHFS);
+    cmd.arg("-no-progress")
+        .arg("-force")
+        .arg("-offset")
+        .arg(offset.to_string())
+        .arg("-dest")
+        .arg(target)
+        .arg(image);
+
+    debug!("Running {:?}", cmd);
+    let output = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| Error::Io(format!("failed to run {:?}", cmd), e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::Simulation(format!(
+            "{:?} failed: {}: {}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Remove the extracted image in `target`
+pub(super) async fn remove(target: &Path) -> Result<(), Error> {
+    fs::remove_dir_all(target)
+        .await
+        .map_err(|e| Error::Io(format!("failed to remove {}", target.display()), e))
+}
@@ -0,0 +1,85 @@
+use super::{
+    error::{Context, Error},
+    Container,
+};
+use crate::common::{name::Name, non_nul_string::NonNulString};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+use tracing::debug;
+
+/// Filename of the snapshot in the run dir
+const SNAPSHOT: &str = "northstar.state";
+
+/// Set of mounted and started containers. The snapshot is written to the run dir
+/// whenever the state changes and removed on a clean shutdown. A snapshot found
+/// on startup indicates that the previous runtime instance did not shut down
+/// cleanly.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct Snapshot {
+    /// Mounted containers
+    pub mounted: Vec<Container>,
+    /// Started containers
+    pub started: Vec<Started>,
+}
+
+/// Started container with the arguments, environment and template parameters passed
+/// in the start request
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Started {
+    pub container: Container,
+    pub args: Vec<NonNulString>,
+    pub env: HashMap<NonNulString, NonNulString>,
+    #[serde(default)]
+    pub parameters: HashMap<Name, NonNulString>,
+}
+
+impl Snapshot {
+    /// Load a snapshot from `run_dir` if present
+    pub(super) async fn load(run_dir: &Path) -> Result<Option<Snapshot>, Error> {
+        let path = path(run_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        debug!("Loading snapshot {}", path.display());
+        let content = fs::read(&path)
+            .await
+            .context(format!("failed to read {}", path.display()))?;
+        serde_json::from_slice(&content)
+            .map(Some)
+            .context(format!("failed to parse {}", path.display()))
+    }
+
+    /// Write the snapshot to `run_dir`. The file is replaced atomically.
+    pub(super) async fn save(&self, run_dir: &Path) -> Result<(), Error> {
+        let path = path(run_dir);
+        let tmp = path.with_extension("tmp");
+        let content = serde_json::to_vec(self).context("failed to serialize snapshot")?;
+        fs::write(&tmp, content)
+            .await
+            .context(format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path)
+            .await
+            .context(format!("failed to rename {}", tmp.display()))
+    }
+
+    /// Remove the snapshot from `run_dir`
+    pub(super) async fn remove(run_dir: &Path) -> Result<(), Error> {
+        let path = path(run_dir);
+        if path.exists() {
+            debug!("Removing snapshot {}", path.display());
+            fs::remove_file(&path)
+                .await
+                .context(format!("failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn path(run_dir: &Path) -> PathBuf {
+    run_dir.join(SNAPSHOT)
+}
@@ -0,0 +1,73 @@
+use super::{
+    config::Config,
+    error::{Context, Error},
+};
+use crate::{common::name::Name, npk::manifest::Manifest};
+use nix::unistd;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::debug;
+
+/// Directory within the run dir that holds the socket directories of the containers
+const SOCKETS_DIR: &str = "sockets";
+
+/// Directory on the host that is mounted to `socket` in the container `name` and
+/// in all containers that link it
+pub(crate) fn dir(run_dir: &Path, name: &Name, socket: &Path) -> PathBuf {
+    // Escape the socket to get a unique directory name
+    let socket = socket
+        .display()
+        .to_string()
+        .trim_start_matches('/')
+        .replace('%', "%25")
+        .replace('/', "%2F");
+    run_dir
+        .join(SOCKETS_DIR)
+        .join(name.to_string())
+        .join(socket)
+}
+
+/// Create the socket directories of `manifest` that do not exist yet with mode 0755
+/// and the uid and gid of the container. The directories outlive the container
+/// in order to keep the links of other containers intact across restarts.
+pub(super) async fn create(config: &Config, manifest: &Manifest) -> Result<(), Error> {
+    let sockets = match &manifest.ipc {
+        Some(ipc) => &ipc.sockets,
+        None => return Ok(()),
+    };
+
+    let uid = unistd::Uid::from_raw(manifest.uid.into());
+    let gid = unistd::Gid::from_raw(manifest.gid.into());
+    for socket in sockets {
+        let dir = dir(&config.run_dir, &manifest.name, socket);
+        if dir.exists() {
+            continue;
+        }
+        debug!(
+            "Creating socket directory {} of {}",
+            dir.display(),
+            manifest.name
+        );
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o755)
+            .create(&dir)
+            .await
+            .context(format!("failed to create {}", dir.display()))?;
+        unistd::chown(&dir, Some(uid), Some(gid))
+            .context(format!("failed to chown {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Remove all socket directories
+pub(super) async fn remove(config: &Config) -> Result<(), Error> {
+    let dir = config.run_dir.join(SOCKETS_DIR);
+    if !dir.exists() {
+        return Ok(());
+    }
+    debug!("Removing sockets in {}", dir.display());
+    fs::remove_dir_all(&dir)
+        .await
+        .context(format!("failed to remove {}", dir.display()))
+}
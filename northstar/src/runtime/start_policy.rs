@@ -0,0 +1,46 @@
+use crate::{api::model::Container, npk::manifest::Manifest};
+use std::{fmt::Debug, time::Duration};
+
+/// Origin of a container start
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// Autostart of the container after the repositories are loaded
+    Autostart,
+    /// Restart of the container by its oom policy
+    Restart,
+    /// Start request of a console or an embedder
+    Request,
+}
+
+/// Decision of a start policy about a container start
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Start the container
+    Start,
+    /// Reject the start with a reason that is reported to the requester
+    Reject(String),
+    /// Ask the policy again after the duration. The requester of a deferred start
+    /// gets the response once the container is started or rejected.
+    Delay(Duration),
+}
+
+/// Policy that decides about container starts e.g. based on the state of the
+/// device. Implement this trait to approve, delay or reorder starts from an
+/// embedding process. The policy is called from the main loop of the runtime and
+/// must not block.
+pub trait StartPolicy: Debug + Send + Sync {
+    /// Decide about the start of `container` with the manifest `manifest`
+    fn check(&self, _container: &Container, _manifest: &Manifest, _origin: Origin) -> Decision {
+        Decision::Start
+    }
+
+    /// Order the containers of an autostart wave. The containers of a wave don't
+    /// depend on each other and are started in the order of `containers`.
+    fn order(&self, _containers: &mut [Container]) {}
+}
+
+/// Start policy that starts every container in the default order
+#[derive(Debug, Default)]
+pub struct PassThrough;
+
+impl StartPolicy for PassThrough {}
@@ -1,99 +1,287 @@
 use super::{
-    cgroups,
-    config::{Config, RepositoryType},
+    bpf, browse, cgroups, clock,
+    config::{self, Config, RepositoryType, Restore},
     console::Request,
+    core_dump, data, dbus,
     error::Error,
+    events::{RuntimeEvent, Subscribers},
     fork::Forker,
+    handoff::{self, Handoff},
+    installs::Installs,
     io,
-    mount::MountControl,
-    repository::{DirRepository, MemRepository, Npk},
+    kmsg::{KernelMessage, KernelMessageKind},
+    mount::{self, MountControl},
+    network::Network,
+    overlay,
+    parameters::{self, Provider},
+    pending::Pending,
+    persist, pipe,
+    policy::{Policies, Transition},
+    process,
+    repository::{self, DirRepository, MemRepository, Npk},
+    secret, shm,
+    snapshot::{Snapshot, Started},
+    socket,
+    start_policy::{Decision, Origin, StartPolicy},
     stats::ContainerStats,
-    Container, ContainerEvent, Event, EventTx, ExitStatus, NotificationTx, Pid, RepositoryId,
+    system, Container, ContainerEvent, Event, EventTx, ExitStatus, NotificationTx, Pid,
+    RepositoryId,
 };
 use crate::{
     api::{self, model},
     common::{name::Name, non_nul_string::NonNulString, version::VersionReq},
-    npk::manifest::{
-        mount::{Mount, Resource},
-        Autostart, Manifest,
-    },
+    npk::manifest::{mount::Mount, Autostart, Manifest, OomPolicy},
     runtime::{
         console::{Console, Peer},
+        error::Context,
         io::ContainerIo,
         ipc::owned_fd::OwnedFd,
-        CGroupEvent, ENV_CONSOLE, ENV_CONTAINER, ENV_NAME, ENV_VERSION,
+        CGroupEvent, MemoryEvent, ENV_CONSOLE, ENV_CONTAINER, ENV_NAME, ENV_VERSION,
     },
+    seccomp,
 };
 use bytes::Bytes;
 use futures::{
-    future::{join_all, ready, Either},
-    Future, FutureExt, Stream, StreamExt, TryFutureExt,
+    future::{join_all, pending, ready, Either},
+    stream, Future, Stream, StreamExt,
 };
 use humantime::format_duration;
 use itertools::Itertools;
-use log::{debug, error, info, warn};
-use nix::sys::signal::Signal;
+use nix::{sys::signal::Signal, unistd};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
     fmt::Debug,
     iter::{once, FromIterator},
-    os::unix::net::UnixStream as StdUnixStream,
-    path::PathBuf,
+    os::unix::{
+        io::{FromRawFd, IntoRawFd},
+        net::UnixStream as StdUnixStream,
+    },
+    path::{Path, PathBuf},
     result,
+    str::FromStr,
     sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::{
+    fs,
     net::UnixStream,
-    pin,
+    pin, select,
     sync::{mpsc, oneshot},
     task::{self, JoinHandle},
     time,
 };
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
+
+/// Number of exits kept in the history of a container
+const EXIT_HISTORY: usize = 10;
+
+/// Default maximum number of consecutive restarts by the oom policy
+const OOM_RESTARTS: u32 = 3;
 
 /// Repository
-type Repository = Box<dyn super::repository::Repository + Send + Sync>;
+pub(super) type Repository = Box<dyn super::repository::Repository + Send + Sync>;
 
 #[derive(Debug)]
 pub(super) struct State {
     config: Config,
+    provider: Arc<dyn Provider>,
+    start_policy: Arc<dyn StartPolicy>,
     events_tx: EventTx,
     notification_tx: NotificationTx,
+    /// Console installs passed to the container consoles
+    installs: Installs,
+    /// Cancellable console requests passed to the container consoles
+    pending: Pending,
+    /// In-process subscribers of the embedder
+    subscribers: Subscribers,
     mount_control: Arc<MountControl>,
     launcher: Forker,
     containers: HashMap<Container, ContainerState>,
     repositories: HashMap<RepositoryId, Repository>,
+    /// Repositories that are loaded in the background
+    loading: HashSet<RepositoryId>,
+    /// Loaded repositories that wait for the repositories with a higher priority
+    loaded: HashMap<RepositoryId, Repository>,
+    /// Forced uninstalls that wait for the exit of the containers using the container
+    uninstalls: Vec<(Container, bool, oneshot::Sender<model::Response>)>,
+    /// Containers that are mounted in the background
+    mounting: HashSet<Container>,
+    /// Starts that wait for background mounts of their container or resources
+    mount_waiting: Vec<DeferredStart>,
+    /// Snapshot of a previous instance that is restored once all repositories are loaded
+    snapshot: Option<Snapshot>,
+    /// Containers with the autostart flag that are handled and whether they started
+    autostarted: HashMap<Container, bool>,
+    /// Stop the watchers of repository directories
+    watch: CancellationToken,
+    /// Containers frozen by a system suspend
+    suspended: Option<Vec<Container>>,
+    /// Throttling policies
+    policies: Policies,
+    /// Deferrable containers frozen by a throttling policy
+    throttled: HashSet<Container>,
+    /// Loop and verity devices that were unused at the last audit for leftovers
+    unused_devices: HashSet<String>,
+    /// Number of starts that are deferred by the start policy
+    deferred: usize,
+    /// Containers of a previous instance that are taken over once their repository
+    /// is loaded
+    adopting: Vec<handoff::Entry>,
+}
+
+/// Container start that is deferred by the start policy
+#[derive(Debug)]
+pub(super) struct DeferredStart {
+    container: Container,
+    args: Vec<NonNulString>,
+    env: HashMap<NonNulString, NonNulString>,
+    parameters: HashMap<Name, NonNulString>,
+    origin: Origin,
+    /// Requester waiting for the response
+    response: Option<oneshot::Sender<model::Response>>,
+}
+
+/// Containers mounted in the background
+#[derive(Debug)]
+pub(super) struct Mounts {
+    /// Containers of the operation
+    containers: Vec<Container>,
+    /// Containers with a mount attempt. The others failed before the mount.
+    mounting: Vec<Container>,
+    /// Mount results in the order of `containers`
+    results: Vec<Result<PathBuf, Error>>,
+    /// Operation that continues once the mounts completed
+    then: AfterMount,
+}
+
+/// Operation that waits for background mounts
+#[derive(Debug)]
+enum AfterMount {
+    /// Respond to a mount request
+    Request(oneshot::Sender<model::Response>),
+    /// Continue a start that needs mounts of the container or its resources
+    Start(DeferredStart),
+}
+
+impl DeferredStart {
+    /// Start of `container` with the arguments and environment of its manifest
+    fn new(container: Container, origin: Origin) -> DeferredStart {
+        DeferredStart {
+            container,
+            args: Vec::with_capacity(0),
+            env: HashMap::with_capacity(0),
+            parameters: HashMap::with_capacity(0),
+            origin,
+            response: None,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
 pub(super) struct ContainerState {
     /// Reference to the repository where the npk resides
     pub repository: RepositoryId,
+    /// Repositories with a lower priority that contain the container as well
+    pub shadowed: Vec<RepositoryId>,
     /// Mount point of the root fs
     pub root: Option<PathBuf>,
     /// Process information when started
     pub process: Option<ContainerContext>,
+    /// Phases of the last successful start
+    pub start_timings: Option<model::StartTimings>,
+    /// Restarts and last exits
+    pub history: model::ExitHistory,
+    /// Mounted for a start and umounted once idle if `idle_umount` is configured
+    pub mounted_for_start: bool,
+    /// Exit of the last process that used the container
+    pub last_used: Option<time::Instant>,
+    /// Start the container again after it exited because of the oom policy
+    pub oom_restart: bool,
+    /// Consecutive restarts because of the oom policy
+    pub oom_restarts: u32,
 }
 
 impl ContainerState {
     pub fn is_mounted(&self) -> bool {
         self.root.is_some()
     }
+
+    /// Record an exit and drop the oldest exits exceeding `EXIT_HISTORY`
+    fn record_exit(&mut self, exit: model::ContainerExit) {
+        let exits = &mut self.history.exits;
+        exits.push(exit);
+        if exits.len() > EXIT_HISTORY {
+            exits.drain(..exits.len() - EXIT_HISTORY);
+        }
+    }
+
+    /// Request a restart after the exit unless `max` consecutive restarts are reached
+    fn request_oom_restart(&mut self, max: u32) -> bool {
+        self.oom_restart = self.oom_restarts < max;
+        self.oom_restart
+    }
+
+    /// Cancel a pending restart e.g. because the container is stopped
+    fn cancel_restart(&mut self) {
+        self.oom_restart = false;
+    }
+
+    /// Take a pending restart and count it
+    fn take_restart(&mut self) -> bool {
+        let restart = std::mem::take(&mut self.oom_restart);
+        if restart {
+            self.oom_restarts += 1;
+        }
+        restart
+    }
 }
 
 #[derive(Debug)]
 pub(super) struct ContainerContext {
     pid: Pid,
+    /// Pid of the forker that created the container
+    forker: Pid,
     started: time::Instant,
     debug: super::debug::Debug,
     cgroups: cgroups::CGroups,
     stop: CancellationToken,
-    log_task: Option<JoinHandle<std::io::Result<()>>>,
+    log_task: Option<JoinHandle<std::io::Result<Option<io::PipedOutput>>>>,
+    /// Stop the output task for a handoff
+    handoff: CancellationToken,
+    /// Flag set if the cgroup of this container is frozen
+    paused: bool,
+    /// Number of oom kills reported by the memory cgroup
+    oom_kills: u64,
+    /// Flag set if a process of this container was killed by the oom killer
+    oom: bool,
+    /// Signals sent by the runtime to the processes of this container
+    signals: HashSet<Signal>,
+    /// Syscall of the last seccomp violation reported by the kernel
+    syscall: Option<u32>,
+    /// Reply to a data reset request that is executed once the container exited
+    data_reset: Option<oneshot::Sender<model::Response>>,
+    /// Arguments passed with the start request
+    args_extra: Vec<NonNulString>,
+    /// Environment passed with the start request
+    env_extra: HashMap<NonNulString, NonNulString>,
+    /// Template parameters passed with the start request
+    parameters: HashMap<Name, NonNulString>,
     /// Resources used by this container. This list differs from
     /// manifest because the manifest just containers version
     /// requirements and not concrete resources.
     resources: HashSet<Container>,
+    /// File the init process writes a minidump of the crashed application to
+    minidump: Option<std::fs::File>,
+    /// Attachments to the PTY of containers with `io.tty`
+    pty: Option<mpsc::Sender<io::Attachment>>,
+    /// Input to the stdin of containers with `io.stdin`
+    stdin: Option<mpsc::Sender<Bytes>>,
+    /// D-Bus proxy of containers with `ipc.dbus`
+    dbus: Option<dbus::Proxy>,
+    /// Network namespace of containers with `network`
+    network: Option<Network>,
 }
 
 impl ContainerContext {
@@ -111,17 +299,109 @@ impl ContainerContext {
             .await
             .expect("failed to destroy debug utilities");
 
+        if let Some(dbus) = self.dbus.take() {
+            if let Err(e) = dbus.destroy().await {
+                warn!("failed to destroy dbus proxy: {}", e);
+            }
+        }
+
+        if let Some(network) = self.network.take() {
+            network.destroy();
+        }
+
         self.cgroups.destroy().await;
     }
+
+    /// Stop the tasks of the container and move the process state and the fds that
+    /// are passed to the next instance into `handoff`
+    async fn handoff(mut self, handoff: &mut Handoff) -> handoff::Process {
+        self.stop.cancel();
+        self.handoff.cancel();
+
+        let output = match self.log_task.take() {
+            Some(log_task) => match log_task.await {
+                Ok(Ok(Some(output))) => Some((handoff.pass(output.stream), output.buffer)),
+                Ok(Err(e)) => {
+                    warn!("failed to forward the output of {}: {}", self.pid, e);
+                    None
+                }
+                _ => None,
+            },
+            None => None,
+        };
+
+        self.cgroups.handoff().await;
+
+        let network = self.network.take().map(|network| {
+            let (namespace, interfaces) = network.handoff();
+            (handoff.pass(namespace), interfaces)
+        });
+        let minidump = self.minidump.take().map(|file| handoff.pass(file));
+
+        handoff::Process {
+            pid: self.pid,
+            forker: self.forker,
+            uptime: self.started.elapsed(),
+            paused: self.paused,
+            oom_kills: self.oom_kills,
+            oom: self.oom,
+            signals: self.signals.iter().map(|signal| *signal as i32).collect(),
+            syscall: self.syscall,
+            args: self.args_extra,
+            env: self.env_extra,
+            parameters: self.parameters,
+            resources: self.resources.into_iter().collect(),
+            output,
+            minidump,
+            network,
+        }
+    }
+
+    /// Determine the cause of a terminating signal from the seccomp violations and the
+    /// oom kills of the container and the signals sent by the runtime
+    fn exit_status(&mut self, exit_status: &ExitStatus) -> ExitStatus {
+        let signal = match exit_status {
+            ExitStatus::Exit(_) => return exit_status.clone(),
+            ExitStatus::Signalled(signal, _) => *signal,
+        };
+
+        // The memory events of the cgroup are possibly not yet processed
+        if let Some(oom_kills) = self.cgroups.oom_kills() {
+            if oom_kills > self.oom_kills {
+                self.oom_kills = oom_kills;
+                self.oom = true;
+            }
+        }
+
+        // A kill by the oom policy is an oom kill
+        let cause = match Signal::try_from(signal as i32) {
+            Ok(Signal::SIGSYS) if !self.signals.contains(&Signal::SIGSYS) => {
+                model::SignalCause::Seccomp {
+                    syscall: self.syscall,
+                }
+            }
+            Ok(Signal::SIGKILL) if self.oom => model::SignalCause::Oom,
+            Ok(signal) if self.signals.contains(&signal) => model::SignalCause::Runtime,
+            _ => model::SignalCause::External,
+        };
+        ExitStatus::Signalled(signal, cause)
+    }
 }
 
 impl State {
     /// Create a new empty State instance
+    #[allow(clippy::too_many_arguments)]
     pub(super) async fn new(
         config: Config,
+        provider: Arc<dyn Provider>,
+        start_policy: Arc<dyn StartPolicy>,
         events_tx: EventTx,
         notification_tx: NotificationTx,
+        installs: Installs,
+        pending: Pending,
+        subscribers: Subscribers,
         forker: Forker,
+        handoff: Option<handoff::State>,
     ) -> Result<State, Error> {
         let repositories = HashMap::new();
         let containers = HashMap::new();
@@ -129,76 +409,534 @@ impl State {
             MountControl::new(
                 config.device_mapper_device_timeout,
                 config.loop_device_timeout,
+                config.mount_backend,
+                config.verity_cache.as_deref(),
             )
             .await
             .expect("failed to initialize mount control"),
         );
 
+        let policies = Policies::new(&config.policies);
+
         let mut state = State {
             events_tx,
             notification_tx,
+            installs,
+            pending,
+            subscribers,
             repositories,
             containers,
             config,
+            provider,
+            start_policy,
             launcher: forker,
             mount_control,
+            loading: HashSet::new(),
+            loaded: HashMap::new(),
+            uninstalls: Vec::new(),
+            mounting: HashSet::new(),
+            mount_waiting: Vec::new(),
+            snapshot: None,
+            autostarted: HashMap::new(),
+            watch: CancellationToken::new(),
+            suspended: None,
+            policies,
+            throttled: HashSet::new(),
+            unused_devices: HashSet::new(),
+            deferred: 0,
+            adopting: Vec::new(),
         };
 
-        // Initialize repositories. This populates self.containers and self.repositories
-        let mount_repositories = state.initialize_repositories().await?;
+        let inherited = handoff.is_some();
+        if let Some(handoff) = handoff {
+            // The containers of the previous instance that handed over are still running
+            state.autostarted = handoff.autostarted.into_iter().collect();
+            state.adopting = handoff.containers;
+        } else {
+            // A snapshot is present if the previous instance did not shut down cleanly
+            let snapshot = Snapshot::load(&state.config.run_dir).await?;
+            if let Some(snapshot) = snapshot.as_ref() {
+                warn!("Found state of a previous runtime instance that did not shut down cleanly");
+                state.cleanup_stale_mounts(snapshot).await;
+            }
+            state.snapshot = snapshot;
+        }
+
+        // Initialize repositories. Directory repositories are loaded in the background and
+        // added with `on_repository`. The repositories are loaded before the main loop
+        // starts if the containers of a previous instance are taken over.
+        state.initialize_repositories(inherited).await?;
+
+        if state.loading.is_empty() {
+            state.on_repositories_loaded().await?;
+        }
+
+        state.schedule_reconcile();
+
+        Ok(state)
+    }
+
+    /// Add a repository that finished loading in the background. Repositories are added in
+    /// the order of their priority: A repository waits until the repositories with a higher
+    /// priority are added. This registers containers that are present in several
+    /// repositories from the repository with the highest priority.
+    pub(super) async fn on_repository(
+        &mut self,
+        id: RepositoryId,
+        repository: Result<Repository, Error>,
+    ) -> Result<(), Error> {
+        self.loaded.insert(id, repository?);
+
+        loop {
+            let next = self
+                .loaded
+                .keys()
+                .min_by(|a, b| self.config.repository_order(a, b))
+                .filter(|next| {
+                    self.loading
+                        .iter()
+                        .filter(|id| !self.loaded.contains_key(*id))
+                        .all(|id| self.config.repository_order(next, id).is_lt())
+                })
+                .cloned();
+            match next {
+                Some(id) => {
+                    let repository = self.loaded.remove(&id).expect("internal error");
+                    self.on_repository_loaded(id, repository).await?;
+                }
+                None => break Ok(()),
+            }
+        }
+    }
+
+    /// Add a loaded repository. Mount its containers if configured and start the containers
+    /// flagged with autostart that are available.
+    async fn on_repository_loaded(
+        &mut self,
+        id: RepositoryId,
+        repository: Repository,
+    ) -> Result<(), Error> {
+        self.loading.remove(&id);
+        info!("Loaded repository {}", id);
+        self.add_repository(id.clone(), repository);
+        self.adopt(&id).await;
+
+        let configuration = self.config.repositories.get(&id).cloned();
 
         // Mount all containers if configured
-        state.automount(&mount_repositories).await?;
+        let mount_on_start = configuration
+            .as_ref()
+            .map(|repository| repository.mount_on_start)
+            .unwrap_or_default();
+        if mount_on_start {
+            self.automount(&HashSet::from([id.clone()])).await?;
+        }
+
+        // Watch the repository directory if configured. Npks that are copied in between
+        // the loading and the start of the watch are picked up by the initial refresh.
+        if let Some(config::Repository {
+            watch: true,
+            r#type: RepositoryType::Fs { dir },
+            ..
+        }) = configuration
+        {
+            repository::watch(id.clone(), &dir, self.events_tx.clone(), self.watch.clone())
+                .context("failed to watch repository")?;
+            self.on_repository_changed(&id).await?;
+        }
+
+        self.subscribers
+            .publish(RuntimeEvent::RepositoryLoaded(id.clone()));
+        self.notification(model::Notification::RepositoryLoaded(id));
+
+        if self.loading.is_empty() {
+            self.on_repositories_loaded().await
+        } else {
+            // Start the containers flagged with autostart that have their
+            // dependencies available
+            self.autostart().await
+        }
+    }
+
+    /// Start the remaining autostart containers and restore the snapshot of a
+    /// previous instance once all repositories are available
+    async fn on_repositories_loaded(&mut self) -> Result<(), Error> {
+        info!("Repositories loaded");
+
+        // Containers of the previous instance whose repository is gone
+        for entry in std::mem::take(&mut self.adopting) {
+            self.abandon(entry).await;
+        }
 
         // Start containers flagged with autostart
-        state.autostart().await?;
+        self.autostart().await?;
 
-        Ok(state)
+        // Restore containers from the snapshot as configured
+        if let Some(snapshot) = self.snapshot.take() {
+            self.restore(snapshot).await;
+        }
+
+        self.save_snapshot().await;
+
+        Ok(())
+    }
+
+    /// Umount left over mounts of a previous instance
+    async fn cleanup_stale_mounts(&self, snapshot: &Snapshot) {
+        for container in &snapshot.mounted {
+            let root = self.config.run_dir.join(container.to_string());
+            if root.exists() {
+                info!("Umounting stale mount of {}", container);
+                if let Err(e) = self.mount_control.umount(&root).await {
+                    warn!("failed to umount stale {}: {}", root.display(), e);
+                }
+            }
+        }
     }
 
-    /// Iterate the list of repositories and initialize them
-    async fn initialize_repositories(&mut self) -> Result<HashSet<RepositoryId>, Error> {
-        // List of repositories to mount
-        let mut mount_repositories = HashSet::with_capacity(self.config.repositories.len());
+    /// Mount and start the containers from a snapshot according to the configured restore policy
+    async fn restore(&mut self, snapshot: Snapshot) {
+        if self.config.restore == Restore::None {
+            Snapshot::remove(&self.config.run_dir).await.ok();
+            return;
+        }
+
+        let to_mount = snapshot
+            .mounted
+            .into_iter()
+            .filter(|c| self.state(c).map(|s| !s.is_mounted()).unwrap_or(false))
+            .collect::<Vec<_>>();
+        if !to_mount.is_empty() {
+            info!("Restoring mount of {} container(s)", to_mount.len());
+            self.mount_all(&to_mount).await;
+        }
+
+        if self.config.restore == Restore::Start {
+            for started in snapshot.started {
+                // Skip unknown containers and containers started in the meantime e.g by autostart
+                match self.state(&started.container) {
+                    Ok(state) if state.process.is_none() => (),
+                    _ => continue,
+                }
 
-        // Build a map of repositories from the configuration
-        for (id, repository) in &self.config.repositories {
-            if repository.mount_on_start {
-                mount_repositories.insert(id.clone());
+                info!("Restoring {}", started.container);
+                if let Err(e) = self
+                    .start(
+                        &started.container,
+                        &started.args,
+                        &started.env,
+                        &started.parameters,
+                        Origin::Autostart,
+                        false,
+                    )
+                    .await
+                {
+                    warn!("failed to restore {}: {}", started.container, e);
+                }
             }
+        }
+    }
 
-            let repository = match &repository.r#type {
+    /// Write the set of mounted and started containers to the run dir
+    async fn save_snapshot(&self) {
+        // Keep the snapshot of the previous instance until it's restored
+        if self.config.restore == Restore::None || !self.loading.is_empty() {
+            return;
+        }
+
+        let mounted = self
+            .containers
+            .iter()
+            .filter(|(_, state)| state.is_mounted())
+            .map(|(container, _)| container.clone())
+            .collect();
+        let started = self
+            .containers
+            .iter()
+            .filter_map(|(container, state)| {
+                state.process.as_ref().map(|process| Started {
+                    container: container.clone(),
+                    args: process.args_extra.clone(),
+                    env: process.env_extra.clone(),
+                    parameters: process.parameters.clone(),
+                })
+            })
+            .collect();
+        let snapshot = Snapshot { mounted, started };
+
+        if let Err(e) = snapshot.save(&self.config.run_dir).await {
+            warn!("failed to save snapshot: {}", e);
+        }
+    }
+
+    /// Iterate the list of repositories and initialize them. Memory repositories are empty
+    /// and added immediately. Directory repositories are loaded in the background and sent
+    /// to the main loop once ready or loaded here if `inline` is set.
+    async fn initialize_repositories(&mut self, inline: bool) -> Result<(), Error> {
+        let mut load = Vec::new();
+        for (id, repository) in self.config.repositories.clone() {
+            let keys = repository.keys();
+            let readonly = repository.readonly;
+            match repository.r#type {
+                RepositoryType::Fs { dir } if inline => {
+                    self.loading.insert(id.clone());
+                    load.push((id, dir, keys, readonly));
+                }
                 RepositoryType::Fs { dir } => {
-                    let repository = DirRepository::new(dir, repository.key.as_deref()).await?;
-                    Box::new(repository) as Repository
+                    let events_tx = self.events_tx.clone();
+                    self.loading.insert(id.clone());
+                    task::spawn(async move {
+                        let repository = DirRepository::new(&dir, &keys, readonly)
+                            .await
+                            .map(|repository| Box::new(repository) as Repository);
+                        events_tx.send(Event::Repository(id, repository)).await.ok();
+                    });
                 }
                 RepositoryType::Memory => {
-                    let repository = MemRepository::new(repository.key.as_deref()).await?;
-                    Box::new(repository) as Repository
+                    let repository = MemRepository::new(&keys).await?;
+                    self.add_repository(id, Box::new(repository));
                 }
-            };
+            }
+        }
 
-            for npk in repository.containers() {
-                let name = npk.manifest().name.clone();
-                let version = npk.manifest().version.clone();
-                let container = Container::new(name, version);
+        for (id, dir, keys, readonly) in load {
+            let repository = DirRepository::new(&dir, &keys, readonly)
+                .await
+                .map(|repository| Box::new(repository) as Repository);
+            self.on_repository(id, repository).await?;
+        }
 
-                if let Ok(state) = self.state(&container) {
-                    warn!("Skipping duplicate container {} which is already loaded from repository {}", container, state.repository);
-                } else {
-                    self.containers.insert(
-                        container,
-                        ContainerState {
-                            repository: id.clone(),
-                            ..Default::default()
-                        },
-                    );
+        Ok(())
+    }
+
+    /// Take over the containers of the previous instance that are registered from the
+    /// repository `id`
+    async fn adopt(&mut self, id: &str) {
+        let (adopting, remaining) = std::mem::take(&mut self.adopting)
+            .into_iter()
+            .partition::<Vec<_>, _>(|entry| {
+                let installed = entry.container.without_instance();
+                entry.repository == id
+                    && matches!(self.containers.get(&installed), Some(state) if state.repository == id)
+            });
+        self.adopting = remaining;
+
+        for entry in adopting {
+            let container = entry.container;
+            if container.instance().is_some() {
+                let state = ContainerState {
+                    repository: id.into(),
+                    ..Default::default()
+                };
+                self.containers.insert(container.clone(), state);
+            }
+            let state = self.containers.get_mut(&container).expect("internal error");
+            state.root = entry.root;
+            state.mounted_for_start = entry.mounted_for_start;
+            state.start_timings = entry.start_timings;
+            state.history = entry.history;
+            state.oom_restarts = entry.oom_restarts;
+
+            match entry.process {
+                Some(process) => {
+                    let pid = process.pid;
+                    match self.adopt_process(&container, process).await {
+                        Ok(context) => {
+                            info!("Took over {} ({})", container, pid);
+                            let state =
+                                self.containers.get_mut(&container).expect("internal error");
+                            state.process = Some(context);
+                        }
+                        Err(e) => {
+                            warn!("failed to take over {} ({}): {}", container, pid, e);
+                            kill_process_group(pid);
+                            if container.instance().is_some() {
+                                self.containers.remove(&container);
+                            }
+                        }
+                    }
+                }
+                // Containers mounted for a start are umounted once idle
+                None if state.mounted_for_start && state.is_mounted() => {
+                    self.schedule_umount_idle(&container);
                 }
+                None => (),
+            }
+        }
+    }
+
+    /// Resume the supervision of the started `container` of the previous instance
+    async fn adopt_process(
+        &self,
+        container: &Container,
+        process: handoff::Process,
+    ) -> Result<ContainerContext, Error> {
+        // The files are closed if the container cannot be taken over
+        let output = process.output.map(|(fd, buffer)| io::PipedOutput {
+            stream: unsafe { StdUnixStream::from_raw_fd(fd) },
+            buffer,
+        });
+        let network = process
+            .network
+            .map(|(fd, interfaces)| (unsafe { std::fs::File::from_raw_fd(fd) }, interfaces));
+        let minidump = process
+            .minidump
+            .map(|fd| unsafe { std::fs::File::from_raw_fd(fd) });
+
+        let mut manifest = self.manifest(container)?.clone();
+        parameters::Arguments::new(&manifest, container.instance(), &process.parameters)
+            .and_then(|arguments| {
+                parameters::apply(&mut manifest, self.provider.as_ref(), &arguments)
+            })
+            .map_err(|e| Error::InvalidArguments(format!("{}: {}", container, e)))?;
+
+        let config = manifest.cgroups.clone().unwrap_or_default();
+        let cgroups = cgroups::CGroups::adopt(
+            &self.config.cgroup,
+            self.events_tx.clone(),
+            container,
+            &config,
+        )
+        .await?;
+
+        let (log_task, handoff) = match output {
+            Some(output) => {
+                let notification_tx = self.notification_tx.clone();
+                let (log_task, handoff) = io::resume(container, &manifest, notification_tx, output)
+                    .await
+                    .context("failed to resume the output forwarding")?;
+                (Some(log_task), handoff)
+            }
+            None => (None, CancellationToken::new()),
+        };
+
+        let network = match network {
+            Some((namespace, interfaces)) => {
+                Some(Network::adopt(container, namespace, interfaces)?)
+            }
+            None => None,
+        };
+
+        let now = time::Instant::now();
+        Ok(ContainerContext {
+            pid: process.pid,
+            forker: process.forker,
+            started: now.checked_sub(process.uptime).unwrap_or(now),
+            debug: super::debug::Debug::default(),
+            cgroups,
+            stop: CancellationToken::new(),
+            log_task,
+            handoff,
+            paused: process.paused,
+            oom_kills: process.oom_kills,
+            oom: process.oom,
+            signals: process
+                .signals
+                .into_iter()
+                .filter_map(|signal| Signal::try_from(signal).ok())
+                .collect(),
+            syscall: process.syscall,
+            data_reset: None,
+            args_extra: process.args,
+            env_extra: process.env,
+            parameters: process.parameters,
+            resources: process.resources.into_iter().collect(),
+            minidump,
+            pty: None,
+            stdin: None,
+            dbus: None,
+            network,
+        })
+    }
+
+    /// Kill the process of a container of the previous instance that is not available
+    /// anymore. The leftover mounts and cgroups are removed by the audit for leftovers.
+    async fn abandon(&self, entry: handoff::Entry) {
+        warn!("Abandoning {} of the previous instance", entry.container);
+        if let Some(process) = entry.process {
+            kill_process_group(process.pid);
+        }
+        if let Some(root) = entry.root.filter(|_| entry.container.instance().is_none()) {
+            if let Err(e) = self.mount_control.umount(&root).await {
+                warn!("failed to umount {}: {}", root.display(), e);
+            }
+        }
+    }
+
+    /// Register the npks that were added to the directory of a watched repository
+    pub(super) async fn on_repository_changed(&mut self, id: &str) -> Result<(), Error> {
+        let repository = match self.repositories.get_mut(id) {
+            Some(repository) => repository,
+            None => return Ok(()),
+        };
+        let added = match repository.refresh().await {
+            Ok(added) => added,
+            Err(e) => {
+                warn!("Failed to refresh repository {}: {}", id, e);
+                return Ok(());
             }
-            self.repositories.insert(id.clone(), repository);
+        };
+
+        for container in added {
+            if self.register(&container, id) {
+                info!("Discovered {} in repository {}", container, id);
+                self.container_event(&container, ContainerEvent::Installed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the containers of `repository`
+    fn add_repository(&mut self, id: RepositoryId, repository: Repository) {
+        for container in repository.containers() {
+            self.register(&container, &id);
         }
+        self.repositories.insert(id, repository);
+    }
+
+    /// Register `container` of repository `id`. A container that is already present in
+    /// another repository is taken from the repository with the higher priority unless it
+    /// is mounted, being mounted or started. Returns false if the container is shadowed by
+    /// the other repository.
+    fn register(&mut self, container: &Container, id: &str) -> bool {
+        let current = match self.containers.get(container) {
+            Some(state) => state.repository.clone(),
+            None => {
+                self.containers.insert(
+                    container.clone(),
+                    ContainerState {
+                        repository: id.into(),
+                        ..Default::default()
+                    },
+                );
+                return true;
+            }
+        };
 
-        Ok(mount_repositories)
+        let config = &self.config;
+        let mounting = self.mounting.contains(container);
+        let state = self.containers.get_mut(container).expect("internal error");
+        let replace = config.repository_order(id, &current).is_lt()
+            && !state.is_mounted()
+            && !mounting
+            && state.process.is_none();
+        if replace {
+            info!(
+                "Taking {} from repository {} instead of {}",
+                container, id, current
+            );
+            state.repository = id.into();
+            state.shadowed.push(current);
+        } else {
+            warn!(
+                "Skipping duplicate container {} in repository {} which is taken from repository {}",
+                container, id, current
+            );
+            state.shadowed.push(id.into());
+        }
+        state.shadowed.sort_by(|a, b| config.repository_order(a, b));
+        replace
     }
 
     /// Try to mount all installed continers
@@ -215,7 +953,7 @@ impl State {
         let containers = self
             .containers
             .iter()
-            .filter(|(_, state)| repositories.contains(&state.repository))
+            .filter(|(_, state)| repositories.contains(&state.repository) && !state.is_mounted())
             .map(|(container, _)| container.clone())
             .collect::<Vec<Container>>();
 
@@ -228,58 +966,179 @@ impl State {
         Ok(())
     }
 
+    /// Start the containers with the autostart flag set. Containers are started in waves:
+    /// A container is started once the containers listed in `after` are started. The
+    /// containers of a wave and their resources are mounted in parallel with at most
+    /// `autostart_concurrency` concurrent mounts. While repositories are loading only
+    /// the containers whose resources and dependencies are available are started.
     async fn autostart(&mut self) -> Result<(), Error> {
-        // List of containers from all repositories with the autostart flag set
-        let mut autostarts = Vec::with_capacity(self.containers.len());
-        // List of containers that need to be mounted
-        let mut to_mount = Vec::with_capacity(self.containers.len());
-
-        for (container, state) in self.containers.iter() {
-            if let Some(autostart) = self
-                .manifest(container)
-                .expect("internal error")
-                .autostart
-                .as_ref()
-            {
-                autostarts.push((container.clone(), autostart.clone()));
-                if !state.is_mounted() {
-                    to_mount.push(container.clone())
+        // Containers from all loaded repositories with the autostart flag set that are not handled yet
+        let mut pending = Vec::with_capacity(self.containers.len());
+        for container in self.containers.keys() {
+            if container.instance().is_some() || self.autostarted.contains_key(container) {
+                continue;
+            }
+            let manifest = self.manifest(container).expect("internal error");
+            if let Some(autostart) = manifest.autostart.as_ref() {
+                pending.push((container.clone(), autostart.clone(), manifest.after.clone()));
+            }
+        }
+
+        let mut started = HashSet::new();
+        let mut failed = HashSet::new();
+        for (container, success) in &self.autostarted {
+            if *success {
+                started.insert(container.name().clone());
+            } else {
+                failed.insert(container.name().clone());
+            }
+        }
+
+        if !self.loading.is_empty() {
+            // Defer containers with resources or dependencies that might be in a repository
+            // that is not loaded yet. Repeat until all deferred dependencies are removed.
+            let loaded = self
+                .containers
+                .keys()
+                .map(|container| container.name().clone())
+                .collect::<HashSet<_>>();
+            let autostart = pending
+                .iter()
+                .map(|(container, _, _)| container.name().clone())
+                .collect::<HashSet<_>>();
+            loop {
+                let ready = pending
+                    .iter()
+                    .map(|(container, _, _)| container.name().clone())
+                    .collect::<HashSet<_>>();
+                let count = pending.len();
+                pending.retain(|(container, _, after)| {
+                    let manifest = self.manifest(container).expect("internal error");
+                    let resources = manifest.mounts.values().flat_map(Mount::resources).all(
+                        |(name, version)| {
+                            State::match_container(name, version, self.containers.keys()).is_some()
+                        },
+                    );
+                    let dependencies = after.iter().all(|name| {
+                        started.contains(name)
+                            || failed.contains(name)
+                            || ready.contains(name)
+                            || (loaded.contains(name) && !autostart.contains(name))
+                    });
+                    resources && dependencies
+                });
+                if pending.len() == count {
+                    break;
                 }
             }
         }
 
-        // Add resources of containers that have the autostart flag set
-        for (container, autostart) in &autostarts {
-            let manifest = self.manifest(container)?;
-            for mount in manifest.mounts.values() {
-                if let Mount::Resource(Resource { name, version, .. }) = mount {
+        // Dependencies on containers that are not autostarted are ignored
+        let names = pending
+            .iter()
+            .map(|(container, _, _)| container.name().clone())
+            .chain(started.iter().cloned())
+            .chain(failed.iter().cloned())
+            .collect::<HashSet<_>>();
+        for (container, _, after) in pending.iter_mut() {
+            for name in after.iter().filter(|name| !names.contains(name)) {
+                warn!(
+                    "Ignoring dependency {} of {} that is not autostarted",
+                    name, container
+                );
+            }
+            after.retain(|name| names.contains(name));
+        }
+
+        while !pending.is_empty() {
+            let (wave, blocked): (Vec<_>, Vec<_>) =
+                pending.into_iter().partition(|(_, _, after)| {
+                    after
+                        .iter()
+                        .all(|name| started.contains(name) || failed.contains(name))
+                });
+            pending = blocked;
+
+            // The remaining containers depend on each other
+            if wave.is_empty() {
+                for (container, autostart, after) in pending.drain(..) {
+                    self.autostarted.insert(container.clone(), false);
+                    let error = Error::StartContainerFailed(
+                        container.clone(),
+                        format!("cyclic autostart dependency on {}", after.iter().join(", ")),
+                    );
+                    Self::warn_autostart_failure(&container, &autostart, error)?;
+                }
+                break;
+            }
+
+            // Collect the containers of this wave and their resources that need to be mounted.
+            // Missing resources are reported by the start.
+            let mut to_mount = Vec::new();
+            for (container, _, _) in &wave {
+                if !self.state(container)?.is_mounted() {
+                    to_mount.push(container.clone());
+                }
+                let manifest = self.manifest(container)?;
+                for (name, version) in manifest.mounts.values().flat_map(Mount::resources) {
                     if let Some(resource) =
                         State::match_container(name, version, self.containers.keys())
                     {
-                        to_mount.push(resource.clone());
-                    } else {
-                        let error = Error::StartContainerMissingResource(
-                            container.clone(),
-                            name.clone(),
-                            version.to_string(),
-                        );
-                        Self::warn_autostart_failure(container, autostart, error)?
+                        if !self.state(resource)?.is_mounted() && !to_mount.contains(resource) {
+                            to_mount.push(resource.clone());
+                        }
                     }
                 }
             }
-        }
 
-        // Mount (parallel). Do not care about the result - this normally is fine. If not, the container will not start.
-        if !to_mount.is_empty() {
-            self.mount_all(&to_mount).await;
+            // Mount (parallel). Do not care about the result - this normally is fine. If not, the container will not start.
+            for chunk in to_mount.chunks(self.config.autostart_concurrency) {
+                for mounted in self.mount_all(chunk).await.into_iter().flatten() {
+                    self.state_mut(&mounted)?.mounted_for_start = true;
+                }
+            }
 
-            for (container, autostart) in autostarts {
-                info!("Autostarting {} ({:?})", container, autostart);
-                if let Err(e) = self
-                    .start(&container, &[], &HashMap::with_capacity(0))
-                    .await
-                {
-                    Self::warn_autostart_failure(&container, &autostart, e)?
+            // The forker creates the container processes one after the other in the
+            // order of the start policy
+            let mut wave = wave;
+            let mut order = wave.iter().map(|(c, _, _)| c.clone()).collect::<Vec<_>>();
+            self.start_policy.order(&mut order);
+            wave.sort_by_key(|(c, _, _)| order.iter().position(|o| o == c));
+            for (container, autostart, after) in wave {
+                let result = match after.iter().find(|name| failed.contains(*name)) {
+                    Some(dependency) => Err(Error::StartContainerFailed(
+                        container.clone(),
+                        format!("dependency {} failed to start", dependency),
+                    )),
+                    None => {
+                        info!("Autostarting {} ({:?})", container, autostart);
+                        self.start(
+                            &container,
+                            &[],
+                            &HashMap::with_capacity(0),
+                            &HashMap::with_capacity(0),
+                            Origin::Autostart,
+                            false,
+                        )
+                        .await
+                    }
+                };
+                self.autostarted.insert(container.clone(), result.is_ok());
+                match result {
+                    Ok(()) => {
+                        started.insert(container.name().clone());
+                    }
+                    // The dependencies of a deferred container fail to start
+                    Err(Error::StartDeferred(_, delay)) => {
+                        info!("Autostart of {} is deferred for {:?}", container, delay);
+                        failed.insert(container.name().clone());
+                        let start = DeferredStart::new(container.clone(), Origin::Autostart);
+                        self.defer(start, delay);
+                    }
+                    Err(e) => {
+                        failed.insert(container.name().clone());
+                        Self::warn_autostart_failure(&container, &autostart, e)?
+                    }
                 }
             }
         }
@@ -306,20 +1165,39 @@ impl State {
 
     /// Create a future that mounts `container`
     fn mount(&self, container: &Container) -> impl Future<Output = Result<PathBuf, Error>> {
-        // Find the repository that has the container
-        let container_state = self.containers.get(container).expect("internal error");
-        let repository = self
-            .repositories
-            .get(&container_state.repository)
-            .expect("internal error");
-        let key = repository.key().cloned();
-        let npk = self.npk(container).expect("internal error");
+        let npk = match self.npk(container) {
+            Ok(npk) => npk,
+            Err(e) => return Either::Right(ready(Err(e))),
+        };
+        // The key that verified the npk is set for npks from verified repositories
+        let key = npk.key().cloned();
         let root = self.config.run_dir.join(container.to_string());
         let mount_control = self.mount_control.clone();
-        mount_control
-            .mount(npk, &root, key.as_ref())
-            .map_err(Error::Mount)
-            .map(|_| Ok(root))
+        let mount = mount_control.mount(npk, &root, key.as_ref());
+        let timeout = self.config.mount_timeout;
+        let container = container.clone();
+
+        Either::Left(async move {
+            let mut mount = task::spawn(mount);
+            match time::timeout(timeout, &mut mount).await {
+                Ok(result) => result
+                    .expect("task error")
+                    .map_err(Error::Mount)
+                    .map(|_| root),
+                Err(_) => {
+                    warn!("Mount of {} timed out", container);
+                    // Undo the mount if it completes after all
+                    let timed_out = container.clone();
+                    task::spawn(async move {
+                        if let Ok(Ok(())) = mount.await {
+                            info!("Umounting {} after the mount timed out", timed_out);
+                            mount_control.umount(&root).await.ok();
+                        }
+                    });
+                    Err(Error::Timeout(container, model::Operation::Mount, timeout))
+                }
+            }
+        })
     }
 
     /// Create a future that umounts `container`. Return a futures that yield
@@ -346,31 +1224,320 @@ impl State {
                 .as_ref()
                 .ok_or_else(|| Error::UmountBusy(container.clone()))
         }) {
-            Ok(root) => Either::Left(MountControl::umount(root).map_err(Error::Mount)),
+            Ok(root) => {
+                let umount = self.mount_control.umount(root);
+                let mount_control = self.mount_control.clone();
+                let timeout = self.config.umount_timeout;
+                let container = container.clone();
+                let root = root.clone();
+                Either::Left(async move {
+                    match time::timeout(timeout, umount).await {
+                        Ok(result) => result.map_err(Error::Mount),
+                        Err(_) => {
+                            // The file system is wedged. Detach it and leave the cleanup
+                            // to the kernel.
+                            warn!("Umount of {} timed out. Detaching it", container);
+                            mount_control.detach(&root).await.map_err(|e| {
+                                warn!("Failed to detach {}: {}", container, e);
+                                Error::Timeout(container, model::Operation::Umount, timeout)
+                            })
+                        }
+                    }
+                })
+            }
             Err(e) => Either::Right(ready(Err(e))),
         }
     }
 
-    /// Start a container
-    /// `container`: Container to start
-    /// `args_extra`: Optional command line arguments that overwrite the values from the manifest
-    /// `env_extra`: Optional env variables that overwrite the values from the manifest
-    pub(super) async fn start(
-        &mut self,
+    /// Check that the containers of the persist share group of `manifest` agree on
+    /// the owner of the shared directory
+    fn check_persist_share(&self, container: &Container, manifest: &Manifest) -> Result<(), Error> {
+        if let Some(group) = persist::share(manifest) {
+            let conflict = self
+                .containers
+                .keys()
+                .filter_map(|c| self.manifest(c).ok())
+                .find(|m| {
+                    persist::share(m) == Some(group)
+                        && (m.uid, m.gid) != (manifest.uid, manifest.gid)
+                });
+            if let Some(conflict) = conflict {
+                return Err(Error::StartContainerFailed(
+                    container.clone(),
+                    format!(
+                        "share group {} is used by {} with uid {} and gid {}",
+                        group,
+                        conflict.container(),
+                        conflict.uid,
+                        conflict.gid
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Manifests of the installed containers that declare the linked sockets of `manifest`
+    fn link_providers(
+        &self,
         container: &Container,
-        args_extra: &[NonNulString],
+        manifest: &Manifest,
+    ) -> Result<Vec<Manifest>, Error> {
+        let mut providers = Vec::with_capacity(manifest.links.len());
+        for link in &manifest.links {
+            let provider = self
+                .containers
+                .keys()
+                .filter_map(|c| self.manifest(c).ok())
+                .find(|m| {
+                    m.name == link.from
+                        && m.ipc
+                            .as_ref()
+                            .map_or(false, |ipc| ipc.sockets.contains(&link.socket))
+                })
+                .ok_or_else(|| {
+                    Error::StartContainerFailed(
+                        container.clone(),
+                        format!(
+                            "no installed container {} declares the socket {}",
+                            link.from,
+                            link.socket.display()
+                        ),
+                    )
+                })?;
+            providers.push(provider.clone());
+        }
+        Ok(providers)
+    }
+
+    /// Manifests of the installed containers that own the shared memory segments
+    /// `manifest` uses and grant access to it
+    fn shm_owners(
+        &self,
+        container: &Container,
+        manifest: &Manifest,
+    ) -> Result<Vec<Manifest>, Error> {
+        let mut owners = Vec::new();
+        for shm in manifest.ipc.iter().flat_map(|ipc| &ipc.shm) {
+            if let Some(from) = &shm.from {
+                let owner = self
+                    .containers
+                    .keys()
+                    .filter_map(|c| self.manifest(c).ok())
+                    .find(|m| {
+                        m.name == *from
+                            && m.ipc.iter().flat_map(|ipc| &ipc.shm).any(|s| {
+                                s.name == shm.name
+                                    && s.size.is_some()
+                                    && s.access.contains(&manifest.name)
+                            })
+                    })
+                    .ok_or_else(|| {
+                        Error::StartContainerFailed(
+                            container.clone(),
+                            format!(
+                                "no installed container {} grants access to the shm {}",
+                                from, shm.name
+                            ),
+                        )
+                    })?;
+                owners.push(owner.clone());
+            }
+        }
+        Ok(owners)
+    }
+
+    /// Run the checks of a start of `container` without mounting, creating or forking
+    /// anything. Template parameters take their defaults. Supplementary groups that
+    /// are skipped by the start are reported.
+    pub(super) fn start_dry_run(
+        &self,
+        container: &Container,
+    ) -> Result<Vec<model::StartCheck>, Error> {
+        info!("Checking the start of {}", container);
+
+        let mut checks = Vec::new();
+        let mut check = |kind, result: result::Result<(), String>| {
+            checks.push(model::StartCheck {
+                kind,
+                error: result.err(),
+            })
+        };
+
+        // Unknown containers fail the request
+        let mut manifest = self.manifest(container)?.clone();
+        check(
+            model::StartCheckKind::Npk,
+            self.npk(container).map(drop).map_err(|e| e.to_string()),
+        );
+
+        let arguments =
+            parameters::Arguments::new(&manifest, container.instance(), &HashMap::new()).and_then(
+                |arguments| {
+                    parameters::apply(&mut manifest, self.provider.as_ref(), &arguments)
+                        .map(|_| arguments)
+                },
+            );
+        let parameters = match &arguments {
+            Ok(arguments) => manifest
+                .env
+                .iter()
+                .try_for_each(|(key, value)| {
+                    parameters::resolve(value, self.provider.as_ref(), arguments)
+                        .map_err(|e| e.to_string())
+                        .and_then(|value| NonNulString::try_from(value).map_err(|e| e.to_string()))
+                        .map(drop)
+                        .map_err(|e| format!("failed to resolve env {}: {}", key, e))
+                })
+                .and_then(|_| {
+                    clock::offset(container, &manifest)
+                        .map(drop)
+                        .map_err(|e| e.to_string())
+                }),
+            Err(e) => Err(e.to_string()),
+        };
+        check(model::StartCheckKind::Parameters, parameters);
+
+        let init = match &manifest.init {
+            Some(_) => Ok(()),
+            None => Err(Error::StartContainerResource(container.clone()).to_string()),
+        };
+        check(model::StartCheckKind::Init, init);
+
+        let users = manifest
+            .suppl_groups
+            .iter()
+            .try_for_each(|group| match unistd::Group::from_name(group.as_str()) {
+                Ok(Some(_)) => Ok(()),
+                _ => Err(format!("unknown supplementary group {}", group)),
+            })
+            .and_then(|_| {
+                self.check_persist_share(container, &manifest)
+                    .map_err(|e| e.to_string())
+            });
+        check(model::StartCheckKind::Users, users);
+
+        let config = self
+            .config
+            .check_security_label(&manifest)
+            .and_then(|_| self.config.rlimits(&manifest).map(drop))
+            .map_err(|e| e.to_string());
+        check(model::StartCheckKind::Config, config);
+
+        let network = self
+            .config
+            .check_network_interfaces(&manifest)
+            .and_then(|_| bpf::Filters::load(container, &manifest).map(drop))
+            .map_err(|e| e.to_string());
+        check(model::StartCheckKind::Network, network);
+
+        let dependencies = self
+            .link_providers(container, &manifest)
+            .and_then(|_| self.shm_owners(container, &manifest))
+            .map(drop)
+            .map_err(|e| e.to_string())
+            .and_then(|_| {
+                match manifest
+                    .after
+                    .iter()
+                    .find(|name| !self.containers.keys().any(|c| c.name() == *name))
+                {
+                    Some(name) => Err(format!("{} is not installed", name)),
+                    None => Ok(()),
+                }
+            });
+        check(model::StartCheckKind::Dependencies, dependencies);
+
+        let resources = manifest
+            .mounts
+            .values()
+            .flat_map(Mount::resources)
+            .try_for_each(|(name, version)| {
+                match State::match_container(name, version, self.containers.keys()) {
+                    Some(_) => Ok(()),
+                    None => Err(Error::StartContainerMissingResource(
+                        container.clone(),
+                        name.clone(),
+                        version.to_string(),
+                    )
+                    .to_string()),
+                }
+            });
+        check(model::StartCheckKind::Resources, resources);
+
+        let mounts = manifest
+            .mounts
+            .iter()
+            .try_for_each(|(target, mount)| match mount {
+                Mount::Bind(bind) if !bind.host.exists() => Err(format!(
+                    "bind mount source {} of {} does not exist",
+                    bind.host.display(),
+                    target.display()
+                )),
+                Mount::Secret(secret) => match secret
+                    .secrets
+                    .iter()
+                    .find(|name| self.provider.secret(name).is_none())
+                {
+                    Some(name) => Err(format!("missing secret {}", name)),
+                    None => Ok(()),
+                },
+                _ => Ok(()),
+            })
+            .and_then(|_| {
+                let realtime = manifest.time.iter().any(|time| time.realtime.is_some());
+                if realtime && self.config.faketime.is_none() {
+                    Err("faketime is not configured".to_string())
+                } else {
+                    Ok(())
+                }
+            });
+        check(model::StartCheckKind::Mounts, mounts);
+
+        let seccomp = manifest.seccomp.as_ref().map_or(Ok(()), |seccomp| {
+            seccomp::seccomp_filter(
+                seccomp.profile.as_ref(),
+                seccomp.allow.as_ref(),
+                seccomp.deny.as_ref(),
+                &manifest.capabilities.permitted,
+            )
+            .and_then(|filter| filter.check())
+            .map_err(|e| e.to_string())
+        });
+        check(model::StartCheckKind::Seccomp, seccomp);
+
+        Ok(checks)
+    }
+
+    /// Start a container
+    /// `container`: Container to start
+    /// `args_extra`: Optional command line arguments that overwrite the values from the manifest
+    /// `env_extra`: Optional env variables that overwrite the values from the manifest
+    /// `parameters`: Values of the template parameters declared in the manifest
+    #[instrument(skip_all, fields(container = %container))]
+    pub(super) async fn start(
+        &mut self,
+        container: &Container,
+        args_extra: &[NonNulString],
         env_extra: &HashMap<NonNulString, NonNulString>,
+        parameters: &HashMap<Name, NonNulString>,
+        origin: Origin,
+        background: bool,
     ) -> Result<(), Error> {
         let start = time::Instant::now();
         info!("Trying to start {}", container);
 
         // Check if the container is already running
-        let container_state = self.state(container)?;
-        if container_state.process.is_some() {
+        let started = self.containers.get(container).map(|s| s.process.is_some());
+        if started == Some(true) {
             warn!("Application {} is already running", container);
             return Err(Error::StartContainerStarted(container.clone()));
         }
 
+        // Named instances share the npk and the root filesystem of the container
+        let installed = container.without_instance();
+        let container_state = self.state(&installed)?;
+
         // Check optional env variables for reserved ENV_NAME or ENV_VERSION key which cannot be overwritten
         if env_extra.keys().any(|k| {
             k.as_str() == ENV_NAME
@@ -384,7 +1551,55 @@ impl State {
             )));
         }
 
-        let manifest = self.manifest(container)?.clone();
+        // Open and verify the npk before its manifest is used
+        self.npk(container)?;
+        let mut manifest = self.manifest(container)?.clone();
+
+        // Fill in the template parameters
+        let arguments = parameters::Arguments::new(&manifest, container.instance(), parameters)
+            .and_then(|arguments| {
+                parameters::apply(&mut manifest, self.provider.as_ref(), &arguments)
+                    .map(|_| arguments)
+            })
+            .map_err(|e| Error::InvalidArguments(format!("{}: {}", container, e)))?;
+
+        // Only variables listed in the manifest can be overridden
+        if let Some(key) = env_extra
+            .keys()
+            .find(|key| !manifest.env_overrides.contains(*key))
+        {
+            return Err(Error::InvalidArguments(format!(
+                "env {} of {} cannot be overridden",
+                key, container
+            )));
+        }
+        self.config.check_security_label(&manifest)?;
+        self.config.rlimits(&manifest)?;
+        self.config.check_network_interfaces(&manifest)?;
+        let filters = bpf::Filters::load(container, &manifest)?;
+
+        match self.start_policy.check(container, &manifest, origin) {
+            Decision::Start => (),
+            Decision::Reject(reason) => {
+                return Err(Error::StartContainerFailed(
+                    container.clone(),
+                    format!("rejected by the start policy: {}", reason),
+                ))
+            }
+            Decision::Delay(delay) => return Err(Error::StartDeferred(container.clone(), delay)),
+        }
+
+        // Limit the number of named instances to the manifest maximum
+        if container.instance().is_some() {
+            let max = manifest.instances.unwrap_or_default() as usize;
+            let instances = self.instances(&installed).count();
+            if instances >= max {
+                return Err(Error::StartContainerFailed(
+                    container.clone(),
+                    format!("maximum number of {} instances is started", max),
+                ));
+            }
+        }
 
         // Check if the container is not a resource
         let init = if let Some(ref init) = manifest.init {
@@ -395,6 +1610,15 @@ impl State {
             return Err(Error::StartContainerResource(container.clone()));
         };
 
+        // Containers of a persist share group must agree on the owner of the directory
+        self.check_persist_share(container, &manifest)?;
+
+        // Linked sockets must be declared by the linked container
+        let providers = self.link_providers(container, &manifest)?;
+
+        // Shared memory segments of other containers must grant access
+        let owners = self.shm_owners(container, &manifest)?;
+
         // Containers that need to be mounted before container can be started
         let mut need_mount = HashSet::new();
         // Resources use by this container
@@ -402,26 +1626,22 @@ impl State {
 
         // The container to be started
         if !container_state.is_mounted() {
-            need_mount.insert(container.clone());
+            need_mount.insert(installed.clone());
         }
 
         // Collect resources used by container
         let required_resources = manifest
             .mounts
             .values()
-            .filter_map(|m| match m {
-                Mount::Resource(resource) => Some(resource),
-                _ => None,
-            })
+            .flat_map(Mount::resources)
             .collect::<Vec<_>>();
-        for resource in required_resources {
-            let best_match =
-                State::match_container(&resource.name, &resource.version, self.containers.keys())
-                    .ok_or_else(|| {
+        for (name, version) in required_resources {
+            let best_match = State::match_container(name, version, self.containers.keys())
+                .ok_or_else(|| {
                     Error::StartContainerMissingResource(
                         container.clone(),
-                        resource.name.clone(),
-                        resource.version.to_string(),
+                        name.clone(),
+                        version.to_string(),
                     )
                 })?;
             let state = self
@@ -435,21 +1655,71 @@ impl State {
             }
         }
 
+        let mut timings = model::StartTimings {
+            manifest: start.elapsed().as_nanos() as u64,
+            ..Default::default()
+        };
+
         // Mount containers
+        let phase = time::Instant::now();
+        if background && !need_mount.is_empty() {
+            // The start continues once the background mounts completed
+            return Err(Error::StartMount(
+                container.clone(),
+                need_mount.into_iter().sorted().collect(),
+            ));
+        }
         if !need_mount.is_empty() {
             info!(
                 "Mounting {} container(s) for the start of {}",
                 need_mount.len(),
                 container
             );
-            for mount in self.mount_all(&Vec::from_iter(need_mount)).await {
-                // Abort if at least one container failed to mount
-                if let Err(e) = mount {
-                    warn!("failed to mount: {}", e);
-                    return Err(e);
+            let results = self.mount_all(&Vec::from_iter(need_mount)).await;
+            let mounted = results
+                .iter()
+                .filter_map(|result| result.as_ref().ok())
+                .cloned()
+                .collect::<Vec<_>>();
+            // Abort if at least one container failed to mount and umount the
+            // containers mounted for this start
+            if let Some(e) = results.into_iter().find_map(Result::err) {
+                warn!("failed to mount: {}", e);
+                if !mounted.is_empty() {
+                    info!(
+                        "Rolling back {} mount(s) for the start of {}",
+                        mounted.len(),
+                        container
+                    );
+                    self.umount_all(&mounted).await;
                 }
+                return Err(e);
             }
+            for mounted in &mounted {
+                self.state_mut(mounted)?.mounted_for_start = true;
+            }
+        }
+
+        timings.mount = phase.elapsed().as_nanos() as u64;
+        let phase = time::Instant::now();
+
+        // Overwrite the env variables from the manifest with the variables provided
+        // with the start command. Parameters are substituted in the manifest values
+        // only. Values passed with the start command are taken verbatim.
+        let mut env = HashMap::with_capacity(manifest.env.len() + env_extra.len());
+        for (key, value) in &manifest.env {
+            let value = parameters::resolve(value, self.provider.as_ref(), &arguments)
+                .map_err(|e| e.to_string())
+                .and_then(|value| NonNulString::try_from(value).map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    Error::StartContainerFailed(
+                        container.clone(),
+                        format!("failed to resolve env {}: {}", key, e),
+                    )
+                })?;
+            env.insert(key.clone(), value);
         }
+        env.extend(env_extra.clone());
 
         // Spawn process
         info!("Creating {}", container);
@@ -469,8 +1739,10 @@ impl State {
                 .and_then(|_| UnixStream::from_std(runtime_stream))
                 .expect("failed to set socket into nonblocking mode");
 
-            let notifications = self.notification_tx.subscribe();
+            let notification_tx = self.notification_tx.clone();
             let events_tx = self.events_tx.clone();
+            let installs = self.installs.clone();
+            let pending = self.pending.clone();
             let stop = stop.clone();
             let container = Some(container.clone());
             let connection = Console::connection(
@@ -479,9 +1751,12 @@ impl State {
                 stop,
                 container,
                 configuration,
-                self.config.token_validity,
+                self.config.token_validity(),
                 events_tx,
-                notifications,
+                notification_tx,
+                installs,
+                pending,
+                None,
                 None,
             );
 
@@ -493,13 +1768,106 @@ impl State {
             None
         };
 
+        // Mount the persist image if a quota is configured
+        persist::mount(&self.config, container, &manifest).await?;
+
+        // Materialize the secrets of secret mounts
+        secret::create(&self.config, container, &manifest, self.provider.as_ref()).await?;
+
+        // Create the named pipes shared with other containers
+        pipe::create(&self.config, &manifest).await?;
+
+        // Create the socket directories of the container and of its links
+        for manifest in providers.iter().chain(Some(&manifest)) {
+            socket::create(&self.config, manifest).await?;
+        }
+
+        // Create the shared memory segments of the container and of their owners
+        for manifest in owners.iter().chain(Some(&manifest)) {
+            shm::create(&self.config, manifest).await?;
+        }
+
+        // Prepare the writable layer of the root filesystem
+        if let Err(e) = overlay::create(&self.config, container, &manifest).await {
+            secret::remove(&self.config, container).await.ok();
+            overlay::remove(&self.config, container).await.ok();
+            return Err(e);
+        }
+
+        // Start the D-Bus proxy. The proxy is killed if the start fails.
+        let dbus = match dbus::Proxy::new(&self.config, container, &manifest).await {
+            Ok(dbus) => dbus,
+            Err(e) => {
+                secret::remove(&self.config, container).await.ok();
+                overlay::remove(&self.config, container).await.ok();
+                return Err(e);
+            }
+        };
+
+        timings.prepare = phase.elapsed().as_nanos() as u64;
+        let phase = time::Instant::now();
+
+        // File the init process writes a minidump to if the application crashes
+        let minidump = match self.config.core_dumps.as_ref().filter(|c| c.minidump) {
+            Some(config) => Some(core_dump::minidump_file(config)?),
+            None => None,
+        };
+        let minidump_fd = match minidump.as_ref() {
+            Some(file) => {
+                let file = file
+                    .try_clone()
+                    .context("failed to duplicate minidump file")?;
+                Some(unsafe { OwnedFd::from_raw_fd(file.into_raw_fd()) })
+            }
+            None => None,
+        };
+
         // Create container
         let config = &self.config;
         let containers = self.containers.iter().map(|(c, _)| c);
-        let pid = self
+        let pid = match self
             .launcher
-            .create(config, &manifest, console_fd, containers)
-            .await?;
+            .create(
+                config,
+                container,
+                &manifest,
+                console_fd,
+                minidump_fd,
+                containers,
+            )
+            .await
+        {
+            Ok((pid, seccomp)) => {
+                timings.seccomp = seccomp.as_nanos() as u64;
+                timings.clone = phase.elapsed().saturating_sub(seccomp).as_nanos() as u64;
+                pid
+            }
+            Err(e) => {
+                secret::remove(&self.config, container).await.ok();
+                overlay::remove(&self.config, container).await.ok();
+                if let Error::StartContainerFailed(_, reason) = &e {
+                    let event = ContainerEvent::StartFailed(reason.clone());
+                    self.container_event(container, event);
+                }
+                return Err(e);
+            }
+        };
+
+        let phase = time::Instant::now();
+
+        // Adjust the oom score of init. The value is inherited by the application.
+        if let Some(score_adj) = manifest.oom.as_ref().and_then(|oom| oom.score_adj) {
+            debug!("Setting oom_score_adj of {} to {}", container, score_adj);
+            fs::write(
+                format!("/proc/{}/oom_score_adj", pid),
+                score_adj.to_string(),
+            )
+            .await
+            .context(format!("failed to set oom_score_adj of {}", container))?;
+        }
+
+        // Move the network interfaces into the network namespace of the container
+        let network = Network::new(container, &manifest, pid)?;
 
         // Debug
         let debug = super::debug::Debug::new(&self.config, &manifest, pid).await?;
@@ -515,8 +1883,22 @@ impl State {
                 .expect("failed to create cgroup")
         };
 
+        // Attach the network filters to the cgroup of the container
+        if let Some(filters) = filters {
+            let path = cgroups.unified_path().expect("missing unified cgroup");
+            filters
+                .attach(&path)
+                .expect("failed to attach network filters");
+        }
+
         // Open a file handle for stdin, stdout and stderr according to the manifest
-        let ContainerIo { io, log_task } = io::open(container, &manifest.io)
+        let ContainerIo {
+            io,
+            log_task,
+            handoff,
+            pty,
+            stdin,
+        } = io::open(container, &manifest, self.notification_tx.clone())
             .await
             .expect("IO setup error");
 
@@ -535,26 +1917,26 @@ impl State {
             args.extend(manifest.args.iter().cloned());
         };
 
-        // Overwrite the env variables from the manifest if variables are provided
-        // with the start command
-        let env = if env_extra.is_empty() {
-            &manifest.env
-        } else {
-            env_extra
-        };
-
         let env = env
             .iter()
             .map(|(k, v)| format!("{}={}", k, v))
             .chain(once(format!("{}={}", ENV_CONTAINER, container)))
             .chain(once(format!("{}={}", ENV_NAME, container.name())))
             .chain(once(format!("{}={}", ENV_VERSION, container.version())))
+            .chain(dbus::env(&manifest))
+            .chain(clock::env(&manifest))
             .map(|s| unsafe { NonNulString::from_string_unchecked(s) })
             .collect::<Vec<_>>();
 
         debug!("Container {} init is {:?}", container, init);
         debug!("Container {} argv is {}", container, args.iter().join(" "));
-        debug!("Container {} env is {}", container, env.iter().join(", "));
+        debug!(
+            "Container {} env is {}",
+            container,
+            env.iter()
+                .map(|e| e.split_once('=').map(|(k, _)| k).unwrap_or_default())
+                .join(", ")
+        );
 
         // Send exec request to launcher
         if let Err(e) = self
@@ -564,6 +1946,12 @@ impl State {
         {
             warn!("failed to exec {} ({}): {}", container, pid, e);
 
+            // The forker did not answer in time. Init waits for the exec request or
+            // is wedged and is not needed anymore.
+            if matches!(e, Error::Timeout(..)) {
+                self.launcher.kill(container, pid);
+            }
+
             stop.cancel();
 
             if let Some(log_task) = log_task {
@@ -571,30 +1959,87 @@ impl State {
             }
             debug.destroy().await.expect("failed to destroy debug");
             cgroups.destroy().await;
+            if let Some(network) = network {
+                network.destroy();
+            }
             return Err(e);
         }
 
+        // Named instances are part of the state until they exit. A restarted instance
+        // keeps its history.
+        if container.instance().is_some() && !self.containers.contains_key(container) {
+            let state = self.state(&installed)?;
+            let state = ContainerState {
+                repository: state.repository.clone(),
+                root: state.root.clone(),
+                ..Default::default()
+            };
+            self.containers.insert(container.clone(), state);
+        }
+
         // Get a mutable reference to the container state in order to update the process field
         let container_state = self.containers.get_mut(container).expect("Internal error");
 
+        // Restarts by the oom policy are limited until the container is started otherwise
+        if origin != Origin::Restart {
+            container_state.oom_restarts = 0;
+        }
+
         // Add process context to process
         let started = time::Instant::now();
         container_state.process = Some(ContainerContext {
             pid,
+            forker: self.launcher.pid(),
             started,
             debug,
             cgroups,
             stop,
             log_task,
+            handoff,
+            paused: false,
+            oom_kills: 0,
+            oom: false,
+            signals: HashSet::new(),
+            syscall: None,
+            data_reset: None,
+            args_extra: args_extra.to_vec(),
+            env_extra: env_extra.clone(),
+            parameters: parameters.clone(),
             resources,
+            minidump,
+            pty,
+            stdin,
+            dbus,
+            network,
         });
 
+        timings.exec = phase.elapsed().as_nanos() as u64;
+        timings.total = start.elapsed().as_nanos() as u64;
+        let ms = |nanos: u64| nanos as f64 / 1_000_000.0;
+        debug!(
+            "Start of {} took manifest {:.03}ms, mount {:.03}ms, prepare {:.03}ms, \
+             seccomp {:.03}ms, clone {:.03}ms, exec {:.03}ms",
+            container,
+            ms(timings.manifest),
+            ms(timings.mount),
+            ms(timings.prepare),
+            ms(timings.seccomp),
+            ms(timings.clone),
+            ms(timings.exec),
+        );
+        container_state.start_timings = Some(timings);
+
         let duration = start.elapsed().as_secs_f32();
         info!("Started {} ({}) in {:.03}s", container, pid, duration);
 
         // Send container started event
         self.container_event(container, ContainerEvent::Started);
 
+        // Apply the throttling policies that are active already
+        if self.policies.is_active() && self.manifest(container)?.deferrable {
+            self.throttle(container).await?;
+        }
+
         Ok(())
     }
 
@@ -605,120 +2050,767 @@ impl State {
         signal: Signal,
     ) -> Result<(), Error> {
         let container_state = self.state_mut(container)?;
+        // A stopped container is not restarted by the oom policy
+        container_state.cancel_restart();
 
         match &mut container_state.process {
             Some(context) => {
+                // Signals are not delivered to frozen processes. Thaw the container
+                // before the signal is sent in order to let it handle the signal. A
+                // failing thaw leaves the container paused and unsignalled.
+                let resumed = if context.paused {
+                    info!("Resuming paused {} for signal delivery", container);
+                    context.cgroups.thaw()?;
+                    context.paused = false;
+                    true
+                } else {
+                    false
+                };
+
                 info!("Killing {} with {}", container, signal.as_str());
+                context.signals.insert(signal);
                 let pid = context.pid;
                 let process_group = nix::unistd::Pid::from_raw(-(pid as i32));
                 match nix::sys::signal::kill(process_group, Some(signal)) {
-                    Ok(_) => Ok(()),
+                    Ok(_) => (),
                     Err(nix::Error::ESRCH) => {
                         debug!("Process {} already exited", pid);
-                        Ok(())
                     }
                     Err(e) => unimplemented!("Kill error {}", e),
                 }
+
+                if resumed {
+                    self.container_event(container, ContainerEvent::Resumed);
+                }
+                Ok(())
             }
             None => Err(Error::StopContainerNotStarted(container.clone())),
         }
     }
 
-    /// Shutdown the runtime: stop running applications and umount npks
-    pub(super) async fn shutdown(
-        mut self,
-        event_rx: impl Stream<Item = Event>,
+    /// Send `signal` to the application process of `container` or to all of its
+    /// processes named `process`. Unlike `kill` the signal is not sent to init and a
+    /// paused container is not resumed.
+    pub(super) async fn signal(
+        &mut self,
+        container: &Container,
+        signal: Signal,
+        process: Option<&str>,
     ) -> Result<(), Error> {
-        let started_containers = self
-            .containers
-            .iter()
-            .filter_map(|(container, state)| state.process.as_ref().map(|_| container.clone()))
+        let context = self
+            .state_mut(container)?
+            .process
+            .as_mut()
+            .ok_or_else(|| Error::ContainerNotStarted(container.clone()))?;
+
+        // The application is the only child of init
+        let targets = process::list(context.cgroups.tasks())
+            .into_iter()
+            .filter(|p| match process {
+                Some(name) => p.name == name,
+                None => p.ppid == context.pid,
+            })
+            .map(|p| p.pid)
             .collect::<Vec<_>>();
-
-        // Send a SIGKILL to each started container
-        for container in &started_containers {
-            self.kill(container, Signal::SIGKILL).await?;
+        if targets.is_empty() {
+            return Err(Error::InvalidArguments(format!(
+                "{} has no process {}",
+                container,
+                process.unwrap_or("application")
+            )));
         }
 
-        // Wait until all processes are gone
-        pin!(event_rx);
-        while self
-            .containers
-            .values()
-            .any(|state| state.process.is_some())
-        {
-            if let Some(Event::Container(container, event)) = event_rx.next().await {
-                self.on_event(&container, &event, true).await?;
+        context.signals.insert(signal);
+        for pid in targets {
+            info!("Sending {} to {} of {}", signal.as_str(), pid, container);
+            match nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), Some(signal)) {
+                Ok(_) => (),
+                Err(nix::Error::ESRCH) => debug!("Process {} already exited", pid),
+                Err(e) => return Err(e).context(format!("failed to signal {}", pid)),
             }
         }
+        Ok(())
+    }
 
-        // Try to umount mounted containers
-        let to_umount = self
-            .containers
-            .iter()
-            .filter(|(_, state)| state.is_mounted())
-            .map(|(container, _)| container.clone())
-            .collect::<Vec<_>>();
-        self.umount_all(&to_umount).await;
+    /// Freeze all processes of `container` with the cgroup freezer
+    pub(super) async fn pause(&mut self, container: &Container) -> Result<(), Error> {
+        let container_state = self.state_mut(container)?;
 
-        Ok(())
+        match &mut container_state.process {
+            Some(context) if context.paused => Err(Error::PauseContainerPaused(container.clone())),
+            Some(context) => {
+                info!("Pausing {}", container);
+                context.cgroups.freeze()?;
+                context.paused = true;
+                self.container_event(container, ContainerEvent::Paused);
+                Ok(())
+            }
+            None => Err(Error::ContainerNotStarted(container.clone())),
+        }
     }
 
-    /// Install an NPK
-    async fn install(
-        &mut self,
-        id: &str,
-        rx: &mut mpsc::Receiver<Bytes>,
-    ) -> Result<Container, Error> {
-        // Find the repository
-        let repository = self
-            .repositories
-            .get_mut(id)
-            .ok_or_else(|| Error::InvalidRepository(id.to_string()))?;
+    /// Thaw all processes of a paused `container`
+    pub(super) async fn resume(&mut self, container: &Container) -> Result<(), Error> {
+        let container_state = self.state_mut(container)?;
 
-        // Add the npk to the repository
-        let container = repository.insert(rx).await?;
+        match &mut container_state.process {
+            Some(context) if context.paused => {
+                info!("Resuming {}", container);
+                context.cgroups.thaw()?;
+                context.paused = false;
+                self.container_event(container, ContainerEvent::Resumed);
+                Ok(())
+            }
+            Some(_) => Err(Error::ResumeContainerNotPaused(container.clone())),
+            None => Err(Error::ContainerNotStarted(container.clone())),
+        }
+    }
 
-        // Check if container is already known and remove newly installed one if so
-        let already_installed = self
-            .state(&container)
-            .ok()
-            .map(|state| state.repository.clone());
+    /// Prepare a system suspend: signal and freeze the started `containers` or all
+    /// started containers if `containers` is empty and flush the persist data
+    pub(super) async fn system_suspend(&mut self, containers: &[Container]) -> Result<(), Error> {
+        if self.suspended.is_some() {
+            return Err(Error::SystemSuspended);
+        }
 
-        if let Some(current_repository) = already_installed {
-            warn!(
-                "Skipping duplicate container {} which is already in repository {}",
-                container, current_repository
-            );
+        let containers = if containers.is_empty() {
+            self.containers
+                .iter()
+                .filter(|(_, state)| state.process.is_some())
+                .map(|(container, _)| container.clone())
+                .collect()
+        } else {
+            for container in containers {
+                if self.state(container)?.process.is_none() {
+                    return Err(Error::ContainerNotStarted(container.clone()));
+                }
+            }
+            containers.to_vec()
+        };
 
-            let repository = self
-                .repositories
-                .get_mut(id)
-                .ok_or_else(|| Error::InvalidRepository(id.to_string()))?;
-            repository.remove(&container).await?;
-            return Err(Error::InstallDuplicate(container));
+        // Notify the containers that want to prepare for the suspend
+        let mut grace = time::Duration::ZERO;
+        let mut to_freeze = Vec::with_capacity(containers.len());
+        for container in containers {
+            let suspend = self
+                .manifest(&container)?
+                .suspend
+                .clone()
+                .unwrap_or_default();
+            let paused = self
+                .state(&container)?
+                .process
+                .as_ref()
+                .map(|context| context.paused)
+                .unwrap_or(false);
+            if suspend.exempt || paused {
+                continue;
+            }
+            if let Some(signal) = suspend.signal {
+                match Signal::from_str(&signal) {
+                    Ok(signal) => {
+                        self.kill(&container, signal).await?;
+                        let timeout = time::Duration::from_millis(suspend.timeout.unwrap_or(0));
+                        grace = grace.max(timeout);
+                    }
+                    Err(e) => warn!("Invalid suspend signal {} of {}: {}", signal, container, e),
+                }
+            }
+            to_freeze.push(container);
         }
 
-        // Add the container to the state
-        self.containers.insert(
-            container.clone(),
-            ContainerState {
-                repository: id.into(),
-                ..Default::default()
-            },
-        );
-        info!("Successfully installed {}", container);
+        if !grace.is_zero() {
+            debug!("Waiting {:?} for containers to prepare the suspend", grace);
+            time::sleep(grace).await;
+        }
 
-        self.container_event(&container, ContainerEvent::Installed);
+        // Freeze the containers that are still started
+        let mut frozen = Vec::with_capacity(to_freeze.len());
+        for container in to_freeze {
+            if self.state(&container)?.process.is_some() {
+                self.pause(&container).await?;
+                frozen.push(container);
+            }
+        }
 
-        Ok(container)
+        // Flush the persist data of the frozen containers
+        debug!("Flushing filesystems");
+        task::spawn_blocking(nix::unistd::sync)
+            .await
+            .expect("task error");
+
+        info!("Suspended {}", frozen.iter().join(", "));
+        self.suspended = Some(frozen);
+        Ok(())
     }
 
-    /// Remove and umount a specific app
-    async fn uninstall(&mut self, container: &Container) -> Result<(), Error> {
-        info!("Trying to uninstall {}", container);
+    /// Thaw the containers frozen by a system suspend
+    pub(super) async fn system_resume(&mut self) -> Result<(), Error> {
+        let suspended = self.suspended.take().ok_or(Error::SystemNotSuspended)?;
+        for container in &suspended {
+            // The container might have been killed or resumed in the meantime
+            if let Err(e) = self.resume(container).await {
+                debug!("Skipping resume of {}: {}", container, e);
+            }
+        }
+        info!("Resumed {}", suspended.iter().join(", "));
+        Ok(())
+    }
 
-        let state = self.state(container)?;
+    /// Update an `input` of the throttling policies and apply the policies to the
+    /// started deferrable containers if a policy is activated or deactivated
+    pub(super) async fn policy_input(&mut self, input: &str, value: i64) -> Result<(), Error> {
+        if !self.policies.is_input(input) {
+            return Err(Error::InvalidArguments(format!(
+                "unknown policy input {}",
+                input
+            )));
+        }
+
+        let transitions = self.policies.input(input, value);
+        if transitions.is_empty() {
+            return Ok(());
+        }
+
+        for transition in transitions {
+            match transition {
+                Transition::Activated(policy) => {
+                    info!("Activated policy {} ({} is {})", policy, input, value);
+                    self.notification(model::Notification::PolicyActivated(policy));
+                }
+                Transition::Deactivated(policy) => {
+                    info!("Deactivated policy {} ({} is {})", policy, input, value);
+                    self.notification(model::Notification::PolicyDeactivated(policy));
+                }
+            }
+        }
+
+        let deferrable = self
+            .containers
+            .iter()
+            .filter(|(_, state)| state.process.is_some())
+            .map(|(container, _)| container.clone())
+            .filter(|container| {
+                self.manifest(container)
+                    .map(|manifest| manifest.deferrable)
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        for container in deferrable {
+            self.throttle(&container).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Limit the cpu bandwidth of a started deferrable `container` and freeze or thaw it
+    /// according to the active throttling policies
+    async fn throttle(&mut self, container: &Container) -> Result<(), Error> {
+        let cpu_max = self.policies.cpu_max();
+        let freeze = self.policies.freeze();
+
+        let paused = match self.state(container)?.process.as_ref() {
+            Some(context) => {
+                context.cgroups.cpu_max(cpu_max)?;
+                context.paused
+            }
+            None => return Ok(()),
+        };
+
+        if freeze && !paused {
+            self.pause(container).await?;
+            self.throttled.insert(container.clone());
+        } else if !freeze && self.throttled.remove(container) {
+            // The container might have been resumed in the meantime
+            if let Err(e) = self.resume(container).await {
+                debug!("Skipping resume of {}: {}", container, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Shutdown the runtime: stop running applications and umount npks
+    pub(super) async fn shutdown(
+        mut self,
+        event_rx: impl Stream<Item = Event>,
+    ) -> Result<(), Error> {
+        self.watch.cancel();
+        self.subscribers.publish(RuntimeEvent::Shutdown);
+        self.notification(model::Notification::Shutdown);
+
+        // Group the started containers by their shutdown priority
+        let mut stages = BTreeMap::<u32, Vec<(Container, Option<time::Duration>)>>::new();
+        for (container, state) in &self.containers {
+            if state.process.is_some() {
+                let manifest = self.manifest(container)?;
+                let priority = manifest.shutdown_priority.unwrap_or_default();
+                let timeout = manifest.shutdown_timeout.map(time::Duration::from_secs);
+                stages
+                    .entry(priority)
+                    .or_default()
+                    .push((container.clone(), timeout));
+            }
+        }
+
+        pin!(event_rx);
+
+        // Stop the containers stage by stage in ascending priority
+        for (priority, stage) in stages {
+            let containers = stage.iter().map(|(c, _)| c.clone()).sorted().collect_vec();
+            info!(
+                "Stopping {} (shutdown priority {})",
+                containers.iter().join(", "),
+                priority
+            );
+            self.notification(model::Notification::ShutdownStage(
+                priority,
+                containers.clone(),
+            ));
+
+            // Send a SIGTERM to containers with a grace period and a SIGKILL to all others.
+            // Killed containers that do not exit within the stop timeout are abandoned.
+            let stop_timeout = self.config.stop_timeout;
+            let mut deadlines = Vec::new();
+            let mut killed = Vec::new();
+            for (container, timeout) in stage {
+                match timeout {
+                    Some(timeout) => {
+                        self.kill(&container, Signal::SIGTERM).await?;
+                        deadlines.push((time::Instant::now() + timeout, container));
+                    }
+                    None => {
+                        self.kill(&container, Signal::SIGKILL).await?;
+                        killed.push((time::Instant::now() + stop_timeout, container));
+                    }
+                }
+            }
+
+            // Wait until all processes of this stage are gone
+            while containers.iter().any(|container| {
+                self.containers
+                    .get(container)
+                    .map(|state| state.process.is_some())
+                    .unwrap_or(false)
+            }) {
+                let deadline = deadlines
+                    .iter()
+                    .chain(killed.iter())
+                    .map(|(deadline, _)| *deadline)
+                    .min();
+                let expired = match deadline {
+                    Some(deadline) => Either::Left(time::sleep_until(deadline)),
+                    None => Either::Right(pending()),
+                };
+
+                select! {
+                    event = event_rx.next() => match event {
+                        Some(Event::Container(container, event)) => {
+                            self.on_event(&container, &event, true).await?;
+                        }
+                        Some(Event::Mounted(mounts)) => {
+                            self.apply_mounts(&mounts.containers, &mounts.mounting, mounts.results);
+                        }
+                        _ => (),
+                    },
+                    _ = expired => {
+                        let now = time::Instant::now();
+                        let (expired, pending) = deadlines
+                            .into_iter()
+                            .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+                        deadlines = pending;
+                        for (_, container) in expired {
+                            if self.state(&container)?.process.is_some() {
+                                warn!("{} did not stop within its shutdown timeout", container);
+                                self.kill(&container, Signal::SIGKILL).await?;
+                                killed.push((time::Instant::now() + stop_timeout, container));
+                            }
+                        }
+
+                        let (expired, pending) = killed
+                            .into_iter()
+                            .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+                        killed = pending;
+                        for (_, container) in expired {
+                            if self.state_mut(&container)?.process.take().is_some() {
+                                error!(
+                                    "{} did not exit within {:?} after it was killed",
+                                    container, stop_timeout
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Wait for the background mounts. Starts that wait for them are dropped.
+        self.mount_waiting.clear();
+        while !self.mounting.is_empty() {
+            match event_rx.next().await {
+                Some(Event::Mounted(mounts)) => {
+                    self.apply_mounts(&mounts.containers, &mounts.mounting, mounts.results);
+                }
+                Some(Event::Container(container, event)) => {
+                    self.on_event(&container, &event, true).await?;
+                }
+                Some(_) => (),
+                None => break,
+            }
+        }
+
+        // Try to umount mounted containers
+        let to_umount = self
+            .containers
+            .iter()
+            .filter(|(_, state)| state.is_mounted())
+            .map(|(container, _)| container.clone())
+            .collect::<Vec<_>>();
+        self.umount_all(&to_umount).await;
+
+        if let Err(e) = pipe::remove(&self.config).await {
+            warn!("failed to remove pipes: {}", e);
+        }
+
+        if let Err(e) = socket::remove(&self.config).await {
+            warn!("failed to remove sockets: {}", e);
+        }
+
+        if let Err(e) = shm::remove(&self.config).await {
+            warn!("failed to remove shm: {}", e);
+        }
+
+        // Clean shutdown - nothing to restore on the next start
+        Snapshot::remove(&self.config.run_dir).await?;
+
+        Ok(())
+    }
+
+    /// Check whether the started containers can be handed over to a new runtime binary.
+    /// Operations in progress and connections to the runtime are not handed over.
+    pub(super) fn check_handoff(&self) -> Result<(), Error> {
+        let refuse = |reason: String| Err(Error::Handoff(reason));
+        if !self.loading.is_empty() {
+            return refuse("repositories are loading".into());
+        }
+        if !self.mounting.is_empty() || !self.mount_waiting.is_empty() {
+            return refuse("mounts are in progress".into());
+        }
+        if !self.uninstalls.is_empty() {
+            return refuse("uninstalls are pending".into());
+        }
+        if self.suspended.is_some() {
+            return refuse("system is suspended".into());
+        }
+        if self.policies.is_active() {
+            return refuse("throttling policy is active".into());
+        }
+        if self.deferred > 0 {
+            return refuse("container starts are deferred".into());
+        }
+        if self.launcher.is_busy() {
+            return refuse("forker requests timed out".into());
+        }
+
+        // The npks of memory repositories are lost
+        for (id, repository) in &self.repositories {
+            let memory = matches!(
+                self.config.repositories.get(id).map(|r| &r.r#type),
+                Some(RepositoryType::Memory)
+            );
+            if memory && !repository.containers().is_empty() {
+                return refuse(format!("memory repository {} is not empty", id));
+            }
+        }
+
+        for (container, state) in &self.containers {
+            let process = match state.process.as_ref() {
+                Some(process) => process,
+                None => continue,
+            };
+            let uses = if self.manifest(container)?.console.is_some() {
+                "a console"
+            } else if process.pty.is_some() {
+                "a tty"
+            } else if process.stdin.is_some() {
+                "stdin"
+            } else if process.dbus.is_some() {
+                "a dbus proxy"
+            } else if process.debug.is_attached() {
+                "a debugger"
+            } else if process.data_reset.is_some() || state.oom_restart {
+                "a pending restart or data reset"
+            } else {
+                continue;
+            };
+            return refuse(format!("{} uses {}", container, uses));
+        }
+
+        Ok(())
+    }
+
+    /// Stop the supervision of the started containers and move the state that is passed
+    /// to the next instance into `handoff`. The containers keep running. Returns the
+    /// forker.
+    pub(super) async fn handoff(mut self, handoff: &mut Handoff) -> Result<Forker, Error> {
+        self.watch.cancel();
+
+        for (container, state) in self.containers.drain() {
+            let process = match state.process {
+                Some(process) => Some(process.handoff(handoff).await),
+                None => None,
+            };
+            // Containers that are not used and have no history are registered again
+            let empty = state.root.is_none()
+                && process.is_none()
+                && state.start_timings.is_none()
+                && state.history == model::ExitHistory::default();
+            if empty {
+                continue;
+            }
+            handoff.state.containers.push(handoff::Entry {
+                container,
+                repository: state.repository,
+                root: state.root,
+                mounted_for_start: state.mounted_for_start,
+                start_timings: state.start_timings,
+                history: state.history,
+                oom_restarts: state.oom_restarts,
+                process,
+            });
+        }
+        handoff.state.autostarted = self.autostarted.into_iter().collect();
+
+        Ok(self.launcher)
+    }
+
+    /// Install an NPK
+    #[instrument(skip_all, fields(repository = %id))]
+    async fn install(
+        &mut self,
+        id: &str,
+        size: u64,
+        rx: &mut mpsc::Receiver<Bytes>,
+    ) -> Result<Container, Error> {
+        if self
+            .config
+            .repositories
+            .get(id)
+            .map_or(false, |repository| repository.readonly)
+        {
+            warn!("Rejecting install into read only repository {}", id);
+            return Err(Error::RepositoryReadOnly(id.to_string()));
+        }
+
+        // Reject npks that do not fit into the repository directory before the stream
+        // is consumed
+        if let Some(config::Repository {
+            r#type: RepositoryType::Fs { dir },
+            install_margin,
+            ..
+        }) = self.config.repositories.get(id)
+        {
+            let available = system::disk_usage(dir)?.available;
+            let needed = size.saturating_add(*install_margin);
+            if needed > available {
+                warn!(
+                    "Rejecting install of {} bytes into repository {}: {} bytes available",
+                    size, id, available
+                );
+                return Err(Error::InsufficientSpace(needed, available));
+            }
+        }
+
+        // Find the repository
+        let repository = self
+            .repositories
+            .get_mut(id)
+            .ok_or_else(|| Error::InvalidRepository(id.to_string()))?;
+
+        // Add the npk to the repository
+        let container = repository.insert(rx).await?;
+
+        // Check if container is already known and remove newly installed one if so
+        let already_installed = self
+            .state(&container)
+            .ok()
+            .map(|state| state.repository.clone());
+
+        if let Some(current_repository) = already_installed {
+            warn!(
+                "Skipping duplicate container {} which is already in repository {}",
+                container, current_repository
+            );
+
+            let repository = self
+                .repositories
+                .get_mut(id)
+                .ok_or_else(|| Error::InvalidRepository(id.to_string()))?;
+            repository.remove(&container).await?;
+            return Err(Error::InstallDuplicate(container));
+        }
+
+        // Reject containers for other systems, without a label of the mandatory
+        // security module or with resource limits above the configured maximum
+        let repository = self
+            .repositories
+            .get_mut(id)
+            .ok_or_else(|| Error::InvalidRepository(id.to_string()))?;
+        let manifest = repository.manifest(&container).expect("internal error");
+        let check = system::check_requirements(manifest)
+            .and_then(|_| self.config.check_security_label(manifest))
+            .and_then(|_| self.config.rlimits(manifest).map(drop));
+        if let Err(e) = check {
+            warn!("Rejecting {}: {}", container, e);
+            repository.remove(&container).await?;
+            return Err(e);
+        }
+
+        // Add the container to the state
+        self.containers.insert(
+            container.clone(),
+            ContainerState {
+                repository: id.into(),
+                ..Default::default()
+            },
+        );
+        info!("Successfully installed {}", container);
+
+        self.container_event(&container, ContainerEvent::Installed);
+
+        Ok(container)
+    }
+
+    /// Uninstall `container` and respond once it is removed. A forced uninstall of a
+    /// container that is used by started containers kills them and completes once they
+    /// exited.
+    async fn on_uninstall(
+        &mut self,
+        container: Container,
+        force: bool,
+        wipe: bool,
+        response: oneshot::Sender<model::Response>,
+    ) {
+        let users = match self.check_uninstall(&container, force, wipe) {
+            Ok(_) if force => self.users(&container),
+            Ok(_) => Vec::with_capacity(0),
+            Err(e) => {
+                warn!("failed to uninstall {}: {}", container, e);
+                response.send(model::Response::Error(e.into())).ok();
+                return;
+            }
+        };
+
+        if !users.is_empty() {
+            info!(
+                "Killing {} for the uninstall of {}",
+                users.iter().join(", "),
+                container
+            );
+            for user in &users {
+                if let Err(e) = self.kill(user, Signal::SIGKILL).await {
+                    warn!("failed to kill {}: {}", user, e);
+                }
+                self.schedule_stop_timeout(user);
+            }
+            self.uninstalls.push((container, wipe, response));
+            return;
+        }
+
+        let payload = match self.uninstall(&container, force, wipe).await {
+            Ok(_) => model::Response::Ok,
+            Err(e) => {
+                warn!("failed to uninstall {}: {}", container, e);
+                model::Response::Error(e.into())
+            }
+        };
+        response.send(payload).ok();
+    }
+
+    /// Complete the forced uninstalls of containers that are no longer used
+    async fn complete_uninstalls(&mut self) {
+        let (ready, waiting) = std::mem::take(&mut self.uninstalls)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(container, _, _)| self.users(container).is_empty());
+        self.uninstalls = waiting;
+
+        for (container, wipe, response) in ready {
+            let payload = match self.uninstall(&container, true, wipe).await {
+                Ok(_) => model::Response::Ok,
+                Err(e) => {
+                    warn!("failed to uninstall {}: {}", container, e);
+                    model::Response::Error(e.into())
+                }
+            };
+            response.send(payload).ok();
+        }
+    }
+
+    /// Check whether `container` can be uninstalled. Containers that are mounted or used
+    /// by started containers and resources of installed containers are uninstalled only
+    /// if `force` is set. The persistent data is wiped only if no other installed
+    /// container uses it.
+    fn check_uninstall(&self, container: &Container, force: bool, wipe: bool) -> Result<(), Error> {
+        let state = self.state(container)?;
+        let repository = &state.repository;
+
+        if self
+            .config
+            .repositories
+            .get(repository)
+            .map_or(false, |repository| repository.readonly)
+        {
+            warn!(
+                "Rejecting uninstall of {} from read only repository {}",
+                container, repository
+            );
+            return Err(Error::RepositoryReadOnly(repository.clone()));
+        }
+
+        if wipe {
+            let users = self.persist_users(container, self.manifest(container)?);
+            if !users.is_empty() {
+                return Err(Error::Data(
+                    container.clone(),
+                    format!("persist directory is used by {}", users.iter().join(", ")),
+                ));
+            }
+        }
+
+        // The npk is in use until the mount completed
+        if self.mounting.contains(container) {
+            return Err(Error::MountBusy(container.clone()));
+        }
+
+        if force {
+            return Ok(());
+        }
+
+        let started = self.users(container);
+        let dependents = self.dependents(container);
+        if state.is_mounted() || !started.is_empty() || !dependents.is_empty() {
+            return Err(Error::UninstallInUse(
+                container.clone(),
+                state.is_mounted(),
+                started,
+                dependents,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Remove and umount a specific app
+    #[instrument(skip_all, fields(container = %container))]
+    async fn uninstall(
+        &mut self,
+        container: &Container,
+        force: bool,
+        wipe: bool,
+    ) -> Result<(), Error> {
+        info!("Trying to uninstall {}", container);
+
+        self.check_uninstall(container, force, wipe)?;
+        let state = self.state(container)?;
         let repository = state.repository.clone();
 
         // Umount
@@ -729,6 +2821,27 @@ impl State {
                 .expect("internal error")?;
         }
 
+        // Remove the persistent data if requested or configured in the manifest
+        let manifest = self.manifest(container)?.clone();
+        let remove_on_uninstall = manifest
+            .persist
+            .as_ref()
+            .map_or(false, |persist| persist.remove_on_uninstall);
+        if (wipe || remove_on_uninstall) && persist::has_persist_mount(&manifest) {
+            let users = self.persist_users(container, &manifest);
+            if users.is_empty() {
+                info!("Removing the persistent data of {}", container);
+                persist::remove(&self.config, container, &manifest).await?;
+                self.container_event(container, ContainerEvent::DataReset);
+            } else {
+                info!(
+                    "Keeping the persistent data of {} which is used by {}",
+                    container,
+                    users.iter().join(", ")
+                );
+            }
+        }
+
         // Remove from repository
         debug!("Removing {} from {}", container, repository);
         self.repositories
@@ -737,11 +2850,25 @@ impl State {
             .remove(container)
             .await?;
 
-        self.containers.remove(container);
+        let state = self.containers.remove(container).expect("internal error");
         info!("Successfully uninstalled {}", container);
 
         self.container_event(container, ContainerEvent::Uninstalled);
 
+        // Fall back to the repository with the next lower priority
+        if let Some((repository, shadowed)) = state.shadowed.split_first() {
+            info!("Taking {} from repository {}", container, repository);
+            self.containers.insert(
+                container.clone(),
+                ContainerState {
+                    repository: repository.clone(),
+                    shadowed: shadowed.to_vec(),
+                    ..Default::default()
+                },
+            );
+            self.container_event(container, ContainerEvent::Installed);
+        }
+
         Ok(())
     }
 
@@ -756,12 +2883,362 @@ impl State {
         // Gather stats if the container is running
         if let Some(process) = state.process.as_ref() {
             info!("Collecting stats of {}", container);
-            Ok(process.cgroups.stats())
+            let mut stats = process.cgroups.stats();
+            let manifest = self.manifest(container)?;
+            if let Some(usage) = persist::usage(&self.config, container, manifest).await {
+                stats.insert("persist".into(), usage);
+            }
+            Ok(stats)
         } else {
             Err(Error::ContainerNotStarted(container.clone()))
         }
     }
 
+    /// List the processes of `container` including init
+    fn ps(&self, container: &Container) -> Result<Vec<model::ProcessInfo>, Error> {
+        let context = self
+            .state(container)?
+            .process
+            .as_ref()
+            .ok_or_else(|| Error::ContainerNotStarted(container.clone()))?;
+
+        let processes = process::list(context.cgroups.tasks())
+            .into_iter()
+            .map(|p| model::ProcessInfo {
+                pid: p.pid,
+                ppid: p.ppid,
+                name: p.name,
+                rss: p.rss,
+            })
+            .collect();
+        Ok(processes)
+    }
+
+    /// List the services of the started containers with `name` or all services
+    fn services(&self, name: Option<&Name>) -> Vec<model::Service> {
+        self.containers
+            .iter()
+            .filter(|(_, state)| state.process.is_some())
+            .filter_map(|(container, _)| {
+                self.manifest(container)
+                    .ok()
+                    .map(|manifest| (container, manifest))
+            })
+            .flat_map(|(container, manifest)| {
+                let namespace = manifest.network.as_ref().map_or(false, |n| n.namespace);
+                manifest
+                    .services
+                    .iter()
+                    .filter(move |service| name.map_or(true, |name| &service.name == name))
+                    .map(move |service| model::Service {
+                        container: container.clone(),
+                        name: service.name.clone(),
+                        port: service.port,
+                        protocol: service.protocol,
+                        namespace,
+                    })
+            })
+            .collect()
+    }
+
+    /// List or read files of a mounted container. Paths within a persist mount
+    /// refer to the persist directory.
+    async fn fs(
+        &self,
+        container: &Container,
+        path: &Path,
+        op: &model::FsOp,
+    ) -> Result<model::FsResponse, Error> {
+        let state = self.state(container)?;
+        let root = state
+            .root
+            .as_ref()
+            .ok_or_else(|| Error::Fs(container.clone(), "container is not mounted".to_string()))?;
+        let manifest = self.manifest(container)?;
+
+        if !path.is_absolute() {
+            return Err(Error::Fs(
+                container.clone(),
+                format!("path {} is not absolute", path.display()),
+            ));
+        }
+
+        // Check if the path is within a persist mount
+        let persist = manifest
+            .mounts
+            .iter()
+            .filter(|(_, mount)| matches!(mount, Mount::Persist(_)))
+            .find_map(|(target, _)| path.strip_prefix(target).ok());
+        let (base, path) = match persist {
+            Some(path) => (
+                persist::dir(&self.config, container, manifest),
+                Path::new("/").join(path),
+            ),
+            None => (root.clone(), path.to_owned()),
+        };
+
+        match op {
+            model::FsOp::List => browse::list(container, &base, &path).await,
+            model::FsOp::Read { offset, length } => {
+                browse::read(container, &base, &path, *offset, *length).await
+            }
+        }
+    }
+
+    /// Stream a tar archive of the persist directory of `container` to `tx`
+    async fn data_export(
+        &self,
+        container: &Container,
+        tx: mpsc::Sender<Bytes>,
+    ) -> Result<u64, Error> {
+        let manifest = self.manifest(container)?;
+        let started = self.state(container)?.process.is_some();
+        if !persist::has_persist_mount(manifest) {
+            return Err(Error::Data(
+                container.clone(),
+                "container has no persist mount".to_string(),
+            ));
+        }
+        let dir = persist::dir(&self.config, container, manifest);
+
+        info!("Exporting data of {}", container);
+        // The persist image of a stopped container is not mounted
+        if !started {
+            persist::mount(&self.config, container, manifest).await?;
+        }
+        let result = data::export(&self.config.run_dir, &dir, tx).await;
+        if !started {
+            persist::umount(&self.config, container, manifest).await?;
+        }
+        result
+    }
+
+    /// Core dump configuration. Fails if `container` is unknown or core dumps are not configured.
+    fn core_dump_config(&self, container: &Container) -> Result<&config::CoreDumps, Error> {
+        self.state(container)?;
+        self.config.core_dumps.as_ref().ok_or_else(|| {
+            Error::CoreDump(
+                container.clone(),
+                "core dumps are not configured".to_string(),
+            )
+        })
+    }
+
+    /// List the core dumps of `container`
+    async fn core_dumps(&self, container: &Container) -> Result<Vec<model::CoreDump>, Error> {
+        core_dump::list(self.core_dump_config(container)?, container).await
+    }
+
+    /// Attach `output` and `input` to the PTY of the started `container`. Attaching
+    /// must be enabled in the debug section of the runtime configuration.
+    async fn attach(
+        &self,
+        container: &Container,
+        output: mpsc::Sender<Bytes>,
+        input: mpsc::Receiver<Bytes>,
+    ) -> Result<(), Error> {
+        if !self
+            .config
+            .debug
+            .as_ref()
+            .map_or(false, |debug| debug.attach)
+        {
+            return Err(Error::Attach(
+                container.clone(),
+                "attaching is disabled".to_string(),
+            ));
+        }
+
+        let process = self
+            .state(container)?
+            .process
+            .as_ref()
+            .ok_or_else(|| Error::ContainerNotStarted(container.clone()))?;
+        let pty = process
+            .pty
+            .as_ref()
+            .ok_or_else(|| Error::Attach(container.clone(), "container has no tty".to_string()))?;
+
+        info!("Attaching to {}", container);
+        pty.send((output, input))
+            .await
+            .map_err(|_| Error::Attach(container.clone(), "container exited".to_string()))
+    }
+
+    /// Write `data` to the stdin of the started `container`. The input is buffered
+    /// and the request fails if the container does not keep up with reading.
+    fn stdin(&self, container: &Container, data: &[u8]) -> Result<(), Error> {
+        let process = self
+            .state(container)?
+            .process
+            .as_ref()
+            .ok_or_else(|| Error::ContainerNotStarted(container.clone()))?;
+        let stdin = process
+            .stdin
+            .as_ref()
+            .ok_or_else(|| Error::Stdin(container.clone(), "container has no stdin".to_string()))?;
+
+        stdin
+            .try_send(Bytes::copy_from_slice(data))
+            .map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => {
+                    Error::Stdin(container.clone(), "input buffer is full".to_string())
+                }
+                mpsc::error::TrySendError::Closed(_) => {
+                    Error::Stdin(container.clone(), "stdin is closed".to_string())
+                }
+            })
+    }
+
+    /// Stream the core dump `name` of `container` to `tx`
+    async fn core_dump_export(
+        &self,
+        container: &Container,
+        name: &str,
+        tx: mpsc::Sender<Bytes>,
+    ) -> Result<u64, Error> {
+        core_dump::export(self.core_dump_config(container)?, container, name, tx).await
+    }
+
+    /// Remove the core dump `name` of `container`
+    async fn core_dump_remove(&self, container: &Container, name: &str) -> Result<(), Error> {
+        core_dump::remove(self.core_dump_config(container)?, container, name).await
+    }
+
+    /// Store the minidump written by the init process of `container` with `pid`
+    async fn store_minidump(
+        &self,
+        container: &Container,
+        file: std::fs::File,
+        pid: Pid,
+        signal: u8,
+    ) {
+        if let Some(config) = self.config.core_dumps.as_ref() {
+            match core_dump::store_minidump(config, container, file, pid, signal as u32).await {
+                Ok(Some(name)) => info!("Stored minidump {} of {}", name, container),
+                Ok(None) => (),
+                Err(e) => warn!("failed to store minidump of {}: {}", container, e),
+            }
+        }
+    }
+
+    /// Send a notification for each core dump of `container` written since the process
+    /// started `uptime` ago
+    async fn forward_core_dumps(&self, container: &Container, uptime: time::Duration) {
+        let config = match self.config.core_dumps.as_ref() {
+            Some(config) => config,
+            None => return,
+        };
+        let since = SystemTime::now()
+            .checked_sub(uptime)
+            .and_then(|started| started.duration_since(UNIX_EPOCH).ok())
+            .map(|started| started.as_secs())
+            .unwrap_or_default();
+        match core_dump::list(config, container).await {
+            Ok(dumps) => {
+                for dump in dumps.into_iter().filter(|dump| dump.time >= since) {
+                    warn!("Process {} dumped core {}", container, dump.name);
+                    self.container_event(container, ContainerEvent::CoreDump(dump));
+                }
+            }
+            Err(e) => warn!("failed to list core dumps of {}: {}", container, e),
+        }
+    }
+
+    /// Replace the persist directory of `container` with the tar archive received from `rx`
+    async fn data_import(
+        &self,
+        container: &Container,
+        rx: &mut mpsc::Receiver<Bytes>,
+    ) -> Result<(), Error> {
+        let manifest = self.manifest(container)?;
+        let error = if self.state(container)?.process.is_some() {
+            Some("container is started")
+        } else if !persist::has_persist_mount(manifest) {
+            Some("container has no persist mount")
+        } else if self.is_share_started(container, manifest) {
+            Some("persist directory is used by a started container")
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            // The console drains the upload if the receiver is dropped
+            return Err(Error::Data(container.clone(), error.to_string()));
+        }
+        let dir = persist::dir(&self.config, container, manifest);
+
+        info!("Importing data of {}", container);
+        persist::mount(&self.config, container, manifest).await?;
+        let result = data::import(&self.config.run_dir, &dir, manifest.uid, manifest.gid, rx).await;
+        persist::umount(&self.config, container, manifest).await?;
+        result
+    }
+
+    /// Wipe the persist directory of `container`. A started container is killed and
+    /// the reply is sent once the data is removed after the container exited.
+    async fn data_reset(
+        &mut self,
+        container: &Container,
+        response: oneshot::Sender<model::Response>,
+    ) {
+        let manifest = match self.manifest(container) {
+            Ok(manifest) if persist::has_persist_mount(manifest) => manifest.clone(),
+            Ok(_) => {
+                let error = Error::Data(
+                    container.clone(),
+                    "container has no persist mount".to_string(),
+                );
+                response.send(model::Response::Error(error.into())).ok();
+                return;
+            }
+            Err(e) => {
+                response.send(model::Response::Error(e.into())).ok();
+                return;
+            }
+        };
+
+        if self.is_share_started(container, &manifest) {
+            let error = Error::Data(
+                container.clone(),
+                "persist directory is used by a started container".to_string(),
+            );
+            response.send(model::Response::Error(error.into())).ok();
+            return;
+        }
+
+        if let Some(context) = self
+            .containers
+            .get_mut(container)
+            .and_then(|state| state.process.as_mut())
+        {
+            if context.data_reset.is_some() {
+                let error = Error::Data(container.clone(), "reset is pending".to_string());
+                response.send(model::Response::Error(error.into())).ok();
+                return;
+            }
+
+            info!("Killing {} for data reset", container);
+            context.data_reset = Some(response);
+            if let Err(e) = self.kill(container, Signal::SIGKILL).await {
+                warn!("failed to kill {}: {}", container, e);
+            }
+            self.schedule_stop_timeout(container);
+            return;
+        }
+
+        let payload = match persist::reset(&self.config, container, &manifest).await {
+            Ok(_) => {
+                self.container_event(container, ContainerEvent::DataReset);
+                model::Response::Ok
+            }
+            Err(e) => {
+                warn!("failed to reset data of {}: {}", container, e);
+                model::Response::Error(e.into())
+            }
+        };
+        response.send(payload).ok();
+    }
+
     /// Handle the exit of a container
     async fn on_exit(
         &mut self,
@@ -773,11 +3250,27 @@ impl State {
             .manifest(container)
             .ok()
             .and_then(|manfiest| manfiest.autostart.clone());
+        // Containers killed for a forced uninstall are not critical
+        let uninstall = self
+            .uninstalls
+            .iter()
+            .any(|(uninstall, _, _)| self.users(uninstall).contains(container));
 
         if let Ok(state) = self.state_mut(container) {
-            if let Some(process) = state.process.take() {
+            if let Some(mut process) = state.process.take() {
+                let exit_status = &process.exit_status(exit_status);
+                let restart = (state.take_restart() && !is_shutdown).then(|| {
+                    (
+                        process.args_extra.clone(),
+                        process.env_extra.clone(),
+                        process.parameters.clone(),
+                    )
+                });
+                let data_reset = process.data_reset.take();
                 let is_critical = autostart == Some(Autostart::Critical);
-                let is_critical = is_critical && !is_shutdown;
+                // Named instances are never critical
+                let is_critical = is_critical && container.instance().is_none();
+                let is_critical = is_critical && !is_shutdown && data_reset.is_none() && !uninstall;
                 let duration = process.started.elapsed();
                 if is_critical {
                     error!(
@@ -795,12 +3288,108 @@ impl State {
                     );
                 }
 
-                process.destroy().await;
+                let time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_secs())
+                    .unwrap_or_default();
+                state.record_exit(model::ContainerExit {
+                    time,
+                    uptime: duration.as_nanos() as u64,
+                    status: exit_status.clone().into(),
+                    oom: process.oom,
+                });
+
+                let minidump = process.minidump.take();
+                let pid = process.pid;
+                let timeout = self.config.stop_timeout;
+                if time::timeout(timeout, process.destroy()).await.is_err() {
+                    warn!("Cleanup of {} timed out after {:?}", container, timeout);
+                }
+                self.throttled.remove(container);
+
+                if let (Some(file), ExitStatus::Signalled(signal, _)) = (minidump, exit_status) {
+                    self.store_minidump(container, file, pid, *signal).await;
+                }
+                self.forward_core_dumps(container, duration).await;
+
+                if let Err(e) = secret::remove(&self.config, container).await {
+                    warn!("failed to remove secrets of {}: {}", container, e);
+                }
+
+                if let Err(e) = overlay::remove(&self.config, container).await {
+                    warn!("failed to remove root overlay of {}: {}", container, e);
+                }
+
+                if let Ok(manifest) = self.manifest(container) {
+                    if let Err(e) = persist::umount(&self.config, container, manifest).await {
+                        warn!("failed to umount persist image of {}: {}", container, e);
+                    }
+                }
 
                 self.container_event(container, ContainerEvent::Exit(exit_status.clone()));
+                self.schedule_umount_idle(container);
+
+                // Wipe the persist dir if requested
+                if let Some(reply) = data_reset {
+                    let manifest = self.manifest(container)?;
+                    let payload = match persist::reset(&self.config, container, manifest).await {
+                        Ok(_) => {
+                            self.container_event(container, ContainerEvent::DataReset);
+                            model::Response::Ok
+                        }
+                        Err(e) => {
+                            warn!("failed to reset data of {}: {}", container, e);
+                            model::Response::Error(e.into())
+                        }
+                    };
+                    reply.send(payload).ok();
+                }
+
+                self.complete_uninstalls().await;
 
                 info!("Container {} exited with status {}", container, exit_status);
 
+                // Restart requested by the oom policy
+                if let Some((args, env, parameters)) = restart {
+                    info!("Restarting {}", container);
+                    let origin = Origin::Restart;
+                    match self
+                        .start(container, &args, &env, &parameters, origin, false)
+                        .await
+                    {
+                        Ok(_) => {
+                            self.state_mut(container)?.history.restarts += 1;
+                            return Ok(());
+                        }
+                        Err(Error::StartDeferred(_, delay)) => {
+                            info!("Restart of {} is deferred for {:?}", container, delay);
+                            let start = DeferredStart {
+                                container: container.clone(),
+                                args,
+                                env,
+                                parameters,
+                                origin,
+                                response: None,
+                            };
+                            self.defer(start, delay);
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            warn!("failed to restart {}: {}", container, e);
+                            // Failed container starts are already notified by `start`
+                            if !matches!(e, Error::StartContainerFailed(..)) {
+                                let error = format!("restart failed: {}", e);
+                                self.container_event(container, ContainerEvent::StartFailed(error));
+                            }
+                        }
+                    }
+                }
+
+                // Named instances are removed once they exited
+                if container.instance().is_some() {
+                    self.containers.remove(container);
+                }
+
                 // This is a critical flagged container that exited with a error exit code. That's not good...
                 if !exit_status.success() && is_critical {
                     return Err(Error::CriticalContainer(
@@ -822,68 +3411,276 @@ impl State {
     ) -> Result<(), Error> {
         match event {
             ContainerEvent::Started => (),
+            ContainerEvent::StartFailed(_) => (),
+            ContainerEvent::Exit(_) if self.launcher.is_killed(container) => {
+                debug!("Ignoring exit of the killed init of {}", container);
+            }
             ContainerEvent::Exit(exit_status) => {
                 self.on_exit(container, exit_status, is_shutdown).await?;
             }
             ContainerEvent::Installed => (),
             ContainerEvent::Uninstalled => (),
-            ContainerEvent::CGroup(CGroupEvent::Memory(_)) => {
-                warn!("Process {} is out of memory", container);
+            ContainerEvent::Paused => (),
+            ContainerEvent::Resumed => (),
+            ContainerEvent::DataReset => (),
+            ContainerEvent::Log(_) => (),
+            ContainerEvent::Kernel(_) => (),
+            ContainerEvent::CoreDump(_) => (),
+            ContainerEvent::CGroup(CGroupEvent::Memory(memory)) => {
+                self.on_memory_event(container, memory).await?;
             }
         }
 
+        if !is_shutdown {
+            self.save_snapshot().await;
+        }
+
+        Ok(())
+    }
+
+    /// Attribute a kernel message to a started container and forward it. Messages of
+    /// processes that are not part of a container are ignored.
+    pub(super) async fn on_kernel_message(&mut self, message: KernelMessage) -> Result<(), Error> {
+        if let Some(container) = self.kernel_message_container(&message).await {
+            info!("Kernel message of {}: {}", container, message.message);
+            // Remember the cause of a kill for the exit status
+            if let Some(context) = self
+                .containers
+                .get_mut(&container)
+                .and_then(|state| state.process.as_mut())
+            {
+                match message.kind {
+                    KernelMessageKind::Oom => context.oom = true,
+                    KernelMessageKind::Seccomp { syscall } => context.syscall = syscall,
+                    KernelMessageKind::Segfault => (),
+                }
+            }
+            self.container_event(&container, ContainerEvent::Kernel(message));
+        }
         Ok(())
     }
 
+    /// Find the started container that `message` relates to. The cgroup is taken from
+    /// the message or from procfs if the process is still alive.
+    async fn kernel_message_container(&self, message: &KernelMessage) -> Option<Container> {
+        let started = || {
+            self.containers
+                .iter()
+                .filter_map(|(c, state)| state.process.as_ref().map(|p| (c, p)))
+        };
+
+        // The init process of a container
+        if let Some((container, _)) = started().find(|(_, p)| p.pid == message.pid) {
+            return Some(container.clone());
+        }
+
+        let cgroups = match &message.cgroup {
+            Some(cgroup) => vec![cgroup.clone()],
+            // Lines of /proc/<pid>/cgroup are "hierarchy-ID:controller-list:cgroup-path"
+            None => fs::read_to_string(format!("/proc/{}/cgroup", message.pid))
+                .await
+                .ok()?
+                .lines()
+                .filter_map(|l| l.splitn(3, ':').nth(2))
+                .map(ToString::to_string)
+                .collect(),
+        };
+
+        // Container cgroups are "<config.cgroup>/<name>"
+        let top_level = Path::new(self.config.cgroup.as_str());
+        let name = cgroups.iter().find_map(|cgroup| {
+            let cgroup = Path::new(cgroup.trim_start_matches('/'));
+            if cgroup.parent()? == top_level {
+                cgroup.file_name()?.to_str()
+            } else {
+                None
+            }
+        })?;
+        started()
+            .find(|(c, _)| c.name().to_string() == name)
+            .map(|(c, _)| c.clone())
+    }
+
+    /// Forward a memory event and apply the oom policy of `container`
+    async fn on_memory_event(
+        &mut self,
+        container: &Container,
+        event: &MemoryEvent,
+    ) -> Result<(), Error> {
+        warn!("Process {} is out of memory", container);
+
+        self.container_event(
+            container,
+            ContainerEvent::CGroup(CGroupEvent::Memory(event.clone())),
+        );
+
+        let (policy, max_restarts) = self
+            .manifest(container)?
+            .oom
+            .as_ref()
+            .map(|oom| (oom.policy.clone(), oom.max_restarts))
+            .unwrap_or_default();
+
+        let context = match self.state_mut(container)?.process.as_mut() {
+            Some(context) => context,
+            None => return Ok(()),
+        };
+
+        // cgroups v2 reports cumulative counters while v1 signals each oom condition
+        let oom = match event.oom_kill {
+            Some(kills) => {
+                let new = kills > context.oom_kills;
+                context.oom_kills = kills;
+                new
+            }
+            None => event.oom.is_some(),
+        };
+
+        if !oom {
+            return Ok(());
+        }
+        context.oom = true;
+
+        match policy {
+            OomPolicy::Notify => Ok(()),
+            OomPolicy::Kill => {
+                info!("Killing {} because of oom policy", container);
+                self.kill(container, Signal::SIGKILL).await
+            }
+            OomPolicy::Restart => {
+                // The kill cancels pending restarts and is sent before the restart is requested
+                self.kill(container, Signal::SIGKILL).await?;
+                let max_restarts = max_restarts.unwrap_or(OOM_RESTARTS);
+                if self.state_mut(container)?.request_oom_restart(max_restarts) {
+                    info!("Restarting {} because of oom policy", container);
+                } else {
+                    warn!(
+                        "Not restarting {} after {} consecutive oom restarts",
+                        container, max_restarts
+                    );
+                    let error = format!("oom restart limit of {} reached", max_restarts);
+                    self.container_event(container, ContainerEvent::StartFailed(error));
+                }
+                Ok(())
+            }
+        }
+    }
+
     /// Process console events
+    #[instrument(name = "request", skip_all, fields(request = ?request))]
     pub(super) async fn on_request(
         &mut self,
         request: Request,
         response: oneshot::Sender<model::Response>,
     ) -> Result<(), Error> {
+        // The console drops the receiver of the response if the request is cancelled
+        // or the connection is closed before the request is processed
+        if response.is_closed() {
+            debug!("Skipping cancelled request {:?}", request);
+            return Ok(());
+        }
+
         match request {
+            Request::Request(model::Request::DataReset(container)) => {
+                self.data_reset(&container, response).await;
+            }
+            Request::Request(model::Request::Start(container, args, env)) => {
+                let start = DeferredStart {
+                    container,
+                    args,
+                    env,
+                    parameters: HashMap::with_capacity(0),
+                    origin: Origin::Request,
+                    response: Some(response),
+                };
+                self.on_start(start).await?;
+            }
+            Request::Request(model::Request::Uninstall {
+                container,
+                force,
+                wipe,
+            }) => {
+                self.on_uninstall(container, force, wipe, response).await;
+            }
+            Request::Request(model::Request::Mount(containers)) => {
+                self.mount_background(containers, AfterMount::Request(response));
+            }
+            Request::Request(model::Request::MountMatching(pattern)) => {
+                let containers = self.matching(&pattern, |state| !state.is_mounted());
+                self.mount_background(containers, AfterMount::Request(response));
+            }
+            Request::Request(model::Request::StartTemplate(container, parameters)) => {
+                let start = DeferredStart {
+                    container,
+                    args: Vec::with_capacity(0),
+                    env: HashMap::with_capacity(0),
+                    parameters,
+                    origin: Origin::Request,
+                    response: Some(response),
+                };
+                self.on_start(start).await?;
+            }
             Request::Request(ref request) => {
                 let payload = match request {
                     model::Request::Containers => {
                         model::Response::Containers(self.list_containers())
                     }
                     model::Request::Install { .. } => unreachable!(),
-                    model::Request::Mount(containers) => {
-                        let result = self
-                            .mount_all(containers)
-                            .await
-                            .drain(..)
-                            .zip(containers)
-                            .map(|(r, c)| match r {
-                                Ok(r) => model::MountResult::Ok { container: r },
-                                Err(e) => model::MountResult::Error {
-                                    container: c.clone(),
-                                    error: e.into(),
-                                },
-                            })
-                            .collect();
-                        model::Response::Mount(result)
+                    model::Request::Attach { .. } => unreachable!(),
+                    model::Request::DataExport { .. } => unreachable!(),
+                    model::Request::CoreDumpExport { .. } => unreachable!(),
+                    model::Request::CoreDumps(container) => {
+                        match self.core_dumps(container).await {
+                            Ok(dumps) => model::Response::CoreDumps(dumps),
+                            Err(e) => {
+                                warn!("failed to list core dumps of {}: {}", container, e);
+                                model::Response::Error(e.into())
+                            }
+                        }
                     }
-                    model::Request::Umount(containers) => {
-                        let result = self
-                            .umount_all(containers)
-                            .await
-                            .drain(..)
-                            .zip(containers)
-                            .map(|(r, c)| match r {
-                                Ok(r) => model::UmountResult::Ok { container: r },
-                                Err(e) => model::UmountResult::Error {
-                                    container: c.clone(),
-                                    error: e.into(),
-                                },
-                            })
-                            .collect();
-                        model::Response::Umount(result)
+                    model::Request::CoreDumpRemove(container, name) => {
+                        match self.core_dump_remove(container, name).await {
+                            Ok(_) => model::Response::Ok,
+                            Err(e) => {
+                                warn!(
+                                    "failed to remove core dump {} of {}: {}",
+                                    name, container, e
+                                );
+                                model::Response::Error(e.into())
+                            }
+                        }
+                    }
+                    model::Request::DataImport { .. } => unreachable!(),
+                    model::Request::DataReset { .. } => unreachable!(),
+                    model::Request::Mount(_) => unreachable!(),
+                    model::Request::MountMatching(_) => unreachable!(),
+                    request @ model::Request::Umount(_) => self.apply(request, false).await,
+                    model::Request::UmountMatching(pattern) => {
+                        let containers = self.matching(pattern, |state| {
+                            state.is_mounted() && state.process.is_none()
+                        });
+                        let request = model::Request::Umount(containers);
+                        self.apply(&request, false).await
+                    }
+                    model::Request::Group(selector, op) => {
+                        model::Response::Group(self.group(selector, op).await)
+                    }
+                    model::Request::Batch(requests, mode) => {
+                        model::Response::Batch(self.batch(requests, *mode).await)
                     }
                     model::Request::Repositories => {
                         let repositories = self.repositories.keys().cloned().collect();
                         model::Response::Repositories(repositories)
                     }
+                    model::Request::SystemInfo => {
+                        match system::info(&self.config, self.events_tx.depths()) {
+                            Ok(info) => model::Response::SystemInfo(info),
+                            Err(e) => {
+                                warn!("failed to gather system information: {}", e);
+                                model::Response::Error(e.into())
+                            }
+                        }
+                    }
                     model::Request::Shutdown => {
                         self.events_tx
                             .send(Event::Shutdown)
@@ -891,35 +3688,79 @@ impl State {
                             .expect("Internal channel error on main");
                         model::Response::Ok
                     }
-                    model::Request::Start(container, args, env) => {
-                        match self.start(container, args, env).await {
+                    model::Request::Start { .. } => unreachable!(),
+                    model::Request::StartTemplate { .. } => unreachable!(),
+                    model::Request::Uninstall { .. } => unreachable!(),
+                    model::Request::Kill(..) => self.apply(request, false).await,
+                    model::Request::Signal(container, signal, process) => {
+                        let process = process.as_deref();
+                        match Signal::try_from(*signal) {
+                            Ok(signal) => match self.signal(container, signal, process).await {
+                                Ok(_) => model::Response::Ok,
+                                Err(e) => {
+                                    warn!("failed to signal {} with {}: {}", container, signal, e);
+                                    model::Response::Error(e.into())
+                                }
+                            },
+                            Err(e) => {
+                                warn!("failed to signal {} with {}: {}", container, signal, e);
+                                model::Response::Error(model::Error::Unexpected {
+                                    module: "invalid signal".into(),
+                                    error: e.to_string(),
+                                })
+                            }
+                        }
+                    }
+                    model::Request::Stdin(container, data) => match self.stdin(container, data) {
+                        Ok(_) => model::Response::Ok,
+                        Err(e) => {
+                            warn!("failed to write stdin of {}: {}", container, e);
+                            model::Response::Error(e.into())
+                        }
+                    },
+                    model::Request::Pause(container) => match self.pause(container).await {
+                        Ok(_) => model::Response::Ok,
+                        Err(e) => {
+                            warn!("failed to pause {}: {}", container, e);
+                            model::Response::Error(e.into())
+                        }
+                    },
+                    model::Request::Resume(container) => match self.resume(container).await {
+                        Ok(_) => model::Response::Ok,
+                        Err(e) => {
+                            warn!("failed to resume {}: {}", container, e);
+                            model::Response::Error(e.into())
+                        }
+                    },
+                    model::Request::PolicyInput(input, value) => {
+                        match self.policy_input(input, *value).await {
                             Ok(_) => model::Response::Ok,
                             Err(e) => {
-                                warn!("failed to start {}: {}", container, e);
+                                warn!("failed to update policy input {}: {}", input, e);
                                 model::Response::Error(e.into())
                             }
                         }
                     }
-                    model::Request::Kill(container, signal) => match Signal::try_from(*signal) {
-                        Ok(signal) => match self.kill(container, signal).await {
+                    model::Request::SystemSuspend(containers) => {
+                        match self.system_suspend(containers).await {
                             Ok(_) => model::Response::Ok,
                             Err(e) => {
-                                error!("failed to kill {} with {}: {}", container, signal, e);
+                                warn!("failed to suspend: {}", e);
                                 model::Response::Error(e.into())
                             }
-                        },
+                        }
+                    }
+                    model::Request::SystemResume => match self.system_resume().await {
+                        Ok(_) => model::Response::Ok,
                         Err(e) => {
-                            error!("failed to kill {} with {}: {}", container, signal, e);
-                            model::Response::Error(model::Error::Unexpected {
-                                module: "invalid signal".into(),
-                                error: e.to_string(),
-                            })
+                            warn!("failed to resume: {}", e);
+                            model::Response::Error(e.into())
                         }
                     },
-                    model::Request::Uninstall(container) => match self.uninstall(container).await {
-                        Ok(_) => api::model::Response::Ok,
+                    model::Request::Inspect(container) => match self.inspect(container) {
+                        Ok(inspect) => model::Response::Inspect(Box::new(inspect)),
                         Err(e) => {
-                            warn!("failed to uninstall {}: {}", container, e);
+                            warn!("failed to inspect {}: {}", container, e);
                             model::Response::Error(e.into())
                         }
                     },
@@ -934,32 +3775,497 @@ impl State {
                             }
                         }
                     }
-                    model::Request::Ident => unreachable!(), // handled in module console
-                    model::Request::TokenCreate(..) => unreachable!(), // handled in module console
-                    model::Request::TokenVerify(..) => unreachable!(), // handled in module console
+                    model::Request::Services(name) => {
+                        model::Response::Services(self.services(name.as_ref()))
+                    }
+                    model::Request::StartDryRun(container) => match self.start_dry_run(container) {
+                        Ok(checks) => model::Response::StartDryRun(checks),
+                        Err(e) => {
+                            warn!("failed to check the start of {}: {}", container, e);
+                            model::Response::Error(e.into())
+                        }
+                    },
+                    model::Request::Ps(container) => match self.ps(container) {
+                        Ok(processes) => model::Response::Ps(processes),
+                        Err(e) => {
+                            warn!("failed to list processes of {}: {}", container, e);
+                            model::Response::Error(e.into())
+                        }
+                    },
+                    model::Request::Fs {
+                        container,
+                        path,
+                        op,
+                    } => match self.fs(container, path, op).await {
+                        Ok(response) => model::Response::Fs(response),
+                        Err(e) => {
+                            warn!(
+                                "failed to access {} of {}: {}",
+                                path.display(),
+                                container,
+                                e
+                            );
+                            model::Response::Error(e.into())
+                        }
+                    },
+                    model::Request::Ident => unreachable!(), // handled in module console
+                    model::Request::InstallCancel(..) => unreachable!(), // handled in module console
+                    model::Request::Installs => unreachable!(), // handled in module console
+                    model::Request::Cancel(..) => unreachable!(), // handled in module console
+                    model::Request::TokenCreate(..) => unreachable!(), // handled in module console
+                    model::Request::TokenVerify(..) => unreachable!(), // handled in module console
+                };
+
+                // A error on the response_tx means that the connection
+                // was closed in the meantime. Ignore it.
+                response.send(payload).ok();
+
+                self.save_snapshot().await;
+            }
+            Request::Install(repository, size, mut rx) => {
+                let payload = match self.install(&repository, size, &mut rx).await {
+                    Ok(container) => model::Response::Install(container),
+                    Err(e) => model::Response::Error(e.into()),
+                };
+
+                // A error on the response_tx means that the connection
+                // was closed in the meantime. Ignore it.
+                response.send(payload).ok();
+            }
+            Request::DataExport(container, tx) => {
+                let payload = match self.data_export(&container, tx).await {
+                    Ok(size) => model::Response::DataExport(size),
+                    Err(e) => {
+                        warn!("failed to export data of {}: {}", container, e);
+                        model::Response::Error(e.into())
+                    }
+                };
+                response.send(payload).ok();
+            }
+            Request::CoreDumpExport(container, name, tx) => {
+                let payload = match self.core_dump_export(&container, &name, tx).await {
+                    Ok(size) => model::Response::CoreDumpExport(size),
+                    Err(e) => {
+                        warn!(
+                            "failed to export core dump {} of {}: {}",
+                            name, container, e
+                        );
+                        model::Response::Error(e.into())
+                    }
+                };
+                response.send(payload).ok();
+            }
+            Request::Attach(container, output, input) => {
+                let payload = match self.attach(&container, output, input).await {
+                    Ok(_) => model::Response::Ok,
+                    Err(e) => {
+                        warn!("failed to attach to {}: {}", container, e);
+                        model::Response::Error(e.into())
+                    }
+                };
+                response.send(payload).ok();
+            }
+            Request::DataImport(container, mut rx) => {
+                let payload = match self.data_import(&container, &mut rx).await {
+                    Ok(_) => model::Response::Ok,
+                    Err(e) => {
+                        warn!("failed to import data of {}: {}", container, e);
+                        model::Response::Error(e.into())
+                    }
+                };
+                response.send(payload).ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the requests of a batch in order and return a response for each request.
+    /// An all or nothing batch is checked before any request is applied and stops at
+    /// the first failed request. The requests applied so far are rolled back.
+    async fn batch(
+        &mut self,
+        requests: &[model::Request],
+        mode: model::BatchMode,
+    ) -> Vec<model::Response> {
+        let all_or_nothing = mode == model::BatchMode::AllOrNothing;
+
+        if all_or_nothing {
+            if let Err((n, e)) = self.batch_check(requests) {
+                warn!("failed to check request {} of batch: {}", n, e);
+                return Self::batch_aborted(requests.len(), n, e.into());
+            }
+        }
+
+        let mut responses = Vec::with_capacity(requests.len());
+        for (n, request) in requests.iter().enumerate() {
+            let response = self.apply(request, !all_or_nothing).await;
+            let error = match &response {
+                model::Response::Error(error) => Some(error.clone()),
+                model::Response::Mount(results) => results.iter().find_map(|r| match r {
+                    model::MountResult::Error { error, .. } => Some(error.clone()),
+                    model::MountResult::Ok { .. } => None,
+                }),
+                model::Response::Umount(results) => results.iter().find_map(|r| match r {
+                    model::UmountResult::Error { error, .. } => Some(error.clone()),
+                    model::UmountResult::Ok { .. } => None,
+                }),
+                _ => None,
+            };
+            responses.push(response);
+
+            if let Some(error) = error.filter(|_| all_or_nothing) {
+                warn!("Request {} of batch failed. Rolling back...", n);
+                // Failed mounts and umounts might be partially applied
+                for (request, response) in requests.iter().zip(&responses).rev() {
+                    self.rollback(request, response).await;
+                }
+                self.save_snapshot().await;
+                return Self::batch_aborted(requests.len(), n, error);
+            }
+        }
+
+        self.save_snapshot().await;
+        responses
+    }
+
+    /// Responses of an aborted batch with `error` for the failed request
+    fn batch_aborted(len: usize, failed: usize, error: model::Error) -> Vec<model::Response> {
+        let mut responses = (0..len)
+            .map(|_| model::Response::Error(Error::BatchAborted(failed).into()))
+            .collect::<Vec<_>>();
+        responses[failed] = model::Response::Error(error);
+        responses
+    }
+
+    /// Check the requests of an all or nothing batch against the current state and
+    /// the effect of the preceding requests. Returns the index and error of the first
+    /// request that would fail.
+    fn batch_check(&self, requests: &[model::Request]) -> Result<(), (usize, Error)> {
+        // Started and mounted state changed by the preceding requests. Killed
+        // containers exit asynchronously and are considered started.
+        let mut started = HashMap::<&Container, bool>::new();
+        let mut mounted = HashMap::<&Container, bool>::new();
+        let is_started = |started: &HashMap<&Container, bool>, container: &Container| {
+            started.get(container).copied().unwrap_or_else(
+                || matches!(self.state(container), Ok(state) if state.process.is_some()),
+            )
+        };
+        let is_mounted = |mounted: &HashMap<&Container, bool>, container: &Container| {
+            mounted
+                .get(container)
+                .copied()
+                .unwrap_or_else(|| matches!(self.state(container), Ok(state) if state.is_mounted()))
+        };
+
+        for (n, request) in requests.iter().enumerate() {
+            match request {
+                model::Request::Start(container, ..)
+                | model::Request::StartTemplate(container, _) => {
+                    if is_started(&started, container) {
+                        return Err((n, Error::StartContainerStarted(container.clone())));
+                    }
+                    started.insert(container, true);
+                    mounted.insert(container, true);
+                }
+                model::Request::Kill(container, signal) => {
+                    if let Err(e) = Signal::try_from(*signal) {
+                        return Err((n, Error::InvalidArguments(e.to_string())));
+                    }
+                    if !is_started(&started, container) {
+                        return Err((n, Error::StopContainerNotStarted(container.clone())));
+                    }
+                }
+                model::Request::Mount(containers) => {
+                    for container in containers {
+                        if let Err(e) = self.state(container) {
+                            return Err((n, e));
+                        }
+                        if is_mounted(&mounted, container) {
+                            return Err((n, Error::MountBusy(container.clone())));
+                        }
+                        mounted.insert(container, true);
+                    }
+                }
+                model::Request::Umount(containers) => {
+                    for container in containers {
+                        if !is_mounted(&mounted, container) || is_started(&started, container) {
+                            return Err((n, Error::UmountBusy(container.clone())));
+                        }
+                        mounted.insert(container, false);
+                    }
+                }
+                _ => {
+                    let error = Error::InvalidArguments("invalid batch request".to_string());
+                    return Err((n, error));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo the applied part of a batch `request` with `response`. Sent signals are
+    /// not undone.
+    async fn rollback(&mut self, request: &model::Request, response: &model::Response) {
+        match (request, response) {
+            (
+                model::Request::Start(container, ..) | model::Request::StartTemplate(container, _),
+                model::Response::Ok,
+            ) => {
+                info!("Rolling back start of {}", container);
+                self.kill(container, Signal::SIGKILL).await.ok();
+            }
+            (_, model::Response::Mount(results)) => {
+                let mounted = results
+                    .iter()
+                    .filter_map(|r| match r {
+                        model::MountResult::Ok { container } => Some(container.clone()),
+                        model::MountResult::Error { .. } => None,
+                    })
+                    .collect::<Vec<_>>();
+                info!("Rolling back mount of {} containers", mounted.len());
+                self.umount_all(&mounted).await;
+            }
+            (_, model::Response::Umount(results)) => {
+                let umounted = results
+                    .iter()
+                    .filter_map(|r| match r {
+                        model::UmountResult::Ok { container } => Some(container.clone()),
+                        model::UmountResult::Error { .. } => None,
+                    })
+                    .collect::<Vec<_>>();
+                info!("Rolling back umount of {} containers", umounted.len());
+                self.mount_all(&umounted).await;
+            }
+            _ => (),
+        }
+    }
+
+    /// Apply a start, kill, mount or umount request. A deferred start is scheduled if
+    /// `defer` is set and fails otherwise.
+    async fn apply(&mut self, request: &model::Request, defer: bool) -> model::Response {
+        match request {
+            model::Request::Start(container, args, env) => {
+                let start = DeferredStart {
+                    container: container.clone(),
+                    args: args.clone(),
+                    env: env.clone(),
+                    parameters: HashMap::with_capacity(0),
+                    origin: Origin::Request,
+                    response: None,
+                };
+                self.apply_start(start, defer).await
+            }
+            model::Request::StartTemplate(container, parameters) => {
+                let start = DeferredStart {
+                    parameters: parameters.clone(),
+                    ..DeferredStart::new(container.clone(), Origin::Request)
+                };
+                self.apply_start(start, defer).await
+            }
+            model::Request::Kill(container, signal) => match Signal::try_from(*signal) {
+                Ok(signal) => match self.kill(container, signal).await {
+                    Ok(_) => model::Response::Ok,
+                    Err(e) => {
+                        error!("failed to kill {} with {}: {}", container, signal, e);
+                        model::Response::Error(e.into())
+                    }
+                },
+                Err(e) => {
+                    error!("failed to kill {} with {}: {}", container, signal, e);
+                    model::Response::Error(model::Error::Unexpected {
+                        module: "invalid signal".into(),
+                        error: e.to_string(),
+                    })
+                }
+            },
+            model::Request::Mount(containers) => {
+                let results = self.mount_all(containers).await;
+                Self::mount_response(containers, results)
+            }
+            model::Request::Umount(containers) => {
+                let result = self
+                    .umount_all(containers)
+                    .await
+                    .drain(..)
+                    .zip(containers)
+                    .map(|(r, c)| match r {
+                        Ok(r) => model::UmountResult::Ok { container: r },
+                        Err(e) => model::UmountResult::Error {
+                            container: c.clone(),
+                            error: e.into(),
+                        },
+                    })
+                    .collect();
+                model::Response::Umount(result)
+            }
+            _ => model::Response::Error(
+                Error::InvalidArguments("invalid batch request".to_string()).into(),
+            ),
+        }
+    }
+
+    async fn apply_start(&mut self, start: DeferredStart, defer: bool) -> model::Response {
+        let result = self
+            .start(
+                &start.container,
+                &start.args,
+                &start.env,
+                &start.parameters,
+                start.origin,
+                false,
+            )
+            .await;
+        match result {
+            Ok(_) => model::Response::Ok,
+            Err(Error::StartDeferred(container, delay)) if defer => {
+                info!("Start of {} is deferred for {:?}", container, delay);
+                self.defer(start, delay);
+                model::Response::Ok
+            }
+            Err(e) => {
+                warn!("failed to start {}: {}", start.container, e);
+                model::Response::Error(e.into())
+            }
+        }
+    }
+
+    /// Containers without instance name whose `name:version` matches `pattern` and
+    /// whose state is accepted by `filter`. A `*` in `pattern` matches any sequence of
+    /// characters.
+    fn matching<F>(&self, pattern: &str, filter: F) -> Vec<Container>
+    where
+        F: Fn(&ContainerState) -> bool,
+    {
+        let mut containers = self
+            .containers
+            .iter()
+            .filter(|(container, state)| {
+                container.instance().is_none()
+                    && filter(state)
+                    && wildcard_match(pattern, &container.to_string())
+            })
+            .map(|(container, _)| container.clone())
+            .collect::<Vec<_>>();
+        containers.sort();
+        containers
+    }
+
+    /// Apply `op` to all containers whose labels match `selector`. Named instances
+    /// match with the labels of their container.
+    async fn group(
+        &mut self,
+        selector: &model::Selector,
+        op: &model::GroupOp,
+    ) -> Vec<model::GroupResult> {
+        let mut matches = self
+            .containers
+            .iter()
+            .filter(|(container, _)| {
+                self.manifest(container)
+                    .map(|manifest| selector.matches(&manifest.labels))
+                    .unwrap_or(false)
+            })
+            .filter(|(container, state)| match op {
+                // Named instances are started with a start request of the instance
+                model::GroupOp::Start => {
+                    container.instance().is_none()
+                        && state.process.is_none()
+                        && self
+                            .manifest(container)
+                            .map(|manifest| manifest.init.is_some())
+                            .unwrap_or(false)
+                }
+                model::GroupOp::Kill(_) => state.process.is_some(),
+                model::GroupOp::Mount => container.instance().is_none() && !state.is_mounted(),
+                model::GroupOp::Umount => container.instance().is_none() && state.is_mounted(),
+            })
+            .map(|(container, _)| container.clone())
+            .collect::<Vec<_>>();
+        matches.sort();
+
+        info!(
+            "Applying {:?} to {} containers matching {}",
+            op,
+            matches.len(),
+            selector
+        );
+
+        let results: Vec<Result<Container, Error>> = match op {
+            model::GroupOp::Start => {
+                let mut results = Vec::with_capacity(matches.len());
+                for container in &matches {
+                    let result = match self
+                        .start(
+                            container,
+                            &[],
+                            &HashMap::with_capacity(0),
+                            &HashMap::with_capacity(0),
+                            Origin::Request,
+                            false,
+                        )
+                        .await
+                    {
+                        Ok(()) => Ok(container.clone()),
+                        Err(Error::StartDeferred(_, delay)) => {
+                            info!("Start of {} is deferred for {:?}", container, delay);
+                            let start = DeferredStart::new(container.clone(), Origin::Request);
+                            self.defer(start, delay);
+                            Ok(container.clone())
+                        }
+                        Err(e) => Err(e),
+                    };
+                    results.push(result);
+                }
+                self.save_snapshot().await;
+                results
+            }
+            model::GroupOp::Kill(signal) => {
+                let signal = match Signal::try_from(*signal) {
+                    Ok(signal) => signal,
+                    Err(e) => {
+                        return matches
+                            .drain(..)
+                            .map(|container| model::GroupResult::Error {
+                                container,
+                                error: model::Error::Unexpected {
+                                    module: "invalid signal".into(),
+                                    error: e.to_string(),
+                                },
+                            })
+                            .collect()
+                    }
                 };
-
-                // A error on the response_tx means that the connection
-                // was closed in the meantime. Ignore it.
-                response.send(payload).ok();
+                let mut results = Vec::with_capacity(matches.len());
+                for container in &matches {
+                    let result = self.kill(container, signal).await;
+                    results.push(result.map(|_| container.clone()));
+                }
+                results
             }
-            Request::Install(repository, mut rx) => {
-                let payload = match self.install(&repository, &mut rx).await {
-                    Ok(container) => model::Response::Install(container),
-                    Err(e) => model::Response::Error(e.into()),
-                };
+            model::GroupOp::Mount => self.mount_all(&matches).await,
+            model::GroupOp::Umount => self.umount_all(&matches).await,
+        };
 
-                // A error on the response_tx means that the connection
-                // was closed in the meantime. Ignore it.
-                response.send(payload).ok();
-            }
-        }
-        Ok(())
+        results
+            .into_iter()
+            .zip(matches)
+            .map(|(result, container)| match result {
+                Ok(_) => model::GroupResult::Ok { container },
+                Err(e) => {
+                    warn!("failed to apply {:?} to {}: {}", op, container, e);
+                    model::GroupResult::Error {
+                        container,
+                        error: e.into(),
+                    }
+                }
+            })
+            .collect()
     }
 
-    /// Try to mount all containers in `containers` in parallel and return the results. The parallelism
-    /// is archived by a dedicated thread pool that executes the blocking mount operations on n threads
-    /// as configured in the runtime configuration.
+    /// Try to mount all containers in `containers` in parallel and return the results. The blocking
+    /// mount operations run on the blocking thread pool with at most `mount_concurrency` mounts in
+    /// flight.
+    #[instrument(skip_all)]
     async fn mount_all(&mut self, containers: &[Container]) -> Vec<Result<Container, Error>> {
         let start = time::Instant::now();
         let mut mounts = Vec::with_capacity(containers.len());
@@ -969,11 +4275,14 @@ impl State {
             match self.state(container) {
                 // Containers cannot be mounted twice. If the container
                 // is already mounted return an error for this entity.
-                Ok(state) if state.is_mounted() => {
+                Ok(state) if state.is_mounted() || self.mounting.contains(container) => {
                     let error = Err(Error::MountBusy(container.clone()));
                     mounts.push(Either::Right(ready(error)));
                 }
-                Ok(_) => mounts.push(Either::Left(self.mount(container))),
+                Ok(_) => {
+                    let span = info_span!("mount", container = %container);
+                    mounts.push(Either::Left(self.mount(container).instrument(span)))
+                }
                 Err(_) => {
                     let error = Err(Error::InvalidContainer(container.clone()));
                     mounts.push(Either::Right(ready(error)));
@@ -981,9 +4290,13 @@ impl State {
             }
         }
 
-        // Mount and process results
+        // Mount and process results in the order of `containers`
+        let mount_results = stream::iter(mounts)
+            .buffered(self.config.mount_concurrency)
+            .collect::<Vec<_>>()
+            .await;
         let mut result = Vec::with_capacity(containers.len());
-        for (container, mount_result) in containers.iter().zip(join_all(mounts).await) {
+        for (container, mount_result) in containers.iter().zip(mount_results) {
             match mount_result {
                 Ok(root) => {
                     let state = self.state_mut(container).expect("Internal error");
@@ -1031,8 +4344,8 @@ impl State {
                 continue;
             };
 
-            // Check if container is mounted at all
-            if !container_state.is_mounted() {
+            // Check if container is mounted at all or still being mounted
+            if !container_state.is_mounted() || self.mounting.contains(umount_container) {
                 let error = Err(Error::UmountBusy(umount_container.clone()));
                 mounts.push(Either::Right(ready(error)));
                 continue;
@@ -1045,6 +4358,14 @@ impl State {
                 continue;
             }
 
+            // Check if a named instance of the container is started
+            if let Some(instance) = self.instances(umount_container).next() {
+                warn!("{} is used by {}", umount_container, instance);
+                let error = Err(Error::UmountBusy(instance.clone()));
+                mounts.push(Either::Right(ready(error)));
+                continue;
+            }
+
             // If this container is a resource check all running containers if they
             // depend on `container`
             if manifest.init.is_none() {
@@ -1062,20 +4383,18 @@ impl State {
                         continue;
                     }
 
-                    for mount in &manifest.mounts {
-                        if let Mount::Resource(Resource { name, version, .. }) = mount.1 {
-                            if State::match_container(name, version, self.containers.keys())
-                                .filter(|resource| &umount_container == resource)
-                                .is_some()
-                            {
-                                warn!(
-                                    "Resource container {} is used by {}",
-                                    umount_container, running_container
-                                );
-                                let error = Err(Error::UmountBusy(running_container.clone()));
-                                mounts.push(Either::Right(ready(error)));
-                                continue 'outer;
-                            }
+                    for (name, version) in manifest.mounts.values().flat_map(Mount::resources) {
+                        if State::match_container(name, version, self.containers.keys())
+                            .filter(|resource| &umount_container == resource)
+                            .is_some()
+                        {
+                            warn!(
+                                "Resource container {} is used by {}",
+                                umount_container, running_container
+                            );
+                            let error = Err(Error::UmountBusy(running_container.clone()));
+                            mounts.push(Either::Right(ready(error)));
+                            continue 'outer;
                         }
                     }
                 }
@@ -1094,6 +4413,7 @@ impl State {
                 Ok(_) => {
                     let state = self.state_mut(container).expect("Internal error");
                     state.root = None;
+                    state.mounted_for_start = false;
                     info!("Umounted {}", container);
                     result.push(Ok(container.clone()));
                 }
@@ -1120,6 +4440,163 @@ impl State {
         result
     }
 
+    /// Mount `containers` in a background task and continue with `then` once the mounts
+    /// completed. The main loop processes other requests and events meanwhile. Containers
+    /// that are mounted or being mounted fail with a busy error.
+    fn mount_background(&mut self, containers: Vec<Container>, then: AfterMount) {
+        let mut mounting = Vec::with_capacity(containers.len());
+        let mut mounts = Vec::with_capacity(containers.len());
+
+        for container in &containers {
+            let busy = self
+                .state(container)
+                .map(|state| state.is_mounted() || self.mounting.contains(container));
+            match busy {
+                Ok(true) => {
+                    let error = Err(Error::MountBusy(container.clone()));
+                    mounts.push(Either::Right(ready(error)));
+                }
+                Ok(false) => {
+                    let span = info_span!("mount", container = %container);
+                    mounts.push(Either::Left(self.mount(container).instrument(span)));
+                    self.mounting.insert(container.clone());
+                    mounting.push(container.clone());
+                }
+                Err(_) => {
+                    let error = Err(Error::InvalidContainer(container.clone()));
+                    mounts.push(Either::Right(ready(error)));
+                }
+            }
+        }
+
+        let concurrency = self.config.mount_concurrency;
+        let events_tx = self.events_tx.clone();
+        task::spawn(async move {
+            let results = stream::iter(mounts).buffered(concurrency).collect().await;
+            let mounts = Mounts {
+                containers,
+                mounting,
+                results,
+                then,
+            };
+            events_tx.send(Event::Mounted(mounts)).await.ok();
+        });
+    }
+
+    /// Apply the results of background mounts and continue the operation that waited
+    /// for them. Starts that waited for mounts of other operations are retried.
+    pub(super) async fn on_mounted(&mut self, mounts: Mounts) -> Result<(), Error> {
+        let Mounts {
+            containers,
+            mounting,
+            results,
+            then,
+        } = mounts;
+        let results = self.apply_mounts(&containers, &mounting, results);
+
+        match then {
+            AfterMount::Request(response) => {
+                response
+                    .send(Self::mount_response(&containers, results))
+                    .ok();
+            }
+            AfterMount::Start(mut start) => {
+                let mounted = results
+                    .iter()
+                    .filter_map(|result| result.as_ref().ok())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                // Abort if at least one container failed to mount and umount the
+                // containers mounted for this start
+                if let Some(e) = results.into_iter().find_map(Result::err) {
+                    warn!("failed to start {}: {}", start.container, e);
+                    if !mounted.is_empty() {
+                        info!(
+                            "Rolling back {} mount(s) for the start of {}",
+                            mounted.len(),
+                            start.container
+                        );
+                        self.umount_all(&mounted).await;
+                    }
+                    if let Some(response) = start.response.take() {
+                        response.send(model::Response::Error(e.into())).ok();
+                    }
+                } else {
+                    for mounted in &mounted {
+                        self.state_mut(mounted)?.mounted_for_start = true;
+                    }
+                    self.on_start(start).await?;
+                }
+            }
+        }
+
+        for start in std::mem::take(&mut self.mount_waiting) {
+            self.on_start(start).await?;
+        }
+
+        self.save_snapshot().await;
+        Ok(())
+    }
+
+    /// Set the roots of the containers mounted in the background and return the results
+    /// in the order of `containers`
+    fn apply_mounts(
+        &mut self,
+        containers: &[Container],
+        mounting: &[Container],
+        results: Vec<Result<PathBuf, Error>>,
+    ) -> Vec<Result<Container, Error>> {
+        for container in mounting {
+            self.mounting.remove(container);
+        }
+
+        let mut mounted = Vec::with_capacity(containers.len());
+        for (container, result) in containers.iter().zip(results) {
+            match result {
+                Ok(root) => match self.containers.get_mut(container) {
+                    Some(state) => {
+                        state.root = Some(root);
+                        info!("Mounted {}", container);
+                        mounted.push(Ok(container.clone()));
+                    }
+                    None => {
+                        // The container was removed while the mount was in progress
+                        warn!("Umounting {} that was removed while mounting", container);
+                        let mount_control = self.mount_control.clone();
+                        task::spawn(async move {
+                            mount_control.umount(&root).await.ok();
+                        });
+                        mounted.push(Err(Error::InvalidContainer(container.clone())));
+                    }
+                },
+                Err(e) => {
+                    warn!("failed to mount {}: {}", container, e);
+                    mounted.push(Err(e));
+                }
+            }
+        }
+        mounted
+    }
+
+    /// Response to a mount request
+    fn mount_response(
+        containers: &[Container],
+        results: Vec<Result<Container, Error>>,
+    ) -> model::Response {
+        let results = results
+            .into_iter()
+            .zip(containers)
+            .map(|(r, c)| match r {
+                Ok(r) => model::MountResult::Ok { container: r },
+                Err(e) => model::MountResult::Error {
+                    container: c.clone(),
+                    error: e.into(),
+                },
+            })
+            .collect();
+        model::Response::Mount(results)
+    }
+
     /// Find a resource container that best matches the given version requirement.
     pub fn match_container<'a, I: Iterator<Item = &'a Container>>(
         name: &Name,
@@ -1127,6 +4604,7 @@ impl State {
         containers: I,
     ) -> Option<&'a Container> {
         containers
+            .filter(|c| c.instance().is_none())
             .filter(|c| c.name() == name && version_req.matches(c.version()))
             .sorted_by(|c1, c2| c1.version().cmp(c2.version()))
             .next()
@@ -1140,6 +4618,11 @@ impl State {
             let process = state.process.as_ref().map(|context| api::model::Process {
                 pid: context.pid,
                 uptime: context.started.elapsed().as_nanos() as u64,
+                state: if context.paused {
+                    api::model::ProcessState::Paused
+                } else {
+                    api::model::ProcessState::Running
+                },
             });
             let repository = state.repository.clone();
             let mounted = state.is_mounted();
@@ -1150,6 +4633,7 @@ impl State {
                 manifest,
                 process,
                 mounted,
+                history: state.history.clone(),
             };
             result.push(container_data);
         }
@@ -1157,18 +4641,426 @@ impl State {
         result
     }
 
-    /// Send a container event to all subscriber consoles
+    /// Details of an installed container including the software bill of materials
+    fn inspect(&self, container: &Container) -> Result<api::model::Inspect, Error> {
+        let npk = self.npk(container)?;
+        let repository = self.state(container)?.repository.clone();
+        Ok(api::model::Inspect {
+            container: container.clone(),
+            repository,
+            shadowed: self.state(container)?.shadowed.clone(),
+            manifest: npk.manifest().clone(),
+            sbom: npk.sbom().cloned(),
+            key: npk.key().map(|key| hex::encode(key.as_bytes())),
+            start_timings: self.state(container)?.start_timings.clone(),
+            history: self.state(container)?.history.clone(),
+        })
+    }
+
+    /// Start a container and respond to the requester. The start is deferred if the
+    /// start policy asks for a delay. The container and its resources are mounted in
+    /// the background and the start continues once they are mounted.
+    pub(super) async fn on_start(&mut self, mut start: DeferredStart) -> Result<(), Error> {
+        self.deferred = self.deferred.saturating_sub(1);
+
+        // The requester is gone while the start was deferred
+        if matches!(&start.response, Some(response) if response.is_closed()) {
+            debug!("Skipping deferred start of {}", start.container);
+            return Ok(());
+        }
+
+        let result = self
+            .start(
+                &start.container,
+                &start.args,
+                &start.env,
+                &start.parameters,
+                start.origin,
+                true,
+            )
+            .await;
+        let payload = match result {
+            Ok(_) => model::Response::Ok,
+            Err(Error::StartDeferred(container, delay)) => {
+                info!("Start of {} is deferred for {:?}", container, delay);
+                self.defer(start, delay);
+                return Ok(());
+            }
+            // Wait for the mounts of other operations to complete before mounting
+            Err(Error::StartMount(_, containers))
+                if containers.iter().any(|c| self.mounting.contains(c)) =>
+            {
+                debug!("Start of {} waits for mounts in progress", start.container);
+                self.mount_waiting.push(start);
+                return Ok(());
+            }
+            Err(Error::StartMount(_, containers)) => {
+                info!(
+                    "Mounting {} for the start of {}",
+                    containers.iter().join(", "),
+                    start.container
+                );
+                self.mount_background(containers, AfterMount::Start(start));
+                return Ok(());
+            }
+            Err(e) => {
+                warn!("failed to start {}: {}", start.container, e);
+                model::Response::Error(e.into())
+            }
+        };
+
+        if let Some(response) = start.response.take() {
+            // A error on the response_tx means that the connection
+            // was closed in the meantime. Ignore it.
+            response.send(payload).ok();
+        }
+
+        self.save_snapshot().await;
+        Ok(())
+    }
+
+    /// Schedule an idle umount of `container` and its resources after `idle_umount`
+    fn schedule_umount_idle(&mut self, container: &Container) {
+        let delay = match self.config.idle_umount {
+            Some(delay) => delay,
+            None => return,
+        };
+
+        let container = container.without_instance();
+        let mut containers = vec![container.clone()];
+        if let Ok(manifest) = self.manifest(&container) {
+            for (name, version) in manifest.mounts.values().flat_map(Mount::resources) {
+                if let Some(resource) =
+                    State::match_container(name, version, self.containers.keys())
+                {
+                    containers.push(resource.clone());
+                }
+            }
+        }
+
+        let now = time::Instant::now();
+        for container in &containers {
+            if let Ok(state) = self.state_mut(container) {
+                state.last_used = Some(now);
+            }
+        }
+
+        let events_tx = self.events_tx.clone();
+        task::spawn(async move {
+            time::sleep(delay).await;
+            events_tx.send(Event::UmountIdle(containers)).await.ok();
+        });
+    }
+
+    /// Schedule a check whether the killed process of `container` exited within
+    /// `stop_timeout`
+    fn schedule_stop_timeout(&self, container: &Container) {
+        let pid = match self.state(container).map(|state| state.process.as_ref()) {
+            Ok(Some(process)) => process.pid,
+            _ => return,
+        };
+        let container = container.clone();
+        let delay = self.config.stop_timeout;
+        let events_tx = self.events_tx.clone();
+        task::spawn(async move {
+            time::sleep(delay).await;
+            events_tx
+                .send(Event::StopTimeout(container, pid))
+                .await
+                .ok();
+        });
+    }
+
+    /// Fail the data reset and the forced uninstalls that wait for the exit of the
+    /// process `pid` of `container` if it did not exit within `stop_timeout`
+    pub(super) async fn on_stop_timeout(
+        &mut self,
+        container: &Container,
+        pid: Pid,
+    ) -> Result<(), Error> {
+        let process = self
+            .containers
+            .get_mut(container)
+            .and_then(|state| state.process.as_mut())
+            .filter(|process| process.pid == pid);
+        let process = match process {
+            Some(process) => process,
+            None => return Ok(()),
+        };
+
+        let timeout = self.config.stop_timeout;
+        warn!(
+            "{} did not exit within {:?} after it was killed",
+            container, timeout
+        );
+        let error = || Error::Timeout(container.clone(), model::Operation::Stop, timeout);
+
+        if let Some(response) = process.data_reset.take() {
+            response.send(model::Response::Error(error().into())).ok();
+        }
+
+        let (failed, waiting) = std::mem::take(&mut self.uninstalls)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(uninstall, _, _)| self.users(uninstall).contains(container));
+        self.uninstalls = waiting;
+        for (uninstall, _, response) in failed {
+            warn!(
+                "Failed to uninstall {}: {} did not exit",
+                uninstall, container
+            );
+            response.send(model::Response::Error(error().into())).ok();
+        }
+
+        Ok(())
+    }
+
+    /// Schedule an audit for leftovers of exited containers after `reconcile_interval`
+    fn schedule_reconcile(&self) {
+        let interval = match self.config.reconcile_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+
+        let events_tx = self.events_tx.clone();
+        task::spawn(async move {
+            time::sleep(interval).await;
+            events_tx.send(Event::Reconcile).await.ok();
+        });
+    }
+
+    /// Clean up leftovers of exited containers e.g. after a failed teardown or a crash
+    /// of a previous instance: Tasks in the cgroups of containers that are not started,
+    /// mounts in the run dir of containers that are not mounted and the loop and verity
+    /// devices of npks that are not mounted.
+    pub(super) async fn on_reconcile(&mut self) -> Result<(), Error> {
+        let started = self
+            .containers
+            .iter()
+            .filter(|(_, state)| state.process.is_some())
+            .map(|(container, _)| container.name().to_string())
+            .collect::<HashSet<_>>();
+        let cgroups = cgroups::reap(self.config.cgroup.as_str(), &started).await;
+
+        // Background mounts set up devices and mount points that are not yet part of
+        // the state
+        let mut mounts = Vec::new();
+        let mut devices = Vec::new();
+        if self.mounting.is_empty() {
+            let mounted = self
+                .containers
+                .iter()
+                .filter(|(_, state)| state.is_mounted())
+                .map(|(container, _)| container.to_string())
+                .collect::<HashSet<_>>();
+            // Mount points in mountinfo are canonical
+            let mount_points = fs::canonicalize(&self.config.run_dir)
+                .await
+                .context("failed to canonicalize run dir")
+                .and_then(|run_dir| mount::mount_points(&run_dir).map_err(Error::Mount));
+            let mount_points = match mount_points {
+                Ok(mount_points) => mount_points,
+                Err(e) => {
+                    warn!("failed to read mounts: {}", e);
+                    Vec::with_capacity(0)
+                }
+            };
+            for mount_point in mount_points {
+                // Only the roots of containers are mounted directly in the run dir
+                let name = match mount_point.file_name().and_then(|name| name.to_str()) {
+                    Some(name) if Container::try_from(name).is_ok() => name,
+                    _ => continue,
+                };
+                if mounted.contains(name) {
+                    continue;
+                }
+                warn!("Detaching leftover mount {}", mount_point.display());
+                match self.mount_control.detach(&mount_point).await {
+                    Ok(_) => mounts.push(mount_point),
+                    Err(e) => warn!("failed to detach {}: {}", mount_point.display(), e),
+                }
+            }
+
+            // A device is released if it is unused in two consecutive audits. The devices
+            // of a mount in progress e.g. of another runtime instance are in use by then.
+            match self.mount_control.unused_devices().await {
+                Ok(unused) => {
+                    let (stale, unused) = unused
+                        .into_iter()
+                        .partition::<Vec<_>, _>(|device| self.unused_devices.contains(device));
+                    devices = self.mount_control.release_devices(stale).await;
+                    self.unused_devices = unused.into_iter().collect();
+                }
+                Err(e) => warn!("failed to read devices: {}", e),
+            }
+        }
+
+        if !(cgroups.is_empty() && mounts.is_empty() && devices.is_empty()) {
+            warn!(
+                "Cleaned up {} cgroup(s), {} mount(s) and {} device(s) of exited containers",
+                cgroups.len(),
+                mounts.len(),
+                devices.len()
+            );
+        }
+
+        self.schedule_reconcile();
+        Ok(())
+    }
+
+    /// Umount the containers of `containers` that were mounted for a start and are
+    /// not used since `idle_umount`
+    pub(super) async fn on_umount_idle(&mut self, containers: &[Container]) -> Result<(), Error> {
+        let delay = match self.config.idle_umount {
+            Some(delay) => delay,
+            None => return Ok(()),
+        };
+
+        let idle = containers
+            .iter()
+            .filter(|container| {
+                matches!(self.state(container), Ok(state) if state.is_mounted()
+                    && state.mounted_for_start
+                    && state.last_used.map_or(false, |last_used| last_used.elapsed() >= delay))
+            })
+            .filter(|container| !self.is_used(container))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !idle.is_empty() {
+            info!("Umounting {} idle container(s)", idle.len());
+            self.umount_all(&idle).await;
+        }
+        Ok(())
+    }
+
+    /// True if `container` or a named instance of it is started or if `container` is a
+    /// resource of a started container
+    fn is_used(&self, container: &Container) -> bool {
+        !self.users(container).is_empty()
+    }
+
+    /// Started containers that use `container`: The container itself, its named instances
+    /// and the containers that use it as resource
+    fn users(&self, container: &Container) -> Vec<Container> {
+        self.containers
+            .iter()
+            .filter(|(_, state)| state.process.is_some())
+            .map(|(started, _)| started)
+            .filter(|started| {
+                *started == container
+                    || self.instances(container).any(|i| i == *started)
+                    || self.resources(started).into_iter().any(|(name, version)| {
+                        State::match_container(name, version, self.containers.keys())
+                            == Some(container)
+                    })
+            })
+            .cloned()
+            .sorted()
+            .collect()
+    }
+
+    /// Installed containers with a resource that is provided by `container` only
+    fn dependents(&self, container: &Container) -> Vec<Container> {
+        let others = || self.containers.keys().filter(move |c| *c != container);
+        others()
+            .filter(|c| c.instance().is_none())
+            .filter(|c| {
+                self.resources(c).into_iter().any(|(name, version)| {
+                    name == container.name()
+                        && version.matches(container.version())
+                        && State::match_container(name, version, others()).is_none()
+                })
+            })
+            .cloned()
+            .sorted()
+            .collect()
+    }
+
+    /// Installed containers that use the persist directory of `container` as well: Other
+    /// versions and containers of the same share group. The container itself is listed if
+    /// it remains installed from a repository with a lower priority.
+    fn persist_users(&self, container: &Container, manifest: &Manifest) -> Vec<Container> {
+        let dir = persist::dir(&self.config, container, manifest);
+        let shadowed = self
+            .state(container)
+            .map_or(false, |state| !state.shadowed.is_empty());
+        self.containers
+            .keys()
+            .filter(|c| c.instance().is_none() && (*c != container || shadowed))
+            .filter(|c| {
+                self.manifest(c).map_or(false, |manifest| {
+                    persist::has_persist_mount(manifest)
+                        && persist::dir(&self.config, c, manifest) == dir
+                })
+            })
+            .cloned()
+            .sorted()
+            .collect()
+    }
+
+    /// Resources required by `container`
+    fn resources(&self, container: &Container) -> Vec<(&Name, &VersionReq)> {
+        self.manifest(container)
+            .map(|manifest| {
+                manifest
+                    .mounts
+                    .values()
+                    .flat_map(Mount::resources)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Send `start` to the main loop after `delay`
+    fn defer(&mut self, start: DeferredStart, delay: time::Duration) {
+        self.deferred += 1;
+        let events_tx = self.events_tx.clone();
+        task::spawn(async move {
+            time::sleep(delay).await;
+            events_tx.send(Event::Start(start)).await.ok();
+        });
+    }
+
+    /// Send a container event to all subscriber consoles and in-process subscribers
     fn container_event(&self, container: &Container, event: ContainerEvent) {
+        self.subscribers
+            .publish(RuntimeEvent::Container(container.clone(), event.clone()));
         // Do not fill the notification channel if there's nobody subscribed
         if self.notification_tx.receiver_count() > 0 {
-            self.notification_tx.send((container.clone(), event)).ok();
+            self.notification((container.clone(), event).into());
         }
     }
 
-    fn state(&self, container: &Container) -> Result<&ContainerState, Error> {
+    /// Send a notification to all subscriber consoles
+    fn notification(&self, notification: model::Notification) {
+        if self.notification_tx.receiver_count() > 0 {
+            self.notification_tx.send(notification).ok();
+        }
+    }
+
+    /// True if a container other than `container` that shares the persist directory
+    /// of `manifest` is started
+    fn is_share_started(&self, container: &Container, manifest: &Manifest) -> bool {
+        let group = match persist::share(manifest) {
+            Some(group) => group,
+            None => return false,
+        };
         self.containers
-            .get(container)
-            .ok_or_else(|| Error::InvalidContainer(container.clone()))
+            .iter()
+            .filter(|(c, state)| *c != container && state.process.is_some())
+            .filter_map(|(c, _)| self.manifest(c).ok())
+            .any(|m| persist::share(m) == Some(group))
+    }
+
+    fn state(&self, container: &Container) -> Result<&ContainerState, Error> {
+        self.containers.get(container).ok_or_else(|| {
+            // The container might be in a repository that is not loaded yet
+            if self.loading.is_empty() {
+                Error::InvalidContainer(container.clone())
+            } else {
+                Error::ContainerLoading(container.clone())
+            }
+        })
     }
 
     fn state_mut(&mut self, container: &Container) -> Result<&mut ContainerState, Error> {
@@ -1177,17 +5069,37 @@ impl State {
             .ok_or_else(|| Error::InvalidContainer(container.clone()))
     }
 
+    /// Named instances use the npk of the container
     fn npk(&self, container: &Container) -> Result<&Npk, Error> {
+        let container = &container.without_instance();
         let state = self.state(container)?;
         let repository = self.repository(&state.repository)?;
         repository
             .get(container)
-            .ok_or_else(|| Error::InvalidContainer(container.clone()))
+            .ok_or_else(|| Error::InvalidContainer(container.clone()))?
     }
 
+    /// Named instances use the manifest of the container
     fn manifest(&self, container: &Container) -> Result<&Manifest, Error> {
-        let npk = self.npk(container)?;
-        Ok(npk.manifest())
+        let container = &container.without_instance();
+        let state = self.state(container)?;
+        let repository = self.repository(&state.repository)?;
+        repository
+            .manifest(container)
+            .ok_or_else(|| Error::InvalidContainer(container.clone()))
+    }
+
+    /// Started named instances of `container`
+    fn instances<'a>(&'a self, container: &'a Container) -> impl Iterator<Item = &'a Container> {
+        self.containers
+            .iter()
+            .filter(move |(c, state)| {
+                c.instance().is_some()
+                    && c.name() == container.name()
+                    && c.version() == container.version()
+                    && state.process.is_some()
+            })
+            .map(|(c, _)| c)
     }
 
     fn repository(&self, repository: &str) -> Result<&Repository, Error> {
@@ -1197,6 +5109,36 @@ impl State {
     }
 }
 
+/// Match `text` against `pattern` where `*` matches any sequence of characters
+/// Kill the processes of the container whose init is `pid`
+fn kill_process_group(pid: Pid) {
+    let process_group = unistd::Pid::from_raw(-(pid as i32));
+    nix::sys::signal::kill(process_group, Signal::SIGKILL).ok();
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let text = match text.strip_prefix(first) {
+        Some(text) => text,
+        None => return false,
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let (last, middle) = match parts.split_last() {
+        Some((last, middle)) => (last, middle),
+        // No wildcard in pattern
+        None => return text.is_empty(),
+    };
+    let mut text = text;
+    for part in middle {
+        match text.find(part) {
+            Some(position) => text = &text[position + part.len()..],
+            None => return false,
+        }
+    }
+    text.ends_with(last)
+}
+
 #[test]
 #[allow(clippy::unwrap_used)]
 fn find_newest_resource() {
@@ -1231,3 +5173,35 @@ fn cannot_find_newer_resource() {
     );
     assert!(resource.is_none());
 }
+
+#[test]
+fn wildcard() {
+    assert!(wildcard_match("*", "hello:0.0.1"));
+    assert!(wildcard_match("hello:0.0.1", "hello:0.0.1"));
+    assert!(wildcard_match("hello*", "hello-world:0.0.1"));
+    assert!(wildcard_match("*:0.0.1", "hello:0.0.1"));
+    assert!(wildcard_match("he*:*.1", "hello:0.0.1"));
+    assert!(!wildcard_match("hello", "hello:0.0.1"));
+    assert!(!wildcard_match("*:0.0.2", "hello:0.0.1"));
+    assert!(!wildcard_match("hello*world*", "hello:0.0.1"));
+}
+
+#[test]
+fn oom_restart() {
+    let mut state = ContainerState::default();
+
+    // A stop cancels a requested restart
+    assert!(state.request_oom_restart(2));
+    state.cancel_restart();
+    assert!(!state.take_restart());
+    assert_eq!(state.oom_restarts, 0);
+
+    // Consecutive restarts are limited
+    assert!(state.request_oom_restart(2));
+    assert!(state.take_restart());
+    assert!(state.request_oom_restart(2));
+    assert!(state.take_restart());
+    assert!(!state.request_oom_restart(2));
+    assert!(!state.take_restart());
+    assert_eq!(state.oom_restarts, 2);
+}
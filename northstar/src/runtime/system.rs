@@ -0,0 +1,122 @@
+use super::{
+    config::{Config, RepositoryType},
+    error::{Context, Error},
+};
+use crate::{api::model, common::version::Version, npk::manifest::Manifest};
+use nix::sys::{statvfs, sysinfo, utsname};
+use std::{env::consts::ARCH, path::Path};
+
+/// Gather kernel, memory, load, disk and version information
+pub(super) fn info(
+    config: &Config,
+    event_queues: model::EventQueues,
+) -> Result<model::SystemInfo, Error> {
+    let uname = utsname::uname().context("failed to get kernel information")?;
+    let sysinfo = sysinfo::sysinfo().context("failed to get system information")?;
+
+    let (one, five, fifteen) = sysinfo.load_average();
+    let load = |average: f64| (average * 100.0).round() as u32;
+
+    let repositories = config
+        .repositories
+        .iter()
+        .filter_map(|(id, repository)| match &repository.r#type {
+            RepositoryType::Fs { dir } => Some(disk_usage(dir).map(|usage| (id.clone(), usage))),
+            RepositoryType::Memory => None,
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(model::SystemInfo {
+        kernel: uname.release().to_string_lossy().into_owned(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        commit: option_env!("NORTHSTAR_GIT_COMMIT").map(ToString::to_string),
+        memory_total: sysinfo.ram_total(),
+        memory_free: sysinfo.ram_unused(),
+        load_average: [load(one), load(five), load(fifteen)],
+        run_dir: disk_usage(&config.run_dir)?,
+        data_dir: disk_usage(&config.data_dir)?,
+        repositories,
+        event_queues,
+    })
+}
+
+/// Size and available space of the filesystem of `path`
+// The statvfs fields are 32 bit wide on some targets
+#[allow(clippy::useless_conversion)]
+pub(super) fn disk_usage(path: &Path) -> Result<model::DiskUsage, Error> {
+    let stat = statvfs::statvfs(path).context(format!("failed to stat {}", path.display()))?;
+    let fragment_size = u64::from(stat.fragment_size());
+    Ok(model::DiskUsage {
+        path: path.to_owned(),
+        total: u64::from(stat.blocks()) * fragment_size,
+        available: u64::from(stat.blocks_available()) * fragment_size,
+    })
+}
+
+/// Check the requirements of `manifest` on the architecture, the kernel release and
+/// the runtime version
+pub(super) fn check_requirements(manifest: &Manifest) -> Result<(), Error> {
+    let requires = match &manifest.requires {
+        Some(requires) => requires,
+        None => return Ok(()),
+    };
+    let incompatible = |requirement: &str, required: String, found: String| {
+        Error::Incompatible(manifest.container(), requirement.into(), required, found)
+    };
+
+    if let Some(arch) = &requires.arch {
+        if arch != ARCH {
+            return Err(incompatible("arch", arch.clone(), ARCH.to_string()));
+        }
+    }
+
+    if let Some(kernel) = &requires.kernel {
+        let uname = utsname::uname().context("failed to get kernel information")?;
+        let release = uname.release().to_string_lossy();
+        if !kernel_version(&release).map_or(false, |version| kernel.matches(&version)) {
+            return Err(incompatible(
+                "kernel",
+                kernel.to_string(),
+                release.into_owned(),
+            ));
+        }
+    }
+
+    if let Some(northstar) = &requires.northstar {
+        let version = Version::parse(env!("CARGO_PKG_VERSION")).expect("invalid crate version");
+        if !northstar.matches(&version) {
+            return Err(incompatible(
+                "northstar",
+                northstar.to_string(),
+                version.to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Version of a kernel release e.g. `5.10.0-13-amd64`. Missing minor and patch
+/// numbers are zero.
+fn kernel_version(release: &str) -> Option<Version> {
+    let end = release
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(release.len());
+    let mut numbers = release[..end].split('.').map(str::parse::<u64>);
+    let major = numbers.next()?.ok()?;
+    let minor = numbers.next().unwrap_or(Ok(0)).ok()?;
+    let patch = numbers.next().unwrap_or(Ok(0)).ok()?;
+    Some(Version::new(major, minor, patch))
+}
+
+#[test]
+fn kernel_versions() {
+    assert_eq!(
+        kernel_version("5.10.0-13-amd64"),
+        Some(Version::new(5, 10, 0))
+    );
+    assert_eq!(kernel_version("6.1.55-rt15"), Some(Version::new(6, 1, 55)));
+    assert_eq!(kernel_version("5.4"), Some(Version::new(5, 4, 0)));
+    assert_eq!(kernel_version("4.19.0+"), Some(Version::new(4, 19, 0)));
+    assert_eq!(kernel_version("linux"), None);
+}
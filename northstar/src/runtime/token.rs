@@ -10,7 +10,7 @@ use std::{
     time::{self},
 };
 
-use crate::api;
+use crate::api::{self, model::TokenScope};
 
 lazy_static! {
     static ref MAC_KEY: [u8; 32] = {
@@ -33,10 +33,32 @@ lazy_static! {
 type HmacSha256 = hmac::Hmac<Sha256>;
 type Hmac = CtOutput<HmacSha256>;
 
+/// Length of the encoded creation time and validity
+const HEADER_LEN: usize = 16;
+/// Length of the encoded HMAC
+const HMAC_LEN: usize = 32;
+
+/// Token validity configuration
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Validity {
+    /// Validity of tokens created without a requested validity
+    pub default: time::Duration,
+    /// Upper bound of a requested validity
+    pub max: time::Duration,
+}
+
+impl Validity {
+    /// Validity for a token creation request. The requested validity is capped
+    /// by the configured maximum.
+    pub fn get(&self, requested: Option<time::Duration>) -> time::Duration {
+        requested.unwrap_or(self.default).min(self.max)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum VerificationResult {
     /// Verification succeeded
-    Ok,
+    Ok(TokenScope),
     /// Verification failed
     Invalid,
     /// Token is expired
@@ -52,23 +74,41 @@ pub(crate) struct Token {
     validity: time::Duration,
     /// Creation timestamp
     time: time::Duration,
+    /// Encoded scope claims
+    scope: Vec<u8>,
     /// HMAC
     hmac: Hmac,
 }
 
 impl Token {
     /// Create a new token
-    pub fn new<U, T, S>(validity: time::Duration, user: U, target: T, shared: S) -> Token
+    pub fn new<U, T, S>(
+        validity: time::Duration,
+        user: U,
+        target: T,
+        shared: S,
+        scope: &TokenScope,
+    ) -> Token
     where
         U: AsRef<[u8]>,
         T: AsRef<[u8]>,
         S: AsRef<[u8]>,
     {
         let now = now();
-        let hmac = calculate_hmac(&now, user.as_ref(), target.as_ref(), shared.as_ref());
+        let validity = time::Duration::from_secs(validity.as_secs());
+        let scope = serde_json::to_vec(scope).expect("failed to serialize token scope");
+        let hmac = calculate_hmac(
+            &now,
+            &validity,
+            user.as_ref(),
+            target.as_ref(),
+            shared.as_ref(),
+            &scope,
+        );
         Token {
             validity,
             time: now,
+            scope,
             hmac,
         }
     }
@@ -86,10 +126,19 @@ impl Token {
             VerificationResult::Future
         } else if now - self.time > self.validity {
             VerificationResult::Expired
-        } else if calculate_hmac(&self.time, user.as_ref(), target.as_ref(), shared.as_ref())
-            == self.hmac
+        } else if calculate_hmac(
+            &self.time,
+            &self.validity,
+            user.as_ref(),
+            target.as_ref(),
+            shared.as_ref(),
+            &self.scope,
+        ) == self.hmac
         {
-            VerificationResult::Ok
+            // The scope is covered by the HMAC and was encoded by the runtime
+            serde_json::from_slice(&self.scope)
+                .map(VerificationResult::Ok)
+                .unwrap_or(VerificationResult::Invalid)
         } else {
             VerificationResult::Invalid
         }
@@ -100,7 +149,14 @@ fn now() -> time::Duration {
     time::Duration::from_secs(START.elapsed().as_secs())
 }
 
-fn calculate_hmac(time: &time::Duration, user: &[u8], target: &[u8], shared: &[u8]) -> Hmac {
+fn calculate_hmac(
+    time: &time::Duration,
+    validity: &time::Duration,
+    user: &[u8],
+    target: &[u8],
+    shared: &[u8],
+    scope: &[u8],
+) -> Hmac {
     let mut hmac = HmacSha256::new_from_slice(MAC_KEY.as_slice())
         .expect("Failed to create SHA-256 HMAC instance");
     hmac.update(user);
@@ -109,32 +165,50 @@ fn calculate_hmac(time: &time::Duration, user: &[u8], target: &[u8], shared: &[u
     let target = hmac.finalize_reset();
     hmac.update(shared);
     let shared = hmac.finalize_reset();
+    hmac.update(scope);
+    let scope = hmac.finalize_reset();
     hmac.update(&time.as_millis().to_be_bytes());
+    hmac.update(&validity.as_millis().to_be_bytes());
     hmac.update(&user.into_bytes());
     hmac.update(&target.into_bytes());
     hmac.update(&shared.into_bytes());
+    hmac.update(&scope.into_bytes());
     hmac.finalize()
 }
 
-impl From<(time::Duration, [u8; 40])> for Token {
-    fn from((validity, bytes): (time::Duration, [u8; 40])) -> Self {
+/// Decode a token from `creation time | validity | scope | hmac`. Fails if `bytes`
+/// is too short.
+impl TryFrom<&[u8]> for Token {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() < HEADER_LEN + HMAC_LEN {
+            return Err(());
+        }
         let mut time = [0u8; 8];
         time.copy_from_slice(&bytes[..8]);
         let time = time::Duration::from_secs(u64::from_be_bytes(time));
-        let hmac = CtOutput::<HmacSha256>::new(GenericArray::clone_from_slice(&bytes[8..]));
-        Token {
+        let mut validity = [0u8; 8];
+        validity.copy_from_slice(&bytes[8..HEADER_LEN]);
+        let validity = time::Duration::from_secs(u64::from_be_bytes(validity));
+        let (scope, hmac) = bytes[HEADER_LEN..].split_at(bytes.len() - HEADER_LEN - HMAC_LEN);
+        let hmac = CtOutput::<HmacSha256>::new(GenericArray::clone_from_slice(hmac));
+        Ok(Token {
             validity,
             time,
+            scope: scope.to_vec(),
             hmac,
-        }
+        })
     }
 }
 
-impl From<Token> for [u8; 40] {
+impl From<Token> for Vec<u8> {
     fn from(token: Token) -> Self {
-        let mut bytes = [0u8; 40];
-        bytes[..8].copy_from_slice(&token.time.as_secs().to_be_bytes());
-        bytes[8..].copy_from_slice(&token.hmac.into_bytes());
+        let mut bytes = Vec::with_capacity(HEADER_LEN + token.scope.len() + HMAC_LEN);
+        bytes.extend_from_slice(&token.time.as_secs().to_be_bytes());
+        bytes.extend_from_slice(&token.validity.as_secs().to_be_bytes());
+        bytes.extend_from_slice(&token.scope);
+        bytes.extend_from_slice(&token.hmac.into_bytes());
         bytes
     }
 }
@@ -143,6 +217,8 @@ impl fmt::Debug for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Token")
             .field("time", &self.time)
+            .field("validity", &self.validity)
+            .field("scope", &String::from_utf8_lossy(&self.scope))
             .field("hmac", &self.hmac.clone().into_bytes())
             .finish()
     }
@@ -151,7 +227,7 @@ impl fmt::Debug for Token {
 impl From<VerificationResult> for api::model::VerificationResult {
     fn from(result: VerificationResult) -> Self {
         match result {
-            VerificationResult::Ok => api::model::VerificationResult::Ok,
+            VerificationResult::Ok(scope) => api::model::VerificationResult::Ok(scope),
             VerificationResult::Invalid => api::model::VerificationResult::Invalid,
             VerificationResult::Expired => api::model::VerificationResult::Expired,
             VerificationResult::Future => api::model::VerificationResult::Future,
@@ -165,6 +241,7 @@ mod test {
     use std::time::Duration;
 
     use super::*;
+    use crate::{api::model::Container, npk::manifest::console::Permission};
 
     const SHARED: &[u8] = b"hello";
     const USER: &[u8] = b"user";
@@ -174,27 +251,42 @@ mod test {
     #[test]
     fn verify_new() {
         assert_eq!(
-            Token::new(VALIDITY, USER, TARGET, SHARED).verify(USER, TARGET, SHARED),
-            VerificationResult::Ok
+            Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default())
+                .verify(USER, TARGET, SHARED),
+            VerificationResult::Ok(TokenScope::default())
         );
     }
 
     #[test]
     fn verify_recent() {
-        let mut recent_token = Token::new(VALIDITY, USER, TARGET, SHARED);
+        let mut recent_token = Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default());
         recent_token.time = now() - recent_token.validity / 2;
-        recent_token.hmac = calculate_hmac(&recent_token.time, USER, TARGET, SHARED); // Fix HMAC for changed timestamp
+        recent_token.hmac = calculate_hmac(
+            &recent_token.time,
+            &recent_token.validity,
+            USER,
+            TARGET,
+            SHARED,
+            &recent_token.scope,
+        ); // Fix HMAC for changed timestamp
         assert_eq!(
             recent_token.verify(USER, TARGET, SHARED),
-            VerificationResult::Ok
+            VerificationResult::Ok(TokenScope::default())
         );
     }
 
     #[test]
     fn verify_expired() {
-        let mut old_token = Token::new(VALIDITY, USER, TARGET, SHARED);
+        let mut old_token = Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default());
         old_token.time = time::Duration::from_secs(0);
-        old_token.hmac = calculate_hmac(&old_token.time, USER, TARGET, SHARED); // Fix HMAC for changed timestamp
+        old_token.hmac = calculate_hmac(
+            &old_token.time,
+            &old_token.validity,
+            USER,
+            TARGET,
+            SHARED,
+            &old_token.scope,
+        ); // Fix HMAC for changed timestamp
         assert_eq!(
             old_token.verify(USER, TARGET, SHARED),
             VerificationResult::Expired
@@ -203,7 +295,7 @@ mod test {
 
     #[test]
     fn verify_future() {
-        let mut future_token = Token::new(VALIDITY, USER, TARGET, SHARED);
+        let mut future_token = Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default());
         future_token.time = now() + time::Duration::from_secs(3600);
         assert_eq!(
             future_token.verify(USER, TARGET, SHARED),
@@ -213,7 +305,7 @@ mod test {
 
     #[test]
     fn verify_broken_mac() {
-        let mut broken_token = Token::new(VALIDITY, USER, TARGET, SHARED);
+        let mut broken_token = Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default());
         let mut broken_mac = broken_token.hmac.clone().into_bytes().to_vec();
         broken_mac[0] = broken_mac[0].overflowing_add(1).0;
         let broken_mac: [u8; 32] = broken_mac.try_into().unwrap();
@@ -228,16 +320,74 @@ mod test {
     #[test]
     fn verify_wrong_shared() {
         assert_eq!(
-            Token::new(VALIDITY, USER, TARGET, SHARED).verify(USER, TARGET, "XMPP"),
+            Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default())
+                .verify(USER, TARGET, "XMPP"),
             VerificationResult::Invalid
         );
     }
 
     #[test]
     fn byte_array_roundtrip() {
-        let original = Token::new(VALIDITY, USER, TARGET, SHARED);
-        let bytes: [u8; 40] = original.clone().into();
-        let token: Token = (VALIDITY, bytes).into();
+        let original = Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default());
+        let bytes: Vec<u8> = original.clone().into();
+        let token = Token::try_from(bytes.as_slice()).unwrap();
         assert_eq!(original, token);
     }
+
+    #[test]
+    fn verify_scope() {
+        let scope = TokenScope {
+            permissions: [Permission::Inspect].into_iter().collect(),
+            containers: [Container::try_from("hello:0.0.1").unwrap()]
+                .into_iter()
+                .collect(),
+        };
+        let token = Token::new(VALIDITY, USER, TARGET, SHARED, &scope);
+        let bytes: Vec<u8> = token.into();
+        let token = Token::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            token.verify(USER, TARGET, SHARED),
+            VerificationResult::Ok(scope)
+        );
+    }
+
+    #[test]
+    fn verify_tampered_scope() {
+        let mut token = Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default());
+        token.scope = serde_json::to_vec(&TokenScope {
+            permissions: [Permission::Shutdown].into_iter().collect(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            token.verify(USER, TARGET, SHARED),
+            VerificationResult::Invalid
+        );
+    }
+
+    #[test]
+    fn verify_tampered_validity() {
+        let mut token = Token::new(VALIDITY, USER, TARGET, SHARED, &TokenScope::default());
+        token.validity = VALIDITY * 10;
+        assert_eq!(
+            token.verify(USER, TARGET, SHARED),
+            VerificationResult::Invalid
+        );
+    }
+
+    #[test]
+    fn short_token() {
+        assert!(Token::try_from([0u8; HEADER_LEN + HMAC_LEN - 1].as_slice()).is_err());
+    }
+
+    #[test]
+    fn validity_is_capped() {
+        let validity = Validity {
+            default: VALIDITY,
+            max: VALIDITY * 2,
+        };
+        assert_eq!(validity.get(None), VALIDITY);
+        assert_eq!(validity.get(Some(VALIDITY / 2)), VALIDITY / 2);
+        assert_eq!(validity.get(Some(VALIDITY * 3)), VALIDITY * 2);
+    }
 }
@@ -1,7 +1,10 @@
 use crate::{
     common::non_nul_string::NonNulString,
     npk::manifest::Capability,
-    seccomp::{profiles::default, Profile, SyscallArgRule, SyscallRule},
+    seccomp::{
+        profiles::{default, media, minimal, networking},
+        Profile, SyscallArgRule, SyscallRule,
+    },
 };
 use bindings::{
     seccomp_data, sock_filter, sock_fprog, BPF_ABS, BPF_ALU, BPF_AND, BPF_IMM, BPF_JEQ, BPF_JMP,
@@ -23,9 +26,9 @@ mod bindings {
 }
 
 #[cfg(target_arch = "aarch64")]
-const AUDIT_ARCH: u32 = bindings::AUDIT_ARCH_AARCH64;
+pub(super) const AUDIT_ARCH: u32 = bindings::AUDIT_ARCH_AARCH64;
 #[cfg(target_arch = "x86_64")]
-const AUDIT_ARCH: u32 = bindings::AUDIT_ARCH_X86_64;
+pub(super) const AUDIT_ARCH: u32 = bindings::AUDIT_ARCH_X86_64;
 
 /// Syscalls used by northstar after the seccomp rules are applied and before the actual execve is done.
 const REQUIRED_SYSCALLS: &[u32] = &[bindings::SYS_execve];
@@ -35,22 +38,29 @@ const EVAL_NEXT: u8 = 0;
 /// Skip next instruction
 const SKIP_NEXT: u8 = 1;
 
+/// Seccomp filter error
 #[derive(Error, Debug)]
 pub enum Error {
+    /// The filter exceeds the maximum number of BPF statements
     #[error("Seccomp filter list exceeds maximum number of BPF statements")]
     ListTooLong,
+    /// The system call is unknown on the target architecture
     #[error("Unknown system call {0}")]
     UnknownSyscall(String),
+    /// Failed to apply the filter
     #[error("OS error: {0}")]
     Os(nix::Error),
 }
 
-/// Construct a allowlist syscall filter that is applied post clone.
+/// Construct a allowlist syscall filter that is applied post clone. The syscalls in `deny`
+/// are removed from the syscalls allowed by `profile` and `rules`. Fails if a syscall in
+/// `deny` is unknown.
 pub fn seccomp_filter(
     profile: Option<&Profile>,
     rules: Option<&HashMap<NonNulString, SyscallRule>>,
+    deny: Option<&HashSet<NonNulString>>,
     caps: &HashSet<Capability>,
-) -> AllowList {
+) -> Result<AllowList, Error> {
     check_platform_requirements();
 
     let mut builder = Builder::new();
@@ -60,7 +70,12 @@ pub fn seccomp_filter(
     if let Some(rules) = rules {
         builder.extend(builder_from_rules(rules));
     }
-    builder.build()
+    if let Some(deny) = deny {
+        for name in deny {
+            builder.deny_syscall_name(name)?;
+        }
+    }
+    Ok(builder.build())
 }
 
 /// Create an AllowList Builder from a list of syscall names
@@ -81,90 +96,91 @@ pub(crate) fn builder_from_rules(rules: &HashMap<NonNulString, SyscallRule>) ->
 
 /// Create an AllowList Builder from a pre-defined profile
 fn builder_from_profile(profile: &Profile, caps: &HashSet<Capability>) -> Builder {
-    match profile {
-        Profile::Default => {
-            let mut builder = default::BASE.clone();
-
-            // Allow additional syscalls depending on granted capabilities
-            if !caps.is_empty() {
-                let mut cap_sys_admin = false;
-                for cap in caps {
-                    match cap {
-                        Capability::CAP_CHOWN => {}
-                        Capability::CAP_DAC_OVERRIDE => {}
-                        Capability::CAP_DAC_READ_SEARCH => {
-                            builder.extend(default::CAP_DAC_READ_SEARCH.clone());
-                        }
-                        Capability::CAP_FOWNER => {}
-                        Capability::CAP_FSETID => {}
-                        Capability::CAP_KILL => {}
-                        Capability::CAP_SETGID => {}
-                        Capability::CAP_SETUID => {}
-                        Capability::CAP_SETPCAP => {}
-                        Capability::CAP_LINUX_IMMUTABLE => {}
-                        Capability::CAP_NET_BIND_SERVICE => {}
-                        Capability::CAP_NET_BROADCAST => {}
-                        Capability::CAP_NET_ADMIN => {
-                            cap_sys_admin = true;
-                            builder.extend(default::CAP_SYS_ADMIN.clone());
-                        }
-                        Capability::CAP_NET_RAW => {}
-                        Capability::CAP_IPC_LOCK => {}
-                        Capability::CAP_IPC_OWNER => {}
-                        Capability::CAP_SYS_MODULE => {
-                            builder.extend(default::CAP_SYS_MODULE.clone());
-                        }
-                        Capability::CAP_SYS_RAWIO => {
-                            builder.extend(default::CAP_SYS_RAWIO.clone());
-                        }
-                        Capability::CAP_SYS_CHROOT => {
-                            builder.extend(default::CAP_SYS_CHROOT.clone());
-                        }
-                        Capability::CAP_SYS_PTRACE => {
-                            builder.extend(default::CAP_SYS_PTRACE.clone());
-                        }
-                        Capability::CAP_SYS_PACCT => {
-                            builder.extend(default::CAP_SYS_PACCT.clone());
-                        }
-                        Capability::CAP_SYS_ADMIN => {}
-                        Capability::CAP_SYS_BOOT => {
-                            builder.extend(default::CAP_SYS_BOOT.clone());
-                        }
-                        Capability::CAP_SYS_NICE => {
-                            builder.extend(default::CAP_SYS_NICE.clone());
-                        }
-                        Capability::CAP_SYS_RESOURCE => {}
-                        Capability::CAP_SYS_TIME => {
-                            builder.extend(default::CAP_SYS_TIME.clone());
-                        }
-                        Capability::CAP_SYS_TTY_CONFIG => {
-                            builder.extend(default::CAP_SYS_TTY_CONFIG.clone());
-                        }
-                        Capability::CAP_MKNOD => {}
-                        Capability::CAP_LEASE => {}
-                        Capability::CAP_AUDIT_WRITE => {}
-                        Capability::CAP_AUDIT_CONTROL => {}
-                        Capability::CAP_SETFCAP => {}
-                        Capability::CAP_MAC_OVERRIDE => {}
-                        Capability::CAP_MAC_ADMIN => {}
-                        Capability::CAP_SYSLOG => {
-                            builder.extend(default::CAP_SYSLOG.clone());
-                        }
-                        Capability::CAP_WAKE_ALARM => {}
-                        Capability::CAP_BLOCK_SUSPEND => {}
-                        Capability::CAP_AUDIT_READ => {}
-                        Capability::CAP_PERFMON => {}
-                        Capability::CAP_BPF => {}
-                        Capability::CAP_CHECKPOINT_RESTORE => {}
-                    };
+    let mut builder = match profile {
+        Profile::Default => default::BASE.clone(),
+        Profile::Minimal => minimal::MINIMAL.clone(),
+        Profile::Networking => networking::NETWORKING.clone(),
+        Profile::Media => media::MEDIA.clone(),
+    };
+
+    // Allow additional syscalls depending on granted capabilities
+    if !caps.is_empty() {
+        let mut cap_sys_admin = false;
+        for cap in caps {
+            match cap {
+                Capability::CAP_CHOWN => {}
+                Capability::CAP_DAC_OVERRIDE => {}
+                Capability::CAP_DAC_READ_SEARCH => {
+                    builder.extend(default::CAP_DAC_READ_SEARCH.clone());
                 }
-                if !cap_sys_admin {
-                    builder.extend(default::NON_CAP_SYS_ADMIN.clone());
+                Capability::CAP_FOWNER => {}
+                Capability::CAP_FSETID => {}
+                Capability::CAP_KILL => {}
+                Capability::CAP_SETGID => {}
+                Capability::CAP_SETUID => {}
+                Capability::CAP_SETPCAP => {}
+                Capability::CAP_LINUX_IMMUTABLE => {}
+                Capability::CAP_NET_BIND_SERVICE => {}
+                Capability::CAP_NET_BROADCAST => {}
+                Capability::CAP_NET_ADMIN => {
+                    cap_sys_admin = true;
+                    builder.extend(default::CAP_SYS_ADMIN.clone());
                 }
-            }
-            builder
+                Capability::CAP_NET_RAW => {}
+                Capability::CAP_IPC_LOCK => {}
+                Capability::CAP_IPC_OWNER => {}
+                Capability::CAP_SYS_MODULE => {
+                    builder.extend(default::CAP_SYS_MODULE.clone());
+                }
+                Capability::CAP_SYS_RAWIO => {
+                    builder.extend(default::CAP_SYS_RAWIO.clone());
+                }
+                Capability::CAP_SYS_CHROOT => {
+                    builder.extend(default::CAP_SYS_CHROOT.clone());
+                }
+                Capability::CAP_SYS_PTRACE => {
+                    builder.extend(default::CAP_SYS_PTRACE.clone());
+                }
+                Capability::CAP_SYS_PACCT => {
+                    builder.extend(default::CAP_SYS_PACCT.clone());
+                }
+                Capability::CAP_SYS_ADMIN => {}
+                Capability::CAP_SYS_BOOT => {
+                    builder.extend(default::CAP_SYS_BOOT.clone());
+                }
+                Capability::CAP_SYS_NICE => {
+                    builder.extend(default::CAP_SYS_NICE.clone());
+                }
+                Capability::CAP_SYS_RESOURCE => {}
+                Capability::CAP_SYS_TIME => {
+                    builder.extend(default::CAP_SYS_TIME.clone());
+                }
+                Capability::CAP_SYS_TTY_CONFIG => {
+                    builder.extend(default::CAP_SYS_TTY_CONFIG.clone());
+                }
+                Capability::CAP_MKNOD => {}
+                Capability::CAP_LEASE => {}
+                Capability::CAP_AUDIT_WRITE => {}
+                Capability::CAP_AUDIT_CONTROL => {}
+                Capability::CAP_SETFCAP => {}
+                Capability::CAP_MAC_OVERRIDE => {}
+                Capability::CAP_MAC_ADMIN => {}
+                Capability::CAP_SYSLOG => {
+                    builder.extend(default::CAP_SYSLOG.clone());
+                }
+                Capability::CAP_WAKE_ALARM => {}
+                Capability::CAP_BLOCK_SUSPEND => {}
+                Capability::CAP_AUDIT_READ => {}
+                Capability::CAP_PERFMON => {}
+                Capability::CAP_BPF => {}
+                Capability::CAP_CHECKPOINT_RESTORE => {}
+            };
+        }
+        if !cap_sys_admin {
+            builder.extend(default::NON_CAP_SYS_ADMIN.clone());
         }
     }
+    builder
 }
 
 /// Check if the current platform is supported and return an error if not
@@ -229,6 +245,15 @@ pub struct AllowList {
 }
 
 impl AllowList {
+    /// Check that the filter can be loaded by the kernel
+    pub fn check(&self) -> Result<(), Error> {
+        if self.list.len() > BPF_MAXINSNS as usize {
+            Err(Error::ListTooLong)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Apply this seccomp filter settings to the current thread
     pub fn apply(&self) -> Result<(), Error> {
         #[cfg(target_os = "android")]
@@ -239,9 +264,7 @@ impl AllowList {
         #[cfg(not(target_os = "android"))]
         use nix::libc::{PR_SET_SECCOMP, SECCOMP_MODE_FILTER};
 
-        if self.list.len() > BPF_MAXINSNS as usize {
-            return Err(Error::ListTooLong);
-        }
+        self.check()?;
 
         // Convert the list of instructions into the bindings sock_filter
         let list = self
@@ -309,6 +332,19 @@ impl Builder {
         }
     }
 
+    /// Remove all rules of a syscall from the allowlist by name. Syscalls required by
+    /// northstar (e.g. `execve`) are kept.
+    pub(crate) fn deny_syscall_name(&mut self, name: &str) -> Result<&mut Builder, Error> {
+        match translate_syscall(name) {
+            Some(nr) if REQUIRED_SYSCALLS.contains(&nr) => Ok(self),
+            Some(nr) => {
+                self.allowlist.retain(|rule| rule.nr != nr);
+                Ok(self)
+            }
+            None => Err(Error::UnknownSyscall(name.into())),
+        }
+    }
+
     /// Log syscall violations instead of aborting the program
     #[allow(unused)]
     pub(crate) fn log_only(&mut self) -> &mut Builder {
@@ -421,6 +457,19 @@ fn translate_syscall(name: &str) -> Option<u32> {
     SYSCALL_MAP.get(name).cloned()
 }
 
+/// True if `name` is a syscall of the target architecture
+pub(crate) fn is_syscall(name: &str) -> bool {
+    translate_syscall(name).is_some()
+}
+
+/// Get syscall name by number
+pub(super) fn syscall_name(nr: u32) -> Option<&'static str> {
+    SYSCALL_MAP
+        .iter()
+        .find(|(_, n)| **n == nr)
+        .map(|(name, _)| *name)
+}
+
 /// Load architecture identifier number into accumulator
 fn load_arch_into_acc(filter: &mut AllowList) -> u32 {
     filter.list.push(bpf_stmt(
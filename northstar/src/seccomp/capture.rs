@@ -0,0 +1,116 @@
+use super::{bpf, profiles, Profile, Seccomp, SyscallRule};
+use crate::common::non_nul_string::{InvalidNulChar, NonNulString};
+use std::collections::{HashMap, HashSet};
+
+/// Name of the syscall of a line of strace output e.g. `[pid 42] openat(...) = 3`. Signals,
+/// exits and resumed calls are ignored.
+pub fn strace_syscall(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    // Strip the pid prefix of `strace -f` either as `[pid 42]` or `42`
+    let line = match line.strip_prefix('[') {
+        Some(rest) => rest.split_once(']')?.1,
+        None => line.trim_start_matches(|c: char| c.is_ascii_digit()),
+    };
+    let line = line.trim_start();
+    let end = line.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+    if end > 0 && line[end..].starts_with('(') {
+        Some(&line[..end])
+    } else {
+        None
+    }
+}
+
+/// Name of the syscall of a seccomp audit record e.g. a line of `dmesg` or `audit.log`.
+/// Records of other architectures are ignored.
+pub fn audit_syscall(line: &str) -> Option<&'static str> {
+    // Seccomp audit records have type 1326 (AUDIT_SECCOMP)
+    if !line.contains("type=1326") && !line.contains("type=SECCOMP") {
+        return None;
+    }
+    let arch = field(line, " arch=").and_then(|a| u32::from_str_radix(a, 16).ok())?;
+    if arch != bpf::AUDIT_ARCH {
+        return None;
+    }
+    field(line, " syscall=")
+        .and_then(|s| s.parse().ok())
+        .and_then(bpf::syscall_name)
+}
+
+/// Seccomp configuration that allows `syscalls` on top of `profile`. Syscalls that are
+/// allowed unconditionally by the profile are not added to the allow list.
+pub fn seccomp<'a, I>(syscalls: I, profile: Option<Profile>) -> Result<Seccomp, InvalidNulChar>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let allowed = profile
+        .as_ref()
+        .map(profiles::syscalls)
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let allow = syscalls
+        .into_iter()
+        .filter(|syscall| !allowed.contains(syscall))
+        .map(|syscall| NonNulString::try_from(syscall).map(|name| (name, SyscallRule::Any)))
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(Seccomp {
+        profile,
+        allow: if allow.is_empty() { None } else { Some(allow) },
+        deny: None,
+    })
+}
+
+/// Value of the field starting with `key` up to the next space
+fn field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let start = line.find(key)? + key.len();
+    line[start..].split_whitespace().next()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strace() {
+        assert_eq!(strace_syscall("brk(NULL) = 0x5572b5c8b000"), Some("brk"));
+        assert_eq!(
+            strace_syscall("[pid 193911] openat(AT_FDCWD, \"/etc/ld.so.cache\", O_RDONLY) = 3"),
+            Some("openat")
+        );
+        assert_eq!(
+            strace_syscall("193911 write(1, \"Hello\\n\", 6) = 6"),
+            Some("write")
+        );
+        assert_eq!(strace_syscall("[pid 193911] <... read resumed>) = 0"), None);
+        assert_eq!(strace_syscall("--- SIGCHLD {si_signo=SIGCHLD} ---"), None);
+        assert_eq!(strace_syscall("+++ exited with 0 +++"), None);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn audit() {
+        let record = "audit: type=1326 audit(1650000000.123:42): auid=4294967295 uid=1000 gid=1000 ses=4294967295 pid=4242 comm=\"hello\" exe=\"/hello\" sig=0 arch=c000003e syscall=39 compat=0 ip=0x7f code=0x7ffc0000";
+        assert_eq!(audit_syscall(record), Some("getpid"));
+        // Other architecture
+        let record = record.replace("arch=c000003e", "arch=c00000b7");
+        assert_eq!(audit_syscall(&record), None);
+        // Other record type
+        assert_eq!(audit_syscall("type=SYSCALL arch=c000003e syscall=39"), None);
+    }
+
+    #[test]
+    fn seccomp_from_syscalls() {
+        let config = seccomp(["read", "delete_module"], Some(Profile::Default)).unwrap();
+        assert_eq!(config.profile, Some(Profile::Default));
+        let allow = config.allow.unwrap();
+        assert_eq!(allow.len(), 1);
+        assert!(allow.contains_key(&NonNulString::try_from("delete_module").unwrap()));
+
+        let config = seccomp(["read"], Some(Profile::Minimal)).unwrap();
+        assert!(config.allow.is_none());
+
+        let config = seccomp(["read", "socket"], None).unwrap();
+        assert_eq!(config.allow.unwrap().len(), 2);
+    }
+}
@@ -1,6 +1,10 @@
 // Write Berkeley Packet Filter (BPF) programs
 mod bpf;
-pub use bpf::{seccomp_filter, AllowList};
+pub(crate) use bpf::is_syscall;
+pub use bpf::{seccomp_filter, AllowList, Error};
+
+/// Generate seccomp configurations from strace and audit captures
+pub mod capture;
 
 /// Predefined seccomp profiles
 pub mod profiles;
@@ -0,0 +1,41 @@
+use crate::seccomp::{bpf::Builder, profiles::minimal};
+
+/// Syscalls added to the minimal profile for audio and video processing: Shared memory
+/// and message queues for buffers, scheduling priorities for realtime threads and local
+/// sockets to connect to audio and display servers.
+pub const SYSCALLS: &[&str] = &[
+    "connect",
+    "getsockopt",
+    "ioprio_get",
+    "ioprio_set",
+    "mq_getsetattr",
+    "mq_notify",
+    "mq_open",
+    "mq_timedreceive",
+    "mq_timedsend",
+    "mq_unlink",
+    "recvmsg",
+    "sched_setaffinity",
+    "sched_setattr",
+    "sched_setparam",
+    "sched_setscheduler",
+    "sendmsg",
+    "setpriority",
+    "setsockopt",
+    "shmat",
+    "shmctl",
+    "shmdt",
+    "shmget",
+    "shutdown",
+    "socket",
+    "socketpair",
+];
+
+lazy_static::lazy_static! {
+    /// Pre-computed builder of the media profile
+    pub static ref MEDIA: Builder = {
+        let mut builder = minimal::MINIMAL.clone();
+        builder.extend(super::builder_from_names(SYSCALLS));
+        builder
+    };
+}
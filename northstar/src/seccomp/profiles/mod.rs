@@ -1,3 +1,42 @@
+use crate::{
+    common::non_nul_string::NonNulString,
+    seccomp::{
+        bpf::{builder_from_rules, Builder},
+        Profile, SyscallRule,
+    },
+};
+use std::{collections::HashMap, convert::TryInto};
+
 // TODO: Remove this allow
 #[allow(missing_docs)]
 pub mod default;
+/// Minimal profile plus audio and video processing
+pub mod media;
+/// Minimal profile with process, memory, file, time and signal handling
+pub mod minimal;
+/// Minimal profile plus sockets
+pub mod networking;
+
+/// Syscalls unconditionally allowed by `profile`. Syscalls that are allowed depending on
+/// the capabilities or with restricted arguments (e.g. `clone`) are not included.
+pub fn syscalls(profile: &Profile) -> Vec<&'static str> {
+    match profile {
+        Profile::Default => default::SYSCALLS_BASE.to_vec(),
+        Profile::Minimal => minimal::SYSCALLS.to_vec(),
+        Profile::Networking => [minimal::SYSCALLS, networking::SYSCALLS].concat(),
+        Profile::Media => [minimal::SYSCALLS, media::SYSCALLS].concat(),
+    }
+}
+
+/// Create a builder that allows `names` unconditionally
+fn builder_from_names(names: &[&str]) -> Builder {
+    let rules = names
+        .iter()
+        .map(|name| {
+            #[allow(clippy::unwrap_used)]
+            let name: NonNulString = name.to_string().try_into().unwrap();
+            (name, SyscallRule::Any)
+        })
+        .collect::<HashMap<_, _>>();
+    builder_from_rules(&rules)
+}
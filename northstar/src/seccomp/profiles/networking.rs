@@ -0,0 +1,33 @@
+use crate::seccomp::{bpf::Builder, profiles::minimal};
+
+/// Syscalls added to the minimal profile for network clients and servers
+pub const SYSCALLS: &[&str] = &[
+    "accept",
+    "accept4",
+    "bind",
+    "connect",
+    "getpeername",
+    "getsockname",
+    "getsockopt",
+    "listen",
+    "recvfrom",
+    "recvmmsg",
+    "recvmsg",
+    "sendfile",
+    "sendmmsg",
+    "sendmsg",
+    "sendto",
+    "setsockopt",
+    "shutdown",
+    "socket",
+    "socketpair",
+];
+
+lazy_static::lazy_static! {
+    /// Pre-computed builder of the networking profile
+    pub static ref NETWORKING: Builder = {
+        let mut builder = minimal::MINIMAL.clone();
+        builder.extend(super::builder_from_names(SYSCALLS));
+        builder
+    };
+}
@@ -1,7 +1,7 @@
 use crate::common::non_nul_string::NonNulString;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Predefined seccomp profile
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize, JsonSchema)]
@@ -9,6 +9,16 @@ pub enum Profile {
     /// Default seccomp filter similar to docker's default profile
     #[serde(rename = "default")]
     Default,
+    /// Process, memory, file, time and signal handling without sockets
+    #[serde(rename = "minimal")]
+    Minimal,
+    /// Minimal profile plus sockets for network clients and servers
+    #[serde(rename = "networking")]
+    Networking,
+    /// Minimal profile plus shared memory, message queues, scheduling priorities and
+    /// local sockets for audio and video processing
+    #[serde(rename = "media")]
+    Media,
 }
 
 /// Seccomp configuration
@@ -21,13 +31,19 @@ pub struct Seccomp {
     /// Explicit list of allowed syscalls
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allow: Option<HashMap<NonNulString, SyscallRule>>,
+    /// Syscalls removed from the profile and the allow list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny: Option<HashSet<NonNulString>>,
 }
 
 /// SELinux configuration
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Selinux {
-    /// Explicit list of allowed syscalls
+    /// Context of the container mounts
     pub context: NonNulString,
+    /// Label of the container process e.g. `system_u:system_r:container_t:s0`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<NonNulString>,
 }
 
 /// Syscall rule
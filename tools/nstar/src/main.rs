@@ -10,7 +10,10 @@ use futures::{sink::SinkExt, StreamExt};
 use northstar::{
     api::{
         self,
-        model::{Container, NonNulString, Request},
+        model::{
+            BatchMode, ConsolePermission, Container, FsOp, GroupOp, NonNulString, Request,
+            Selector, TokenScope,
+        },
     },
     common::{name::Name, version::Version},
 };
@@ -26,6 +29,7 @@ trait N: AsyncRead + AsyncWrite + Send + Unpin {}
 impl<T> N for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
 
 mod pretty;
+mod shell;
 
 /// Default nstar address
 const DEFAULT_HOST: &str = "tcp://localhost:4200";
@@ -47,28 +51,45 @@ enum Subcommand {
     Repositories,
     /// Mount a container
     Mount {
-        /// Container name and optional version
+        /// Container name and optional version or a single pattern e.g. `hello-*`
         #[clap(value_name = "name[:version]")]
         containers: Vec<String>,
     },
     /// Umount a container
     Umount {
-        /// Container name and optional version
+        /// Container name and optional version or a single pattern e.g. `hello-*`
         #[clap(value_name = "name[:version]")]
         containers: Vec<String>,
     },
     /// Start a container
     Start {
-        /// Container name and optional version
-        #[clap(value_name = "name[:version]")]
+        /// Container name, optional version and optional instance name
+        #[clap(value_name = "name[:version][@instance]")]
         container: String,
         /// Command line arguments
         #[clap(short, long)]
         args: Option<Vec<String>>,
-        /// Environment variables in KEY=VALUE format
+        /// Environment variables in KEY=VALUE format. Only variables listed in
+        /// env_overrides of the manifest can be set.
         #[clap(short, long)]
         env: Option<Vec<String>>,
     },
+    /// Check whether a container can be started without starting it
+    StartDryRun {
+        /// Container name, optional version and optional instance name
+        #[clap(value_name = "name[:version][@instance]")]
+        container: String,
+    },
+    /// Start a template container with parameters
+    StartTemplate {
+        /// Container name, optional version and optional instance name
+        #[clap(value_name = "name[:version][@instance]")]
+        container: String,
+        /// Template parameters in NAME=VALUE format. Parameters without a value use
+        /// the default of the manifest.
+        #[clap(short, long)]
+        parameter: Option<Vec<String>>,
+    },
     /// Stop a container
     Kill {
         /// Container name and optional version
@@ -77,6 +98,73 @@ enum Subcommand {
         /// Signal
         signal: Option<i32>,
     },
+    /// Apply the start, kill, mount and umount requests of a json file in one request
+    Batch {
+        /// Json file with a list of requests e.g. `[{"mount": ["hello:0.0.1"]}]`
+        requests: PathBuf,
+        /// Apply all requests regardless of failures instead of rolling back
+        #[clap(short, long)]
+        best_effort: bool,
+    },
+    /// Start, kill, mount or umount all containers matching a label selector
+    Group {
+        /// Label selector e.g. `group=hmi,!debug`
+        #[clap(value_name = "selector")]
+        selector: String,
+        /// Operation
+        #[clap(subcommand)]
+        operation: GroupOperation,
+    },
+    /// Send a signal to the application or named processes of a container
+    Signal {
+        /// Container name, optional version and optional instance name
+        #[clap(value_name = "name[:version][@instance]")]
+        container: String,
+        /// Signal
+        signal: i32,
+        /// Process name. Defaults to the application process.
+        #[clap(short, long)]
+        process: Option<String>,
+    },
+    /// Pause a container
+    Pause {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+    },
+    /// Write a line to the stdin of a container
+    Stdin {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Input
+        data: String,
+        /// Do not append a newline
+        #[clap(short, long)]
+        no_newline: bool,
+    },
+    /// Resume a paused container
+    Resume {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+    },
+    /// Freeze the started containers before a system suspend
+    SystemSuspend {
+        /// Containers to freeze. Default: all started containers
+        #[clap(value_name = "name[:version]")]
+        containers: Vec<String>,
+    },
+    /// Thaw the containers frozen by a system suspend
+    SystemResume,
+    /// Update an input of the throttling policies
+    PolicyInput {
+        /// Input name e.g. thermal
+        input: String,
+        /// Input value
+        #[clap(allow_hyphen_values = true)]
+        value: i64,
+    },
     /// Install a npk
     Install {
         /// Path to the .npk file
@@ -84,11 +172,30 @@ enum Subcommand {
         /// Target repository
         repository: String,
     },
+    /// List the queued and transferring installs
+    Installs,
+    /// Cancel an install
+    InstallCancel {
+        /// Install id
+        id: u64,
+    },
+    /// Cancel a request that was sent with an id
+    Cancel {
+        /// Request id
+        id: u64,
+    },
     /// Uninstall a container
     Uninstall {
         /// Container name and optional version
         #[clap(value_name = "name[:version]")]
         container: String,
+        /// Kill the started containers that use the container and uninstall
+        /// mounted containers and resources of installed containers
+        #[clap(short, long)]
+        force: bool,
+        /// Remove the persistent data of the container
+        #[clap(short, long)]
+        wipe: bool,
     },
     /// Shutdown Northstar
     Shutdown,
@@ -107,18 +214,114 @@ enum Subcommand {
         #[clap(short, long)]
         shell: clap_complete::Shell,
     },
+    /// List a directory of a mounted container
+    Dir {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Absolute path within the container
+        #[clap(default_value = "/")]
+        path: PathBuf,
+    },
+    /// Print a file of a mounted container
+    Cat {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Absolute path within the container
+        path: PathBuf,
+    },
+    /// Export the persist directory of a container to a tar archive
+    DataExport {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Output file
+        output: PathBuf,
+    },
+    /// Attach to the PTY of a started container with a tty. Input is read line wise
+    /// from stdin.
+    Attach {
+        /// Container name, optional version and optional instance name
+        #[clap(value_name = "name[:version][@instance]")]
+        container: String,
+    },
+    /// Replace the persist directory of a stopped container with a tar archive
+    DataImport {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Input file
+        input: PathBuf,
+    },
+    /// Wipe the persist directory of a container. A started container is killed.
+    DataReset {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+    },
+    /// List the core dumps of a container
+    CoreDumps {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+    },
+    /// Export a core dump of a container
+    CoreDumpExport {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Name of the core dump
+        name: String,
+        /// Output file
+        output: PathBuf,
+    },
+    /// Remove a core dump of a container
+    CoreDumpRemove {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Name of the core dump
+        name: String,
+    },
+    /// Inspect the manifest and software bill of materials of a container
+    Inspect {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+    },
     /// Request container statistics
     ContainerStats {
         /// Container name and optional version
         #[clap(value_name = "name[:version]")]
         container: String,
     },
+    /// List the processes of a container
+    Ps {
+        /// Container name, optional version and optional instance name
+        #[clap(value_name = "name[:version][@instance]")]
+        container: String,
+    },
+    /// List the services of started containers
+    Services {
+        /// Service name
+        name: Option<String>,
+    },
     /// Create a token
     Token {
         /// Token target
         target: String,
         /// Shared info
         shared: String,
+        /// Permission granted to the token holder
+        #[clap(short, long = "permission", parse(try_from_str = parse_permission))]
+        permissions: Vec<ConsolePermission>,
+        /// Container the token holder may act on
+        #[clap(short, long = "container", value_name = "name[:version]")]
+        containers: Vec<String>,
+        /// Validity in seconds. Capped by the runtime configuration
+        #[clap(short, long, parse(try_from_str = parse_secs))]
+        validity: Option<time::Duration>,
     },
     /// Create a token
     VerifyToken {
@@ -131,6 +334,30 @@ enum Subcommand {
     },
     /// Identification
     Ident,
+    /// Kernel, memory, disk, event queue and version information of the host
+    SystemInfo,
+    /// Interactive shell with command history, completion and live notifications
+    Shell {
+        /// File to load the command history from and save it to
+        #[clap(long)]
+        history: Option<PathBuf>,
+    },
+}
+
+/// Operations of the group subcommand
+#[derive(clap::Subcommand, Clone)]
+enum GroupOperation {
+    /// Start the containers that are not started
+    Start,
+    /// Stop the started containers
+    Kill {
+        /// Signal
+        signal: Option<i32>,
+    },
+    /// Mount the containers
+    Mount,
+    /// Umount the containers
+    Umount,
 }
 
 /// CLI
@@ -158,6 +385,17 @@ fn parse_secs(src: &str) -> Result<time::Duration, anyhow::Error> {
         .map_err(Into::into)
 }
 
+/// Parse a console permission e.g. `inspect`
+fn parse_permission(src: &str) -> Result<ConsolePermission, anyhow::Error> {
+    serde_json::from_value(serde_json::Value::String(src.to_string()))
+        .with_context(|| format!("invalid permission {}", src))
+}
+
+/// True if `containers` is a single pattern with a `*` wildcard
+fn is_pattern(containers: &[String]) -> bool {
+    matches!(containers, [pattern] if pattern.contains('*'))
+}
+
 /// Parse the container name and version out of the user input
 ///
 /// # Format
@@ -171,6 +409,12 @@ async fn parse_container<T>(name: &str, client: &mut Client<T>) -> Result<Contai
 where
     T: AsyncRead + AsyncWrite + Unpin,
 {
+    // Split the optional instance name
+    let (name, instance) = match name.split_once('@') {
+        Some((name, instance)) => (name, Some(Name::try_from(instance)?)),
+        None => (name, None),
+    };
+
     let (name, version): (Name, Version) = if let Some((name, version)) = name.split_once(':') {
         (Name::try_from(name)?, Version::parse(version)?)
     } else {
@@ -179,6 +423,7 @@ where
             .containers()
             .await?
             .into_iter()
+            .filter(|c| c.container.instance().is_none())
             .filter_map(|c| (c.manifest.name == name).then(|| c.manifest.version))
             .collect();
 
@@ -190,7 +435,11 @@ where
             (name, versions[0].clone())
         }
     };
-    Ok(Container::new(name, version))
+    let container = Container::new(name, version);
+    Ok(match instance {
+        Some(instance) => container.with_instance(instance),
+        None => container,
+    })
 }
 
 async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
@@ -201,6 +450,10 @@ async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
         Subcommand::Ident => Ok(Request::Ident),
         Subcommand::Containers => Ok(Request::Containers),
         Subcommand::Repositories => Ok(Request::Repositories),
+        Subcommand::SystemInfo => Ok(Request::SystemInfo),
+        Subcommand::Mount { containers } if is_pattern(&containers) => {
+            Ok(Request::MountMatching(containers[0].clone()))
+        }
         Subcommand::Mount { containers } => {
             let mut converted = Vec::with_capacity(containers.len());
             for container in containers {
@@ -208,6 +461,9 @@ async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
             }
             Ok(Request::Mount(converted))
         }
+        Subcommand::Umount { containers } if is_pattern(&containers) => {
+            Ok(Request::UmountMatching(containers[0].clone()))
+        }
         Subcommand::Umount { containers } => {
             let mut converted = Vec::with_capacity(containers.len());
             for container in containers {
@@ -255,27 +511,164 @@ async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
 
             Ok(Request::Start(container, args, env))
         }
+        Subcommand::StartDryRun { container } => Ok(Request::StartDryRun(
+            parse_container(&container, client).await?,
+        )),
+        Subcommand::StartTemplate {
+            container,
+            parameter,
+        } => {
+            let container = parse_container(&container, client).await?;
+            let mut parameters = HashMap::new();
+            for parameter in parameter.unwrap_or_default() {
+                let (name, value) = parameter
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("invalid parameter"))?;
+                let name = Name::try_from(name).context("invalid parameter name")?;
+                let value = NonNulString::try_from(value).context("invalid parameter value")?;
+                parameters.insert(name, value);
+            }
+            Ok(Request::StartTemplate(container, parameters))
+        }
         Subcommand::Kill { container, signal } => {
             let container = parse_container(&container, client).await?;
             let signal = signal.unwrap_or(15);
             Ok(Request::Kill(container, signal))
         }
+        Subcommand::Batch {
+            requests,
+            best_effort,
+        } => {
+            let requests = fs::read(&requests)
+                .await
+                .with_context(|| format!("failed to read {}", requests.display()))?;
+            let requests = serde_json::from_slice(&requests).context("invalid requests")?;
+            let mode = if best_effort {
+                BatchMode::BestEffort
+            } else {
+                BatchMode::AllOrNothing
+            };
+            Ok(Request::Batch(requests, mode))
+        }
+        Subcommand::Group {
+            selector,
+            operation,
+        } => {
+            let selector = Selector::from_str(&selector)?;
+            let op = match operation {
+                GroupOperation::Start => GroupOp::Start,
+                GroupOperation::Kill { signal } => GroupOp::Kill(signal.unwrap_or(15)),
+                GroupOperation::Mount => GroupOp::Mount,
+                GroupOperation::Umount => GroupOp::Umount,
+            };
+            Ok(Request::Group(selector, op))
+        }
+        Subcommand::Signal {
+            container,
+            signal,
+            process,
+        } => {
+            let container = parse_container(&container, client).await?;
+            Ok(Request::Signal(container, signal, process))
+        }
+        Subcommand::Pause { container } => {
+            Ok(Request::Pause(parse_container(&container, client).await?))
+        }
+        Subcommand::Resume { container } => {
+            Ok(Request::Resume(parse_container(&container, client).await?))
+        }
+        Subcommand::Stdin {
+            container,
+            data,
+            no_newline,
+        } => {
+            let container = parse_container(&container, client).await?;
+            let mut data = data.into_bytes();
+            if !no_newline {
+                data.push(b'\n');
+            }
+            Ok(Request::Stdin(container, data))
+        }
         Subcommand::Install { npk, repository } => {
             let size = npk.metadata().map(|m| m.len())?;
             Ok(Request::Install(repository, size))
         }
-        Subcommand::Uninstall { container } => Ok(Request::Uninstall(
+        Subcommand::Installs => Ok(Request::Installs),
+        Subcommand::InstallCancel { id } => Ok(Request::InstallCancel(id)),
+        Subcommand::Cancel { id } => Ok(Request::Cancel(id)),
+        Subcommand::Uninstall {
+            container,
+            force,
+            wipe,
+        } => Ok(Request::Uninstall {
+            container: parse_container(&container, client).await?,
+            force,
+            wipe,
+        }),
+        Subcommand::Shutdown => Ok(Request::Shutdown),
+        Subcommand::SystemSuspend { containers } => {
+            let mut converted = Vec::with_capacity(containers.len());
+            for container in containers {
+                converted.push(parse_container(&container, client).await?);
+            }
+            Ok(Request::SystemSuspend(converted))
+        }
+        Subcommand::SystemResume => Ok(Request::SystemResume),
+        Subcommand::PolicyInput { input, value } => Ok(Request::PolicyInput(input, value)),
+        Subcommand::Dir { container, path } => {
+            let container = parse_container(&container, client).await?;
+            let op = FsOp::List;
+            Ok(Request::Fs {
+                container,
+                path,
+                op,
+            })
+        }
+        Subcommand::DataReset { container } => Ok(Request::DataReset(
             parse_container(&container, client).await?,
         )),
-        Subcommand::Shutdown => Ok(Request::Shutdown),
+        Subcommand::CoreDumps { container } => Ok(Request::CoreDumps(
+            parse_container(&container, client).await?,
+        )),
+        Subcommand::CoreDumpRemove { container, name } => Ok(Request::CoreDumpRemove(
+            parse_container(&container, client).await?,
+            name,
+        )),
+        Subcommand::Inspect { container } => {
+            Ok(Request::Inspect(parse_container(&container, client).await?))
+        }
         Subcommand::ContainerStats { container } => {
             let container = parse_container(&container, client).await?;
             Ok(Request::ContainerStats(container))
         }
-        Subcommand::Token { target, shared } => {
+        Subcommand::Ps { container } => Ok(Request::Ps(parse_container(&container, client).await?)),
+        Subcommand::Services { name } => {
+            let name = name
+                .map(|name| Name::try_from(name.as_str()))
+                .transpose()
+                .context("invalid service name")?;
+            Ok(Request::Services(name))
+        }
+        Subcommand::Token {
+            target,
+            shared,
+            permissions,
+            containers,
+            validity,
+        } => {
             let target = target.as_bytes().to_vec();
             let shared = shared.as_bytes().to_vec();
-            Ok(Request::TokenCreate(target, shared))
+            let mut scope = TokenScope {
+                permissions: permissions.into_iter().collect(),
+                ..Default::default()
+            };
+            for container in containers {
+                scope
+                    .containers
+                    .insert(parse_container(&container, client).await?);
+            }
+            let validity = validity.map(|validity| validity.as_secs());
+            Ok(Request::TokenCreate(target, shared, scope, validity))
         }
         Subcommand::VerifyToken {
             token,
@@ -285,13 +678,82 @@ async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
             let user = user.as_bytes().to_vec();
             let shared = shared.as_bytes().to_vec();
             let token = hex::decode(token.as_bytes()).context("invalid token")?;
-            let token: [u8; 40] = token.try_into().map_err(|_| anyhow!("invalid token"))?;
             Ok(Request::TokenVerify(token.into(), user, shared))
         }
-        Subcommand::Notifications { .. } | Subcommand::Completion { .. } => unreachable!(),
+        Subcommand::Notifications { .. }
+        | Subcommand::Completion { .. }
+        | Subcommand::Cat { .. }
+        | Subcommand::DataExport { .. }
+        | Subcommand::DataImport { .. }
+        | Subcommand::CoreDumpExport { .. }
+        | Subcommand::Attach { .. }
+        | Subcommand::Shell { .. } => unreachable!(),
     }
 }
 
+/// Execute commands that transfer data in addition to the request
+async fn transfer<T: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut Client<T>,
+    command: Subcommand,
+) -> Result<()> {
+    match command {
+        Subcommand::Cat { container, path } => {
+            let container = parse_container(&container, client).await?;
+            client
+                .fs_read(container, &path, tokio::io::stdout())
+                .await
+                .with_context(|| format!("failed to read {}", path.display()))?;
+        }
+        Subcommand::DataExport { container, output } => {
+            let container = parse_container(&container, client).await?;
+            let mut file = fs::File::create(&output)
+                .await
+                .with_context(|| format!("failed to create {}", output.display()))?;
+            let size = client
+                .data_export(container, &mut file)
+                .await
+                .context("failed to export data")?;
+            println!("exported {} bytes to {}", size, output.display());
+        }
+        Subcommand::CoreDumpExport {
+            container,
+            name,
+            output,
+        } => {
+            let container = parse_container(&container, client).await?;
+            let mut file = fs::File::create(&output)
+                .await
+                .with_context(|| format!("failed to create {}", output.display()))?;
+            let size = client
+                .core_dump_export(container, &name, &mut file)
+                .await
+                .context("failed to export core dump")?;
+            println!("exported {} bytes to {}", size, output.display());
+        }
+        Subcommand::Attach { container } => {
+            let container = parse_container(&container, client).await?;
+            client
+                .attach(container, tokio::io::stdin(), tokio::io::stdout())
+                .await
+                .context("failed to attach")?;
+        }
+        Subcommand::DataImport { container, input } => {
+            let container = parse_container(&container, client).await?;
+            let file = fs::File::open(&input)
+                .await
+                .with_context(|| format!("failed to open {}", input.display()))?;
+            let size = file.metadata().await?.len();
+            client
+                .data_import(container, file, size)
+                .await
+                .context("failed to import data")?;
+            println!("imported {}", input.display());
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let opt = Opt::parse();
@@ -371,6 +833,24 @@ async fn main() -> Result<()> {
                 process::exit(0);
             }
         }
+        // Interactive shell on a persistent connection
+        Subcommand::Shell { history } => {
+            let client = Client::new(io, Some(100), opt.timeout)
+                .await
+                .with_context(|| format!("failed to connect to {}", opt.url))?;
+            shell::run(client, history, opt.json).await?;
+        }
+        // Commands that transfer data in addition to the request
+        command @ (Subcommand::Cat { .. }
+        | Subcommand::DataExport { .. }
+        | Subcommand::DataImport { .. }
+        | Subcommand::CoreDumpExport { .. }
+        | Subcommand::Attach { .. }) => {
+            let mut client = Client::new(io, None, opt.timeout)
+                .await
+                .context("failed to connect")?;
+            transfer(&mut client, command).await?;
+        }
         // Request response mode
         command => {
             // Connect
@@ -388,7 +868,7 @@ async fn main() -> Result<()> {
             let mut framed = client.framed();
 
             framed
-                .send(Message::Request { request })
+                .send(Message::Request { request, id: None })
                 .await
                 .context("failed to send request")?;
 
@@ -423,7 +903,7 @@ async fn main() -> Result<()> {
                     .await
                     .ok_or_else(|| anyhow!("failed to receive response"))??
                 {
-                    api::model::Message::Response { response } => pretty::response(&response),
+                    api::model::Message::Response { response, .. } => pretty::response(&response),
                     _ => unreachable!(),
                 };
                 process::exit(exit);
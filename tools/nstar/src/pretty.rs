@@ -1,29 +1,86 @@
 use itertools::Itertools;
 use model::ExitStatus;
 use northstar::api::model::{
-    self, ContainerData, MountResult, Notification, RepositoryId, Response, UmountResult,
+    self, ContainerData, CoreDump, FsEntry, FsEntryKind, FsResponse, GroupResult, InstallStatus,
+    MountResult, Notification, ProcessInfo, ProcessState, Protocol, RepositoryId, Response,
+    Service, SignalCause, StartCheck, UmountResult, VerificationResult,
 };
 use prettytable::{format, Attr, Cell, Row, Table};
 use std::collections::HashSet;
 use tokio::time;
 
 pub(crate) fn notification(notification: &Notification) {
+    println!("{}", format_notification(notification));
+}
+
+pub(crate) fn format_notification(notification: &Notification) -> String {
     match notification {
+        Notification::Audit {
+            listener,
+            peer,
+            action,
+        } => match listener {
+            Some(listener) => format!("{} on {}: {}", peer, listener, action),
+            None => format!("{}: {}", peer, action),
+        },
         Notification::CGroup(container, notification) => {
-            println!("container {} memory event {:?}", container, notification)
+            format!("container {} memory event {:?}", container, notification)
         }
-        Notification::Exit(container, status) => println!(
+        Notification::Exit(container, status) => format!(
             "container {} exited with status {}",
             container,
             match status {
                 ExitStatus::Exit { code } => format!("exit code {}", code),
-                ExitStatus::Signalled { signal } => format!("signalled {}", signal),
+                ExitStatus::Signalled { signal, cause } => {
+                    format!("signalled {}{}", signal, format_cause(cause))
+                }
             }
         ),
-        Notification::Install(container) => println!("installed {}", container),
-        Notification::Uninstall(container) => println!("uninstalled {}", container),
-        Notification::Started(container) => println!("started {}", container),
-        Notification::Shutdown => println!("shutting down"),
+        Notification::CoreDump(container, dump) => format!(
+            "container {} dumped core {} (pid {}, signal {}, {} bytes)",
+            container, dump.name, dump.pid, dump.signal, dump.size
+        ),
+        Notification::DataReset(container) => format!("data of {} reset", container),
+        Notification::Install(container) => format!("installed {}", container),
+        Notification::InstallProgress { id, bytes, total } => {
+            format!("install {}: {} of {} bytes", id, bytes, total)
+        }
+        Notification::Uninstall(container) => format!("uninstalled {}", container),
+        Notification::Kernel(container, notification) => match &notification.kind {
+            model::KernelNotificationKind::Oom => format!(
+                "container {} was oom killed (pid {})",
+                container, notification.pid
+            ),
+            model::KernelNotificationKind::Seccomp { syscall } => format!(
+                "container {} violated its seccomp filter with syscall {} (pid {})",
+                container,
+                syscall.map(|s| s.to_string()).unwrap_or_else(|| "?".into()),
+                notification.pid
+            ),
+            model::KernelNotificationKind::Segfault => format!(
+                "container {} segfaulted (pid {})",
+                container, notification.pid
+            ),
+        },
+        Notification::Log(container, line) => format!("{}: {}", container, line),
+        Notification::Paused(container) => format!("paused {}", container),
+        Notification::PeerBanned(peer, duration) => {
+            format!("banned {} for {}s", peer, duration)
+        }
+        Notification::PolicyActivated(policy) => format!("activated policy {}", policy),
+        Notification::PolicyDeactivated(policy) => format!("deactivated policy {}", policy),
+        Notification::RepositoryLoaded(repository) => format!("loaded repository {}", repository),
+        Notification::Resumed(container) => format!("resumed {}", container),
+        Notification::Started(container) => format!("started {}", container),
+        Notification::StartFailed(container, reason) => {
+            format!("failed to start {}: {}", container, reason)
+        }
+        Notification::Shutdown => "shutting down".to_string(),
+        Notification::ShutdownStage(priority, containers) => format!(
+            "stopping {} (shutdown priority {})",
+            containers.iter().join(", "),
+            priority
+        ),
     }
 }
 
@@ -35,7 +92,10 @@ fn containers(containers: &[ContainerData]) {
         "Type",
         "Mounted",
         "PID",
+        "State",
         "Uptime",
+        "Restarts",
+        "Last exit",
     ];
 
     let rows = containers
@@ -44,7 +104,13 @@ fn containers(containers: &[ContainerData]) {
         .sorted_by_key(|c| c.manifest.init.is_none())
         .map(|container| {
             [
-                Cell::new(container.container.name().as_ref()).with_style(Attr::Bold),
+                match container.container.instance() {
+                    Some(instance) => {
+                        Cell::new(&format!("{}@{}", container.container.name(), instance))
+                    }
+                    None => Cell::new(container.container.name().as_ref()),
+                }
+                .with_style(Attr::Bold),
                 Cell::new(&container.container.version().to_string()),
                 Cell::new(&container.repository),
                 if container.manifest.init.is_some() {
@@ -66,6 +132,12 @@ fn containers(containers: &[ContainerData]) {
                         .unwrap_or_default(),
                 )
                 .with_style(Attr::ForegroundColor(prettytable::color::GREEN)),
+                match container.process.as_ref().map(|p| &p.state) {
+                    Some(ProcessState::Running) => Cell::new("running"),
+                    Some(ProcessState::Paused) => Cell::new("paused")
+                        .with_style(Attr::ForegroundColor(prettytable::color::YELLOW)),
+                    None => Cell::new(""),
+                },
                 Cell::new(
                     &container
                         .process
@@ -76,12 +148,130 @@ fn containers(containers: &[ContainerData]) {
                         })
                         .unwrap_or_default(),
                 ),
+                Cell::new(&container.history.restarts.to_string()),
+                Cell::new(
+                    &container
+                        .history
+                        .exits
+                        .last()
+                        .map(|exit| {
+                            let status = match &exit.status {
+                                ExitStatus::Exit { code } => format!("exit code {}", code),
+                                // The oom flag of the exit is appended below
+                                ExitStatus::Signalled {
+                                    signal,
+                                    cause: SignalCause::Oom,
+                                } => format!("signalled {}", signal),
+                                ExitStatus::Signalled { signal, cause } => {
+                                    format!("signalled {}{}", signal, format_cause(cause))
+                                }
+                            };
+                            if exit.oom {
+                                format!("{} (oom)", status)
+                            } else {
+                                status
+                            }
+                        })
+                        .unwrap_or_default(),
+                ),
             ]
         });
 
     print_table(titles, rows);
 }
 
+fn fs_entries(entries: &[FsEntry]) {
+    let rows = entries.iter().map(|entry| {
+        let kind = match entry.kind {
+            FsEntryKind::File => "-",
+            FsEntryKind::Directory => "d",
+            FsEntryKind::Symlink => "l",
+            FsEntryKind::Other => "?",
+        };
+        [
+            Cell::new(&format!("{}{:04o}", kind, entry.mode)),
+            Cell::new(&entry.size.to_string()),
+            Cell::new(&entry.name).with_style(Attr::Bold),
+        ]
+    });
+    print_table(["Mode", "Size", "Name"], rows);
+}
+
+fn core_dumps(dumps: &[CoreDump]) {
+    let rows = dumps.iter().map(|dump| {
+        [
+            Cell::new(&dump.name).with_style(Attr::Bold),
+            Cell::new(&format!("{:?}", dump.kind).to_lowercase()),
+            Cell::new(&dump.pid.to_string()),
+            Cell::new(&dump.signal.to_string()),
+            Cell::new(&dump.time.to_string()),
+            Cell::new(&dump.size.to_string()),
+        ]
+    });
+    print_table(["Name", "Kind", "PID", "Signal", "Time", "Size"], rows);
+}
+
+fn processes(processes: &[ProcessInfo]) {
+    let rows = processes.iter().sorted_by_key(|p| p.pid).map(|p| {
+        [
+            Cell::new(&p.pid.to_string()).with_style(Attr::Bold),
+            Cell::new(&p.ppid.to_string()),
+            Cell::new(&p.name),
+            Cell::new(&p.rss.to_string()),
+        ]
+    });
+    print_table(["PID", "PPID", "Name", "RSS"], rows);
+}
+
+fn services(services: &[Service]) {
+    let rows = services
+        .iter()
+        .sorted_by_key(|s| (s.name.to_string(), s.container.to_string()))
+        .map(|s| {
+            let protocol = match s.protocol {
+                Protocol::Tcp => "tcp",
+                Protocol::Udp => "udp",
+            };
+            [
+                Cell::new(&s.name.to_string()).with_style(Attr::Bold),
+                Cell::new(&s.container.to_string()),
+                Cell::new(&s.port.to_string()),
+                Cell::new(protocol),
+                Cell::new(if s.namespace { "container" } else { "host" }),
+            ]
+        });
+    print_table(["Name", "Container", "Port", "Protocol", "Network"], rows);
+}
+
+fn start_checks(checks: &[StartCheck]) {
+    let rows = checks.iter().map(|check| {
+        let kind = format!("{:?}", check.kind).to_lowercase();
+        [
+            Cell::new(&kind).with_style(Attr::Bold),
+            Cell::new(if check.error.is_some() {
+                "failed"
+            } else {
+                "ok"
+            }),
+            Cell::new(check.error.as_deref().unwrap_or_default()),
+        ]
+    });
+    print_table(["Check", "Result", "Error"], rows);
+}
+
+fn installs(installs: &[InstallStatus]) {
+    let rows = installs.iter().map(|install| {
+        [
+            Cell::new(&install.id.to_string()).with_style(Attr::Bold),
+            Cell::new(&install.repository),
+            Cell::new(&format!("{:?}", install.state).to_lowercase()),
+            Cell::new(&install.bytes.to_string()),
+            Cell::new(&install.total.to_string()),
+        ]
+    });
+    print_table(["ID", "Repository", "State", "Bytes", "Size"], rows);
+}
+
 fn repositories(repositories: &HashSet<RepositoryId>) {
     let iter = repositories
         .iter()
@@ -104,6 +294,20 @@ fn mounts(mounts: &[MountResult]) {
     print_table(["Name", "Result"].iter(), iter);
 }
 
+fn group(results: &[GroupResult]) {
+    let iter = results.iter().map(|r| match r {
+        GroupResult::Ok { container } => [
+            Cell::new(&container.to_string()).with_style(Attr::Bold),
+            Cell::new("ok"),
+        ],
+        GroupResult::Error { container, error } => [
+            Cell::new(&container.to_string()).with_style(Attr::Bold),
+            Cell::new(&format_err(error)),
+        ],
+    });
+    print_table(["Name", "Result"], iter);
+}
+
 fn umounts(mounts: &[UmountResult]) {
     let iter = mounts.iter().map(|r| match r {
         UmountResult::Ok { container } => [
@@ -122,11 +326,35 @@ pub(crate) fn response(response: &Response) -> i32 {
     match response {
         Response::Ident(c) => println!("{}", c),
         Response::Containers(c) => containers(c),
+        Response::DataExport(size) => println!("export of {} bytes", size),
+        Response::CoreDumps(dumps) => core_dumps(dumps),
+        Response::CoreDumpExport(size) => println!("export of {} bytes", size),
+        Response::Fs(FsResponse::List(entries)) => fs_entries(entries),
+        Response::Fs(FsResponse::Read { data, .. }) => {
+            print!("{}", String::from_utf8_lossy(data))
+        }
+        Response::Ps(p) => processes(p),
+        Response::Services(s) => services(s),
+        Response::StartDryRun(checks) => start_checks(checks),
         Response::Repositories(r) => repositories(r),
         Response::Mount(result) => mounts(result),
         Response::Umount(result) => umounts(result),
+        Response::Group(result) => group(result),
+        Response::Batch(responses) => {
+            return responses.iter().enumerate().fold(0, |code, (n, r)| {
+                println!("request {}:", n);
+                code.max(response(r))
+            })
+        }
         Response::Ok => println!("ok"),
         Response::Install(container) => println!("installed {}", container),
+        Response::Installs(i) => installs(i),
+        Response::SystemInfo(info) => {
+            println!("{}", serde_json::to_string_pretty(&info).unwrap());
+        }
+        Response::Inspect(inspect) => {
+            println!("{}", serde_json::to_string_pretty(&inspect).unwrap());
+        }
         Response::ContainerStats(container, stats) => {
             println!("{}:", container);
             println!("{}", serde_json::to_string_pretty(&stats).unwrap());
@@ -134,6 +362,21 @@ pub(crate) fn response(response: &Response) -> i32 {
         Response::Token(token) => {
             println!("created: {}", hex::encode(token.as_ref()));
         }
+        Response::TokenVerification(VerificationResult::Ok(scope)) => {
+            println!("verification result: Ok");
+            if !scope.permissions.is_empty() {
+                println!(
+                    "permissions: {}",
+                    scope.permissions.iter().sorted().join(", ")
+                );
+            }
+            if !scope.containers.is_empty() {
+                println!(
+                    "containers: {}",
+                    scope.containers.iter().sorted().join(", ")
+                );
+            }
+        }
         Response::TokenVerification(result) => {
             println!("verification result: {:?}", result);
         }
@@ -148,16 +391,38 @@ pub(crate) fn response(response: &Response) -> i32 {
     }
 }
 
+/// Suffix that describes the origin of a terminating signal
+fn format_cause(cause: &SignalCause) -> String {
+    match cause {
+        SignalCause::Oom => " (oom)".into(),
+        SignalCause::Seccomp {
+            syscall: Some(syscall),
+        } => format!(" (seccomp violation of syscall {})", syscall),
+        SignalCause::Seccomp { syscall: None } => " (seccomp violation)".into(),
+        SignalCause::Runtime => " (runtime)".into(),
+        SignalCause::External => String::new(),
+    }
+}
+
 fn format_err(err: &model::Error) -> String {
     match err {
         model::Error::Configuration { context } => format!("invalid configuration: {}", context),
         model::Error::PermissionDenied { required, .. } => {
             format!("permission denied: required: {}", required)
         }
+        model::Error::OutOfScope {
+            container: Some(container),
+        } => format!("container {} is not in the token scope", container),
+        model::Error::OutOfScope { container: None } => {
+            "containers selected by a pattern are not in the token scope".to_string()
+        }
         model::Error::DuplicateContainer { container } => {
             format!("duplicate container name and version {}", container)
         }
         model::Error::InvalidContainer { container } => format!("invalid container {}", container),
+        model::Error::ContainerLoading { container } => {
+            format!("container {} is loading", container)
+        }
         model::Error::InvalidArguments { cause } => format!("invalid arguments {}", cause),
         model::Error::MountBusy { container } => format!("container busy: {}", container),
         model::Error::UmountBusy { container } => format!("container busy: {}", container),
@@ -183,22 +448,127 @@ fn format_err(err: &model::Error) -> String {
         model::Error::StopContainerNotStarted { container } => {
             format!("failed to stop container {}: not started", container)
         }
+        model::Error::PauseContainerPaused { container } => {
+            format!("failed to pause container {}: already paused", container)
+        }
+        model::Error::ResumeContainerNotPaused { container } => {
+            format!("failed to resume container {}: not paused", container)
+        }
+        model::Error::SystemSuspended => "system is suspended".to_string(),
+        model::Error::SystemNotSuspended => "system is not suspended".to_string(),
+        model::Error::Fs { container, error } => {
+            format!("failed to access container {}: {}", container, error)
+        }
+        model::Error::Data { container, error } => {
+            format!(
+                "failed to access data of container {}: {}",
+                container, error
+            )
+        }
+        model::Error::CoreDump { container, error } => {
+            format!(
+                "failed to access core dumps of container {}: {}",
+                container, error
+            )
+        }
+        model::Error::Attach { container, error } => {
+            format!("failed to attach to container {}: {}", container, error)
+        }
+        model::Error::Stdin { container, error } => {
+            format!(
+                "failed to write stdin of container {}: {}",
+                container, error
+            )
+        }
         model::Error::InvalidRepository { repository } => {
             format!("invalid repository {}", repository)
         }
+        model::Error::RepositoryReadOnly { repository } => {
+            format!("repository {} is read only", repository)
+        }
         model::Error::InstallDuplicate { container } => {
             format!("failed to install {}: installed", container)
         }
+        model::Error::UninstallInUse {
+            container,
+            mounted,
+            started,
+            dependents,
+        } => {
+            let mut uses = Vec::new();
+            if *mounted {
+                uses.push("mounted".to_string());
+            }
+            if !started.is_empty() {
+                uses.push(format!("used by {}", started.iter().join(", ")));
+            }
+            if !dependents.is_empty() {
+                uses.push(format!("required by {}", dependents.iter().join(", ")));
+            }
+            format!(
+                "failed to uninstall {}: {}. Use --force to uninstall anyway",
+                container,
+                uses.join(", ")
+            )
+        }
+        model::Error::Cancelled { id } => format!("request {} cancelled", id),
+        model::Error::InsufficientSpace { needed, available } => format!(
+            "insufficient space: {} bytes needed, {} bytes available",
+            needed, available
+        ),
+        model::Error::MissingSecurityLabel { container, module } => {
+            format!("container {} has no {} label", container, module)
+        }
+        model::Error::RLimitExceeded {
+            container,
+            resource,
+            max,
+        } => {
+            format!(
+                "container {} exceeds the maximum {} of rlimit {}",
+                container, max, resource
+            )
+        }
+        model::Error::Incompatible {
+            container,
+            requirement,
+            required,
+            found,
+        } => format!(
+            "container {} requires {} {} but the system has {}",
+            container, requirement, required, found
+        ),
+        model::Error::BatchAborted { failed } => {
+            format!("not applied: request {} of the batch failed", failed)
+        }
         model::Error::CriticalContainer { container, status } => {
             format!(
                 "critical container {} exited with: {}",
                 container,
                 match status {
                     ExitStatus::Exit { code } => format!("exit code {}", code),
-                    ExitStatus::Signalled { signal } => format!("signaled {}", signal),
+                    ExitStatus::Signalled { signal, cause } => {
+                        format!("signaled {}{}", signal, format_cause(cause))
+                    }
                 }
             )
         }
+        model::Error::Timeout {
+            container,
+            operation,
+            timeout,
+        } => {
+            let operation = match operation {
+                model::Operation::Mount => "mount",
+                model::Operation::Umount => "umount",
+                model::Operation::Start => "start",
+                model::Operation::Stop => "stop",
+            };
+            format!(
+                "{} of {} timed out after {}ms",
+                operation, container, timeout
+            )
+        }
         model::Error::Unexpected { module, error } => format!("{}: {}", module, error),
     }
 }
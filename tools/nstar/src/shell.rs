@@ -0,0 +1,282 @@
+//! Interactive shell mode
+
+use crate::{command_to_request, pretty, transfer, Subcommand};
+use anyhow::{Context as AnyhowContext, Result};
+use clap::{IntoApp, Parser};
+use futures::StreamExt;
+use itertools::Itertools;
+use northstar::api::{
+    client::{self, Client},
+    model::Response,
+};
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    validate::Validator, Context, Editor, ExternalPrinter, Helper,
+};
+use std::{
+    iter,
+    path::PathBuf,
+    sync::{mpsc as sync_mpsc, Arc, Mutex},
+    thread,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    select,
+    sync::mpsc,
+};
+
+/// Shell prompt
+const PROMPT: &str = "nstar> ";
+
+/// Subcommands that make no sense within a shell session
+const UNAVAILABLE: &[&str] = &["attach", "completion", "notifications", "shell"];
+
+/// Shell builtins
+const BUILTINS: &[&str] = &["exit", "help", "history", "quit"];
+
+/// Line editor helper that completes subcommands and container names
+struct ShellHelper {
+    /// Subcommand names and aliases
+    commands: Vec<String>,
+    /// Container names in `name` and `name:version` format
+    containers: Arc<Mutex<Vec<String>>>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map(|n| n + 1).unwrap_or(0);
+        let word = &line[start..];
+
+        // The first word is a command. Everything else is completed with container names.
+        let candidates = if line[..start].trim().is_empty() {
+            self.commands
+                .iter()
+                .filter(|c| c.starts_with(word))
+                .cloned()
+                .collect()
+        } else {
+            self.containers
+                .lock()
+                .expect("failed to lock container list")
+                .iter()
+                .filter(|c| c.starts_with(word))
+                .cloned()
+                .collect()
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Input read from the line editor thread
+enum Input {
+    Line(String),
+    Eof,
+}
+
+/// Run an interactive shell on an established connection. Commands are read with a line editor
+/// that offers history and completion of subcommands and container names. Notifications are
+/// printed above the prompt as they arrive.
+pub(crate) async fn run<T>(
+    mut client: Client<T>,
+    history: Option<PathBuf>,
+    json: bool,
+) -> Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let containers = Arc::new(Mutex::new(Vec::new()));
+    update_containers(&mut client, &containers).await?;
+
+    let commands = Subcommand::command()
+        .get_subcommands()
+        .filter(|c| !UNAVAILABLE.contains(&c.get_name()))
+        .flat_map(|c| iter::once(c.get_name()).chain(c.get_all_aliases()))
+        .chain(BUILTINS.iter().cloned())
+        .map(ToString::to_string)
+        .collect();
+    let helper = ShellHelper {
+        commands,
+        containers: containers.clone(),
+    };
+
+    let mut editor = Editor::<ShellHelper>::new().context("failed to create line editor")?;
+    editor.set_helper(Some(helper));
+    if let Some(history) = history.as_ref() {
+        // A missing history file is not an error
+        editor.load_history(history).ok();
+    }
+    let mut printer = editor
+        .create_external_printer()
+        .context("failed to create printer")?;
+
+    // The line editor blocks. Run it on a dedicated thread and wait for each line
+    // to be processed before displaying the next prompt.
+    let (line_tx, mut line_rx) = mpsc::channel(1);
+    let (done_tx, done_rx) = sync_mpsc::channel::<()>();
+    let editor = thread::spawn(move || {
+        loop {
+            let input = match editor.readline(PROMPT) {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) if line.trim() == "history" => {
+                    editor.add_history_entry(line.as_str());
+                    for (n, entry) in editor.history().iter().enumerate() {
+                        println!("{:>5}  {}", n + 1, entry);
+                    }
+                    continue;
+                }
+                Ok(line) => {
+                    editor.add_history_entry(line.as_str());
+                    Input::Line(line)
+                }
+                Err(ReadlineError::Interrupted) => continue,
+                Err(_) => Input::Eof,
+            };
+
+            let eof = matches!(input, Input::Eof);
+            if line_tx.blocking_send(input).is_err() || eof || done_rx.recv().is_err() {
+                break;
+            }
+        }
+        editor
+    });
+
+    loop {
+        select! {
+            input = line_rx.recv() => {
+                let line = match input {
+                    Some(Input::Line(line)) => line,
+                    Some(Input::Eof) | None => break,
+                };
+
+                let words = line.split_whitespace().collect::<Vec<_>>();
+                match words[0] {
+                    "exit" | "quit" => break,
+                    "help" => {
+                        Subcommand::command().print_help().ok();
+                        println!();
+                    }
+                    word if UNAVAILABLE.contains(&word) => {
+                        eprintln!("{} is not available in the shell", word);
+                    }
+                    _ => match Subcommand::try_parse_from(iter::once("nstar").chain(words)) {
+                        Ok(command) => {
+                            if let Err(e) = execute(&mut client, command, json).await {
+                                eprintln!("{:?}", e);
+                            }
+                            update_containers(&mut client, &containers).await?;
+                        }
+                        Err(e) => {
+                            e.print().ok();
+                        }
+                    },
+                }
+
+                done_tx.send(()).ok();
+            }
+            notification = client.next() => {
+                match notification {
+                    Some(Ok(notification)) => {
+                        let text = if json {
+                            serde_json::to_string(&notification)?
+                        } else {
+                            pretty::format_notification(&notification)
+                        };
+                        printer.print(format!("{}\n", text)).ok();
+                    }
+                    Some(Err(e)) => return Err(e).context("failed to receive notification"),
+                    None => {
+                        // The editor thread is blocked in the prompt and cannot be joined
+                        eprintln!("connection closed");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    // Dropping the sender unblocks the editor thread if it waits for completion
+    drop(done_tx);
+    drop(line_rx);
+
+    if let Some(history) = history {
+        if let Ok(mut editor) = editor.join() {
+            editor
+                .save_history(&history)
+                .with_context(|| format!("failed to save history to {}", history.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert `command` into a request, send it and print the response
+async fn execute<T>(client: &mut Client<T>, command: Subcommand, json: bool) -> Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let response = match command {
+        command @ (Subcommand::Cat { .. }
+        | Subcommand::DataExport { .. }
+        | Subcommand::DataImport { .. }
+        | Subcommand::CoreDumpExport { .. }) => return transfer(client, command).await,
+        Subcommand::Install { npk, repository } => {
+            match client.install_file(&npk, &repository).await {
+                Ok(container) => Response::Install(container),
+                Err(client::Error::Runtime(error)) => Response::Error(error),
+                Err(e) => return Err(e).context("failed to install npk"),
+            }
+        }
+        command => {
+            let request = command_to_request(command, client)
+                .await
+                .context("failed to convert command into request")?;
+            client
+                .request(request)
+                .await
+                .context("failed to execute request")?
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        pretty::response(&response);
+    }
+    Ok(())
+}
+
+/// Refresh the container list used for completion
+async fn update_containers<T>(client: &mut Client<T>, containers: &Mutex<Vec<String>>) -> Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let list = client
+        .containers()
+        .await
+        .context("failed to request container list")?
+        .into_iter()
+        .flat_map(|c| [c.container.name().to_string(), c.container.to_string()])
+        .sorted()
+        .dedup()
+        .collect::<Vec<_>>();
+    *containers.lock().expect("failed to lock container list") = list;
+    Ok(())
+}
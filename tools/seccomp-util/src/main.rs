@@ -1,16 +1,18 @@
 use anyhow::{Context, Result};
 use clap::Parser;
-use northstar::{
-    common::non_nul_string::NonNulString,
-    seccomp::{profiles::default::SYSCALLS_BASE, Profile, Seccomp, SyscallRule},
-};
-use regex::Regex;
-use std::{collections::HashMap, convert::TryFrom, fs::File, io, io::BufRead, path::PathBuf};
+use northstar::seccomp::{capture, Profile};
+use std::{collections::HashSet, fs::File, io, io::BufRead, path::PathBuf};
 
 #[derive(Debug, Parser)]
 struct Opt {
-    /// Path to strace log file
+    /// Path to strace log file or audit log if `--audit` is set
     input: PathBuf,
+    /// Input is a log of seccomp audit records (e.g. dmesg or audit.log)
+    #[clap(long)]
+    audit: bool,
+    /// Profile that is extended by the syscalls of the input: default, minimal, networking or media
+    #[clap(long, parse(try_from_str = parse_profile), conflicts_with = "no-default-profile")]
+    profile: Option<Profile>,
     /// Whether or not to allow the syscalls defined by the default profile
     #[clap(long)]
     no_default_profile: bool,
@@ -18,39 +20,37 @@ struct Opt {
 
 fn main() -> Result<()> {
     env_logger::init();
-    let path: PathBuf = Opt::parse().input;
-    let no_default_profile = Opt::parse().no_default_profile;
+    let opt = Opt::parse();
+    let path = opt.input;
 
-    // Collect syscall names from strace file
+    // Collect syscall names from strace or audit file
     let file =
-        File::open(&path).context(format!("failed to open strace log: {}", &path.display()))?;
-    let mut syscalls: HashMap<NonNulString, SyscallRule> = HashMap::new();
-    // unwrap(): Creating regex from constant expression will never fail
-    let regex = Regex::new(r"^\s*(?:\[[^]]*]|\d+)?\s*([a-zA-Z0-9_]+)\(([^)<]*)").unwrap();
-    io::BufReader::new(file)
-        .lines()
-        .try_for_each(|line| -> Result<()> {
-            if let Some(caps) = regex.captures(line?.as_str()) {
-                if let Some(m) = caps.get(1) {
-                    if no_default_profile || !SYSCALLS_BASE.contains(&m.as_str()) {
-                        syscalls.insert(NonNulString::try_from(m.as_str())?, SyscallRule::Any);
-                    }
-                }
-            }
-            Ok(())
-        })?;
+        File::open(&path).context(format!("failed to open syscall log: {}", &path.display()))?;
+    let mut syscalls: HashSet<String> = HashSet::new();
+    for line in io::BufReader::new(file).lines() {
+        let line = line?;
+        let syscall = if opt.audit {
+            capture::audit_syscall(&line)
+        } else {
+            capture::strace_syscall(&line)
+        };
+        if let Some(syscall) = syscall {
+            syscalls.insert(syscall.to_string());
+        }
+    }
 
     // Write manifest entry to stdout
-    let profile = if no_default_profile {
-        None
-    } else {
-        Some(Profile::Default)
-    };
-    let allow = if syscalls.is_empty() {
+    let profile = if opt.no_default_profile {
         None
     } else {
-        Some(syscalls)
+        Some(opt.profile.unwrap_or(Profile::Default))
     };
-    println!("{}", &serde_yaml::to_string(&Seccomp { profile, allow })?);
+    let seccomp = capture::seccomp(syscalls.iter().map(String::as_str), profile)?;
+    println!("{}", &serde_yaml::to_string(&seccomp)?);
     Ok(())
 }
+
+/// Parse a profile name as used in the manifest
+fn parse_profile(s: &str) -> Result<Profile> {
+    serde_yaml::from_str(s).context(format!("invalid profile: {}", s))
+}
@@ -68,3 +68,49 @@ allow:
     }
     Ok(())
 }
+
+#[test]
+fn parse_strace_log_with_profile() -> Result<()> {
+    use assert_cmd::Command;
+
+    const EXPECTED: &str = "---
+profile: minimal
+allow:
+  delete_module: any
+
+";
+    let stdout = String::from_utf8(
+        Command::cargo_bin("seccomp-util")?
+            .arg("./res/test_strace_data.txt")
+            .arg("--profile")
+            .arg("minimal")
+            .output()?
+            .stdout,
+    )?;
+    assert_eq!(stdout, EXPECTED);
+    Ok(())
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn parse_audit_log() -> Result<()> {
+    use assert_cmd::Command;
+
+    const EXPECTED: &str = "---
+profile: minimal
+allow:
+  socket: any
+
+";
+    let stdout = String::from_utf8(
+        Command::cargo_bin("seccomp-util")?
+            .arg("./res/test_audit_data.txt")
+            .arg("--audit")
+            .arg("--profile")
+            .arg("minimal")
+            .output()?
+            .stdout,
+    )?;
+    assert_eq!(stdout, EXPECTED);
+    Ok(())
+}
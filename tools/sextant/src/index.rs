@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use northstar::{
+    common::container::Container,
+    npk::{
+        index::{self, Index},
+        npk::{Npk, NPK_EXT},
+    },
+};
+use std::{fs, path::Path};
+
+/// Create a signed index of the npks in the repository `dir`
+pub(crate) fn index(dir: &Path, key: &Path, revoke: &[Container]) -> Result<()> {
+    let mut index = Index {
+        revoked: revoke.iter().cloned().collect(),
+        ..Default::default()
+    };
+
+    for entry in fs::read_dir(dir).context("failed to read repository")? {
+        let path = entry.context("failed to read repository")?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(NPK_EXT) {
+            continue;
+        }
+
+        let npk = Npk::from_path(&path, None)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let container = npk.manifest().container();
+        let file =
+            fs::File::open(&path).with_context(|| format!("failed to open {}", path.display()))?;
+        let hash =
+            index::hash(file).with_context(|| format!("failed to hash {}", path.display()))?;
+        println!("{} {}", container, hash);
+        index.npks.insert(container, hash);
+    }
+
+    index.write(dir, key).context("failed to write index")
+}
@@ -5,13 +5,18 @@
 
 use anyhow::Result;
 use clap::Parser;
-use northstar::npk::{
-    self,
-    npk::{CompressionAlgorithm, SquashfsOptions},
+use northstar::{
+    common::container::Container,
+    npk::{
+        self,
+        npk::{CompressionAlgorithm, SquashfsOptions},
+    },
 };
 use std::path::PathBuf;
 
+mod index;
 mod inspect;
+mod oci;
 mod pack;
 
 #[derive(Debug, Parser)]
@@ -31,18 +36,51 @@ enum Opt {
         /// Output directory
         #[clap(short, long)]
         out: PathBuf,
-        /// Compression algorithm to use in squashfs (default gzip)
+        /// Compression algorithm to use in squashfs: gzip, lz4, lzo, xz or zstd (default gzip)
         #[clap(short, long, default_value = "gzip")]
         compression_algorithm: CompressionAlgorithm,
         /// mksqushfs binary
         #[clap(long, default_value = "mksquashfs")]
         mksquashfs: PathBuf,
-        /// Block size used by squashfs (default 128 KiB)
+        /// Block size used by squashfs. Power of two between 4 KiB and 1 MiB (default 128 KiB)
         #[clap(short, long)]
         block_size: Option<u32>,
         /// Create n clones of the container
         #[clap(long)]
         clones: Option<u32>,
+        /// Create a byte for byte reproducible NPK (requires mksquashfs 4.4)
+        #[clap(long)]
+        reproducible: bool,
+        /// SPDX or CycloneDX JSON software bill of materials added to the NPK
+        #[clap(long)]
+        sbom: Option<PathBuf>,
+    },
+    /// Verify that a NPK is reproducible from a manifest and root
+    Verify {
+        /// NPK path
+        #[clap(short, long)]
+        npk: PathBuf,
+        /// Manifest path
+        #[clap(short, long)]
+        manifest: PathBuf,
+        /// Container source directory
+        #[clap(short, long)]
+        root: PathBuf,
+        /// Key file
+        #[clap(short, long)]
+        key: Option<PathBuf>,
+        /// Compression algorithm to use in squashfs: gzip, lz4, lzo, xz or zstd (default gzip)
+        #[clap(short, long, default_value = "gzip")]
+        compression_algorithm: CompressionAlgorithm,
+        /// mksqushfs binary
+        #[clap(long, default_value = "mksquashfs")]
+        mksquashfs: PathBuf,
+        /// Block size used by squashfs. Power of two between 4 KiB and 1 MiB (default 128 KiB)
+        #[clap(short, long)]
+        block_size: Option<u32>,
+        /// SPDX or CycloneDX JSON software bill of materials added to the NPK
+        #[clap(long)]
+        sbom: Option<PathBuf>,
     },
     /// Unpack Northstar containers
     Unpack {
@@ -56,6 +94,18 @@ enum Opt {
         #[clap(long, default_value = "unsquashfs")]
         unsquashfs: PathBuf,
     },
+    /// Export a Northstar container to an OCI runtime bundle
+    Export {
+        /// NPK path
+        #[clap(short, long)]
+        npk: PathBuf,
+        /// Bundle directory
+        #[clap(short, long)]
+        out: PathBuf,
+        /// unsquashfs binary
+        #[clap(long, default_value = "unsquashfs")]
+        unsquashfs: PathBuf,
+    },
     /// Print information about a Northstar container
     Inspect {
         #[clap(short, long)]
@@ -74,6 +124,18 @@ enum Opt {
         #[clap(short, long)]
         out: PathBuf,
     },
+    /// Create a signed index with the hashes of the NPKs in a repository directory
+    Index {
+        /// Repository directory
+        #[clap(short, long)]
+        dir: PathBuf,
+        /// Key file
+        #[clap(short, long)]
+        key: PathBuf,
+        /// Revoked container e.g. hello-world:0.0.1. Can be repeated.
+        #[clap(short, long, parse(try_from_str = Container::try_from))]
+        revoke: Vec<Container>,
+    },
 }
 
 fn main() -> Result<()> {
@@ -89,6 +151,8 @@ fn main() -> Result<()> {
             mksquashfs,
             block_size,
             clones,
+            reproducible,
+            sbom,
         } => pack::pack(
             &manifest,
             &root,
@@ -100,18 +164,47 @@ fn main() -> Result<()> {
                 block_size,
             },
             clones,
+            reproducible,
+            sbom.as_deref(),
+        )?,
+        Opt::Verify {
+            npk,
+            manifest,
+            root,
+            key,
+            compression_algorithm,
+            mksquashfs,
+            block_size,
+            sbom,
+        } => npk::npk::verify_reproducible(
+            &npk,
+            &manifest,
+            &root,
+            key.as_deref(),
+            SquashfsOptions {
+                compression_algorithm,
+                mksquashfs,
+                block_size,
+            },
+            sbom.as_deref(),
         )?,
         Opt::Unpack {
             npk,
             out,
             unsquashfs,
         } => npk::npk::unpack_with(&npk, &out, &unsquashfs)?,
+        Opt::Export {
+            npk,
+            out,
+            unsquashfs,
+        } => oci::export(&npk, &out, &unsquashfs)?,
         Opt::Inspect {
             npk,
             short,
             unsquashfs,
         } => inspect::inspect(&npk, short, &unsquashfs)?,
         Opt::GenKey { name, out } => npk::npk::generate_key(&name, &out)?,
+        Opt::Index { dir, key, revoke } => index::index(&dir, &key, &revoke)?,
     }
     Ok(())
 }
@@ -0,0 +1,286 @@
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use northstar::npk::{
+    manifest::{
+        mount::{Bind, Mount, MountOption, MountOptions, Tmpfs},
+        Capability, Manifest,
+    },
+    npk::{unpack_with, Npk},
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+};
+
+/// Version of the OCI runtime specification the generated config complies to
+const OCI_VERSION: &str = "1.0.2";
+/// Directory of the root filesystem within the bundle
+const ROOTFS: &str = "rootfs";
+/// Name of the OCI bundle configuration
+const CONFIG_JSON: &str = "config.json";
+
+/// Unpack `npk` into an OCI runtime bundle in `out`. The bundle contains the root
+/// filesystem in `rootfs` and a `config.json` that is generated from the manifest.
+pub(crate) fn export(npk: &Path, out: &Path, unsquashfs: &Path) -> Result<()> {
+    let manifest = Npk::<BufReader<File>>::from_path(npk, None)?
+        .manifest()
+        .clone();
+
+    let rootfs = out.join(ROOTFS);
+    if rootfs.exists() {
+        return Err(anyhow!("{} already exists", rootfs.display()));
+    }
+    fs::create_dir_all(out).with_context(|| format!("failed to create {}", out.display()))?;
+
+    // Unpack within out in order to move the root filesystem without copying
+    let tmp = tempfile::tempdir_in(out).context("failed to create temporary directory")?;
+    unpack_with(npk, tmp.path(), unsquashfs)?;
+    fs::rename(tmp.path().join("squashfs-root"), &rootfs)
+        .with_context(|| format!("failed to move root filesystem to {}", rootfs.display()))?;
+
+    let config = config(&manifest)?;
+    let config_json = out.join(CONFIG_JSON);
+    let file = File::create(&config_json)
+        .with_context(|| format!("failed to create {}", config_json.display()))?;
+    serde_json::to_writer_pretty(file, &config)
+        .with_context(|| format!("failed to write {}", config_json.display()))?;
+
+    Ok(())
+}
+
+/// Map `manifest` to an OCI runtime configuration. Manifest settings that depend on
+/// the Northstar runtime cannot be represented and are skipped with a warning.
+fn config(manifest: &Manifest) -> Result<Value> {
+    let init = manifest
+        .init
+        .as_ref()
+        .ok_or_else(|| anyhow!("{} is a resource container", manifest.container()))?;
+
+    let args = std::iter::once(init.display().to_string())
+        .chain(manifest.args.iter().map(ToString::to_string))
+        .collect::<Vec<_>>();
+
+    let mut env = manifest
+        .env
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>();
+    env.sort();
+
+    let capabilities = |set: &HashSet<Capability>| -> Result<Vec<Value>> {
+        let mut set = set
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        set.sort_by_key(ToString::to_string);
+        Ok(set)
+    };
+    let bounding = capabilities(&manifest.capabilities.bounding)?;
+    let permitted = capabilities(&manifest.capabilities.permitted)?;
+    let ambient = capabilities(&manifest.capabilities.ambient)?;
+
+    let mut rlimits = manifest
+        .rlimits
+        .iter()
+        .map(|(resource, value)| {
+            let resource = serde_json::to_value(resource)?
+                .as_str()
+                .map(|r| format!("RLIMIT_{}", r.to_uppercase()))
+                .unwrap_or_default();
+            Ok(json!({
+                "type": resource,
+                "soft": value.soft.unwrap_or(u64::MAX),
+                "hard": value.hard.unwrap_or(u64::MAX),
+            }))
+        })
+        .collect::<Result<Vec<_>, serde_json::Error>>()?;
+    rlimits.sort_by_key(ToString::to_string);
+
+    let mut targets = manifest.mounts.keys().collect::<Vec<_>>();
+    targets.sort();
+    let mut mounts = Vec::with_capacity(targets.len());
+    for target in targets {
+        let destination = target.display().to_string();
+        match &manifest.mounts[target] {
+            Mount::Bind(Bind { host, options }) => {
+                let mut bind_options = vec!["rbind".to_string()];
+                bind_options.extend(options_to_oci(options));
+                mounts.push(json!({
+                    "destination": destination,
+                    "type": "bind",
+                    "source": host.display().to_string(),
+                    "options": bind_options,
+                }));
+            }
+            Mount::Dev => mounts.push(json!({
+                "destination": destination,
+                "type": "tmpfs",
+                "source": "tmpfs",
+                "options": ["nosuid", "strictatime", "mode=755", "size=65536k"],
+            })),
+            Mount::Proc => mounts.push(json!({
+                "destination": destination,
+                "type": "proc",
+                "source": "proc",
+                "options": ["nosuid", "noexec", "nodev", "ro"],
+            })),
+            Mount::Tmpfs(Tmpfs { size }) => mounts.push(json!({
+                "destination": destination,
+                "type": "tmpfs",
+                "source": "tmpfs",
+                "options": ["nosuid", "noexec", "nodev", "mode=1777", format!("size={}", size)],
+            })),
+            Mount::Overlay(_) | Mount::Persist(_) | Mount::Resource(_) | Mount::Secret(_) => {
+                warn!(
+                    "Skipping mount {} that is provided by the Northstar runtime",
+                    destination
+                );
+            }
+        }
+    }
+
+    if manifest.cgroups.is_some() {
+        warn!("Skipping cgroup configuration");
+    }
+    if manifest.seccomp.is_some() {
+        warn!("Skipping seccomp configuration");
+    }
+
+    let mut process = json!({
+        "terminal": false,
+        "user": {
+            "uid": manifest.uid,
+            "gid": manifest.gid,
+        },
+        "args": args,
+        "env": env,
+        "cwd": "/",
+        "capabilities": {
+            "bounding": bounding,
+            "effective": permitted,
+            "permitted": permitted,
+            "inheritable": permitted,
+            "ambient": ambient,
+        },
+        "rlimits": rlimits,
+        "noNewPrivileges": manifest.no_new_privs.unwrap_or(true),
+    });
+    let mut linux = json!({
+        "namespaces": [
+            { "type": "pid" },
+            { "type": "mount" },
+        ],
+    });
+    if let Some(selinux) = &manifest.selinux {
+        process["selinuxLabel"] = json!(selinux.context.to_string());
+        linux["mountLabel"] = json!(selinux.context.to_string());
+    }
+
+    Ok(json!({
+        "ociVersion": OCI_VERSION,
+        "process": process,
+        "root": {
+            "path": ROOTFS,
+            // Northstar root filesystems are read only unless an overlay is configured
+            "readonly": manifest.root.is_none(),
+        },
+        "hostname": manifest.name.to_string(),
+        "mounts": mounts,
+        "linux": linux,
+        "annotations": {
+            "org.northstar.name": manifest.name.to_string(),
+            "org.northstar.version": manifest.version.to_string(),
+        },
+    }))
+}
+
+fn options_to_oci(options: &MountOptions) -> Vec<String> {
+    let mut result = vec![if options.contains(&MountOption::Rw) {
+        "rw".to_string()
+    } else {
+        "ro".to_string()
+    }];
+    for option in options.iter() {
+        match option {
+            MountOption::NoExec => result.push("noexec".into()),
+            MountOption::NoSuid => result.push("nosuid".into()),
+            MountOption::NoDev => result.push("nodev".into()),
+            MountOption::Rw | MountOption::Rec => (),
+        }
+    }
+    result[1..].sort();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::config;
+    use northstar::npk::manifest::Manifest;
+    use std::str::FromStr;
+
+    const TEST_MANIFEST: &str = "name: hello
+version: 0.0.2
+init: /hello
+args:
+  - --world
+env:
+  HELLO: north
+uid: 1000
+gid: 1001
+capabilities:
+  - CAP_KILL
+rlimits:
+  nofile:
+    soft: 100
+    hard: 200
+mounts:
+    /data:
+      type: persist
+    /proc:
+      type: proc
+    /lib:
+      type: bind
+      host: /lib
+      options: nosuid,nodev";
+
+    #[test]
+    fn oci_config() {
+        let manifest = Manifest::from_str(TEST_MANIFEST).expect("failed to parse manifest");
+        let config = config(&manifest).expect("failed to generate config");
+
+        assert_eq!(
+            config["process"]["args"],
+            serde_json::json!(["/hello", "--world"])
+        );
+        assert_eq!(config["process"]["env"], serde_json::json!(["HELLO=north"]));
+        assert_eq!(config["process"]["user"]["uid"], 1000);
+        assert_eq!(config["process"]["user"]["gid"], 1001);
+        assert_eq!(
+            config["process"]["capabilities"]["bounding"],
+            serde_json::json!(["CAP_KILL"])
+        );
+        assert_eq!(config["process"]["rlimits"][0]["type"], "RLIMIT_NOFILE");
+        assert_eq!(config["process"]["rlimits"][0]["soft"], 100);
+        assert_eq!(config["root"]["readonly"], true);
+
+        // The persist mount is skipped
+        let mounts = config["mounts"].as_array().unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0]["destination"], "/lib");
+        assert_eq!(
+            mounts[0]["options"],
+            serde_json::json!(["rbind", "ro", "nodev", "nosuid"])
+        );
+        assert_eq!(mounts[1]["destination"], "/proc");
+    }
+
+    #[test]
+    fn oci_config_resource_container() {
+        let manifest = Manifest::from_str("name: resource\nversion: 0.0.1\nuid: 1000\ngid: 1000")
+            .expect("failed to parse manifest");
+        assert!(config(&manifest).is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use northstar::npk::{
     manifest::Manifest,
-    npk::{pack_with, SquashfsOptions},
+    npk::{pack_reproducible, pack_with, SquashfsOptions},
 };
 use std::{convert::TryInto, fs, path::Path};
 use tempfile::tempdir;
@@ -14,12 +14,19 @@ pub(crate) fn pack(
     key: Option<&Path>,
     squashfs_options: SquashfsOptions,
     clones: Option<u32>,
+    reproducible: bool,
+    sbom: Option<&Path>,
 ) -> Result<()> {
+    let pack_with = if reproducible {
+        pack_reproducible
+    } else {
+        pack_with
+    };
+
     // Create npk clones with the number appended to the name
     if let Some(clones) = clones {
         let manifest_file = manifest;
-        let reader = fs::File::open(&manifest_file).context("failed to open manifest")?;
-        let mut manifest = Manifest::from_reader(reader).context("failed to read manifest")?;
+        let mut manifest = Manifest::from_path(manifest_file).context("failed to read manifest")?;
 
         // Only clone non-resource containers
         if manifest.init.is_some() {
@@ -32,14 +39,128 @@ pub(crate) fn pack(
                     .context("failed to parse name")?;
                 let m = tmp.path().join(n.to_string());
                 fs::write(&m, manifest.to_string()).context("failed to write manifest")?;
-                pack_with(&m, root, out, key, squashfs_options.clone())?;
+                pack_with(&m, root, out, key, squashfs_options.clone(), sbom)?;
             }
         } else {
-            pack_with(manifest_file, root, out, key, squashfs_options)?;
+            pack_with(manifest_file, root, out, key, squashfs_options, sbom)?;
         }
     } else {
-        pack_with(manifest, root, out, key, squashfs_options)?;
+        pack_with(manifest, root, out, key, squashfs_options, sbom)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use northstar::npk::npk::{
+        generate_key, pack_reproducible, pack_with, verify_reproducible, Npk, SbomFormat,
+    };
+    use std::fs;
+    use tempfile::TempDir;
+
+    const TEST_MANIFEST: &str = "name: hello
+version: 0.0.2
+init: /hello
+env:
+  HELLO: north
+  WORLD: south
+uid: 1000
+gid: 1000
+capabilities:
+  - CAP_KILL
+  - CAP_NET_RAW
+mounts:
+    /data:
+      type: persist
+    /dev:
+      type: dev
+    /lib:
+      type: bind
+      host: /lib
+      options: nosuid,nodev";
+
+    #[test]
+    fn pack_reproducible_npk() {
+        let src = TempDir::new().expect("Create tmp dir");
+        let manifest = src.path().join("manifest.yaml");
+        fs::write(&manifest, TEST_MANIFEST).expect("Write test manifest");
+        let root = src.path().join("root");
+        fs::create_dir(&root).expect("Create root");
+        fs::write(root.join("hello"), "hello").expect("Write test file");
+        generate_key("test_key", src.path()).expect("Generate key pair");
+        let key = src.path().join("test_key.key");
+
+        let a = TempDir::new().expect("Create tmp dir");
+        let b = TempDir::new().expect("Create tmp dir");
+        for dest in [a.path(), b.path()] {
+            pack_reproducible(&manifest, &root, dest, Some(&key), Default::default(), None)
+                .expect("Pack NPK");
+        }
+        let a = a.path().join("hello-0.0.2.npk");
+        let b = b.path().join("hello-0.0.2.npk");
+        assert_eq!(
+            fs::read(&a).expect("Read NPK"),
+            fs::read(&b).expect("Read NPK")
+        );
+
+        verify_reproducible(&a, &manifest, &root, Some(&key), Default::default(), None)
+            .expect("Verify NPK");
+
+        // Modify the content
+        fs::write(root.join("hello"), "world").expect("Write test file");
+        assert!(
+            verify_reproducible(&a, &manifest, &root, Some(&key), Default::default(), None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn pack_sbom() {
+        let src = TempDir::new().expect("Create tmp dir");
+        let manifest = src.path().join("manifest.yaml");
+        fs::write(&manifest, TEST_MANIFEST).expect("Write test manifest");
+        let root = src.path().join("root");
+        fs::create_dir(&root).expect("Create root");
+        fs::write(root.join("hello"), "hello").expect("Write test file");
+        generate_key("test_key", src.path()).expect("Generate key pair");
+        let key = src.path().join("test_key.key");
+        let sbom = src.path().join("sbom.json");
+        fs::write(&sbom, r#"{"bomFormat": "CycloneDX", "specVersion": "1.4"}"#)
+            .expect("Write sbom");
+
+        let dest = TempDir::new().expect("Create tmp dir");
+        pack_with(
+            &manifest,
+            &root,
+            dest.path(),
+            Some(&key),
+            Default::default(),
+            Some(&sbom),
+        )
+        .expect("Pack NPK");
+
+        let public_key = fs::read(src.path().join("test_key.pub")).expect("Read public key");
+        let public_key =
+            ed25519_dalek::PublicKey::from_bytes(&public_key).expect("Parse public key");
+        let npk = Npk::<std::io::BufReader<fs::File>>::from_path(
+            &dest.path().join("hello-0.0.2.npk"),
+            Some(&public_key),
+        )
+        .expect("Open NPK");
+        let sbom = npk.sbom().expect("Missing sbom");
+        assert_eq!(sbom.format, SbomFormat::CycloneDx);
+
+        // Invalid sboms are rejected
+        fs::write(src.path().join("invalid.json"), "{}").expect("Write sbom");
+        assert!(pack_with(
+            &manifest,
+            &root,
+            dest.path(),
+            Some(&key),
+            Default::default(),
+            Some(&src.path().join("invalid.json")),
+        )
+        .is_err());
+    }
+}
@@ -173,8 +173,13 @@ async fn main() -> Result<()> {
                         .await
                         .context("failed to stop container")?;
                     info!("{}: waiting for termination", container);
-                    let stopped =
-                        Notification::Exit(container.clone(), ExitStatus::Signalled { signal: 15 });
+                    let stopped = Notification::Exit(
+                        container.clone(),
+                        ExitStatus::Signalled {
+                            signal: 15,
+                            cause: model::SignalCause::Runtime,
+                        },
+                    );
                     await_notification(&mut client, stopped, opt.timeout).await?;
                 }
 
@@ -300,7 +305,13 @@ async fn iteration(
             .context("failed to stop container")?;
 
         info!("{}: waiting for termination", container);
-        let stopped = Notification::Exit(container.clone(), ExitStatus::Signalled { signal: 15 });
+        let stopped = Notification::Exit(
+            container.clone(),
+            ExitStatus::Signalled {
+                signal: 15,
+                cause: model::SignalCause::Runtime,
+            },
+        );
         await_notification(client, stopped, timeout).await?;
     }
 
@@ -362,7 +373,7 @@ async fn install_uninstall(opt: &Opt) -> Result<()> {
             n = client.next() => {
                 match n {
                     Some(Ok(model::Notification::Install ( container ))) => {
-                        client.uninstall(container).await?;
+                        client.uninstall(container, false, false).await?;
                     }
                     Some(Ok(model::Notification::Uninstall( _ ))) => {
                         client.install_file(npk, repository).await?;